@@ -0,0 +1,604 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque},
+};
+
+use crate::{edge::Edge, node::Node};
+
+/// How the cost of traversing an edge is computed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Weight {
+    /// Every edge costs 1, i.e. hop count
+    Unit,
+    /// Cost is read from a numeric edge attribute, edges without it cost 1.
+    /// Negative values are clamped to 0.
+    Attribute(String),
+}
+
+impl Weight {
+    pub fn cost(&self, edge: &Edge) -> f32 {
+        match self {
+            Weight::Unit => 1.0,
+            Weight::Attribute(name) => edge
+                .attributes
+                .get(name)
+                .and_then(|v| v.as_number())
+                .filter(|w| !w.is_nan())
+                .unwrap_or(1.0)
+                .max(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// Node indices from start to goal, inclusive
+    pub nodes: Vec<u32>,
+    /// Edge indices traversed, `nodes.len() - 1` of them
+    pub edges: Vec<u32>,
+    pub cost: f32,
+}
+
+/// Open set entry, ordered so the `BinaryHeap` pops the lowest estimate first
+struct Candidate {
+    estimate: f32,
+    cost: f32,
+    node: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Weighted shortest path (Dijkstra), edges are treated as undirected
+pub fn shortest_path(
+    node_count: usize,
+    edges: &[Edge],
+    edge_map: &BTreeMap<u32, Vec<u32>>,
+    from: u32,
+    to: u32,
+    weight: &Weight,
+) -> Option<Path> {
+    astar(node_count, edges, edge_map, from, to, weight, |_| 0.0)
+}
+
+/// A* search with a caller supplied heuristic estimating the remaining cost
+/// from a node to `to`. The heuristic must not overestimate for the result to
+/// be optimal.
+pub fn astar<H>(
+    node_count: usize,
+    edges: &[Edge],
+    edge_map: &BTreeMap<u32, Vec<u32>>,
+    from: u32,
+    to: u32,
+    weight: &Weight,
+    heuristic: H,
+) -> Option<Path>
+where
+    H: Fn(u32) -> f32,
+{
+    if from as usize >= node_count || to as usize >= node_count {
+        return None;
+    }
+
+    let mut cost = vec![f32::INFINITY; node_count];
+    // (previous node, edge used to get here)
+    let mut came_from: Vec<Option<(u32, u32)>> = vec![None; node_count];
+    let mut open = BinaryHeap::new();
+
+    cost[from as usize] = 0.0;
+    open.push(Candidate {
+        estimate: heuristic(from),
+        cost: 0.0,
+        node: from,
+    });
+
+    while let Some(Candidate {
+        cost: current,
+        node,
+        ..
+    }) = open.pop()
+    {
+        if node == to {
+            break;
+        }
+        // Stale entry, a cheaper route to this node was found after it was queued
+        if current > cost[node as usize] {
+            continue;
+        }
+
+        for &edge_id in edge_map.get(&node).into_iter().flatten() {
+            let edge = &edges[edge_id as usize];
+            let next = if edge.a_id == node {
+                edge.b_id
+            } else {
+                edge.a_id
+            };
+            let next_cost = cost[node as usize] + weight.cost(edge);
+            if next_cost < cost[next as usize] {
+                cost[next as usize] = next_cost;
+                came_from[next as usize] = Some((node, edge_id));
+                open.push(Candidate {
+                    estimate: next_cost + heuristic(next),
+                    cost: next_cost,
+                    node: next,
+                });
+            }
+        }
+    }
+
+    if !cost[to as usize].is_finite() {
+        return None;
+    }
+
+    let mut nodes = vec![to];
+    let mut path_edges = vec![];
+    let mut current = to;
+    while let Some((prev, edge)) = came_from[current as usize] {
+        nodes.push(prev);
+        path_edges.push(edge);
+        current = prev;
+    }
+    nodes.reverse();
+    path_edges.reverse();
+
+    Some(Path {
+        nodes,
+        edges: path_edges,
+        cost: cost[to as usize],
+    })
+}
+
+/// Straight-line distance heuristic. Distances are scaled by the smallest
+/// cost-per-unit-length of any edge, which keeps the estimate admissible
+/// whatever the weights are.
+pub fn euclidean_heuristic<'a>(
+    nodes: &'a [Node],
+    edges: &[Edge],
+    weight: &Weight,
+    to: u32,
+) -> impl Fn(u32) -> f32 + 'a {
+    let scale = edges
+        .iter()
+        .filter_map(|e| {
+            let len = (e.b_center - e.a_center).truncate();
+            let len = (len.x * len.x + len.y * len.y).sqrt();
+            (len > 0.0).then(|| weight.cost(e) / len)
+        })
+        .fold(f32::INFINITY, f32::min);
+    let scale = if scale.is_finite() { scale } else { 0.0 };
+    let goal = nodes[to as usize].position;
+
+    move |i| {
+        let d = nodes[i as usize].position - goal;
+        (d.x * d.x + d.y * d.y).sqrt() * scale
+    }
+}
+
+/// Outgoing `(target, edge index)` pairs per node, treating each edge as
+/// pointing from `a_id` to `b_id`
+fn directed_adjacency(node_count: usize, edges: &[Edge]) -> Vec<Vec<(u32, u32)>> {
+    let mut adjacency = vec![vec![]; node_count];
+    for (i, edge) in edges.iter().enumerate() {
+        adjacency[edge.a_id as usize].push((edge.b_id, i as u32));
+    }
+    adjacency
+}
+
+/// Tarjan's strongly connected components over the directed graph (edges
+/// point from `a_id` to `b_id`). Every node belongs to exactly one component.
+pub fn strongly_connected_components(node_count: usize, edges: &[Edge]) -> Vec<Vec<u32>> {
+    const UNVISITED: u32 = u32::MAX;
+
+    let adjacency = directed_adjacency(node_count, edges);
+    let mut index = vec![UNVISITED; node_count];
+    let mut low = vec![0; node_count];
+    let mut on_stack = vec![false; node_count];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+
+    for root in 0..node_count as u32 {
+        if index[root as usize] != UNVISITED {
+            continue;
+        }
+
+        // Explicit call stack of (node, next child to visit) so deep graphs
+        // can't overflow the real one
+        let mut calls = vec![(root, 0usize)];
+        index[root as usize] = next_index;
+        low[root as usize] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root as usize] = true;
+
+        while let Some((v, child)) = calls.last_mut() {
+            let v = *v;
+            if let Some(&(w, _)) = adjacency[v as usize].get(*child) {
+                *child += 1;
+                if index[w as usize] == UNVISITED {
+                    index[w as usize] = next_index;
+                    low[w as usize] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w as usize] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w as usize] {
+                    low[v as usize] = low[v as usize].min(index[w as usize]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent as usize] = low[parent as usize].min(low[v as usize]);
+            }
+            if low[v as usize] == index[v as usize] {
+                let mut component = vec![];
+                while let Some(w) = stack.pop() {
+                    on_stack[w as usize] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
+
+/// Components that contain at least one cycle: more than one node, or a
+/// single node with a self-loop
+pub fn cyclic_components(node_count: usize, edges: &[Edge]) -> Vec<Vec<u32>> {
+    let mut looped = vec![false; node_count];
+    for edge in edges.iter().filter(|e| e.a_id == e.b_id) {
+        looped[edge.a_id as usize] = true;
+    }
+    strongly_connected_components(node_count, edges)
+        .into_iter()
+        .filter(|c| c.len() > 1 || looped[c[0] as usize])
+        .collect()
+}
+
+/// Finds a directed cycle, returned as a path that starts and ends at the same
+/// node
+pub fn find_cycle(node_count: usize, edges: &[Edge]) -> Option<Path> {
+    let component = cyclic_components(node_count, edges).into_iter().next()?;
+    let adjacency = directed_adjacency(node_count, edges);
+    let mut in_component = vec![false; node_count];
+    component
+        .iter()
+        .for_each(|&n| in_component[n as usize] = true);
+
+    // Breadth first search from the start back to itself, staying inside the
+    // component which guarantees the way back exists
+    let start = component[0];
+    let mut came_from: Vec<Option<(u32, u32)>> = vec![None; node_count];
+    let mut queue = std::collections::VecDeque::from([start]);
+    'search: while let Some(v) = queue.pop_front() {
+        for &(w, edge) in &adjacency[v as usize] {
+            if !in_component[w as usize] || came_from[w as usize].is_some() {
+                continue;
+            }
+            came_from[w as usize] = Some((v, edge));
+            if w == start {
+                break 'search;
+            }
+            queue.push_back(w);
+        }
+    }
+
+    let mut nodes = vec![start];
+    let mut cycle_edges = vec![];
+    let mut current = start;
+    loop {
+        let (prev, edge) = came_from[current as usize]?;
+        nodes.push(prev);
+        cycle_edges.push(edge);
+        current = prev;
+        if current == start {
+            break;
+        }
+    }
+    nodes.reverse();
+    cycle_edges.reverse();
+
+    Some(Path {
+        cost: cycle_edges.len() as f32,
+        nodes,
+        edges: cycle_edges,
+    })
+}
+
+/// Kruskal's minimum spanning forest: one spanning tree per connected
+/// component. Returns the indices of the edges in the forest.
+pub fn minimum_spanning_forest(node_count: usize, edges: &[Edge], weight: &Weight) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..edges.len() as u32).collect();
+    order.sort_by(|&a, &b| {
+        weight
+            .cost(&edges[a as usize])
+            .total_cmp(&weight.cost(&edges[b as usize]))
+            .then(a.cmp(&b))
+    });
+
+    let mut parent: Vec<u32> = (0..node_count as u32).collect();
+    fn find(parent: &mut [u32], mut x: u32) -> u32 {
+        while parent[x as usize] != x {
+            // Path halving
+            parent[x as usize] = parent[parent[x as usize] as usize];
+            x = parent[x as usize];
+        }
+        x
+    }
+
+    order
+        .into_iter()
+        .filter(|&i| {
+            let edge = &edges[i as usize];
+            let (a, b) = (find(&mut parent, edge.a_id), find(&mut parent, edge.b_id));
+            if a == b {
+                return false;
+            }
+            parent[a as usize] = b;
+            true
+        })
+        .collect()
+}
+
+/// Every node reachable from `seeds` when edge direction is ignored, seeds
+/// included. Seeds that aren't in the graph are skipped, so an empty graph
+/// has an empty component.
+pub fn connected_component<I>(node_count: usize, edges: &[Edge], seeds: I) -> BTreeSet<u32>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut adjacency = vec![vec![]; node_count];
+    for edge in edges {
+        adjacency[edge.a_id as usize].push(edge.b_id);
+        adjacency[edge.b_id as usize].push(edge.a_id);
+    }
+    let mut component = BTreeSet::new();
+    let mut stack: Vec<u32> = seeds
+        .into_iter()
+        .filter(|seed| (*seed as usize) < node_count)
+        .collect();
+    while let Some(node) = stack.pop() {
+        if component.insert(node) {
+            stack.extend(&adjacency[node as usize]);
+        }
+    }
+    component
+}
+
+/// Nodes at most `hops` links away from `node` when edge direction is
+/// ignored, `node` included
+pub fn neighborhood(edges: &[Edge], node: u32, hops: usize) -> BTreeSet<u32> {
+    let mut reached = BTreeSet::from([node]);
+    let mut frontier = vec![node];
+    for _ in 0..hops {
+        let mut next = vec![];
+        for edge in edges {
+            for (from, to) in [(edge.a_id, edge.b_id), (edge.b_id, edge.a_id)] {
+                if frontier.contains(&from) && reached.insert(to) {
+                    next.push(to);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    reached
+}
+
+/// Links between `node` and every other node when edge direction is
+/// ignored, by index. `None` for nodes it can't reach, which is all of them
+/// when `node` isn't in the graph.
+pub fn hops(node_count: usize, edges: &[Edge], node: u32) -> Vec<Option<u32>> {
+    if node as usize >= node_count {
+        return vec![None; node_count];
+    }
+    let mut adjacency = vec![vec![]; node_count];
+    for edge in edges {
+        adjacency[edge.a_id as usize].push(edge.b_id);
+        adjacency[edge.b_id as usize].push(edge.a_id);
+    }
+    let mut hops = vec![None; node_count];
+    hops[node as usize] = Some(0);
+    let mut queue = VecDeque::from([node]);
+    while let Some(node) = queue.pop_front() {
+        let next = hops[node as usize].map(|h| h + 1);
+        for &other in &adjacency[node as usize] {
+            if hops[other as usize].is_none() {
+                hops[other as usize] = next;
+                queue.push_back(other);
+            }
+        }
+    }
+    hops
+}
+
+/// Maps node index to the indices of its incident edges, restricted to the
+/// given subset of edges (same layout as `EdgeRenderPass::edge_map`)
+pub fn incidence_map<I>(edges: &[Edge], subset: I) -> BTreeMap<u32, Vec<u32>>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut map: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for i in subset {
+        let edge = &edges[i as usize];
+        map.entry(edge.a_id).or_default().push(i);
+        map.entry(edge.b_id).or_default().push(i);
+    }
+    map
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use cgmath::{vec4, One, Quaternion};
+
+    use super::*;
+    use crate::attributes::AttrValue;
+
+    fn graph(positions: &[(f32, f32)], links: &[(u32, u32, f32)]) -> (Vec<Node>, Vec<Edge>) {
+        let nodes: Vec<Node> = positions
+            .iter()
+            .map(|&(x, y)| {
+                Node::new(
+                    (50.0, 50.0),
+                    (x, y, 0.0),
+                    Quaternion::one(),
+                    vec4(1.0, 1.0, 1.0, 1.0),
+                )
+            })
+            .collect();
+        let edges = links
+            .iter()
+            .map(|&(a, b, w)| {
+                let mut edge = Edge::from_nodes(
+                    (&nodes[a as usize], a),
+                    (&nodes[b as usize], b),
+                    vec4(0.0, 1.0, 0.0, 1.0),
+                    10.0,
+                );
+                edge.attributes
+                    .insert("latency".into(), AttrValue::Number(w));
+                edge
+            })
+            .collect();
+        (nodes, edges)
+    }
+
+    fn edge_map(edges: &[Edge]) -> BTreeMap<u32, Vec<u32>> {
+        incidence_map(edges, 0..edges.len() as u32)
+    }
+
+    #[test]
+    fn weighted_path_prefers_cheaper_detour() {
+        // 0 - 3 directly is one hop but slow, 0 - 1 - 2 - 3 is fast
+        let (nodes, edges) = graph(
+            &[(0.0, 0.0), (100.0, 0.0), (200.0, 0.0), (300.0, 0.0)],
+            &[(0, 3, 50.0), (0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0)],
+        );
+        let map = edge_map(&edges);
+
+        let hops = shortest_path(nodes.len(), &edges, &map, 0, 3, &Weight::Unit).unwrap();
+        assert_eq!(hops.nodes, vec![0, 3]);
+
+        let weight = Weight::Attribute("latency".into());
+        let dijkstra = shortest_path(nodes.len(), &edges, &map, 0, 3, &weight).unwrap();
+        assert_eq!(dijkstra.nodes, vec![0, 1, 2, 3]);
+        assert_eq!(dijkstra.edges, vec![1, 2, 3]);
+        assert_eq!(dijkstra.cost, 3.0);
+
+        let heuristic = euclidean_heuristic(&nodes, &edges, &weight, 3);
+        let astar = astar(nodes.len(), &edges, &map, 0, 3, &weight, heuristic).unwrap();
+        assert_eq!(astar, dijkstra);
+    }
+
+    #[test]
+    fn strongly_connected_components_and_cycles() {
+        // 0 -> 1 -> 2 -> 0 is a cycle, 3 hangs off it, 4 has a self-loop
+        let (nodes, edges) = graph(
+            &[(0.0, 0.0); 5],
+            &[
+                (0, 1, 1.0),
+                (1, 2, 1.0),
+                (2, 0, 1.0),
+                (2, 3, 1.0),
+                (4, 4, 1.0),
+            ],
+        );
+
+        let mut components: Vec<Vec<u32>> = strongly_connected_components(nodes.len(), &edges)
+            .into_iter()
+            .map(|mut c| {
+                c.sort();
+                c
+            })
+            .collect();
+        components.sort();
+        assert_eq!(components, vec![vec![0, 1, 2], vec![3], vec![4]]);
+        assert_eq!(cyclic_components(nodes.len(), &edges).len(), 2);
+
+        let cycle = find_cycle(nodes.len(), &edges).unwrap();
+        assert_eq!(cycle.nodes.first(), cycle.nodes.last());
+        assert_eq!(cycle.edges.len(), cycle.nodes.len() - 1);
+        assert!(find_cycle(4, &edges[3..4]).is_none());
+    }
+
+    #[test]
+    fn minimum_spanning_forest_skips_heavy_edges() {
+        let (nodes, edges) = graph(
+            &[(0.0, 0.0); 5],
+            &[(0, 1, 1.0), (1, 2, 2.0), (0, 2, 5.0), (3, 4, 1.0)],
+        );
+        let weight = Weight::Attribute("latency".into());
+        let mut forest = minimum_spanning_forest(nodes.len(), &edges, &weight);
+        forest.sort();
+        assert_eq!(forest, vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn connected_component_ignores_direction() {
+        let (nodes, edges) = graph(&[(0.0, 0.0); 6], &[(1, 0, 1.0), (1, 2, 1.0), (3, 4, 1.0)]);
+        let component = connected_component(nodes.len(), &edges, [2]);
+        assert_eq!(component, BTreeSet::from([0, 1, 2]));
+        let both = connected_component(nodes.len(), &edges, [0, 4]);
+        assert_eq!(both.len(), 5);
+        assert_eq!(
+            connected_component(nodes.len(), &edges, [5]),
+            BTreeSet::from([5])
+        );
+        assert_eq!(neighborhood(&edges, 2, 1), BTreeSet::from([1, 2]));
+        assert_eq!(neighborhood(&edges, 2, 2), component);
+        assert_eq!(neighborhood(&edges, 5, 3), BTreeSet::from([5]));
+        assert_eq!(
+            hops(nodes.len(), &edges, 2),
+            [Some(2), Some(1), Some(0), None, None, None]
+        );
+    }
+
+    #[test]
+    fn empty_graphs_give_empty_results() {
+        let map = BTreeMap::new();
+        assert!(shortest_path(0, &[], &map, 0, 0, &Weight::Unit).is_none());
+        assert!(strongly_connected_components(0, &[]).is_empty());
+        assert!(find_cycle(0, &[]).is_none());
+        assert!(minimum_spanning_forest(0, &[], &Weight::Unit).is_empty());
+        assert!(connected_component(0, &[], [0]).is_empty());
+        assert!(hops(0, &[], 0).is_empty());
+        // Nodes past the end of a graph are in no component
+        let (nodes, edges) = graph(&[(0.0, 0.0); 2], &[(0, 1, 1.0)]);
+        assert_eq!(
+            connected_component(nodes.len(), &edges, [1, 7]),
+            BTreeSet::from([0, 1])
+        );
+        assert_eq!(hops(nodes.len(), &edges, 7), [None, None]);
+    }
+}
@@ -0,0 +1,397 @@
+use cgmath::{vec2, vec4, InnerSpace};
+
+use crate::{font, hull::HullVertex, RenderSettings};
+
+pub const DEFAULT_VERTEX_BUFFER_CAP: usize = 1 << 15;
+
+/// World units per font pixel of a note
+pub const NOTE_PIXEL_SIZE: f32 = 4.0;
+/// Space between a note's border and its text, in font pixels
+const NOTE_PADDING: f32 = 3.0;
+const NOTE_BACKGROUND: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.75);
+const NOTE_TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 1.0);
+const MARK_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const STROKE_WIDTH: f32 = 6.0;
+const ARROW_HEAD_LENGTH: f32 = 30.0;
+const ARROW_HEAD_WIDTH: f32 = 26.0;
+
+/// Free floating note drawn on top of the graph. Annotations live in world
+/// space so they pan and zoom with the graph, but physics never touches them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    /// Text box whose top-left corner is at `position`
+    Note {
+        position: cgmath::Vector2<f32>,
+        text: String,
+    },
+    /// Callout arrow pointing at `to`
+    Arrow {
+        from: cgmath::Vector2<f32>,
+        to: cgmath::Vector2<f32>,
+    },
+    /// Rectangle outline spanning two opposite corners
+    Rect {
+        a: cgmath::Vector2<f32>,
+        b: cgmath::Vector2<f32>,
+    },
+}
+
+impl Annotation {
+    /// Parses the console form of an annotation:
+    /// `note <x> <y> <text>`, `arrow <x1> <y1> <x2> <y2>` or `rect <x1> <y1> <x2> <y2>`.
+    /// A literal `\n` in a note starts a new line.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (name, rest) = next_word(line);
+        match name {
+            "note" => {
+                let (x, rest) = next_word(rest);
+                let (y, text) = next_word(rest);
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err("usage: note <x> <y> <text>".into());
+                }
+                Ok(Annotation::Note {
+                    position: parse_point(x, y)?,
+                    text: text.replace("\\n", "\n"),
+                })
+            }
+            "arrow" | "rect" => {
+                let coords: Vec<&str> = rest.split_whitespace().collect();
+                let [x1, y1, x2, y2] = coords[..] else {
+                    return Err(format!("usage: {name} <x1> <y1> <x2> <y2>"));
+                };
+                let (a, b) = (parse_point(x1, y1)?, parse_point(x2, y2)?);
+                Ok(if name == "arrow" {
+                    Annotation::Arrow { from: a, to: b }
+                } else {
+                    Annotation::Rect { a, b }
+                })
+            }
+            other => Err(format!("not an annotation: {other}")),
+        }
+    }
+
+    /// The console command that recreates this annotation, see `Annotation::parse`
+    pub fn to_command(&self) -> String {
+        match self {
+            Annotation::Note { position, text } => format!(
+                "note {} {} {}",
+                position.x,
+                position.y,
+                text.replace('\n', "\\n")
+            ),
+            Annotation::Arrow { from, to } => {
+                format!("arrow {} {} {} {}", from.x, from.y, to.x, to.y)
+            }
+            Annotation::Rect { a, b } => format!("rect {} {} {} {}", a.x, a.y, b.x, b.y),
+        }
+    }
+
+    fn triangles(&self) -> Vec<HullVertex> {
+        match self {
+            Annotation::Note { position, text } => {
+                let (w, h) = font::measure(text);
+                let size = vec2(w as f32, h as f32) + vec2(NOTE_PADDING, NOTE_PADDING) * 2.0;
+                let mut vertices = quad(
+                    *position - vec2(0.0, size.y * NOTE_PIXEL_SIZE),
+                    *position + vec2(size.x * NOTE_PIXEL_SIZE, 0.0),
+                    NOTE_BACKGROUND,
+                );
+                // Font rows grow downwards while world space y points up
+                for run in font::runs(text) {
+                    let x = position.x + (NOTE_PADDING + run.col as f32) * NOTE_PIXEL_SIZE;
+                    let top = position.y - (NOTE_PADDING + run.row as f32) * NOTE_PIXEL_SIZE;
+                    vertices.extend(quad(
+                        vec2(x, top - NOTE_PIXEL_SIZE),
+                        vec2(x + run.len as f32 * NOTE_PIXEL_SIZE, top),
+                        NOTE_TEXT_COLOR,
+                    ));
+                }
+                vertices
+            }
+            Annotation::Arrow { from, to } => arrow(
+                *from,
+                *to,
+                STROKE_WIDTH,
+                vec2(ARROW_HEAD_LENGTH, ARROW_HEAD_WIDTH),
+                MARK_COLOR,
+            ),
+            Annotation::Rect { a, b } => {
+                let min = vec2(a.x.min(b.x), a.y.min(b.y));
+                let max = vec2(a.x.max(b.x), a.y.max(b.y));
+                let s = STROKE_WIDTH;
+                [
+                    quad(min, vec2(max.x, min.y + s), MARK_COLOR),
+                    quad(vec2(min.x, max.y - s), max, MARK_COLOR),
+                    quad(
+                        vec2(min.x, min.y + s),
+                        vec2(min.x + s, max.y - s),
+                        MARK_COLOR,
+                    ),
+                    quad(
+                        vec2(max.x - s, min.y + s),
+                        vec2(max.x, max.y - s),
+                        MARK_COLOR,
+                    ),
+                ]
+                .concat()
+            }
+        }
+    }
+}
+
+/// Draws annotations in world space above the graph and its edges
+pub struct AnnotationRenderPass {
+    pub annotations: Vec<Annotation>,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl AnnotationRenderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+    ) -> Self {
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            include_str!("annotation.wgsl"),
+        );
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Annotation Vertex Buffer"),
+            size: (std::mem::size_of::<HullVertex>() * DEFAULT_VERTEX_BUFFER_CAP) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            annotations: vec![],
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `annotation.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Annotation Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Annotation"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Annotation Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[HullVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                ..Default::default()
+            },
+            // Drawn after nodes and edges without testing against them
+            depth_stencil: settings.depth_stencil(wgpu::CompareFunction::Always),
+            multisample: settings.multisample(),
+            multiview: None,
+        })
+    }
+
+    pub fn write(&mut self, queue: &wgpu::Queue) {
+        let mut vertices: Vec<HullVertex> = self
+            .annotations
+            .iter()
+            .flat_map(Annotation::triangles)
+            .collect();
+        vertices.truncate(DEFAULT_VERTEX_BUFFER_CAP - DEFAULT_VERTEX_BUFFER_CAP % 3);
+        self.vertex_count = vertices.len() as u32;
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Parses an annotation file, one console command per line. Blank lines and
+/// lines starting with `#` are ignored.
+pub fn parse_annotations(src: &str) -> Result<Vec<Annotation>, String> {
+    src.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| Annotation::parse(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+fn next_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    s.split_once(char::is_whitespace).unwrap_or((s, ""))
+}
+
+fn parse_point(x: &str, y: &str) -> Result<cgmath::Vector2<f32>, String> {
+    let coord = |s: &str| {
+        s.parse::<f32>()
+            .map_err(|_| format!("expected a number, got {s:?}"))
+    };
+    Ok(vec2(coord(x)?, coord(y)?))
+}
+
+fn quad(
+    min: cgmath::Vector2<f32>,
+    max: cgmath::Vector2<f32>,
+    color: cgmath::Vector4<f32>,
+) -> Vec<HullVertex> {
+    let v = |x, y| HullVertex::new(vec2(x, y), color);
+    vec![
+        v(min.x, min.y),
+        v(max.x, min.y),
+        v(max.x, max.y),
+        v(min.x, min.y),
+        v(max.x, max.y),
+        v(min.x, max.y),
+    ]
+}
+
+/// Line from `from` with a triangular head of `head` (length, width) at `to`.
+/// The head is cut to the arrow's length, leaving no line when it's shorter.
+pub(crate) fn arrow(
+    from: cgmath::Vector2<f32>,
+    to: cgmath::Vector2<f32>,
+    width: f32,
+    head: cgmath::Vector2<f32>,
+    color: cgmath::Vector4<f32>,
+) -> Vec<HullVertex> {
+    let dir = to - from;
+    if dir.magnitude2() == 0.0 {
+        return vec![];
+    }
+    let length = dir.magnitude();
+    let dir = dir / length;
+    let normal = vec2(-dir.y, dir.x);
+    let base = to - dir * length.min(head.x);
+    let mut vertices = if length > head.x {
+        segment(from, base, width, color)
+    } else {
+        vec![]
+    };
+    vertices.extend([
+        HullVertex::new(to, color),
+        HullVertex::new(base + normal * head.y / 2.0, color),
+        HullVertex::new(base - normal * head.y / 2.0, color),
+    ]);
+    vertices
+}
+
+pub(crate) fn segment(
+    a: cgmath::Vector2<f32>,
+    b: cgmath::Vector2<f32>,
+    width: f32,
+    color: cgmath::Vector4<f32>,
+) -> Vec<HullVertex> {
+    let dir = (b - a).normalize();
+    let offset = vec2(-dir.y, dir.x) * width / 2.0;
+    let v = |p| HullVertex::new(p, color);
+    vec![
+        v(a + offset),
+        v(a - offset),
+        v(b - offset),
+        v(a + offset),
+        v(b - offset),
+        v(b + offset),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use cgmath::vec2;
+
+    use super::*;
+
+    #[test]
+    fn command_round_trip() {
+        let annotations = vec![
+            Annotation::Note {
+                position: vec2(-10.5, 20.0),
+                text: "first line\nsecond = line".into(),
+            },
+            Annotation::Arrow {
+                from: vec2(0.0, 0.0),
+                to: vec2(100.0, -50.0),
+            },
+            Annotation::Rect {
+                a: vec2(1.0, 2.0),
+                b: vec2(3.0, 4.0),
+            },
+        ];
+        let src = annotations
+            .iter()
+            .map(Annotation::to_command)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(parse_annotations(&src), Ok(annotations));
+        assert!(Annotation::parse("note 1 2").is_err());
+        assert!(Annotation::parse("arrow 1 2 x 4").is_err());
+    }
+
+    #[test]
+    fn arrows_shorter_than_their_head_stay_finite() {
+        let color = cgmath::vec4(1.0, 1.0, 1.0, 1.0);
+        let head = vec2(20.0, 10.0);
+        for length in [5.0, 20.0] {
+            let vertices = arrow(vec2(0.0, 0.0), vec2(length, 0.0), 2.0, head, color);
+            assert_eq!(vertices.len(), 3, "{length}");
+            assert!(vertices
+                .iter()
+                .all(|v| v.position.iter().all(|c| c.is_finite())));
+            // The head spans the whole arrow
+            assert_eq!(vertices[1].position[0], 0.0);
+        }
+        assert_eq!(
+            arrow(vec2(0.0, 0.0), vec2(30.0, 0.0), 2.0, head, color).len(),
+            9
+        );
+    }
+}
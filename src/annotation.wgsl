@@ -0,0 +1,36 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    dimensions: vec2<f32>,
+    scale: f32,
+};
+
+@binding(0) @group(0) var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+#include "constants.wgsl"
+#include "color.wgsl"
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let pos = camera.view_proj * vec4<f32>(in.pos, 0.0, 1.0);
+
+    var out: VertexOutput;
+    // Depth testing is disabled for annotations, they always draw on top
+    out.position = vec4<f32>(pos.xy, 0.0, 1.0);
+    out.color = srgb_to_linear(in.color);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
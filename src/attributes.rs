@@ -0,0 +1,61 @@
+use std::{collections::BTreeMap, fmt};
+
+/// Arbitrary per-node/per-edge data, keyed by attribute name
+pub type Attributes = BTreeMap<String, AttrValue>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    Number(f32),
+    Text(String),
+}
+
+impl AttrValue {
+    /// A number if `s` reads as one, text otherwise
+    pub fn parse(s: &str) -> Self {
+        match s.trim().parse() {
+            Ok(n) => AttrValue::Number(n),
+            Err(_) => AttrValue::Text(s.to_string()),
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f32> {
+        match self {
+            AttrValue::Number(n) => Some(*n),
+            AttrValue::Text(s) => s.trim().parse().ok(),
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            AttrValue::Text(s) => Some(s),
+            AttrValue::Number(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::Number(n) => write!(f, "{n}"),
+            AttrValue::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+impl From<f32> for AttrValue {
+    fn from(n: f32) -> Self {
+        AttrValue::Number(n)
+    }
+}
+
+impl From<&str> for AttrValue {
+    fn from(s: &str) -> Self {
+        AttrValue::Text(s.to_string())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(s: String) -> Self {
+        AttrValue::Text(s)
+    }
+}
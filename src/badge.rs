@@ -0,0 +1,108 @@
+use cgmath::{vec2, vec4};
+
+use crate::{attributes::AttrValue, node::Node, overlay::Anchor};
+
+/// Most characters a text badge shows, longer values are cut short
+pub const MAX_BADGE_CHARS: usize = 3;
+/// Counts above this show as `99+`
+pub const MAX_BADGE_COUNT: f32 = 99.0;
+pub const BADGE_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.95, 0.95, 1.0);
+pub const BADGE_BACKGROUND: cgmath::Vector4<f32> = vec4(0.1, 0.1, 0.12, 0.85);
+
+/// Attribute values drawn as an icon rather than spelled out, and the color
+/// of that icon
+const ICONS: &[(&str, char, cgmath::Vector4<f32>)] = &[
+    ("ok", '✓', vec4(0.3, 0.8, 0.4, 1.0)),
+    ("error", '✗', vec4(0.95, 0.3, 0.25, 1.0)),
+    ("warning", '⚠', vec4(0.95, 0.69, 0.2, 1.0)),
+    ("star", '★', vec4(0.95, 0.85, 0.3, 1.0)),
+    ("dot", '●', BADGE_COLOR),
+];
+
+/// Shows the value of an attribute on one corner of every node that has it:
+/// numbers as counts, `ok`, `error`, `warning`, `star` and `dot` (or those
+/// characters themselves) as icons, and other text by its first few
+/// characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    pub attr: String,
+    pub corner: Anchor,
+}
+
+impl Badge {
+    pub fn new<S: Into<String>>(attr: S, corner: Anchor) -> Self {
+        Self {
+            attr: attr.into(),
+            corner,
+        }
+    }
+
+    /// Text and color of the badge on `node`, `None` when the node doesn't
+    /// have the attribute, or has it empty or zero
+    pub fn content(&self, node: &Node) -> Option<(String, cgmath::Vector4<f32>)> {
+        match node.attributes.get(&self.attr)? {
+            AttrValue::Number(n) if n.round() == 0.0 => None,
+            AttrValue::Number(n) if *n > MAX_BADGE_COUNT => {
+                Some((format!("{MAX_BADGE_COUNT}+"), BADGE_COLOR))
+            }
+            AttrValue::Number(n) => Some((format!("{}", n.round()), BADGE_COLOR)),
+            AttrValue::Text(text) => {
+                let text = text.trim();
+                if let Some((_, icon, color)) = ICONS.iter().find(|(name, icon, _)| {
+                    text.eq_ignore_ascii_case(name) || text.chars().eq([*icon])
+                }) {
+                    return Some((icon.to_string(), *color));
+                }
+                let short: String = text.chars().take(MAX_BADGE_CHARS).collect();
+                (!short.is_empty()).then_some((short, BADGE_COLOR))
+            }
+        }
+    }
+
+    /// Where the badge is centered on `node`: the point of its disk's rim
+    /// towards the badge's corner
+    pub fn center(&self, node: &Node) -> cgmath::Vector2<f32> {
+        let (x, y) = match self.corner {
+            Anchor::TopLeft => (-1.0, 1.0),
+            Anchor::TopRight => (1.0, 1.0),
+            Anchor::BottomLeft => (-1.0, -1.0),
+            Anchor::BottomRight => (1.0, -1.0),
+        };
+        let reach = node.radius() * std::f32::consts::FRAC_1_SQRT_2;
+        node.position.truncate() + vec2(x, y) * reach
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn badges_show_counts_icons_and_short_text() {
+        let mut node = Node::new(
+            (10.0, 10.0),
+            (100.0, 0.0, 0.0),
+            cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            (1.0, 1.0, 1.0, 1.0),
+        );
+        let badge = Badge::new("status", Anchor::TopRight);
+        let text = |node: &Node| badge.content(node).map(|(text, _)| text);
+        assert_eq!(text(&node), None);
+        for (value, shown) in [
+            (AttrValue::Number(3.0), Some("3")),
+            (AttrValue::Number(0.2), None),
+            (AttrValue::Number(250.0), Some("99+")),
+            (AttrValue::from("Warning"), Some("⚠")),
+            (AttrValue::from("✓"), Some("✓")),
+            (AttrValue::from("degraded"), Some("deg")),
+            (AttrValue::from(" "), None),
+        ] {
+            node.attributes.insert("status".into(), value);
+            assert_eq!(text(&node).as_deref(), shown);
+        }
+
+        let center = badge.center(&node);
+        assert!((center - vec2(107.071, 7.071)).x.abs() < 1e-3, "{center:?}");
+        assert!((center - vec2(107.071, 7.071)).y.abs() < 1e-3, "{center:?}");
+    }
+}
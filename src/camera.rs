@@ -2,27 +2,39 @@ use bytemuck::{Pod, Zeroable};
 use cgmath::{vec4, Matrix4, SquareMatrix};
 use wgpu::util::DeviceExt;
 
-use crate::OPENGL_TO_WGPU_MATRIX;
+use crate::{export::graph_bounds, node::Node, OPENGL_TO_WGPU_MATRIX};
+
+/// Where the view starts, and where `Camera::reset` takes it back to
+pub const HOME_TRANSLATE: cgmath::Vector3<f32> = cgmath::vec3(0.0, 0.0, 1.0);
+pub const HOME_SCALE: f32 = 1.0;
+/// Default `State::lod_zoom`, past which nodes are a few pixels across
+pub const DEFAULT_LOD_ZOOM: f32 = 0.25;
+/// Room `Camera::fit` leaves around the graph on each side, as a fraction
+/// of its extent
+pub const FIT_MARGIN: f32 = 0.05;
+const MIN_SCALE: f32 = 0.01;
+const MAX_SCALE: f32 = 256.0;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
-pub struct CameraRaw {
+pub(crate) struct CameraRaw {
     matrix: [[f32; 4]; 4],
     dimensions: [f32; 2],
     scale: f32,
-    // ugh
-    _pad: f32,
+    /// Seconds the view has been animating for, see `Camera::set_time`
+    time: f32,
 }
 
 pub struct Camera {
     pub matrix: Matrix4<f32>,
 
     buffer: wgpu::Buffer,
-    pub bind_group: wgpu::BindGroup,
+    pub(crate) bind_group: wgpu::BindGroup,
     pub translate: cgmath::Vector3<f32>,
     pub scale: f32,
     pub height: f32,
     pub width: f32,
+    pub time: f32,
 }
 
 impl Camera {
@@ -45,46 +57,89 @@ impl Camera {
             * scale
     }
 
-    pub fn update_scale(&mut self, queue: &wgpu::Queue, scale: f32) {
-        self.scale = scale.clamp(0.01, 256.0);
+    /// Zooms to `scale`, keeping whatever is under `anchor` (window pixels
+    /// relative to the center, y up, such as the cursor) in place on screen.
+    /// Without an anchor the world origin stays put.
+    pub fn update_scale(
+        &mut self,
+        queue: &wgpu::Queue,
+        scale: f32,
+        anchor: Option<cgmath::Vector2<f32>>,
+    ) {
+        let old = self.scale;
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        if let Some(anchor) = anchor {
+            self.translate = anchored_translate(self.translate, old, self.scale, anchor);
+        }
         self.matrix = Self::make_matrix(
             self.width,
             self.height,
-            &cgmath::Matrix4::from_translation(self.translate.clone()),
+            &cgmath::Matrix4::from_translation(self.translate),
             &cgmath::Matrix4::from_scale(self.scale),
         );
         queue.write_buffer(
             &self.buffer,
             0,
             bytemuck::cast_slice(&[Self::to_raw(
-                self.matrix.clone(),
+                self.matrix,
                 self.width,
                 self.height,
                 self.scale,
+                self.time,
             )]),
         );
     }
 
+    /// World position under a point in window pixels relative to the center, y up
+    pub fn screen_to_world(&self, pos: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        unproject(&self.matrix, self.width, self.height, pos)
+    }
+
+    /// Window pixels relative to the center, y up, of a world position
+    pub fn world_to_screen(&self, pos: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        project(&self.matrix, self.width, self.height, pos)
+    }
+
     pub fn update_translate(&mut self, queue: &wgpu::Queue, translate: cgmath::Vector3<f32>) {
         self.translate = translate;
         self.matrix = Self::make_matrix(
             self.width,
             self.height,
-            &cgmath::Matrix4::from_translation(self.translate.clone()),
+            &cgmath::Matrix4::from_translation(self.translate),
             &cgmath::Matrix4::from_scale(self.scale),
         );
         queue.write_buffer(
             &self.buffer,
             0,
             bytemuck::cast_slice(&[Self::to_raw(
-                self.matrix.clone(),
+                self.matrix,
                 self.width,
                 self.height,
                 self.scale,
+                self.time,
             )]),
         );
     }
 
+    /// Zooms and pans so every node is on screen with `FIT_MARGIN` to
+    /// spare. Returns false, leaving the view alone, when there are none, or
+    /// none with a finite position.
+    pub fn fit(&mut self, queue: &wgpu::Queue, nodes: &[Node]) -> bool {
+        let Some((min, max)) = graph_bounds(nodes) else {
+            return false;
+        };
+        let (translate, scale) = fit_view(self.width, self.height, min, max, FIT_MARGIN);
+        self.update_scale(queue, scale, None);
+        self.update_translate(queue, translate.extend(self.translate.z));
+        true
+    }
+
+    /// Back to the view the window opened with
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        self.update_scale(queue, HOME_SCALE, None);
+        self.update_translate(queue, HOME_TRANSLATE);
+    }
+
     pub fn new(
         translate: cgmath::Vector3<f32>,
         width: f32,
@@ -92,20 +147,6 @@ impl Camera {
         scale: f32,
         device: &wgpu::Device,
     ) -> (Self, wgpu::BindGroupLayout) {
-        let view_proj = Self::make_matrix(
-            width,
-            height,
-            &cgmath::Matrix4::from_translation(translate.clone()),
-            &cgmath::Matrix4::from_scale(scale),
-        );
-
-        let camera_raw = Self::to_raw(view_proj.clone(), width, height, scale);
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_raw]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
@@ -121,8 +162,45 @@ impl Camera {
                 label: Some("camera_bind_group_layout"),
             });
 
+        (
+            Self::with_layout(
+                translate,
+                width,
+                height,
+                scale,
+                device,
+                &camera_bind_group_layout,
+            ),
+            camera_bind_group_layout,
+        )
+    }
+
+    /// Creates another camera usable with pipelines built for `layout`, e.g.
+    /// to render offscreen without touching the window's camera
+    pub fn with_layout(
+        translate: cgmath::Vector3<f32>,
+        width: f32,
+        height: f32,
+        scale: f32,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let view_proj = Self::make_matrix(
+            width,
+            height,
+            &cgmath::Matrix4::from_translation(translate),
+            &cgmath::Matrix4::from_scale(scale),
+        );
+
+        let camera_raw = Self::to_raw(view_proj, width, height, scale, 0.0);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_raw]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
+            layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: camera_buffer.as_entire_binding(),
@@ -130,18 +208,16 @@ impl Camera {
             label: Some("camera_bind_group"),
         });
 
-        (
-            Self {
-                translate,
-                scale,
-                height,
-                width,
-                matrix: view_proj,
-                buffer: camera_buffer,
-                bind_group: camera_bind_group,
-            },
-            camera_bind_group_layout,
-        )
+        Self {
+            translate,
+            scale,
+            height,
+            width,
+            matrix: view_proj,
+            time: 0.0,
+            buffer: camera_buffer,
+            bind_group: camera_bind_group,
+        }
     }
 
     pub fn resize(&mut self, width: f32, height: f32, queue: &wgpu::Queue) {
@@ -150,23 +226,251 @@ impl Camera {
         self.matrix = Self::make_matrix(
             width,
             height,
-            &cgmath::Matrix4::from_translation(self.translate.clone()),
+            &cgmath::Matrix4::from_translation(self.translate),
             &cgmath::Matrix4::from_scale(self.scale),
         );
         queue.write_buffer(
             &self.buffer,
             0,
-            bytemuck::cast_slice(&[Self::to_raw(self.matrix.clone(), width, height, self.scale)]),
+            bytemuck::cast_slice(&[Self::to_raw(
+                self.matrix,
+                width,
+                height,
+                self.scale,
+                self.time,
+            )]),
+        );
+    }
+
+    /// Moves the clock shaders animate by, such as pulsing nodes, to `time`
+    /// seconds
+    pub fn set_time(&mut self, queue: &wgpu::Queue, time: f32) {
+        self.time = time;
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[Self::to_raw(
+                self.matrix,
+                self.width,
+                self.height,
+                self.scale,
+                self.time,
+            )]),
         );
     }
 
-    fn to_raw(matrix: Matrix4<f32>, width: f32, height: f32, scale: f32) -> CameraRaw {
+    fn to_raw(matrix: Matrix4<f32>, width: f32, height: f32, scale: f32, time: f32) -> CameraRaw {
         CameraRaw {
             matrix: matrix.into(),
             // dimensions: [WIDTH * 2.0 * scale, HEIGHT * 2.0 * scale],
             dimensions: [width * 2.0, height * 2.0],
             scale,
-            _pad: 0.0,
+            time,
+        }
+    }
+}
+
+/// Translation that keeps the world point under `anchor` there when the scale
+/// goes from `old` to `new`. Screen positions are `world * scale - translate`.
+pub fn anchored_translate(
+    translate: cgmath::Vector3<f32>,
+    old: f32,
+    new: f32,
+    anchor: cgmath::Vector2<f32>,
+) -> cgmath::Vector3<f32> {
+    let world = (anchor + translate.truncate()) / old;
+    (world * new - anchor).extend(translate.z)
+}
+
+/// Translation and scale centering the world space box `min`..`max` in a
+/// `width` by `height` view, zoomed as far in as fits it with `margin` (a
+/// fraction of its extent) on each side. A box with no extent, a single
+/// node, keeps the home scale.
+pub fn fit_view(
+    width: f32,
+    height: f32,
+    min: cgmath::Vector2<f32>,
+    max: cgmath::Vector2<f32>,
+    margin: f32,
+) -> (cgmath::Vector2<f32>, f32) {
+    let extent = (max - min) * (1.0 + margin * 2.0);
+    let scale = if extent.x <= 0.0 && extent.y <= 0.0 {
+        HOME_SCALE
+    } else {
+        (width / extent.x.max(f32::EPSILON)).min(height / extent.y.max(f32::EPSILON))
+    };
+    let scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+    ((min + max) / 2.0 * scale, scale)
+}
+
+/// Where `matrix` puts the world position `pos` on a `width` by `height`
+/// window, in pixels relative to the center with y up
+pub fn project(
+    matrix: &Matrix4<f32>,
+    width: f32,
+    height: f32,
+    pos: cgmath::Vector2<f32>,
+) -> cgmath::Vector2<f32> {
+    let clip = matrix * vec4(pos.x, pos.y, 0.0, 1.0);
+    cgmath::vec2(clip.x * width / 2.0, clip.y * height / 2.0) / clip.w
+}
+
+/// Inverse of `project`, the world position on the z = 0 plane under `pos`
+pub fn unproject(
+    matrix: &Matrix4<f32>,
+    width: f32,
+    height: f32,
+    pos: cgmath::Vector2<f32>,
+) -> cgmath::Vector2<f32> {
+    let Some(inverse) = matrix.invert() else {
+        return pos;
+    };
+    // Depth of the graph plane, x and y don't depend on it with an
+    // orthographic projection but it keeps the result on z = 0
+    let depth = (matrix * vec4(0.0, 0.0, 0.0, 1.0)).z;
+    let world = inverse * vec4(pos.x * 2.0 / width, pos.y * 2.0 / height, depth, 1.0);
+    world.truncate().truncate() / world.w
+}
+
+#[cfg(test)]
+mod test {
+    use cgmath::{vec2, vec3, InnerSpace};
+
+    use super::*;
+    use crate::node::{pick, Node, PICK_TOLERANCE};
+
+    fn matrix(
+        translate: cgmath::Vector3<f32>,
+        scale: f32,
+        width: f32,
+        height: f32,
+    ) -> Matrix4<f32> {
+        Camera::make_matrix(
+            width,
+            height,
+            &Matrix4::from_translation(translate),
+            &Matrix4::from_scale(scale),
+        )
+    }
+
+    #[test]
+    fn picking_across_camera_configurations() {
+        let configurations = [
+            (vec3(0.0, 0.0, 1.0), 1.0, 800.0, 600.0),
+            (vec3(250.0, -120.0, 1.0), 1.0, 800.0, 600.0),
+            (vec3(0.0, 0.0, 1.0), 2.5, 1920.0, 1080.0),
+            (vec3(-400.0, 75.0, 1.0), 0.2, 333.0, 1000.0),
+        ];
+        let node = |x, y| {
+            Node::new(
+                (50.0, 50.0),
+                (x, y, 0.0),
+                cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                (1.0, 1.0, 1.0, 1.0),
+            )
+        };
+        let nodes = [node(0.0, 0.0), node(300.0, 200.0), node(330.0, 200.0)];
+
+        for (translate, scale, width, height) in configurations {
+            let m = matrix(translate, scale, width, height);
+            for p in [vec2(0.0, 0.0), vec2(-317.0, 42.5), vec2(1000.0, -2000.0)] {
+                let screen = project(&m, width, height, p);
+                // What the shaders compute, scaled and shifted by the camera
+                assert!((screen - (p * scale - translate.truncate())).magnitude() < 1e-2);
+                assert!((unproject(&m, width, height, screen) - p).magnitude() < 1e-2);
+            }
+
+            let at = |p| {
+                let world = unproject(&m, width, height, project(&m, width, height, p));
+                pick(&nodes, world, 0.0, &Default::default())
+            };
+            assert_eq!(at(vec2(0.0, 0.0)), Some(0));
+            // Inside the quad's corner but outside the disk
+            assert_eq!(at(vec2(45.0, 45.0)), None);
+            // Overlapping disks go to the one drawn last
+            assert_eq!(at(vec2(315.0, 200.0)), Some(2));
+            assert_eq!(at(vec2(260.0, 200.0)), Some(1));
+            assert_eq!(pick(&nodes, vec2(315.0, 200.0), 0.0, &[2].into()), Some(1));
+
+            // Tolerance is in screen pixels, so it covers less of the world
+            // the further the camera zooms in
+            let near_miss = vec2(0.0, 50.0 + 3.0 / scale);
+            let tolerance = PICK_TOLERANCE / scale;
+            assert_eq!(pick(&nodes, near_miss, 0.0, &Default::default()), None);
+            assert_eq!(
+                pick(&nodes, near_miss, tolerance, &Default::default()),
+                Some(0)
+            );
+
+            // Zooming around the cursor leaves the world point under it there
+            let cursor = vec2(-120.0, 90.0);
+            let before = unproject(&m, width, height, cursor);
+            for zoom in [scale * 1.7, scale / 3.0] {
+                let moved = anchored_translate(translate, scale, zoom, cursor);
+                assert_eq!(moved.z, translate.z);
+                let after = matrix(moved, zoom, width, height);
+                let world = unproject(&after, width, height, cursor);
+                assert!((world - before).magnitude() < 1e-2 * before.magnitude().max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn fitting_keeps_the_whole_box_on_screen() {
+        let (width, height) = (800.0, 600.0);
+        for (min, max) in [
+            (vec2(-100.0, -50.0), vec2(100.0, 50.0)),
+            (vec2(1000.0, 2000.0), vec2(1040.0, 2400.0)),
+            (vec2(-9000.0, -10.0), vec2(9000.0, 10.0)),
+        ] {
+            let (translate, scale) = fit_view(width, height, min, max, FIT_MARGIN);
+            let m = matrix(translate.extend(1.0), scale, width, height);
+            let corners = [min, max, vec2(min.x, max.y), vec2(max.x, min.y)];
+            for corner in corners {
+                let screen = project(&m, width, height, corner);
+                assert!(screen.x.abs() <= width / 2.0 + 1e-2, "{screen:?}");
+                assert!(screen.y.abs() <= height / 2.0 + 1e-2, "{screen:?}");
+            }
+            // Centered, and snug along one axis but for the margin
+            let center = project(&m, width, height, (min + max) / 2.0);
+            assert!(center.magnitude() < 1e-2, "{center:?}");
+            let span = project(&m, width, height, max) - project(&m, width, height, min);
+            let fill = (span.x / width).max(span.y / height);
+            assert!(
+                (fill - 1.0 / (1.0 + FIT_MARGIN * 2.0)).abs() < 1e-3,
+                "{fill}"
+            );
         }
+
+        // A lone point is centered at the home zoom
+        let point = vec2(30.0, -40.0);
+        assert_eq!(
+            fit_view(width, height, point, point, FIT_MARGIN),
+            (point, HOME_SCALE)
+        );
+    }
+
+    #[test]
+    fn empty_graphs_have_nothing_to_fit() {
+        let node = |x, y| {
+            Node::new(
+                (10.0, 10.0),
+                (x, y, 0.0),
+                cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                (1.0, 1.0, 1.0, 1.0),
+            )
+        };
+        assert_eq!(graph_bounds(&[]), None);
+        assert_eq!(graph_bounds(&[node(f32::NAN, 0.0)]), None);
+        // Nodes gone off to infinity don't drag the box with them
+        let nodes = [
+            node(0.0, 0.0),
+            node(f32::NAN, 1.0),
+            node(f32::INFINITY, 0.0),
+        ];
+        assert_eq!(
+            graph_bounds(&nodes),
+            Some((vec2(-10.0, -10.0), vec2(10.0, 10.0)))
+        );
     }
 }
@@ -0,0 +1,110 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::graph::{EdgeRejection, NodeId};
+
+/// Something about the visualization that an embedder mirroring it may need
+/// to catch up on. Positions aren't reported tick by tick, only once they
+/// come to rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Usually the last node. Undoing a removal puts nodes back under the
+    /// ids they had, lowest first, each moving the nodes from it on up by one.
+    NodeAdded(NodeId),
+    /// Index into the edge list, like `NodeAdded`
+    EdgeAdded(u32),
+    /// Replaced by a batch, see `GraphMutation::update_nodes`
+    NodeUpdated(NodeId),
+    /// Replaced by a batch, like `NodeUpdated`
+    EdgeUpdated(u32),
+    /// Removed by a batch, under the id it had before it. Nodes after it
+    /// move down by one, removals in the same batch come highest id first.
+    NodeRemoved(NodeId),
+    /// Removed by a batch, like `NodeRemoved`
+    EdgeRemoved(u32),
+    /// Every node and edge was removed
+    GraphCleared,
+    /// The graph being loaded was past the hard limits and only a sample of
+    /// it was added, see `Limits::sample`. Carries the counts it had.
+    GraphSampled {
+        nodes: usize,
+        edges: usize,
+    },
+    /// Connecting two nodes was turned down, nothing was added
+    EdgeRejected {
+        from: NodeId,
+        to: NodeId,
+        reason: EdgeRejection,
+    },
+    /// A dragged node was let go
+    NodeMoved(NodeId),
+    /// The simulation came to rest, any node may have moved since the last
+    /// time it did
+    LayoutSettled,
+    /// The view was panned, zoomed or resized
+    CameraChanged,
+    SelectionChanged,
+}
+
+/// Collects changes over a frame and hands them to every subscriber at the
+/// end of it. Repeats in a row, such as a camera panned over several events,
+/// are sent once.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pending: Vec<Change>,
+    subscribers: Vec<Sender<Change>>,
+}
+
+impl Changes {
+    /// A new receiver for every change from now on. Poll it with `try_iter`
+    /// or block on it from another thread, dropping it unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<Change> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    pub fn emit(&mut self, change: Change) {
+        if self.subscribers.is_empty() || self.pending.last() == Some(&change) {
+            return;
+        }
+        self.pending.push(change);
+    }
+
+    /// Sends the pending changes, forgetting subscribers that hung up
+    pub fn flush(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.subscribers
+            .retain(|tx| pending.iter().all(|change| tx.send(*change).is_ok()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes_reach_live_subscribers() {
+        let mut changes = Changes::default();
+        // Nobody is listening, nothing piles up
+        changes.emit(Change::NodeAdded(0));
+        assert!(changes.pending.is_empty());
+
+        let first = changes.subscribe();
+        let second = changes.subscribe();
+        changes.emit(Change::CameraChanged);
+        changes.emit(Change::CameraChanged);
+        changes.emit(Change::NodeAdded(1));
+        assert_eq!(first.try_iter().count(), 0, "sent before the flush");
+        changes.flush();
+        assert_eq!(
+            first.try_iter().collect::<Vec<_>>(),
+            [Change::CameraChanged, Change::NodeAdded(1)]
+        );
+
+        drop(second);
+        changes.emit(Change::GraphCleared);
+        changes.flush();
+        assert_eq!(changes.subscribers.len(), 1);
+        assert_eq!(first.try_recv(), Ok(Change::GraphCleared));
+    }
+}
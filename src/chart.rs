@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use cgmath::{vec2, vec4};
+
+use crate::{
+    node::Node,
+    overlay::{Anchor, Rect},
+};
+
+pub const DEFAULT_HISTOGRAM_BINS: usize = 16;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.6);
+const BAR_COLOR: cgmath::Vector4<f32> = vec4(0.37, 0.71, 0.61, 0.9);
+const SELECTED_BAR_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+
+/// A small chart anchored to a corner of the window. Holds the layout shared by
+/// every overlay chart, the charts themselves only decide what to draw in it.
+#[derive(Debug, Clone, Copy)]
+pub struct ChartFrame {
+    pub anchor: Anchor,
+    pub size: cgmath::Vector2<f32>,
+    pub margin: f32,
+    pub padding: f32,
+}
+
+impl Default for ChartFrame {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::BottomLeft,
+            size: vec2(320.0, 140.0),
+            margin: 16.0,
+            padding: 8.0,
+        }
+    }
+}
+
+impl ChartFrame {
+    pub fn background(&self, width: f32, height: f32) -> Rect {
+        Rect::new(
+            self.anchor.place(width, height, self.size, self.margin),
+            self.size,
+            BACKGROUND_COLOR,
+        )
+    }
+
+    /// The area inside the padding where data is plotted
+    pub fn plot_area(&self, width: f32, height: f32) -> Rect {
+        let bg = self.background(width, height);
+        Rect::new(
+            bg.origin + vec2(self.padding, self.padding),
+            bg.size - vec2(self.padding, self.padding) * 2.0,
+            BACKGROUND_COLOR,
+        )
+    }
+
+    pub fn contains(&self, width: f32, height: f32, pos: &cgmath::Vector2<f32>) -> bool {
+        self.background(width, height).contains(pos)
+    }
+
+    /// Horizontal position of `pos` within the plot area in [0, 1]
+    pub fn fraction_at(&self, width: f32, height: f32, pos: &cgmath::Vector2<f32>) -> Option<f32> {
+        let plot = self.plot_area(width, height);
+        if !plot.contains(pos) {
+            return None;
+        }
+        Some(((pos.x - plot.origin.x) / plot.size.x).clamp(0.0, 1.0))
+    }
+
+    /// Index of the column under `pos` when the plot area is split into `columns`
+    /// equally wide columns
+    pub fn column_at(
+        &self,
+        width: f32,
+        height: f32,
+        columns: usize,
+        pos: &cgmath::Vector2<f32>,
+    ) -> Option<usize> {
+        if columns == 0 {
+            return None;
+        }
+        self.fraction_at(width, height, pos)
+            .map(|t| ((t * columns as f32) as usize).min(columns - 1))
+    }
+
+    /// Vertical bars, one per value, scaled so that `max` fills the plot height
+    pub fn bars<F>(&self, width: f32, height: f32, values: &[f32], max: f32, color: F) -> Vec<Rect>
+    where
+        F: Fn(usize) -> cgmath::Vector4<f32>,
+    {
+        let column = self.plot_area(width, height).size.x / values.len().max(1) as f32;
+        let gap = if column > 4.0 { 2.0 } else { 0.0 };
+        self.columns(width, height, values, max, gap, color)
+    }
+
+    /// Filled area under `values`, drawn as gapless columns
+    pub fn area(
+        &self,
+        width: f32,
+        height: f32,
+        values: &[f32],
+        max: f32,
+        color: cgmath::Vector4<f32>,
+    ) -> Vec<Rect> {
+        self.columns(width, height, values, max, 0.0, |_| color)
+    }
+
+    /// A full height vertical marker at `t` in [0, 1] across the plot area
+    pub fn marker(
+        &self,
+        width: f32,
+        height: f32,
+        t: f32,
+        thickness: f32,
+        color: cgmath::Vector4<f32>,
+    ) -> Rect {
+        let plot = self.plot_area(width, height);
+        Rect::new(
+            vec2(
+                plot.origin.x + plot.size.x * t.clamp(0.0, 1.0) - thickness / 2.0,
+                plot.origin.y,
+            ),
+            vec2(thickness, plot.size.y),
+            color,
+        )
+    }
+
+    fn columns<F>(
+        &self,
+        width: f32,
+        height: f32,
+        values: &[f32],
+        max: f32,
+        gap: f32,
+        color: F,
+    ) -> Vec<Rect>
+    where
+        F: Fn(usize) -> cgmath::Vector4<f32>,
+    {
+        let plot = self.plot_area(width, height);
+        if values.is_empty() || max <= 0.0 {
+            return vec![];
+        }
+        let column = plot.size.x / values.len() as f32;
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let h = plot.size.y * (value / max).clamp(0.0, 1.0);
+                Rect::new(
+                    vec2(
+                        plot.origin.x + column * i as f32,
+                        plot.origin.y + plot.size.y - h,
+                    ),
+                    vec2(column - gap, h),
+                    color(i),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bin {
+    /// Inclusive lower bound
+    pub start: f32,
+    /// Exclusive upper bound, except for the last bin which includes it
+    pub end: f32,
+    /// Node indices whose value falls in this bin
+    pub members: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    pub bins: Vec<Bin>,
+}
+
+impl Histogram {
+    /// Buckets `(node index, value)` pairs. Integer data with a small range
+    /// (e.g. degrees) gets one bin per integer, everything else gets
+    /// `max_bins` equally wide bins.
+    pub fn new(values: &[(u32, f32)], max_bins: usize) -> Self {
+        let finite = values.iter().filter(|(_, v)| v.is_finite());
+        let (min, max) = finite
+            .clone()
+            .fold((f32::MAX, f32::MIN), |(min, max), (_, v)| {
+                (min.min(*v), max.max(*v))
+            });
+        if min > max || max_bins == 0 {
+            return Self::default();
+        }
+
+        let integral = finite.clone().all(|(_, v)| v.fract() == 0.0);
+        let (start, width, count) = if integral && (max - min + 1.0) as usize <= max_bins {
+            (min, 1.0, (max - min) as usize + 1)
+        } else if max == min {
+            (min, 1.0, 1)
+        } else {
+            (min, (max - min) / max_bins as f32, max_bins)
+        };
+
+        let mut bins: Vec<Bin> = (0..count)
+            .map(|i| Bin {
+                start: start + width * i as f32,
+                end: start + width * (i + 1) as f32,
+                members: vec![],
+            })
+            .collect();
+        for (idx, value) in finite {
+            let bin = (((value - start) / width) as usize).min(count - 1);
+            bins[bin].members.push(*idx);
+        }
+
+        Self { bins }
+    }
+
+    pub fn max_count(&self) -> usize {
+        self.bins.iter().map(|b| b.members.len()).max().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistogramSource {
+    Degree,
+    /// A numeric node attribute, nodes without it are left out
+    Attribute(String),
+}
+
+impl HistogramSource {
+    pub fn values(&self, nodes: &[Node], edge_map: &BTreeMap<u32, Vec<u32>>) -> Vec<(u32, f32)> {
+        match self {
+            HistogramSource::Degree => (0..nodes.len() as u32)
+                .map(|i| (i, edge_map.get(&i).map(Vec::len).unwrap_or(0) as f32))
+                .collect(),
+            HistogramSource::Attribute(name) => nodes
+                .iter()
+                .enumerate()
+                .filter_map(|(i, node)| {
+                    node.attributes
+                        .get(name)
+                        .and_then(|v| v.as_number())
+                        .map(|v| (i as u32, v))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Histogram overlay with brushing: clicking a bar selects the nodes in that
+/// bin so the main view can highlight them.
+pub struct HistogramChart {
+    pub source: HistogramSource,
+    pub frame: ChartFrame,
+    pub histogram: Histogram,
+    pub max_bins: usize,
+    pub selected: Option<usize>,
+}
+
+impl HistogramChart {
+    pub fn new(source: HistogramSource) -> Self {
+        Self {
+            source,
+            frame: ChartFrame::default(),
+            histogram: Histogram::default(),
+            max_bins: DEFAULT_HISTOGRAM_BINS,
+            selected: None,
+        }
+    }
+
+    pub fn update(&mut self, nodes: &[Node], edge_map: &BTreeMap<u32, Vec<u32>>) {
+        self.histogram = Histogram::new(&self.source.values(nodes, edge_map), self.max_bins);
+        if self
+            .selected
+            .is_some_and(|i| i >= self.histogram.bins.len())
+        {
+            self.selected = None;
+        }
+    }
+
+    pub fn rects(&self, width: f32, height: f32) -> Vec<Rect> {
+        let counts: Vec<f32> = self
+            .histogram
+            .bins
+            .iter()
+            .map(|b| b.members.len() as f32)
+            .collect();
+        let max = self.histogram.max_count() as f32;
+
+        std::iter::once(self.frame.background(width, height))
+            .chain(self.frame.bars(width, height, &counts, max, |i| {
+                if self.selected == Some(i) {
+                    SELECTED_BAR_COLOR
+                } else {
+                    BAR_COLOR
+                }
+            }))
+            .collect()
+    }
+
+    /// Handles a click in overlay space, returns true if the chart consumed it
+    pub fn click(&mut self, width: f32, height: f32, pos: &cgmath::Vector2<f32>) -> bool {
+        if !self.frame.contains(width, height, pos) {
+            return false;
+        }
+        let bar = self
+            .frame
+            .column_at(width, height, self.histogram.bins.len(), pos);
+        self.selected = if bar == self.selected { None } else { bar };
+        true
+    }
+
+    /// Nodes in the brushed bin, if any
+    pub fn selection(&self) -> Option<BTreeSet<u32>> {
+        self.selected
+            .and_then(|i| self.histogram.bins.get(i))
+            .map(|bin| bin.members.iter().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::attributes::AttrValue;
+
+    #[test]
+    fn small_integer_ranges_get_a_bin_per_value() {
+        let degrees = [(0, 1.0), (1, 3.0), (2, 3.0), (3, 2.0), (4, f32::NAN)];
+        let histogram = Histogram::new(&degrees, DEFAULT_HISTOGRAM_BINS);
+        let bins: Vec<_> = histogram
+            .bins
+            .iter()
+            .map(|bin| (bin.start, bin.members.clone()))
+            .collect();
+        assert_eq!(bins, [(1.0, vec![0]), (2.0, vec![3]), (3.0, vec![1, 2])]);
+        assert_eq!(histogram.max_count(), 2);
+
+        // Too many values for a bin each, the maximum lands in the last bin
+        let values: Vec<_> = (0..100).map(|i| (i, i as f32)).collect();
+        let histogram = Histogram::new(&values, 4);
+        assert_eq!(histogram.bins.len(), 4);
+        assert!(histogram.bins.iter().all(|bin| bin.members.len() == 25));
+        assert!(Histogram::new(&[], 4).bins.is_empty());
+    }
+
+    #[test]
+    fn clicking_a_bar_brushes_its_nodes() {
+        let mut graph = crate::graph::Graph::new();
+        for (name, age) in [("a", None), ("b", Some(30.0)), ("c", Some(40.0))] {
+            let node = graph.add_named_node(name);
+            if let Some(age) = age {
+                graph.nodes[node as usize]
+                    .attributes
+                    .insert("age".into(), AttrValue::Number(age));
+            }
+        }
+        let mut chart = HistogramChart::new(HistogramSource::Attribute("age".into()));
+        chart.max_bins = 2;
+        chart.update(&graph.nodes, &BTreeMap::new());
+
+        let (width, height) = (800.0, 600.0);
+        let plot = chart.frame.plot_area(width, height);
+        let right_half = plot.origin + vec2(plot.size.x * 0.75, plot.size.y / 2.0);
+        assert!(chart.click(width, height, &right_half));
+        assert_eq!(chart.selection(), Some(BTreeSet::from([2])));
+        // The background and a bar per bin, the brushed one stands out
+        let rects = chart.rects(width, height);
+        assert_eq!(rects.len(), 3);
+        assert_eq!(rects[2].color, SELECTED_BAR_COLOR);
+
+        assert!(chart.click(width, height, &right_half));
+        assert_eq!(chart.selection(), None);
+        assert!(!chart.click(width, height, &vec2(width / 2.0, 0.0)));
+    }
+}
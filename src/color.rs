@@ -0,0 +1,132 @@
+//! Colors are authored in sRGB, like the hex codes they come from, and stay
+//! that way on the CPU. The shaders decode them to linear light and the
+//! frame is rendered into an sRGB target, so blending and MSAA resolves
+//! happen in linear space and the GPU encodes the result back to sRGB.
+
+use cgmath::{vec4, Vector4};
+
+/// Window background, in sRGB
+pub const BACKGROUND: Vector4<f32> = vec4(20.0 / 255.0, 20.0 / 255.0, 28.0 / 255.0, 1.0);
+
+/// Largest sRGB value on the linear segment of sRGB's transfer function.
+/// `color.wgsl` decodes with these `SRGB_` constants too, through
+/// `shader::CONSTANTS`, so the shaders and `blend_over` agree.
+pub const SRGB_CUTOFF: f32 = 0.04045;
+/// Slope of the linear segment
+pub const SRGB_SLOPE: f32 = 12.92;
+/// Offset of the curved segment
+pub const SRGB_OFFSET: f32 = 0.055;
+/// Exponent of the curved segment
+pub const SRGB_GAMMA: f32 = 2.4;
+
+/// Decodes one sRGB channel in `0..=1` to linear light
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= SRGB_CUTOFF {
+        c / SRGB_SLOPE
+    } else {
+        ((c + SRGB_OFFSET) / (1.0 + SRGB_OFFSET)).powf(SRGB_GAMMA)
+    }
+}
+
+/// Encodes one linear channel in `0..=1` to sRGB
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= SRGB_CUTOFF / SRGB_SLOPE {
+        c * SRGB_SLOPE
+    } else {
+        (1.0 + SRGB_OFFSET) * c.powf(1.0 / SRGB_GAMMA) - SRGB_OFFSET
+    }
+}
+
+/// Alpha is linear already and passes through
+pub fn to_linear(color: Vector4<f32>) -> Vector4<f32> {
+    vec4(
+        srgb_to_linear(color.x),
+        srgb_to_linear(color.y),
+        srgb_to_linear(color.z),
+        color.w,
+    )
+}
+
+pub fn to_srgb(color: Vector4<f32>) -> Vector4<f32> {
+    vec4(
+        linear_to_srgb(color.x),
+        linear_to_srgb(color.y),
+        linear_to_srgb(color.z),
+        color.w,
+    )
+}
+
+/// Parses `RGB` or `RRGGBB`, with or without a leading `#`, as an opaque
+/// sRGB color
+pub fn from_hex(hex: &str) -> Option<Vector4<f32>> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.is_ascii() {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        3 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 => (0..6)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    Some(vec4(
+        digits[0] as f32 / 255.0,
+        digits[1] as f32 / 255.0,
+        digits[2] as f32 / 255.0,
+        1.0,
+    ))
+}
+
+/// `#rrggbb`, alpha is dropped
+pub fn to_hex(color: Vector4<f32>) -> String {
+    let [r, g, b, _] = to_bytes(color);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// What alpha blending `src` over an opaque `dst` produces in an sRGB
+/// target, both colors and the result in sRGB. This is the CPU reference
+/// for the pipeline the shaders set up.
+pub fn blend_over(src: Vector4<f32>, dst: Vector4<f32>) -> Vector4<f32> {
+    let (src, dst) = (to_linear(src), to_linear(dst));
+    let a = src.w;
+    let mixed = src.truncate() * a + dst.truncate() * (1.0 - a);
+    to_srgb(mixed.extend(1.0))
+}
+
+/// Rounds to the 8-bit value a render target stores
+pub fn to_bytes(color: Vector4<f32>) -> [u8; 4] {
+    let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [byte(color.x), byte(color.y), byte(color.z), byte(color.w)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trip() {
+        for byte in 0..=255u8 {
+            let c = byte as f32 / 255.0;
+            let back = linear_to_srgb(srgb_to_linear(c));
+            assert!((back - c).abs() < 1e-5, "{byte}: {back}");
+        }
+        // Half the light is well above half the sRGB range
+        assert_eq!(
+            to_bytes(to_srgb(vec4(0.5, 0.5, 0.5, 1.0))),
+            [188, 188, 188, 255]
+        );
+        assert_eq!(from_hex("#fff"), Some(vec4(1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(
+            to_bytes(from_hex("5FB49C").unwrap()),
+            [0x5f, 0xb4, 0x9c, 255]
+        );
+        assert_eq!(to_hex(from_hex("#5fb49c").unwrap()), "#5fb49c");
+        assert_eq!(from_hex("12345"), None);
+        assert_eq!(from_hex("zzz"), None);
+    }
+}
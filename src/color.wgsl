@@ -0,0 +1,11 @@
+// sRGB decoding shared by every shader that draws colors, included with
+// `#include "color.wgsl"` after `#include "constants.wgsl"`, which carries
+// the SRGB_ constants `color::srgb_to_linear` decodes with
+
+// Colors arrive in sRGB and the render target is sRGB, which expects linear
+// values and encodes them after blending
+fn srgb_to_linear(color: vec4<f32>) -> vec4<f32> {
+    let low = color.rgb / SRGB_SLOPE;
+    let high = pow((color.rgb + SRGB_OFFSET) / (1.0 + SRGB_OFFSET), vec3<f32>(SRGB_GAMMA));
+    return vec4<f32>(select(high, low, color.rgb <= vec3<f32>(SRGB_CUTOFF)), color.a);
+}
@@ -0,0 +1,1173 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::{
+    algorithms::Weight,
+    annotation::Annotation,
+    attributes::AttrValue,
+    camera::DEFAULT_LOD_ZOOM,
+    color,
+    edge::Marker,
+    export::{ExportOptions, ExportRegion},
+    generators::{Generator, DEFAULT_FANOUT, DEFAULT_LINKS},
+    graph::{Aggregate, EdgeMerge, NAME_ATTRS},
+    node::{Node, Shape},
+    overlay::Anchor,
+    physics::{
+        BipartiteShape, Preset, Repulsion, DEFAULT_REGION_MARGIN, DEFAULT_SHAKE, DEFAULT_THETA,
+    },
+    quality::DEFAULT_FRAME_BUDGET,
+    sampling::{SampleView, DEFAULT_FULL_ZOOM},
+    session::DEFAULT_SESSION_PATH,
+    style::{Channel, LabelTemplate, StyleRule},
+    ticker::MAX_TICK_RATE,
+    units::Units,
+};
+
+/// Names of the commands `Command::parse` knows, for listing them
+pub const COMMANDS: &[&str] = &[
+    "anchor",
+    "annotations",
+    "arrow",
+    "background",
+    "badge",
+    "bind",
+    "bipartite",
+    "clear",
+    "cull",
+    "cycle",
+    "dedup",
+    "diff",
+    "export",
+    "forces",
+    "freeze",
+    "generate",
+    "image",
+    "label",
+    "labels",
+    "lang",
+    "legend",
+    "limits",
+    "load",
+    "lod",
+    "marker",
+    "metrics",
+    "mst",
+    "multilevel",
+    "note",
+    "path",
+    "picktolerance",
+    "prefs",
+    "preset",
+    "pulse",
+    "quality",
+    "rect",
+    "region",
+    "reheat",
+    "restore",
+    "sample",
+    "save",
+    "scc",
+    "session",
+    "shake",
+    "shape",
+    "snapshot",
+    "snapshots",
+    "spectral",
+    "spring",
+    "style",
+    "tickrate",
+    "unfreeze",
+    "units",
+    "vsync",
+];
+
+/// Text command interface. On native builds lines typed into stdin are read on
+/// a background thread; embedders (and wasm builds) can push lines with
+/// `Console::submit`.
+pub struct Console {
+    tx: Sender<String>,
+    rx: Receiver<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Heuristic {
+    None,
+    Euclidean,
+}
+
+/// Where the `metrics` command sends frame metrics
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricsTarget {
+    /// A CSV file, replaced if it exists
+    Csv(String),
+    /// A Prometheus scrape endpoint listening on an address
+    Prometheus(String),
+}
+
+/// What a `spring` command does to the edges' springs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Spring {
+    /// Sets the rest length, strength or both, leaving out either keeps it
+    Set {
+        rest_length: Option<f32>,
+        strength: Option<f32>,
+    },
+    /// Derives both from a numeric edge attribute, see `weigh_springs`
+    Weight(String),
+    /// Back to the link distance and the usual strength
+    Reset,
+}
+
+/// Nodes a `freeze`, `unfreeze` or `reheat` command acts on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    /// Every node
+    All,
+    /// The connected components touching the selection
+    Selected,
+    /// The connected component of one node, see `resolve_node`
+    Component(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `path <from> <to> [weight=<attr>] [heuristic=none|euclidean]`
+    Path {
+        from: String,
+        to: String,
+        weight: Weight,
+        heuristic: Heuristic,
+    },
+    /// `scc`: toggles hulls around strongly connected components with cycles
+    Scc,
+    /// `cycle`: highlights one directed cycle
+    Cycle,
+    /// `mst [weight=<attr>] [links=on|off]`: emphasizes the minimum spanning
+    /// forest, optionally running the link force on its edges only
+    Mst {
+        weight: Weight,
+        links_only: bool,
+    },
+    /// `load <file>`: replaces the graph with the file's contents
+    Load {
+        path: String,
+    },
+    /// `dedup [weight=<attr>] [by=sum|max|mean]`: merges parallel edges now
+    /// and in every graph loaded after, see `EdgeMerge`. `dedup off` stops
+    /// merging loaded graphs.
+    Dedup(Option<EdgeMerge>),
+    /// `save <file> [selected]`: writes the graph as GraphML or DOT, by
+    /// extension. `selected` writes just the selected nodes and the edges
+    /// between them.
+    Save {
+        path: String,
+        selected: bool,
+    },
+    /// `diff <old file> <new file>`: shows the union of two snapshots colored
+    /// by what was added, removed or kept
+    Diff {
+        old: String,
+        new: String,
+    },
+    /// `note`, `arrow` or `rect`: adds an annotation, see `Annotation::parse`
+    Annotate(Annotation),
+    /// `annotations save <file>`
+    SaveAnnotations {
+        path: String,
+    },
+    /// `annotations load <file>`: replaces the current annotations
+    LoadAnnotations {
+        path: String,
+    },
+    /// `annotations clear`
+    ClearAnnotations,
+    /// `style color|size|width <attr>`: maps an attribute onto node color or
+    /// size, or edge width, replacing any rule for the same channel
+    Style(StyleRule),
+    /// `style label <template>`: captions every node from a template such as
+    /// `{name} ({degree})`, see `LabelTemplate`
+    LabelTemplate(LabelTemplate),
+    /// `style clear`, which also puts back the labels a template replaced
+    ClearStyle,
+    /// `spring [length=<units>] [strength=<n>] [selected]`, `spring
+    /// weight=<attr> [selected]` or `spring reset [selected]`: gives every
+    /// edge, or only the edges between selected nodes, a spring of its own
+    Spring {
+        spring: Spring,
+        selected_only: bool,
+    },
+    /// `marker head|tail none|arrow|circle|diamond|tee [selected]`: sets the
+    /// marker at one end of every edge, or only of the edges between
+    /// selected nodes. Heads are at the end an edge points to.
+    Marker {
+        head: bool,
+        marker: Marker,
+        selected_only: bool,
+    },
+    /// `shape circle|square|diamond [selected]`: sets the shape of every
+    /// node, or only of the selected ones
+    Shape {
+        shape: Shape,
+        selected_only: bool,
+    },
+    /// `image <file.png|file.jpg>|off [selected]`: draws an image inside
+    /// every node, or only the selected ones, such as an avatar or an icon.
+    /// Relative paths are found next to the loaded graph file. `image retry`
+    /// loads images that failed to load again.
+    Image {
+        path: Option<String>,
+        selected_only: bool,
+    },
+    ImageRetry,
+    /// `badge <corner> <attr>|off`: shows an attribute as a badge on that
+    /// corner of every node that has it, see `Badge`
+    Badge {
+        corner: Anchor,
+        attr: Option<String>,
+    },
+    /// `pulse <node>|selected [off]` or `pulse clear`: starts or stops nodes
+    /// pulsing to draw attention to them. `node` is `None` for all of them.
+    Pulse {
+        node: Option<String>,
+        on: bool,
+    },
+    /// `legend <corner>|off`, corners are `top-left`, `top-right`, etc.
+    Legend(Option<Anchor>),
+    /// `label <node> <text>|off`: sets the caption drawn under a node, or
+    /// goes back to its name attribute
+    Label {
+        node: String,
+        text: Option<String>,
+    },
+    /// `labels on|off [zoom=<scale>]`: shows or hides node captions, `zoom`
+    /// sets the camera scale below which they fade out
+    Labels {
+        visible: bool,
+        min_zoom: Option<f32>,
+    },
+    /// `metrics csv <file>|serve <addr>|off`: records fps, physics and
+    /// upload time and graph size every frame, to a CSV file or a
+    /// Prometheus endpoint such as `serve 0.0.0.0:9898`
+    Metrics(Option<MetricsTarget>),
+    /// `export <file.png|file.html> [scale=2..8] [width=<px>] [region=view|graph]`:
+    /// renders a high resolution image of the window or of the whole graph,
+    /// `width` allows poster sized images beyond the largest scale. `.html`
+    /// files get a page to pan, zoom and hover the graph in instead.
+    Export {
+        path: String,
+        options: ExportOptions,
+    },
+    /// `shake [selected] [radius=<units>]`: nudges every node, or only the
+    /// selected ones, in a random direction and reheats the layout
+    Shake {
+        radius: f32,
+        selected_only: bool,
+    },
+    /// `freeze [selected|<node>]`: stops simulating the given components, or
+    /// the whole graph, so they keep their layout
+    Freeze(Scope),
+    /// `unfreeze [selected|<node>]`
+    Unfreeze(Scope),
+    /// `reheat [selected|<node>]`: unfreezes the given components, or
+    /// everything, and restarts the layout's cooling
+    Reheat(Scope),
+    /// `anchor <node>|selected|off`: pins a node at the origin and lays the
+    /// rest of its component out in rings around it, one per link away.
+    /// `selected` needs exactly one node selected.
+    Anchor(Option<String>),
+    /// `bipartite <attr> [shape=columns|rings]|off`: lays the two sides of
+    /// an attribute with two values out in columns or rings, and keeps them
+    /// apart while the forces run, see `Partition`
+    Bipartite(Option<(String, BipartiteShape)>),
+    /// `multilevel`: lays the graph out again from scratch, coarsening it
+    /// first so that big graphs settle far sooner. The viewer waits for it,
+    /// which takes seconds once graphs reach tens of thousands of nodes
+    Multilevel,
+    /// `spectral`: moves every node to a spectral placement and lets the
+    /// forces take it from there, which unfolds meshes and grids
+    Spectral,
+    /// `forces [auto] [link=<units>] [range=<units>] [strength=<n>]
+    /// [gravity=<n>] [collision=<n>] [passes=<n>]
+    /// [solver=exact|barnes-hut] [theta=<n>]`: shows the force parameters.
+    /// `auto` recalibrates them for the current graph, as happens on load,
+    /// and the options override single values.
+    Forces {
+        auto: bool,
+        link_distance: Option<f32>,
+        repulsion_range: Option<f32>,
+        strength: Option<f32>,
+        gravity: Option<f32>,
+        collision: Option<f32>,
+        collision_iterations: Option<u32>,
+        repulsion: Option<Repulsion>,
+    },
+    /// `snapshot <name>`: saves the current node positions, replacing any
+    /// snapshot with the same name
+    Snapshot {
+        name: String,
+    },
+    /// `restore <name>`: animates the nodes back to a saved snapshot
+    Restore {
+        name: String,
+    },
+    /// `snapshots`: lists saved snapshots
+    ListSnapshots,
+    /// `snapshots save <file>`
+    SaveSnapshots {
+        path: String,
+    },
+    /// `snapshots load <file>`: adds the file's snapshots, replacing those
+    /// with the same names
+    LoadSnapshots {
+        path: String,
+    },
+    /// `session save [file]`: writes the graph, its layout and styling, the
+    /// view and the forces to a file, `DEFAULT_SESSION_PATH` without one
+    SaveSession {
+        path: String,
+    },
+    /// `session load [file]`: replaces all of those with a saved session's
+    LoadSession {
+        path: String,
+    },
+    /// `units <amount> <name>`: makes one world unit stand for `amount` of
+    /// `name`, e.g. `units 0.5 ms`. `units reset` goes back to plain units.
+    Units(Units),
+    /// `picktolerance <px>`: how far outside a node, in logical pixels, a
+    /// press still grabs it
+    PickTolerance(f32),
+    /// `preset small|medium|large|auto`: switches to a preset's solver,
+    /// collisions and tick rate and loads every graph with it from then on.
+    /// `auto` goes back to picking one by graph size, see `Preset::for_size`.
+    Preset(Option<Preset>),
+    /// `limits [soft-nodes=<n>] [soft-edges=<n>] [hard-nodes=<n>]
+    /// [hard-edges=<n>]`: shows the element counts past which labels and
+    /// markers are dropped (soft) or loaded graphs are sampled (hard), the
+    /// options change them
+    Limits {
+        soft_nodes: Option<usize>,
+        soft_edges: Option<usize>,
+        hard_nodes: Option<usize>,
+        hard_edges: Option<usize>,
+    },
+    /// `sample <nodes> [seed=<n>] [zoom=<scale>]` or `sample off`: draws only
+    /// a sample of that many nodes, picked to keep the graph's degrees, until
+    /// the camera zooms in to `zoom`. See `SampleView`.
+    Sample(Option<SampleView>),
+    /// `region [margin]` or `region off`: simulates only the nodes in view,
+    /// and within `margin` of its size around it, leaving the rest where
+    /// they are. See `Physics::region`.
+    Region(Option<f32>),
+    /// `cull on|off`: whether nodes and edges off screen are left out of
+    /// the instance buffers, see `NodeRenderPass::view`
+    Cull(bool),
+    /// `generate random <nodes> <p>`, `generate scalefree <nodes> [links]`,
+    /// `generate grid <width> [height]` or `generate tree <depth> [fanout]`,
+    /// each taking `[seed=<n>]`: adds a synthetic graph beside the current
+    /// one, see `generators`
+    Generate {
+        generator: Generator,
+        seed: Option<u64>,
+    },
+    /// `quality [budget]` or `quality off`: lowers MSAA, then resolution,
+    /// while frames take the GPU over `budget` milliseconds and restores
+    /// them when there's room again. `off` draws at full quality whatever
+    /// the cost. See `AdaptiveQuality`.
+    Quality(Option<f32>),
+    /// `lod [zoom]` or `lod off`: draws with less detail while the camera
+    /// is zoomed out past `zoom`, see `State::lod_zoom`
+    Lod(Option<f32>),
+    /// `vsync on|off`: whether frames wait for the display's refresh
+    Vsync(bool),
+    /// `background <#rrggbb>|default`: the color the window is cleared to
+    Background(Option<cgmath::Vector4<f32>>),
+    /// `bind <key> <as>` or `bind <key> off`: makes a key do what another
+    /// one in the keymap does, or undoes that
+    Bind {
+        key: char,
+        acts_as: Option<char>,
+    },
+    /// `prefs [reset]`: shows the saved preferences, or resets them to
+    /// their defaults
+    Prefs {
+        reset: bool,
+    },
+    /// `tickrate <hz>`: runs the simulation at a fixed rate, drawing frames
+    /// in between ticks interpolated. `tickrate frame` ticks once per frame.
+    TickRate(Option<f32>),
+    /// `lang <file.ftl>`: switches the UI to a message catalog, named after
+    /// the file. `lang en` goes back to English.
+    Lang(Option<String>),
+    /// `clear`: removes highlights left by previous commands
+    Clear,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        Self { tx, rx }
+    }
+
+    /// Starts forwarding stdin lines to the console
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_stdin() -> Self {
+        let console = Self::new();
+        let tx = console.tx.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        console
+    }
+
+    pub fn submit<S: Into<String>>(&self, line: S) {
+        // The receiver lives as long as self, so this can't fail
+        let _ = self.tx.send(line.into());
+    }
+
+    /// Drains the lines received since the last call
+    pub fn poll(&self) -> Vec<String> {
+        self.rx.try_iter().collect()
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or_else(|| "empty command".to_string())?;
+        let (args, options): (Vec<&str>, Vec<&str>) = words.partition(|w| !w.contains('='));
+        let option = |key: &str| {
+            options
+                .iter()
+                .find_map(|o| o.strip_prefix(key)?.strip_prefix('='))
+        };
+
+        match name {
+            "path" => {
+                let [from, to] = args[..] else {
+                    return Err(
+                        "usage: path <from> <to> [weight=<attr>] [heuristic=none|euclidean]".into(),
+                    );
+                };
+                let weight = parse_weight(option("weight"));
+                let heuristic = match option("heuristic") {
+                    None | Some("none") => Heuristic::None,
+                    Some("euclidean") => Heuristic::Euclidean,
+                    Some(other) => return Err(format!("unknown heuristic: {other}")),
+                };
+                Ok(Command::Path {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    weight,
+                    heuristic,
+                })
+            }
+            "scc" => Ok(Command::Scc),
+            "cycle" => Ok(Command::Cycle),
+            "mst" => Ok(Command::Mst {
+                weight: parse_weight(option("weight")),
+                links_only: match option("links") {
+                    None | Some("off") => false,
+                    Some("on") => true,
+                    Some(other) => return Err(format!("links must be on or off, got {other}")),
+                },
+            }),
+            "load" => match args[..] {
+                [path] => Ok(Command::Load {
+                    path: path.to_string(),
+                }),
+                _ => Err("usage: load <file>".into()),
+            },
+            "dedup" => {
+                let usage = "usage: dedup [weight=<attr>] [by=sum|max|mean] or dedup off";
+                match args[..] {
+                    ["off"] if options.is_empty() => return Ok(Command::Dedup(None)),
+                    [] => (),
+                    _ => return Err(usage.into()),
+                }
+                let aggregate = match option("by") {
+                    None | Some("sum") => Aggregate::Sum,
+                    Some("max") => Aggregate::Max,
+                    Some("mean") => Aggregate::Mean,
+                    Some(other) => return Err(format!("unknown aggregate: {other}, {usage}")),
+                };
+                Ok(Command::Dedup(Some(EdgeMerge {
+                    weight: option("weight").unwrap_or("weight").to_string(),
+                    aggregate,
+                })))
+            }
+            "save" => match args[..] {
+                [path] | [path, "selected"] => Ok(Command::Save {
+                    path: path.to_string(),
+                    selected: args.len() == 2,
+                }),
+                _ => Err("usage: save <file.graphml|file.dot> [selected]".into()),
+            },
+            "diff" => match args[..] {
+                [old, new] => Ok(Command::Diff {
+                    old: old.to_string(),
+                    new: new.to_string(),
+                }),
+                _ => Err("usage: diff <old file> <new file>".into()),
+            },
+            "note" | "arrow" | "rect" => Annotation::parse(line).map(Command::Annotate),
+            "annotations" => match args[..] {
+                ["save", path] => Ok(Command::SaveAnnotations {
+                    path: path.to_string(),
+                }),
+                ["load", path] => Ok(Command::LoadAnnotations {
+                    path: path.to_string(),
+                }),
+                ["clear"] => Ok(Command::ClearAnnotations),
+                _ => Err("usage: annotations save|load <file> or annotations clear".into()),
+            },
+            "style" if args.first() == Some(&"label") => {
+                // Templates may contain spaces and `=`
+                let template = line.trim_start()[name.len()..].trim_start()["label".len()..].trim();
+                if template.is_empty() {
+                    return Err("usage: style label <template>".into());
+                }
+                LabelTemplate::parse(template).map(Command::LabelTemplate)
+            }
+            "style" => match args[..] {
+                ["color", attr] => Ok(Command::Style(StyleRule::new(attr, Channel::Color))),
+                ["size", attr] => Ok(Command::Style(StyleRule::new(attr, Channel::Size))),
+                ["width", attr] => Ok(Command::Style(StyleRule::new(attr, Channel::Width))),
+                ["clear"] => Ok(Command::ClearStyle),
+                _ => Err(
+                    "usage: style color|size|width <attr>, style label <template> or style clear"
+                        .into(),
+                ),
+            },
+            "spring" => {
+                const USAGE: &str = "usage: spring [length=<units>] [strength=<n>] [selected], spring weight=<attr> [selected] or spring reset [selected]";
+                let (reset, selected_only) = match args[..] {
+                    [] => (false, false),
+                    ["selected"] => (false, true),
+                    ["reset"] => (true, false),
+                    ["reset", "selected"] => (true, true),
+                    _ => return Err(USAGE.into()),
+                };
+                let number = |key: &str| {
+                    option(key)
+                        .map(|value| match value.parse::<f32>() {
+                            Ok(n) if n > 0.0 => Ok(n),
+                            _ => Err(format!("{key} must be a positive number, got {value}")),
+                        })
+                        .transpose()
+                };
+                let (rest_length, strength) = (number("length")?, number("strength")?);
+                let spring = match (reset, option("weight"), rest_length, strength) {
+                    (true, None, None, None) => Spring::Reset,
+                    (false, Some(attr), None, None) => Spring::Weight(attr.into()),
+                    (false, None, Some(_), _) | (false, None, _, Some(_)) => Spring::Set {
+                        rest_length,
+                        strength,
+                    },
+                    _ => return Err(USAGE.into()),
+                };
+                Ok(Command::Spring {
+                    spring,
+                    selected_only,
+                })
+            }
+            "marker" => {
+                let (end, shape, selected_only) = match args[..] {
+                    [end, shape] => (end, shape, false),
+                    [end, shape, "selected"] => (end, shape, true),
+                    _ => {
+                        return Err(
+                            "usage: marker head|tail none|arrow|circle|diamond|tee [selected]"
+                                .into(),
+                        )
+                    }
+                };
+                let head = match end {
+                    "head" => true,
+                    "tail" => false,
+                    _ => return Err(format!("unknown edge end: {end}, use head or tail")),
+                };
+                let marker =
+                    Marker::parse(shape).ok_or_else(|| format!("unknown marker: {shape}"))?;
+                Ok(Command::Marker {
+                    head,
+                    marker,
+                    selected_only,
+                })
+            }
+            "shape" => {
+                let (name, selected_only) = match args[..] {
+                    [name] => (name, false),
+                    [name, "selected"] => (name, true),
+                    _ => return Err("usage: shape circle|square|diamond [selected]".into()),
+                };
+                let shape = Shape::parse(name).ok_or_else(|| format!("unknown shape: {name}"))?;
+                Ok(Command::Shape {
+                    shape,
+                    selected_only,
+                })
+            }
+            "image" => {
+                let (path, selected_only) = match args[..] {
+                    ["retry"] => return Ok(Command::ImageRetry),
+                    [path] => (path, false),
+                    [path, "selected"] => (path, true),
+                    _ => return Err("usage: image <file>|off [selected] or image retry".into()),
+                };
+                Ok(Command::Image {
+                    path: (path != "off").then(|| path.to_string()),
+                    selected_only,
+                })
+            }
+            "badge" => match args[..] {
+                [corner, attr] => {
+                    let corner = Anchor::from_name(corner)
+                        .ok_or_else(|| format!("unknown corner: {corner}"))?;
+                    let attr = (attr != "off").then(|| attr.to_string());
+                    Ok(Command::Badge { corner, attr })
+                }
+                _ => Err(
+                    "usage: badge top-left|top-right|bottom-left|bottom-right <attr>|off".into(),
+                ),
+            },
+            "pulse" => {
+                let (node, on) = match args[..] {
+                    ["clear"] => (None, false),
+                    [node] => (Some(node), true),
+                    [node, "off"] => (Some(node), false),
+                    _ => return Err("usage: pulse <node>|selected [off] | pulse clear".into()),
+                };
+                Ok(Command::Pulse {
+                    node: node.map(str::to_string),
+                    on,
+                })
+            }
+            "legend" => match args[..] {
+                ["off"] => Ok(Command::Legend(None)),
+                [corner] => Anchor::from_name(corner)
+                    .map(|anchor| Command::Legend(Some(anchor)))
+                    .ok_or_else(|| format!("unknown corner: {corner}")),
+                _ => Err("usage: legend top-left|top-right|bottom-left|bottom-right|off".into()),
+            },
+            "label" => {
+                // The caption is the rest of the line, spaces and all
+                let rest = line.trim_start()[name.len()..].trim();
+                let (node, text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let text = text.trim();
+                if node.is_empty() || text.is_empty() {
+                    return Err("usage: label <node> <text>|off".into());
+                }
+                Ok(Command::Label {
+                    node: node.to_string(),
+                    text: (text != "off").then(|| text.to_string()),
+                })
+            }
+            "labels" => {
+                let visible = match args[..] {
+                    ["on"] => true,
+                    ["off"] => false,
+                    _ => return Err("usage: labels on|off [zoom=<scale>]".into()),
+                };
+                let min_zoom = option("zoom")
+                    .map(|zoom| {
+                        zoom.parse()
+                            .map_err(|_| format!("zoom must be a number, got {zoom}"))
+                    })
+                    .transpose()?;
+                Ok(Command::Labels { visible, min_zoom })
+            }
+            "metrics" => match args[..] {
+                ["csv", path] => Ok(Command::Metrics(Some(MetricsTarget::Csv(path.into())))),
+                ["serve", addr] => Ok(Command::Metrics(Some(MetricsTarget::Prometheus(
+                    addr.into(),
+                )))),
+                ["off"] => Ok(Command::Metrics(None)),
+                _ => Err("usage: metrics csv <file>|serve <addr>|off".into()),
+            },
+            "export" => {
+                let [path] = args[..] else {
+                    return Err(
+                        "usage: export <file.png|file.html> [scale=2..8] [width=<px>] [region=view|graph]"
+                            .into(),
+                    );
+                };
+                let mut options = ExportOptions::default();
+                if let Some(scale) = option("scale") {
+                    options.scale = scale
+                        .parse()
+                        .map_err(|_| format!("scale must be an integer, got {scale}"))?;
+                }
+                if let Some(width) = option("width") {
+                    options.width = Some(
+                        width
+                            .parse()
+                            .map_err(|_| format!("width must be an integer, got {width}"))?,
+                    );
+                }
+                options.region = match option("region") {
+                    None | Some("view") => ExportRegion::View,
+                    Some("graph") => ExportRegion::Graph,
+                    Some(other) => return Err(format!("unknown region: {other}")),
+                };
+                Ok(Command::Export {
+                    path: path.to_string(),
+                    options,
+                })
+            }
+            "shake" => {
+                let selected_only = match args[..] {
+                    [] => false,
+                    ["selected"] => true,
+                    _ => return Err("usage: shake [selected] [radius=<units>]".into()),
+                };
+                let radius = match option("radius") {
+                    None => DEFAULT_SHAKE,
+                    Some(radius) => radius
+                        .parse()
+                        .map_err(|_| format!("radius must be a number, got {radius}"))?,
+                };
+                Ok(Command::Shake {
+                    radius,
+                    selected_only,
+                })
+            }
+            "freeze" | "unfreeze" | "reheat" => {
+                let scope = match args[..] {
+                    [] => Scope::All,
+                    ["selected"] => Scope::Selected,
+                    [node] => Scope::Component(node.to_string()),
+                    _ => return Err(format!("usage: {name} [selected|<node>]")),
+                };
+                Ok(match name {
+                    "freeze" => Command::Freeze(scope),
+                    "unfreeze" => Command::Unfreeze(scope),
+                    _ => Command::Reheat(scope),
+                })
+            }
+            "anchor" => match args[..] {
+                ["off"] => Ok(Command::Anchor(None)),
+                [node] => Ok(Command::Anchor(Some(node.to_string()))),
+                _ => Err("usage: anchor <node>|selected|off".into()),
+            },
+            "bipartite" => {
+                let shape = match option("shape") {
+                    None | Some("columns") => BipartiteShape::Columns,
+                    Some("rings") => BipartiteShape::Rings,
+                    Some(other) => return Err(format!("unknown shape: {other}")),
+                };
+                match args[..] {
+                    ["off"] => Ok(Command::Bipartite(None)),
+                    [attr] => Ok(Command::Bipartite(Some((attr.to_string(), shape)))),
+                    _ => {
+                        Err("usage: bipartite <attr> [shape=columns|rings] or bipartite off".into())
+                    }
+                }
+            }
+            "multilevel" => Ok(Command::Multilevel),
+            "spectral" => Ok(Command::Spectral),
+            "forces" => {
+                let auto =
+                    match args[..] {
+                        [] => false,
+                        ["auto"] => true,
+                        _ => return Err(
+                            "usage: forces [auto] [link=<units>] [range=<units>] [strength=<n>] [gravity=<n>] [collision=<n>] [passes=<n>]"
+                                .into(),
+                        ),
+                    };
+                let number = |key: &str| {
+                    option(key)
+                        .map(|value| {
+                            value
+                                .parse::<f32>()
+                                .map_err(|_| format!("{key} must be a number, got {value}"))
+                        })
+                        .transpose()
+                };
+                Ok(Command::Forces {
+                    auto,
+                    link_distance: number("link")?,
+                    repulsion_range: number("range")?,
+                    strength: number("strength")?,
+                    gravity: number("gravity")?,
+                    collision: number("collision")?,
+                    collision_iterations: option("passes")
+                        .map(|value| {
+                            value
+                                .parse::<u32>()
+                                .map_err(|_| format!("passes must be a whole number, got {value}"))
+                        })
+                        .transpose()?,
+                    repulsion: match (option("solver"), number("theta")?) {
+                        (Some("exact"), _) => Some(Repulsion::Exact),
+                        (Some("barnes-hut"), theta) => Some(Repulsion::BarnesHut {
+                            theta: theta.unwrap_or(DEFAULT_THETA),
+                        }),
+                        (Some(other), _) => return Err(format!("unknown solver: {other}")),
+                        (None, Some(theta)) => Some(Repulsion::BarnesHut { theta }),
+                        (None, None) => None,
+                    },
+                })
+            }
+            "snapshot" | "restore" => {
+                // Names may contain spaces
+                let rest = line.trim_start()[name.len()..].trim();
+                if rest.is_empty() {
+                    return Err(format!("usage: {name} <name>"));
+                }
+                let snapshot = rest.to_string();
+                Ok(if name == "snapshot" {
+                    Command::Snapshot { name: snapshot }
+                } else {
+                    Command::Restore { name: snapshot }
+                })
+            }
+            "session" => match args[..] {
+                ["save"] => Ok(Command::SaveSession {
+                    path: DEFAULT_SESSION_PATH.into(),
+                }),
+                ["save", path] => Ok(Command::SaveSession {
+                    path: path.to_string(),
+                }),
+                ["load"] => Ok(Command::LoadSession {
+                    path: DEFAULT_SESSION_PATH.into(),
+                }),
+                ["load", path] => Ok(Command::LoadSession {
+                    path: path.to_string(),
+                }),
+                _ => Err("usage: session save|load [file]".into()),
+            },
+            "snapshots" => match args[..] {
+                [] => Ok(Command::ListSnapshots),
+                ["save", path] => Ok(Command::SaveSnapshots {
+                    path: path.to_string(),
+                }),
+                ["load", path] => Ok(Command::LoadSnapshots {
+                    path: path.to_string(),
+                }),
+                _ => Err("usage: snapshots [save|load <file>]".into()),
+            },
+            "units" => match args[..] {
+                ["reset"] => Ok(Command::Units(Units::default())),
+                [amount, name] => match amount.parse::<f32>() {
+                    Ok(amount) if amount > 0.0 && amount.is_finite() => {
+                        Ok(Command::Units(Units::new(amount, name)))
+                    }
+                    _ => Err(format!("amount must be a positive number, got {amount}")),
+                },
+                _ => Err("usage: units <amount> <name> or units reset".into()),
+            },
+            "picktolerance" => match args[..] {
+                [pixels] => match pixels.parse::<f32>() {
+                    Ok(pixels) if pixels >= 0.0 && pixels.is_finite() => {
+                        Ok(Command::PickTolerance(pixels))
+                    }
+                    _ => Err(format!(
+                        "tolerance must be a number of pixels, got {pixels}"
+                    )),
+                },
+                _ => Err("usage: picktolerance <px>".into()),
+            },
+            "preset" => match args[..] {
+                ["auto"] => Ok(Command::Preset(None)),
+                [name] => Preset::from_name(name)
+                    .map(|preset| Command::Preset(Some(preset)))
+                    .ok_or_else(|| format!("unknown preset: {name}")),
+                _ => Err("usage: preset small|medium|large|auto".into()),
+            },
+            "limits" => {
+                let count = |key: &str| {
+                    option(key)
+                        .map(|value| {
+                            value
+                                .parse::<usize>()
+                                .map_err(|_| format!("{key} must be a whole number, got {value}"))
+                        })
+                        .transpose()
+                };
+                Ok(Command::Limits {
+                    soft_nodes: count("soft-nodes")?,
+                    soft_edges: count("soft-edges")?,
+                    hard_nodes: count("hard-nodes")?,
+                    hard_edges: count("hard-edges")?,
+                })
+            }
+            "sample" => match args[..] {
+                ["off"] => Ok(Command::Sample(None)),
+                [size] => {
+                    let size = size
+                        .parse::<usize>()
+                        .map_err(|_| format!("sample size must be a whole number, got {size}"))?;
+                    let seed = match option("seed") {
+                        Some(seed) => seed
+                            .parse::<u64>()
+                            .map_err(|_| format!("seed must be a whole number, got {seed}"))?,
+                        None => 0,
+                    };
+                    let full_zoom = match option("zoom") {
+                        Some(zoom) => match zoom.parse::<f32>() {
+                            Ok(zoom) if zoom > 0.0 => zoom,
+                            _ => return Err(format!("zoom must be a positive number, got {zoom}")),
+                        },
+                        None => DEFAULT_FULL_ZOOM,
+                    };
+                    Ok(Command::Sample(Some(SampleView::new(
+                        size, seed, full_zoom,
+                    ))))
+                }
+                _ => Err("usage: sample <nodes> [seed=<n>] [zoom=<scale>] | sample off".into()),
+            },
+            "vsync" => match args[..] {
+                ["on"] => Ok(Command::Vsync(true)),
+                ["off"] => Ok(Command::Vsync(false)),
+                _ => Err("usage: vsync on|off".into()),
+            },
+            "background" => match args[..] {
+                ["default"] => Ok(Command::Background(None)),
+                [hex] => color::from_hex(hex)
+                    .map(|color| Command::Background(Some(color)))
+                    .ok_or_else(|| format!("not a color: {hex}")),
+                _ => Err("usage: background <#rrggbb>|default".into()),
+            },
+            "bind" => {
+                let usage = "usage: bind <key> <as> or bind <key> off";
+                let key = |s: &str| {
+                    let mut chars = s.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Ok(c.to_ascii_lowercase()),
+                        _ => Err(format!("expected a single key, got {s}")),
+                    }
+                };
+                match args[..] {
+                    [from, "off"] => Ok(Command::Bind {
+                        key: key(from)?,
+                        acts_as: None,
+                    }),
+                    [from, to] => Ok(Command::Bind {
+                        key: key(from)?,
+                        acts_as: Some(key(to)?),
+                    }),
+                    _ => Err(usage.into()),
+                }
+            }
+            "prefs" => match args[..] {
+                [] => Ok(Command::Prefs { reset: false }),
+                ["reset"] => Ok(Command::Prefs { reset: true }),
+                _ => Err("usage: prefs [reset]".into()),
+            },
+            "cull" => match args[..] {
+                ["on"] => Ok(Command::Cull(true)),
+                ["off"] => Ok(Command::Cull(false)),
+                _ => Err("usage: cull on|off".into()),
+            },
+            "generate" => {
+                let count = |name: &str, value: &str| {
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("{name} must be a whole number, got {value}"))
+                };
+                let generator = match args[..] {
+                    ["random", nodes, p] => Generator::Random {
+                        nodes: count("nodes", nodes)?,
+                        p: match p.parse::<f32>() {
+                            Ok(p) if (0.0..=1.0).contains(&p) => p,
+                            _ => return Err(format!("p must be between 0 and 1, got {p}")),
+                        },
+                    },
+                    ["scalefree", nodes, ref links @ ..] if links.len() <= 1 => {
+                        Generator::ScaleFree {
+                            nodes: count("nodes", nodes)?,
+                            links: match links {
+                                [links] => count("links", links)?.max(1),
+                                _ => DEFAULT_LINKS,
+                            },
+                        }
+                    }
+                    ["grid", width, ref height @ ..] if height.len() <= 1 => {
+                        let width = count("width", width)?;
+                        Generator::Grid {
+                            width,
+                            height: match height {
+                                [height] => count("height", height)?,
+                                _ => width,
+                            },
+                        }
+                    }
+                    ["tree", depth, ref fanout @ ..] if fanout.len() <= 1 => Generator::Tree {
+                        depth: count("depth", depth)?,
+                        fanout: match fanout {
+                            [fanout] => count("fanout", fanout)?,
+                            _ => DEFAULT_FANOUT,
+                        },
+                    },
+                    _ => {
+                        return Err("usage: generate random <nodes> <p> | scalefree <nodes> [links] | grid <width> [height] | tree <depth> [fanout], each [seed=<n>]".into())
+                    }
+                };
+                generator.check()?;
+                let seed = option("seed")
+                    .map(|seed| {
+                        seed.parse::<u64>()
+                            .map_err(|_| format!("seed must be a whole number, got {seed}"))
+                    })
+                    .transpose()?;
+                Ok(Command::Generate { generator, seed })
+            }
+            "quality" => match args[..] {
+                ["off"] => Ok(Command::Quality(None)),
+                [] => Ok(Command::Quality(Some(DEFAULT_FRAME_BUDGET))),
+                [budget] => match budget.parse::<f32>() {
+                    Ok(budget) if budget > 0.0 && budget.is_finite() => {
+                        Ok(Command::Quality(Some(budget)))
+                    }
+                    _ => Err(format!(
+                        "budget must be a positive number of ms, got {budget}"
+                    )),
+                },
+                _ => Err("usage: quality [budget] | quality off".into()),
+            },
+            "lod" => match args[..] {
+                ["off"] => Ok(Command::Lod(None)),
+                [] => Ok(Command::Lod(Some(DEFAULT_LOD_ZOOM))),
+                [zoom] => match zoom.parse::<f32>() {
+                    Ok(zoom) if zoom > 0.0 && zoom.is_finite() => Ok(Command::Lod(Some(zoom))),
+                    _ => Err(format!("zoom must be a positive number, got {zoom}")),
+                },
+                _ => Err("usage: lod [zoom] | lod off".into()),
+            },
+            "region" => match args[..] {
+                ["off"] => Ok(Command::Region(None)),
+                [] => Ok(Command::Region(Some(DEFAULT_REGION_MARGIN))),
+                [margin] => match margin.parse::<f32>() {
+                    Ok(margin) if margin >= 0.0 && margin.is_finite() => {
+                        Ok(Command::Region(Some(margin)))
+                    }
+                    _ => Err(format!(
+                        "margin must be a fraction of the view, got {margin}"
+                    )),
+                },
+                _ => Err("usage: region [margin] | region off".into()),
+            },
+            "tickrate" => match args[..] {
+                ["frame"] => Ok(Command::TickRate(None)),
+                [hz] => match hz.parse::<f32>() {
+                    Ok(hz) if hz > 0.0 && hz <= MAX_TICK_RATE => Ok(Command::TickRate(Some(hz))),
+                    _ => Err(format!(
+                        "rate must be a number of ticks per second up to {MAX_TICK_RATE}, got {hz}"
+                    )),
+                },
+                _ => Err("usage: tickrate <hz> or tickrate frame".into()),
+            },
+            "lang" => match args[..] {
+                ["en"] => Ok(Command::Lang(None)),
+                [path] => Ok(Command::Lang(Some(path.to_string()))),
+                _ => Err("usage: lang <file.ftl> or lang en".into()),
+            },
+            "clear" => Ok(Command::Clear),
+            other => Err(format!("unknown command: {other}")),
+        }
+    }
+}
+
+fn parse_weight(option: Option<&str>) -> Weight {
+    match option {
+        None | Some("1") | Some("hops") => Weight::Unit,
+        Some(attr) => Weight::Attribute(attr.to_string()),
+    }
+}
+
+/// Finds a node by its external id or label/name/id attribute, falling back
+/// to its index
+pub fn resolve_node(nodes: &[Node], key: &str) -> Option<u32> {
+    let named = |node: &Node| {
+        node.external_id.as_deref() == Some(key)
+            || NAME_ATTRS
+                .iter()
+                .any(|attr| match node.attributes.get(*attr) {
+                    Some(AttrValue::Text(s)) => s == key,
+                    Some(AttrValue::Number(n)) => key.parse::<f32>().ok() == Some(*n),
+                    None => false,
+                })
+    };
+    nodes.iter().position(named).map(|i| i as u32).or_else(|| {
+        key.parse::<u32>()
+            .ok()
+            .filter(|i| (*i as usize) < nodes.len())
+    })
+}
+
+/// How to refer to node `id` in a command, the first of its external id, its
+/// name attributes and its index that `resolve_node` takes back to it. Keys
+/// with spaces don't survive being split into arguments and are passed over.
+pub fn node_key(nodes: &[Node], id: u32) -> Option<String> {
+    let node = nodes.get(id as usize)?;
+    let names = NAME_ATTRS
+        .iter()
+        .filter_map(|attr| node.attributes.get(*attr))
+        .map(|value| value.to_string());
+    node.external_id
+        .clone()
+        .into_iter()
+        .chain(names)
+        .chain([id.to_string()])
+        .find(|key| {
+            !key.is_empty()
+                && !key.contains(char::is_whitespace)
+                && resolve_node(nodes, key) == Some(id)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn pulse_takes_a_node_the_selection_or_clear() {
+        let pulse = |node: Option<&str>, on| Command::Pulse {
+            node: node.map(str::to_string),
+            on,
+        };
+        assert_eq!(Command::parse("pulse db"), Ok(pulse(Some("db"), true)));
+        assert_eq!(
+            Command::parse("pulse selected off"),
+            Ok(pulse(Some("selected"), false))
+        );
+        assert_eq!(Command::parse("pulse clear"), Ok(pulse(None, false)));
+        assert!(Command::parse("pulse").is_err());
+        assert!(Command::parse("pulse db on").is_err());
+    }
+
+    #[test]
+    fn node_keys_resolve_back() {
+        let mut graph = Graph::new();
+        graph.add_named_node("3");
+        graph.add_named_node("b");
+        graph.add_named_node("two words");
+        graph.add_named_node("d");
+        graph.nodes[1].external_id = Some("svc-b".into());
+        let nodes = &graph.nodes;
+        assert_eq!(node_key(nodes, 1).as_deref(), Some("svc-b"));
+        assert_eq!(node_key(nodes, 0).as_deref(), Some("3"));
+        assert_eq!(node_key(nodes, 2).as_deref(), Some("2"));
+        assert_eq!(node_key(nodes, 3).as_deref(), Some("d"));
+        // "3" is node 0's name, which leaves nothing naming node 3
+        graph.nodes[3].attributes.clear();
+        graph.nodes[3].external_id = None;
+        assert_eq!(node_key(&graph.nodes, 3), None);
+        for id in 0..3 {
+            let key = node_key(&graph.nodes, id).unwrap();
+            assert_eq!(resolve_node(&graph.nodes, &key), Some(id));
+        }
+    }
+}
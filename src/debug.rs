@@ -0,0 +1,192 @@
+use cgmath::{vec2, ElementWise};
+
+use crate::{
+    annotation::{arrow, segment},
+    hull::HullVertex,
+    RenderSettings,
+};
+
+pub const DEFAULT_VERTEX_BUFFER_CAP: usize = 1 << 16;
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// Scratch geometry in world space for seeing what the physics is doing, such
+/// as force arrows and the trail of a dragged node. It's rebuilt every frame
+/// and drawn above everything but the overlay.
+pub struct DebugRenderPass {
+    vertices: Vec<HullVertex>,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl DebugRenderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+    ) -> Self {
+        // Flat colored triangles on top of the graph, same as annotations
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            include_str!("annotation.wgsl"),
+        );
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Vertex Buffer"),
+            size: (std::mem::size_of::<HullVertex>() * DEFAULT_VERTEX_BUFFER_CAP) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            vertices: vec![],
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `annotation.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[HullVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                ..Default::default()
+            },
+            depth_stencil: settings.depth_stencil(wgpu::CompareFunction::Always),
+            multisample: settings.multisample(),
+            multiview: None,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// Straight line of `width` world units
+    pub fn line(
+        &mut self,
+        a: cgmath::Vector2<f32>,
+        b: cgmath::Vector2<f32>,
+        width: f32,
+        color: cgmath::Vector4<f32>,
+    ) {
+        if a != b {
+            self.vertices.extend(segment(a, b, width, color));
+        }
+    }
+
+    /// Line of `width` world units with a head proportional to it
+    pub fn arrow(
+        &mut self,
+        from: cgmath::Vector2<f32>,
+        to: cgmath::Vector2<f32>,
+        width: f32,
+        color: cgmath::Vector4<f32>,
+    ) {
+        let head = vec2(width * 4.0, width * 3.5);
+        self.vertices.extend(arrow(from, to, width, head, color));
+    }
+
+    /// Outline of an axis aligned box, drawn inside it
+    pub fn rect(
+        &mut self,
+        min: cgmath::Vector2<f32>,
+        max: cgmath::Vector2<f32>,
+        width: f32,
+        color: cgmath::Vector4<f32>,
+    ) {
+        let inset = width / 2.0;
+        let (min, max) = (min.add_element_wise(inset), max.sub_element_wise(inset));
+        let corners = [min, vec2(max.x, min.y), max, vec2(min.x, max.y)];
+        for i in 0..4 {
+            self.line(corners[i], corners[(i + 1) % 4], width, color);
+        }
+    }
+
+    /// Circle outline approximated with `CIRCLE_SEGMENTS` lines
+    pub fn circle(
+        &mut self,
+        center: cgmath::Vector2<f32>,
+        radius: f32,
+        width: f32,
+        color: cgmath::Vector4<f32>,
+    ) {
+        let point = |i: usize| {
+            let angle = std::f32::consts::TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + vec2(angle.cos(), angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), width, color);
+        }
+    }
+
+    pub fn write(&mut self, queue: &wgpu::Queue) {
+        let len = self
+            .vertices
+            .len()
+            .min(DEFAULT_VERTEX_BUFFER_CAP - DEFAULT_VERTEX_BUFFER_CAP % 3);
+        self.vertex_count = len as u32;
+        queue.write_buffer(
+            &self.vertex_buffer,
+            0,
+            bytemuck::cast_slice(&self.vertices[..len]),
+        );
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
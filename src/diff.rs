@@ -0,0 +1,188 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use cgmath::vec4;
+
+use crate::{
+    attributes::AttrValue,
+    graph::{Graph, NodeId},
+};
+
+/// Text attribute recording whether an element was added, removed or kept
+pub const DIFF_ATTR: &str = "diff";
+
+pub const ADDED_COLOR: cgmath::Vector4<f32> = vec4(0.3, 0.85, 0.4, 1.0);
+/// Removed elements are ghosted
+pub const REMOVED_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.25, 0.25, 0.35);
+pub const UNCHANGED_COLOR: cgmath::Vector4<f32> = vec4(0.6, 0.6, 0.6, 1.0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+impl DiffStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiffStatus::Added => "added",
+            DiffStatus::Removed => "removed",
+            DiffStatus::Unchanged => "unchanged",
+        }
+    }
+
+    pub fn color(&self) -> cgmath::Vector4<f32> {
+        match self {
+            DiffStatus::Added => ADDED_COLOR,
+            DiffStatus::Removed => REMOVED_COLOR,
+            DiffStatus::Unchanged => UNCHANGED_COLOR,
+        }
+    }
+}
+
+/// Union of two snapshots with every node and edge colored by its diff status.
+/// Nodes and edges are matched by node name (see `Graph::node_key`), and
+/// nodes present in both keep their position from `old` so the layout of
+/// what didn't change stays put.
+pub fn diff(old: &Graph, new: &Graph) -> Graph {
+    let mut union = Graph::new();
+    let mut ids: HashMap<String, NodeId> = HashMap::new();
+    let old_keys: HashMap<String, NodeId> = (0..old.nodes.len() as NodeId)
+        .map(|i| (old.node_key(i), i))
+        .collect();
+
+    for i in 0..new.nodes.len() as NodeId {
+        let key = new.node_key(i);
+        let mut node = new.nodes[i as usize].clone();
+        let status = match old_keys.get(&key) {
+            Some(&prev) => {
+                node.position = old.nodes[prev as usize].position;
+                DiffStatus::Unchanged
+            }
+            None => DiffStatus::Added,
+        };
+        ids.insert(key, tag(&mut union, node, status));
+    }
+    for i in 0..old.nodes.len() as NodeId {
+        if let Entry::Vacant(slot) = ids.entry(old.node_key(i)) {
+            let node = old.nodes[i as usize].clone();
+            slot.insert(tag(&mut union, node, DiffStatus::Removed));
+        }
+    }
+
+    let edge_key = |graph: &Graph, e: usize| {
+        let edge = &graph.edges[e];
+        (graph.node_key(edge.a_id), graph.node_key(edge.b_id))
+    };
+    let old_edges: Vec<_> = (0..old.edges.len()).map(|e| edge_key(old, e)).collect();
+    let new_edges: Vec<_> = (0..new.edges.len()).map(|e| edge_key(new, e)).collect();
+    let in_old: HashSet<_> = old_edges.iter().collect();
+    let in_new: HashSet<_> = new_edges.iter().collect();
+
+    let edges = new_edges
+        .iter()
+        .zip(new.edges.iter())
+        .map(|(key, edge)| {
+            let status = if in_old.contains(key) {
+                DiffStatus::Unchanged
+            } else {
+                DiffStatus::Added
+            };
+            (key, edge, status)
+        })
+        .chain(
+            old_edges
+                .iter()
+                .zip(old.edges.iter())
+                .filter(|(key, _)| !in_new.contains(key))
+                .map(|(key, edge)| (key, edge, DiffStatus::Removed)),
+        );
+    for ((a, b), edge, status) in edges {
+        let id = union.add_edge(ids[a], ids[b]) as usize;
+        let added = &mut union.edges[id];
+        added.attributes = edge.attributes.clone();
+        added.line_width = edge.line_width;
+        added.color = status.color();
+        added
+            .attributes
+            .insert(DIFF_ATTR.into(), AttrValue::from(status.as_str()));
+    }
+
+    union
+}
+
+fn tag(graph: &mut Graph, mut node: crate::node::Node, status: DiffStatus) -> NodeId {
+    node.color = status.color();
+    node.attributes
+        .insert(DIFF_ATTR.into(), AttrValue::from(status.as_str()));
+    graph.add_node(node)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn graph(edges: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new();
+        for (a, b) in edges {
+            let a = graph.node_or_insert(a);
+            let b = graph.node_or_insert(b);
+            graph.add_edge(a, b);
+        }
+        graph
+    }
+
+    #[test]
+    fn union_tags_what_changed() {
+        let mut old = graph(&[("a", "b"), ("b", "c")]);
+        old.nodes[1].position = cgmath::vec3(10.0, 20.0, 0.0);
+        let new = graph(&[("a", "b"), ("b", "d")]);
+        let union = diff(&old, &new);
+
+        let status = |attributes: &crate::attributes::Attributes| {
+            attributes[DIFF_ATTR].as_text().unwrap().to_string()
+        };
+        let nodes: Vec<_> = (0..union.nodes.len() as NodeId)
+            .map(|i| {
+                (
+                    union.node_key(i),
+                    status(&union.nodes[i as usize].attributes),
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("a".into(), "unchanged".into()),
+                ("b".into(), "unchanged".into()),
+                ("d".into(), "added".into()),
+                ("c".into(), "removed".into()),
+            ]
+        );
+        // Kept nodes stay where they were
+        let b = union.node_by_name("b").unwrap();
+        assert_eq!(union.nodes[b as usize].position, old.nodes[1].position);
+        assert_eq!(union.nodes[b as usize].color, UNCHANGED_COLOR);
+
+        let edges: Vec<_> = union
+            .edges
+            .iter()
+            .map(|e| {
+                (
+                    union.node_key(e.a_id),
+                    union.node_key(e.b_id),
+                    status(&e.attributes),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("a".into(), "b".into(), "unchanged".into()),
+                ("b".into(), "d".into(), "added".into()),
+                ("b".into(), "c".into(), "removed".into()),
+            ]
+        );
+        union.check().unwrap();
+    }
+}
@@ -1,23 +1,143 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
+#[cfg(feature = "render")]
 use bytemuck::{Pod, Zeroable};
-use cgmath::{vec3, InnerSpace};
-use wgpu::util::DeviceExt;
+use cgmath::{vec2, vec3, InnerSpace, Vector2, Vector3};
 
-use crate::{node::Node, texture::Texture, Vertex, SAMPLE_COUNT};
+use crate::{attributes::Attributes, node::Node};
+#[cfg(feature = "render")]
+use crate::{
+    instancing::{self, InstanceBuffer, Quad},
+    node::DIMMED_ALPHA,
+    physics::Region,
+    shader, RenderSettings, Vertex,
+};
 
+/// Edges the instance buffer starts out with room for, it doubles from there
+#[cfg(feature = "render")]
 pub const DEFAULT_INSTANCE_BUFFER_CAP: usize = 1024;
+pub const DEFAULT_EDGE_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.0, 1.0, 0.0, 1.0);
+pub const DEFAULT_LINE_WIDTH: f32 = 10.0;
+/// `Edge::strength` of edges that haven't been given one
+pub const DEFAULT_EDGE_STRENGTH: f32 = 1.0;
+/// Weakest spring `weigh_springs` gives an edge, as a multiple of the usual
+pub const MIN_SPRING: f32 = 0.25;
+/// Strongest spring `weigh_springs` gives an edge
+pub const MAX_SPRING: f32 = 4.0;
+/// How far apart neighbouring parallel edges bow out at their middle, as a
+/// share of their length
+pub const PARALLEL_BEND: f32 = 0.2;
+/// Straight pieces a curved edge is measured along when picking it
+const PICK_SEGMENTS: usize = 16;
+/// Length and width of an end marker, in multiples of the edge's line width
+pub const MARKER_SCALE: f32 = 3.0;
 
+/// Shape drawn at one end of an edge, just outside the node's disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Marker {
+    #[default]
+    None,
+    /// Triangle pointing at the node
+    Arrow,
+    Circle,
+    Diamond,
+    /// Bar across the line
+    Tee,
+}
+
+impl Marker {
+    pub const ALL: [Marker; 5] = [
+        Marker::None,
+        Marker::Arrow,
+        Marker::Circle,
+        Marker::Diamond,
+        Marker::Tee,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Marker::None => "none",
+            Marker::Arrow => "arrow",
+            Marker::Circle => "circle",
+            Marker::Diamond => "diamond",
+            Marker::Tee => "tee",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|marker| marker.name() == name)
+    }
+
+    /// The marker closest to a Graphviz arrow type such as `normal`, `odot`
+    /// or `lteetee`. Open and half shapes are drawn whole and only the first
+    /// of several shapes counts, `None` for shapes with no close match.
+    pub fn from_dot(arrow: &str) -> Option<Self> {
+        let shape = arrow.trim_start_matches(['o', 'l', 'r']);
+        [
+            ("none", Marker::None),
+            ("normal", Marker::Arrow),
+            ("vee", Marker::Arrow),
+            ("dot", Marker::Circle),
+            ("diamond", Marker::Diamond),
+            ("tee", Marker::Tee),
+        ]
+        .into_iter()
+        .find(|(name, _)| shape.starts_with(name))
+        .map(|(_, marker)| marker)
+    }
+
+    /// Graphviz arrow type drawing the same shape
+    pub fn dot_name(self) -> &'static str {
+        match self {
+            Marker::Arrow => "normal",
+            Marker::Circle => "dot",
+            marker => marker.name(),
+        }
+    }
+}
+
+#[cfg(feature = "render")]
 pub struct EdgeRenderPass {
     pub edges: Vec<Edge>,
     /// Maps node idx to edge indices
     pub edge_map: BTreeMap<u32, Vec<u32>>,
-    pub pipeline: wgpu::RenderPipeline,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub instance_buffer: wgpu::Buffer,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    /// Draws the end markers over the same instances
+    pub(crate) marker_pipeline: wgpu::RenderPipeline,
+    /// Draws the same instances as 1 pixel lines over `line`, see `thin`
+    pub(crate) line_pipeline: wgpu::RenderPipeline,
+    pub(crate) quad: Quad,
+    /// A quad for each end of an edge
+    pub(crate) marker_quads: Quad,
+    pub(crate) line: Quad,
+    pub(crate) instance_buffer: InstanceBuffer,
+    /// More instances than the buffer holds as of the last `write`
+    overflowed: bool,
+    /// Where each edge sits in the instance buffer, sorted like the nodes'
+    slots: Vec<u32>,
+    /// Edges in the instance buffer as of the last `write`, see
+    /// `NodeRenderPass::view`
+    drawn: usize,
+    /// How far each edge curves away from its parallels as of the last
+    /// `write`, see `parallel_offsets`
+    bends: Vec<f32>,
+    /// When set, edges not in the set are drawn dimmed
+    pub highlight: Option<BTreeSet<u32>>,
+    /// Edges in this set are not drawn
+    pub hidden: BTreeSet<u32>,
+    /// Edges in this set are drawn brightened, such as the ones touching
+    /// the node under the cursor
+    pub glowing: BTreeSet<u32>,
+    /// Set while the graph is past its soft limits, which leaves markers out
+    pub degraded: bool,
+    /// When set, only edges that may cross it are uploaded and drawn
+    pub view: Option<Region>,
+    /// Set while zoomed out past `State::lod_zoom`, which draws edges as 1
+    /// pixel lines without markers instead of ribbons
+    pub thin: bool,
 }
 
+#[derive(Debug, Clone)]
 pub struct Edge {
     pub a_id: u32,
     pub b_id: u32,
@@ -26,51 +146,181 @@ pub struct Edge {
 
     pub color: cgmath::Vector4<f32>,
     pub line_width: f32,
+    /// Marker at `b`
+    pub head: Marker,
+    /// Marker at `a`
+    pub tail: Marker,
+    /// Radii of the disks at either end, which markers sit just outside of.
+    /// Kept up to date by `fit_markers`.
+    pub a_radius: f32,
+    pub b_radius: f32,
+    /// Distance the link force pulls the two nodes to, the current
+    /// `ForceParams::link_distance` when unset
+    pub rest_length: Option<f32>,
+    /// How hard the link force pulls, as a multiple of what every edge gets
+    pub strength: f32,
+    pub attributes: Attributes,
 }
 
+#[cfg(feature = "render")]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C)]
-pub struct EdgeRaw {
+pub(crate) struct EdgeRaw {
     pub color: [f32; 4],
     pub a: [f32; 3],
     pub b: [f32; 3],
-    pub a_norm: [f32; 3],
-    pub b_norm: [f32; 3],
+    pub control: [f32; 3],
     pub line_width: f32,
+    /// `Marker`s at `a` and `b`, numbered in declaration order
+    pub markers: [u32; 2],
+    pub radii: [f32; 2],
+    /// 1 brightens the edge
+    pub glow: f32,
 }
 
+#[cfg(feature = "render")]
 impl EdgeRenderPass {
-    const INDICES: &[u16] = &[0, 1, 3, 3, 1, 2];
-    const VERTICES: &[Vertex] = &[
-        Vertex {
-            // vertex a, index = 0
-            position: [-1.0, -1.0],
-        },
-        Vertex {
-            // vertex b, index = 1
-            position: [1.0, -1.0],
-        },
-        Vertex {
-            // vertex c, index = 2
-            position: [1.0, 1.0],
-        },
-        Vertex {
-            // vertex d, index = 3
-            position: [-1.0, 1.0],
-        },
-    ];
-
     pub fn new(
         edges: Vec<Edge>,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
         camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
     ) -> Self {
-        let edges = edges;
+        let source = shader::builtin(include_str!("edge.wgsl"));
+        let pipeline =
+            Self::create_pipeline(device, format, camera_bind_group_layout, settings, &source);
+        let marker_pipeline = Self::create_marker_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            &source,
+        );
+        let line_pipeline =
+            Self::create_line_pipeline(device, format, camera_bind_group_layout, settings, &source);
+
+        let quad = Quad::ribbon(device, "Edge");
+        let marker_quads = Quad::pair(device, "Edge Marker");
+        let line = Quad::line(device, "Edge Line");
+
+        let instance_buffer = InstanceBuffer::new(
+            device,
+            "Edge",
+            std::mem::size_of::<EdgeRaw>(),
+            DEFAULT_INSTANCE_BUFFER_CAP,
+        );
+
+        let mut node_to_edge = BTreeMap::new();
+
+        for (i, edge) in edges.iter().enumerate() {
+            node_to_edge
+                .entry(edge.a_id)
+                .or_insert_with(Vec::new)
+                .push(i as u32);
+            node_to_edge
+                .entry(edge.b_id)
+                .or_insert_with(Vec::new)
+                .push(i as u32);
+        }
+
+        let mut pass = Self {
+            pipeline,
+            marker_pipeline,
+            line_pipeline,
+            quad,
+            marker_quads,
+            line,
+            instance_buffer,
+            overflowed: false,
+            slots: vec![],
+            drawn: 0,
+            bends: vec![],
+            edge_map: node_to_edge,
+            edges,
+            highlight: None,
+            hidden: BTreeSet::new(),
+            glowing: BTreeSet::new(),
+            degraded: false,
+            view: None,
+            thin: false,
+        };
+        pass.write(device, queue);
+        pass
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `edge.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        Self::pipeline_with(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            source,
+            ("vs_main", "fs_main"),
+            wgpu::PrimitiveTopology::TriangleList,
+        )
+    }
+
+    /// Like `create_pipeline`, for the markers drawn over `marker_quads`
+    pub(crate) fn create_marker_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        Self::pipeline_with(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            source,
+            ("vs_marker", "fs_marker"),
+            wgpu::PrimitiveTopology::TriangleList,
+        )
+    }
+
+    /// Like `create_pipeline`, drawing the middle of each ribbon as lines
+    /// over `line`. Lines are a pixel wide whatever the zoom.
+    pub(crate) fn create_line_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        Self::pipeline_with(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            source,
+            ("vs_main", "fs_main"),
+            wgpu::PrimitiveTopology::LineList,
+        )
+    }
+
+    fn pipeline_with(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+        (vertex, fragment): (&str, &str),
+        topology: wgpu::PrimitiveTopology,
+    ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Edge Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("edge.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -79,17 +329,17 @@ impl EdgeRenderPass {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Edge Render Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
-                entry_point: "vs_main",
+                entry_point: vertex,
                 buffers: &[Vertex::desc(), EdgeRaw::desc()],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: "fs_main",
+                entry_point: fragment,
                 targets: &[Some(wgpu::ColorTargetState {
                     format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
@@ -105,7 +355,7 @@ impl EdgeRenderPass {
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology,
                 strip_index_format: None,
                 // front_face: wgpu::FrontFace::Cw,
                 cull_mode: None,
@@ -113,94 +363,95 @@ impl EdgeRenderPass {
                 ..Default::default()
             },
             // depth_stencil: None,
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: SAMPLE_COUNT as u32,
-                ..Default::default()
-            },
+            depth_stencil: settings.depth_stencil(wgpu::CompareFunction::LessEqual),
+            multisample: settings.multisample(),
             multiview: None,
-        });
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Edge Vertex Buffer"),
-            contents: bytemuck::cast_slice(Self::VERTICES),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: "Edge Index Buffer".into(),
-            contents: bytemuck::cast_slice(Self::INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Node Instance Buffer"),
-            size: (std::mem::size_of::<EdgeRaw>() * DEFAULT_INSTANCE_BUFFER_CAP) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        queue.write_buffer(
-            &instance_buffer,
-            0,
-            bytemuck::cast_slice(&edges.iter().map(Edge::to_instance).collect::<Vec<_>>()),
-        );
-
-        let mut node_to_edge = BTreeMap::new();
+        })
+    }
 
-        for (i, edge) in edges.iter().enumerate() {
-            node_to_edge
-                .entry(edge.a_id)
-                .or_insert_with(Vec::new)
-                .push(i as u32);
-            node_to_edge
-                .entry(edge.b_id)
-                .or_insert_with(Vec::new)
-                .push(i as u32);
+    fn instance(&self, idx: usize) -> EdgeRaw {
+        let bend = self.bends.get(idx).copied().unwrap_or_default();
+        let mut raw = self.edges[idx].to_instance(bend);
+        if self.glowing.contains(&(idx as u32)) {
+            raw.glow = 1.0;
         }
-
-        Self {
-            pipeline,
-            vertex_buffer,
-            index_buffer,
-            instance_buffer,
-            edge_map: node_to_edge,
-            edges,
+        if self.degraded || self.thin {
+            // Lines run all the way to the disks without markers to stop at
+            raw.markers = [0, 0];
+        }
+        if self.hidden.contains(&(idx as u32)) {
+            raw.color[3] = 0.0;
+        } else if let Some(highlight) = &self.highlight {
+            if !highlight.contains(&(idx as u32)) {
+                raw.color[3] *= DIMMED_ALPHA;
+            }
         }
+        raw
     }
 
-    pub fn write(&mut self, queue: &wgpu::Queue) {
+    fn instance_count(&self) -> u32 {
+        instancing::instance_count(self.drawn, self.instance_buffer.capacity())
+    }
+
+    /// Uploads every edge that may cross `view`, packed together at the
+    /// start of the instance buffer
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.bends = parallel_offsets(&self.edges);
+        let mut order = instancing::back_to_front(
+            self.edges
+                .iter()
+                .map(|edge| (edge.a_center.z + edge.b_center.z) / 2.0),
+        );
+        if let Some(view) = &self.view {
+            order.retain(|i| {
+                let (min, max) = self.edges[*i as usize].bounds(self.bends[*i as usize]);
+                view.overlaps(min, max)
+            });
+        }
+        self.drawn = order.len();
+        self.instance_buffer.reserve(device, queue, self.drawn);
+        instancing::warn_overflow(
+            self.drawn,
+            self.instance_buffer.capacity(),
+            "edges",
+            &mut self.overflowed,
+        );
         queue.write_buffer(
-            &self.instance_buffer,
+            self.instance_buffer.buffer(),
             0,
-            bytemuck::cast_slice(&self.edges.iter().map(Edge::to_instance).collect::<Vec<_>>()),
+            bytemuck::cast_slice(
+                &order[..self.instance_count() as usize]
+                    .iter()
+                    .map(|i| self.instance(*i as usize))
+                    .collect::<Vec<_>>(),
+            ),
         );
+        self.slots = instancing::slots(&order, self.edges.len());
     }
 
-    pub fn add_edge(&mut self, edge: Edge, queue: &wgpu::Queue) {
-        let raw = edge.to_instance();
+    pub fn add_edge(&mut self, edge: Edge, device: &wgpu::Device, queue: &wgpu::Queue) {
         let idx = self.edges.len();
-        self.edge_map
-            .entry(edge.a_id)
-            .or_insert_with(Vec::new)
-            .push(idx as u32);
-        self.edge_map
-            .entry(edge.b_id)
-            .or_insert_with(Vec::new)
-            .push(idx as u32);
+        self.edge_map.entry(edge.a_id).or_default().push(idx as u32);
+        self.edge_map.entry(edge.b_id).or_default().push(idx as u32);
         self.edges.push(edge);
+        // Like `NodeRenderPass::add_node`. A parallel edge is drawn straight
+        // until the next `write` fans it out.
+        self.instance_buffer
+            .reserve(device, queue, self.edges.len());
+        let slot = self.drawn;
+        if self.slots.len() != idx || slot >= self.instance_buffer.capacity() {
+            return;
+        }
+        self.slots.push(slot as u32);
+        self.drawn += 1;
         queue.write_buffer(
-            &self.instance_buffer,
-            (idx * std::mem::size_of::<EdgeRaw>()) as u64,
-            bytemuck::cast_slice(&[raw]),
+            self.instance_buffer.buffer(),
+            (slot * std::mem::size_of::<EdgeRaw>()) as u64,
+            bytemuck::cast_slice(&[self.instance(idx)]),
         )
     }
 
-    pub fn render<'a, 'b>(
+    pub fn render<'a>(
         &'a self,
         camera_bind_group: &'a wgpu::BindGroup,
         render_pass: &mut wgpu::RenderPass<'a>,
@@ -209,12 +460,35 @@ impl EdgeRenderPass {
             return;
         }
 
-        render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, camera_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..Self::INDICES.len() as u32, 0, 0..self.edges.len() as u32);
+        if self.thin {
+            render_pass.set_pipeline(&self.line_pipeline);
+            self.line.draw(
+                render_pass,
+                self.instance_buffer.buffer(),
+                std::mem::size_of::<EdgeRaw>(),
+                self.instance_count(),
+            );
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        self.quad.draw(
+            render_pass,
+            self.instance_buffer.buffer(),
+            std::mem::size_of::<EdgeRaw>(),
+            self.instance_count(),
+        );
+        if self.degraded {
+            return;
+        }
+        // Edges without markers collapse theirs to nothing in the shader
+        render_pass.set_pipeline(&self.marker_pipeline);
+        self.marker_quads.draw(
+            render_pass,
+            self.instance_buffer.buffer(),
+            std::mem::size_of::<EdgeRaw>(),
+            self.instance_count(),
+        );
     }
 }
 
@@ -232,35 +506,206 @@ impl Edge {
             b_center: b.position,
             color,
             line_width,
+            head: Marker::None,
+            tail: Marker::None,
+            a_radius: a.radius(),
+            b_radius: b.radius(),
+            rest_length: None,
+            strength: DEFAULT_EDGE_STRENGTH,
+            attributes: Attributes::new(),
+        }
+    }
+
+    /// The edge with its own spring, for weighing it against the others
+    pub fn with_spring(mut self, rest_length: Option<f32>, strength: f32) -> Self {
+        self.rest_length = rest_length;
+        self.strength = strength;
+        self
+    }
+
+    /// The end opposite `node`, which is assumed to be one of the two
+    pub fn other(&self, node: u32) -> u32 {
+        if node == self.a_id {
+            self.b_id
+        } else {
+            self.a_id
         }
     }
 
-    pub fn to_instance(&self) -> EdgeRaw {
-        let dx = self.b_center.x - self.a_center.x;
-        let dy = self.b_center.y - self.a_center.y;
+    /// Box around the edge as drawn with `bend`, its line and markers. The
+    /// curve never leaves the triangle of its ends and control point.
+    pub fn bounds(&self, bend: f32) -> (Vector2<f32>, Vector2<f32>) {
+        let control = self.control_point(bend).truncate();
+        let (a, b) = (self.a_center.truncate(), self.b_center.truncate());
+        let reach = self.line_width * MARKER_SCALE;
+        let min = vec2(a.x.min(b.x).min(control.x), a.y.min(b.y).min(control.y));
+        let max = vec2(a.x.max(b.x).max(control.x), a.y.max(b.y).max(control.y));
+        (min - vec2(reach, reach), max + vec2(reach, reach))
+    }
+
+    /// Control point of the quadratic bezier the edge is drawn as. It sits
+    /// `bend` times `PARALLEL_BEND` of the edge's length off its midpoint,
+    /// to the left going from the lower node id to the higher one, so
+    /// parallel edges bend the same way whichever direction they point.
+    pub fn control_point(&self, bend: f32) -> Vector3<f32> {
+        let mid = (self.a_center + self.b_center) / 2.0;
+        let (from, to) = if self.a_id <= self.b_id {
+            (self.a_center, self.b_center)
+        } else {
+            (self.b_center, self.a_center)
+        };
+        let d = to - from;
+        if bend == 0.0 || d.x == 0.0 && d.y == 0.0 {
+            return mid;
+        }
+        let left = vec3(-d.y, d.x, 0.0);
+        mid + left * bend * PARALLEL_BEND
+    }
+
+    /// Point `t` of the way along the edge as drawn with `bend`, from `a` at
+    /// 0 to `b` at 1
+    pub fn point_at(&self, t: f32, bend: f32) -> Vector2<f32> {
+        let (a, b) = (self.a_center.truncate(), self.b_center.truncate());
+        let c = self.control_point(bend).truncate();
+        let s = 1.0 - t;
+        a * s * s + c * 2.0 * s * t + b * t * t
+    }
 
-        let a_norm = vec3(-dy, dx, 0.0).normalize();
-        let b_norm = vec3(dy, -dx, 0.0).normalize();
+    /// Distance from `pos` to the line drawn for the edge with `bend`, and
+    /// how far along it the closest point is, from 0 at `a` to 1 at `b`
+    pub fn distance(&self, pos: Vector2<f32>, bend: f32) -> (f32, f32) {
+        let segments = if bend == 0.0 { 1 } else { PICK_SEGMENTS };
+        let mut nearest = (f32::INFINITY, 0.0);
+        for i in 0..segments {
+            let (t0, t1) = (i as f32 / segments as f32, (i + 1) as f32 / segments as f32);
+            let (distance, along) =
+                segment_distance(pos, self.point_at(t0, bend), self.point_at(t1, bend));
+            if distance < nearest.0 {
+                nearest = (distance, t0 + along * (t1 - t0));
+            }
+        }
+        nearest
+    }
 
+    #[cfg(feature = "render")]
+    pub(crate) fn to_instance(&self, bend: f32) -> EdgeRaw {
         EdgeRaw {
             color: self.color.into(),
             a: self.a_center.into(),
             b: self.b_center.into(),
-            a_norm: a_norm.into(),
-            b_norm: b_norm.into(),
+            control: self.control_point(bend).into(),
             line_width: self.line_width,
+            markers: [self.tail as u32, self.head as u32],
+            radii: [self.a_radius, self.b_radius],
+            glow: 0.0,
         }
     }
 }
 
+/// Distance from `pos` to the segment from `a` to `b`, and how far along it
+/// the closest point is, from 0 to 1
+fn segment_distance(pos: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> (f32, f32) {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    let t = if length2 == 0.0 {
+        0.0
+    } else {
+        ((pos - a).dot(ab) / length2).clamp(0.0, 1.0)
+    };
+    ((pos - (a + ab * t)).magnitude(), t)
+}
+
+/// The edge under the world position `pos`, skipping `hidden` ones, and how
+/// far along it `pos` is from 0 at `a` to 1 at `b`. Lines are drawn
+/// `line_width` out to either side, an edge counts when `pos` is within that
+/// plus `tolerance` of it and the closest one wins. Parallel edges are
+/// measured along their curves, see `parallel_offsets`.
+pub fn pick(
+    edges: &[Edge],
+    pos: Vector2<f32>,
+    tolerance: f32,
+    hidden: &BTreeSet<u32>,
+) -> Option<(u32, f32)> {
+    let bends = parallel_offsets(edges);
+    let mut nearest: Option<(u32, f32, f32)> = None;
+    for (i, edge) in edges.iter().enumerate() {
+        if hidden.contains(&(i as u32)) {
+            continue;
+        }
+        let (distance, along) = edge.distance(pos, bends[i]);
+        if distance <= edge.line_width + tolerance
+            && nearest.is_none_or(|(_, best, _)| distance < best)
+        {
+            nearest = Some((i as u32, distance, along));
+        }
+    }
+    nearest.map(|(i, _, along)| (i, along))
+}
+
+/// Takes up the current sizes of the nodes at either end of every edge, for
+/// markers to sit just outside them
+pub fn fit_markers(edges: &mut [Edge], nodes: &[Node]) {
+    for edge in edges {
+        let radius = |id: u32| nodes.get(id as usize).map_or(0.0, Node::radius);
+        (edge.a_radius, edge.b_radius) = (radius(edge.a_id), radius(edge.b_id));
+    }
+}
+
+/// Springs every edge with a positive number under `attr` in proportion to
+/// its weight over the mean of theirs: twice the mean pulls twice as hard, to
+/// half of `link_distance`. Returns how many edges have the weight.
+pub fn weigh_springs(edges: &mut [&mut Edge], attr: &str, link_distance: f32) -> usize {
+    let weight = |edge: &Edge| {
+        edge.attributes
+            .get(attr)
+            .and_then(|value| value.as_number())
+            .filter(|weight| *weight > 0.0)
+    };
+    let weights: Vec<f32> = edges.iter().filter_map(|edge| weight(edge)).collect();
+    if weights.is_empty() {
+        return 0;
+    }
+    let mean = weights.iter().sum::<f32>() / weights.len() as f32;
+    for edge in edges.iter_mut() {
+        if let Some(weight) = weight(edge) {
+            let ratio = (weight / mean).clamp(MIN_SPRING, MAX_SPRING);
+            edge.strength = ratio;
+            edge.rest_length = Some(link_distance / ratio);
+        }
+    }
+    weights.len()
+}
+
+/// How far each edge curves away from the straight line between its nodes,
+/// in multiples of `PARALLEL_BEND`. Edges sharing the same two nodes, in
+/// either direction, fan out evenly around it; a lone edge stays straight.
+pub fn parallel_offsets(edges: &[Edge]) -> Vec<f32> {
+    let mut pairs: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        let pair = (edge.a_id.min(edge.b_id), edge.a_id.max(edge.b_id));
+        pairs.entry(pair).or_default().push(i);
+    }
+    let mut offsets = vec![0.0; edges.len()];
+    for group in pairs.values().filter(|group| group.len() > 1) {
+        let middle = (group.len() - 1) as f32 / 2.0;
+        for (nth, i) in group.iter().enumerate() {
+            offsets[*i] = nth as f32 - middle;
+        }
+    }
+    offsets
+}
+
+#[cfg(feature = "render")]
 impl EdgeRaw {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+    const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
         1 => Float32x4,
         2 => Float32x3,
         3 => Float32x3,
         4 => Float32x3,
-        5 => Float32x3,
-        6 => Float32,
+        5 => Float32,
+        6 => Uint32x2,
+        7 => Float32x2,
+        8 => Float32,
     ];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -271,3 +716,117 @@ impl EdgeRaw {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edge(a_id: u32, b_id: u32) -> Edge {
+        let node = |id: u32| {
+            Node::new(
+                (10.0, 10.0),
+                (id as f32 * 100.0, 0.0, 0.0),
+                cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                (1.0, 1.0, 1.0, 1.0),
+            )
+        };
+        Edge::from_nodes(
+            (&node(a_id), a_id),
+            (&node(b_id), b_id),
+            DEFAULT_EDGE_COLOR,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn parallel_edges_fan_out() {
+        let edges = [edge(0, 1), edge(1, 2), edge(1, 0), edge(0, 1)];
+        assert_eq!(parallel_offsets(&edges), [-1.0, 0.0, 0.0, 1.0]);
+        let edges = [edge(0, 1), edge(1, 0)];
+        assert_eq!(parallel_offsets(&edges), [-0.5, 0.5]);
+    }
+
+    #[test]
+    fn picks_the_nearest_curve() {
+        let hidden = BTreeSet::new();
+        let edges = [edge(0, 1), edge(1, 2), edge(1, 0)];
+        let picked = |x, y, tolerance, hidden: &BTreeSet<u32>| {
+            pick(&edges, cgmath::vec2(x, y), tolerance, hidden)
+                .map(|(edge, along)| (edge, (along * 100.0).round() / 100.0))
+        };
+        // The parallels bow 5 units out either side of the straight line
+        assert_eq!(picked(50.0, 0.0, 0.0, &hidden), None);
+        assert_eq!(picked(50.0, 5.5, 0.0, &hidden), Some((2, 0.5)));
+        assert_eq!(picked(50.0, -5.8, 0.0, &hidden), Some((0, 0.5)));
+        assert_eq!(picked(175.0, 1.0, 0.0, &hidden), Some((1, 0.75)));
+        assert_eq!(picked(175.0, 1.5, 0.0, &hidden), None);
+        assert_eq!(picked(175.0, 1.5, 1.0, &hidden), Some((1, 0.75)));
+        assert_eq!(picked(175.0, 1.0, 0.0, &BTreeSet::from([1])), None);
+    }
+
+    #[test]
+    fn bends_ignore_direction() {
+        let (ab, ba) = (edge(0, 1), edge(1, 0));
+        assert_eq!(ab.control_point(0.0), cgmath::vec3(50.0, 0.0, 0.0));
+        assert_eq!(ab.control_point(1.0), ba.control_point(1.0));
+        assert_eq!(ab.control_point(1.0), cgmath::vec3(50.0, 20.0, 0.0));
+        assert_eq!(ab.control_point(-1.0), cgmath::vec3(50.0, -20.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_hold_the_whole_curve() {
+        let edge = edge(0, 2);
+        for bend in [0.0, 1.0, -2.5] {
+            let (min, max) = edge.bounds(bend);
+            for i in 0..=PICK_SEGMENTS {
+                let p = edge.point_at(i as f32 / PICK_SEGMENTS as f32, bend);
+                assert!(p.x >= min.x && p.y >= min.y, "{p:?} below {min:?}");
+                assert!(p.x <= max.x && p.y <= max.y, "{p:?} above {max:?}");
+            }
+        }
+        // Markers reach past the ends
+        let (min, max) = edge.bounds(0.0);
+        assert_eq!((min.x, max.x), (-MARKER_SCALE, 200.0 + MARKER_SCALE));
+    }
+
+    #[test]
+    fn heavier_edges_get_shorter_stronger_springs() {
+        let mut edges = [edge(0, 1), edge(1, 2), edge(2, 3), edge(3, 4)];
+        for (edge, weight) in edges.iter_mut().zip([1.0, 3.0, 100.0, -1.0]) {
+            edge.attributes.insert("w".into(), weight.into());
+        }
+        let mut weighed: Vec<&mut Edge> = edges.iter_mut().take(2).collect();
+        assert_eq!(weigh_springs(&mut weighed, "w", 100.0), 2);
+        assert_eq!(weigh_springs(&mut weighed, "missing", 100.0), 0);
+        let springs: Vec<_> = edges.iter().map(|e| (e.rest_length, e.strength)).collect();
+        assert_eq!(
+            springs,
+            [
+                (Some(200.0), 0.5),
+                (Some(200.0 / 3.0), 1.5),
+                (None, DEFAULT_EDGE_STRENGTH),
+                (None, DEFAULT_EDGE_STRENGTH),
+            ]
+        );
+
+        let mut all: Vec<&mut Edge> = edges.iter_mut().collect();
+        assert_eq!(weigh_springs(&mut all, "w", 100.0), 3);
+        // Far lighter than the mean, which the heavy one drags up
+        assert_eq!(edges[0].strength, MIN_SPRING);
+        assert_eq!(edges[0].rest_length, Some(400.0));
+        assert_eq!(edges[3].rest_length, None);
+    }
+
+    #[test]
+    fn markers_read_graphviz_arrows() {
+        for marker in Marker::ALL {
+            assert_eq!(Marker::parse(marker.name()), Some(marker));
+            assert_eq!(Marker::from_dot(marker.dot_name()), Some(marker));
+        }
+        assert_eq!(Marker::from_dot("odot"), Some(Marker::Circle));
+        assert_eq!(Marker::from_dot("lteetee"), Some(Marker::Tee));
+        assert_eq!(Marker::from_dot("vee"), Some(Marker::Arrow));
+        assert_eq!(Marker::from_dot("crow"), None);
+        assert_eq!(Marker::parse("normal"), None);
+    }
+}
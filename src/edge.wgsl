@@ -7,71 +7,78 @@ struct Camera {
 @binding(0) @group(0) var<uniform> camera: Camera;
 
 struct VertexInput {
-    @location(0) pos: vec3<f32>,
+    // Distance along the edge from 0 to 1, and which side of it
+    @location(0) pos: vec2<f32>,
 };
 
 struct Edge {
     @location(1) color: vec4<f32>,
     @location(2) a: vec3<f32>,
     @location(3) b: vec3<f32>,
-    @location(4) a_norm: vec3<f32>,
-    @location(5) b_norm: vec3<f32>,
-    @location(6) line_width: f32,
+    // Quadratic bezier control point, the midpoint for a straight edge
+    @location(4) control: vec3<f32>,
+    @location(5) line_width: f32,
+    // Marker at a and b: 0 none, 1 arrow, 2 circle, 3 diamond, 4 tee
+    @location(6) markers: vec2<u32>,
+    // Radii of the disks at a and b, markers sit just outside them
+    @location(7) radii: vec2<f32>,
+    // 1 brightens the edge
+    @location(8) glow: f32,
 }
 
+// EDGE_DEPTH, behind every node, and MARKER_SCALE, marker length and width
+// in line widths
+#include "constants.wgsl"
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
 };
 
-@vertex
-fn vs_main(in: VertexInput, edge: Edge, @builtin(vertex_index) vertex_index: u32) -> VertexOutput {
-    var pos: vec3<f32>;
-    var norm: vec3<f32>;
-
-    switch vertex_index {
-        case 0u {
-            pos = edge.a;
-            norm = edge.a_norm;
-        }
-        case 1u {
-            pos = edge.a;
-            norm = edge.a_norm * -1.0;
-        }
-        case 2u {
-            pos = edge.b;
-            norm = edge.b_norm;
-        }
-        case 3u {
-            pos = edge.b;
-            norm = edge.b_norm * -1.0;
-        }
-        case 4u {
-            pos = edge.b;
-            norm = edge.b_norm;
-        }
-        default: {
-            pos = edge.a;
-            norm = edge.a_norm;
-        }
-    }
+#include "color.wgsl"
+
+#include "glow.wgsl"
 
-    // let line_width = vec3<f32>(1.0, 600.0/800.0, 1.0);
-    // let norm = camera.view_proj * vec4<f32>(norm.xy, 0.0, 0.0);
+fn bezier(edge: Edge, t: f32) -> vec2<f32> {
+    let s = 1.0 - t;
+    return s * s * edge.a.xy + 2.0 * s * t * edge.control.xy + t * t * edge.b.xy;
+}
 
-    let delta = vec4<f32>(norm.xyz * edge.line_width, 1.0);
-    // let delta = vec4<f32>(norm.xyz * line_width, 1.0);
+fn bezier_tangent(edge: Edge, t: f32) -> vec2<f32> {
+    return 2.0 * (1.0 - t) * (edge.control.xy - edge.a.xy) + 2.0 * t * (edge.b.xy - edge.control.xy);
+}
 
-    // let delta = vec4<f32>(delta.x, .y * (600.0/800.0), norm.z, norm.w);
+// Share of the edge's length its end at a or b leaves out: the disk and the
+// marker, when there is one. Measured along the straight line, which is
+// close enough for the gentle curves of parallel edges.
+fn trim(edge: Edge, marker: u32, radius: f32) -> f32 {
+    let chord = length(edge.b.xy - edge.a.xy);
+    if (marker == 0u || chord == 0.0) {
+        return 0.0;
+    }
+    return (radius + MARKER_SCALE * edge.line_width) / chord;
+}
 
-    let pos = camera.view_proj * vec4<f32>(pos.xy + delta.xy, 0.0, 1.0);
-    // let pos = vec4<f32>(pos.xy + delta.xy, 0.1, 1.0);
-    // let pos = vec4<f32>(pos.xy, max(edge.a.z, edge.b.z), 1.0);
-    let pos = vec4<f32>(pos.xy, 0.1, 1.0);
+@vertex
+fn vs_main(in: VertexInput, edge: Edge) -> VertexOutput {
+    // Stops short of the markers so the line doesn't show around them
+    let start = trim(edge, edge.markers.x, edge.radii.x);
+    let end = max(start, 1.0 - trim(edge, edge.markers.y, edge.radii.y));
+    let t = mix(start, end, in.pos.x);
+    let pos = bezier(edge, t);
+    let tangent = bezier_tangent(edge, t);
+    // Both ends on top of each other leave no direction to widen in
+    var norm = vec2<f32>(0.0, 0.0);
+    if (length(tangent) > 0.0) {
+        norm = normalize(vec2<f32>(-tangent.y, tangent.x));
+    }
+
+    let pos = camera.view_proj * vec4<f32>(pos + norm * in.pos.y * edge.line_width, 0.0, 1.0);
+    let pos = vec4<f32>(pos.xy, EDGE_DEPTH, 1.0);
 
     var out: VertexOutput;
     out.position = pos;
-    out.color = edge.color;
+    out.color = srgb_to_linear(brighten(edge.color, edge.glow));
     return out;
 }
 
@@ -80,3 +87,81 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.color.rgba);
 }
 
+
+struct MarkerOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Position in the marker's box, x running from -1 at its base to 1 at
+    // the tip touching the disk
+    @location(1) local: vec2<f32>,
+    @location(2) @interpolate(flat) marker: u32,
+};
+
+// Vertices 0 to 3 make the marker at a, 4 to 7 the one at b
+@vertex
+fn vs_marker(in: VertexInput, edge: Edge, @builtin(vertex_index) index: u32) -> MarkerOutput {
+    let at_b = index >= 4u;
+    let marker = select(edge.markers.x, edge.markers.y, at_b);
+    let radius = select(edge.radii.x, edge.radii.y, at_b);
+    let chord = length(edge.b.xy - edge.a.xy);
+
+    var out: MarkerOutput;
+    out.color = srgb_to_linear(brighten(edge.color, edge.glow));
+    out.local = in.pos;
+    out.marker = marker;
+    // Without a marker, or room for it, every vertex lands on the same
+    // point and nothing is drawn
+    out.position = vec4<f32>(0.0, 0.0, EDGE_DEPTH, 1.0);
+    if (marker == 0u || chord <= edge.radii.x + edge.radii.y) {
+        return out;
+    }
+
+    let t = select(radius / chord, 1.0 - radius / chord, at_b);
+    let tangent = bezier_tangent(edge, t);
+    if (length(tangent) == 0.0) {
+        return out;
+    }
+    // Pointing into the node
+    let dir = normalize(tangent) * select(-1.0, 1.0, at_b);
+    let norm = vec2<f32>(-dir.y, dir.x);
+    let half_size = MARKER_SCALE * edge.line_width / 2.0;
+    let center = bezier(edge, t) - dir * half_size;
+    let pos = center + (dir * in.pos.x + norm * in.pos.y) * half_size;
+
+    let pos = camera.view_proj * vec4<f32>(pos, 0.0, 1.0);
+    out.position = vec4<f32>(pos.xy, EDGE_DEPTH, 1.0);
+    return out;
+}
+
+// Roughly the signed distance to the marker's outline, in box units
+fn marker_distance(marker: u32, p: vec2<f32>) -> f32 {
+    switch (marker) {
+        // Arrow, its tip on the disk
+        case 1u: {
+            return max(abs(p.y) - (1.0 - p.x) / 2.0, -1.0 - p.x);
+        }
+        case 2u: {
+            return length(p) - 1.0;
+        }
+        case 3u: {
+            return abs(p.x) + abs(p.y) - 1.0;
+        }
+        // Tee, the line carries on up to a bar against the disk
+        case 4u: {
+            let bar = max(abs(p.y) - 1.0, 0.5 - p.x);
+            let stem = abs(p.y) - 2.0 / MARKER_SCALE;
+            return min(bar, stem);
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
+@fragment
+fn fs_marker(in: MarkerOutput) -> @location(0) vec4<f32> {
+    let d = marker_distance(in.marker, in.local);
+    let delta = fwidth(d);
+    let alpha = 1.0 - smoothstep(-delta, delta, d);
+    return vec4<f32>(in.color.rgb, in.color.a * alpha);
+}
@@ -0,0 +1,369 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    num::NonZeroU32,
+    path::Path,
+    sync::mpsc::channel,
+};
+
+use anyhow::{anyhow, bail, Context};
+use cgmath::vec2;
+
+use crate::{camera::Camera, node::Node};
+
+pub const MAX_EXPORT_SCALE: u32 = 8;
+pub const DEFAULT_EXPORT_SCALE: u32 = 4;
+/// Largest offscreen target rendered at once, bigger images are stitched
+/// together from tiles of this size
+pub const DEFAULT_TILE_SIZE: u32 = 2048;
+/// Empty space left around the graph when exporting its full bounds, as a
+/// fraction of the bounds' size
+const GRAPH_MARGIN: f32 = 0.05;
+
+/// Part of the scene an export covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportRegion {
+    /// Exactly what the window shows
+    View,
+    /// Every node, regardless of where the camera is
+    Graph,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Output pixels per window pixel
+    pub scale: u32,
+    /// Output width in pixels, overrides `scale` for poster sized images
+    pub width: Option<u32>,
+    pub region: ExportRegion,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            scale: DEFAULT_EXPORT_SCALE,
+            width: None,
+            region: ExportRegion::View,
+        }
+    }
+}
+
+/// Part of the output image rendered in one pass, in image pixels with the
+/// origin at the top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Camera placement and output size for an export
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportView {
+    pub translate: cgmath::Vector3<f32>,
+    pub scale: f32,
+    pub width: u32,
+    pub height: u32,
+    /// Factor applied to overlay geometry, which is laid out in window pixels
+    pub ui_scale: f32,
+}
+
+impl ExportView {
+    /// The output is always the window's size times `options.scale`, so
+    /// overlays keep their place relative to the image's corners
+    pub fn new(camera: &Camera, nodes: &[Node], options: &ExportOptions) -> Self {
+        let window = vec2(camera.width, camera.height);
+        Self::of_window(window, camera.translate, camera.scale, nodes, options)
+    }
+
+    /// `new` for a camera showing a `window` sized view
+    fn of_window(
+        window: cgmath::Vector2<f32>,
+        camera_translate: cgmath::Vector3<f32>,
+        camera_scale: f32,
+        nodes: &[Node],
+        options: &ExportOptions,
+    ) -> Self {
+        let factor = match options.width {
+            Some(width) => width as f32 / window.x,
+            None => options.scale as f32,
+        };
+        let (translate, scale) = match (options.region, graph_bounds(nodes)) {
+            (ExportRegion::Graph, Some((min, max))) => {
+                let center = (min + max) / 2.0;
+                let extent = (max - min) * (1.0 + GRAPH_MARGIN * 2.0);
+                let fit = (window.x / extent.x.max(1.0)).min(window.y / extent.y.max(1.0));
+                (center * fit * factor, fit * factor)
+            }
+            _ => (camera_translate.truncate() * factor, camera_scale * factor),
+        };
+        Self {
+            translate: translate.extend(camera_translate.z),
+            scale,
+            width: (window.x * factor) as u32,
+            height: (window.y * factor) as u32,
+            ui_scale: factor,
+        }
+    }
+}
+
+impl ExportView {
+    /// Splits the image into rows of tiles no larger than `size`, the last
+    /// row and column are cropped to the image
+    pub fn tiles(&self, size: u32) -> Vec<Vec<Tile>> {
+        (0..self.height)
+            .step_by(size as usize)
+            .map(|y| {
+                (0..self.width)
+                    .step_by(size as usize)
+                    .map(|x| Tile {
+                        x,
+                        y,
+                        width: size.min(self.width - x),
+                        height: size.min(self.height - y),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Translation that makes a camera of `size` pixels show the part of the
+    /// image whose top-left corner is at `tile`. Tiles are always rendered at
+    /// full size and cropped afterwards, so this takes the uncropped size.
+    pub fn tile_translate(&self, tile: &Tile, size: u32) -> cgmath::Vector3<f32> {
+        // Offset of the tile's center from the image's center, y up
+        let center = vec2(
+            tile.x as f32 + size as f32 / 2.0 - self.width as f32 / 2.0,
+            self.height as f32 / 2.0 - (tile.y as f32 + size as f32 / 2.0),
+        );
+        self.translate + center.extend(0.0)
+    }
+}
+
+/// Smallest world space box containing every node, including its size.
+/// Nodes whose position or size isn't finite are left out, and `None` means
+/// there's nothing to contain: the graph is empty or every node is off in
+/// NaN land.
+pub fn graph_bounds(nodes: &[Node]) -> Option<(cgmath::Vector2<f32>, cgmath::Vector2<f32>)> {
+    let finite = |node: &&Node| {
+        let (position, size) = (node.position, node.size);
+        [position.x, position.y, size.x, size.y]
+            .iter()
+            .all(|v| v.is_finite())
+    };
+    nodes.iter().filter(finite).fold(None, |bounds, node| {
+        let min = node.position.truncate() - node.size;
+        let max = node.position.truncate() + node.size;
+        Some(match bounds {
+            None => (min, max),
+            Some((lo, hi)) => (
+                vec2(lo.x.min(min.x), lo.y.min(min.y)),
+                vec2(hi.x.max(max.x), hi.y.max(max.y)),
+            ),
+        })
+    })
+}
+
+/// Copies a rendered texture back to the CPU as tightly packed RGBA8 rows
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> anyhow::Result<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+    let swap_red_blue = match format {
+        Rgba8Unorm | Rgba8UnormSrgb => false,
+        Bgra8Unorm | Bgra8UnormSrgb => true,
+        other => bail!("can't export from a {other:?} surface"),
+    };
+
+    let row = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row = row.div_ceil(align) * align;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Export Readback Buffer"),
+        size: padded_row as u64 * height as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Export Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = tx.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    rx.recv()
+        .map_err(|_| anyhow!("readback was dropped"))?
+        .context("mapping readback buffer")?;
+
+    let mut pixels = Vec::with_capacity((row * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for padded in data.chunks(padded_row as usize) {
+            pixels.extend_from_slice(&padded[..row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(pixels)
+}
+
+/// PNG file written band by band, so the whole image never has to be in
+/// memory at once
+pub struct PngStream {
+    writer: png::StreamWriter<'static, BufWriter<File>>,
+}
+
+impl PngStream {
+    pub fn create<P: AsRef<Path>>(path: P, width: u32, height: u32) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let writer = encoder
+            .write_header()
+            .and_then(png::Writer::into_stream_writer)
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(Self { writer })
+    }
+
+    /// Appends full RGBA8 rows
+    pub fn write_rows(&mut self, rgba: &[u8]) -> anyhow::Result<()> {
+        self.writer.write_all(rgba).context("writing image rows")
+    }
+
+    pub fn finish(self) -> anyhow::Result<()> {
+        self.writer.finish().context("finishing image")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_image() {
+        let view = ExportView {
+            translate: cgmath::vec3(10.0, -20.0, 1.0),
+            scale: 2.0,
+            width: 5000,
+            height: 3000,
+            ui_scale: 2.0,
+        };
+        let tiles = view.tiles(2048);
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[0].len(), 3);
+
+        let area: u32 = tiles.iter().flatten().map(|t| t.width * t.height).sum();
+        assert_eq!(area, view.width * view.height);
+        assert_eq!(
+            tiles[1][2],
+            Tile {
+                x: 4096,
+                y: 2048,
+                width: 904,
+                height: 952
+            }
+        );
+
+        // A single tile covering the image exactly needs no offset
+        let whole = Tile {
+            x: 0,
+            y: 0,
+            width: 5000,
+            height: 5000,
+        };
+        let single = ExportView {
+            height: 5000,
+            ..view
+        };
+        assert_eq!(single.tile_translate(&whole, 5000), view.translate);
+        // The top-left tile sits up and to the left of the image's center
+        let offset = view.tile_translate(&tiles[0][0], 2048) - view.translate;
+        assert_eq!(offset, cgmath::vec3(-1476.0, 476.0, 0.0));
+    }
+
+    #[test]
+    fn regions_fit_the_view_or_the_graph() {
+        let mut graph = crate::graph::Graph::new();
+        for (name, x, y) in [
+            ("a", -100.0, 0.0),
+            ("b", 300.0, 50.0),
+            ("lost", f32::NAN, 0.0),
+        ] {
+            let node = graph.add_named_node(name);
+            let node = &mut graph.nodes[node as usize];
+            node.position = cgmath::vec3(x, y, 0.0);
+            node.size = vec2(10.0, 10.0);
+        }
+        assert_eq!(
+            graph_bounds(&graph.nodes),
+            Some((vec2(-110.0, -10.0), vec2(310.0, 60.0)))
+        );
+        assert_eq!(graph_bounds(&graph.nodes[2..]), None);
+
+        let window = vec2(800.0, 600.0);
+        let camera = cgmath::vec3(5.0, -5.0, 1.0);
+        let view = ExportView::of_window(window, camera, 0.5, &graph.nodes, &Default::default());
+        assert_eq!((view.width, view.height), (3200, 2400));
+        assert_eq!(view.translate, cgmath::vec3(20.0, -20.0, 1.0));
+        assert_eq!((view.scale, view.ui_scale), (2.0, 4.0));
+
+        // The graph's 420 units and margins across the window's 800 pixels
+        let options = ExportOptions {
+            scale: 2,
+            region: ExportRegion::Graph,
+            ..Default::default()
+        };
+        let view = ExportView::of_window(window, camera, 0.5, &graph.nodes, &options);
+        let fit = 800.0 / (420.0 * (1.0 + GRAPH_MARGIN * 2.0));
+        assert!((view.scale - fit * 2.0).abs() < 1e-4, "{}", view.scale);
+        assert!((view.translate.x - 100.0 * fit * 2.0).abs() < 1e-3);
+        assert_eq!(view.translate.z, 1.0);
+
+        // A width picks the factor, whatever the scale
+        let options = ExportOptions {
+            width: Some(2000),
+            ..options
+        };
+        let view = ExportView::of_window(window, camera, 0.5, &graph.nodes, &options);
+        assert_eq!((view.width, view.height, view.ui_scale), (2000, 1500, 2.5));
+    }
+}
@@ -0,0 +1,230 @@
+/// Width of a glyph in font pixels
+pub const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph in font pixels
+pub const GLYPH_HEIGHT: u32 = 7;
+/// Horizontal distance between the start of two glyphs
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between the top of two lines
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Classic 5x7 ASCII font for printable characters (0x20..=0x7E). Each glyph
+/// is stored column by column, bit `n` of a column is row `n` from the top.
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// Icons past ASCII, drawn like glyphs so badges can use them, stored the same
+/// way as `GLYPHS`
+const ICONS: [(char, [u8; 5]); 6] = [
+    ('✓', [0x10, 0x30, 0x60, 0x18, 0x06]),
+    ('✗', [0x22, 0x14, 0x08, 0x14, 0x22]),
+    ('⚠', [0x70, 0x4E, 0x6D, 0x4E, 0x70]),
+    ('●', [0x1C, 0x3E, 0x3E, 0x3E, 0x1C]),
+    ('★', [0x24, 0x1C, 0x0F, 0x1C, 0x24]),
+    // Solid, for backdrops
+    ('█', [0x7F, 0x7F, 0x7F, 0x7F, 0x7F]),
+];
+
+/// Horizontal run of lit font pixels, row 0 is the top of the first line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+    pub col: u32,
+    pub row: u32,
+    pub len: u32,
+}
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    match c {
+        ' '..='~' => &GLYPHS[c as usize - 0x20],
+        _ => ICONS
+            .iter()
+            .find(|(icon, _)| *icon == c)
+            .map_or(&GLYPHS['?' as usize - 0x20], |(_, columns)| columns),
+    }
+}
+
+/// Lit pixels of `text` merged into horizontal runs. Lines are split on `\n`.
+pub fn runs(text: &str) -> Vec<Run> {
+    let mut runs = vec![];
+    for (line, chars) in text.lines().enumerate() {
+        for (i, c) in chars.chars().enumerate() {
+            let columns = glyph(c);
+            for y in 0..GLYPH_HEIGHT {
+                let mut start = None;
+                for x in 0..=GLYPH_WIDTH {
+                    let lit = columns
+                        .get(x as usize)
+                        .is_some_and(|col| col & (1 << y) != 0);
+                    match (lit, start) {
+                        (true, None) => start = Some(x),
+                        (false, Some(s)) => {
+                            runs.push(Run {
+                                col: i as u32 * ADVANCE + s,
+                                row: line as u32 * LINE_HEIGHT + y,
+                                len: x - s,
+                            });
+                            start = None;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    runs
+}
+
+/// Size of `text` in font pixels, without trailing spacing
+pub fn measure(text: &str) -> (u32, u32) {
+    let lines = text.lines().count() as u32;
+    let columns = text
+        .lines()
+        .map(|l| l.chars().count() as u32)
+        .max()
+        .unwrap_or(0);
+    if lines == 0 || columns == 0 {
+        return (0, 0);
+    }
+    (
+        columns * ADVANCE - 1,
+        lines * LINE_HEIGHT - (LINE_HEIGHT - GLYPH_HEIGHT),
+    )
+}
+
+/// Coverage mask of `text` with `scale` by `scale` texels per font pixel, as
+/// width, height and one byte per texel, rows top to bottom
+pub fn rasterize(text: &str, scale: u32) -> (u32, u32, Vec<u8>) {
+    let (w, h) = measure(text);
+    let (width, height) = (w * scale, h * scale);
+    let mut texels = vec![0; (width * height) as usize];
+    for run in runs(text) {
+        for y in run.row * scale..(run.row + 1) * scale {
+            let start = (y * width + run.col * scale) as usize;
+            texels[start..start + (run.len * scale) as usize].fill(u8::MAX);
+        }
+    }
+    (width, height, texels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rasterize_scales_runs() {
+        let (w, h, texels) = rasterize("-", 2);
+        assert_eq!((w, h), (10, 14));
+        // The dash is row 3, every column
+        let lit: Vec<usize> = (0..h as usize)
+            .filter(|y| {
+                texels[y * w as usize..][..w as usize]
+                    .iter()
+                    .all(|t| *t == 255)
+            })
+            .collect();
+        assert_eq!(lit, [6, 7]);
+        assert_eq!(texels.iter().filter(|t| **t == 255).count(), 20);
+        assert_eq!(rasterize("", 3), (0, 0, vec![]));
+    }
+
+    #[test]
+    fn icons_have_glyphs_of_their_own() {
+        assert_eq!(runs("█").iter().map(|r| r.len).sum::<u32>(), 35);
+        assert_ne!(runs("✓"), runs("?"));
+        assert_eq!(runs("\u{1F600}"), runs("?"));
+    }
+}
@@ -0,0 +1,602 @@
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    color,
+    edge::Marker,
+    graph::{Graph, NodeId},
+    node::Shape,
+    ColorGenerator,
+};
+
+use super::{Exporter, Importer};
+
+/// Graphviz measures positions in points and node sizes in inches, world
+/// units are read as points
+const POINTS_PER_INCH: f32 = 72.0;
+/// Node attributes turned into the node's position, size and color
+const LAYOUT_ATTRS: &[&str] = &["pos", "width", "height", "fillcolor", "color", "style"];
+/// Edge attributes turned into the edge's end markers, `Edge::head` and
+/// `Edge::tail`
+const MARKER_ATTRS: [&str; 2] = ["arrowhead", "arrowtail"];
+
+/// Graphviz DOT. Nodes keep their position (`pos`), size (`width` and
+/// `height`), color (`fillcolor`, or `color` when there's none, hex codes
+/// only), shape (`shape`, see `Shape::from_dot`) and image (`image`, a
+/// file path), edges their markers (`arrowhead` and `arrowtail`, see
+/// `Marker::from_dot`) and rest length (`len`, in inches); every other
+/// attribute is kept as is, edge weights and arrows with no matching marker
+/// included. Both `graph` and `digraph` are read, edges always keep the
+/// direction they're written in. Subgraphs are flattened, `node` and `edge`
+/// defaults apply within the subgraph that sets them.
+pub struct Dot;
+
+impl Importer for Dot {
+    fn import(&self, src: &str) -> Result<Graph> {
+        let mut parser = Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            graph: Graph::new(),
+            ids: HashMap::new(),
+            defaults: vec![Defaults::default()],
+        };
+        parser.graph()?;
+        let mut graph = parser.graph;
+
+        let palette = ColorGenerator::new().colors;
+        let mut positions = vec![];
+        for (i, node) in graph.nodes.iter_mut().enumerate() {
+            let attrs = &mut node.attributes;
+            let inches = |attrs: &Attributes, key| {
+                attrs
+                    .get(key)
+                    .and_then(AttrValue::as_number)
+                    .map(|n| n * POINTS_PER_INCH)
+            };
+            if let Some(width) = inches(attrs, "width") {
+                node.size.x = width;
+            }
+            if let Some(height) = inches(attrs, "height") {
+                node.size.y = height;
+            }
+            let hex = |key| {
+                attrs
+                    .get(key)
+                    .and_then(AttrValue::as_text)
+                    .and_then(color::from_hex)
+            };
+            node.color = hex("fillcolor")
+                .or_else(|| hex("color"))
+                .unwrap_or(palette[i % palette.len()]);
+            // Shapes with no match stay an attribute, to be written back out
+            let shape = attrs
+                .get("shape")
+                .and_then(AttrValue::as_text)
+                .and_then(Shape::from_dot);
+            if let Some(shape) = shape {
+                node.shape = shape;
+                attrs.remove("shape");
+            }
+            if let Some(AttrValue::Text(image)) = attrs.remove("image") {
+                node.image = Some(image);
+            }
+            positions.push(
+                attrs
+                    .get("pos")
+                    .and_then(AttrValue::as_text)
+                    .and_then(point),
+            );
+            attrs.retain(|key, _| !LAYOUT_ATTRS.contains(&key.as_str()));
+        }
+        for edge in &mut graph.edges {
+            let [head, tail] = MARKER_ATTRS.map(|key| {
+                let marker = edge
+                    .attributes
+                    .get(key)
+                    .and_then(AttrValue::as_text)
+                    .and_then(Marker::from_dot)?;
+                edge.attributes.remove(key);
+                Some(marker)
+            });
+            edge.head = head.unwrap_or_default();
+            edge.tail = tail.unwrap_or_default();
+            let len = edge.attributes.get("len").and_then(AttrValue::as_number);
+            if let Some(len) = len.filter(|len| *len > 0.0) {
+                edge.rest_length = Some(len * POINTS_PER_INCH);
+                edge.attributes.remove("len");
+            }
+        }
+        super::place(&mut graph, positions);
+        Ok(graph)
+    }
+}
+
+/// `x,y` in points, with an optional `!` for pinned nodes
+fn point(pos: &str) -> Option<cgmath::Vector2<f32>> {
+    let (x, y) = pos.trim_end_matches('!').split_once(',')?;
+    Some(cgmath::vec2(x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Identifier, number, quoted string or HTML string
+    Id(String),
+    /// `->` or `--`
+    Edge,
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+    let mut line_start = true;
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // Preprocessor output lines
+            '#' if line_start => while chars.next_if(|(_, c)| *c != '\n').is_some() {},
+            '/' if next == Some('/') => while chars.next_if(|(_, c)| *c != '\n').is_some() {},
+            '/' if next == Some('*') => {
+                chars.next();
+                let end = src[i + 2..]
+                    .find("*/")
+                    .ok_or_else(|| anyhow!("unterminated comment"))?;
+                while chars.next_if(|(j, _)| *j < i + 2 + end + 2).is_some() {}
+            }
+            '-' if matches!(next, Some('>' | '-')) => {
+                chars.next();
+                tokens.push(Token::Edge);
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => tokens.push(Token::Punct(c)),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next().map(|(_, c)| c) {
+                        Some('"') => break,
+                        Some('\\') => match chars.next().map(|(_, c)| c) {
+                            Some('"') => s.push('"'),
+                            // Line continuation
+                            Some('\n') => (),
+                            Some(c) => {
+                                s.push('\\');
+                                s.push(c);
+                            }
+                            None => bail!("unterminated string"),
+                        },
+                        Some(c) => s.push(c),
+                        None => bail!("unterminated string"),
+                    }
+                }
+                tokens.push(Token::Id(s));
+            }
+            '<' => {
+                let mut depth = 1;
+                let mut s = String::new();
+                while depth > 0 {
+                    let (_, c) = chars
+                        .next()
+                        .ok_or_else(|| anyhow!("unterminated HTML string"))?;
+                    depth += match c {
+                        '<' => 1,
+                        '>' => -1,
+                        _ => 0,
+                    };
+                    if depth > 0 {
+                        s.push(c);
+                    }
+                }
+                tokens.push(Token::Id(s));
+            }
+            c if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') => {
+                let mut s = String::from(c);
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| c.is_alphanumeric() || matches!(c, '_' | '.'))
+                {
+                    s.push(c);
+                }
+                tokens.push(Token::Id(s));
+            }
+            c => bail!("unexpected {c:?}"),
+        }
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+/// `node [...]` and `edge [...]` in effect
+#[derive(Debug, Clone, Default)]
+struct Defaults {
+    node: Attributes,
+    edge: Attributes,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    graph: Graph,
+    ids: HashMap<String, NodeId>,
+    /// One entry per subgraph being parsed, innermost last
+    defaults: Vec<Defaults>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, punct: char) -> Result<()> {
+        match self.next()? {
+            Token::Punct(c) if c == punct => Ok(()),
+            other => bail!("expected {punct:?}, got {other:?}"),
+        }
+    }
+
+    fn id(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Id(id) => Ok(id),
+            other => bail!("expected an identifier, got {other:?}"),
+        }
+    }
+
+    /// Whether the next token is the keyword `word`, keywords aren't case
+    /// sensitive
+    fn at_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(word))
+    }
+
+    fn graph(&mut self) -> Result<()> {
+        if self.at_keyword("strict") {
+            self.pos += 1;
+        }
+        if !(self.at_keyword("graph") || self.at_keyword("digraph")) {
+            bail!("expected graph or digraph");
+        }
+        self.pos += 1;
+        if matches!(self.peek(), Some(Token::Id(_))) {
+            self.pos += 1;
+        }
+        self.expect('{')?;
+        self.statements()?;
+        if let Some(token) = self.peek() {
+            bail!("unexpected {token:?} after the graph");
+        }
+        Ok(())
+    }
+
+    /// Statements up to and including the closing brace, returns the nodes
+    /// they mention
+    fn statements(&mut self) -> Result<Vec<NodeId>> {
+        let mut mentioned = vec![];
+        while !self.eat('}') {
+            mentioned.extend(self.statement()?);
+            while self.eat(';') || self.eat(',') {}
+        }
+        Ok(mentioned)
+    }
+
+    fn statement(&mut self) -> Result<Vec<NodeId>> {
+        let keyword = ["graph", "node", "edge"]
+            .into_iter()
+            .find(|word| self.at_keyword(word));
+        if let (Some(keyword), Some(Token::Punct('['))) = (keyword, self.tokens.get(self.pos + 1)) {
+            self.pos += 1;
+            let attrs = self.attributes()?;
+            let defaults = self.defaults.last_mut().unwrap();
+            match keyword {
+                "node" => defaults.node.extend(attrs),
+                "edge" => defaults.edge.extend(attrs),
+                _ => (),
+            }
+            return Ok(vec![]);
+        }
+        if let (Some(Token::Id(_)), Some(Token::Punct('='))) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+        {
+            // Graph attribute
+            self.pos += 2;
+            self.id()?;
+            return Ok(vec![]);
+        }
+
+        let mut ends = vec![self.operand()?];
+        while self.peek() == Some(&Token::Edge) {
+            self.pos += 1;
+            ends.push(self.operand()?);
+        }
+        let attrs = self.attributes()?;
+        if ends.len() == 1 {
+            for id in &ends[0] {
+                self.graph.nodes[*id as usize]
+                    .attributes
+                    .extend(attrs.clone());
+            }
+        } else {
+            let mut edge_attrs = self.defaults.last().unwrap().edge.clone();
+            edge_attrs.extend(attrs);
+            for pair in ends.windows(2) {
+                for a in &pair[0] {
+                    for b in &pair[1] {
+                        let edge = self.graph.add_edge(*a, *b);
+                        self.graph.edges[edge as usize].attributes = edge_attrs.clone();
+                    }
+                }
+            }
+        }
+        Ok(ends.concat())
+    }
+
+    /// A node, or every node of a subgraph
+    fn operand(&mut self) -> Result<Vec<NodeId>> {
+        if self.at_keyword("subgraph") {
+            self.pos += 1;
+            if matches!(self.peek(), Some(Token::Id(_))) {
+                self.pos += 1;
+            }
+        } else if !matches!(self.peek(), Some(Token::Punct('{'))) {
+            return Ok(vec![self.node()?]);
+        }
+        self.expect('{')?;
+        let inherited = self.defaults.last().unwrap().clone();
+        self.defaults.push(inherited);
+        let mentioned = self.statements();
+        self.defaults.pop();
+        mentioned
+    }
+
+    fn node(&mut self) -> Result<NodeId> {
+        let name = self.id()?;
+        // Ports only matter to Graphviz's edge routing
+        while self.eat(':') {
+            self.id()?;
+        }
+        if let Some(id) = self.ids.get(&name) {
+            return Ok(*id);
+        }
+        let id = self.graph.add_named_node(&name);
+        let defaults = &self.defaults.last().unwrap().node;
+        self.graph.nodes[id as usize]
+            .attributes
+            .extend(defaults.clone());
+        self.ids.insert(name, id);
+        Ok(id)
+    }
+
+    /// Any number of `[a=b, c=d]` lists, merged
+    fn attributes(&mut self) -> Result<Attributes> {
+        let mut attrs = Attributes::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let key = self.id()?;
+                let value = if self.eat('=') {
+                    self.id()?
+                } else {
+                    "true".into()
+                };
+                attrs.insert(key, AttrValue::parse(&value));
+                while self.eat(';') || self.eat(',') {}
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+impl Exporter for Dot {
+    fn export(&self, graph: &Graph) -> String {
+        let mut out = String::from("digraph {\n");
+        // Quoted, so ids that happen to be keywords like `node` stay ids
+        let ids: Vec<String> = super::node_ids(graph).iter().map(|id| quote(id)).collect();
+        for (node, node_id) in graph.nodes.iter().zip(&ids) {
+            let p = node.position;
+            let mut attrs = vec![
+                ("pos".to_string(), quote(&format!("{},{}!", p.x, p.y))),
+                ("width".into(), (node.size.x / POINTS_PER_INCH).to_string()),
+                ("height".into(), (node.size.y / POINTS_PER_INCH).to_string()),
+                ("style".into(), "filled".into()),
+                ("fillcolor".into(), quote(&color::to_hex(node.color))),
+            ];
+            // Graphviz's own default is an ellipse, close enough to a circle
+            let shaped = node.shape != Shape::Circle;
+            if shaped {
+                attrs.push(("shape".into(), node.shape.name().into()));
+            }
+            if let Some(image) = &node.image {
+                attrs.push(("image".into(), quote(image)));
+            }
+            attrs.extend(
+                node.attributes
+                    .iter()
+                    .filter(|(key, _)| !LAYOUT_ATTRS.contains(&key.as_str()))
+                    .filter(|(key, _)| !shaped || *key != "shape")
+                    .filter(|(key, _)| node.image.is_none() || *key != "image")
+                    .map(|(key, value)| (id(key), attr_value(value))),
+            );
+            writeln!(out, "  {node_id} [{}];", list(&attrs)).unwrap();
+        }
+        for edge in &graph.edges {
+            write!(
+                out,
+                "  {} -> {}",
+                ids[edge.a_id as usize], ids[edge.b_id as usize]
+            )
+            .unwrap();
+            let mut attrs: Vec<_> = MARKER_ATTRS
+                .iter()
+                .zip([edge.head, edge.tail])
+                .filter(|(_, marker)| *marker != Marker::None)
+                .map(|(key, marker)| (key.to_string(), marker.dot_name().to_string()))
+                .collect();
+            if let Some(length) = edge.rest_length {
+                attrs.push(("len".into(), (length / POINTS_PER_INCH).to_string()));
+            }
+            attrs.extend(
+                edge.attributes
+                    .iter()
+                    .map(|(key, value)| (id(key), attr_value(value))),
+            );
+            if !attrs.is_empty() {
+                write!(out, " [{}]", list(&attrs)).unwrap();
+            }
+            out += ";\n";
+        }
+        out += "}\n";
+        out
+    }
+}
+
+fn list(attrs: &[(String, String)]) -> String {
+    attrs
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn attr_value(value: &AttrValue) -> String {
+    match value {
+        AttrValue::Number(n) => n.to_string(),
+        AttrValue::Text(s) => quote(s),
+    }
+}
+
+/// `s` as is if it's a plain identifier, quoted otherwise
+fn id(s: &str) -> String {
+    let mut chars = s.chars();
+    let plain = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if plain {
+        s.to_string()
+    } else {
+        quote(s)
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dot_round_trip() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("say \"hi\"");
+        let c = graph.add_named_node("c");
+        graph.nodes[a as usize].position = cgmath::vec3(1.5, -2.0, 0.0);
+        graph.nodes[c as usize].size = cgmath::vec2(36.0, 18.0);
+        graph.nodes[c as usize].color = color::from_hex("#f2b134").unwrap();
+        graph.nodes[c as usize].shape = Shape::Diamond;
+        graph.nodes[c as usize].image = Some("icons/c.png".into());
+        graph.nodes[c as usize]
+            .attributes
+            .insert("in group".into(), 3.0.into());
+        let edge = graph.add_edge(c, a);
+        graph.edges[edge as usize]
+            .attributes
+            .insert("weight".into(), 2.5.into());
+        graph.edges[edge as usize].head = Marker::Diamond;
+        graph.edges[edge as usize].rest_length = Some(36.0);
+
+        let back = Dot.import(&Dot.export(&graph)).unwrap();
+        assert_eq!(back.node_by_name("say \"hi\""), Some(0));
+        assert_eq!(back.nodes[0].position, graph.nodes[0].position);
+        assert_eq!(back.nodes[1].size, graph.nodes[1].size);
+        assert_eq!(
+            color::to_bytes(back.nodes[1].color),
+            color::to_bytes(graph.nodes[1].color)
+        );
+        assert_eq!(back.nodes[1].attributes, graph.nodes[1].attributes);
+        assert_eq!(
+            (back.nodes[0].shape, back.nodes[1].shape),
+            (Shape::Circle, Shape::Diamond)
+        );
+        assert_eq!(back.nodes[1].image.as_deref(), Some("icons/c.png"));
+        assert_eq!((back.edges[0].a_id, back.edges[0].b_id), (1, 0));
+        assert_eq!(back.edges[0].attributes, graph.edges[0].attributes);
+        assert_eq!(
+            (back.edges[0].head, back.edges[0].tail),
+            (Marker::Diamond, Marker::None)
+        );
+        assert_eq!(back.edges[0].rest_length, Some(36.0));
+    }
+
+    #[test]
+    fn arrows_become_markers() {
+        let graph = Dot
+            .import(
+                "digraph { edge [arrowhead=onormal]; a -> b [arrowtail=tee]; b -> c [arrowhead=crow] }",
+            )
+            .unwrap();
+        let ends: Vec<_> = graph.edges.iter().map(|e| (e.head, e.tail)).collect();
+        assert_eq!(
+            ends,
+            [(Marker::Arrow, Marker::Tee), (Marker::None, Marker::None)]
+        );
+        assert!(graph.edges[0].attributes.is_empty());
+        // Kept for writing back out
+        assert_eq!(
+            graph.edges[1].attributes.get("arrowhead"),
+            Some(&AttrValue::Text("crow".into()))
+        );
+    }
+
+    #[test]
+    fn reads_foreign_dot() {
+        let graph = Dot
+            .import(
+                r##"
+                # generated
+                strict Graph G {
+                    rankdir=LR; // layout hint
+                    edge [weight=2]
+                    a [label=<<b>A</b>>, color="#ff0000"];
+                    /* a chain, and a fan out to a subgraph */
+                    a -- b:port -- c [weight=5];
+                    subgraph cluster_0 { node [shape=box]; d; e }
+                    c -- {d e}
+                }"##,
+            )
+            .unwrap();
+        let names: Vec<_> = graph.nodes.iter().map(crate::graph::node_name).collect();
+        let names: Vec<_> = names.iter().map(|n| n.as_deref().unwrap()).collect();
+        assert_eq!(names, ["<b>A</b>", "b", "c", "d", "e"]);
+        assert_eq!(graph.nodes[0].color, cgmath::vec4(1.0, 0.0, 0.0, 1.0));
+        // The subgraph's default shape applies only within it
+        assert_eq!(graph.nodes[3].shape, Shape::Square);
+        assert_eq!(graph.nodes[3].attributes.get("shape"), None);
+        assert_eq!(graph.nodes[2].shape, Shape::Circle);
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.a_id, e.b_id, e.attributes["weight"].as_number().unwrap()))
+            .collect();
+        assert_eq!(edges, [(0, 1, 5.0), (1, 2, 5.0), (2, 3, 2.0), (2, 4, 2.0)]);
+
+        assert!(Dot.import("graph { a -- }").is_err());
+        assert!(Dot.import("digraph { \"open }").is_err());
+        assert!(Dot.import("node { a }").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+
+use crate::{attributes::AttrValue, graph::Graph, layout, ColorGenerator};
+
+use super::Importer;
+
+/// Whitespace separated edge list, one edge per line:
+///
+/// ```text
+/// # comment
+/// a b
+/// a c 2.5              # third column is the edge weight
+/// b c latency=30 kind=rpc
+/// d                    # a lone node
+/// ```
+pub struct EdgeList;
+
+impl Importer for EdgeList {
+    fn import(&self, src: &str) -> Result<Graph> {
+        let mut graph = Graph::new();
+
+        for (line_no, line) in src.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut words = line.split_whitespace();
+            let Some(a) = words.next() else { continue };
+            let a = graph.node_or_insert(a);
+            let Some(b) = words.next() else { continue };
+            let b = graph.node_or_insert(b);
+            let edge = graph.add_edge(a, b);
+
+            for word in words {
+                let (key, value) = match word.split_once('=') {
+                    Some((key, value)) => (key, value),
+                    None => ("weight", word),
+                };
+                let value = match value.parse::<f32>() {
+                    Ok(n) => AttrValue::Number(n),
+                    Err(_) if key != "weight" => AttrValue::Text(value.to_string()),
+                    Err(_) => return Err(anyhow!("line {}: invalid weight {value}", line_no + 1)),
+                };
+                graph.edges[edge as usize]
+                    .attributes
+                    .insert(key.to_string(), value);
+            }
+        }
+
+        for (node, color) in graph.nodes.iter_mut().zip(ColorGenerator::new()) {
+            node.color = color;
+        }
+        layout::initial_placement(&mut graph);
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_the_documented_example() {
+        let graph = EdgeList
+            .import(
+                "# comment
+                 a b
+                 a c 2.5              # third column is the edge weight
+                 b c latency=30 kind=rpc
+                 d                    # a lone node",
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.node_by_name("d"), Some(3));
+        let edge = |i: usize| &graph.edges[i].attributes;
+        assert!(edge(0).is_empty());
+        assert_eq!(edge(1)["weight"].as_number(), Some(2.5));
+        assert_eq!(edge(2)["latency"].as_number(), Some(30.0));
+        assert_eq!(edge(2)["kind"].as_text(), Some("rpc"));
+        assert!(EdgeList.import("a b heavy").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::{anyhow, bail, Result};
+use cgmath::vec4;
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    color,
+    graph::Graph,
+    ColorGenerator,
+};
+
+use super::{Exporter, Importer};
+
+/// Node data written and read with a fixed meaning, named the way Gephi
+/// names them
+const NODE_KEYS: &[(&str, &str)] = &[
+    ("x", "float"),
+    ("y", "float"),
+    ("size", "float"),
+    ("height", "float"),
+    ("r", "int"),
+    ("g", "int"),
+    ("b", "int"),
+];
+
+/// GraphML, as read and written by Gephi, yEd and networkx. Nodes keep
+/// their position (`x`, `y`), size (`size`, and `height` for nodes that
+/// aren't square, which Gephi leaves as an attribute) and color
+/// (`r`, `g`, `b`, or a hex `color` when reading); every other `data`
+/// element is an attribute, edge weights included. Nested graphs are
+/// flattened, hyperedges and ports aren't supported.
+pub struct GraphMl;
+
+struct Key<'a> {
+    /// `node`, `edge`, `graph` or `all`
+    kind: &'a str,
+    name: String,
+    numeric: bool,
+    default: Option<String>,
+}
+
+impl Importer for GraphMl {
+    fn import(&self, src: &str) -> Result<Graph> {
+        let doc = roxmltree::Document::parse(src)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "graphml" {
+            bail!("expected <graphml>, got <{}>", root.tag_name().name());
+        }
+        let keys: HashMap<&str, Key> = root
+            .children()
+            .filter(|n| n.has_tag_name("key"))
+            .filter_map(|key| {
+                let id = key.attribute("id")?;
+                let numeric = matches!(
+                    key.attribute("attr.type"),
+                    Some("int" | "long" | "float" | "double")
+                );
+                let default = key
+                    .children()
+                    .find(|n| n.has_tag_name("default"))
+                    .and_then(|n| n.text())
+                    .map(str::to_string);
+                let name = key.attribute("attr.name").unwrap_or(id).to_string();
+                Some((
+                    id,
+                    Key {
+                        kind: key.attribute("for").unwrap_or("all"),
+                        name,
+                        numeric,
+                        default,
+                    },
+                ))
+            })
+            .collect();
+        // Keys with defaults apply to every element of their kind
+        let data = |element: roxmltree::Node| -> Attributes {
+            let kind = element.tag_name().name();
+            let mut attributes: Attributes = keys
+                .values()
+                .filter(|key| key.kind == kind || key.kind == "all")
+                .filter_map(|key| Some((key.name.clone(), value(key, key.default.as_deref()?))))
+                .collect();
+            for node in element.children().filter(|n| n.has_tag_name("data")) {
+                let id = node.attribute("key").unwrap_or_default();
+                let text = node.text().unwrap_or_default().trim();
+                match keys.get(id) {
+                    Some(key) => attributes.insert(key.name.clone(), value(key, text)),
+                    None => attributes.insert(id.to_string(), AttrValue::parse(text)),
+                };
+            }
+            attributes
+        };
+
+        let mut graph = Graph::new();
+        let mut ids = HashMap::new();
+        let mut positions = vec![];
+        let palette = ColorGenerator::new().colors;
+        for (i, element) in root
+            .descendants()
+            .filter(|n| n.has_tag_name("node"))
+            .enumerate()
+        {
+            let name = element
+                .attribute("id")
+                .ok_or_else(|| anyhow!("node {i} has no id"))?;
+            if ids.contains_key(name) {
+                bail!("duplicate node id {name}");
+            }
+            let id = graph.add_named_node(name);
+            ids.insert(name, id);
+
+            let mut attributes = data(element);
+            let mut take = |name: &str| attributes.remove(name).and_then(|v| v.as_number());
+            let position = take("x").zip(take("y")).map(|(x, y)| cgmath::vec2(x, y));
+            let size = take("size");
+            let height = take("height");
+            let rgb = [take("r"), take("g"), take("b")];
+            let node = &mut graph.nodes[id as usize];
+            if let Some(size) = size {
+                node.size = cgmath::vec2(size, height.unwrap_or(size));
+            }
+            node.color = if rgb.iter().any(Option::is_some) {
+                let [r, g, b] = rgb.map(|c| c.unwrap_or(0.0) / 255.0);
+                vec4(r, g, b, 1.0)
+            } else {
+                let hex = attributes.get("color").and_then(AttrValue::as_text);
+                hex.and_then(color::from_hex)
+                    .unwrap_or(palette[i % palette.len()])
+            };
+            node.attributes.extend(attributes);
+            positions.push(position);
+        }
+
+        for (i, element) in root
+            .descendants()
+            .filter(|n| n.has_tag_name("edge"))
+            .enumerate()
+        {
+            let end = |attr: &str| {
+                let name = element
+                    .attribute(attr)
+                    .ok_or_else(|| anyhow!("edge {i} has no {attr}"))?;
+                ids.get(name)
+                    .copied()
+                    .ok_or_else(|| anyhow!("edge {i}: no node {name}"))
+            };
+            let edge = graph.add_edge(end("source")?, end("target")?);
+            graph.edges[edge as usize].attributes = data(element);
+        }
+
+        super::place(&mut graph, positions);
+        Ok(graph)
+    }
+}
+
+fn value(key: &Key, text: &str) -> AttrValue {
+    match text.trim().parse() {
+        Ok(n) if key.numeric => AttrValue::Number(n),
+        _ => AttrValue::Text(text.to_string()),
+    }
+}
+
+impl Exporter for GraphMl {
+    fn export(&self, graph: &Graph) -> String {
+        let node_attrs = attribute_types(graph.nodes.iter().map(|n| &n.attributes), NODE_KEYS);
+        // Edges have no fixed keys, an edge's `x` is an attribute like any
+        // other under a key of its own
+        let edge_attrs = attribute_types(graph.edges.iter().map(|e| &e.attributes), &[]);
+
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        let mut key_ids = HashMap::new();
+        let keys = NODE_KEYS
+            .iter()
+            .map(|(name, ty)| ("node", *name, *ty))
+            .chain(node_attrs.iter().map(|(name, ty)| ("node", *name, *ty)))
+            .chain(edge_attrs.iter().map(|(name, ty)| ("edge", *name, *ty)));
+        for (i, (kind, name, ty)) in keys.enumerate() {
+            key_ids.insert((kind, name), format!("d{i}"));
+            writeln!(
+                out,
+                "  <key id=\"d{i}\" for=\"{kind}\" attr.name=\"{}\" attr.type=\"{ty}\"/>",
+                escape(name)
+            )
+            .unwrap();
+        }
+        out += "  <graph edgedefault=\"directed\">\n";
+
+        let data = |out: &mut String, kind, name, value: &dyn std::fmt::Display| {
+            let key = &key_ids[&(kind, name)];
+            let value = escape(&value.to_string());
+            writeln!(out, "      <data key=\"{key}\">{value}</data>").unwrap();
+        };
+        let ids: Vec<String> = super::node_ids(graph).iter().map(|id| escape(id)).collect();
+        for (node, id) in graph.nodes.iter().zip(&ids) {
+            writeln!(out, "    <node id=\"{id}\">").unwrap();
+            let [r, g, b, _] = color::to_bytes(node.color);
+            data(&mut out, "node", "x", &node.position.x);
+            data(&mut out, "node", "y", &node.position.y);
+            data(&mut out, "node", "size", &node.size.x);
+            data(&mut out, "node", "height", &node.size.y);
+            data(&mut out, "node", "r", &r);
+            data(&mut out, "node", "g", &g);
+            data(&mut out, "node", "b", &b);
+            for (name, value) in &node.attributes {
+                if key_ids.contains_key(&("node", name.as_str())) && !is_reserved(name) {
+                    data(&mut out, "node", name, value);
+                }
+            }
+            out += "    </node>\n";
+        }
+        for (i, edge) in graph.edges.iter().enumerate() {
+            writeln!(
+                out,
+                "    <edge id=\"e{i}\" source=\"{}\" target=\"{}\">",
+                ids[edge.a_id as usize], ids[edge.b_id as usize]
+            )
+            .unwrap();
+            for (name, value) in &edge.attributes {
+                data(&mut out, "edge", name, value);
+            }
+            out += "    </edge>\n";
+        }
+        out += "  </graph>\n</graphml>\n";
+        out
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    NODE_KEYS.iter().any(|(key, _)| *key == name)
+}
+
+/// Every attribute name in use but the `reserved` ones, `double` if all its
+/// values are numbers
+fn attribute_types<'a>(
+    all: impl Iterator<Item = &'a Attributes>,
+    reserved: &[(&str, &str)],
+) -> std::collections::BTreeMap<&'a str, &'static str> {
+    let mut types = std::collections::BTreeMap::new();
+    for attributes in all {
+        for (name, value) in attributes {
+            if reserved.iter().any(|(key, _)| key == name) {
+                continue;
+            }
+            let ty = types.entry(name.as_str()).or_insert("double");
+            if matches!(value, AttrValue::Text(_)) {
+                *ty = "string";
+            }
+        }
+    }
+    types
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::GraphView;
+
+    #[test]
+    fn graphml_round_trip() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("a & b");
+        let c = graph.add_named_node("c");
+        graph.nodes[a as usize].position = cgmath::vec3(1.5, -2.0, 0.0);
+        graph.nodes[c as usize].size = cgmath::vec2(36.0, 18.0);
+        graph.nodes[c as usize].color = color::from_hex("#f2b134").unwrap();
+        graph.nodes[c as usize]
+            .attributes
+            .insert("group".into(), 3.0.into());
+        let edge = graph.add_edge(a, c);
+        graph.edges[edge as usize]
+            .attributes
+            .insert("weight".into(), 2.5.into());
+
+        let back = GraphMl.import(&GraphMl.export(&graph)).unwrap();
+        assert_eq!(back.node_by_name("a & b"), Some(0));
+        assert_eq!(back.node_by_external_id("c"), Some(1));
+        assert_eq!(back.nodes[0].position, graph.nodes[0].position);
+        assert_eq!(back.nodes[1].size, graph.nodes[1].size);
+        assert_eq!(
+            color::to_bytes(back.nodes[1].color),
+            color::to_bytes(graph.nodes[1].color)
+        );
+        assert_eq!(back.nodes[1].attributes, graph.nodes[1].attributes);
+        assert_eq!((back.edges[0].a_id, back.edges[0].b_id), (0, 1));
+        assert_eq!(back.edges[0].attributes, graph.edges[0].attributes);
+    }
+
+    #[test]
+    fn edge_attributes_named_like_node_keys_round_trip() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("a");
+        let b = graph.add_named_node("b");
+        let edge = graph.add_edge(a, b);
+        for (i, (name, _)) in NODE_KEYS.iter().enumerate() {
+            graph.edges[edge as usize]
+                .attributes
+                .insert(name.to_string(), (i as f32).into());
+        }
+        graph.edges[edge as usize]
+            .attributes
+            .insert("label".into(), AttrValue::Text("y".into()));
+
+        let back = GraphMl.import(&GraphMl.export(&graph)).unwrap();
+        assert_eq!(back.edges[0].attributes, graph.edges[0].attributes);
+        // Nodes pick up none of the edge's keys
+        assert_eq!(back.nodes[0].attributes, graph.nodes[0].attributes);
+        assert_eq!(back.nodes[1].attributes, graph.nodes[1].attributes);
+    }
+
+    #[test]
+    fn reads_foreign_graphml() {
+        let graph = GraphMl
+            .import(
+                r##"<?xml version="1.0"?>
+                <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+                  <key id="w" for="edge" attr.name="weight" attr.type="double">
+                    <default>1</default>
+                  </key>
+                  <key id="c" for="node" attr.name="color" attr.type="string"/>
+                  <graph edgedefault="undirected">
+                    <node id="x"><data key="c">#ff0000</data></node>
+                    <node id="y"/>
+                    <edge source="x" target="y"/>
+                    <edge source="y" target="x"><data key="w">4</data></edge>
+                  </graph>
+                </graphml>"##,
+            )
+            .unwrap();
+        assert_eq!(graph.nodes[0].color, vec4(1.0, 0.0, 0.0, 1.0));
+        let weights: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| e.attributes["weight"].as_number())
+            .collect();
+        assert_eq!(weights, [Some(1.0), Some(4.0)]);
+        assert!(GraphMl.import("<graph/>").is_err());
+        assert!(GraphMl
+            .import("<graphml><graph><edge source=\"a\" target=\"b\"/></graph></graphml>")
+            .is_err());
+    }
+}
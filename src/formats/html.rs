@@ -0,0 +1,313 @@
+use serde_json::{json, Value};
+
+use crate::{color, graph::Graph, node::Node};
+
+use super::Exporter;
+
+/// Camera scale from which captions are drawn, as `label::DEFAULT_LABEL_ZOOM`
+const LABEL_ZOOM: f32 = 0.5;
+/// Marks the line the graph's data is on, for finding it again
+const DATA_MARKER: &str = "const GRAPH = ";
+
+/// What the page first shows: the world position at its middle, and pixels
+/// per world unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HtmlView {
+    pub center: cgmath::Vector2<f32>,
+    pub scale: f32,
+}
+
+/// A standalone web page drawing the graph where it's laid out, on a canvas
+/// that pans on drag, zooms on the wheel and shows a node's caption and
+/// attributes on hover. Everything is in the one file, for sharing results
+/// with people who won't install the viewer. Write-only.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Html {
+    /// Opens fitted to the graph when unset
+    pub view: Option<HtmlView>,
+}
+
+impl Exporter for Html {
+    fn export(&self, graph: &Graph) -> String {
+        let nodes: Vec<Value> = graph.nodes.iter().map(node_json).collect();
+        let edges: Vec<Value> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "a": edge.a_id,
+                    "b": edge.b_id,
+                    "color": color::to_hex(edge.color),
+                    "alpha": edge.color.w,
+                    "width": edge.line_width,
+                })
+            })
+            .collect();
+        let view = self.view.map(|view| {
+            json!({
+                "x": view.center.x,
+                "y": view.center.y,
+                "scale": view.scale,
+            })
+        });
+        let data = json!({
+            "nodes": nodes,
+            "edges": edges,
+            "view": view,
+            "labelZoom": LABEL_ZOOM,
+        });
+        // Captions are free text, a `</script>` in one mustn't end the script
+        let data = data.to_string().replace('<', "\\u003c");
+        PAGE.replace("/*GRAPH*/", &format!("{DATA_MARKER}{data};"))
+    }
+}
+
+fn node_json(node: &Node) -> Value {
+    let attributes: serde_json::Map<String, Value> = node
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.clone(), json!(value.to_string())))
+        .collect();
+    json!({
+        "x": node.position.x,
+        "y": node.position.y,
+        "r": node.radius(),
+        "color": color::to_hex(node.color),
+        "alpha": node.color.w,
+        "shape": node.shape.name(),
+        "caption": node.caption(),
+        "attributes": attributes,
+    })
+}
+
+/// The viewer, with `/*GRAPH*/` where the data goes. Shapes match
+/// `Shape::norm`: a node's radius is half its width whatever its shape.
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Graph</title>
+<style>
+  html, body { margin: 0; height: 100%; overflow: hidden; background: #202124; }
+  canvas { display: block; width: 100%; height: 100%; cursor: grab; }
+  #tip {
+    position: fixed; display: none; pointer-events: none; max-width: 320px;
+    padding: 6px 8px; background: rgba(0, 0, 0, 0.8); color: #fff;
+    font: 12px sans-serif; white-space: pre-wrap; border-radius: 3px;
+  }
+</style>
+</head>
+<body>
+<canvas id="graph"></canvas>
+<div id="tip"></div>
+<script>
+/*GRAPH*/
+const canvas = document.getElementById("graph");
+const tip = document.getElementById("tip");
+const ctx = canvas.getContext("2d");
+const FIT_MARGIN = 0.05;
+let view = GRAPH.view;
+let hovered = null;
+let drag = null;
+
+function fit() {
+  if (!GRAPH.nodes.length) {
+    return { x: 0, y: 0, scale: 1 };
+  }
+  // A loop rather than spreading into Math.min, which runs out of stack on
+  // big graphs
+  let [minX, maxX, minY, maxY] = [Infinity, -Infinity, Infinity, -Infinity];
+  for (const n of GRAPH.nodes) {
+    minX = Math.min(minX, n.x - n.r);
+    maxX = Math.max(maxX, n.x + n.r);
+    minY = Math.min(minY, n.y - n.r);
+    maxY = Math.max(maxY, n.y + n.r);
+  }
+  const w = (maxX - minX) * (1 + 2 * FIT_MARGIN), h = (maxY - minY) * (1 + 2 * FIT_MARGIN);
+  const scale = Math.min(canvas.clientWidth / Math.max(w, 1e-6), canvas.clientHeight / Math.max(h, 1e-6));
+  return { x: (minX + maxX) / 2, y: (minY + maxY) / 2, scale };
+}
+
+// World y points up, the canvas's down
+function toScreen(x, y) {
+  return [canvas.clientWidth / 2 + (x - view.x) * view.scale, canvas.clientHeight / 2 - (y - view.y) * view.scale];
+}
+
+function toWorld(sx, sy) {
+  return [view.x + (sx - canvas.clientWidth / 2) / view.scale, view.y - (sy - canvas.clientHeight / 2) / view.scale];
+}
+
+function norm(shape, dx, dy) {
+  if (shape === "square") return Math.max(Math.abs(dx), Math.abs(dy));
+  if (shape === "diamond") return Math.abs(dx) + Math.abs(dy);
+  return Math.hypot(dx, dy);
+}
+
+// Topmost node under a point, later nodes being drawn over earlier ones
+function nodeAt(sx, sy) {
+  const [x, y] = toWorld(sx, sy);
+  for (let i = GRAPH.nodes.length - 1; i >= 0; i--) {
+    const n = GRAPH.nodes[i];
+    if (norm(n.shape, x - n.x, y - n.y) <= n.r) return i;
+  }
+  return null;
+}
+
+function outline(n, sx, sy, r) {
+  ctx.beginPath();
+  if (n.shape === "square") {
+    ctx.rect(sx - r, sy - r, 2 * r, 2 * r);
+  } else if (n.shape === "diamond") {
+    ctx.moveTo(sx, sy - r);
+    ctx.lineTo(sx + r, sy);
+    ctx.lineTo(sx, sy + r);
+    ctx.lineTo(sx - r, sy);
+    ctx.closePath();
+  } else {
+    ctx.arc(sx, sy, r, 0, 2 * Math.PI);
+  }
+}
+
+function draw() {
+  const ratio = window.devicePixelRatio || 1;
+  canvas.width = canvas.clientWidth * ratio;
+  canvas.height = canvas.clientHeight * ratio;
+  ctx.setTransform(ratio, 0, 0, ratio, 0, 0);
+  ctx.clearRect(0, 0, canvas.clientWidth, canvas.clientHeight);
+
+  for (const e of GRAPH.edges) {
+    const lit = hovered !== null && (e.a === hovered || e.b === hovered);
+    const [ax, ay] = toScreen(GRAPH.nodes[e.a].x, GRAPH.nodes[e.a].y);
+    const [bx, by] = toScreen(GRAPH.nodes[e.b].x, GRAPH.nodes[e.b].y);
+    ctx.globalAlpha = lit ? 1 : e.alpha;
+    ctx.strokeStyle = lit ? "#ffffff" : e.color;
+    ctx.lineWidth = Math.max(e.width * view.scale, 1);
+    ctx.beginPath();
+    ctx.moveTo(ax, ay);
+    ctx.lineTo(bx, by);
+    ctx.stroke();
+  }
+  GRAPH.nodes.forEach((n, i) => {
+    const [sx, sy] = toScreen(n.x, n.y);
+    outline(n, sx, sy, Math.max(n.r * view.scale, 1));
+    ctx.globalAlpha = n.alpha;
+    ctx.fillStyle = n.color;
+    ctx.fill();
+    if (i === hovered) {
+      ctx.globalAlpha = 1;
+      ctx.strokeStyle = "#f2b033";
+      ctx.lineWidth = 3;
+      ctx.stroke();
+    }
+  });
+  if (view.scale >= GRAPH.labelZoom) {
+    ctx.globalAlpha = 0.9;
+    ctx.fillStyle = "#ffffff";
+    ctx.font = "12px sans-serif";
+    ctx.textAlign = "center";
+    ctx.textBaseline = "top";
+    for (const n of GRAPH.nodes) {
+      if (!n.caption) continue;
+      const [sx, sy] = toScreen(n.x, n.y);
+      ctx.fillText(n.caption, sx, sy + n.r * view.scale + 4);
+    }
+  }
+  ctx.globalAlpha = 1;
+}
+
+function showTip(i, sx, sy) {
+  if (i === null) {
+    tip.style.display = "none";
+    return;
+  }
+  const n = GRAPH.nodes[i];
+  const lines = [n.caption ?? "#" + i].concat(Object.entries(n.attributes).map(([k, v]) => k + ": " + v));
+  tip.textContent = lines.join("\n");
+  tip.style.left = sx + 12 + "px";
+  tip.style.top = sy + 12 + "px";
+  tip.style.display = "block";
+}
+
+canvas.addEventListener("pointerdown", ev => {
+  drag = { x: ev.clientX, y: ev.clientY };
+  canvas.setPointerCapture(ev.pointerId);
+  canvas.style.cursor = "grabbing";
+});
+canvas.addEventListener("pointerup", () => {
+  drag = null;
+  canvas.style.cursor = "grab";
+});
+canvas.addEventListener("pointermove", ev => {
+  if (drag) {
+    view.x -= (ev.clientX - drag.x) / view.scale;
+    view.y += (ev.clientY - drag.y) / view.scale;
+    drag = { x: ev.clientX, y: ev.clientY };
+  } else {
+    hovered = nodeAt(ev.clientX, ev.clientY);
+    showTip(hovered, ev.clientX, ev.clientY);
+  }
+  draw();
+});
+canvas.addEventListener("pointerleave", () => {
+  hovered = null;
+  showTip(null);
+  draw();
+});
+// Zooms about the cursor, keeping whatever is under it in place
+canvas.addEventListener("wheel", ev => {
+  ev.preventDefault();
+  const [x, y] = toWorld(ev.clientX, ev.clientY);
+  view.scale *= Math.exp(-ev.deltaY * 0.001);
+  const [nx, ny] = toWorld(ev.clientX, ev.clientY);
+  view.x += x - nx;
+  view.y += y - ny;
+  draw();
+}, { passive: false });
+window.addEventListener("resize", draw);
+
+if (!view) view = fit();
+draw();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node::Shape;
+
+    #[test]
+    fn pages_embed_the_graph() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("</script><b>a</b>");
+        let b = graph.add_named_node("b");
+        graph.nodes[b as usize].position = cgmath::vec3(40.0, -10.0, 0.0);
+        graph.nodes[b as usize].shape = Shape::Square;
+        graph.add_edge(a, b);
+
+        let page = Html::default().export(&graph);
+        assert_eq!(page.matches("</script>").count(), 1);
+        let line = page
+            .lines()
+            .find_map(|line| line.strip_prefix(DATA_MARKER))
+            .unwrap();
+        let data: Value = serde_json::from_str(line.trim_end_matches(';')).unwrap();
+        assert_eq!(data["nodes"][0]["caption"], "</script><b>a</b>");
+        assert_eq!(data["nodes"][1]["x"], 40.0);
+        assert_eq!(data["nodes"][1]["shape"], "square");
+        assert_eq!(
+            (&data["edges"][0]["a"], &data["edges"][0]["b"]),
+            (&0.into(), &1.into())
+        );
+        assert!(data["view"].is_null());
+
+        let view = HtmlView {
+            center: cgmath::vec2(5.0, 6.0),
+            scale: 2.0,
+        };
+        let page = Html { view: Some(view) }.export(&graph);
+        assert!(page.contains(r#""view":{"scale":2.0,"x":5.0,"y":6.0}"#));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    color,
+    graph::{Graph, NodeId},
+    ColorGenerator,
+};
+
+use super::Importer;
+
+/// D3's node-link JSON, as written by `networkx.node_link_data` and most
+/// force layout examples:
+///
+/// ```text
+/// {
+///   "nodes": [{"id": "a", "group": 1}, {"id": "b", "x": 10, "y": 20}],
+///   "links": [{"source": "a", "target": "b", "value": 3}]
+/// }
+/// ```
+///
+/// Links name their ends by node `id`, by index into `nodes` when nodes have
+/// no ids, or by embedding the node object the way D3 rewrites them. `edges`
+/// is accepted in place of `links`. Other scalar fields become attributes,
+/// and a `color` field holding a hex code colors the node. Positions are
+/// kept only when every node has both `x` and `y`, with y flipped from
+/// screen coordinates to y up.
+pub struct NodeLink;
+
+impl Importer for NodeLink {
+    fn import(&self, src: &str) -> Result<Graph> {
+        let root: Value = serde_json::from_str(src)?;
+        let nodes = root
+            .get("nodes")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("expected a \"nodes\" array"))?;
+        let links = match root.get("links").or_else(|| root.get("edges")) {
+            Some(links) => links
+                .as_array()
+                .ok_or_else(|| anyhow!("\"links\" must be an array"))?
+                .as_slice(),
+            None => &[],
+        };
+
+        let mut graph = Graph::new();
+        let mut ids = HashMap::new();
+        let mut positions = vec![];
+        let palette = ColorGenerator::new().colors;
+        for (i, node) in nodes.iter().enumerate() {
+            let fields = node
+                .as_object()
+                .ok_or_else(|| anyhow!("node {i} is not an object"))?;
+            let name = match fields.get("id") {
+                Some(id) => scalar_key(id).with_context(|| format!("node {i}"))?,
+                None => i.to_string(),
+            };
+            if ids.contains_key(&name) {
+                bail!("node {i}: duplicate id {name}");
+            }
+            let id = graph.add_named_node(&name);
+            ids.insert(name, id);
+
+            let node = &mut graph.nodes[id as usize];
+            node.attributes
+                .extend(attributes(fields, &["id", "x", "y", "color"]));
+            node.color = fields
+                .get("color")
+                .and_then(Value::as_str)
+                .and_then(color::from_hex)
+                .unwrap_or(palette[i % palette.len()]);
+            let coord = |key| fields.get(key).and_then(Value::as_f64);
+            positions.push(
+                coord("x")
+                    .zip(coord("y"))
+                    .map(|(x, y)| cgmath::vec2(x as f32, -y as f32)),
+            );
+        }
+
+        for (i, link) in links.iter().enumerate() {
+            let fields = link
+                .as_object()
+                .ok_or_else(|| anyhow!("link {i} is not an object"))?;
+            let end = |key: &str| -> Result<NodeId> {
+                let value = fields
+                    .get(key)
+                    .ok_or_else(|| anyhow!("link {i}: missing {key}"))?;
+                // D3 replaces the reference with the node itself once laid out
+                let value = value.get("id").unwrap_or(value);
+                let name = scalar_key(value).with_context(|| format!("link {i}: {key}"))?;
+                ids.get(&name)
+                    .copied()
+                    .ok_or_else(|| anyhow!("link {i}: no node {name}"))
+            };
+            let (a, b) = (end("source")?, end("target")?);
+            let edge = graph.add_edge(a, b);
+            graph.edges[edge as usize]
+                .attributes
+                .extend(attributes(fields, &["source", "target"]));
+        }
+
+        super::place(&mut graph, positions);
+        Ok(graph)
+    }
+}
+
+/// Ids can be strings or numbers, both name the node the same way
+fn scalar_key(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => Err(anyhow!("expected a string or number id, got {value}")),
+    }
+}
+
+/// The object's string, number and boolean fields, skipping `except`
+fn attributes(fields: &Map<String, Value>, except: &[&str]) -> Attributes {
+    fields
+        .iter()
+        .filter(|(key, _)| !except.contains(&key.as_str()))
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => AttrValue::Text(s.clone()),
+                Value::Number(n) => AttrValue::Number(n.as_f64()? as f32),
+                Value::Bool(b) => AttrValue::Text(b.to_string()),
+                _ => return None,
+            };
+            Some((key.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_d3_node_link_json() {
+        let graph = NodeLink
+            .import(
+                r##"{
+                    "directed": false,
+                    "nodes": [
+                        {"id": "a", "group": 1, "x": 0, "y": 0},
+                        {"id": 7, "color": "#ff0000", "x": 10, "y": 20}
+                    ],
+                    "links": [{"source": "a", "target": {"id": 7}, "value": 2.5}]
+                }"##,
+            )
+            .unwrap();
+        assert_eq!(graph.node_by_name("7"), Some(1));
+        assert_eq!(
+            graph.nodes[0].attributes.get("group"),
+            Some(&AttrValue::Number(1.0))
+        );
+        assert_eq!(graph.nodes[1].color, cgmath::vec4(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(graph.nodes[1].position, cgmath::vec3(10.0, -20.0, 0.0));
+        let edge = &graph.edges[0];
+        assert_eq!((edge.a_id, edge.b_id), (0, 1));
+        assert_eq!(edge.b_center, graph.nodes[1].position);
+        assert_eq!(edge.attributes.get("value"), Some(&AttrValue::Number(2.5)));
+
+        // Index references, `edges` for `links`, and no positions
+        let graph = NodeLink
+            .import(r#"{"nodes": [{}, {}, {}], "edges": [{"source": 0, "target": 2}]}"#)
+            .unwrap();
+        assert_eq!((graph.edges[0].a_id, graph.edges[0].b_id), (0, 2));
+        assert_ne!(graph.nodes[1].position, graph.nodes[2].position);
+
+        assert!(NodeLink.import(r#"{"links": []}"#).is_err());
+        assert!(NodeLink
+            .import(r#"{"nodes": [{"id": 1}], "links": [{"source": 1, "target": 2}]}"#)
+            .is_err());
+        assert!(NodeLink
+            .import(r#"{"nodes": [{"id": 1}, {"id": "1"}]}"#)
+            .is_err());
+    }
+}
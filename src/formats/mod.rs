@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{graph::Graph, layout};
+
+pub mod dot;
+pub mod edgelist;
+pub mod graphml;
+pub mod html;
+pub mod json;
+
+/// Turns the contents of a graph file into a `Graph`
+pub trait Importer {
+    fn import(&self, src: &str) -> Result<Graph>;
+}
+
+/// Turns a `Graph` into the contents of a graph file
+pub trait Exporter {
+    fn export(&self, graph: &Graph) -> String;
+}
+
+/// Picks an importer from the file extension
+pub fn importer_for(path: &Path) -> Result<Box<dyn Importer>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("txt" | "edges" | "edgelist") | None => Ok(Box::new(edgelist::EdgeList)),
+        Some("json") => Ok(Box::new(json::NodeLink)),
+        Some("graphml") => Ok(Box::new(graphml::GraphMl)),
+        Some("dot" | "gv") => Ok(Box::new(dot::Dot)),
+        Some(ext) => Err(anyhow!("unsupported graph format: .{ext}")),
+    }
+}
+
+/// Picks an exporter from the file extension
+pub fn exporter_for(path: &Path) -> Result<Box<dyn Exporter>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("graphml") => Ok(Box::new(graphml::GraphMl)),
+        Some("dot" | "gv") => Ok(Box::new(dot::Dot)),
+        Some("html" | "htm") => Ok(Box::new(html::Html::default())),
+        Some(ext) => Err(anyhow!(
+            "can't write .{ext} files, use .graphml, .dot or .html"
+        )),
+        None => Err(anyhow!("no file extension to pick a format from")),
+    }
+}
+
+pub fn save<P: AsRef<Path>>(path: P, graph: &Graph) -> Result<()> {
+    let path = path.as_ref();
+    let src = exporter_for(path)?.export(graph);
+    std::fs::write(path, src).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Graph> {
+    let path = path.as_ref();
+    load_with(path, importer_for(path)?.as_ref())
+}
+
+/// Reads a file with a given importer, whatever its extension
+pub fn load_with<P: AsRef<Path>>(path: P, importer: &dyn Importer) -> Result<Graph> {
+    let path = path.as_ref();
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    importer
+        .import(&src)
+        .with_context(|| format!("failed to import {}", path.display()))
+}
+
+/// Ids to write nodes out under: their external ids so they survive a round
+/// trip, or their indices as `n0`, `n1`, ... when those aren't all there and
+/// unique (see `Graph::stable_ids`)
+fn node_ids(graph: &Graph) -> Vec<String> {
+    match graph.stable_ids() {
+        Some(ids) => ids.into_iter().map(String::from).collect(),
+        None => (0..graph.nodes.len()).map(|i| format!("n{i}")).collect(),
+    }
+}
+
+/// Puts nodes where the file had them if it had a position for every node,
+/// otherwise spreads them out like any other import
+fn place(graph: &mut Graph, positions: Vec<Option<cgmath::Vector2<f32>>>) {
+    match positions.into_iter().collect::<Option<Vec<_>>>() {
+        Some(positions) if !positions.is_empty() => {
+            for (node, position) in graph.nodes.iter_mut().zip(positions) {
+                node.position = position.extend(0.0);
+            }
+            graph.sync_edges();
+        }
+        _ => layout::initial_placement(graph),
+    }
+}
@@ -0,0 +1,319 @@
+use cgmath::One;
+
+use crate::{
+    graph::{Graph, NodeId, DEFAULT_NODE_SIZE},
+    layout,
+    node::Node,
+    rng::Rng,
+    ColorGenerator,
+};
+
+/// Most nodes a generator makes, past which it would run out of memory
+/// long before the viewer ran out of frames
+pub const MAX_NODES: usize = 1 << 20;
+/// Most edges a generator is expected to make, see `MAX_NODES`
+pub const MAX_EDGES: usize = 1 << 22;
+
+/// Earlier nodes each new one links to in a scale-free graph, unless told
+pub const DEFAULT_LINKS: usize = 2;
+/// Children per node in a tree, unless told
+pub const DEFAULT_FANOUT: usize = 2;
+
+/// Synthetic graphs for stress testing and demoing the layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Generator {
+    /// `random_gnp`
+    Random { nodes: usize, p: f32 },
+    /// `barabasi_albert`
+    ScaleFree { nodes: usize, links: usize },
+    /// `grid`
+    Grid { width: usize, height: usize },
+    /// `balanced_tree`
+    Tree { depth: usize, fanout: usize },
+}
+
+impl Generator {
+    /// What the number keys spawn, in order: mid-sized graphs of each kind
+    /// that settle in a few seconds
+    pub const DEMOS: [Generator; 4] = [
+        Generator::Random {
+            nodes: 300,
+            p: 0.01,
+        },
+        Generator::ScaleFree {
+            nodes: 500,
+            links: 2,
+        },
+        Generator::Grid {
+            width: 20,
+            height: 20,
+        },
+        Generator::Tree {
+            depth: 5,
+            fanout: 3,
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Random { .. } => "random",
+            Generator::ScaleFree { .. } => "scalefree",
+            Generator::Grid { .. } => "grid",
+            Generator::Tree { .. } => "tree",
+        }
+    }
+
+    /// Nodes the graph will have, `None` if that doesn't fit in a `usize`
+    pub fn node_count(&self) -> Option<usize> {
+        match *self {
+            Generator::Random { nodes, .. } | Generator::ScaleFree { nodes, .. } => Some(nodes),
+            Generator::Grid { width, height } => width.checked_mul(height),
+            Generator::Tree { depth, fanout } => match fanout {
+                // Just the root or a chain, however deep, counted without
+                // walking every level
+                0 => Some(1),
+                1 => depth.checked_add(1),
+                // Overflows within 64 levels, however deep it's asked to be
+                _ => (0..depth)
+                    .try_fold(1usize, |count, _| count.checked_mul(fanout)?.checked_add(1)),
+            },
+        }
+    }
+
+    /// Edges the graph is expected to have, random ones varying around it
+    fn edge_estimate(&self, nodes: usize) -> f64 {
+        let n = nodes as f64;
+        match *self {
+            Generator::Random { p, .. } => p as f64 * n * (n - 1.0) / 2.0,
+            Generator::ScaleFree { links, .. } => links as f64 * n,
+            Generator::Grid { .. } => 2.0 * n,
+            Generator::Tree { .. } => n,
+        }
+    }
+
+    /// Why the graph can't be made, if it's too big to
+    pub fn check(&self) -> Result<(), String> {
+        let nodes = self
+            .node_count()
+            .filter(|nodes| *nodes <= MAX_NODES)
+            .ok_or_else(|| format!("{} would have over {MAX_NODES} nodes", self.name()))?;
+        if self.edge_estimate(nodes) > MAX_EDGES as f64 {
+            return Err(format!("{} would have over {MAX_EDGES} edges", self.name()));
+        }
+        Ok(())
+    }
+
+    /// Makes the graph, the same seed always giving the same one. Only
+    /// random kinds use it.
+    pub fn generate(&self, seed: u64) -> Graph {
+        match *self {
+            Generator::Random { nodes, p } => random_gnp(nodes, p, seed),
+            Generator::ScaleFree { nodes, links } => barabasi_albert(nodes, links, seed),
+            Generator::Grid { width, height } => grid(width, height),
+            Generator::Tree { depth, fanout } => balanced_tree(depth, fanout),
+        }
+    }
+}
+
+/// `n` unlinked nodes of the default size in the default palette
+fn nodes(n: usize) -> Graph {
+    let mut graph = Graph::new();
+    for color in ColorGenerator::new().take(n) {
+        graph.add_node(Node::new(
+            (DEFAULT_NODE_SIZE, DEFAULT_NODE_SIZE),
+            (0.0, 0.0, 0.0),
+            cgmath::Quaternion::one(),
+            color,
+        ));
+    }
+    graph
+}
+
+/// Erdős–Rényi G(n, p): `n` nodes with each pair linked with probability
+/// `p`. Skips ahead to the next edge rather than rolling for every pair, so
+/// sparse graphs take time in their edges rather than in `n` squared.
+pub fn random_gnp(n: usize, p: f32, seed: u64) -> Graph {
+    let mut graph = nodes(n);
+    let mut rng = Rng::new(seed);
+    // `ln_1p` keeps odds too small to take from 1 from rounding to a skip
+    // of zero, which would be a jump to infinity. Zero is left for `p` of 0.
+    let skip = (-(p as f64)).ln_1p();
+    if p >= 1.0 {
+        for b in 0..n {
+            for a in 0..b {
+                graph.add_edge(a as NodeId, b as NodeId);
+            }
+        }
+    } else if skip < 0.0 {
+        // Batagelj and Brandes: pairs (a, b) with a < b, walked in order.
+        // A jump past every pair ends the walk, so longer ones are cut
+        // short of overflowing `a`.
+        let pairs = n as f64 * n as f64;
+        let (mut b, mut a) = (1usize, -1i64);
+        while b < n {
+            let roll = 1.0 - rng.next_f32() as f64;
+            let jump = (roll.ln() / skip).floor().min(pairs) as i64;
+            a = a.saturating_add(1).saturating_add(jump);
+            while a >= b as i64 && b < n {
+                a -= b as i64;
+                b += 1;
+            }
+            if b < n {
+                graph.add_edge(a as NodeId, b as NodeId);
+            }
+        }
+    }
+    layout::initial_placement(&mut graph);
+    graph
+}
+
+/// Barabási–Albert scale-free graph: starts from `m + 1` nodes all linked
+/// to each other, then every new node links to `m` of the earlier ones,
+/// picked with odds proportional to their degree. A few hubs end up with
+/// most of the links.
+pub fn barabasi_albert(n: usize, m: usize, seed: u64) -> Graph {
+    let mut graph = nodes(n);
+    let mut rng = Rng::new(seed);
+    let m = m.max(1);
+    let seeds = (m + 1).min(n);
+    // Every node once per link it has, so a uniform pick is by degree
+    let mut ends: Vec<NodeId> = vec![];
+    for b in 0..seeds as NodeId {
+        for a in 0..b {
+            graph.add_edge(a, b);
+            ends.extend([a, b]);
+        }
+    }
+    for new in seeds..n {
+        let mut targets: Vec<NodeId> = Vec::with_capacity(m);
+        while targets.len() < m {
+            let target = ends[(rng.next_u64() % ends.len() as u64) as usize];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for target in targets {
+            graph.add_edge(target, new as NodeId);
+            ends.extend([target, new as NodeId]);
+        }
+    }
+    layout::initial_placement(&mut graph);
+    graph
+}
+
+/// `width` by `height` grid, each node linked to its right and lower
+/// neighbours. Node `x + y * width` is at column `x` of row `y`.
+pub fn grid(width: usize, height: usize) -> Graph {
+    let mut graph = nodes(width * height);
+    for i in 0..width * height {
+        if i % width + 1 < width {
+            graph.add_edge(i as NodeId, i as NodeId + 1);
+        }
+        if i + width < width * height {
+            graph.add_edge(i as NodeId, (i + width) as NodeId);
+        }
+    }
+    layout::initial_placement(&mut graph);
+    graph
+}
+
+/// Tree with every node down to `depth` having `fanout` children, each edge
+/// pointing from parent to child. Nodes are numbered level by level from
+/// the root, 0.
+pub fn balanced_tree(depth: usize, fanout: usize) -> Graph {
+    let n = Generator::Tree { depth, fanout }
+        .node_count()
+        .expect("tree size fits in memory");
+    let mut graph = nodes(n);
+    for child in 1..n {
+        graph.add_edge(((child - 1) / fanout) as NodeId, child as NodeId);
+    }
+    layout::initial_placement(&mut graph);
+    graph
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithms;
+
+    fn degrees(graph: &Graph) -> Vec<usize> {
+        let mut degrees = vec![0; graph.nodes.len()];
+        for edge in &graph.edges {
+            degrees[edge.a_id as usize] += 1;
+            degrees[edge.b_id as usize] += 1;
+        }
+        degrees
+    }
+
+    #[test]
+    fn generated_graphs_have_their_shape() {
+        let lattice = grid(4, 3);
+        assert_eq!((lattice.nodes.len(), lattice.edges.len()), (12, 17));
+        let tree = balanced_tree(3, 2);
+        assert_eq!((tree.nodes.len(), tree.edges.len()), (15, 14));
+        assert_eq!(degrees(&tree)[0], 2);
+        assert_eq!(balanced_tree(4, 0).nodes.len(), 1);
+        assert_eq!(
+            algorithms::connected_component(tree.nodes.len(), &tree.edges, [0]).len(),
+            15
+        );
+
+        let complete = random_gnp(6, 1.0, 1);
+        assert_eq!(complete.edges.len(), 15);
+        assert!(random_gnp(50, 0.0, 1).edges.is_empty());
+        assert!(random_gnp(100, 1e-17, 1).edges.is_empty(), "tiny odds");
+        assert!(random_gnp(100, f32::MIN_POSITIVE, 1).edges.is_empty());
+        let sparse = random_gnp(400, 0.05, 7);
+        let expected = 0.05 * 400.0 * 399.0 / 2.0;
+        assert!((sparse.edges.len() as f32 - expected).abs() < expected * 0.1);
+        assert!(sparse.edges.iter().all(|e| e.a_id < e.b_id));
+        assert_eq!(
+            random_gnp(400, 0.05, 7).edges.len(),
+            sparse.edges.len(),
+            "seeded"
+        );
+
+        let scale_free = barabasi_albert(300, 2, 3);
+        assert_eq!(scale_free.edges.len(), 3 + 2 * 297);
+        let degrees = degrees(&scale_free);
+        assert!(degrees.iter().all(|d| *d >= 2));
+        assert!(*degrees.iter().max().unwrap() > 20, "hubs emerge");
+        assert!(barabasi_albert(0, 2, 1).nodes.is_empty());
+        assert_eq!(barabasi_albert(2, 3, 1).edges.len(), 1);
+
+        assert!(Generator::DEMOS.iter().all(|demo| demo.check().is_ok()));
+        assert!(Generator::Grid {
+            width: usize::MAX,
+            height: 2
+        }
+        .check()
+        .is_err());
+        // Counted without walking every level
+        for fanout in [0, 1] {
+            let tree = Generator::Tree {
+                depth: usize::MAX,
+                fanout,
+            };
+            assert_eq!(tree.check().is_ok(), fanout == 0);
+        }
+        assert!(Generator::Tree {
+            depth: 100_000_000_000,
+            fanout: 1
+        }
+        .check()
+        .is_err());
+        assert!(Generator::Tree {
+            depth: 1_000_000,
+            fanout: 2
+        }
+        .check()
+        .is_err());
+        assert!(Generator::Random {
+            nodes: 100_000,
+            p: 0.5
+        }
+        .check()
+        .is_err());
+    }
+}
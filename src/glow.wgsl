@@ -0,0 +1,8 @@
+// Brightening shared by nodes and edges, included with `#include "glow.wgsl"`
+
+// How far a glowing node or edge's color moves towards white
+let GLOW_LIFT: f32 = 0.45;
+
+fn brighten(color: vec4<f32>, glow: f32) -> vec4<f32> {
+    return vec4<f32>(mix(color.rgb, vec3<f32>(1.0), GLOW_LIFT * glow), color.a);
+}
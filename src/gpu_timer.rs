@@ -0,0 +1,225 @@
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+/// Names of the parts of a frame `GpuTimings::phases` times, in order
+pub const PHASES: [&str; 5] = ["edges", "nodes", "labels", "overlay", "resolve"];
+/// Weight of the latest frame in the running averages
+const SMOOTHING: f32 = 0.1;
+
+/// Points in a frame a timestamp is written at, in order, which is also
+/// their query index when every one is written. Hulls count as edges,
+/// annotations as labels, and debug drawing, resolving MSAA and upscaling
+/// as overlay, whose pass comes after them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mark {
+    /// Before the render pass, so clearing the target is timed
+    Begin,
+    PassStart,
+    Edges,
+    Nodes,
+    Labels,
+    Overlay,
+    /// After the overlay pass has ended
+    End,
+}
+
+/// GPU milliseconds a frame takes, averaged over recent frames
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GpuTimings {
+    pub total: f32,
+    /// Each of `PHASES`, when the adapter can write timestamps inside a pass.
+    /// `resolve` is the part of the frame outside draws: clearing, loading
+    /// and storing the targets.
+    pub phases: Option<[f32; PHASES.len()]>,
+}
+
+impl GpuTimings {
+    /// Timings from one frame's raw timestamps, `period` nanoseconds apart.
+    /// Takes either every `Mark`'s timestamp or just `Begin` and `End`'s.
+    /// `None` when they don't run forwards, as when the GPU's clock reset.
+    pub fn from_ticks(ticks: &[u64], period: f32) -> Option<Self> {
+        let ms = |from: usize, to: usize| -> Option<f32> {
+            Some(ticks[to].checked_sub(ticks[from])? as f32 * period / 1e6)
+        };
+        match ticks.len() {
+            2 => Some(Self {
+                total: ms(0, 1)?,
+                phases: None,
+            }),
+            7 => Some(Self {
+                total: ms(0, 6)?,
+                phases: Some([
+                    ms(1, 2)?,
+                    ms(2, 3)?,
+                    ms(3, 4)?,
+                    ms(4, 5)?,
+                    ms(0, 1)? + ms(5, 6)?,
+                ]),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Moves the averages `SMOOTHING` of the way towards `frame`
+    fn blend(&mut self, frame: Self) {
+        let blend = |average: &mut f32, latest: f32| *average += (latest - *average) * SMOOTHING;
+        blend(&mut self.total, frame.total);
+        match (&mut self.phases, frame.phases) {
+            (Some(phases), Some(latest)) => {
+                for (average, latest) in phases.iter_mut().zip(latest) {
+                    blend(average, latest);
+                }
+            }
+            (phases, latest) => *phases = latest,
+        }
+    }
+}
+
+/// Measures how long the GPU spends on each part of a frame with timestamp
+/// queries, for telling whether MSAA, labels or the sheer number of
+/// instances is what holds a weak GPU back. Results come back a few frames
+/// late without stalling; frames drawn while the last one's are still on
+/// their way aren't timed.
+pub struct GpuTimer {
+    queries: wgpu::QuerySet,
+    /// Where the queries are resolved to and read back from
+    readback: wgpu::Buffer,
+    /// Whether timestamps can be written between draws, or only around the
+    /// render pass
+    inside_passes: bool,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Mapping of `readback` requested after the last timed frame
+    pending: Option<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    pub timings: Option<GpuTimings>,
+}
+
+impl GpuTimer {
+    /// Features the device is asked for, when the adapter has them
+    pub const FEATURES: wgpu::Features =
+        wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::WRITE_TIMESTAMP_INSIDE_PASSES);
+
+    /// `None` when the device wasn't given timestamp queries
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        let features = device.features();
+        if !features.contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let inside_passes = features.contains(wgpu::Features::WRITE_TIMESTAMP_INSIDE_PASSES);
+        let count = if inside_passes { 7 } else { 2 };
+        let queries = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Readback Buffer"),
+            size: count as u64 * std::mem::size_of::<u64>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            queries,
+            readback,
+            inside_passes,
+            period: queue.get_timestamp_period(),
+            pending: None,
+            timings: None,
+        })
+    }
+
+    fn count(&self) -> u32 {
+        if self.inside_passes {
+            7
+        } else {
+            2
+        }
+    }
+
+    /// Picks up the results of the last timed frame if they're in, returns
+    /// whether the next frame can be timed
+    pub fn collect(&mut self, device: &wgpu::Device) -> bool {
+        let Some(pending) = &self.pending else {
+            return true;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        match pending.try_recv() {
+            Err(TryRecvError::Empty) => return false,
+            Ok(Ok(())) => {
+                let frame = {
+                    let data = self.readback.slice(..).get_mapped_range();
+                    let ticks: &[u64] = bytemuck::cast_slice(&data);
+                    GpuTimings::from_ticks(ticks, self.period)
+                };
+                self.readback.unmap();
+                if let Some(frame) = frame {
+                    self.timings.get_or_insert(frame).blend(frame);
+                }
+            }
+            Ok(Err(err)) => log::warn!("GPU timings were lost: {err}"),
+            Err(TryRecvError::Disconnected) => log::warn!("GPU timings were dropped"),
+        }
+        self.pending = None;
+        true
+    }
+
+    /// Writes the timestamp for `mark` between passes
+    pub fn write(&self, encoder: &mut wgpu::CommandEncoder, mark: Mark) {
+        match mark {
+            Mark::Begin => encoder.write_timestamp(&self.queries, 0),
+            Mark::End => encoder.write_timestamp(&self.queries, self.count() - 1),
+            _ => debug_assert!(false, "{mark:?} is written inside the pass"),
+        }
+    }
+
+    /// Writes the timestamp for `mark` inside the render pass, if the
+    /// adapter can
+    pub fn write_in_pass(&self, pass: &mut wgpu::RenderPass, mark: Mark) {
+        if self.inside_passes {
+            pass.write_timestamp(&self.queries, mark as u32);
+        }
+    }
+
+    /// Copies the frame's timestamps out, after `Mark::End`
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.queries, 0..self.count(), &self.readback, 0);
+    }
+
+    /// Asks for the timestamps once the frame they were resolved in has
+    /// been submitted
+    pub fn request(&mut self) {
+        let (tx, rx) = channel();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+        self.pending = Some(rx);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamps_become_milliseconds() {
+        // A tick a microsecond apart
+        let ticks = [0, 1000, 3000, 7000, 8000, 8500, 10000];
+        let timings = GpuTimings::from_ticks(&ticks, 1000.0).unwrap();
+        assert_eq!(timings.total, 10.0);
+        assert_eq!(timings.phases, Some([2.0, 4.0, 1.0, 0.5, 2.5]));
+        let total = GpuTimings::from_ticks(&[5, 2005], 1000.0).unwrap();
+        assert_eq!((total.total, total.phases), (2.0, None));
+        assert_eq!(GpuTimings::from_ticks(&[10, 5], 1.0), None);
+        assert_eq!(GpuTimings::from_ticks(&[1, 2, 3], 1.0), None);
+
+        // One slow frame only nudges the averages
+        let mut average = timings;
+        average.blend(GpuTimings {
+            total: 20.0,
+            phases: Some([12.0, 4.0, 1.0, 0.5, 2.5]),
+        });
+        assert!((average.total - 11.0).abs() < 1e-4);
+        assert!((average.phases.unwrap()[0] - 3.0).abs() < 1e-4);
+    }
+}
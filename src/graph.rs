@@ -0,0 +1,805 @@
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use cgmath::{vec4, One};
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    edge::{Edge, DEFAULT_EDGE_COLOR, DEFAULT_LINE_WIDTH},
+    formats,
+    node::Node,
+};
+
+/// Index of a node in its graph
+pub type NodeId = u32;
+
+/// Attributes checked, in order, when looking a node up by name
+pub const NAME_ATTRS: &[&str] = &["label", "name", "id"];
+
+pub const DEFAULT_NODE_SIZE: f32 = 50.0;
+
+/// A graph that isn't attached to a window: what importers produce and what
+/// gets loaded into `State` in one go
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        (self.nodes.len() - 1) as NodeId
+    }
+
+    /// Reads D3 node-link JSON, see `formats::json::NodeLink`. Other formats
+    /// go through `formats::load`.
+    pub fn load_json<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        formats::load_with(path, &formats::json::NodeLink)
+    }
+
+    /// Adds a default looking node with a `label` attribute, and `name` as
+    /// its external id
+    pub fn add_named_node(&mut self, name: &str) -> NodeId {
+        let mut node = Node::new(
+            (DEFAULT_NODE_SIZE, DEFAULT_NODE_SIZE),
+            (0.0, 0.0, 0.0),
+            cgmath::Quaternion::one(),
+            vec4(1.0, 1.0, 1.0, 1.0),
+        );
+        node.attributes.insert("label".into(), name.into());
+        node.external_id = Some(name.into());
+        self.add_node(node)
+    }
+
+    /// Looks up a node by name, adding it if it doesn't exist yet
+    pub fn node_or_insert(&mut self, name: &str) -> NodeId {
+        match self.node_by_name(name) {
+            Some(id) => id,
+            None => self.add_named_node(name),
+        }
+    }
+
+    /// Adds a default looking edge between two existing nodes
+    pub fn add_edge(&mut self, a: NodeId, b: NodeId) -> u32 {
+        let edge = Edge::from_nodes(
+            (&self.nodes[a as usize], a),
+            (&self.nodes[b as usize], b),
+            DEFAULT_EDGE_COLOR,
+            DEFAULT_LINE_WIDTH,
+        );
+        self.edges.push(edge);
+        (self.edges.len() - 1) as u32
+    }
+
+    pub fn node_by_name(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node_name(node).as_deref() == Some(name))
+            .map(|i| i as NodeId)
+    }
+
+    /// Every node's external id for writing the graph out, `None` unless all
+    /// of them have one and no two share it
+    pub fn stable_ids(&self) -> Option<Vec<&str>> {
+        let ids: Vec<&str> = self
+            .nodes
+            .iter()
+            .map(|node| node.external_id.as_deref())
+            .collect::<Option<_>>()?;
+        let unique: BTreeSet<&str> = ids.iter().copied().collect();
+        (unique.len() == ids.len()).then_some(ids)
+    }
+
+    /// Name used to match this graph's nodes with another graph's, falls back
+    /// to the node's index for unnamed nodes
+    pub fn node_key(&self, id: NodeId) -> String {
+        node_name(&self.nodes[id as usize]).unwrap_or_else(|| format!("#{id}"))
+    }
+
+    /// Whether every edge connects nodes the graph has, which a graph put
+    /// together by hand or by a faulty importer might not
+    pub fn check(&self) -> Result<(), String> {
+        let n = self.nodes.len();
+        let dangling = |e: &Edge| e.a_id as usize >= n || e.b_id as usize >= n;
+        match self.edges.iter().position(dangling) {
+            Some(i) => {
+                let edge = &self.edges[i];
+                Err(format!(
+                    "edge {i} connects nodes {} and {}, but there are only {n}",
+                    edge.a_id, edge.b_id
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Applies every change in `mutation`, or none of them if it refers to a
+    /// node or edge that doesn't exist
+    pub fn apply(&mut self, mutation: GraphMutation) -> Result<Remap, String> {
+        let node_count = self.nodes.len() + mutation.add_nodes.len();
+        let edge_count = self.edges.len() + mutation.add_edges.len();
+        let node_ids = mutation
+            .update_nodes
+            .iter()
+            .map(|(id, _)| id)
+            .chain(&mutation.remove_nodes)
+            .chain(
+                (mutation.add_edges.iter())
+                    .chain(mutation.update_edges.iter().map(|(_, e)| e))
+                    .flat_map(|e| [&e.a_id, &e.b_id]),
+            );
+        for id in node_ids {
+            if *id as usize >= node_count {
+                return Err(format!("no node {id}"));
+            }
+        }
+        let edge_ids =
+            (mutation.update_edges.iter().map(|(id, _)| id)).chain(mutation.remove_edges.last());
+        for id in edge_ids {
+            if *id as usize >= edge_count {
+                return Err(format!("no edge {id}"));
+            }
+        }
+
+        for (id, node) in mutation.update_nodes {
+            self.nodes[id as usize] = node;
+        }
+        self.nodes.extend(mutation.add_nodes);
+        self.edges.extend(mutation.add_edges);
+        for (id, edge) in mutation.update_edges {
+            self.edges[id as usize] = edge;
+        }
+
+        let nodes = compact(&mut self.nodes, |i, _| {
+            !mutation.remove_nodes.contains(&(i as NodeId))
+        });
+        let edges = compact(&mut self.edges, |i, edge| {
+            !mutation.remove_edges.contains(&(i as u32))
+                && nodes[edge.a_id as usize].is_some()
+                && nodes[edge.b_id as usize].is_some()
+        });
+        for edge in self.edges.iter_mut() {
+            edge.a_id = nodes[edge.a_id as usize].unwrap();
+            edge.b_id = nodes[edge.b_id as usize].unwrap();
+        }
+        self.sync_edges();
+        Ok(Remap { nodes, edges })
+    }
+
+    /// Puts nodes and edges removed by `apply` back under the ids they had,
+    /// each list lowest id first. Edges refer to nodes by those ids too.
+    /// Whatever is in the way moves up, the returned map says where to.
+    pub fn reinsert(&mut self, nodes: Vec<(NodeId, Node)>, edges: Vec<(u32, Edge)>) -> Remap {
+        let node_ids = interleave(&mut self.nodes, nodes);
+        for edge in self.edges.iter_mut() {
+            edge.a_id = node_ids[edge.a_id as usize];
+            edge.b_id = node_ids[edge.b_id as usize];
+        }
+        let edge_ids = interleave(&mut self.edges, edges);
+        self.sync_edges();
+        Remap {
+            nodes: node_ids.into_iter().map(Some).collect(),
+            edges: edge_ids.into_iter().map(Some).collect(),
+        }
+    }
+
+    /// Collapses parallel edges, see `EdgeMerge`, and returns how many went
+    pub fn merge_parallel_edges(&mut self, merge: &EdgeMerge) -> usize {
+        let (edges, removed) = merge.apply(&self.edges);
+        self.edges = edges;
+        removed
+    }
+
+    /// Refreshes the cached endpoint positions of every edge
+    pub fn sync_edges(&mut self) {
+        for edge in self.edges.iter_mut() {
+            edge.a_center = self.nodes[edge.a_id as usize].position;
+            edge.b_center = self.nodes[edge.b_id as usize].position;
+        }
+    }
+}
+
+/// Changes to a graph applied in one go, see `Graph::apply` and
+/// `State::apply_batch`. Ids refer to the graph before the batch, with added
+/// nodes numbered after the existing ones in order, so added edges can
+/// connect them. Updates and additions happen first, removals last; removing
+/// a node removes its edges too, and the remaining nodes and edges move down
+/// to fill the gaps.
+#[derive(Debug, Clone, Default)]
+pub struct GraphMutation {
+    pub add_nodes: Vec<Node>,
+    pub add_edges: Vec<Edge>,
+    /// Replaces a node, position included
+    pub update_nodes: Vec<(NodeId, Node)>,
+    /// Replaces an edge, such as to change its spring or markers
+    pub update_edges: Vec<(u32, Edge)>,
+    pub remove_nodes: BTreeSet<NodeId>,
+    pub remove_edges: BTreeSet<u32>,
+}
+
+impl GraphMutation {
+    pub fn is_empty(&self) -> bool {
+        self.add_nodes.is_empty()
+            && self.add_edges.is_empty()
+            && self.update_nodes.is_empty()
+            && self.update_edges.is_empty()
+            && self.remove_nodes.is_empty()
+            && self.remove_edges.is_empty()
+    }
+
+    pub fn removes(&self) -> bool {
+        !self.remove_nodes.is_empty() || !self.remove_edges.is_empty()
+    }
+}
+
+/// Where every node and edge of a batch ended up, indexed by the ids they had
+/// during it (see `GraphMutation`), `None` for removed ones
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Remap {
+    pub nodes: Vec<Option<NodeId>>,
+    pub edges: Vec<Option<u32>>,
+}
+
+impl Remap {
+    pub fn node(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes.get(id as usize).copied().flatten()
+    }
+
+    pub fn edge(&self, id: u32) -> Option<u32> {
+        self.edges.get(id as usize).copied().flatten()
+    }
+
+    /// The members of a set of node ids that survived, under their new ids
+    pub fn node_set(&self, ids: &BTreeSet<NodeId>) -> BTreeSet<NodeId> {
+        ids.iter().filter_map(|id| self.node(*id)).collect()
+    }
+}
+
+/// Read-only access to a graph wherever it lives, a plain `Graph`, a running
+/// `Simulation` or the window's `State`
+pub trait GraphView {
+    fn nodes(&self) -> &[Node];
+    fn edges(&self) -> &[Edge];
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes().get(id as usize)
+    }
+
+    /// Node with the external id `id` (see `Node::external_id`), the first
+    /// one if several share it
+    fn node_by_external_id(&self, id: &str) -> Option<NodeId> {
+        self.nodes()
+            .iter()
+            .position(|node| node.external_id.as_deref() == Some(id))
+            .map(|i| i as NodeId)
+    }
+
+    fn external_id(&self, id: NodeId) -> Option<&str> {
+        self.node(id)?.external_id.as_deref()
+    }
+
+    fn position(&self, id: NodeId) -> Option<cgmath::Vector2<f32>> {
+        self.node(id).map(|node| node.position.truncate())
+    }
+
+    /// Whether `id` is filtered out of the drawing, e.g. by the timeline.
+    /// Only a window hides anything.
+    fn node_hidden(&self, _id: NodeId) -> bool {
+        false
+    }
+
+    fn edge_hidden(&self, _index: usize) -> bool {
+        false
+    }
+
+    /// Every node with its id, hidden ones included
+    fn iter_nodes(&self) -> impl Iterator<Item = NodeRef<'_>> {
+        self.nodes().iter().enumerate().map(|(i, node)| NodeRef {
+            id: i as NodeId,
+            node,
+            hidden: self.node_hidden(i as NodeId),
+        })
+    }
+
+    /// Every edge with its index, hidden ones included
+    fn iter_edges(&self) -> impl Iterator<Item = EdgeRef<'_>> {
+        self.edges()
+            .iter()
+            .enumerate()
+            .map(|(index, edge)| EdgeRef {
+                index,
+                edge,
+                hidden: self.edge_hidden(index),
+            })
+    }
+
+    /// Whether an edge from `from` to `to` would be new. One running the
+    /// other way doesn't count as a duplicate.
+    fn check_edge(&self, from: NodeId, to: NodeId) -> Result<(), EdgeRejection> {
+        if from == to {
+            return Err(EdgeRejection::SelfLoop);
+        }
+        match self
+            .edges()
+            .iter()
+            .position(|edge| edge.a_id == from && edge.b_id == to)
+        {
+            Some(existing) => Err(EdgeRejection::Duplicate(existing as u32)),
+            None => Ok(()),
+        }
+    }
+
+    /// The nodes in `ids` and the edges between them, copied out attributes
+    /// and positions included. They're numbered in the order they had here,
+    /// ids that don't exist are skipped.
+    fn subgraph(&self, ids: &BTreeSet<NodeId>) -> Graph {
+        let mut kept = vec![None; self.nodes().len()];
+        let mut graph = Graph::new();
+        for &id in ids {
+            if let Some(node) = self.node(id) {
+                kept[id as usize] = Some(graph.add_node(node.clone()));
+            }
+        }
+        graph.edges = self
+            .edges()
+            .iter()
+            .filter_map(|edge| {
+                let a = kept[edge.a_id as usize]?;
+                let b = kept[edge.b_id as usize]?;
+                Some(Edge {
+                    a_id: a,
+                    b_id: b,
+                    ..edge.clone()
+                })
+            })
+            .collect();
+        graph
+    }
+
+    /// Nodes sharing an edge with `id`, once per edge
+    fn neighbors(&self, id: NodeId) -> Vec<NodeId> {
+        self.edges()
+            .iter()
+            .filter_map(|edge| {
+                if edge.a_id == id {
+                    Some(edge.b_id)
+                } else if edge.b_id == id {
+                    Some(edge.a_id)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl GraphView for Graph {
+    fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+}
+
+/// Why an edge wasn't created, see `GraphView::check_edge`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeRejection {
+    /// Both ends are the same node
+    SelfLoop,
+    /// The edge at this index already connects them
+    Duplicate(u32),
+}
+
+impl std::fmt::Display for EdgeRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeRejection::SelfLoop => write!(f, "a node can't be connected to itself"),
+            EdgeRejection::Duplicate(edge) => write!(f, "edge {edge} already connects them"),
+        }
+    }
+}
+
+/// Edge attribute counting the parallel edges merged into an edge, see
+/// `EdgeMerge`
+pub const MULTIPLICITY_ATTR: &str = "multiplicity";
+
+/// How the weights of merged edges combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Max,
+    Mean,
+}
+
+impl Aggregate {
+    fn combine(self, values: &[f32]) -> f32 {
+        match self {
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Max => values.iter().copied().fold(f32::MIN, f32::max),
+            Aggregate::Mean => values.iter().sum::<f32>() / values.len() as f32,
+        }
+    }
+}
+
+impl std::fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Max => "max",
+            Aggregate::Mean => "mean",
+        })
+    }
+}
+
+/// Collapses parallel edges, those running from the same node to the same
+/// node, into the first of them. The survivor's `weight` attribute combines
+/// theirs, skipping edges without one, and `MULTIPLICITY_ATTR` says how many
+/// edges it stands for. Its other attributes and looks are its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeMerge {
+    pub weight: String,
+    pub aggregate: Aggregate,
+}
+
+impl EdgeMerge {
+    /// `edges` folded into the first one
+    pub fn merge(&self, edges: &[&Edge]) -> Edge {
+        let mut merged = edges[0].clone();
+        let number = |edge: &&Edge, attr: &str| edge.attributes.get(attr)?.as_number();
+        let weights: Vec<f32> = edges
+            .iter()
+            .filter_map(|edge| number(edge, &self.weight))
+            .collect();
+        if !weights.is_empty() {
+            merged.attributes.insert(
+                self.weight.clone(),
+                AttrValue::Number(self.aggregate.combine(&weights)),
+            );
+        }
+        // Merging merged edges again adds up what they already stood for
+        let multiplicity: f32 = edges
+            .iter()
+            .map(|edge| number(edge, MULTIPLICITY_ATTR).unwrap_or(1.0))
+            .sum();
+        merged
+            .attributes
+            .insert(MULTIPLICITY_ATTR.into(), AttrValue::Number(multiplicity));
+        merged
+    }
+
+    /// Every edge with parallels merged, in the order of their first edges,
+    /// and how many edges went away. Edges that had no parallels are kept as
+    /// they are, other than counting once.
+    pub fn apply(&self, edges: &[Edge]) -> (Vec<Edge>, usize) {
+        let mut groups: Vec<Vec<&Edge>> = vec![];
+        let mut group_of: BTreeMap<(NodeId, NodeId), usize> = BTreeMap::new();
+        for edge in edges {
+            match group_of.entry((edge.a_id, edge.b_id)) {
+                Entry::Occupied(group) => groups[*group.get()].push(edge),
+                Entry::Vacant(group) => {
+                    group.insert(groups.len());
+                    groups.push(vec![edge]);
+                }
+            }
+        }
+        let merged: Vec<Edge> = groups.iter().map(|group| self.merge(group)).collect();
+        let removed = edges.len() - merged.len();
+        (merged, removed)
+    }
+}
+
+/// A node as seen through a `GraphView`
+#[derive(Debug, Clone, Copy)]
+pub struct NodeRef<'a> {
+    pub id: NodeId,
+    pub node: &'a Node,
+    pub hidden: bool,
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn position(&self) -> cgmath::Vector2<f32> {
+        self.node.position.truncate()
+    }
+
+    /// Radius of the drawn disk, in world units
+    pub fn radius(&self) -> f32 {
+        self.node.radius()
+    }
+
+    pub fn attributes(&self) -> &'a Attributes {
+        &self.node.attributes
+    }
+
+    pub fn name(&self) -> Option<String> {
+        node_name(self.node)
+    }
+}
+
+/// An edge as seen through a `GraphView`
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeRef<'a> {
+    pub index: usize,
+    pub edge: &'a Edge,
+    pub hidden: bool,
+}
+
+impl<'a> EdgeRef<'a> {
+    pub fn source(&self) -> NodeId {
+        self.edge.a_id
+    }
+
+    pub fn target(&self) -> NodeId {
+        self.edge.b_id
+    }
+
+    /// Endpoint positions as of the last frame
+    pub fn endpoints(&self) -> (cgmath::Vector2<f32>, cgmath::Vector2<f32>) {
+        (self.edge.a_center.truncate(), self.edge.b_center.truncate())
+    }
+
+    pub fn attributes(&self) -> &'a Attributes {
+        &self.edge.attributes
+    }
+}
+
+/// Keeps the items `keep` accepts, returns the new index of every old one
+fn compact<T>(items: &mut Vec<T>, keep: impl Fn(usize, &T) -> bool) -> Vec<Option<u32>> {
+    let mut next = 0;
+    let remap: Vec<_> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            keep(i, item).then(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect();
+    let mut i = 0;
+    items.retain(|_| {
+        i += 1;
+        remap[i - 1].is_some()
+    });
+    remap
+}
+
+/// Merges `items` into `list` at their indices, which are sorted, and
+/// returns where each item already in the list ended up
+fn interleave<T>(list: &mut Vec<T>, items: Vec<(u32, T)>) -> Vec<u32> {
+    let kept = std::mem::take(list);
+    let mut moved = Vec::with_capacity(kept.len());
+    let mut items = items.into_iter().peekable();
+    for item in kept {
+        while let Some((_, restored)) = items.next_if(|(id, _)| *id as usize == list.len()) {
+            list.push(restored);
+        }
+        moved.push(list.len() as u32);
+        list.push(item);
+    }
+    list.extend(items.map(|(_, item)| item));
+    moved
+}
+
+/// Nodes by external id, for looking them up faster than
+/// `GraphView::node_by_external_id` can
+pub fn external_ids(nodes: &[Node]) -> BTreeMap<String, NodeId> {
+    let mut ids = BTreeMap::new();
+    for (i, node) in (0..).zip(nodes) {
+        if let Some(id) = &node.external_id {
+            ids.entry(id.clone()).or_insert(i);
+        }
+    }
+    ids
+}
+
+/// The node's first name-like attribute (see `NAME_ATTRS`)
+pub fn node_name(node: &Node) -> Option<String> {
+    NAME_ATTRS
+        .iter()
+        .find_map(|attr| match node.attributes.get(*attr)? {
+            AttrValue::Text(s) => Some(s.clone()),
+            AttrValue::Number(n) => Some(n.to_string()),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn refs_follow_the_graph() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("a");
+        let b = graph.add_named_node("b");
+        graph.nodes[b as usize].position = cgmath::vec3(3.0, 4.0, 0.0);
+        graph.add_edge(a, b);
+        graph.sync_edges();
+
+        let nodes: Vec<_> = graph.iter_nodes().collect();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].id, b);
+        assert_eq!(nodes[1].name().as_deref(), Some("b"));
+        assert_eq!(nodes[1].position(), graph.position(b).unwrap());
+        assert!(nodes.iter().all(|node| !node.hidden));
+
+        let edge = graph.iter_edges().next().unwrap();
+        assert_eq!((edge.source(), edge.target()), (a, b));
+        assert_eq!(edge.endpoints().1, cgmath::vec2(3.0, 4.0));
+
+        assert_eq!(graph.check_edge(a, a), Err(EdgeRejection::SelfLoop));
+        assert_eq!(graph.check_edge(a, b), Err(EdgeRejection::Duplicate(0)));
+        assert_eq!(graph.check_edge(b, a), Ok(()));
+
+        assert!(graph.check().is_ok());
+        graph.edges[0].b_id = 2;
+        assert!(graph.check().is_err());
+    }
+
+    #[test]
+    fn external_ids_outlive_indices() {
+        let mut graph = Graph::new();
+        for name in ["svc-auth", "svc-db", "svc-web"] {
+            graph.add_named_node(name);
+        }
+        assert_eq!(
+            graph.stable_ids(),
+            Some(vec!["svc-auth", "svc-db", "svc-web"])
+        );
+        graph
+            .apply(GraphMutation {
+                remove_nodes: [0].into(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(graph.node_by_external_id("svc-web"), Some(1));
+        assert_eq!(graph.node_by_external_id("svc-auth"), None);
+
+        // Shared ids can't be written out, lookups find the first
+        graph.add_named_node("svc-db");
+        assert_eq!(graph.stable_ids(), None);
+        assert_eq!(external_ids(&graph.nodes)["svc-db"], 0);
+    }
+
+    #[test]
+    fn batches_renumber_what_remains() {
+        let mut graph = Graph::new();
+        for name in ["a", "b", "c"] {
+            graph.add_named_node(name);
+        }
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        let mut d = graph.nodes[0].clone();
+        d.attributes.insert("label".into(), "d".into());
+        d.position = cgmath::vec3(5.0, 0.0, 0.0);
+
+        let mut mutation = GraphMutation {
+            add_nodes: vec![d],
+            remove_nodes: BTreeSet::from([1]),
+            ..Default::default()
+        };
+        // Connects two nodes added or kept by this batch
+        mutation.add_edges.push(graph.edges[0].clone());
+        mutation.add_edges[0].b_id = 3;
+        let remap = graph.apply(mutation).unwrap();
+
+        assert_eq!(remap.nodes, [Some(0), None, Some(1), Some(2)]);
+        // Both old edges touched `b`
+        assert_eq!(remap.edges, [None, None, Some(0)]);
+        assert_eq!(graph.node_by_name("d"), Some(2));
+        assert_eq!((graph.edges[0].a_id, graph.edges[0].b_id), (0, 2));
+        assert_eq!(graph.edges[0].b_center, cgmath::vec3(5.0, 0.0, 0.0));
+
+        let bad = GraphMutation {
+            remove_edges: BTreeSet::from([1]),
+            update_nodes: vec![(7, graph.nodes[0].clone())],
+            ..Default::default()
+        };
+        assert!(graph.apply(bad).is_err());
+        assert_eq!(graph.nodes.len(), 3, "a rejected batch changes nothing");
+
+        let mut stronger = graph.edges[0].clone();
+        stronger.strength = 2.0;
+        let bad = GraphMutation {
+            update_edges: vec![(1, stronger.clone())],
+            ..Default::default()
+        };
+        assert!(graph.apply(bad).is_err());
+        let remap = graph
+            .apply(GraphMutation {
+                update_edges: vec![(0, stronger)],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(remap.edge(0), Some(0));
+        assert_eq!(graph.edges[0].strength, 2.0);
+    }
+
+    #[test]
+    fn parallel_edges_merge() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("a");
+        let b = graph.add_named_node("b");
+        for (from, to, weight) in [
+            (a, b, Some(2.0)),
+            (b, a, None),
+            (a, b, Some(4.0)),
+            (a, b, None),
+        ] {
+            let edge = graph.add_edge(from, to);
+            if let Some(weight) = weight {
+                let attributes = &mut graph.edges[edge as usize].attributes;
+                attributes.insert("w".into(), AttrValue::Number(weight));
+            }
+        }
+        let number = |graph: &Graph, edge: usize, attr: &str| {
+            graph.edges[edge]
+                .attributes
+                .get(attr)
+                .and_then(|v| v.as_number())
+        };
+
+        let mut merge = EdgeMerge {
+            weight: "w".into(),
+            aggregate: Aggregate::Mean,
+        };
+        let mut mean = graph.clone();
+        assert_eq!(mean.merge_parallel_edges(&merge), 2);
+        // The reversed edge isn't a parallel
+        assert_eq!(mean.edges.len(), 2);
+        assert_eq!(number(&mean, 0, "w"), Some(3.0));
+        assert_eq!(number(&mean, 0, MULTIPLICITY_ATTR), Some(3.0));
+        assert_eq!(number(&mean, 1, "w"), None);
+        assert_eq!(number(&mean, 1, MULTIPLICITY_ATTR), Some(1.0));
+
+        merge.aggregate = Aggregate::Sum;
+        graph.merge_parallel_edges(&merge);
+        assert_eq!(number(&graph, 0, "w"), Some(6.0));
+        merge.aggregate = Aggregate::Max;
+        graph.edges.push(graph.edges[0].clone());
+        graph.merge_parallel_edges(&merge);
+        assert_eq!(number(&graph, 0, "w"), Some(6.0));
+        assert_eq!(number(&graph, 0, MULTIPLICITY_ATTR), Some(6.0));
+    }
+
+    #[test]
+    fn subgraphs_keep_edges_inside() {
+        let mut graph = Graph::new();
+        let ids: Vec<NodeId> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| graph.add_named_node(name))
+            .collect();
+        graph.nodes[2].position = cgmath::vec3(3.0, 4.0, 0.0);
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (3, 1)] {
+            let edge = graph.add_edge(ids[a], ids[b]);
+            graph.edges[edge as usize]
+                .attributes
+                .insert("w".into(), AttrValue::Number(a as f32));
+        }
+        graph.sync_edges();
+
+        let sub = graph.subgraph(&BTreeSet::from([1, 2, 3, 9]));
+        let names: Vec<_> = (0..3).map(|id| sub.node_key(id)).collect();
+        assert_eq!(names, ["b", "c", "d"]);
+        assert_eq!(sub.position(1), Some(cgmath::vec2(3.0, 4.0)));
+        let edges: Vec<_> = sub
+            .edges
+            .iter()
+            .map(|e| (e.a_id, e.b_id, e.attributes["w"].clone()))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (0, 1, AttrValue::Number(1.0)),
+                (1, 2, AttrValue::Number(2.0)),
+                (2, 0, AttrValue::Number(3.0)),
+            ]
+        );
+        assert_eq!(sub.edges[0].b_center, cgmath::vec3(3.0, 4.0, 0.0));
+    }
+}
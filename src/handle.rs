@@ -0,0 +1,180 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex, PoisonError, RwLock, RwLockReadGuard,
+};
+
+use crate::{
+    edge::Edge,
+    graph::{Graph, GraphMutation, Remap},
+    node::Node,
+};
+
+/// A graph other threads can read and change while its owner, such as the
+/// viewer's `State` or a `Simulation`, keeps the real one. Readers see a copy
+/// the owner publishes now and then; changes are queued as batches and
+/// applied by the owner between frames, in the order they were submitted.
+/// Clones share the same graph.
+#[derive(Debug, Clone)]
+pub struct GraphHandle {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    graph: RwLock<Graph>,
+    /// Publishes so far
+    version: AtomicU64,
+    pending: Mutex<Vec<Pending>>,
+}
+
+/// A batch waiting for the owner to apply it, see `GraphHandle::drain`
+#[derive(Debug)]
+pub struct Pending {
+    mutation: GraphMutation,
+    done: Sender<Result<Remap, String>>,
+}
+
+impl Pending {
+    /// Applies the batch with `apply`, such as `State::apply_batch`, and
+    /// tells whoever submitted it how it went
+    pub fn run(self, apply: impl FnOnce(GraphMutation) -> Result<Remap, String>) -> bool {
+        let result = apply(self.mutation);
+        let ok = result.is_ok();
+        // The submitter may not be waiting for the outcome
+        let _ = self.done.send(result);
+        ok
+    }
+}
+
+impl GraphHandle {
+    pub fn new(graph: Graph) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                graph: RwLock::new(graph),
+                version: AtomicU64::new(0),
+                pending: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// The graph as of the owner's last `publish`. Publishing is skipped
+    /// while it's being read, so hold on to it only briefly.
+    pub fn read(&self) -> RwLockReadGuard<'_, Graph> {
+        // A reader that panicked can't have left the graph half written
+        self.shared
+            .graph
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// How many times the owner has published, for telling whether `read`
+    /// would show anything new
+    pub fn version(&self) -> u64 {
+        self.shared.version.load(Ordering::Acquire)
+    }
+
+    /// Queues `mutation` for the owner to apply. Its ids refer to the graph
+    /// as it is when it's applied, which may be after other batches, so
+    /// producers sharing a graph should look nodes up by their external ids.
+    /// The receiver gets the batch's `Remap`, or why it was turned down.
+    pub fn submit(&self, mutation: GraphMutation) -> Receiver<Result<Remap, String>> {
+        let (done, rx) = channel();
+        self.pending().push(Pending { mutation, done });
+        rx
+    }
+
+    /// For the owner: the batches submitted since the last call, oldest
+    /// first
+    pub fn drain(&self) -> Vec<Pending> {
+        std::mem::take(&mut *self.pending())
+    }
+
+    /// For the owner: copies its graph over the shared one. Returns false,
+    /// leaving the shared graph as it was, if a reader holds it, so the
+    /// owner never waits on one.
+    pub fn publish(&self, nodes: &[Node], edges: &[Edge]) -> bool {
+        let Ok(mut graph) = self.shared.graph.try_write() else {
+            return false;
+        };
+        graph.nodes.clear();
+        graph.nodes.extend_from_slice(nodes);
+        graph.edges.clear();
+        graph.edges.extend_from_slice(edges);
+        self.shared.version.fetch_add(1, Ordering::Release);
+        true
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, Vec<Pending>> {
+        self.shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{changes::Change, simulation::Simulation};
+
+    fn send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn other_threads_change_the_graph_through_the_owner() {
+        send_sync::<Graph>();
+        send_sync::<GraphMutation>();
+        send_sync::<GraphHandle>();
+        send_sync::<Simulation>();
+        send_sync::<Change>();
+
+        let mut sim = Simulation::seeded(Graph::new(), 1);
+        let handle = GraphHandle::new(sim.graph.clone());
+        let producer = {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                let mut graph = Graph::new();
+                let a = graph.add_named_node("a");
+                let b = graph.add_named_node("b");
+                graph.add_edge(a, b);
+                let added = handle.submit(GraphMutation {
+                    add_nodes: graph.nodes,
+                    add_edges: graph.edges,
+                    ..GraphMutation::default()
+                });
+                let rejected = handle.submit(GraphMutation {
+                    remove_nodes: [7].into(),
+                    ..GraphMutation::default()
+                });
+                (added, rejected)
+            })
+        };
+        let (added, rejected) = producer.join().unwrap();
+
+        let pending = handle.drain();
+        assert_eq!(pending.len(), 2);
+        let applied: Vec<bool> = pending
+            .into_iter()
+            .map(|pending| pending.run(|mutation| sim.apply(mutation)))
+            .collect();
+        assert_eq!(applied, [true, false]);
+        assert!(handle.drain().is_empty());
+        assert_eq!(added.recv().unwrap().unwrap().nodes, [Some(0), Some(1)]);
+        assert!(rejected.recv().unwrap().is_err());
+
+        // Readers see the owner's graph once it's published, not before
+        assert!(handle.read().nodes.is_empty());
+        {
+            let _reading = handle.read();
+            assert!(!handle.publish(&sim.graph.nodes, &sim.graph.edges));
+        }
+        assert!(handle.publish(&sim.graph.nodes, &sim.graph.edges));
+        assert_eq!(handle.version(), 1);
+        let reader = handle.clone();
+        let seen = std::thread::spawn(move || {
+            let graph = reader.read();
+            (graph.nodes.len(), graph.edges.len())
+        });
+        assert_eq!(seen.join().unwrap(), (2, 1));
+    }
+}
@@ -0,0 +1,94 @@
+use cgmath::{vec2, vec4};
+
+use crate::{
+    i18n::Catalog,
+    input::{EDIT_CONTROLS, FILE_CONTROLS, KEYMAP, POINTER_CONTROLS, VIEW_CONTROLS},
+    overlay::{text_rects, text_size, Rect},
+};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.8);
+const TITLE_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const KEY_COLOR: cgmath::Vector4<f32> = vec4(0.6, 0.8, 1.0, 1.0);
+const TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const HINT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.5);
+const GAP: f32 = 6.0;
+/// Space between the input and effect columns
+const COLUMN_GAP: f32 = 24.0;
+
+/// Centered panel listing every control, built from `POINTER_CONTROLS`,
+/// `EDIT_CONTROLS`, `VIEW_CONTROLS`, `FILE_CONTROLS` and `KEYMAP` so it
+/// can't drift from the actual bindings
+#[derive(Debug, Clone, Copy)]
+pub struct HelpOverlay {
+    pub padding: f32,
+}
+
+impl Default for HelpOverlay {
+    fn default() -> Self {
+        Self { padding: 16.0 }
+    }
+}
+
+impl HelpOverlay {
+    /// `(input, effect)` rows, pointer controls first
+    pub fn rows(strings: &Catalog) -> Vec<(String, String)> {
+        POINTER_CONTROLS
+            .iter()
+            .chain(EDIT_CONTROLS)
+            .chain(VIEW_CONTROLS)
+            .chain(FILE_CONTROLS)
+            .map(|(input, message)| (input.to_string(), strings.get(message).to_string()))
+            .chain(KEYMAP.iter().map(|binding| {
+                (
+                    binding.key.to_string(),
+                    strings.get(binding.message).to_string(),
+                )
+            }))
+            .collect()
+    }
+
+    pub fn rects(&self, width: f32, height: f32, strings: &Catalog) -> Vec<Rect> {
+        let title = strings.get("help-title");
+        let hint = strings.get("help-close");
+        let rows = Self::rows(strings);
+        let line = text_size("0", TEXT_PIXEL).y + GAP;
+        let key_width = rows
+            .iter()
+            .map(|(input, _)| text_size(input, TEXT_PIXEL).x)
+            .fold(0.0, f32::max);
+        let effect_width = rows
+            .iter()
+            .map(|(_, effect)| text_size(effect, TEXT_PIXEL).x)
+            .fold(0.0, f32::max);
+        let content = vec2(
+            (key_width + COLUMN_GAP + effect_width)
+                .max(text_size(title, TEXT_PIXEL).x)
+                .max(text_size(hint, TEXT_PIXEL).x),
+            // Title, a blank line, the rows, a blank line and the hint
+            line * (rows.len() + 4) as f32 - GAP,
+        );
+        let size = content + vec2(self.padding, self.padding) * 2.0;
+        let origin = (vec2(width, height) - size) / 2.0;
+
+        let mut rects = vec![Rect::new(origin, size, BACKGROUND_COLOR)];
+        let mut pos = origin + vec2(self.padding, self.padding);
+        rects.extend(text_rects(title, pos, TEXT_PIXEL, TITLE_COLOR));
+        pos.y += line * 2.0;
+        for (input, effect) in &rows {
+            rects.extend(text_rects(input, pos, TEXT_PIXEL, KEY_COLOR));
+            rects.extend(text_rects(
+                effect,
+                pos + vec2(key_width + COLUMN_GAP, 0.0),
+                TEXT_PIXEL,
+                TEXT_COLOR,
+            ));
+            pos.y += line;
+        }
+        pos.y += line;
+        rects.extend(text_rects(hint, pos, TEXT_PIXEL, HINT_COLOR));
+        rects
+    }
+}
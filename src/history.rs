@@ -0,0 +1,462 @@
+//! Undo and redo for edits to the graph. Every batch is recorded as a `Step`
+//! holding what it replaced, added and removed, which is enough to take it
+//! back without keeping copies of the whole graph around.
+
+use crate::{
+    edge::Edge,
+    graph::{Graph, GraphMutation, GraphView, NodeId, Remap},
+    node::Node,
+};
+
+/// Edits kept for undoing, the oldest are forgotten past this
+pub const MAX_UNDO: usize = 100;
+
+/// What one batch did to the graph (see `GraphMutation`), with the nodes and
+/// edges it replaced or removed so it can be reversed
+#[derive(Debug, Clone, Default)]
+pub struct Step {
+    /// Replaced nodes as `(id, before, after)`, which covers moves and
+    /// recoloring
+    updated: Vec<(NodeId, Node, Node)>,
+    /// Replaced edges, like `updated`
+    updated_edges: Vec<(u32, Edge, Edge)>,
+    /// Node and edge counts before anything was added
+    first_node: NodeId,
+    first_edge: u32,
+    added_nodes: Vec<Node>,
+    added_edges: Vec<Edge>,
+    /// Under the ids they had, lowest first. Edges include the ones that went
+    /// with their nodes.
+    removed_nodes: Vec<(NodeId, Node)>,
+    removed_edges: Vec<(u32, Edge)>,
+}
+
+impl Step {
+    /// What applying `mutation` to `graph` does. Ids that don't exist are
+    /// skipped, the batch is turned down anyway.
+    pub fn of(graph: &impl GraphView, mutation: &GraphMutation) -> Self {
+        let nodes: Vec<&Node> = graph.nodes().iter().chain(&mutation.add_nodes).collect();
+        let updated = mutation
+            .update_nodes
+            .iter()
+            .filter_map(|(id, after)| {
+                Some((*id, (*nodes.get(*id as usize)?).clone(), after.clone()))
+            })
+            .collect();
+        let edges: Vec<&Edge> = graph.edges().iter().chain(&mutation.add_edges).collect();
+        let updated_edges = mutation
+            .update_edges
+            .iter()
+            .filter_map(|(id, after)| {
+                Some((*id, (*edges.get(*id as usize)?).clone(), after.clone()))
+            })
+            .collect();
+        // Removal happens after updates, so removed nodes are saved updated
+        let removed_nodes = mutation
+            .remove_nodes
+            .iter()
+            .filter_map(|id| {
+                let node = mutation
+                    .update_nodes
+                    .iter()
+                    .rev()
+                    .find(|(updated, _)| updated == id)
+                    .map(|(_, node)| node)
+                    .or_else(|| nodes.get(*id as usize).copied())?;
+                Some((*id, node.clone()))
+            })
+            .collect();
+        let removed_edges = (0..)
+            .zip(edges)
+            .map(|(i, edge)| {
+                let updated = mutation.update_edges.iter().rev().find(|(id, _)| *id == i);
+                (i, updated.map_or(edge, |(_, edge)| edge))
+            })
+            .filter(|(i, edge)| {
+                mutation.remove_edges.contains(i)
+                    || mutation.remove_nodes.contains(&edge.a_id)
+                    || mutation.remove_nodes.contains(&edge.b_id)
+            })
+            .map(|(i, edge)| (i, edge.clone()))
+            .collect();
+        Self {
+            updated,
+            updated_edges,
+            first_node: graph.nodes().len() as NodeId,
+            first_edge: graph.edges().len() as u32,
+            added_nodes: mutation.add_nodes.clone(),
+            added_edges: mutation.add_edges.clone(),
+            removed_nodes,
+            removed_edges,
+        }
+    }
+
+    /// A node or edge appended to `graph`, which is about to be
+    pub fn added(graph: &impl GraphView, nodes: Vec<Node>, edges: Vec<Edge>) -> Self {
+        Self {
+            first_node: graph.nodes().len() as NodeId,
+            first_edge: graph.edges().len() as u32,
+            added_nodes: nodes,
+            added_edges: edges,
+            ..Default::default()
+        }
+    }
+
+    /// Nodes replaced outside of a batch, as `(id, before, after)`
+    pub fn updated(nodes: Vec<(NodeId, Node, Node)>) -> Self {
+        Self {
+            updated: nodes,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty()
+            && self.updated_edges.is_empty()
+            && self.added_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.removed_edges.is_empty()
+    }
+
+    /// Brings a graph from before the step to after it
+    fn redo(&self) -> Vec<Patch> {
+        vec![Patch::Batch(GraphMutation {
+            add_nodes: self.added_nodes.clone(),
+            add_edges: self.added_edges.clone(),
+            update_nodes: self
+                .updated
+                .iter()
+                .map(|(id, _, after)| (*id, after.clone()))
+                .collect(),
+            update_edges: self
+                .updated_edges
+                .iter()
+                .map(|(id, _, after)| (*id, after.clone()))
+                .collect(),
+            remove_nodes: self.removed_nodes.iter().map(|(id, _)| *id).collect(),
+            remove_edges: self.removed_edges.iter().map(|(id, _)| *id).collect(),
+        })]
+    }
+
+    /// Brings a graph from after the step back to before it, in reverse of
+    /// the order the batch went in: removals, additions, then updates
+    fn undo(&self) -> Vec<Patch> {
+        let node_count = self.first_node + self.added_nodes.len() as NodeId;
+        let edge_count = self.first_edge + self.added_edges.len() as u32;
+        vec![
+            Patch::Reinsert {
+                nodes: self.removed_nodes.clone(),
+                edges: self.removed_edges.clone(),
+            },
+            Patch::Batch(GraphMutation {
+                remove_nodes: (self.first_node..node_count).collect(),
+                remove_edges: (self.first_edge..edge_count).collect(),
+                ..Default::default()
+            }),
+            Patch::Batch(GraphMutation {
+                update_nodes: self
+                    .updated
+                    .iter()
+                    .rev()
+                    .map(|(id, before, _)| (*id, before.clone()))
+                    .collect(),
+                update_edges: self
+                    .updated_edges
+                    .iter()
+                    .rev()
+                    .map(|(id, before, _)| (*id, before.clone()))
+                    .collect(),
+                ..Default::default()
+            }),
+        ]
+    }
+}
+
+/// A change to the graph as `State` carries it out
+#[derive(Debug, Clone)]
+pub enum Patch {
+    Batch(GraphMutation),
+    /// Puts removed nodes and edges back under the ids they had, moving
+    /// whatever came after them up. See `Graph::reinsert`.
+    Reinsert {
+        nodes: Vec<(NodeId, Node)>,
+        edges: Vec<(u32, Edge)>,
+    },
+}
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Patch::Batch(mutation) => mutation.is_empty(),
+            Patch::Reinsert { nodes, edges } => nodes.is_empty() && edges.is_empty(),
+        }
+    }
+
+    pub fn apply_to(self, graph: &mut Graph) -> Result<Remap, String> {
+        match self {
+            Patch::Batch(mutation) => graph.apply(mutation),
+            Patch::Reinsert { nodes, edges } => Ok(graph.reinsert(nodes, edges)),
+        }
+    }
+}
+
+/// Everything one user action did, such as adding a node and connecting it
+#[derive(Debug, Clone, Default)]
+pub struct Edit {
+    steps: Vec<Step>,
+}
+
+impl Edit {
+    /// Patches taking the graph back to before the edit, in order
+    pub fn undo(&self) -> Vec<Patch> {
+        self.steps
+            .iter()
+            .rev()
+            .flat_map(Step::undo)
+            .filter(|patch| !patch.is_empty())
+            .collect()
+    }
+
+    /// Patches making the edit again, in order
+    pub fn redo(&self) -> Vec<Patch> {
+        self.steps
+            .iter()
+            .flat_map(Step::redo)
+            .filter(|patch| !patch.is_empty())
+            .collect()
+    }
+}
+
+/// Edits that can be undone and redone. Steps recorded between `begin` and
+/// `end` undo together.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    open: Option<Edit>,
+}
+
+impl History {
+    pub fn begin(&mut self) {
+        self.open.get_or_insert_with(Edit::default);
+    }
+
+    pub fn end(&mut self) {
+        if let Some(edit) = self.open.take() {
+            self.push(edit);
+        }
+    }
+
+    pub fn record(&mut self, step: Step) {
+        if step.is_empty() {
+            return;
+        }
+        match &mut self.open {
+            Some(edit) => edit.steps.push(step),
+            None => self.push(Edit { steps: vec![step] }),
+        }
+    }
+
+    /// Records `step` as part of the edit it follows from, the open one or
+    /// else the latest. Without either it followed from something that
+    /// can't be undone, such as loading a file, and is dropped.
+    pub fn amend(&mut self, step: Step) {
+        if step.is_empty() {
+            return;
+        }
+        if let Some(edit) = self.open.as_mut().or(self.undo.last_mut()) {
+            edit.steps.push(step);
+        }
+    }
+
+    fn push(&mut self, edit: Edit) {
+        if edit.steps.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push(edit);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    /// The latest edit, moved over to be redone. Its `undo` patches are up
+    /// to the caller.
+    pub fn undo(&mut self) -> Option<&Edit> {
+        let edit = self.undo.pop()?;
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    /// The latest undone edit, moved back to be undone again
+    pub fn redo(&mut self) -> Option<&Edit> {
+        let edit = self.redo.pop()?;
+        self.undo.push(edit);
+        self.undo.last()
+    }
+
+    /// Forgets every edit, for when the graph is replaced wholesale and the
+    /// ids they refer to mean nothing anymore
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Node names with their x, and edges by the names of their ends: what
+    /// undoing has to bring back exactly, order included
+    type Outline = (Vec<(String, f32)>, Vec<(String, String)>);
+
+    fn outline(graph: &Graph) -> Outline {
+        let nodes = (0..graph.nodes.len() as NodeId)
+            .map(|id| (graph.node_key(id), graph.nodes[id as usize].position.x))
+            .collect();
+        let edges = graph
+            .edges
+            .iter()
+            .map(|edge| (graph.node_key(edge.a_id), graph.node_key(edge.b_id)))
+            .collect();
+        (nodes, edges)
+    }
+
+    fn apply(graph: &mut Graph, history: &mut History, mutation: GraphMutation) {
+        history.record(Step::of(graph, &mutation));
+        graph.apply(mutation).unwrap();
+    }
+
+    fn patch(graph: &mut Graph, patches: Vec<Patch>) {
+        for patch in patches {
+            patch.apply_to(graph).unwrap();
+        }
+    }
+
+    fn named_node(name: &str) -> Vec<Node> {
+        let mut graph = Graph::new();
+        graph.add_named_node(name);
+        graph.nodes
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let mut graph = Graph::new();
+        let ids: Vec<NodeId> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| graph.add_named_node(name))
+            .collect();
+        for (i, a) in ids.iter().enumerate() {
+            graph.add_edge(*a, ids[(i + 1) % ids.len()]);
+        }
+        let mut history = History::default();
+        let start = outline(&graph);
+
+        // Removing b takes two edges along, moving c, d and their edges down
+        let mut moved = graph.nodes[2].clone();
+        moved.position.x = 10.0;
+        // d to the new node e
+        let mut edge = graph.edges[3].clone();
+        edge.b_id = 4;
+        apply(
+            &mut graph,
+            &mut history,
+            GraphMutation {
+                add_nodes: named_node("e"),
+                add_edges: vec![edge],
+                update_nodes: vec![(2, moved)],
+                remove_nodes: [1].into(),
+                ..Default::default()
+            },
+        );
+        let edited = outline(&graph);
+        assert_eq!(edited.0.len(), 4);
+        assert_eq!(edited.1.len(), 3);
+
+        patch(&mut graph, history.undo().unwrap().undo());
+        assert_eq!(outline(&graph), start);
+        assert!(history.undo().is_none());
+        patch(&mut graph, history.redo().unwrap().redo());
+        assert_eq!(outline(&graph), edited);
+        assert!(history.redo().is_none());
+
+        // Steps between begin and end undo as one, and a new edit drops
+        // whatever was undone before it
+        history.begin();
+        history.record(Step::added(&graph, named_node("f"), vec![]));
+        graph.add_named_node("f");
+        apply(
+            &mut graph,
+            &mut history,
+            GraphMutation {
+                remove_edges: [0].into(),
+                ..Default::default()
+            },
+        );
+        history.end();
+        patch(&mut graph, history.undo().unwrap().undo());
+        assert_eq!(outline(&graph), edited);
+        apply(
+            &mut graph,
+            &mut history,
+            GraphMutation {
+                remove_nodes: [0].into(),
+                ..Default::default()
+            },
+        );
+        assert!(!history.can_redo());
+        patch(&mut graph, history.undo().unwrap().undo());
+        patch(&mut graph, history.undo().unwrap().undo());
+        assert_eq!(outline(&graph), start);
+    }
+
+    #[test]
+    fn edge_updates_undo() {
+        let mut graph = Graph::new();
+        for name in ["a", "b", "c"] {
+            graph.add_named_node(name);
+        }
+        graph.add_edge(0, 1);
+        graph.add_edge(1, 2);
+        let mut history = History::default();
+        let strengths =
+            |graph: &Graph| -> Vec<f32> { graph.edges.iter().map(|e| e.strength).collect() };
+        let start = strengths(&graph);
+
+        // The second edge is updated and then goes with its node, so it
+        // comes back updated and undoing the update restores it
+        let mut edges = graph.edges.clone();
+        for edge in &mut edges {
+            edge.strength = 3.0;
+        }
+        apply(
+            &mut graph,
+            &mut history,
+            GraphMutation {
+                update_edges: edges
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, e)| (i as u32, e))
+                    .collect(),
+                remove_nodes: [2].into(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(strengths(&graph), [3.0]);
+        patch(&mut graph, history.undo().unwrap().undo());
+        assert_eq!(strengths(&graph), start);
+        patch(&mut graph, history.redo().unwrap().redo());
+        assert_eq!(strengths(&graph), [3.0]);
+    }
+}
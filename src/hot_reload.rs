@@ -0,0 +1,136 @@
+//! Rebuilding render pipelines when their shaders are edited, so tweaking a
+//! `.wgsl` file shows up without restarting. Only debug builds run from a
+//! checkout watch anything.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the shader directory is checked for edits
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Notices `.wgsl` files in a directory being modified, by polling their
+/// modification times
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: BTreeMap<String, SystemTime>,
+    last_poll: Instant,
+    pub interval: Duration,
+}
+
+impl ShaderWatcher {
+    /// Watches the crate's own `src` directory, where the shaders compiled
+    /// into the binary come from. `None` in release builds and the browser,
+    /// which have no sources to watch.
+    pub fn for_sources() -> Option<Self> {
+        if !cfg!(debug_assertions) || cfg!(target_arch = "wasm32") {
+            return None;
+        }
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        dir.is_dir().then(|| Self::new(dir))
+    }
+
+    /// Watches the shaders in `dir`, edits made before now don't count
+    pub fn new(dir: PathBuf) -> Self {
+        let modified = scan(&dir);
+        Self {
+            dir,
+            modified,
+            last_poll: Instant::now(),
+            interval: POLL_INTERVAL,
+        }
+    }
+
+    /// Shaders edited since the last call, by file name with their new
+    /// source. Checks at most once per `interval`, returning nothing between.
+    pub fn changed(&mut self) -> Vec<(String, String)> {
+        if self.last_poll.elapsed() < self.interval {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+        let modified = scan(&self.dir);
+        let changed = modified
+            .iter()
+            .filter(|(file, time)| self.modified.get(*file) != Some(time))
+            // Editors that save by replacing the file can leave it missing
+            // or empty for a moment, the next poll picks it up
+            .filter_map(|(file, _)| {
+                let source = std::fs::read_to_string(self.dir.join(file)).ok()?;
+                (!source.is_empty()).then(|| (file.clone(), source))
+            })
+            .collect::<Vec<_>>();
+        for (file, _) in &changed {
+            self.modified.insert(file.clone(), modified[file]);
+        }
+        changed
+    }
+}
+
+/// Modification time of every `.wgsl` file in `dir`
+fn scan(dir: &Path) -> BTreeMap<String, SystemTime> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "wgsl"))
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.file_name().to_string_lossy().into_owned(), modified))
+        })
+        .collect()
+}
+
+/// Runs `build`, turning the validation errors it causes into an `Err`
+/// where wgpu would otherwise panic. What a broken shader edit goes through.
+pub fn catch_validation<T>(device: &wgpu::Device, build: impl FnOnce() -> T) -> Result<T, String> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let built = build();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err.to_string()),
+        None => Ok(built),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{self, File};
+
+    use super::*;
+
+    #[test]
+    fn notices_edited_shaders() {
+        let dir = std::env::temp_dir().join(format!("shader-watcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("node.wgsl"), "old").unwrap();
+        fs::write(dir.join("notes.txt"), "not a shader").unwrap();
+
+        let mut watcher = ShaderWatcher::new(dir.clone());
+        watcher.interval = Duration::ZERO;
+        assert!(watcher.changed().is_empty());
+
+        // Set the time explicitly, some filesystems only keep whole seconds
+        fs::write(dir.join("node.wgsl"), "new").unwrap();
+        File::options()
+            .write(true)
+            .open(dir.join("node.wgsl"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "still not a shader").unwrap();
+        assert_eq!(
+            watcher.changed(),
+            [("node.wgsl".to_string(), "new".to_string())]
+        );
+        assert!(watcher.changed().is_empty());
+
+        fs::write(dir.join("edge.wgsl"), "added").unwrap();
+        assert_eq!(
+            watcher.changed(),
+            [("edge.wgsl".to_string(), "added".to_string())]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
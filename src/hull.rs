@@ -0,0 +1,224 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::RenderSettings;
+
+pub const DEFAULT_VERTEX_BUFFER_CAP: usize = 1 << 14;
+/// Points sampled around each node's circle before taking the hull
+const CIRCLE_SAMPLES: usize = 12;
+
+/// Draws translucent convex hulls around groups of nodes (components, cycles,
+/// clusters) underneath the graph
+pub struct HullRenderPass {
+    pub hulls: Vec<Hull>,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hull {
+    /// Convex polygon in world space, counter-clockwise
+    pub points: Vec<cgmath::Vector2<f32>>,
+    pub color: cgmath::Vector4<f32>,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct HullVertex {
+    pub(crate) position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl HullRenderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+    ) -> Self {
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            settings,
+            include_str!("hull.wgsl"),
+        );
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Hull Vertex Buffer"),
+            size: (std::mem::size_of::<HullVertex>() * DEFAULT_VERTEX_BUFFER_CAP) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            hulls: vec![],
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `hull.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Hull Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Hull"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Hull Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[HullVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                ..Default::default()
+            },
+            // Overlapping hulls blend with each other instead of occluding
+            depth_stencil: settings.depth_stencil(wgpu::CompareFunction::LessEqual),
+            multisample: settings.multisample(),
+            multiview: None,
+        })
+    }
+
+    pub fn write(&mut self, queue: &wgpu::Queue) {
+        let mut vertices: Vec<HullVertex> = self.hulls.iter().flat_map(Hull::triangles).collect();
+        vertices.truncate(DEFAULT_VERTEX_BUFFER_CAP - DEFAULT_VERTEX_BUFFER_CAP % 3);
+        self.vertex_count = vertices.len() as u32;
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+impl Hull {
+    /// Hull enclosing circles of `radius` (plus `padding`) around every point
+    pub fn around<I>(centers: I, radius: f32, padding: f32, color: cgmath::Vector4<f32>) -> Self
+    where
+        I: IntoIterator<Item = cgmath::Vector2<f32>>,
+    {
+        let r = radius + padding;
+        let samples = centers.into_iter().flat_map(|c| {
+            (0..CIRCLE_SAMPLES).map(move |i| {
+                let theta = std::f32::consts::TAU * i as f32 / CIRCLE_SAMPLES as f32;
+                c + cgmath::vec2(theta.cos(), theta.sin()) * r
+            })
+        });
+        Self {
+            points: convex_hull(samples.collect()),
+            color,
+        }
+    }
+
+    /// Triangle fan around the centroid, flattened into a triangle list
+    fn triangles(&self) -> Vec<HullVertex> {
+        if self.points.len() < 3 {
+            return vec![];
+        }
+        let centroid = self
+            .points
+            .iter()
+            .fold(cgmath::vec2(0.0, 0.0), |acc, p| acc + p)
+            / self.points.len() as f32;
+        let vertex = |p| HullVertex::new(p, self.color);
+        (0..self.points.len())
+            .flat_map(|i| {
+                let next = self.points[(i + 1) % self.points.len()];
+                [vertex(centroid), vertex(self.points[i]), vertex(next)]
+            })
+            .collect()
+    }
+}
+
+/// Andrew's monotone chain, returns the hull counter-clockwise without
+/// repeating the first point
+pub fn convex_hull(mut points: Vec<cgmath::Vector2<f32>>) -> Vec<cgmath::Vector2<f32>> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: cgmath::Vector2<f32>, a: cgmath::Vector2<f32>, b: cgmath::Vector2<f32>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+    let mut hull: Vec<cgmath::Vector2<f32>> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let floor = hull.len();
+        for p in pass {
+            while hull.len() >= floor + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of each chain is the first point of the next
+        hull.pop();
+    }
+    hull
+}
+
+impl HullVertex {
+    pub fn new(position: cgmath::Vector2<f32>, color: cgmath::Vector4<f32>) -> Self {
+        Self {
+            position: position.into(),
+            color: color.into(),
+        }
+    }
+
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x4,
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<HullVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+
+const ENGLISH: &str = include_str!("locales/en.ftl");
+
+/// UI strings for one language, looked up by message id. The format is a
+/// subset of Fluent: one `id = text` per line, `#` comments, and `{ $name }`
+/// placeholders. The overlay font only covers ASCII, anything else draws
+/// as `?`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalog {
+    pub lang: String,
+    messages: BTreeMap<String, String>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+impl Catalog {
+    pub fn english() -> Self {
+        let messages = parse_messages(ENGLISH).expect("bundled English catalog is valid");
+        Self {
+            lang: "en".into(),
+            messages,
+        }
+    }
+
+    /// Catalog for `lang` from `src`, messages it doesn't translate stay
+    /// English
+    pub fn load<S: Into<String>>(lang: S, src: &str) -> Result<Self, String> {
+        let mut catalog = Self::english();
+        catalog.lang = lang.into();
+        catalog.messages.extend(parse_messages(src)?);
+        Ok(catalog)
+    }
+
+    /// Text for `id`, or the id itself if no catalog has it
+    pub fn get<'a>(&'a self, id: &'a str) -> &'a str {
+        self.messages.get(id).map_or(id, String::as_str)
+    }
+
+    /// Text for `id` with its placeholders filled in from `args`. Unknown
+    /// placeholders are left as written.
+    pub fn format(&self, id: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        let mut text = self.get(id);
+        let mut out = String::new();
+        while let Some(start) = text.find('{') {
+            let Some(len) = text[start..].find('}') else {
+                break;
+            };
+            out += &text[..start];
+            let placeholder = &text[start..start + len + 1];
+            let name = placeholder[1..len].trim().trim_start_matches('$');
+            match args.iter().find(|(arg, _)| *arg == name) {
+                Some((_, value)) => out += &value.to_string(),
+                None => out += placeholder,
+            }
+            text = &text[start + len + 1..];
+        }
+        out + text
+    }
+}
+
+fn parse_messages(src: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut messages = BTreeMap::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((id, text)) = line.split_once('=') else {
+            return Err(format!(
+                "line {}: expected `id = text`, got {line:?}",
+                i + 1
+            ));
+        };
+        let id = id.trim();
+        if id.is_empty() || id.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid message id {id:?}", i + 1));
+        }
+        messages.insert(id.to_string(), text.trim().to_string());
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn translations_fall_back_to_english() {
+        let en = Catalog::english();
+        assert_eq!(en.get("help-title"), "controls");
+        assert_eq!(en.get("no-such-message"), "no-such-message");
+        assert_eq!(
+            en.format("quadtree-per-leaf", &[("mean", &"1.50"), ("max", &3)]),
+            "per leaf: 1.50 avg, 3 max"
+        );
+
+        let de = Catalog::load(
+            "de",
+            "# Deutsch\nstatus-selected = { $count } ausgewaehlt\nstatus-paused=pausiert {$why}",
+        )
+        .unwrap();
+        assert_eq!(
+            de.format("status-selected", &[("count", &2)]),
+            "2 ausgewaehlt"
+        );
+        assert_eq!(de.format("status-paused", &[]), "pausiert {$why}");
+        assert_eq!(de.get("help-title"), "controls");
+
+        assert!(Catalog::load("xx", "no equals sign").is_err());
+        assert!(Catalog::load("xx", "two words = text").is_err());
+    }
+}
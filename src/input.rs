@@ -1,21 +1,900 @@
-#[derive(Copy, Clone, Debug)]
-pub enum DragKind {
-    Node(u32),
-    EdgeCreation(u32),
+use std::collections::{BTreeSet, VecDeque};
+
+use cgmath::{vec2, InnerSpace};
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use crate::physics::ForceParam;
+
+/// Minimum distance between two recorded lasso points, in window pixels
+const LASSO_SPACING: f32 = 4.0;
+/// Distance between nodes spawned by the brush, in window pixels
+pub const BRUSH_SPACING: f32 = 120.0;
+
+/// What the pointer is currently doing. Gestures start with a primary button
+/// press and end with its release. Anything interrupting one (Escape, the
+/// pointer leaving the window, letting go of Ctrl while drawing an edge)
+/// cancels it without side effects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Gesture {
+    #[default]
+    Idle,
+    PanningCamera,
+    DraggingNode(u32),
+    CreatingEdge(u32),
+    /// Moving one end of an edge to another node
+    ReconnectingEdge(EdgeEnd),
+    /// Rectangle between the press position and the pointer
+    BoxSelecting {
+        start: cgmath::Vector2<f32>,
+        end: cgmath::Vector2<f32>,
+    },
+    /// Pointer trail, closed back to its first point on release
+    Lasso(Vec<cgmath::Vector2<f32>>),
+    /// Spawning nodes along the pointer's path, `last` is where the previous
+    /// one went
+    Brushing {
+        last: cgmath::Vector2<f32>,
+    },
+}
+
+/// One end of an edge, as grabbed to reconnect it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EdgeEnd {
+    pub edge: u32,
+    /// The node at the grabbed end, the one being replaced
+    pub node: u32,
+}
+
+/// Effect of a gesture on the scene, for `State` to carry out. Positions are
+/// window pixels relative to the center with y pointing up, like `Mouse::pos`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Pan(cgmath::Vector2<f32>),
+    MoveNode {
+        node: u32,
+        delta: cgmath::Vector2<f32>,
+    },
+    CreateEdge {
+        from: u32,
+        to: u32,
+    },
+    DeleteEdge(u32),
+    /// Moves an end of an edge over to the node `to`
+    ReconnectEdge {
+        end: EdgeEnd,
+        to: u32,
+    },
+    /// New node at a position, connected to the node under or nearest to it
+    /// when `connect` is set
+    AddNode {
+        pos: cgmath::Vector2<f32>,
+        connect: bool,
+    },
+    SelectBox {
+        min: cgmath::Vector2<f32>,
+        max: cgmath::Vector2<f32>,
+    },
+    SelectLasso(Vec<cgmath::Vector2<f32>>),
+    /// Node spawned by the brush, connected to the previous one of the same
+    /// stroke when `chain` is set
+    Brush {
+        pos: cgmath::Vector2<f32>,
+        chain: bool,
+    },
+}
+
+/// Pointer and held-key controls handled by `InputState`, as `(input,
+/// message id)` pairs in the order `press` checks them
+pub const POINTER_CONTROLS: &[(&str, &str)] = &[
+    ("alt+click", "pointer-add-node"),
+    ("alt+shift+click", "pointer-add-connected"),
+    ("b+drag", "pointer-brush"),
+    ("b+shift+drag", "pointer-brush-chain"),
+    ("m+move", "pointer-magnet"),
+    ("m+shift+move", "pointer-repel"),
+    ("ctrl+drag node", "pointer-create-edge"),
+    ("drag node", "pointer-move-node"),
+    ("drag edge end", "pointer-reconnect-edge"),
+    ("right-click edge", "pointer-delete-edge"),
+    ("right-click node", "pointer-node-menu"),
+    ("shift+drag", "pointer-box-select"),
+    ("ctrl+drag", "pointer-lasso"),
+    ("drag", "pointer-pan"),
+    ("scroll", "pointer-zoom"),
+    ("esc", "pointer-cancel"),
+];
+
+/// Edits to the graph and its layout, which `State` handles itself, as
+/// `(keys, message id)` pairs
+pub const EDIT_CONTROLS: &[(&str, &str)] = &[
+    ("del", "key-delete"),
+    ("shift+p", "key-pin"),
+    ("shift+c", "key-recolor"),
+    ("double-click node", "pointer-pin"),
+    ("ctrl+z", "key-undo"),
+    ("ctrl+shift+z", "key-redo"),
+];
+
+/// Saving the session, see `session::DEFAULT_SESSION_PATH`, and opening
+/// files, as `(keys, message id)` pairs
+pub const FILE_CONTROLS: &[(&str, &str)] = &[
+    ("ctrl+s", "key-save"),
+    ("ctrl+o", "key-open"),
+    ("ctrl+p", "key-palette"),
+];
+
+/// Camera moves bound to keys outside `KEYMAP`, as `(keys, message id)` pairs
+pub const VIEW_CONTROLS: &[(&str, &str)] = &[("shift+f", "key-fit"), ("home", "key-home")];
+
+/// What a key press toggles or opens, for `State` to carry out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shortcut {
+    Histogram,
+    Timeline,
+    Trail,
+    Forces,
+    Quadtree,
+    Ruler,
+    Pause,
+    ConnectSearch,
+    Help,
+    /// Adds the graph at this index of `Generator::DEMOS`
+    Generate(usize),
+    /// Scales a force parameter up by `FORCE_STEP`, or down when not `up`
+    Force {
+        param: ForceParam,
+        up: bool,
+    },
+    /// Grows the hovered or else the selected nodes by `RESIZE_STEP`, or
+    /// shrinks them when not `up`
+    Resize {
+        up: bool,
+    },
+}
+
+/// Factor the force shortcuts scale their parameter by
+pub const FORCE_STEP: f32 = 1.25;
+/// Factor the resize shortcuts scale nodes by
+pub const RESIZE_STEP: f32 = 1.25;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub key: char,
+    pub shortcut: Shortcut,
+    /// Message id of what the key does
+    pub message: &'static str,
+}
+
+/// Single key shortcuts, also what the help overlay lists
+pub const KEYMAP: &[Binding] = &[
+    Binding {
+        key: 'e',
+        shortcut: Shortcut::ConnectSearch,
+        message: "key-connect",
+    },
+    Binding {
+        key: 'h',
+        shortcut: Shortcut::Histogram,
+        message: "key-histogram",
+    },
+    Binding {
+        key: 't',
+        shortcut: Shortcut::Timeline,
+        message: "key-timeline",
+    },
+    Binding {
+        key: 'p',
+        shortcut: Shortcut::Pause,
+        message: "key-pause",
+    },
+    Binding {
+        key: 'r',
+        shortcut: Shortcut::Ruler,
+        message: "key-ruler",
+    },
+    Binding {
+        key: 'g',
+        shortcut: Shortcut::Trail,
+        message: "key-trail",
+    },
+    Binding {
+        key: 'f',
+        shortcut: Shortcut::Forces,
+        message: "key-forces",
+    },
+    Binding {
+        key: 'q',
+        shortcut: Shortcut::Quadtree,
+        message: "key-quadtree",
+    },
+    Binding {
+        key: '[',
+        shortcut: Shortcut::Force {
+            param: ForceParam::LinkDistance,
+            up: false,
+        },
+        message: "key-link-shorter",
+    },
+    Binding {
+        key: ']',
+        shortcut: Shortcut::Force {
+            param: ForceParam::LinkDistance,
+            up: true,
+        },
+        message: "key-link-longer",
+    },
+    Binding {
+        key: '{',
+        shortcut: Shortcut::Force {
+            param: ForceParam::RepulsionRange,
+            up: false,
+        },
+        message: "key-range-narrower",
+    },
+    Binding {
+        key: '}',
+        shortcut: Shortcut::Force {
+            param: ForceParam::RepulsionRange,
+            up: true,
+        },
+        message: "key-range-wider",
+    },
+    // Repulsion strength had `-` and `=` before resizing asked for `-` and
+    // `+`; it moved to the angle brackets next to the other forces' brackets
+    Binding {
+        key: '<',
+        shortcut: Shortcut::Force {
+            param: ForceParam::Strength,
+            up: false,
+        },
+        message: "key-weaker",
+    },
+    Binding {
+        key: '>',
+        shortcut: Shortcut::Force {
+            param: ForceParam::Strength,
+            up: true,
+        },
+        message: "key-stronger",
+    },
+    Binding {
+        key: '-',
+        shortcut: Shortcut::Resize { up: false },
+        message: "key-shrink",
+    },
+    Binding {
+        key: '+',
+        shortcut: Shortcut::Resize { up: true },
+        message: "key-grow",
+    },
+    Binding {
+        key: '?',
+        shortcut: Shortcut::Help,
+        message: "key-help",
+    },
+    Binding {
+        key: '1',
+        shortcut: Shortcut::Generate(0),
+        message: "key-generate-random",
+    },
+    Binding {
+        key: '2',
+        shortcut: Shortcut::Generate(1),
+        message: "key-generate-scalefree",
+    },
+    Binding {
+        key: '3',
+        shortcut: Shortcut::Generate(2),
+        message: "key-generate-grid",
+    },
+    Binding {
+        key: '4',
+        shortcut: Shortcut::Generate(3),
+        message: "key-generate-tree",
+    },
+];
+
+pub fn shortcut(key: char) -> Option<Shortcut> {
+    KEYMAP
+        .iter()
+        .find(|binding| binding.key == key)
+        .map(|binding| binding.shortcut)
+}
+
+/// How far back a release looks to tell how fast a node was thrown, in
+/// seconds
+pub const THROW_WINDOW: f32 = 0.1;
+
+/// Recent positions of a dragged node, timed, for the velocity it's let go
+/// with. A node held still before release comes out at rest.
+#[derive(Debug, Default)]
+pub struct DragVelocity {
+    /// Seconds since the drag started and position then, oldest first
+    samples: VecDeque<(f32, cgmath::Vector2<f32>)>,
+    time: f32,
+}
+
+impl DragVelocity {
+    /// Records where the node is `elapsed` seconds after the last sample
+    pub fn push(&mut self, elapsed: f32, pos: cgmath::Vector2<f32>) {
+        self.time += elapsed;
+        self.samples.push_back((self.time, pos));
+        // Keep one sample older than the window to measure from
+        while self.samples.len() > 2 && self.time - self.samples[1].0 >= THROW_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Average velocity over the last `THROW_WINDOW`, in units per second
+    pub fn velocity(&self) -> cgmath::Vector2<f32> {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((t0, p0)), Some((t1, p1))) if t1 > t0 => (p1 - p0) / (t1 - t0),
+            _ => vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.time = 0.0;
+    }
 }
 
+/// Pointer gesture state machine. It knows nothing about the scene: callers
+/// pass in the node under the pointer and apply the returned actions.
+#[derive(Default)]
 pub struct InputState {
-    pub dragging: Option<DragKind>,
+    pub gesture: Gesture,
     pub is_ctrl_pressed: bool,
+    pub is_shift_pressed: bool,
     pub is_lalt_pressed: bool,
+    pub is_brush_pressed: bool,
+    /// M held: nodes near the pointer are pulled in, or pushed away with
+    /// Shift, without anything being clicked
+    pub is_magnet_pressed: bool,
+    /// Pointer position at the previous event
+    last_pos: Option<cgmath::Vector2<f32>>,
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        Self {
-            dragging: Default::default(),
-            is_ctrl_pressed: false,
-            is_lalt_pressed: false,
+impl InputState {
+    /// Primary button pressed at `pos`, with `hit` being the node under it
+    /// and `edge_end` the end of an edge grabbed there if any. Alt-click adds
+    /// a node (Alt+Shift also connects it), dragging with B held paints nodes
+    /// (chained with Shift), a press on a node drags it (or starts an edge
+    /// with Ctrl held), a press by the end of an edge picks it up to
+    /// reconnect, and a press on empty space pans, box selects with Shift or
+    /// draws a lasso with Ctrl.
+    pub fn press(
+        &mut self,
+        pos: cgmath::Vector2<f32>,
+        hit: Option<u32>,
+        edge_end: Option<EdgeEnd>,
+    ) -> Option<Action> {
+        self.last_pos = Some(pos);
+        if self.gesture != Gesture::Idle {
+            return None;
+        }
+        if self.is_lalt_pressed {
+            return Some(Action::AddNode {
+                pos,
+                connect: self.is_shift_pressed,
+            });
+        }
+        if self.is_brush_pressed {
+            self.gesture = Gesture::Brushing { last: pos };
+            return Some(Action::Brush { pos, chain: false });
+        }
+        self.gesture = match hit {
+            Some(node) if self.is_ctrl_pressed => Gesture::CreatingEdge(node),
+            Some(node) => Gesture::DraggingNode(node),
+            None if self.is_shift_pressed => Gesture::BoxSelecting {
+                start: pos,
+                end: pos,
+            },
+            None if self.is_ctrl_pressed => Gesture::Lasso(vec![pos]),
+            None => edge_end.map_or(Gesture::PanningCamera, Gesture::ReconnectingEdge),
+        };
+        None
+    }
+
+    /// Secondary button clicked over `edge`, which deletes it unless another
+    /// gesture is underway
+    pub fn secondary_click(&mut self, edge: Option<u32>) -> Option<Action> {
+        if self.gesture != Gesture::Idle {
+            return None;
         }
+        edge.map(Action::DeleteEdge)
+    }
+
+    pub fn moved(&mut self, pos: cgmath::Vector2<f32>) -> Option<Action> {
+        let delta = pos - self.last_pos.replace(pos)?;
+        match &mut self.gesture {
+            Gesture::Idle | Gesture::CreatingEdge(_) | Gesture::ReconnectingEdge(_) => None,
+            Gesture::PanningCamera => Some(Action::Pan(delta)),
+            Gesture::DraggingNode(node) => Some(Action::MoveNode { node: *node, delta }),
+            Gesture::BoxSelecting { end, .. } => {
+                *end = pos;
+                None
+            }
+            Gesture::Brushing { last } => {
+                if (pos - *last).magnitude() < BRUSH_SPACING {
+                    return None;
+                }
+                *last = pos;
+                Some(Action::Brush {
+                    pos,
+                    chain: self.is_shift_pressed,
+                })
+            }
+            Gesture::Lasso(points) => {
+                if points
+                    .last()
+                    .is_none_or(|last| (pos - last).magnitude() >= LASSO_SPACING)
+                {
+                    points.push(pos);
+                }
+                None
+            }
+        }
+    }
+
+    /// Primary button released at `pos`, with `hit` being the node under it
+    pub fn release(&mut self, pos: cgmath::Vector2<f32>, hit: Option<u32>) -> Option<Action> {
+        self.last_pos = Some(pos);
+        match std::mem::take(&mut self.gesture) {
+            // Whether the edge is valid is up to the caller, even releasing
+            // over the node it started from asks for one
+            Gesture::CreatingEdge(from) => hit.map(|to| Action::CreateEdge { from, to }),
+            Gesture::ReconnectingEdge(end) => hit.map(|to| Action::ReconnectEdge { end, to }),
+            Gesture::BoxSelecting { start, .. } => Some(Action::SelectBox {
+                min: vec2(start.x.min(pos.x), start.y.min(pos.y)),
+                max: vec2(start.x.max(pos.x), start.y.max(pos.y)),
+            }),
+            Gesture::Lasso(mut points) => {
+                points.push(pos);
+                (points.len() >= 3).then_some(Action::SelectLasso(points))
+            }
+            _ => None,
+        }
+    }
+
+    /// Tracks modifier keys. Ctrl turns a node drag into edge creation, and
+    /// releasing it abandons the edge.
+    pub fn modifier(&mut self, key: Key, pressed: bool) {
+        match key {
+            Key::Ctrl => {
+                self.is_ctrl_pressed = pressed;
+                match self.gesture {
+                    Gesture::DraggingNode(node) if pressed => {
+                        self.gesture = Gesture::CreatingEdge(node)
+                    }
+                    Gesture::CreatingEdge(_) if !pressed => self.gesture = Gesture::Idle,
+                    _ => (),
+                }
+            }
+            Key::Shift => self.is_shift_pressed = pressed,
+            Key::Alt => self.is_lalt_pressed = pressed,
+            Key::Char('b') => {
+                self.is_brush_pressed = pressed;
+                if !pressed && matches!(self.gesture, Gesture::Brushing { .. }) {
+                    self.gesture = Gesture::Idle;
+                }
+            }
+            Key::Char('m') => self.is_magnet_pressed = pressed,
+            _ => (),
+        }
+    }
+
+    /// The pointer left the window, its next position can't be trusted
+    pub fn pointer_left(&mut self) {
+        self.last_pos = None;
+        self.cancel();
+    }
+
+    /// Abandons the current gesture, returns whether there was one
+    pub fn cancel(&mut self) -> bool {
+        std::mem::take(&mut self.gesture) != Gesture::Idle
+    }
+
+    /// Id for what the pointer is doing, or what a press would do with the
+    /// modifiers currently held. The status bar shows it as the `mode-<id>`
+    /// message.
+    pub fn mode(&self) -> &'static str {
+        match self.gesture {
+            Gesture::Idle if self.is_lalt_pressed => "add-node",
+            Gesture::Idle if self.is_brush_pressed => "brush",
+            Gesture::Idle if self.is_magnet_pressed && self.is_shift_pressed => "repel",
+            Gesture::Idle if self.is_magnet_pressed => "magnet",
+            Gesture::Idle if self.is_ctrl_pressed => "edge-create",
+            Gesture::Idle if self.is_shift_pressed => "select",
+            Gesture::Idle => "navigate",
+            Gesture::PanningCamera => "pan",
+            Gesture::DraggingNode(_) => "move",
+            Gesture::CreatingEdge(_) => "edge-create",
+            Gesture::ReconnectingEdge(_) => "reconnect",
+            Gesture::BoxSelecting { .. } => "select",
+            Gesture::Lasso(_) => "lasso",
+            Gesture::Brushing { .. } => "brush",
+        }
+    }
+
+    /// Node held by the pointer, physics leaves it alone
+    pub fn dragged_node(&self) -> Option<u32> {
+        match self.gesture {
+            Gesture::DraggingNode(node) => Some(node),
+            _ => None,
+        }
+    }
+}
+
+/// Even-odd test of `point` against the polygon through `points`
+pub fn lasso_contains(points: &[cgmath::Vector2<f32>], point: cgmath::Vector2<f32>) -> bool {
+    let mut inside = false;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Nodes moving with `dragged`: the whole `selection` when it's part of it,
+/// otherwise just itself
+pub fn held_nodes(dragged: Option<u32>, selection: Option<&BTreeSet<u32>>) -> BTreeSet<u32> {
+    match (dragged, selection) {
+        (Some(node), Some(selection)) if selection.contains(&node) => selection.clone(),
+        (Some(node), _) => BTreeSet::from([node]),
+        (None, _) => BTreeSet::new(),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointerButton {
+    Primary,
+    Secondary,
+    Middle,
+}
+
+/// Keys the viewer reacts to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Ctrl,
+    Shift,
+    Alt,
+    Escape,
+    Enter,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Home,
+    /// Letters are always lowercase
+    Char(char),
+}
+
+/// Everything `State` reacts to, independent of the windowing library. The
+/// winit adapters below produce these, other hosts (tests, embedders) can
+/// construct them directly.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// Cursor position in window pixels, origin at the top-left corner and y
+    /// pointing down
+    PointerMoved(cgmath::Vector2<f32>),
+    PointerLeft,
+    PointerPressed(PointerButton),
+    PointerReleased(PointerButton),
+    Key {
+        key: Key,
+        pressed: bool,
+    },
+    /// Typed character, already accounting for the keyboard layout and shift
+    Text(char),
+    /// Zoom steps, positive zooms in
+    Scroll(f32),
+    /// Ratio between the current and previous distance of two fingers. winit
+    /// doesn't report pinches, so only other hosts send this.
+    Pinch(f32),
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::PointerMoved(
+                (position.x as f32, position.y as f32).into(),
+            )),
+            WindowEvent::CursorLeft { .. } => Some(InputEvent::PointerLeft),
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => Some(InputEvent::Text(*c)),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::Other(_) => return None,
+                };
+                Some(match state {
+                    ElementState::Pressed => InputEvent::PointerPressed(button),
+                    ElementState::Released => InputEvent::PointerReleased(button),
+                })
+            }
+            WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Scroll(match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(pos) => (pos.y / 100.) as f32,
+            })),
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(code),
+                        ..
+                    },
+                ..
+            } => Some(InputEvent::Key {
+                key: key_from_winit(*code)?,
+                pressed: matches!(state, ElementState::Pressed),
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn key_from_winit(code: VirtualKeyCode) -> Option<Key> {
+    match code {
+        VirtualKeyCode::LControl => Some(Key::Ctrl),
+        VirtualKeyCode::LShift => Some(Key::Shift),
+        VirtualKeyCode::LAlt => Some(Key::Alt),
+        VirtualKeyCode::Escape => Some(Key::Escape),
+        VirtualKeyCode::Return => Some(Key::Enter),
+        VirtualKeyCode::Back => Some(Key::Backspace),
+        VirtualKeyCode::Delete => Some(Key::Delete),
+        VirtualKeyCode::Up => Some(Key::Up),
+        VirtualKeyCode::Down => Some(Key::Down),
+        VirtualKeyCode::Home => Some(Key::Home),
+        code => {
+            // Letters are contiguous in `VirtualKeyCode`
+            let n = code as u32;
+            let a = VirtualKeyCode::A as u32;
+            (a..=VirtualKeyCode::Z as u32)
+                .contains(&n)
+                .then(|| char::from(b'a' + (n - a) as u8))
+                .map(Key::Char)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::i18n::Catalog;
+
+    #[test]
+    fn keymap_is_unambiguous() {
+        for (i, binding) in KEYMAP.iter().enumerate() {
+            assert!(KEYMAP[i + 1..].iter().all(|b| b.key != binding.key));
+            // B and M are held for the brush and magnet, they can't also be
+            // shortcuts
+            assert!(!['b', 'm'].contains(&binding.key));
+        }
+        assert_eq!(shortcut('?'), Some(Shortcut::Help));
+        assert_eq!(
+            shortcut('{'),
+            Some(Shortcut::Force {
+                param: ForceParam::RepulsionRange,
+                up: false
+            })
+        );
+        assert_eq!(shortcut('+'), Some(Shortcut::Resize { up: true }));
+        assert_eq!(shortcut('-'), Some(Shortcut::Resize { up: false }));
+
+        let strings = Catalog::english();
+        let messages = KEYMAP
+            .iter()
+            .map(|binding| binding.message)
+            .chain(POINTER_CONTROLS.iter().map(|(_, message)| *message))
+            .chain(EDIT_CONTROLS.iter().map(|(_, message)| *message))
+            .chain(VIEW_CONTROLS.iter().map(|(_, message)| *message))
+            .chain(FILE_CONTROLS.iter().map(|(_, message)| *message));
+        for message in messages {
+            assert_ne!(strings.get(message), message, "{message} isn't translated");
+        }
+        assert_eq!(shortcut('z'), None);
+    }
+
+    #[test]
+    fn gestures() {
+        let mut input = InputState::default();
+
+        // Dragging a node reports relative motion until release
+        assert_eq!(input.press(vec2(0.0, 0.0), Some(3), None), None);
+        assert_eq!(
+            input.moved(vec2(5.0, -2.0)),
+            Some(Action::MoveNode {
+                node: 3,
+                delta: vec2(5.0, -2.0)
+            })
+        );
+        assert_eq!(input.dragged_node(), Some(3));
+        assert_eq!(input.release(vec2(5.0, -2.0), Some(3)), None);
+        assert_eq!(input.gesture, Gesture::Idle);
+
+        // Ctrl mid-drag starts an edge, letting go of it abandons the edge
+        input.press(vec2(0.0, 0.0), Some(1), None);
+        assert_eq!(input.mode(), "move");
+        input.modifier(Key::Ctrl, true);
+        assert_eq!(input.gesture, Gesture::CreatingEdge(1));
+        assert_eq!(input.mode(), "edge-create");
+        input.modifier(Key::Ctrl, false);
+        assert_eq!(input.gesture, Gesture::Idle);
+        assert_eq!(input.release(vec2(10.0, 0.0), Some(2)), None);
+
+        // Edges need a node under the pointer on release, Escape gives up
+        input.modifier(Key::Ctrl, true);
+        input.press(vec2(0.0, 0.0), Some(1), None);
+        assert_eq!(input.release(vec2(0.0, 0.0), None), None);
+        input.press(vec2(0.0, 0.0), Some(1), None);
+        assert!(input.cancel());
+        assert_eq!(input.release(vec2(10.0, 0.0), Some(2)), None);
+        input.press(vec2(0.0, 0.0), Some(1), None);
+        assert_eq!(
+            input.release(vec2(0.0, 0.0), Some(1)),
+            Some(Action::CreateEdge { from: 1, to: 1 })
+        );
+        input.press(vec2(0.0, 0.0), Some(1), None);
+        assert_eq!(
+            input.release(vec2(10.0, 0.0), Some(2)),
+            Some(Action::CreateEdge { from: 1, to: 2 })
+        );
+        input.modifier(Key::Ctrl, false);
+
+        // Empty space pans
+        input.press(vec2(0.0, 0.0), None, None);
+        assert_eq!(
+            input.moved(vec2(3.0, 4.0)),
+            Some(Action::Pan(vec2(3.0, 4.0)))
+        );
+        input.release(vec2(3.0, 4.0), None);
+
+        // An edge's end is carried to the node it's dropped on, anywhere
+        // else puts it back
+        let end = EdgeEnd { edge: 4, node: 1 };
+        input.press(vec2(0.0, 0.0), None, Some(end));
+        assert_eq!(input.moved(vec2(3.0, 4.0)), None);
+        assert_eq!(input.mode(), "reconnect");
+        assert_eq!(
+            input.release(vec2(3.0, 4.0), Some(5)),
+            Some(Action::ReconnectEdge { end, to: 5 })
+        );
+        input.press(vec2(0.0, 0.0), None, Some(end));
+        assert_eq!(input.release(vec2(3.0, 4.0), None), None);
+
+        // Right-clicking an edge deletes it, but not in the middle of a drag
+        assert_eq!(input.secondary_click(None), None);
+        assert_eq!(input.secondary_click(Some(4)), Some(Action::DeleteEdge(4)));
+        input.press(vec2(0.0, 0.0), None, None);
+        assert_eq!(input.secondary_click(Some(4)), None);
+        input.release(vec2(0.0, 0.0), None);
+
+        // Box selections are normalized, whichever way they were drawn
+        input.modifier(Key::Shift, true);
+        input.press(vec2(10.0, 10.0), None, None);
+        input.moved(vec2(-5.0, 20.0));
+        assert_eq!(
+            input.release(vec2(-5.0, 20.0), None),
+            Some(Action::SelectBox {
+                min: vec2(-5.0, 10.0),
+                max: vec2(10.0, 20.0)
+            })
+        );
+
+        // Leaving the window drops the box without selecting anything
+        input.press(vec2(0.0, 0.0), None, None);
+        input.pointer_left();
+        assert_eq!(input.release(vec2(50.0, 50.0), None), None);
+        input.modifier(Key::Shift, false);
+
+        // Alt-click adds a node without starting a gesture
+        input.modifier(Key::Alt, true);
+        assert_eq!(
+            input.press(vec2(1.0, 2.0), Some(0), None),
+            Some(Action::AddNode {
+                pos: vec2(1.0, 2.0),
+                connect: false
+            })
+        );
+        assert_eq!(input.gesture, Gesture::Idle);
+        input.modifier(Key::Shift, true);
+        assert_eq!(
+            input.press(vec2(1.0, 2.0), None, None),
+            Some(Action::AddNode {
+                pos: vec2(1.0, 2.0),
+                connect: true
+            })
+        );
+        assert!(!input.cancel());
+    }
+
+    #[test]
+    fn brush() {
+        let mut input = InputState::default();
+        input.modifier(Key::Char('b'), true);
+        assert_eq!(
+            input.press(vec2(0.0, 0.0), Some(2), None),
+            Some(Action::Brush {
+                pos: vec2(0.0, 0.0),
+                chain: false
+            })
+        );
+        // Nothing until the pointer is far enough from the last node
+        assert_eq!(input.moved(vec2(BRUSH_SPACING / 2.0, 0.0)), None);
+        input.modifier(Key::Shift, true);
+        assert_eq!(
+            input.moved(vec2(BRUSH_SPACING, 0.0)),
+            Some(Action::Brush {
+                pos: vec2(BRUSH_SPACING, 0.0),
+                chain: true
+            })
+        );
+        // Letting go of B ends the stroke
+        input.modifier(Key::Char('b'), false);
+        assert_eq!(input.gesture, Gesture::Idle);
+        assert_eq!(input.moved(vec2(BRUSH_SPACING * 3.0, 0.0)), None);
+    }
+
+    #[test]
+    fn throw_velocity() {
+        let mut drag = DragVelocity::default();
+        assert_eq!(drag.velocity(), vec2(0.0, 0.0));
+        drag.push(0.0, vec2(0.0, 0.0));
+        assert_eq!(drag.velocity(), vec2(0.0, 0.0));
+        // Slow at first, only the last tenth of a second counts
+        for i in 1..=10 {
+            drag.push(0.1, vec2(i as f32, 0.0));
+        }
+        for i in 1..=4 {
+            drag.push(0.03, vec2(10.0 + 6.0 * i as f32, 0.0));
+        }
+        assert!((drag.velocity().x - 200.0).abs() < 1e-2);
+        // Held still before letting go
+        for _ in 0..6 {
+            drag.push(0.02, vec2(34.0, 0.0));
+        }
+        assert_eq!(drag.velocity(), vec2(0.0, 0.0));
+        drag.clear();
+        assert_eq!(drag.velocity(), vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn dragging_a_selected_node_takes_the_selection_along() {
+        let selection = BTreeSet::from([1, 2, 3]);
+        assert_eq!(held_nodes(Some(2), Some(&selection)), selection);
+        assert_eq!(held_nodes(Some(5), Some(&selection)), BTreeSet::from([5]));
+        assert_eq!(held_nodes(Some(5), None), BTreeSet::from([5]));
+        assert!(held_nodes(None, Some(&selection)).is_empty());
+    }
+
+    #[test]
+    fn lasso() {
+        let mut input = InputState {
+            is_ctrl_pressed: true,
+            ..Default::default()
+        };
+        input.press(vec2(0.0, 0.0), None, None);
+        // Too close to the previous point to be recorded
+        input.moved(vec2(1.0, 0.0));
+        input.moved(vec2(100.0, 0.0));
+        input.moved(vec2(100.0, 100.0));
+        let Some(Action::SelectLasso(points)) = input.release(vec2(0.0, 100.0), None) else {
+            panic!("expected a lasso selection");
+        };
+        assert_eq!(points.len(), 4);
+        assert!(lasso_contains(&points, vec2(50.0, 50.0)));
+        assert!(!lasso_contains(&points, vec2(150.0, 50.0)));
+
+        // A click is not a lasso
+        input.press(vec2(0.0, 0.0), None, None);
+        assert_eq!(input.release(vec2(0.0, 0.0), None), None);
     }
 }
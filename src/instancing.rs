@@ -0,0 +1,360 @@
+//! The quad that nodes, labels and overlay rects are instanced over, the
+//! ribbon edges are, the pair of quads their markers are, the line they
+//! shrink to when zoomed far out, and the draw call math they share.
+
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::Vertex;
+
+pub(crate) const QUAD_INDICES: &[u32] = &[0, 1, 3, 3, 1, 2];
+pub(crate) const QUAD_VERTICES: &[Vertex] = &[
+    Vertex {
+        // vertex a, index = 0
+        position: [-1.0, -1.0],
+    },
+    Vertex {
+        // vertex b, index = 1
+        position: [1.0, -1.0],
+    },
+    Vertex {
+        // vertex c, index = 2
+        position: [1.0, 1.0],
+    },
+    Vertex {
+        // vertex d, index = 3
+        position: [-1.0, 1.0],
+    },
+];
+
+/// Quads along an edge's ribbon, enough for a smooth curve at any zoom
+pub const RIBBON_SEGMENTS: u32 = 16;
+
+/// Mesh of a strip of `segments` quads. Vertices sit at `x` from 0 to 1
+/// along its length and `y` of -1 or 1 across it, for the vertex shader to
+/// bend along a curve.
+pub fn ribbon_mesh(segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = (0..=segments)
+        .flat_map(|i| {
+            let t = i as f32 / segments as f32;
+            [
+                Vertex {
+                    position: [t, -1.0],
+                },
+                Vertex { position: [t, 1.0] },
+            ]
+        })
+        .collect();
+    let indices = (0..segments)
+        .flat_map(|i| {
+            let [a, b, c, d] = [2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3];
+            [a, c, b, b, c, d]
+        })
+        .collect();
+    (vertices, indices)
+}
+
+/// Segments of the line an edge is drawn as when zoomed far out, where
+/// curves are too small for the ribbon's smoothness to show
+pub const LINE_SEGMENTS: u32 = 4;
+
+/// Mesh of the middle of a `ribbon_mesh`, `segments` lines running along it
+/// at `y` of 0, for drawing with a line list
+pub fn line_mesh(segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = (0..=segments)
+        .map(|i| Vertex {
+            position: [i as f32 / segments as f32, 0.0],
+        })
+        .collect();
+    let indices = (0..segments).flat_map(|i| [i, i + 1]).collect();
+    (vertices, indices)
+}
+
+/// Instances submitted per draw call. More are drawn in several calls, each
+/// binding the instance buffer further along, so no single draw relies on
+/// how a backend copes with very large instance ranges.
+pub const MAX_INSTANCES_PER_DRAW: u32 = 1 << 16;
+
+/// Splits `count` instances into draws of at most `max` each
+pub fn chunks(count: u32, max: u32) -> impl Iterator<Item = Range<u32>> {
+    assert!(max > 0, "chunks must hold at least one instance");
+    (0..count)
+        .step_by(max as usize)
+        .map(move |start| start..count.min(start.saturating_add(max)))
+}
+
+/// How many of `len` items fit in a buffer of `capacity` instances
+pub fn instance_count(len: usize, capacity: usize) -> u32 {
+    u32::try_from(len.min(capacity)).expect("instance buffer holds fewer than 2^32 instances")
+}
+
+/// Logs when `len` items stop fitting in `capacity`, once until they fit
+/// again, so the ones left undrawn don't go unnoticed
+pub(crate) fn warn_overflow(len: usize, capacity: usize, what: &str, overflowed: &mut bool) {
+    if len > capacity && !*overflowed {
+        log::warn!("{len} {what} but room for {capacity}, drawing the first {capacity}");
+    }
+    *overflowed = len > capacity;
+}
+
+/// Order to draw translucent instances in, given their world space z: the
+/// farthest from the camera first, so each blends over whatever is behind
+/// it. Ties keep their index order, later items stay on top.
+pub fn back_to_front(depths: impl IntoIterator<Item = f32>) -> Vec<u32> {
+    let mut order: Vec<(u32, f32)> = (0..).zip(depths).collect();
+    order.sort_by(|a, b| a.1.total_cmp(&b.1));
+    order.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Capacity to grow a buffer of `capacity` instances to so `len` fit:
+/// doubled until they do, but never past `max`
+pub fn grown_capacity(capacity: usize, len: usize, max: usize) -> usize {
+    let mut grown = capacity.max(1);
+    while grown < len && grown < max {
+        grown = grown.saturating_mul(2);
+    }
+    grown.min(max).max(capacity)
+}
+
+/// Slot of an item left out of the instance buffer, such as one culled for
+/// being off screen
+pub const NO_SLOT: u32 = u32::MAX;
+
+/// Where each of `len` items ended up in `order`, `NO_SLOT` for those it
+/// leaves out
+pub fn slots(order: &[u32], len: usize) -> Vec<u32> {
+    let mut slots = vec![NO_SLOT; len];
+    for (slot, i) in (0..).zip(order) {
+        slots[*i as usize] = slot;
+    }
+    slots
+}
+
+/// Vertex buffer of per-instance data that grows as more instances are
+/// written to it
+pub(crate) struct InstanceBuffer {
+    buffer: wgpu::Buffer,
+    label: String,
+    /// Bytes per instance
+    stride: usize,
+    capacity: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(device: &wgpu::Device, label: &str, stride: usize, capacity: usize) -> Self {
+        Self {
+            buffer: Self::create(device, label, stride, capacity),
+            label: label.to_string(),
+            stride,
+            capacity,
+        }
+    }
+
+    fn create(device: &wgpu::Device, label: &str, stride: usize, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Instance Buffer")),
+            size: (stride * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Instances that fit
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Makes room for `len` instances, doubling the buffer as many times as
+    /// that takes and copying the old contents over. It can't grow past the
+    /// device's largest buffer, whatever doesn't fit then isn't drawn.
+    pub fn reserve(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, len: usize) {
+        if len <= self.capacity {
+            return;
+        }
+        let max = device.limits().max_buffer_size as usize / self.stride;
+        let capacity = grown_capacity(self.capacity, len, max);
+        if capacity == self.capacity {
+            return;
+        }
+        let buffer = Self::create(device, &self.label, self.stride, capacity);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&format!("{} Instance Buffer Growth", self.label)),
+        });
+        encoder.copy_buffer_to_buffer(
+            &self.buffer,
+            0,
+            &buffer,
+            0,
+            (self.stride * self.capacity) as u64,
+        );
+        queue.submit(Some(encoder.finish()));
+        self.buffer = buffer;
+        self.capacity = capacity;
+    }
+}
+
+/// Vertex and index buffers for the quad, for a ribbon of them, or for a
+/// line
+pub(crate) struct Quad {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl Quad {
+    pub fn new(device: &wgpu::Device, label: &str) -> Self {
+        Self::from_mesh(device, label, QUAD_VERTICES, QUAD_INDICES)
+    }
+
+    /// Two quads in one mesh, the second's vertices numbered after the
+    /// first's for the vertex shader to tell them apart
+    pub fn pair(device: &wgpu::Device, label: &str) -> Self {
+        let vertices = [QUAD_VERTICES, QUAD_VERTICES].concat();
+        let count = QUAD_VERTICES.len() as u32;
+        let indices: Vec<u32> = QUAD_INDICES
+            .iter()
+            .copied()
+            .chain(QUAD_INDICES.iter().map(|i| i + count))
+            .collect();
+        Self::from_mesh(device, label, &vertices, &indices)
+    }
+
+    /// A `ribbon_mesh` of `RIBBON_SEGMENTS` quads
+    pub fn ribbon(device: &wgpu::Device, label: &str) -> Self {
+        let (vertices, indices) = ribbon_mesh(RIBBON_SEGMENTS);
+        Self::from_mesh(device, label, &vertices, &indices)
+    }
+
+    /// A `line_mesh` of `LINE_SEGMENTS` lines
+    pub fn line(device: &wgpu::Device, label: &str) -> Self {
+        let (vertices, indices) = line_mesh(LINE_SEGMENTS);
+        Self::from_mesh(device, label, &vertices, &indices)
+    }
+
+    fn from_mesh(device: &wgpu::Device, label: &str, vertices: &[Vertex], indices: &[u32]) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Index Buffer")),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+
+    /// Draws the first `count` instances of `instances`, whose elements are
+    /// `stride` bytes apart. Expects the pipeline and bind groups to be set.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        instances: &'a wgpu::Buffer,
+        stride: usize,
+        count: u32,
+    ) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for chunk in chunks(count, MAX_INSTANCES_PER_DRAW) {
+            let start = chunk.start as u64 * stride as u64;
+            render_pass.set_vertex_buffer(1, instances.slice(start..));
+            render_pass.draw_indexed(0..self.index_count, 0, 0..chunk.len() as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunks_cover_every_instance_once() {
+        assert_eq!(chunks(0, 4).count(), 0);
+        let mut fits = chunks(3, 4);
+        assert_eq!((fits.next(), fits.next()), (Some(0..3), None));
+        assert_eq!(chunks(8, 4).collect::<Vec<_>>(), [0..4, 4..8]);
+        assert_eq!(chunks(9, 4).collect::<Vec<_>>(), [0..4, 4..8, 8..9]);
+        // Far past what 16-bit math could count
+        let count = MAX_INSTANCES_PER_DRAW * 3 + 7;
+        let drawn: Vec<_> = chunks(count, MAX_INSTANCES_PER_DRAW).collect();
+        assert_eq!(drawn.len(), 4);
+        assert_eq!(drawn.iter().map(|c| c.len() as u32).sum::<u32>(), count);
+        assert!(drawn.windows(2).all(|w| w[0].end == w[1].start));
+        assert_eq!(
+            chunks(u32::MAX, u32::MAX - 1).last(),
+            Some(u32::MAX - 1..u32::MAX)
+        );
+    }
+
+    #[test]
+    fn far_instances_draw_first() {
+        // Higher z is closer to the camera
+        let order = back_to_front([0.0, -2.0, 1.0, 0.0, -2.0]);
+        assert_eq!(order, [1, 4, 0, 3, 2]);
+        assert_eq!(slots(&order, 5), [2, 0, 4, 3, 1]);
+        // Culled down to a few
+        assert_eq!(slots(&[2, 0], 3), [1, NO_SLOT, 0]);
+        assert_eq!(back_to_front([0.0; 4]), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn ribbon_covers_the_strip() {
+        let (vertices, indices) = ribbon_mesh(4);
+        assert_eq!(vertices.len(), 10);
+        assert_eq!(indices.len(), 24);
+        assert!(indices.iter().all(|i| (*i as usize) < vertices.len()));
+        assert_eq!(vertices[0].position, [0.0, -1.0]);
+        assert_eq!(vertices[9].position, [1.0, 1.0]);
+        // Every quad spans one step along the strip and both sides
+        for quad in indices.chunks(6) {
+            let xs: Vec<f32> = quad
+                .iter()
+                .map(|i| vertices[*i as usize].position[0])
+                .collect();
+            let span =
+                xs.iter().cloned().fold(0.0, f32::max) - xs.iter().cloned().fold(1.0, f32::min);
+            assert_eq!(span, 0.25);
+            assert!(quad.iter().any(|i| vertices[*i as usize].position[1] < 0.0));
+            assert!(quad.iter().any(|i| vertices[*i as usize].position[1] > 0.0));
+        }
+        // The line joins the same steps down the middle
+        let (points, lines) = line_mesh(4);
+        assert_eq!(lines, [0, 1, 1, 2, 2, 3, 3, 4]);
+        assert!(points
+            .iter()
+            .zip(vertices.chunks(2))
+            .all(|(point, side)| point.position == [side[0].position[0], 0.0]));
+    }
+
+    #[test]
+    fn capacity_doubles_until_it_fits() {
+        assert_eq!(grown_capacity(1024, 1025, usize::MAX), 2048);
+        assert_eq!(grown_capacity(1024, 5000, usize::MAX), 8192);
+        assert_eq!(grown_capacity(1024, 10, usize::MAX), 1024);
+        assert_eq!(grown_capacity(0, 3, usize::MAX), 4);
+        // The device's limit wins, shrinking is never the answer
+        assert_eq!(grown_capacity(1024, 1 << 20, 3000), 3000);
+        assert_eq!(grown_capacity(4096, 1 << 20, 3000), 4096);
+    }
+
+    #[test]
+    fn instance_count_stops_at_capacity() {
+        assert_eq!(instance_count(10, 1024), 10);
+        assert_eq!(instance_count(100_000, 70_000), 70_000);
+        assert!(QUAD_INDICES
+            .iter()
+            .all(|i| (*i as usize) < QUAD_VERTICES.len()));
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::BTreeSet;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::vec4;
+
+use crate::{
+    badge::{Badge, BADGE_BACKGROUND},
+    font,
+    instancing::{self, InstanceBuffer, Quad},
+    node::{Node, DIMMED_ALPHA},
+    texture::Atlas,
+    RenderSettings, Vertex,
+};
+
+/// Labels the instance buffer starts out with room for
+pub const DEFAULT_INSTANCE_BUFFER_CAP: usize = 1024;
+/// Camera scale below which labels start fading out
+pub const DEFAULT_LABEL_ZOOM: f32 = 0.5;
+/// Share of the zoom threshold at which labels are gone completely
+const FADE_START: f32 = 0.7;
+/// World units per font pixel, labels scale with the graph like nodes do
+pub const LABEL_PIXEL_SIZE: f32 = 3.0;
+/// Atlas texels per font pixel. Above one so linear filtering softens the
+/// edges instead of smearing whole font pixels.
+const TEXELS_PER_PIXEL: u32 = 4;
+/// Longer captions are cut short, past this they'd cover their neighbours
+pub const MAX_LABEL_CHARS: usize = 32;
+const ATLAS_SIZE: u32 = 2048;
+/// Gap between a node's disk and its label, in world units
+const LABEL_GAP: f32 = 6.0;
+const LABEL_COLOR: cgmath::Vector4<f32> = vec4(0.9, 0.9, 0.92, 1.0);
+/// World units per font pixel of a badge, smaller than captions
+pub const BADGE_PIXEL_SIZE: f32 = 2.0;
+/// Backdrop around a badge's glyphs, in font pixels
+const BADGE_PADDING: f32 = 1.0;
+/// Solid glyph badge backdrops are cut from
+const BACKDROP: &str = "█";
+
+/// Draws every node's caption under it, see `Node::caption`, and its badges.
+/// Each distinct caption is rasterized once into an atlas and drawn as a
+/// single textured quad, so labels cost about as much as nodes.
+pub struct LabelRenderPass {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) quad: Quad,
+    pub(crate) instance_buffer: InstanceBuffer,
+    atlas: Atlas<String>,
+    /// What the pipeline is rebuilt with on a shader reload
+    pub(crate) atlas_layout: wgpu::BindGroupLayout,
+    /// Labels written by the last `update`
+    count: u32,
+    pub visible: bool,
+    /// Camera scale below which labels fade out, see `label_alpha`
+    pub min_zoom: f32,
+    /// Set while the graph is past its soft limits or zoomed out past
+    /// `State::lod_zoom`, which leaves labels and badges out
+    pub degraded: bool,
+    /// At most one per corner. They stay when labels are turned off, but
+    /// fade out with them.
+    pub badges: Vec<Badge>,
+    /// Labels left out of the last `update` because the atlas couldn't grow
+    /// any further to hold them
+    pub dropped: usize,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct LabelRaw {
+    center: [f32; 3],
+    size: [f32; 2],
+    uv: [f32; 4],
+    color: [f32; 4],
+}
+
+impl LabelRenderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+    ) -> Self {
+        let atlas_layout = Atlas::<String>::bind_group_layout(device);
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            &atlas_layout,
+            settings,
+            include_str!("label.wgsl"),
+        );
+
+        Self {
+            pipeline,
+            quad: Quad::new(device, "Label"),
+            instance_buffer: InstanceBuffer::new(
+                device,
+                "Label",
+                std::mem::size_of::<LabelRaw>(),
+                DEFAULT_INSTANCE_BUFFER_CAP,
+            ),
+            atlas: Atlas::new(
+                device,
+                &atlas_layout,
+                ATLAS_SIZE,
+                wgpu::TextureFormat::R8Unorm,
+                "Label Atlas",
+            ),
+            atlas_layout,
+            count: 0,
+            visible: true,
+            min_zoom: DEFAULT_LABEL_ZOOM,
+            degraded: false,
+            badges: vec![],
+            dropped: 0,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `label.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        atlas_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Label Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Label"),
+            bind_group_layouts: &[camera_bind_group_layout, atlas_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Label Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), LabelRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                ..Default::default()
+            },
+            // Captions stay readable over nodes and edges
+            depth_stencil: settings.depth_stencil(wgpu::CompareFunction::Always),
+            multisample: settings.multisample(),
+            multiview: None,
+        })
+    }
+
+    /// Lays out the labels and badges of every node not in `hidden` for a
+    /// camera at `scale`, rasterizing captions the atlas doesn't have yet.
+    /// Nodes outside `highlight`, when there is one, get dimmed labels.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        nodes: &[Node],
+        hidden: &BTreeSet<u32>,
+        highlight: Option<&BTreeSet<u32>>,
+        scale: f32,
+    ) {
+        self.count = 0;
+        let dropped = std::mem::take(&mut self.dropped);
+        let alpha = label_alpha(scale, self.min_zoom);
+        if !self.visible && self.badges.is_empty() || alpha == 0.0 || self.degraded {
+            return;
+        }
+        let badges = std::mem::take(&mut self.badges);
+        self.atlas.next_frame();
+        // Coordinates taken before the atlas grew point at the wrong texels;
+        // everything's cached by then, so the second pass is cheap
+        let labels = loop {
+            let size = self.atlas.size();
+            self.dropped = 0;
+            let mut labels = vec![];
+            for (i, node) in nodes.iter().enumerate() {
+                if hidden.contains(&(i as u32)) {
+                    continue;
+                }
+                let dimmed = highlight.is_some_and(|set| !set.contains(&(i as u32)));
+                let shade = |color: cgmath::Vector4<f32>| {
+                    let color = color * if dimmed { DIMMED_ALPHA } else { 1.0 };
+                    [color.x, color.y, color.z, color.w * alpha]
+                };
+                if self.visible {
+                    labels.extend(self.caption(device, queue, node, shade(LABEL_COLOR)));
+                }
+                for badge in &badges {
+                    labels.extend(self.badge(device, queue, node, badge, shade));
+                }
+            }
+            if self.atlas.size() == size {
+                break labels;
+            }
+        };
+        self.badges = badges;
+        if self.dropped > 0 && dropped == 0 {
+            log::warn!(
+                "the label atlas is full, {} labels or badges aren't drawn",
+                self.dropped
+            );
+        }
+        self.instance_buffer.reserve(device, queue, labels.len());
+        self.count = instancing::instance_count(labels.len(), self.instance_buffer.capacity());
+        queue.write_buffer(
+            self.instance_buffer.buffer(),
+            0,
+            bytemuck::cast_slice(&labels[..self.count as usize]),
+        );
+    }
+
+    /// `text` from the atlas, rasterizing it if it isn't there yet, and its
+    /// size with `pixel_size` world units per font pixel. The atlas grows
+    /// when what's on screen fills it; past the device's largest texture the
+    /// text counts as `dropped`.
+    fn glyphs(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        text: &str,
+        pixel_size: f32,
+    ) -> Option<([f32; 4], [f32; 2])> {
+        let rasterize = || font::rasterize(text, TEXELS_PER_PIXEL);
+        let region = loop {
+            if let Some(region) = self
+                .atlas
+                .get_or_insert_with(queue, text.to_string(), rasterize)
+            {
+                break region;
+            }
+            if !self.atlas.grow(device, queue, &self.atlas_layout) {
+                self.dropped += 1;
+                return None;
+            }
+        };
+        let size = [
+            region.width as f32 / TEXELS_PER_PIXEL as f32 * pixel_size,
+            region.height as f32 / TEXELS_PER_PIXEL as f32 * pixel_size,
+        ];
+        Some((region.uv(self.atlas.size()), size))
+    }
+
+    /// The caption under `node`, if it has one
+    fn caption(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        node: &Node,
+        color: [f32; 4],
+    ) -> Option<LabelRaw> {
+        let caption = truncate(&node.caption()?);
+        let (uv, [w, h]) = self.glyphs(device, queue, &caption, LABEL_PIXEL_SIZE)?;
+        let top = node.position.y - node.radius() - LABEL_GAP;
+        Some(LabelRaw {
+            center: [node.position.x, top - h / 2.0, node.position.z],
+            size: [w, h],
+            uv,
+            color,
+        })
+    }
+
+    /// A backdrop and the glyphs on top of it, when `node` has the badge.
+    /// `shade` turns colors into what's drawn.
+    fn badge(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        node: &Node,
+        badge: &Badge,
+        shade: impl Fn(cgmath::Vector4<f32>) -> [f32; 4],
+    ) -> Vec<LabelRaw> {
+        let Some((text, color)) = badge.content(node) else {
+            return vec![];
+        };
+        let Some((uv, [w, h])) = self.glyphs(device, queue, &text, BADGE_PIXEL_SIZE) else {
+            return vec![];
+        };
+        let Some((backdrop, _)) = self.glyphs(device, queue, BACKDROP, 1.0) else {
+            return vec![];
+        };
+        // Sampled well inside the block, where filtering can't pull in the
+        // atlas around it
+        let [u0, v0, u1, v1] = backdrop;
+        let (du, dv) = ((u1 - u0) / 4.0, (v1 - v0) / 4.0);
+        let center = badge.center(node);
+        let center = [center.x, center.y, node.position.z];
+        let padding = 2.0 * BADGE_PADDING * BADGE_PIXEL_SIZE;
+        vec![
+            LabelRaw {
+                center,
+                size: [w + padding, h + padding],
+                uv: [u0 + du, v0 + dv, u1 - du, v1 - dv],
+                color: shade(BADGE_BACKGROUND),
+            },
+            LabelRaw {
+                center,
+                size: [w, h],
+                uv,
+                color: shade(color),
+            },
+        ]
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.atlas.bind_group, &[]);
+        self.quad.draw(
+            render_pass,
+            self.instance_buffer.buffer(),
+            std::mem::size_of::<LabelRaw>(),
+            self.count,
+        );
+    }
+}
+
+/// Opacity of labels at camera `scale`: opaque from `min_zoom` up, fading
+/// to nothing at `FADE_START` times it, so zooming out leaves the shape of
+/// the graph rather than a wall of text
+pub fn label_alpha(scale: f32, min_zoom: f32) -> f32 {
+    let gone = min_zoom * FADE_START;
+    if scale >= min_zoom {
+        1.0
+    } else if scale <= gone {
+        0.0
+    } else {
+        (scale - gone) / (min_zoom - gone)
+    }
+}
+
+/// `caption` cut down to `MAX_LABEL_CHARS`, the last one being `~` when it
+/// was cut, on its first line only
+fn truncate(caption: &str) -> String {
+    let line = caption.lines().next().unwrap_or_default();
+    if line.chars().count() <= MAX_LABEL_CHARS {
+        return line.to_string();
+    }
+    let mut cut: String = line.chars().take(MAX_LABEL_CHARS - 1).collect();
+    cut.push('~');
+    cut
+}
+
+impl LabelRaw {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x4,
+        4 => Float32x4,
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LabelRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels_fade_below_the_threshold() {
+        assert_eq!(label_alpha(2.0, 0.5), 1.0);
+        assert_eq!(label_alpha(0.5, 0.5), 1.0);
+        assert_eq!(label_alpha(0.35, 0.5), 0.0);
+        assert_eq!(label_alpha(0.1, 0.5), 0.0);
+        let halfway = label_alpha(0.425, 0.5);
+        assert!((halfway - 0.5).abs() < 1e-5, "{halfway}");
+        // Zero turns labels on at any zoom
+        assert_eq!(label_alpha(0.0, 0.0), 1.0);
+
+        assert_eq!(truncate("a\nb"), "a");
+        let long = "x".repeat(40);
+        assert_eq!(truncate(&long).chars().count(), MAX_LABEL_CHARS);
+        assert!(truncate(&long).ends_with('~'));
+    }
+}
@@ -0,0 +1,49 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    dimensions: vec2<f32>,
+    scale: f32,
+};
+
+@binding(0) @group(0) var<uniform> camera: Camera;
+@binding(0) @group(1) var atlas: texture_2d<f32>;
+@binding(1) @group(1) var atlas_sampler: sampler;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+};
+
+struct Label {
+    @location(1) center: vec3<f32>,
+    @location(2) size: vec2<f32>,
+    // Top-left and bottom-right corners in the atlas
+    @location(3) uv: vec4<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+#include "constants.wgsl"
+#include "color.wgsl"
+
+@vertex
+fn vs_main(in: VertexInput, label: Label) -> VertexOutput {
+    let world = label.center + vec3<f32>(in.pos * label.size * 0.5, 0.0);
+    // Texture rows go down, quad corners go up
+    let corner = vec2<f32>(in.pos.x * 0.5 + 0.5, 0.5 - in.pos.y * 0.5);
+
+    var out: VertexOutput;
+    out.position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = mix(label.uv.xy, label.uv.zw, corner);
+    out.color = srgb_to_linear(label.color);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,574 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::{
+    graph::{Graph, NodeId},
+    physics::{BipartiteShape, ForceParams, Partition, Preset},
+    rng::Rng,
+    simulation::{Simulation, DEFAULT_SEED_RADIUS},
+};
+
+/// Spacing between consecutive points of the initial spiral
+pub const DEFAULT_PHYLLOTAXIS_RADIUS: f32 = 100.0;
+
+/// Position of the `i`th point of a phyllotaxis (sunflower) spiral. Gives an
+/// even, deterministic initial placement, the same one d3-force uses.
+pub fn phyllotaxis(i: usize, radius: f32) -> cgmath::Vector2<f32> {
+    let angle = i as f32 * std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    let r = radius * (0.5 + i as f32).sqrt();
+    cgmath::vec2(r * angle.cos(), r * angle.sin())
+}
+
+/// Spreads every node of the graph on a phyllotaxis spiral
+pub fn initial_placement(graph: &mut Graph) {
+    for (i, node) in graph.nodes.iter_mut().enumerate() {
+        node.position = phyllotaxis(i, DEFAULT_PHYLLOTAXIS_RADIUS).extend(0.0);
+    }
+    graph.sync_edges();
+}
+
+/// Whether every node is where `initial_placement` puts it, as in a graph
+/// imported from a file without positions
+pub fn is_initial_placement(graph: &Graph) -> bool {
+    graph
+        .nodes
+        .iter()
+        .enumerate()
+        .all(|(i, node)| node.position == phyllotaxis(i, DEFAULT_PHYLLOTAXIS_RADIUS).extend(0.0))
+}
+
+/// Scatters every node uniformly inside a disk of `radius`, the same seed
+/// always gives the same placement
+pub fn random_placement(graph: &mut Graph, seed: u64, radius: f32) {
+    let mut rng = Rng::new(seed);
+    for node in graph.nodes.iter_mut() {
+        let r = radius * rng.next_f32().sqrt();
+        let theta = rng.range(0.0, std::f32::consts::TAU);
+        node.position = cgmath::vec3(r * theta.cos(), r * theta.sin(), 0.0);
+    }
+    graph.sync_edges();
+}
+
+/// `multilevel` stops coarsening once a graph is this small
+pub const MIN_COARSE_NODES: usize = 50;
+/// A round of coarsening that merges fewer nodes than this share is the
+/// last, matching stalls on stars and isolated nodes
+const MIN_COARSENING: f32 = 0.1;
+/// Ticks the coarsest graph gets to settle
+const COARSE_TICKS: usize = 600;
+/// Ticks every finer level gets, at `REFINE_ALPHA`
+const REFINE_TICKS: usize = 150;
+/// Low enough to keep the shape the coarser level found, high enough to
+/// untangle what merging hid. Also what a multilevel layout is left at.
+pub const REFINE_ALPHA: f32 = 0.2;
+/// How much longer links get per level of coarsening. A merged node covers
+/// about twice the area, so links between them are about √2 times longer.
+const LEVEL_SPREAD: f32 = std::f32::consts::SQRT_2;
+/// Most `LEVEL_SPREAD` adds up to. Links pull with the cube of their
+/// length, so the coarsest levels of a deep hierarchy would overshoot
+/// further every tick until positions overflow.
+const MAX_LEVEL_SPREAD: f32 = 4.0;
+
+/// A graph with pairs of neighbouring nodes merged, one level up from the
+/// graph it was made from
+pub struct Level {
+    pub graph: Graph,
+    /// Node of `graph` each node of the finer graph was merged into
+    pub parent: Vec<NodeId>,
+    /// Links of the original graph each edge of `graph` stands for, by index
+    pub weights: Vec<f32>,
+}
+
+/// Merges every node with the unmatched neighbour it shares the heaviest
+/// edge with (heavy-edge matching), visiting nodes in random order. Edges
+/// between merged pairs collapse into one carrying their total weight,
+/// edges within a pair disappear. `weights` are indexed by edge.
+pub fn coarsen(graph: &Graph, weights: &[f32], rng: &mut Rng) -> Level {
+    let mut adjacency = vec![vec![]; graph.nodes.len()];
+    for (edge, weight) in graph.edges.iter().zip(weights) {
+        if edge.a_id != edge.b_id {
+            adjacency[edge.a_id as usize].push((edge.b_id, *weight));
+            adjacency[edge.b_id as usize].push((edge.a_id, *weight));
+        }
+    }
+    let mut order: Vec<usize> = (0..graph.nodes.len()).collect();
+    for i in (1..order.len()).rev() {
+        order.swap(i, (rng.next_u64() % (i as u64 + 1)) as usize);
+    }
+
+    let mut coarse = Graph::new();
+    let mut parent = vec![NodeId::MAX; graph.nodes.len()];
+    for i in order {
+        if parent[i] != NodeId::MAX {
+            continue;
+        }
+        let mate = adjacency[i]
+            .iter()
+            .filter(|(j, _)| parent[*j as usize] == NodeId::MAX)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(j, _)| *j as usize);
+        let mut node = graph.nodes[i].clone();
+        node.attributes.clear();
+        if let Some(mate) = mate {
+            node.position = (node.position + graph.nodes[mate].position) / 2.0;
+        }
+        let id = coarse.add_node(node);
+        parent[i] = id;
+        if let Some(mate) = mate {
+            parent[mate] = id;
+        }
+    }
+
+    let mut merged: HashMap<(NodeId, NodeId), usize> = HashMap::new();
+    let mut coarse_weights = vec![];
+    for (edge, weight) in graph.edges.iter().zip(weights) {
+        let (a, b) = (parent[edge.a_id as usize], parent[edge.b_id as usize]);
+        if a == b {
+            continue;
+        }
+        match merged.entry((a.min(b), a.max(b))) {
+            Entry::Occupied(entry) => coarse_weights[*entry.get()] += weight,
+            Entry::Vacant(entry) => {
+                entry.insert(coarse.add_edge(a, b) as usize);
+                coarse_weights.push(*weight);
+            }
+        }
+    }
+    Level {
+        graph: coarse,
+        parent,
+        weights: coarse_weights,
+    }
+}
+
+/// Multilevel force layout, what gets big graphs close to rest far sooner
+/// than a random start does: a 20 000 node graph takes about 20 seconds in
+/// a release build, and time grows about as N log N from there. It runs
+/// on the calling thread. The graph is coarsened down to
+/// `MIN_COARSE_NODES`, the coarsest graph laid out from a random placement,
+/// then each finer level starts with its nodes around where their merged
+/// node ended up and only needs a short, cool refinement. The same seed
+/// always gives the same layout. An empty graph is left as it is.
+pub fn multilevel(graph: &mut Graph, seed: u64) {
+    if graph.nodes.is_empty() {
+        return;
+    }
+    let mut rng = Rng::new(seed);
+    let params = ForceParams::calibrate(&graph.nodes, &graph.edges);
+    let mut levels: Vec<Level> = vec![];
+    loop {
+        let (finer, weights) = match levels.last() {
+            Some(level) => (&level.graph, level.weights.clone()),
+            None => (&*graph, vec![1.0; graph.edges.len()]),
+        };
+        if finer.nodes.len() <= MIN_COARSE_NODES {
+            break;
+        }
+        let level = coarsen(finer, &weights, &mut rng);
+        let merged = finer.nodes.len() - level.graph.nodes.len();
+        if (merged as f32) < finer.nodes.len() as f32 * MIN_COARSENING {
+            break;
+        }
+        levels.push(level);
+    }
+
+    // Positions of the level just laid out, and how the next finer graph's
+    // nodes map onto them
+    let mut coarser: Option<(Vec<cgmath::Vector2<f32>>, Vec<NodeId>)> = None;
+    while let Some(mut level) = levels.pop() {
+        let spread = LEVEL_SPREAD
+            .powi(levels.len() as i32 + 1)
+            .min(MAX_LEVEL_SPREAD);
+        let params = ForceParams {
+            link_distance: params.link_distance * spread,
+            repulsion_range: params.repulsion_range * spread,
+            strength: params.strength * spread,
+            ..params
+        };
+        refine(&mut level.graph, coarser.as_ref(), params, &mut rng);
+        coarser = Some((positions(&level.graph), level.parent));
+    }
+    refine(graph, coarser.as_ref(), params, &mut rng);
+}
+
+/// Lays out one level of `multilevel`, from a random placement for the
+/// coarsest one. Each level runs with the `Preset` for its size, the way the
+/// viewer would run it, so big levels sum repulsion with Barnes-Hut and
+/// cost N log N a tick rather than N².
+fn refine(
+    graph: &mut Graph,
+    coarser: Option<&(Vec<cgmath::Vector2<f32>>, Vec<NodeId>)>,
+    params: ForceParams,
+    rng: &mut Rng,
+) {
+    let params = Preset::for_size(graph.nodes.len(), graph.edges.len()).apply(params);
+    let Some((positions, parent)) = coarser else {
+        random_placement(graph, rng.next_u64(), DEFAULT_SEED_RADIUS);
+        let mut sim = Simulation::new(std::mem::take(graph));
+        sim.physics.set_params(params);
+        sim.run_until_settled(COARSE_TICKS);
+        *graph = sim.graph;
+        return;
+    };
+    // Merged pairs would start on top of each other, repulsion needs them
+    // apart to tell which way to push
+    let spread = params.link_distance / 2.0;
+    for (node, parent) in graph.nodes.iter_mut().zip(parent) {
+        let r = spread * rng.next_f32().sqrt();
+        let angle = rng.range(0.0, std::f32::consts::TAU);
+        let offset = cgmath::vec2(r * angle.cos(), r * angle.sin());
+        node.position = (positions[*parent as usize] + offset).extend(0.0);
+    }
+    graph.sync_edges();
+    let mut sim = Simulation::new(std::mem::take(graph));
+    sim.physics.set_params(params);
+    sim.physics.alpha = REFINE_ALPHA;
+    sim.run_until_settled(REFINE_TICKS);
+    *graph = sim.graph;
+}
+
+fn positions(graph: &Graph) -> Vec<cgmath::Vector2<f32>> {
+    graph.nodes.iter().map(|n| n.position.truncate()).collect()
+}
+
+/// Power iterations `spectral_placement` runs per axis at most
+const SPECTRAL_ITERATIONS: usize = 1000;
+/// Iterations stop once an axis changes direction by less than this
+const SPECTRAL_TOLERANCE: f64 = 1e-7;
+
+/// Places nodes by the two smallest non-trivial eigenvectors of the graph's
+/// degree-normalized Laplacian (Koren's spectral drawing), found with power
+/// iteration over the adjacency lists. Linked nodes end up close and the
+/// overall shape of meshes and grids comes out unfolded, which random
+/// starts rarely manage. The layout is scaled so links average
+/// `link_distance`; graphs without links, empty ones included, get
+/// `random_placement`.
+pub fn spectral_placement(graph: &mut Graph, seed: u64, link_distance: f32) {
+    let n = graph.nodes.len();
+    let mut adjacency = vec![vec![]; n];
+    for edge in &graph.edges {
+        if edge.a_id != edge.b_id {
+            adjacency[edge.a_id as usize].push(edge.b_id as usize);
+            adjacency[edge.b_id as usize].push(edge.a_id as usize);
+        }
+    }
+    if adjacency.iter().all(Vec::is_empty) {
+        random_placement(graph, seed, DEFAULT_SEED_RADIUS);
+        return;
+    }
+    // Isolated nodes count as their own neighbour, which keeps the
+    // iteration defined and leaves them wherever the constant axis has them
+    let degrees: Vec<f64> = adjacency.iter().map(|a| a.len().max(1) as f64).collect();
+    let d_dot = |a: &[f64], b: &[f64]| -> f64 {
+        a.iter()
+            .zip(b)
+            .zip(&degrees)
+            .map(|((a, b), d)| a * b * d)
+            .sum()
+    };
+
+    let mut rng = Rng::new(seed);
+    let mut axes: Vec<Vec<f64>> = vec![vec![1.0; n]];
+    for _ in 0..2 {
+        let mut axis: Vec<f64> = (0..n).map(|_| rng.range(-1.0, 1.0) as f64).collect();
+        for _ in 0..SPECTRAL_ITERATIONS {
+            // Keeps clear of the axes found so far, the constant one included
+            for other in &axes {
+                let k = d_dot(&axis, other) / d_dot(other, other);
+                axis.iter_mut().zip(other).for_each(|(a, o)| *a -= k * o);
+            }
+            let norm = axis.iter().map(|a| a * a).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                break;
+            }
+            axis.iter_mut().for_each(|a| *a /= norm);
+            // Halfway between staying put and the mean of the neighbours
+            let next: Vec<f64> = (0..n)
+                .map(|i| {
+                    let mean = adjacency[i].iter().map(|j| axis[*j]).sum::<f64>() / degrees[i];
+                    (axis[i] + mean) / 2.0
+                })
+                .collect();
+            let next_norm = next.iter().map(|a| a * a).sum::<f64>().sqrt();
+            let change = 1.0 - next.iter().zip(&axis).map(|(a, b)| a * b).sum::<f64>() / next_norm;
+            axis = next;
+            if change < SPECTRAL_TOLERANCE {
+                break;
+            }
+        }
+        axes.push(axis);
+    }
+
+    let mean_link = graph
+        .edges
+        .iter()
+        .map(|e| {
+            let (a, b) = (e.a_id as usize, e.b_id as usize);
+            (axes[1][a] - axes[1][b]).hypot(axes[2][a] - axes[2][b])
+        })
+        .sum::<f64>()
+        / graph.edges.len() as f64;
+    let scale = if mean_link > 0.0 {
+        link_distance as f64 / mean_link
+    } else {
+        0.0
+    };
+    // Nodes the eigenvectors can't tell apart, such as whole components,
+    // would start on top of each other, where repulsion has no direction
+    let jitter = link_distance / 10.0;
+    for (i, node) in graph.nodes.iter_mut().enumerate() {
+        node.position = cgmath::vec3(
+            (axes[1][i] * scale) as f32 + rng.range(-jitter, jitter),
+            (axes[2][i] * scale) as f32 + rng.range(-jitter, jitter),
+            0.0,
+        );
+    }
+    graph.sync_edges();
+}
+
+/// Rounds of reordering `bipartite_placement` runs, each side once per round
+const BIPARTITE_SWEEPS: usize = 8;
+
+/// Lays the sides of `partition` out in its columns or rings, nodes a link
+/// distance apart along them and nodes in neither side in between. Each
+/// side is ordered to cut down on crossing links with the barycenter
+/// heuristic: nodes move to the mean position of their neighbours on the
+/// other side, one side at a time. An empty graph is left as it is.
+pub fn bipartite_placement(graph: &mut Graph, partition: &Partition, link_distance: f32) {
+    if graph.nodes.is_empty() {
+        return;
+    }
+    let n = graph.nodes.len();
+    let mut adjacency = vec![vec![]; n];
+    for edge in &graph.edges {
+        if edge.a_id != edge.b_id {
+            adjacency[edge.a_id as usize].push(edge.b_id as usize);
+            adjacency[edge.b_id as usize].push(edge.a_id as usize);
+        }
+    }
+    // The two sides, then everything else
+    let mut columns: [Vec<usize>; 3] = Default::default();
+    for i in 0..n {
+        columns[partition.side(i as NodeId).unwrap_or(2)].push(i);
+    }
+    // Where each node is along its column, from 0 to 1 so columns of
+    // different lengths line up
+    let mut rank = vec![0.0; n];
+    let spread = |column: &[usize], rank: &mut [f32]| {
+        for (r, i) in column.iter().enumerate() {
+            rank[*i] = (r as f32 + 0.5) / column.len() as f32;
+        }
+    };
+    for column in &columns {
+        spread(column, &mut rank);
+    }
+    let mut reorder = |c: usize, by: &[usize], rank: &mut Vec<f32>| {
+        let barycenter = |i: usize| {
+            let ranks: Vec<f32> = adjacency[i]
+                .iter()
+                .filter(|j| by.contains(&partition.side(**j as NodeId).unwrap_or(2)))
+                .map(|j| rank[*j])
+                .collect();
+            match ranks.len() {
+                0 => rank[i],
+                len => ranks.iter().sum::<f32>() / len as f32,
+            }
+        };
+        let keys: Vec<f32> = columns[c].iter().map(|i| barycenter(*i)).collect();
+        let mut order: Vec<usize> = (0..columns[c].len()).collect();
+        order.sort_by(|a, b| keys[*a].total_cmp(&keys[*b]));
+        columns[c] = order.into_iter().map(|k| columns[c][k]).collect();
+        spread(&columns[c], rank);
+    };
+    for _ in 0..BIPARTITE_SWEEPS {
+        reorder(1, &[0], &mut rank);
+        reorder(0, &[1], &mut rank);
+    }
+    reorder(2, &[0, 1, 2], &mut rank);
+
+    let offsets = [
+        partition.offset(0, link_distance),
+        partition.offset(1, link_distance),
+    ];
+    for (c, column) in columns.iter().enumerate() {
+        let offset = offsets
+            .get(c)
+            .copied()
+            .unwrap_or((offsets[0] + offsets[1]) / 2.0);
+        for i in column {
+            let position = match partition.shape {
+                BipartiteShape::Columns => cgmath::vec2(
+                    offset,
+                    (0.5 - rank[*i]) * column.len() as f32 * link_distance,
+                ),
+                BipartiteShape::Rings => {
+                    let angle = rank[*i] * std::f32::consts::TAU;
+                    cgmath::vec2(angle.cos(), angle.sin()) * offset
+                }
+            };
+            graph.nodes[*i].position = position.extend(0.0);
+        }
+    }
+    graph.sync_edges();
+}
+
+#[cfg(test)]
+mod test {
+    use cgmath::MetricSpace;
+
+    use super::*;
+
+    /// `side` by `side` grid, each node linked to its right and lower
+    /// neighbours
+    fn grid(side: u32) -> Graph {
+        let mut graph = Graph::new();
+        for i in 0..side * side {
+            graph.add_named_node(&i.to_string());
+        }
+        for i in 0..side * side {
+            if i % side + 1 < side {
+                graph.add_edge(i, i + 1);
+            }
+            if i + side < side * side {
+                graph.add_edge(i, i + side);
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn initial_placement_is_recognized() {
+        let mut graph = grid(3);
+        initial_placement(&mut graph);
+        assert!(is_initial_placement(&graph));
+        graph.nodes[4].position.x += 1.0;
+        assert!(!is_initial_placement(&graph));
+    }
+
+    #[test]
+    fn coarsening_merges_neighbours() {
+        let mut graph = grid(4);
+        // Makes 0 and 1 each other's only choice
+        let mut weights = vec![1.0; graph.edges.len()];
+        weights[0] = 5.0;
+        let level = coarsen(&graph, &weights, &mut Rng::new(7));
+        assert_eq!(level.parent[0], level.parent[1]);
+        assert!(level.graph.nodes.len() >= 8 && level.graph.nodes.len() < 16);
+        for edge in &graph.edges {
+            let (a, b) = (edge.a_id as usize, edge.b_id as usize);
+            if level.parent[a] != level.parent[b] {
+                assert!(level.graph.edges.iter().any(|e| {
+                    let ends = [e.a_id, e.b_id];
+                    ends.contains(&level.parent[a]) && ends.contains(&level.parent[b])
+                }));
+            }
+        }
+        // Every link is accounted for, either merged away or in a weight
+        let inside: f32 = graph
+            .edges
+            .iter()
+            .zip(&weights)
+            .filter(|(e, _)| level.parent[e.a_id as usize] == level.parent[e.b_id as usize])
+            .map(|(_, weight)| weight)
+            .sum();
+        let total: f32 = level.weights.iter().sum();
+        assert_eq!(inside + total, weights.iter().sum::<f32>());
+
+        graph.edges.clear();
+        let isolated = coarsen(&graph, &[], &mut Rng::new(7));
+        assert_eq!(isolated.graph.nodes.len(), 16);
+    }
+
+    #[test]
+    fn multilevel_layout_is_seeded() {
+        let mut a = grid(12);
+        let mut b = a.clone();
+        multilevel(&mut a, 3);
+        multilevel(&mut b, 3);
+        assert_eq!(positions(&a), positions(&b));
+        // Grid neighbours end up closer than opposite corners
+        let p = positions(&a);
+        let link = p[0].distance(p[1]);
+        assert!(link < p[0].distance(p[143]) / 4.0, "{link}");
+    }
+
+    #[test]
+    fn spectral_placement_unfolds_grids() {
+        let mut graph = grid(12);
+        spectral_placement(&mut graph, 5, 100.0);
+        let p = positions(&graph);
+        let mean_link = graph
+            .edges
+            .iter()
+            .map(|e| p[e.a_id as usize].distance(p[e.b_id as usize]))
+            .sum::<f32>()
+            / graph.edges.len() as f32;
+        assert!((mean_link - 100.0).abs() < 15.0, "{mean_link}");
+        // Corners end up about as far apart as the grid is wide, not folded
+        // onto each other
+        for (a, b) in [(0, 143), (11, 132)] {
+            let dist = p[a].distance(p[b]);
+            assert!(dist > 800.0, "{a}-{b}: {dist}");
+        }
+
+        let mut isolated = Graph::new();
+        isolated.add_named_node("a");
+        isolated.add_named_node("b");
+        spectral_placement(&mut isolated, 5, 100.0);
+        assert_ne!(isolated.nodes[0].position, isolated.nodes[1].position);
+    }
+
+    #[test]
+    fn bipartite_columns_uncross() {
+        let mut graph = Graph::new();
+        for (name, kind) in [
+            ("a", "l"),
+            ("b", "l"),
+            ("c", "l"),
+            ("x", "r"),
+            ("y", "r"),
+            ("z", "r"),
+        ] {
+            let id = graph.add_named_node(name);
+            graph.nodes[id as usize]
+                .attributes
+                .insert("kind".into(), kind.into());
+        }
+        graph.add_named_node("loose");
+        for (a, b) in [(0, 5), (1, 4), (2, 3), (6, 0)] {
+            graph.add_edge(a, b);
+        }
+        let partition = Partition::new(&graph.nodes, "kind", BipartiteShape::Columns).unwrap();
+        assert_eq!(partition.side(3), Some(1));
+        assert_eq!(partition.side(6), None);
+
+        bipartite_placement(&mut graph, &partition, 100.0);
+        let y = |i: usize| graph.nodes[i].position.y;
+        let links = [(0, 5), (1, 4), (2, 3)];
+        for (i, (a, b)) in links.iter().enumerate() {
+            for (c, d) in &links[i + 1..] {
+                assert!(
+                    (y(*a) - y(*c)) * (y(*b) - y(*d)) > 0.0,
+                    "{a}-{b} crosses {c}-{d}"
+                );
+            }
+        }
+        let x: Vec<f32> = graph.nodes.iter().map(|node| node.position.x).collect();
+        assert_eq!(x, [-100.0, -100.0, -100.0, 100.0, 100.0, 100.0, 0.0]);
+        assert!(Partition::new(&graph.nodes, "label", BipartiteShape::Rings).is_err());
+    }
+
+    #[test]
+    fn empty_graphs_are_left_alone() {
+        let mut graph = Graph::new();
+        initial_placement(&mut graph);
+        random_placement(&mut graph, 1, 100.0);
+        multilevel(&mut graph, 1);
+        spectral_placement(&mut graph, 1, 100.0);
+        assert!(graph.nodes.is_empty() && graph.edges.is_empty());
+        assert!(coarsen(&graph, &[], &mut Rng::new(1))
+            .graph
+            .nodes
+            .is_empty());
+        assert_eq!(ForceParams::calibrate(&[], &[]), ForceParams::default());
+    }
+}
@@ -0,0 +1,251 @@
+use cgmath::{vec2, vec4};
+
+use crate::{
+    i18n::Catalog,
+    overlay::{text_rects, text_size, Anchor, Rect},
+    style::{
+        gradient, Channel, Scale, StyleRule, MAX_EDGE_WIDTH, MAX_NODE_SIZE, MIN_EDGE_WIDTH,
+        MIN_NODE_SIZE,
+    },
+};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+/// Categories listed before the rest are summarized as "+N more"
+pub const MAX_CATEGORIES: usize = 10;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.6);
+const TITLE_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const LABEL_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const SIZE_SWATCH_COLOR: cgmath::Vector4<f32> = vec4(0.37, 0.71, 0.61, 0.9);
+const SWATCH: f32 = 14.0;
+/// Largest size swatch, drawn for `MAX_NODE_SIZE`
+const MAX_SIZE_SWATCH: f32 = 32.0;
+const GRADIENT_WIDTH: f32 = 160.0;
+const GRADIENT_STEPS: usize = 32;
+const GAP: f32 = 6.0;
+
+/// Explains the active style rules: swatches for categories, a color ramp for
+/// numeric colors and a few sample squares for sizes
+#[derive(Debug, Clone, Copy)]
+pub struct Legend {
+    pub anchor: Anchor,
+    pub margin: f32,
+    pub padding: f32,
+}
+
+impl Default for Legend {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::TopRight,
+            margin: 16.0,
+            padding: 8.0,
+        }
+    }
+}
+
+impl Legend {
+    pub fn rects(
+        &self,
+        width: f32,
+        height: f32,
+        scales: &[(StyleRule, Scale)],
+        strings: &Catalog,
+    ) -> Vec<Rect> {
+        if scales.is_empty() {
+            return vec![];
+        }
+
+        // Laid out from (0, 0) first, then moved into place once the size is known
+        let mut content = vec![];
+        let text_height = text_size("0", TEXT_PIXEL).y;
+        let mut y = 0.0;
+        for (rule, scale) in scales {
+            let channel = match rule.channel {
+                Channel::Color => strings.get("legend-color"),
+                Channel::Size => strings.get("legend-size"),
+                Channel::Width => strings.get("legend-width"),
+            };
+            let title = format!("{} ({channel})", rule.attr);
+            content.extend(text_rects(&title, vec2(0.0, y), TEXT_PIXEL, TITLE_COLOR));
+            y += text_height + GAP;
+
+            match scale {
+                Scale::Categorical(categories) => {
+                    for (label, color) in categories.iter().take(MAX_CATEGORIES) {
+                        content.push(Rect::new(vec2(0.0, y), vec2(SWATCH, SWATCH), *color));
+                        content.extend(text_rects(
+                            label,
+                            vec2(SWATCH + GAP, y + (SWATCH - text_height) / 2.0),
+                            TEXT_PIXEL,
+                            LABEL_COLOR,
+                        ));
+                        y += SWATCH + GAP;
+                    }
+                    if categories.len() > MAX_CATEGORIES {
+                        let more = strings.format(
+                            "legend-more",
+                            &[("count", &(categories.len() - MAX_CATEGORIES))],
+                        );
+                        content.extend(text_rects(&more, vec2(0.0, y), TEXT_PIXEL, LABEL_COLOR));
+                        y += text_height + GAP;
+                    }
+                }
+                Scale::Gradient { min, max } => {
+                    let step = GRADIENT_WIDTH / GRADIENT_STEPS as f32;
+                    content.extend((0..GRADIENT_STEPS).map(|i| {
+                        Rect::new(
+                            vec2(step * i as f32, y),
+                            vec2(step, SWATCH),
+                            gradient(i as f32 / (GRADIENT_STEPS - 1) as f32),
+                        )
+                    }));
+                    y += SWATCH + GAP / 2.0;
+                    let max = format_number(*max);
+                    let max_x = GRADIENT_WIDTH - text_size(&max, TEXT_PIXEL).x;
+                    content.extend(text_rects(
+                        &format_number(*min),
+                        vec2(0.0, y),
+                        TEXT_PIXEL,
+                        LABEL_COLOR,
+                    ));
+                    content.extend(text_rects(&max, vec2(max_x, y), TEXT_PIXEL, LABEL_COLOR));
+                    y += text_height + GAP;
+                }
+                Scale::Size { min, max } => {
+                    let mut x = 0.0;
+                    for t in [0.0, 0.5, 1.0] {
+                        let size = MIN_NODE_SIZE + (MAX_NODE_SIZE - MIN_NODE_SIZE) * t;
+                        let side = MAX_SIZE_SWATCH * size / MAX_NODE_SIZE;
+                        let label = format_number(min + (max - min) * t);
+                        content.push(Rect::new(
+                            vec2(x, y + MAX_SIZE_SWATCH - side),
+                            vec2(side, side),
+                            SIZE_SWATCH_COLOR,
+                        ));
+                        content.extend(text_rects(
+                            &label,
+                            vec2(x, y + MAX_SIZE_SWATCH + GAP / 2.0),
+                            TEXT_PIXEL,
+                            LABEL_COLOR,
+                        ));
+                        x += side.max(text_size(&label, TEXT_PIXEL).x) + GAP * 2.0;
+                    }
+                    y += MAX_SIZE_SWATCH + GAP / 2.0 + text_height + GAP;
+                }
+                Scale::Width { min, max } => {
+                    // Lines of each width, thinnest on top
+                    for t in [0.0, 0.5, 1.0] {
+                        let width = MIN_EDGE_WIDTH + (MAX_EDGE_WIDTH - MIN_EDGE_WIDTH) * t;
+                        let thickness = MAX_SIZE_SWATCH / 4.0 * width / MAX_EDGE_WIDTH;
+                        content.push(Rect::new(
+                            vec2(0.0, y + (text_height - thickness).max(0.0) / 2.0),
+                            vec2(MAX_SIZE_SWATCH, thickness),
+                            SIZE_SWATCH_COLOR,
+                        ));
+                        content.extend(text_rects(
+                            &format_number(min + (max - min) * t),
+                            vec2(MAX_SIZE_SWATCH + GAP, y),
+                            TEXT_PIXEL,
+                            LABEL_COLOR,
+                        ));
+                        y += thickness.max(text_height) + GAP;
+                    }
+                }
+            }
+            y += GAP;
+        }
+
+        let extent = content.iter().fold(vec2(0.0f32, 0.0f32), |acc, r| {
+            vec2(
+                acc.x.max(r.origin.x + r.size.x),
+                acc.y.max(r.origin.y + r.size.y),
+            )
+        });
+        let size = extent + vec2(self.padding, self.padding) * 2.0;
+        let origin = self.anchor.place(width, height, size, self.margin);
+        let offset = origin + vec2(self.padding, self.padding);
+
+        std::iter::once(Rect::new(origin, size, BACKGROUND_COLOR))
+            .chain(content.into_iter().map(|mut r| {
+                r.origin += offset;
+                r
+            }))
+            .collect()
+    }
+}
+
+/// Short label for a number: integers as is, everything else with two decimals
+fn format_number(n: f32) -> String {
+    if n.fract() == 0.0 {
+        format!("{n}")
+    } else {
+        format!("{n:.2}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn legend_sits_in_its_corner_and_lists_categories() {
+        let categories: Vec<_> = (0..MAX_CATEGORIES + 2)
+            .map(|i| (format!("c{i}"), vec4(i as f32 / 20.0, 0.0, 0.0, 1.0)))
+            .collect();
+        let scales = [(
+            StyleRule::new("group", Channel::Color),
+            Scale::Categorical(categories.clone()),
+        )];
+        let legend = Legend::default();
+        let (width, height) = (800.0, 600.0);
+        let rects = legend.rects(width, height, &scales, &Catalog::english());
+
+        let background = rects[0];
+        assert_eq!(background.origin.y, legend.margin);
+        assert_eq!(
+            background.origin.x + background.size.x,
+            width - legend.margin
+        );
+        assert!(rects[1..].iter().all(|r| background.contains(&r.origin)));
+        // A swatch for each category shown, the rest summarized
+        for (_, color) in &categories[..MAX_CATEGORIES] {
+            let swatch = rects.iter().find(|r| r.color == *color);
+            assert_eq!(swatch.map(|r| r.size), Some(vec2(SWATCH, SWATCH)));
+        }
+        let hidden = categories[MAX_CATEGORIES].1;
+        assert!(!rects.iter().any(|r| r.color == hidden));
+
+        assert!(legend
+            .rects(width, height, &[], &Catalog::english())
+            .is_empty());
+    }
+
+    #[test]
+    fn size_swatches_grow_with_the_values() {
+        let scales = [(
+            StyleRule::new("weight", Channel::Size),
+            Scale::Size {
+                min: 0.0,
+                max: 10.0,
+            },
+        )];
+        let legend = Legend {
+            anchor: Anchor::BottomLeft,
+            ..Legend::default()
+        };
+        let rects = legend.rects(800.0, 600.0, &scales, &Catalog::english());
+        let sides: Vec<f32> = rects
+            .iter()
+            .filter(|r| r.color == SIZE_SWATCH_COLOR)
+            .map(|r| r.size.x)
+            .collect();
+        assert_eq!(sides.len(), 3);
+        assert_eq!(sides[0], MAX_SIZE_SWATCH * MIN_NODE_SIZE / MAX_NODE_SIZE);
+        assert_eq!(sides[2], MAX_SIZE_SWATCH);
+        assert_eq!(rects[0].origin.x, legend.margin);
+        assert_eq!(rects[0].origin.y + rects[0].size.y, 600.0 - legend.margin);
+        assert_eq!(format_number(2.0), "2");
+        assert_eq!(format_number(0.25), "0.25");
+    }
+}
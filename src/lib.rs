@@ -1,20 +1,120 @@
+//! Force-directed graph layout and an interactive viewer for it.
+//!
+//! The graph model (`graph::Graph`), the physics and the layouts run without
+//! a window or GPU, so the crate works as a layout library in servers and
+//! tests: build a `Graph`, hand it to `simulation::Simulation`, tick it and
+//! read the positions back. The winit and wgpu front end, `run` and
+//! `main_state::State`, is behind the default `render` feature; depend on the
+//! crate with `default-features = false` to leave it out.
+//!
+//! Threads other than the one drawing can read and change the graph through
+//! a `handle::GraphHandle`, from `State::graph_handle`.
+
+pub mod algorithms;
+#[cfg(feature = "render")]
+pub mod annotation;
+pub mod attributes;
+#[cfg(feature = "render")]
+pub mod badge;
+#[cfg(feature = "render")]
 pub mod camera;
+pub mod changes;
+#[cfg(feature = "render")]
+pub mod chart;
+pub mod color;
+#[cfg(feature = "render")]
+pub mod console;
+#[cfg(feature = "render")]
+pub mod debug;
+pub mod diff;
 pub mod edge;
+#[cfg(feature = "render")]
+pub mod export;
+pub mod font;
+pub mod formats;
+pub mod generators;
+#[cfg(feature = "render")]
+pub mod gpu_timer;
+pub mod graph;
+pub mod handle;
+#[cfg(feature = "render")]
+pub mod help;
+pub mod history;
+#[cfg(feature = "render")]
+pub mod hot_reload;
+#[cfg(feature = "render")]
+pub mod hull;
+pub mod i18n;
+#[cfg(feature = "render")]
 pub mod input;
+#[cfg(feature = "render")]
+pub mod instancing;
+#[cfg(feature = "render")]
+pub mod label;
+pub mod layout;
+#[cfg(feature = "render")]
+pub mod legend;
+pub mod limits;
+#[cfg(feature = "render")]
 pub mod main_state;
+#[cfg(feature = "render")]
+pub mod menu;
+pub mod metrics;
+#[cfg(feature = "render")]
 pub mod mouse;
 pub mod node;
+#[cfg(feature = "render")]
+pub mod overlay;
 pub mod physics;
+#[cfg(feature = "render")]
+pub mod picker;
+#[cfg(feature = "render")]
+pub mod prefs;
+pub mod prelude;
+pub mod quadtree;
+#[cfg(feature = "render")]
+pub mod quality;
+pub mod rng;
+#[cfg(feature = "render")]
+pub mod ruler;
+pub mod sampling;
+#[cfg(feature = "render")]
+pub mod search;
+pub mod session;
+pub mod shader;
+pub mod simulation;
+pub mod snapshot;
+pub mod spatial;
+#[cfg(feature = "render")]
+pub mod stats;
+#[cfg(feature = "render")]
+pub mod status;
+pub mod style;
+#[cfg(feature = "render")]
 pub mod texture;
+pub mod ticker;
+#[cfg(feature = "render")]
+pub mod timeline;
+pub mod units;
+#[cfg(feature = "render")]
+pub mod upscale;
+
+/// Reading and writing GraphML and DOT, for round trips through Gephi and
+/// Graphviz, under the name people look for; `formats` has the other formats
+pub use formats as io;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "render", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "render")]
 use std::time::{SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "render")]
 use bytemuck::{Pod, Zeroable};
 use cgmath::Vector4;
+#[cfg(feature = "render")]
 use main_state::State;
+#[cfg(feature = "render")]
 use winit::{
     dpi::LogicalSize,
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
@@ -22,11 +122,85 @@ use winit::{
     window::WindowBuilder,
 };
 
+#[cfg(feature = "render")]
 pub const SAMPLE_COUNT: u8 = 4;
 
-// For MacOS bc retina screens double the amount of pixels
-pub const SCREEN_SCALE: f32 = 2.0;
+/// How every pipeline draws, picked once the device is. Passes must agree
+/// with the attachments `draw` hands them.
+#[cfg(feature = "render")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderSettings {
+    /// MSAA samples per pixel, 1 turns it off
+    pub sample_count: u8,
+    /// Whether there's a depth buffer for passes to test against
+    pub depth: bool,
+}
 
+#[cfg(feature = "render")]
+impl RenderSettings {
+    pub const DEFAULT: Self = Self {
+        sample_count: SAMPLE_COUNT,
+        depth: true,
+    };
+    /// What `--safe-mode` renders with: no MSAA and no depth buffer, which
+    /// software adapters and old drivers handle. Nodes and edges are drawn
+    /// back to front anyway, so they look the same bar the jagged edges.
+    pub const SAFE: Self = Self {
+        sample_count: 1,
+        depth: false,
+    };
+
+    pub fn multisample(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Read-only depth testing with `compare`, when there's a depth buffer
+    pub fn depth_stencil(&self, compare: wgpu::CompareFunction) -> Option<wgpu::DepthStencilState> {
+        self.depth.then(|| wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    }
+
+    /// MSAA and depth textures to render at `config`'s size into, whichever
+    /// of them these settings use
+    pub fn attachments(
+        &self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> (Option<texture::Texture>, Option<texture::Texture>) {
+        let msaa = (self.sample_count > 1).then(|| {
+            texture::Texture::create_msaa_texture(
+                device,
+                config,
+                &format!("{label} MSAA"),
+                self.sample_count,
+            )
+        });
+        let depth = self.depth.then(|| {
+            texture::Texture::create_depth_texture(
+                device,
+                config,
+                self.sample_count,
+                &format!("{label} Depth"),
+            )
+        });
+        (msaa, depth)
+    }
+}
+/// How often an idle window wakes up to check for console commands, and on
+/// the web whether a hidden canvas is back
+#[cfg(feature = "render")]
+const IDLE_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+#[cfg(feature = "render")]
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -44,8 +218,8 @@ pub fn screen_space_to_clip_space(
     // (-960, -540) -> (960, 540)
     // (-1, -1) -> (1, 1)
     let pos = cgmath::vec2(pos.x - (width), -(pos.y - (height)));
-    let pos = cgmath::vec2(pos.x / (width), pos.y / (height));
-    pos
+
+    cgmath::vec2(pos.x / (width), pos.y / (height))
 }
 
 pub fn clip_space_to_screen_space(
@@ -54,8 +228,8 @@ pub fn clip_space_to_screen_space(
     pos: &cgmath::Vector2<f32>,
 ) -> cgmath::Vector2<f32> {
     let pos = cgmath::vec2(pos.x * width, pos.y * height);
-    let pos = cgmath::vec2(pos.x + width, height - pos.y);
-    pos
+
+    cgmath::vec2(pos.x + width, height - pos.y)
 }
 
 pub fn screen_vec_to_clip_vec(
@@ -63,24 +237,24 @@ pub fn screen_vec_to_clip_vec(
     height: f32,
     pos: &cgmath::Vector2<f32>,
 ) -> cgmath::Vector2<f32> {
-    let pos = cgmath::vec2((2.0 * pos.x) / width, (2.0 * pos.y) / height);
-    pos
+    cgmath::vec2((2.0 * pos.x) / width, (2.0 * pos.y) / height)
 }
 pub fn clip_vec_to_screen_vec(
     width: f32,
     height: f32,
     pos: &cgmath::Vector2<f32>,
 ) -> cgmath::Vector2<f32> {
-    let pos = cgmath::vec2((pos.x / 2.0) * width, (pos.y / 2.0) * height);
-    pos
+    cgmath::vec2((pos.x / 2.0) * width, (pos.y / 2.0) * height)
 }
 
+#[cfg(feature = "render")]
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Vertex {
     pub position: [f32; 2],
 }
 
+#[cfg(feature = "render")]
 impl Vertex {
     const ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0=>Float32x2];
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -92,8 +266,25 @@ impl Vertex {
     }
 }
 
+/// Whether `--safe-mode` was passed, for GPUs and VMs that can't run the
+/// full renderer. It's picked automatically when they fail outright.
+#[cfg(feature = "render")]
+fn safe_mode() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::env::args().skip(1).any(|arg| arg == "--safe-mode");
+    #[cfg(target_arch = "wasm32")]
+    false
+}
+
+#[cfg(feature = "render")]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub fn run() {
+    // Command replies are logged as well as shown in the status bar
+    #[cfg(not(target_arch = "wasm32"))]
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("warn,force_graph_wgpu=info"),
+    )
+    .init();
     let mut frame: u128 = 0;
     let mut start: u128 = 0;
 
@@ -132,7 +323,7 @@ pub fn run() {
             .expect("Couldn't append canvas to document body.");
     }
 
-    let mut state = pollster::block_on(State::new(&window));
+    let mut state = pollster::block_on(State::new(&window, safe_mode()));
 
     event_loop.run(move |event, _, control_flow| {
         if start == 0 {
@@ -142,38 +333,46 @@ pub fn run() {
                 .as_millis();
         }
 
+        // Anything happening to the window may change what's drawn
+        if matches!(&event, Event::WindowEvent { window_id, .. } if *window_id == window.id()) {
+            window.request_redraw();
+        }
+
         match event {
-            Event::DeviceEvent { event, .. } => {
-                state.device_input(&event);
-            }
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == window.id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            // new_inner_size is &mut so w have to dereference it twice
-                            state.resize(**new_inner_size);
-                        }
-                        _ => {}
+            } if window_id == window.id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::DroppedFile(path) => {
+                        let done = state.load_file(path);
+                        state.report(done);
                     }
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    } => {
+                        state.set_scale_factor(*scale_factor);
+                        // new_inner_size is &mut so w have to dereference it twice
+                        state.resize(**new_inner_size);
+                    }
+                    _ => {}
                 }
             }
-            Event::RedrawRequested(window_id) if window_id == window.id() => {
+            Event::RedrawRequested(window_id) if window_id == window.id() && !state.suspended => {
                 state.update();
                 match state.render() {
                     Ok(_) => {}
@@ -198,21 +397,57 @@ pub fn run() {
                     ));
                 }
             }
-            Event::MainEventsCleared => {
-                // RedrawRequested will only trigger once, unless we manually
-                // request it.
+            Event::MainEventsCleared if state.process_console() => {
                 window.request_redraw();
             }
+            // Mobile platforms take the surface away while in the background
+            Event::Suspended => state.suspend(),
+            Event::Resumed => state.resize(window.inner_size()),
+            Event::RedrawEventsCleared => {
+                // `display: none` collapses the canvas without a resize event
+                #[cfg(target_arch = "wasm32")]
+                match (canvas_hidden(&window), state.suspended) {
+                    (true, false) => state.suspend(),
+                    (false, true) => {
+                        state.resize(window.inner_size());
+                        window.request_redraw();
+                    }
+                    _ => (),
+                }
+
+                // Keep drawing while something moves, otherwise sleep until
+                // an event arrives
+                if state.is_animating() {
+                    window.request_redraw();
+                    *control_flow = ControlFlow::Poll;
+                } else {
+                    *control_flow = ControlFlow::WaitUntil(instant::Instant::now() + IDLE_POLL);
+                }
+            }
             _ => {}
         }
     });
 }
 
+/// Whether the canvas takes up no space on the page
+#[cfg(all(feature = "render", target_arch = "wasm32"))]
+fn canvas_hidden(window: &winit::window::Window) -> bool {
+    use winit::platform::web::WindowExtWebSys;
+    let canvas = web_sys::Element::from(window.canvas());
+    canvas.client_width() == 0 || canvas.client_height() == 0
+}
+
 pub struct ColorGenerator {
     pub colors: Vec<Vector4<f32>>,
     pub idx: usize,
 }
 
+impl Default for ColorGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ColorGenerator {
     pub fn new() -> Self {
         Self {
@@ -237,30 +472,14 @@ impl ColorGenerator {
         }
     }
 
-    pub fn next(&mut self) -> Vector4<f32> {
+    pub fn next_color(&mut self) -> Vector4<f32> {
         let idx = self.idx % self.colors.len();
         self.idx += 1;
-        self.colors[idx].clone()
+        self.colors[idx]
     }
 
     fn hex_to_rgba(hex: &str) -> Vector4<f32> {
-        let mut hex = hex.to_string();
-        if hex.len() == 3 {
-            hex = format!(
-                "{}{}{}{}{}{}",
-                hex.chars().nth(0).unwrap(),
-                hex.chars().nth(0).unwrap(),
-                hex.chars().nth(1).unwrap(),
-                hex.chars().nth(1).unwrap(),
-                hex.chars().nth(2).unwrap(),
-                hex.chars().nth(2).unwrap()
-            );
-        }
-        let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
-        let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
-        let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
-        // let a = u8::from_str_radix(&hex[6..8], 16).unwrap_or(255);
-        Vector4::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+        color::from_hex(hex).expect("palette colors are valid hex")
     }
 }
 
@@ -268,6 +487,6 @@ impl Iterator for ColorGenerator {
     type Item = Vector4<f32>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(self.next())
+        Some(self.next_color())
     }
 }
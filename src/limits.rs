@@ -0,0 +1,138 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    graph::{Graph, GraphView, NodeId},
+    rng::Rng,
+};
+
+/// Default `Limits::soft_nodes`
+pub const DEFAULT_SOFT_NODES: usize = 5_000;
+/// Default `Limits::soft_edges`
+pub const DEFAULT_SOFT_EDGES: usize = 20_000;
+/// Default `Limits::hard_nodes`, around where a frame's instance buffers
+/// stop fitting comfortably on integrated GPUs
+pub const DEFAULT_HARD_NODES: usize = 200_000;
+/// Default `Limits::hard_edges`
+pub const DEFAULT_HARD_EDGES: usize = 1_000_000;
+
+/// Element counts past which the viewer cuts corners rather than slow to a
+/// crawl or run out of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Past this many nodes, or `soft_edges` edges, labels, badges and edge
+    /// markers are no longer drawn
+    pub soft_nodes: usize,
+    pub soft_edges: usize,
+    /// Past this many nodes, or `hard_edges` edges, a loaded graph is cut
+    /// down to a sample, see `Limits::sample`
+    pub hard_nodes: usize,
+    pub hard_edges: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            soft_nodes: DEFAULT_SOFT_NODES,
+            soft_edges: DEFAULT_SOFT_EDGES,
+            hard_nodes: DEFAULT_HARD_NODES,
+            hard_edges: DEFAULT_HARD_EDGES,
+        }
+    }
+}
+
+impl Limits {
+    pub fn past_soft(&self, nodes: usize, edges: usize) -> bool {
+        nodes > self.soft_nodes || edges > self.soft_edges
+    }
+
+    pub fn past_hard(&self, nodes: usize, edges: usize) -> bool {
+        nodes > self.hard_nodes || edges > self.hard_edges
+    }
+
+    /// `graph` cut down to the hard limits: a random sample of its nodes
+    /// with the edges between them, and a random sample of those when there
+    /// are still too many. Both keep their order, and a graph within the
+    /// limits comes back whole.
+    pub fn sample(&self, graph: &Graph, rng: &mut Rng) -> Graph {
+        let nodes = choose(graph.nodes.len(), self.hard_nodes, rng);
+        let mut sampled = graph.subgraph(&nodes.into_iter().map(|i| i as NodeId).collect());
+        if sampled.edges.len() > self.hard_edges {
+            let kept = choose(sampled.edges.len(), self.hard_edges, rng);
+            sampled.edges = std::mem::take(&mut sampled.edges)
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| kept.contains(i))
+                .map(|(_, edge)| edge)
+                .collect();
+        }
+        sampled
+    }
+
+    /// Lines describing the limits, for the `limits` command
+    pub fn describe(&self) -> String {
+        format!(
+            "soft: {} nodes, {} edges\nhard: {} nodes, {} edges",
+            self.soft_nodes, self.soft_edges, self.hard_nodes, self.hard_edges
+        )
+    }
+}
+
+/// `count` of the indices below `len` picked at random, all of them when
+/// there aren't more
+pub fn choose(len: usize, count: usize, rng: &mut Rng) -> BTreeSet<usize> {
+    if count >= len {
+        return (0..len).collect();
+    }
+    // The first `count` steps of a Fisher-Yates shuffle
+    let mut indices: Vec<usize> = (0..len).collect();
+    for i in 0..count {
+        let j = i + (rng.next_u64() % (len - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    indices.truncate(count);
+    indices.into_iter().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::node_name;
+
+    #[test]
+    fn samples_fit_the_hard_limits() {
+        let mut graph = Graph::new();
+        let nodes: Vec<NodeId> = (0..100)
+            .map(|i| graph.add_named_node(&i.to_string()))
+            .collect();
+        for (i, a) in nodes.iter().enumerate() {
+            for b in &nodes[i + 1..] {
+                graph.add_edge(*a, *b);
+            }
+        }
+        let limits = Limits {
+            hard_nodes: 40,
+            hard_edges: 300,
+            ..Limits::default()
+        };
+        assert!(limits.past_hard(graph.nodes.len(), 0));
+        let sampled = limits.sample(&graph, &mut Rng::new(3));
+        assert_eq!(sampled.nodes.len(), 40);
+        assert_eq!(sampled.edges.len(), 300);
+        assert!(!limits.past_hard(sampled.nodes.len(), sampled.edges.len()));
+        // Every edge still joins two sampled nodes
+        assert!(sampled
+            .edges
+            .iter()
+            .all(|edge| edge.a_id < 40 && edge.b_id < 40));
+        // Same seed, same sample
+        let again = limits.sample(&graph, &mut Rng::new(3));
+        assert_eq!(
+            again.nodes.iter().map(node_name).collect::<Vec<_>>(),
+            sampled.nodes.iter().map(node_name).collect::<Vec<_>>()
+        );
+
+        let whole = Limits::default().sample(&graph, &mut Rng::new(3));
+        assert_eq!(whole.nodes.len(), 100);
+        assert_eq!(whole.edges.len(), graph.edges.len());
+    }
+}
@@ -0,0 +1,102 @@
+# English UI strings, also the fallback for messages other catalogs leave out.
+# Placeholders are written { $name }.
+
+mode-navigate = navigate
+mode-add-node = add node
+mode-brush = brush
+mode-magnet = magnet
+mode-repel = repel
+mode-edge-create = edge-create
+mode-select = select
+mode-pan = pan
+mode-move = move
+mode-reconnect = reconnect edge
+mode-lasso = lasso
+
+status-selected = { $count } selected
+status-paused = paused
+status-frozen = frozen
+status-settled = settled
+status-running = running, alpha { $alpha }
+status-error = error: { $error }
+
+help-title = controls
+help-close = press any key to close
+pointer-add-node = add node
+pointer-add-connected = add connected node
+pointer-brush = brush nodes
+pointer-brush-chain = brush a chain
+pointer-magnet = pull nearby nodes
+pointer-repel = push nearby nodes away
+pointer-create-edge = create edge
+pointer-move-node = move node
+pointer-reconnect-edge = reconnect edge
+pointer-delete-edge = delete edge
+pointer-node-menu = node actions
+pointer-pin = pin or unpin node
+pointer-box-select = box select
+pointer-lasso = lasso select
+pointer-pan = pan
+pointer-zoom = zoom
+pointer-cancel = cancel, then quit
+key-delete = delete selected nodes
+key-pin = pin or unpin hovered or selected nodes
+key-recolor = give hovered or selected nodes the next color
+key-undo = undo
+key-redo = redo
+key-fit = zoom to fit the graph
+key-home = reset the view
+key-save = save the session
+key-open = open a graph or session file
+key-palette = recent files and commands
+key-connect = connect hovered node
+key-histogram = degree histogram
+key-timeline = timeline
+key-pause = pause simulation
+key-ruler = ruler
+key-trail = drag trail
+key-forces = force debug
+key-quadtree = quadtree stats
+key-link-shorter = shorter links
+key-link-longer = longer links
+key-range-narrower = narrower repulsion range
+key-range-wider = wider repulsion range
+key-weaker = weaker repulsion
+key-stronger = stronger repulsion
+key-shrink = shrink hovered or selected nodes
+key-grow = grow hovered or selected nodes
+key-generate-random = add a random graph
+key-generate-scalefree = add a scale-free graph
+key-generate-grid = add a grid
+key-generate-tree = add a tree
+key-help = this help
+
+menu-delete = delete
+menu-pin = pin
+menu-unpin = unpin
+menu-color = color
+menu-label = label
+menu-edge = start edge
+
+legend-color = color
+legend-size = size
+legend-width = width
+legend-more = +{ $count } more
+
+quadtree-title = quadtree
+shader-error = { $file } failed to compile
+quadtree-nodes = nodes: { $count }
+quadtree-cells = cells: { $count }
+quadtree-leaves = leaves: { $count }
+quadtree-depth = depth: { $depth }
+quadtree-per-leaf = per leaf: { $mean } avg, { $max } max
+quadtree-crowded = crowded leaves: { $count }
+quadtree-build = build: { $ms } ms
+stats-gpu = gpu: { $ms } ms, { $samples }x msaa (edges { $edges }, nodes { $nodes }, labels { $labels }, overlay { $overlay }, resolve { $resolve })
+stats-gpu-total = gpu: { $ms } ms, { $samples }x msaa
+stats-gpu-measuring = gpu: measuring, { $samples }x msaa
+stats-gpu-unsupported = gpu: no timestamp queries, { $samples }x msaa
+stats-quality-adaptive = quality: { $samples }x msaa at { $percent }% resolution, adapting to { $budget } ms
+stats-quality-fixed = quality: { $samples }x msaa at { $percent }% resolution
+stats-preset-auto = preset: { $name } (by size), { $solver }, { $passes } collision passes, ticking { $rate }
+stats-preset-chosen = preset: { $name } (chosen), { $solver }, { $passes } collision passes, ticking { $rate }
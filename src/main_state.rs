@@ -1,91 +1,500 @@
-use cgmath::{vec2, vec3, vec4, Rotation3, SquareMatrix};
-use winit::{
-    event::{DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
-    window::Window,
+use instant::Instant;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    time::Duration,
 };
 
+use anyhow::{bail, Context};
+use cgmath::{vec2, InnerSpace, Rotation3};
+use winit::{event::WindowEvent, window::Window};
+
 use crate::{
-    camera::Camera,
-    edge::{Edge, EdgeRenderPass},
-    input::{DragKind, InputState},
-    mouse::Mouse,
-    node::{Node, NodeRenderPass},
-    physics::{self, Physics, DEFAULT_STRENGTH},
-    screen_space_to_clip_space, screen_vec_to_clip_vec,
+    algorithms::{self, Path, Weight},
+    annotation::{self, AnnotationRenderPass},
+    badge::Badge,
+    camera::{self, Camera},
+    changes::{Change, Changes},
+    chart::{HistogramChart, HistogramSource},
+    color,
+    console::{self, Command, Console, Heuristic, MetricsTarget, Scope, Spring},
+    debug::DebugRenderPass,
+    diff,
+    edge::{
+        self, Edge, EdgeRenderPass, DEFAULT_EDGE_COLOR, DEFAULT_EDGE_STRENGTH, DEFAULT_LINE_WIDTH,
+    },
+    export::{
+        self, ExportOptions, ExportRegion, ExportView, PngStream, DEFAULT_TILE_SIZE,
+        MAX_EXPORT_SCALE,
+    },
+    formats::{
+        self,
+        html::{Html, HtmlView},
+        Exporter,
+    },
+    generators::Generator,
+    gpu_timer::{GpuTimer, GpuTimings, Mark, PHASES},
+    graph::{
+        self, EdgeMerge, EdgeRejection, Graph, GraphMutation, GraphView, NodeId, Remap,
+        MULTIPLICITY_ATTR,
+    },
+    handle::GraphHandle,
+    help::HelpOverlay,
+    history::{History, Patch, Step},
+    hot_reload::{self, ShaderWatcher},
+    hull::{Hull, HullRenderPass},
+    i18n::Catalog,
+    input::{
+        self, Action, DragVelocity, EdgeEnd, Gesture, InputEvent, InputState, Key, PointerButton,
+        Shortcut,
+    },
+    label::LabelRenderPass,
+    layout,
+    legend::Legend,
+    limits::Limits,
+    menu::{ContextMenu, MenuItem},
+    metrics::{CsvSink, FrameMetrics, MetricsSink, PrometheusSink, Stopwatch},
+    mouse::{Mouse, Release},
+    node::{self, Node, NodeRenderPass},
+    overlay::{self, mouse_to_overlay_space, OverlayRenderPass, Rect},
+    physics::{self, Anchor, ForceParams, Magnet, Partition, Physics, Preset, Region},
+    picker,
+    prefs::Preferences,
+    quadtree::{Quadtree, TreeStats},
+    quality::{AdaptiveQuality, Quality, DEFAULT_FRAME_BUDGET},
+    rng::Rng,
+    ruler::Ruler,
+    sampling::SampleView,
+    search::{PaletteAction, QuickOpen, QuickSearch},
+    session::{self, Session, UnknownFields, DEFAULT_SESSION_PATH, SESSION_EXTENSION},
+    shader,
+    snapshot::{self, Snapshot, Transition},
+    spatial::SpatialGrid,
+    stats::StatsPanel,
+    status::StatusBar,
+    style::{Channel, Scale, StyleRule, TemplatedLabels},
     texture::Texture,
-    ColorGenerator, SAMPLE_COUNT, SCREEN_SCALE,
+    ticker::{Ticker, DEFAULT_TICK_RATE},
+    timeline::Timeline,
+    units::{format_amount, Units},
+    upscale::UpscalePass,
+    ColorGenerator, RenderSettings,
 };
 
+const SELECTION_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.95, 0.69, 0.2, 1.0);
+const SELECTION_FILL: cgmath::Vector4<f32> = cgmath::vec4(0.95, 0.69, 0.2, 0.15);
+/// Thickness of the selection outline, in window pixels
+const SELECTION_STROKE: f32 = 2.0;
+/// Share of the window a focused region may take up
+const FOCUS_FILL: f32 = 0.8;
+/// Margin around the view kept when culling, as a fraction of its size, so
+/// rings and glows reaching past a node's quad aren't cut off at the edge
+const CULL_MARGIN: f32 = 0.05;
+/// How far from the pointer a new node looks for a node to connect to, in
+/// window pixels
+const AUTO_CONNECT_RADIUS: f32 = 300.0;
+/// Share of an edge's length at either end where pressing picks up that end
+/// to reconnect it, pressing nearer the middle pans like empty space does
+const EDGE_END_GRAB: f32 = 0.25;
+/// Lines of a shader compile error shown, the rest is in the log
+const SHADER_ERROR_LINES: usize = 12;
+/// Characters of each shader error line shown
+const SHADER_ERROR_WIDTH: usize = 100;
+/// Reach of the magnet around the pointer, in window pixels
+const MAGNET_RADIUS: f32 = 150.0;
+/// Most often the graph is copied to `State::graph_handle`'s readers while
+/// it moves
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+/// How far the magnet moves a node under the pointer each tick, as a share of
+/// its reach
+const MAGNET_PULL: f32 = 0.03;
+/// Links away from a thrown node that get reheated along with it
+const THROW_HOPS: usize = 2;
+/// World units between the current graph and one generated beside it
+const SPAWN_GAP: f32 = 200.0;
+/// Frames of a dragged node's positions kept in its trail
+const TRAIL_LENGTH: usize = 90;
+const TRAIL_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.55, 0.75, 1.0, 0.8);
+const GHOST_EDGE_COLOR: cgmath::Vector4<f32> = cgmath::vec4(1.0, 1.0, 1.0, 0.6);
+const INVALID_EDGE_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.95, 0.25, 0.25, 0.9);
+const REPULSION_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.95, 0.3, 0.3, 0.9);
+const ATTRACTION_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.3, 0.9, 0.45, 0.9);
+const NET_FORCE_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.95, 0.85, 0.3, 0.9);
+const CELL_COLOR: cgmath::Vector4<f32> = cgmath::vec4(1.0, 1.0, 1.0, 0.2);
+const RADIUS_COLOR: cgmath::Vector4<f32> = cgmath::vec4(0.4, 0.8, 1.0, 0.5);
+/// Thickness of the trail and force arrows, in window pixels
+const DEBUG_STROKE: f32 = 3.0;
+/// Arrow length per world unit a force moves its node in one tick
+const FORCE_ARROW_SCALE: f32 = 40.0;
+/// Seconds the shaders' animation clock runs to before starting over, a
+/// whole number of pulses so pulsing nodes don't skip
+const ANIMATION_CLOCK_WRAP: f32 = 60.0;
+/// The shaders pipelines drawn with `RenderSettings` are built from, for
+/// rebuilding them when the MSAA they draw with changes. They're rebuilt
+/// from `State::shader_sources`, which keeps hot reloaded edits.
+const SHADERS: [(&str, &str); 5] = [
+    ("node.wgsl", include_str!("node.wgsl")),
+    ("edge.wgsl", include_str!("edge.wgsl")),
+    ("hull.wgsl", include_str!("hull.wgsl")),
+    ("label.wgsl", include_str!("label.wgsl")),
+    ("annotation.wgsl", include_str!("annotation.wgsl")),
+];
+
+/// The viewer: the graph, its simulation and everything drawn. Its fields
+/// are the renderer's own; read it through the accessors such as `nodes`
+/// and change it through `apply_batch`, `run_command` or a `graph_handle`.
 pub struct State {
-    pub surface: wgpu::Surface,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub config: wgpu::SurfaceConfiguration,
-    pub size: winit::dpi::PhysicalSize<u32>,
-    pub depth_texture: Texture,
-    pub msaa_texture: Texture,
-
-    pub camera: Camera,
-    pub node_render_pass: NodeRenderPass,
-    pub edge_render_pass: EdgeRenderPass,
-    pub physics: Physics,
-    pub mouse: Mouse,
-    pub input: InputState, // pub edges: EdgeRenderPass,
-    pub color: ColorGenerator,
+    pub(crate) surface: wgpu::Surface,
+    pub(crate) device: wgpu::Device,
+    pub(crate) queue: wgpu::Queue,
+    pub(crate) config: wgpu::SurfaceConfiguration,
+    pub(crate) size: winit::dpi::PhysicalSize<u32>,
+    /// Physical pixels per logical pixel of the monitor the window is on
+    pub(crate) scale_factor: f64,
+    /// MSAA and depth buffer use, reduced when the GPU is safe-moded or
+    /// `adaptive` lowers quality
+    pub(crate) settings: RenderSettings,
+    /// What the device was set up to draw with, the most `adaptive` goes
+    /// back up to
+    full_settings: RenderSettings,
+    /// Fraction of the window's resolution the graph is drawn at, see
+    /// `UpscalePass`
+    render_scale: f32,
+    upscale_pass: UpscalePass,
+    pub(crate) depth_texture: Option<Texture>,
+    pub(crate) msaa_texture: Option<Texture>,
+
+    pub(crate) camera: Camera,
+    pub(crate) camera_bind_group_layout: wgpu::BindGroupLayout,
+    pub(crate) node_render_pass: NodeRenderPass,
+    pub(crate) edge_render_pass: EdgeRenderPass,
+    pub(crate) overlay_render_pass: OverlayRenderPass,
+    pub(crate) hull_render_pass: HullRenderPass,
+    pub(crate) annotation_render_pass: AnnotationRenderPass,
+    pub(crate) label_render_pass: LabelRenderPass,
+    pub(crate) debug_render_pass: DebugRenderPass,
+    pub(crate) physics: Physics,
+    pub(crate) mouse: Mouse,
+    pub(crate) input: InputState, // pub edges: EdgeRenderPass,
+    pub(crate) color: ColorGenerator,
+    pub(crate) histogram: Option<HistogramChart>,
+    pub(crate) timeline: Option<Timeline>,
+    pub(crate) console: Console,
+    /// Result of the last `path` command, highlighted until cleared
+    pub(crate) path: Option<Path>,
+    /// Strongly connected components with cycles, drawn as hulls while set
+    pub(crate) components: Option<Vec<Vec<u32>>>,
+    pub(crate) mst: Option<MstView>,
+    /// The graph changed since `components` and `mst` were worked out
+    analysis_stale: bool,
+    /// Attribute encodings, at most one per channel
+    pub(crate) styles: Vec<StyleRule>,
+    /// Captions nodes from their attributes, kept up to date as the graph
+    /// changes, see `relabel`
+    pub(crate) label_template: Option<TemplatedLabels>,
+    /// The graph changed since templated labels were last brought up to date
+    labels_stale: bool,
+    /// Merges parallel edges in every graph loaded, set with `dedup`
+    pub(crate) edge_merge: Option<EdgeMerge>,
+    /// Shown whenever a style rule is active, `None` hides it
+    pub(crate) legend: Option<Legend>,
+    /// Where nodes are, for picking the one under the pointer. Synced every
+    /// frame and as nodes are dragged.
+    pub(crate) node_grid: SpatialGrid,
+    /// What a world unit stands for, used by the ruler
+    pub(crate) units: Units,
+    /// Scale bar along the bottom edge, `None` hides it
+    pub(crate) ruler: Option<Ruler>,
+    /// UI strings in the current language
+    pub(crate) strings: Catalog,
+    /// List of controls, shown on the first run and with `?`
+    pub(crate) help: Option<HelpOverlay>,
+    /// Mode, cursor and simulation readout, `None` hides it
+    pub(crate) status_bar: Option<StatusBar>,
+    /// Reply to the last command, file opened or shortcut that reports
+    /// something, shown at the end of the status bar until the next one
+    pub(crate) message: Option<String>,
+    /// Stops the simulation, nodes only move when dragged
+    pub(crate) paused: bool,
+    /// How far outside a node a press still grabs it, in logical pixels
+    pub(crate) pick_tolerance: f32,
+    /// Set while there's nowhere to draw, see `suspend`
+    pub(crate) suspended: bool,
+    /// Fixed simulation rate, `DEFAULT_TICK_RATE` to start with so motion
+    /// doesn't speed up with the frame rate. `None` ticks once per frame.
+    pub(crate) ticker: Option<Ticker>,
+    /// Solver settings graphs were last loaded with, picked by their size
+    /// unless `preset_override` is set
+    pub(crate) preset: Preset,
+    /// Preset every graph is loaded with instead of one picked by size
+    pub(crate) preset_override: Option<Preset>,
+    /// Counts past which drawing details are dropped and loaded graphs are
+    /// sampled
+    pub(crate) limits: Limits,
+    /// Draws only a sample of the graph while zoomed out when set
+    pub(crate) sample_view: Option<SampleView>,
+    /// Simulates only the nodes around the view when set, see
+    /// `Physics::region`. The margin around it, as a fraction of its size.
+    pub(crate) region_margin: Option<f32>,
+    /// Leaves nodes and edges off screen out of the instance buffers
+    pub(crate) culling: bool,
+    /// Camera scale below which the graph is drawn with less detail: edges
+    /// as 1 pixel lines, nodes without rings or glows, and no labels
+    pub(crate) lod_zoom: Option<f32>,
+    /// Settings kept between runs, saved whenever a command changes them
+    pub(crate) prefs: Preferences,
+    /// Fields the loaded session had from a later version, saved back with
+    /// it
+    session_unknown: UnknownFields,
+    /// When the previous frame was updated, to know how much time to tick
+    last_frame: Option<Instant>,
+    /// Nodes picked with a box or lasso selection
+    pub(crate) selection: Option<BTreeSet<u32>>,
+    /// Node under the cursor while it isn't dragging or drawing anything,
+    /// lit up along with its neighbors
+    pub(crate) hovered: Option<u32>,
+    /// Open while picking an edge target by name
+    pub(crate) search: Option<QuickSearch>,
+    /// Open while picking a recent file or command with Ctrl+P
+    pub(crate) palette: Option<QuickOpen>,
+    /// Open after right-clicking a node, until an item is picked or the
+    /// next click
+    pub(crate) context_menu: Option<ContextMenu>,
+    /// Files picked with Ctrl+O on the web, which arrive a while after the
+    /// chooser closes
+    #[cfg(target_arch = "wasm32")]
+    file_picker: picker::FilePicker,
+    /// While dragging, draw the node's recent path and the forces acting on
+    /// it and its neighbors
+    pub(crate) show_trail: bool,
+    /// Draw every node's net force and radius, and the Barnes–Hut cells
+    pub(crate) show_forces: bool,
+    /// Draw the Barnes–Hut cells along with statistics about the tree
+    pub(crate) show_tree: bool,
+    /// Shape of this frame's tree and how long it took to build, while
+    /// `show_tree` is on
+    tree_stats: Option<(TreeStats, Duration)>,
+    /// GPU time per part of the frame, shown with the tree statistics.
+    /// `None` when the adapter has no timestamp queries.
+    gpu_timer: Option<GpuTimer>,
+    /// Lowers MSAA and resolution while frames go over budget. `None` when
+    /// turned off, or without `gpu_timer` to measure frames with.
+    adaptive: Option<AdaptiveQuality>,
+    /// Recent world positions of the dragged node, oldest first
+    trail: VecDeque<cgmath::Vector2<f32>>,
+    /// How fast the dragged node has been moving, what it's thrown with
+    drag_velocity: DragVelocity,
+    /// Saved layouts, see the `snapshot` and `restore` commands
+    pub(crate) snapshots: Vec<Snapshot>,
+    /// Restore in progress, the physics waits for it to finish
+    transition: Option<Transition>,
+    /// Last node spawned by the current brush stroke
+    brush_tail: Option<u32>,
+    /// Edits to the graph for Ctrl+Z and Ctrl+Shift+Z
+    pub(crate) history: History,
+    /// Nodes by `Node::external_id`, kept up to date as nodes come and go
+    external_ids: BTreeMap<String, NodeId>,
+    /// The held nodes as they were when the current drag started, to record
+    /// where they were moved from
+    drag_start: Vec<(NodeId, Node)>,
+    /// Randomness for interactive commands such as `shake`
+    rng: Rng,
+    /// Feed of changes for embedders, see `Changes::subscribe`
+    pub(crate) changes: Changes,
+    /// Shared with other threads once an embedder asks for it, see
+    /// `graph_handle`
+    shared: Option<GraphHandle>,
+    /// When the graph was last published to `shared`
+    published: Option<Instant>,
+    /// Whether the graph may have changed since it was last published
+    shared_stale: bool,
+    /// State as of the last `observe_changes`
+    observed: Observed,
+    /// Where each frame's metrics go, see the `metrics` command
+    metrics: Option<Box<dyn MetricsSink>>,
+    /// Frames recorded since `metrics` was set
+    metrics_frame: u64,
+    /// Edits to shaders rebuild their pipelines, in debug builds
+    shader_watcher: Option<ShaderWatcher>,
+    /// File and error of the last shader edit that didn't compile, shown
+    /// until one does
+    shader_error: Option<(String, String)>,
+    /// Shaders and the snippets they include by file name, as last edited.
+    /// An edited snippet rebuilds every shader including it from here.
+    shader_sources: BTreeMap<String, String>,
 }
 
-impl State {
-    pub async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
+/// What `observe_changes` compares against to notice changes that happen
+/// over many places or many frames
+struct Observed {
+    camera: cgmath::Matrix4<f32>,
+    settled: bool,
+    dragged: Option<u32>,
+}
+
+/// Minimum spanning forest emphasis, worked out again when the graph changes
+pub struct MstView {
+    pub weight: Weight,
+    /// Run the link force on spanning forest edges only
+    pub links_only: bool,
+    pub edges: Vec<u32>,
+    pub edge_map: BTreeMap<u32, Vec<u32>>,
+}
+
+/// An adapter that can draw to `surface`, and a device on it. The safe
+/// version prefers a software adapter and asks for the fewest limits.
+async fn request_device(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface,
+    safe: bool,
+) -> Option<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let mut adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: Some(surface),
+            force_fallback_adapter: safe,
+        })
+        .await;
+    if adapter.is_none() && safe {
+        // No software adapter on this platform, any GPU at all will do
+        adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                power_preference: wgpu::PowerPreference::LowPower,
+                compatible_surface: Some(surface),
                 force_fallback_adapter: false,
             })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    // features: wgpu::Features::DEPTH_CLIP_CONTROL,
-                    features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
+            .await;
+    }
+    let adapter = adapter?;
+    // WebGL doesn't support all of wgpu's features, so if we're building
+    // for the web we'll have to disable some
+    let limits = if safe || cfg!(target_arch = "wasm32") {
+        wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+    } else {
+        wgpu::Limits::default()
+    };
+    let device = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                // features: wgpu::Features::DEPTH_CLIP_CONTROL,
+                // Timing the GPU is a nicety safe mode does without
+                features: if safe {
+                    wgpu::Features::empty()
+                } else {
+                    adapter.features() & GpuTimer::FEATURES
                 },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
+                limits,
+            },
+            None, // Trace path
+        )
+        .await;
+    match device {
+        Ok((device, queue)) => Some((adapter, device, queue)),
+        Err(err) => {
+            log::warn!("{:?} gave no device: {err}", adapter.get_info().name);
+            None
+        }
+    }
+}
+
+/// First sRGB format the surface supports. The shaders output linear colors
+/// and rely on the target to encode them, any other format makes the frame
+/// look too dark.
+fn srgb_format(formats: &[wgpu::TextureFormat]) -> wgpu::TextureFormat {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.describe().srgb)
+        .unwrap_or_else(|| {
+            log::warn!("no sRGB surface format, colors will be off: {formats:?}");
+            formats[0]
+        })
+}
+
+/// Present mode with or without waiting for the display's refresh. Both
+/// fall back to whatever the surface supports.
+fn present_mode(vsync: bool) -> wgpu::PresentMode {
+    if vsync {
+        wgpu::PresentMode::AutoVsync
+    } else {
+        wgpu::PresentMode::AutoNoVsync
+    }
+}
+
+/// UI strings from a catalog file, named for the file's stem
+fn load_catalog(path: &str) -> Result<Catalog, String> {
+    let src = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let lang = std::path::Path::new(path)
+        .file_stem()
+        .map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+    Catalog::load(lang, &src).map_err(|e| format!("{path}: {e}"))
+}
+
+/// Edges the link force runs on, out of `edge_map` which has them all
+fn link_map<'a>(
+    mst: &'a Option<MstView>,
+    edge_map: &'a BTreeMap<u32, Vec<u32>>,
+) -> &'a BTreeMap<u32, Vec<u32>> {
+    match mst {
+        Some(mst) if mst.links_only => &mst.edge_map,
+        _ => edge_map,
+    }
+}
+
+impl State {
+    /// Sets up rendering to `window`. In `safe_mode`, or when no adapter
+    /// gives a device otherwise, it renders with `RenderSettings::SAFE` on
+    /// a software adapter if there is one.
+    pub async fn new(window: &Window, safe_mode: bool) -> Self {
+        let size = window.inner_size();
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let surface = unsafe { instance.create_surface(window) };
+        let mut gpu = None;
+        if !safe_mode {
+            gpu = request_device(&instance, &surface, false).await;
+            if gpu.is_none() {
+                log::warn!("no usable graphics adapter, retrying in safe mode");
+            }
+        }
+        let settings = if gpu.is_some() {
+            RenderSettings::DEFAULT
+        } else {
+            RenderSettings::SAFE
+        };
+        let gpu = match gpu {
+            Some(gpu) => Some(gpu),
+            None => request_device(&instance, &surface, true).await,
+        };
+        let (adapter, device, queue) = gpu.expect("no graphics adapter, not even in safe mode");
+        if settings == RenderSettings::SAFE {
+            log::info!("safe mode on {:?}", adapter.get_info());
+        }
 
-        let mut color = ColorGenerator::new();
-        let format = surface.get_supported_formats(&adapter)[3];
-        // let format = surface.get_supported_formats(&adapter)[0];
+        let prefs = Preferences::load();
+        let strings = match &prefs.lang {
+            Some(path) => load_catalog(path).unwrap_or_else(|err| {
+                log::warn!("falling back to English: {err}");
+                Catalog::english()
+            }),
+            None => Catalog::english(),
+        };
+        let color = ColorGenerator::new();
+        let format = srgb_format(&surface.get_supported_formats(&adapter));
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: present_mode(prefs.vsync),
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
         surface.configure(&device, &config);
 
-        let (w, h) = (800.0, 600.0);
+        let (w, h) = (size.width as f32, size.height as f32);
         let (camera, camera_bind_group_layout) =
-            Camera::new(cgmath::vec3(0.0, 0.0, 1.0), w, h, 1.0, &device);
+            Camera::new(camera::HOME_TRANSLATE, w, h, camera::HOME_SCALE, &device);
 
-        let depth_texture = Texture::create_depth_texture(&device, &config, SAMPLE_COUNT, "Depth");
-        let msaa_texture = Texture::create_msaa_texture(&device, &config, "MSAA", SAMPLE_COUNT);
+        let (msaa_texture, depth_texture) = settings.attachments(&device, &config, "Window");
 
         // let nodes = vec![
         //     Node::new(
@@ -131,6 +540,7 @@ impl State {
             &queue,
             format,
             &camera_bind_group_layout,
+            settings,
         );
 
         // let edges = vec![
@@ -166,280 +576,3735 @@ impl State {
         //     ),
         // ];
         let edges = vec![];
-        let edge_render_pass =
-            EdgeRenderPass::new(edges, &device, &queue, format, &camera_bind_group_layout);
+        let edge_render_pass = EdgeRenderPass::new(
+            edges,
+            &device,
+            &queue,
+            format,
+            &camera_bind_group_layout,
+            settings,
+        );
+        let overlay_render_pass =
+            OverlayRenderPass::new(&device, format, &camera_bind_group_layout);
+        let hull_render_pass =
+            HullRenderPass::new(&device, format, &camera_bind_group_layout, settings);
+        let annotation_render_pass =
+            AnnotationRenderPass::new(&device, format, &camera_bind_group_layout, settings);
+        let debug_render_pass =
+            DebugRenderPass::new(&device, format, &camera_bind_group_layout, settings);
+        let label_render_pass =
+            LabelRenderPass::new(&device, format, &camera_bind_group_layout, settings);
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        let adaptive = gpu_timer
+            .as_ref()
+            .map(|_| AdaptiveQuality::new(settings, DEFAULT_FRAME_BUDGET));
+        let upscale_pass = UpscalePass::new(&device, format);
 
         let physics = Physics::new(&node_render_pass.nodes);
 
+        let observed = Observed {
+            camera: camera.matrix,
+            settled: false,
+            dragged: None,
+        };
         Self {
             surface,
             device,
             queue,
             config,
             size,
+            scale_factor: window.scale_factor(),
+            settings,
+            full_settings: settings,
+            render_scale: 1.0,
+            upscale_pass,
             depth_texture,
             msaa_texture,
             camera,
+            camera_bind_group_layout,
             node_render_pass,
             edge_render_pass,
+            overlay_render_pass,
+            hull_render_pass,
+            annotation_render_pass,
+            label_render_pass,
+            debug_render_pass,
             physics,
             mouse: Mouse::default(),
             input: InputState::default(),
             color,
+            histogram: None,
+            timeline: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            console: Console::spawn_stdin(),
+            #[cfg(target_arch = "wasm32")]
+            console: Console::new(),
+            path: None,
+            components: None,
+            mst: None,
+            analysis_stale: false,
+            styles: vec![],
+            label_template: None,
+            labels_stale: false,
+            edge_merge: None,
+            legend: Some(Legend::default()),
+            node_grid: SpatialGrid::default(),
+            units: Units::default(),
+            ruler: None,
+            strings,
+            help: (!prefs.seen_help).then(HelpOverlay::default),
+            status_bar: Some(StatusBar::default()),
+            message: None,
+            paused: false,
+            pick_tolerance: node::PICK_TOLERANCE,
+            suspended: false,
+            ticker: Some(Ticker::new(DEFAULT_TICK_RATE)),
+            preset: Preset::SMALL,
+            preset_override: None,
+            limits: Limits::default(),
+            sample_view: None,
+            region_margin: None,
+            culling: true,
+            lod_zoom: Some(camera::DEFAULT_LOD_ZOOM),
+            prefs,
+            session_unknown: UnknownFields::default(),
+            hovered: None,
+            last_frame: None,
+            selection: None,
+            search: None,
+            palette: None,
+            context_menu: None,
+            #[cfg(target_arch = "wasm32")]
+            file_picker: picker::FilePicker::default(),
+            show_trail: false,
+            show_forces: false,
+            show_tree: false,
+            tree_stats: None,
+            gpu_timer,
+            adaptive,
+            trail: VecDeque::new(),
+            drag_velocity: DragVelocity::default(),
+            snapshots: vec![],
+            transition: None,
+            brush_tail: None,
+            history: History::default(),
+            external_ids: BTreeMap::new(),
+            drag_start: vec![],
+            rng: Rng::new(0x5EED),
+            changes: Changes::default(),
+            shared: None,
+            published: None,
+            shared_stale: false,
+            observed,
+            metrics: None,
+            metrics_frame: 0,
+            shader_watcher: ShaderWatcher::for_sources(),
+            shader_error: None,
+            shader_sources: SHADERS
+                .iter()
+                .chain(&shader::SNIPPETS)
+                .map(|(file, source)| (file.to_string(), source.to_string()))
+                .collect(),
         }
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::MouseWheel { delta, phase, .. } => {
-                let y = match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => *y as f64,
-                    winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y / 100.,
-                };
+        InputEvent::from_window_event(event).is_some_and(|event| self.handle_input(event))
+    }
+
+    /// Runs the interaction logic for one event, returns whether the event
+    /// was consumed
+    pub fn handle_input(&mut self, event: InputEvent) -> bool {
+        if let Some(consumed) = self.help_input(event) {
+            return consumed;
+        }
+        if let Some(consumed) = self.search_input(event) {
+            return consumed;
+        }
+        if let Some(consumed) = self.palette_input(event) {
+            return consumed;
+        }
 
+        match event {
+            InputEvent::Scroll(y) => {
+                self.camera
+                    .update_scale(&self.queue, self.camera.scale + y, self.mouse.pos);
+            }
+            InputEvent::Pinch(ratio) => {
                 self.camera
-                    .update_scale(&self.queue, self.camera.scale + y as f32);
+                    .update_scale(&self.queue, self.camera.scale * ratio, self.mouse.pos);
             }
-            WindowEvent::CursorLeft { .. } => {
-                self.mouse.last_pos = self.mouse.pos.unwrap_or((0.0, 0.0).into());
-                self.mouse.pos = None;
+            InputEvent::PointerLeft => {
+                self.mouse.left();
+                self.input.pointer_left();
+                self.hovered = None;
             }
-            WindowEvent::CursorMoved { position, .. } => {
-                let mut vec: cgmath::Vector2<f32> = (position.x as f32, position.y as f32).into();
+            InputEvent::PointerMoved(mut vec) => {
                 vec.x -= self.camera.width / 2.0;
                 vec.y -= self.camera.height / 2.0;
                 // vec.x *= 2.0;
                 vec.y *= -1.0;
                 println!("CURSOR: {:?}", vec);
-                self.mouse.pos = Some(vec);
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: element_state,
-                        virtual_keycode: Some(VirtualKeyCode::LControl),
-                        ..
-                    },
-                ..
+                self.mouse.moved(vec);
+                if let Some(action) = self.input.moved(vec) {
+                    self.apply_action(action);
+                }
+                self.hovered = match self.input.gesture {
+                    Gesture::Idle => self.node_at(vec),
+                    _ => None,
+                };
+            }
+            InputEvent::PointerPressed(PointerButton::Primary) => {
+                self.mouse.press(PointerButton::Primary);
+                // The menu acts on release, so the press can't start a drag
+                if self.context_menu.is_some() {
+                    return true;
+                }
+                let Some(pos) = self.mouse.pos else {
+                    return false;
+                };
+                if self.overlay_click(&pos) {
+                    return false;
+                }
+                let hit = self.node_at(pos);
+                let edge_end = hit.is_none().then(|| self.edge_end_at(pos)).flatten();
+                if let Some(action) = self.input.press(pos, hit, edge_end) {
+                    self.apply_action(action);
+                }
+                if let Some(node) = self.input.dragged_node() {
+                    self.drag_start = self
+                        .held_nodes(Some(node))
+                        .into_iter()
+                        .map(|id| (id, self.node_render_pass.nodes[id as usize].clone()))
+                        .collect();
+                }
+            }
+            InputEvent::PointerPressed(button) => self.mouse.press(button),
+            InputEvent::PointerReleased(PointerButton::Secondary) => {
+                let release = self.mouse.release(PointerButton::Secondary, Instant::now());
+                let Some(pos) = self.mouse.pos.filter(|_| release == Some(Release::Click)) else {
+                    return false;
+                };
+                // Right-clicking anywhere closes an open menu, or moves it to
+                // the node clicked
+                self.context_menu = None;
+                // Nodes are drawn over edges, so they take the click
+                let node = self.node_at(pos);
+                if let (Some(node), Gesture::Idle) = (node, &self.input.gesture) {
+                    let pinned = self.physics.objs[node as usize].fixed;
+                    self.context_menu = Some(ContextMenu::new(node, self.to_ui(&pos), pinned));
+                    return true;
+                }
+                let edge = node
+                    .is_none()
+                    .then(|| self.edge_at(pos).map(|(edge, _)| edge))
+                    .flatten();
+                if let Some(action) = self.input.secondary_click(edge) {
+                    self.apply_action(action);
+                }
+            }
+            InputEvent::PointerReleased(PointerButton::Primary) => {
+                let release = self.mouse.release(PointerButton::Primary, Instant::now());
+                if let Some(menu) = self.context_menu.take() {
+                    let item = self
+                        .mouse
+                        .pos
+                        .and_then(|pos| menu.item_at(self.to_ui(&pos)));
+                    if let Some(item) = item {
+                        self.run_menu_item(menu.node, item);
+                    }
+                    return true;
+                }
+                let Some(pos) = self.mouse.pos else {
+                    self.input.cancel();
+                    return false;
+                };
+                let hit = self.node_at(pos);
+                if let Some(action) = self.input.release(pos, hit) {
+                    self.apply_action(action);
+                }
+                if let (Some(node), Some(Release::DoubleClick)) = (hit, release) {
+                    self.physics.toggle_pin(node);
+                }
+            }
+            InputEvent::Key {
+                key: Key::Escape,
+                pressed: true,
+            } => {
+                // Escape backs out of whatever is in progress before it quits
+                if self.context_menu.take().is_some() || self.input.cancel() {
+                    return true;
+                }
+                if self.selection.take().is_some() {
+                    self.changes.emit(Change::SelectionChanged);
+                    return true;
+                }
+                return false;
+            }
+            InputEvent::Key {
+                key: key @ (Key::Ctrl | Key::Shift | Key::Alt | Key::Char('b' | 'm')),
+                pressed,
             } => {
-                let pressed = matches!(element_state, ElementState::Pressed);
-                self.input.is_ctrl_pressed = pressed;
-                if !pressed {
-                    self.input.dragging = None;
+                self.input.modifier(key, pressed);
+            }
+            InputEvent::Key {
+                key: Key::Char('z'),
+                pressed: true,
+            } if self.input.is_ctrl_pressed => {
+                let done = if self.input.is_shift_pressed {
+                    self.redo()
                 } else {
-                    self.input.dragging = self.input.dragging.map(|drag| match drag {
-                        DragKind::Node(node) => DragKind::EdgeCreation(node),
-                        other => other,
-                    });
+                    self.undo()
+                };
+                return done;
+            }
+            InputEvent::Key {
+                key: Key::Char('p'),
+                pressed: true,
+            } if self.input.is_ctrl_pressed => {
+                self.palette = Some(QuickOpen::default());
+                return true;
+            }
+            InputEvent::Key {
+                key: Key::Char(c @ ('s' | 'o')),
+                pressed: true,
+            } if self.input.is_ctrl_pressed => {
+                let done = if c == 's' {
+                    self.save_session(DEFAULT_SESSION_PATH)
+                } else {
+                    self.pick_file()
+                };
+                self.report(done);
+                return true;
+            }
+            InputEvent::Key {
+                key: Key::Delete,
+                pressed: true,
+            } => {
+                let Some(selection) = self.selection.clone() else {
+                    return false;
+                };
+                self.apply_batch(GraphMutation {
+                    remove_nodes: selection,
+                    ..Default::default()
+                })
+                .expect("selected nodes exist");
+                return true;
+            }
+            InputEvent::Key {
+                key: Key::Char('p'),
+                pressed: true,
+            } if self.input.is_shift_pressed => {
+                let nodes = self.edit_targets();
+                for node in &nodes {
+                    self.physics.toggle_pin(*node);
                 }
+                return !nodes.is_empty();
             }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: element_state,
-                        virtual_keycode: Some(VirtualKeyCode::LAlt),
-                        ..
-                    },
-                ..
+            InputEvent::Key {
+                key: Key::Char('c'),
+                pressed: true,
+            } if self.input.is_shift_pressed => {
+                let updates: Vec<(NodeId, Node)> = self
+                    .edit_targets()
+                    .into_iter()
+                    .map(|node| {
+                        let mut updated = self.node_render_pass.nodes[node as usize].clone();
+                        updated.color = self.color.next_color();
+                        (node, updated)
+                    })
+                    .collect();
+                return self.update_nodes(updates);
+            }
+            InputEvent::Key {
+                key: Key::Char('f'),
+                pressed: true,
+            } if self.input.is_shift_pressed => {
+                let fitted = self.camera.fit(&self.queue, &self.node_render_pass.nodes);
+                if !fitted {
+                    log::info!("nothing to fit the view to, the graph is empty");
+                }
+                return fitted;
+            }
+            InputEvent::Key {
+                key: Key::Home,
+                pressed: true,
+            } => {
+                self.camera.reset(&self.queue);
+                return true;
+            }
+            InputEvent::Key {
+                key: Key::Char(c),
+                pressed: true,
             } => {
-                let pressed = matches!(element_state, ElementState::Pressed);
-                self.input.is_lalt_pressed = pressed;
+                if let Some(shortcut) = input::shortcut(self.prefs.key(c)) {
+                    self.run_shortcut(shortcut);
+                }
+            }
+            // Symbols only arrive as text, letters are handled as keys above
+            InputEvent::Text(c) if !c.is_ascii_alphabetic() => {
+                if let Some(shortcut) = input::shortcut(self.prefs.key(c)) {
+                    self.run_shortcut(shortcut);
+                }
+            }
+            InputEvent::PointerReleased(button) => {
+                self.mouse.release(button, Instant::now());
             }
             _ => (),
         }
         false
     }
 
-    pub fn add_node(&mut self, node: Node) {
-        let idx = self.node_render_pass.nodes.len();
-        self.physics.objs.push(physics::Object::from_node(
-            idx as u32,
-            &node,
-            DEFAULT_STRENGTH,
-        ));
-        self.node_render_pass.add_node(node, &self.queue)
+    fn run_shortcut(&mut self, shortcut: Shortcut) {
+        match shortcut {
+            Shortcut::Histogram => {
+                if self.histogram.is_some() {
+                    self.hide_histogram();
+                } else {
+                    self.show_histogram(HistogramSource::Degree);
+                }
+            }
+            Shortcut::Timeline => {
+                if self.timeline.is_some() {
+                    self.hide_timeline();
+                } else {
+                    self.timeline = Some(Timeline::new());
+                }
+            }
+            Shortcut::Trail => {
+                self.show_trail = !self.show_trail;
+            }
+            Shortcut::Forces => {
+                self.show_forces = !self.show_forces;
+            }
+            Shortcut::Quadtree => {
+                self.show_tree = !self.show_tree;
+            }
+            Shortcut::Ruler => {
+                self.ruler = match self.ruler {
+                    Some(_) => None,
+                    None => Some(Ruler::default()),
+                };
+            }
+            Shortcut::Pause => {
+                self.paused = !self.paused;
+            }
+            Shortcut::ConnectSearch => {
+                // Connect the hovered node, or the only selected one
+                let source = self
+                    .mouse
+                    .pos
+                    .and_then(|pos| self.node_at(pos))
+                    .or_else(|| {
+                        let selection = self.selection.as_ref()?;
+                        (selection.len() == 1).then(|| *selection.first().unwrap())
+                    });
+                self.search = source.map(|source| QuickSearch::opened_with(source, 'e'));
+            }
+            Shortcut::Help => self.help = Some(HelpOverlay::default()),
+            Shortcut::Generate(demo) => {
+                let seed = self.rng.next_u64();
+                let done = self.spawn(Generator::DEMOS[demo], seed);
+                self.report(done);
+            }
+            Shortcut::Force { param, up } => {
+                let factor = if up {
+                    input::FORCE_STEP
+                } else {
+                    1.0 / input::FORCE_STEP
+                };
+                self.physics
+                    .set_param(param, self.physics.params.get(param) * factor);
+                self.physics.reheat();
+                self.report(Ok(format!("forces {}", self.physics.params)));
+            }
+            Shortcut::Resize { up } => {
+                let factor = if up {
+                    input::RESIZE_STEP
+                } else {
+                    1.0 / input::RESIZE_STEP
+                };
+                let targets = self.edit_targets();
+                if targets.is_empty() {
+                    self.report(Err("hover or select nodes to resize them".into()));
+                    return;
+                }
+                let updates: Vec<(NodeId, Node)> = targets
+                    .into_iter()
+                    .filter_map(|node| {
+                        let mut updated = self.node_render_pass.nodes[node as usize].clone();
+                        updated.rescale(factor).then_some((node, updated))
+                    })
+                    .collect();
+                if !self.update_nodes(updates) {
+                    let limit = if up { "largest" } else { "smallest" };
+                    self.report(Err(format!("already at {limit} size")));
+                }
+            }
+        }
     }
 
-    pub fn add_edge(&mut self, edge: Edge) {
-        self.edge_render_pass.add_edge(edge, &self.queue);
+    /// Any key press or click closes the help overlay, `None` lets the event
+    /// through
+    fn help_input(&mut self, event: InputEvent) -> Option<bool> {
+        self.help?;
+        match event {
+            // Modifiers still need tracking so they don't get stuck
+            InputEvent::Key {
+                key: Key::Ctrl | Key::Shift | Key::Alt,
+                ..
+            } => return None,
+            InputEvent::Key { pressed: true, .. }
+            | InputEvent::Text(_)
+            | InputEvent::PointerPressed(_) => {
+                self.help = None;
+                if !self.prefs.seen_help {
+                    self.prefs.seen_help = true;
+                    self.save_prefs();
+                }
+            }
+            _ => return None,
+        }
+        Some(true)
     }
 
-    pub fn set_dragging(&mut self, dragging: Option<DragKind>) {
-        self.input.dragging = dragging;
+    /// Routes keyboard input to the quick search while it's open, `None`
+    /// lets the event through
+    fn search_input(&mut self, event: InputEvent) -> Option<bool> {
+        let search = self.search.as_mut()?;
+        let nodes = &self.node_render_pass.nodes;
+        match event {
+            InputEvent::Text(c) => search.push(c),
+            InputEvent::Key { key, pressed: true } => match key {
+                Key::Backspace => search.pop(),
+                Key::Up => search.select(nodes, -1),
+                Key::Down => search.select(nodes, 1),
+                Key::Escape => self.search = None,
+                Key::Enter => {
+                    let source = search.source;
+                    let target = search.target(nodes);
+                    self.search = None;
+                    if let Some(target) = target {
+                        self.connect(source, target);
+                        let ends = [source, target]
+                            .map(|i| self.node_render_pass.nodes[i as usize].clone());
+                        if let Some((min, max)) = export::graph_bounds(&ends) {
+                            self.focus(min, max);
+                        }
+                    }
+                }
+                // Typed characters arrive as `Text`, and there's no cursor
+                // for Delete to delete after
+                Key::Char(_) | Key::Delete | Key::Home => (),
+                Key::Ctrl | Key::Shift | Key::Alt => return None,
+            },
+            _ => return None,
+        }
+        Some(true)
     }
 
-    pub fn device_input(&mut self, event: &DeviceEvent) -> bool {
+    /// Routes keyboard input to the palette while it's open, `None` lets the
+    /// event through
+    fn palette_input(&mut self, event: InputEvent) -> Option<bool> {
+        let palette = self.palette.as_mut()?;
+        let recent = &self.prefs.recent_files;
         match event {
-            DeviceEvent::MouseMotion { delta } => {
-                match self.input.dragging {
-                    Some(DragKind::Node(node)) => {
-                        self.node_render_pass.nodes[node as usize].position.x +=
-                            delta.0 as f32 * 2.0 * (1. / self.camera.scale);
-                        self.node_render_pass.nodes[node as usize].position.y += -delta.1 as f32
-                        * 2.0
-                        // * (self.camera.height / self.camera.width)
-                        * (1. / self.camera.scale);
-                        self.node_render_pass.update_node(node, &self.queue);
-                        self.physics.objs[node as usize].x =
-                            self.node_render_pass.nodes[node as usize].position.x;
-                        self.physics.objs[node as usize].y =
-                            self.node_render_pass.nodes[node as usize].position.y;
-                    }
-                    _ => (),
-                }
-            }
-            DeviceEvent::Button { state, .. } => match state {
-                ElementState::Pressed => {
-                    if let Some(pos) = &self.mouse.pos {
-                        let pos = pos / self.camera.scale;
-                        let pos3 = pos.extend(0.0);
-
-                        if self.input.is_lalt_pressed {
-                            let node = Node::new(
-                                (50.0, 50.0),
-                                pos.extend(0.0),
-                                cgmath::Quaternion::from_axis_angle(
-                                    cgmath::vec3(0.0, 0.0, 0.0),
-                                    cgmath::Deg(0.0),
-                                ),
-                                self.color.next(),
-                            );
-                            self.add_node(node);
-                            return false;
+            InputEvent::Text(c) => palette.push(c),
+            InputEvent::Key { key, pressed: true } => match key {
+                Key::Backspace => palette.pop(),
+                Key::Up => palette.select(recent, -1),
+                Key::Down => palette.select(recent, 1),
+                Key::Escape => self.palette = None,
+                Key::Enter => {
+                    let Some(action) = palette.enter(recent) else {
+                        return Some(true);
+                    };
+                    self.palette = None;
+                    let done = match action {
+                        PaletteAction::Open(path) => self.open(path),
+                        PaletteAction::Run(line) => {
+                            Command::parse(&line).and_then(|command| self.run_command(command))
                         }
+                    };
+                    self.report(done);
+                }
+                Key::Char(_) | Key::Delete | Key::Home => (),
+                Key::Ctrl | Key::Shift | Key::Alt => return None,
+            },
+            _ => return None,
+        }
+        Some(true)
+    }
 
-                        if let Some((i, _)) = self
-                            .node_render_pass
-                            .nodes
-                            .iter()
-                            .enumerate()
-                            .find(|(_, node)| node.intersects(&pos3))
-                        {
-                            self.set_dragging(if self.input.is_ctrl_pressed {
-                                Some(DragKind::EdgeCreation(i as u32))
-                            } else {
-                                Some(DragKind::Node(i as u32))
-                            });
-                            return false;
-                        }
+    /// Adds an edge between two nodes, unless it would be a self-loop or a
+    /// duplicate, which is reported as `Change::EdgeRejected` instead
+    fn connect(&mut self, from: u32, to: u32) {
+        if let Err(reason) = self.check_edge(from, to) {
+            log::info!("not connecting {from} to {to}: {reason}");
+            self.changes.emit(Change::EdgeRejected { from, to, reason });
+            return;
+        }
+        let edge = Edge::from_nodes(
+            (&self.node_render_pass.nodes[from as usize], from),
+            (&self.node_render_pass.nodes[to as usize], to),
+            DEFAULT_EDGE_COLOR,
+            DEFAULT_LINE_WIDTH,
+        );
+        self.add_edge(edge);
+    }
+
+    /// Carries out what was picked from a node's context menu
+    fn run_menu_item(&mut self, node: u32, item: MenuItem) {
+        match item {
+            MenuItem::Delete => {
+                self.apply_batch(GraphMutation {
+                    remove_nodes: BTreeSet::from([node]),
+                    ..Default::default()
+                })
+                .expect("the menu's node exists");
+            }
+            MenuItem::Pin => {
+                self.physics.toggle_pin(node);
+            }
+            MenuItem::Color => {
+                let mut updated = self.node_render_pass.nodes[node as usize].clone();
+                updated.color = self.color.next_color();
+                self.apply_batch(GraphMutation {
+                    update_nodes: vec![(node, updated)],
+                    ..Default::default()
+                })
+                .expect("the menu's node exists");
+            }
+            MenuItem::Label => {
+                // An index can name some other node, such as one called "3"
+                match console::node_key(&self.node_render_pass.nodes, node) {
+                    Some(key) => {
+                        self.palette = Some(QuickOpen::with_query(format!("label {key} ")))
                     }
+                    None => self.report(Err(format!("node {node} has no name a command can use"))),
                 }
-                ElementState::Released => {
-                    match (self.input.dragging, self.mouse.pos) {
-                        (Some(DragKind::EdgeCreation(a)), Some(pos)) => {
-                            let pos = pos / self.camera.scale;
-                            let pos3 = pos.extend(0.0);
-                            if let Some((b, _)) = self
-                                .node_render_pass
-                                .nodes
-                                .iter()
-                                .enumerate()
-                                .find(|(_, node)| node.intersects(&pos3))
-                            {
-                                let edge = Edge::from_nodes(
-                                    (&self.node_render_pass.nodes[a as usize], a),
-                                    (&self.node_render_pass.nodes[b], b as u32),
-                                    vec4(0.0, 1.0, 0.0, 1.0),
-                                    10.0,
-                                );
-                                self.edge_render_pass.add_edge(edge, &self.queue);
-                            }
-                        }
-                        _ => (),
-                    }
+            }
+            MenuItem::StartEdge => self.input.gesture = Gesture::CreatingEdge(node),
+        }
+    }
 
-                    self.set_dragging(None);
-                }
-            },
-            _ => (),
+    /// Centers the camera on a world space box, zooming out if it doesn't fit
+    fn focus(&mut self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>) {
+        let extent = (max - min) / FOCUS_FILL;
+        let fit =
+            (self.camera.width / extent.x.max(1.0)).min(self.camera.height / extent.y.max(1.0));
+        self.camera
+            .update_scale(&self.queue, self.camera.scale.min(fit), None);
+        let center = (min + max) / 2.0 * self.camera.scale;
+        self.camera
+            .update_translate(&self.queue, center.extend(self.camera.translate.z));
+    }
+
+    /// What's on screen in world space, grown by `margin` of its size
+    fn view_region(&self, margin: f32) -> Region {
+        let corner = cgmath::vec2(self.camera.width, self.camera.height) / 2.0;
+        let min = self.camera.screen_to_world(-corner);
+        let max = self.camera.screen_to_world(corner);
+        Region::around(min, max, margin)
+    }
+
+    /// What node edits from the keyboard act on: the hovered node, or else
+    /// the selection
+    fn edit_targets(&self) -> BTreeSet<u32> {
+        match self.mouse.pos.and_then(|pos| self.node_at(pos)) {
+            Some(node) => BTreeSet::from([node]),
+            None => self.selection.clone().unwrap_or_default(),
         }
-        false
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture =
-                Texture::create_depth_texture(&self.device, &self.config, SAMPLE_COUNT, "depth");
-            self.camera
-                .resize(new_size.width as f32, new_size.height as f32, &self.queue);
+    /// Replaces nodes as one undoable step, returns whether there were any
+    fn update_nodes(&mut self, updates: Vec<(NodeId, Node)>) -> bool {
+        if updates.is_empty() {
+            return false;
         }
+        self.apply_batch(GraphMutation {
+            update_nodes: updates,
+            ..Default::default()
+        })
+        .expect("edited nodes exist");
+        true
     }
 
-    pub fn update(&mut self) {
-        self.physics.tick(
-            self.input.dragging.and_then(|drag| match drag {
-                DragKind::Node(node) => Some(node),
-                _ => None,
-            }),
+    /// Replaces edges as one undoable step, returns whether there were any
+    fn update_edges(&mut self, updates: Vec<(u32, Edge)>) -> bool {
+        if updates.is_empty() {
+            return false;
+        }
+        self.apply_batch(GraphMutation {
+            update_edges: updates,
+            ..Default::default()
+        })
+        .expect("edited edges exist");
+        true
+    }
+
+    /// Topmost node under a pointer position
+    fn node_at(&self, pos: cgmath::Vector2<f32>) -> Option<u32> {
+        let tolerance = self.pick_tolerance * self.scale_factor as f32 / self.camera.scale;
+        self.node_grid.pick(
+            &self.node_render_pass.nodes,
+            self.camera.screen_to_world(pos),
+            tolerance,
+            &self.node_render_pass.hidden,
+        )
+    }
+
+    /// Nodes moving with `dragged`: the whole selection when it's part of
+    /// it, otherwise just itself
+    fn held_nodes(&self, dragged: Option<u32>) -> BTreeSet<u32> {
+        input::held_nodes(dragged, self.selection.as_ref())
+    }
+
+    /// Every edge, or with `selected_only` the edges between selected nodes
+    /// with their ids, as copies to edit and hand to `update_edges`
+    fn edges_in_scope(&self, selected_only: bool) -> Result<Vec<(u32, Edge)>, String> {
+        let selection = match (&self.selection, selected_only) {
+            (_, false) => None,
+            (Some(selection), true) => Some(selection),
+            (None, true) => return Err("nothing selected".into()),
+        };
+        Ok((0..)
+            .zip(&self.edge_render_pass.edges)
+            .filter(|(_, edge)| {
+                selection.is_none_or(|selection| {
+                    selection.contains(&edge.a_id) && selection.contains(&edge.b_id)
+                })
+            })
+            .map(|(id, edge)| (id, edge.clone()))
+            .collect())
+    }
+
+    /// Every node, or with `selected_only` the selected ones, as copies to
+    /// edit and hand to `update_nodes`
+    fn nodes_in_scope(&self, selected_only: bool) -> Result<Vec<(NodeId, Node)>, String> {
+        let nodes = &self.node_render_pass.nodes;
+        let ids: Vec<NodeId> = match (&self.selection, selected_only) {
+            (_, false) => (0..nodes.len() as NodeId).collect(),
+            (Some(selection), true) => selection.iter().copied().collect(),
+            (None, true) => return Err("nothing selected".into()),
+        };
+        Ok(ids
+            .into_iter()
+            .map(|id| (id, nodes[id as usize].clone()))
+            .collect())
+    }
+
+    /// Closest edge under a pointer position, and how far along it that is
+    fn edge_at(&self, pos: cgmath::Vector2<f32>) -> Option<(u32, f32)> {
+        let tolerance = self.pick_tolerance * self.scale_factor as f32 / self.camera.scale;
+        edge::pick(
             &self.edge_render_pass.edges,
-            &self.edge_render_pass.edge_map,
-        );
-        self.physics.apply(
-            self.node_render_pass.nodes.as_mut_slice(),
-            &mut self.edge_render_pass.edges,
-            &self.edge_render_pass.edge_map,
-        );
-        self.node_render_pass.write(&self.queue);
-        self.edge_render_pass.write(&self.queue);
+            self.camera.screen_to_world(pos),
+            tolerance,
+            &self.edge_render_pass.hidden,
+        )
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// End of the edge under a pointer position, when it's near enough to
+    /// one to pick it up, see `EDGE_END_GRAB`
+    fn edge_end_at(&self, pos: cgmath::Vector2<f32>) -> Option<EdgeEnd> {
+        let (id, along) = self.edge_at(pos)?;
+        let edge = &self.edge_render_pass.edges[id as usize];
+        let node = if along <= EDGE_END_GRAB {
+            edge.a_id
+        } else if along >= 1.0 - EDGE_END_GRAB {
+            edge.b_id
+        } else {
+            return None;
+        };
+        Some(EdgeEnd { edge: id, node })
+    }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+    /// The edge `end` belongs to with that end moved over to `to`, or why
+    /// that edge couldn't be created
+    fn reconnected(&self, end: EdgeEnd, to: u32) -> Result<Edge, EdgeRejection> {
+        let mut edge = self.edge_render_pass.edges[end.edge as usize].clone();
+        let position = self.node_render_pass.nodes[to as usize].position;
+        if edge.a_id == end.node {
+            (edge.a_id, edge.a_center) = (to, position);
+        } else {
+            (edge.b_id, edge.b_center) = (to, position);
+        }
+        self.check_edge(edge.a_id, edge.b_id)?;
+        Ok(edge)
+    }
 
-        {
-            let (view, resolve_target) = if SAMPLE_COUNT > 1 {
-                (&self.msaa_texture.view, Some(&view))
-            } else {
-                (&view, None)
-            };
+    /// Moves an end of an edge to `to`, keeping its color and attributes.
+    /// It's a removal and an addition as far as `changes` go, so the edge
+    /// ends up last.
+    fn reconnect(&mut self, end: EdgeEnd, to: u32) {
+        if to == end.node {
+            return;
+        }
+        let edge = match self.reconnected(end, to) {
+            Ok(edge) => edge,
+            Err(reason) => {
+                let from = self.edge_render_pass.edges[end.edge as usize].other(end.node);
+                log::info!("not reconnecting edge {} to {to}: {reason}", end.edge);
+                self.changes.emit(Change::EdgeRejected { from, to, reason });
+                return;
+            }
+        };
+        self.apply_batch(GraphMutation {
+            add_edges: vec![edge],
+            remove_edges: BTreeSet::from([end.edge]),
+            ..Default::default()
+        })
+        .expect("the edge and its new end exist");
+    }
 
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view,
-                    resolve_target,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 20.0 / 256.0,
-                            g: 20.0 / 256.,
-                            b: 28.0 / 256.,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                // depth_stencil_attachment: None,
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
+    /// The pointer's pull while M is held, a push with Shift
+    fn magnet(&self) -> Option<Magnet> {
+        if !self.input.is_magnet_pressed || self.input.gesture != Gesture::Idle {
+            return None;
+        }
+        let pos = self.mouse.pos?;
+        let radius = MAGNET_RADIUS * self.scale_factor as f32 / self.camera.scale;
+        let direction = if self.input.is_shift_pressed {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(Magnet {
+            center: self.camera.screen_to_world(pos),
+            radius,
+            strength: direction * radius * MAGNET_PULL,
+        })
+    }
+
+    /// Adds a default node at a world position, returns its index
+    fn spawn_node(&mut self, pos: cgmath::Vector2<f32>) -> u32 {
+        let node = Node::new(
+            (50.0, 50.0),
+            pos.extend(0.0),
+            cgmath::Quaternion::from_axis_angle(cgmath::vec3(0.0, 0.0, 0.0), cgmath::Deg(0.0)),
+            self.color.next_color(),
+        );
+        self.add_node(node);
+        self.node_render_pass.nodes.len() as u32 - 1
+    }
+
+    /// Closest visible node within `radius` world units of `pos`
+    fn nearest_node(&self, pos: cgmath::Vector2<f32>, radius: f32) -> Option<u32> {
+        self.node_render_pass
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.node_render_pass.hidden.contains(&(*i as u32)))
+            .map(|(i, node)| (i as u32, (node.position.truncate() - pos).magnitude()))
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Carries out an action, as one edit as far as undo goes
+    fn apply_action(&mut self, action: Action) {
+        self.history.begin();
+        self.run_action(action);
+        self.history.end();
+    }
 
-            self.edge_render_pass
-                .render(&self.camera.bind_group, &mut render_pass);
-            self.node_render_pass
-                .render(&self.camera.bind_group, &mut render_pass);
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Pan(delta) => {
+                let translate = self.camera.translate - delta.extend(0.0);
+                self.camera.update_translate(&self.queue, translate);
+            }
+            Action::MoveNode { node, delta } => {
+                let delta = delta / self.camera.scale;
+                for node in self.held_nodes(Some(node)) {
+                    let position = &mut self.node_render_pass.nodes[node as usize].position;
+                    position.x += delta.x;
+                    position.y += delta.y;
+                    self.physics.objs[node as usize].x = position.x;
+                    self.physics.objs[node as usize].y = position.y;
+                    self.node_grid.update(node, position.truncate());
+                    self.node_render_pass.update_node(node, &self.queue);
+                }
+            }
+            Action::CreateEdge { from, to } => self.connect(from, to),
+            Action::DeleteEdge(edge) => {
+                self.apply_batch(GraphMutation {
+                    remove_edges: BTreeSet::from([edge]),
+                    ..Default::default()
+                })
+                .expect("a picked edge exists");
+            }
+            Action::ReconnectEdge { end, to } => self.reconnect(end, to),
+            Action::AddNode { pos, connect } => {
+                let world = self.camera.screen_to_world(pos);
+                let target = if connect {
+                    self.node_at(pos).or_else(|| {
+                        self.nearest_node(world, AUTO_CONNECT_RADIUS / self.camera.scale)
+                    })
+                } else {
+                    None
+                };
+                let id = self.spawn_node(world);
+                if let Some(target) = target {
+                    self.connect(target, id);
+                }
+            }
+            Action::Brush { pos, chain } => {
+                let id = self.spawn_node(self.camera.screen_to_world(pos));
+                if let Some(previous) = self.brush_tail.filter(|_| chain) {
+                    self.connect(previous, id);
+                }
+                self.brush_tail = Some(id);
+            }
+            Action::SelectBox { min, max } => {
+                let (min, max) = (
+                    self.camera.screen_to_world(min),
+                    self.camera.screen_to_world(max),
+                );
+                self.select(|p| p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y);
+            }
+            Action::SelectLasso(points) => {
+                let points: Vec<_> = points
+                    .into_iter()
+                    .map(|p| self.camera.screen_to_world(p))
+                    .collect();
+                self.select(|p| input::lasso_contains(&points, p));
+            }
         }
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+    /// Selects the visible nodes whose center passes `contains`, an empty
+    /// selection clears it
+    fn select(&mut self, contains: impl Fn(cgmath::Vector2<f32>) -> bool) {
+        let selected: BTreeSet<u32> = (0..self.node_render_pass.nodes.len() as u32)
+            .filter(|i| !self.node_render_pass.hidden.contains(i))
+            .filter(|i| contains(self.node_render_pass.nodes[*i as usize].position.truncate()))
+            .collect();
+        self.selection = (!selected.is_empty()).then_some(selected);
+        self.changes.emit(Change::SelectionChanged);
+    }
+
+    pub fn add_node(&mut self, node: Node) {
+        self.history
+            .record(Step::added(self, vec![node.clone()], vec![]));
+        let idx = self.node_render_pass.nodes.len();
+        if let Some(id) = &node.external_id {
+            self.external_ids.entry(id.clone()).or_insert(idx as NodeId);
+        }
+        self.physics.objs.push(physics::Object::from_node(
+            idx as u32,
+            &node,
+            self.physics.params.strength,
+        ));
+        self.node_render_pass
+            .add_node(node, &self.device, &self.queue);
+        self.set_anchor(self.physics.anchor.as_ref().map(|anchor| anchor.node));
+        self.refresh_partition();
+        self.labels_stale = true;
+        self.analysis_stale = true;
+        self.changes.emit(Change::NodeAdded(idx as NodeId));
+    }
+
+    pub fn add_edge(&mut self, edge: Edge) {
+        self.history
+            .record(Step::added(self, vec![], vec![edge.clone()]));
+        self.edge_render_pass
+            .add_edge(edge, &self.device, &self.queue);
+        self.set_anchor(self.physics.anchor.as_ref().map(|anchor| anchor.node));
+        self.labels_stale = true;
+        self.analysis_stale = true;
+        self.changes.emit(Change::EdgeAdded(
+            self.edge_render_pass.edges.len() as u32 - 1,
+        ));
+    }
+
+    /// Applies many changes with one upload per instance buffer and one
+    /// rebuild of the adjacency, instead of one each per node. Nodes added
+    /// start at their given position, removing nodes resets whatever refers
+    /// to them by index such as the selection and the highlighted path.
+    pub fn apply_batch(&mut self, mutation: GraphMutation) -> Result<Remap, String> {
+        let step = Step::of(self, &mutation);
+        let remap = self.batch(mutation)?;
+        self.history.record(step);
+        Ok(remap)
+    }
+
+    /// `apply_batch` without recording it in the history
+    fn batch(&mut self, mutation: GraphMutation) -> Result<Remap, String> {
+        if mutation.is_empty() {
+            return Ok(Remap::default());
+        }
+        let removes = mutation.removes();
+        let updated: Vec<NodeId> = mutation.update_nodes.iter().map(|(id, _)| *id).collect();
+        let updated_edges: Vec<u32> = mutation.update_edges.iter().map(|(id, _)| *id).collect();
+        let (node_count, edge_count) = (
+            self.node_render_pass.nodes.len(),
+            self.edge_render_pass.edges.len(),
+        );
+        let mut graph = Graph {
+            nodes: std::mem::take(&mut self.node_render_pass.nodes),
+            edges: std::mem::take(&mut self.edge_render_pass.edges),
+        };
+        let result = graph.apply(mutation);
+        self.node_render_pass.nodes = graph.nodes;
+        self.edge_render_pass.edges = graph.edges;
+        let remap = result?;
+        self.renumbered(&remap, &updated, removes);
+
+        // Highest first, so each id is still valid when a mirror removes it
+        for old in (0..edge_count).rev().filter(|i| remap.edges[*i].is_none()) {
+            self.changes.emit(Change::EdgeRemoved(old as u32));
+        }
+        for old in (0..node_count).rev().filter(|i| remap.nodes[*i].is_none()) {
+            self.changes.emit(Change::NodeRemoved(old as NodeId));
+        }
+        for new in remap.nodes[node_count..].iter().flatten() {
+            self.changes.emit(Change::NodeAdded(*new));
+        }
+        for new in remap.edges[edge_count..].iter().flatten() {
+            self.changes.emit(Change::EdgeAdded(*new));
+        }
+        for id in updated.into_iter().filter_map(|id| remap.node(id)) {
+            self.changes.emit(Change::NodeUpdated(id));
+        }
+        for id in updated_edges.into_iter().filter_map(|id| remap.edge(id)) {
+            self.changes.emit(Change::EdgeUpdated(id));
+        }
+        Ok(remap)
+    }
+
+    /// Starts or stops `node` pulsing, such as while an alert on it is
+    /// active. Returns whether there is such a node.
+    pub fn set_pulsing(&mut self, node: NodeId, pulsing: bool) -> bool {
+        if node as usize >= self.node_render_pass.nodes.len() {
+            return false;
+        }
+        if pulsing {
+            self.node_render_pass.pulsing.insert(node);
+        } else {
+            self.node_render_pass.pulsing.remove(&node);
+        }
+        true
+    }
+
+    /// Nodes currently pulsing, see `set_pulsing`
+    pub fn pulsing(&self) -> &BTreeSet<u32> {
+        &self.node_render_pass.pulsing
+    }
+
+    /// The graph's nodes, indexed by `NodeId`. Change them through
+    /// `apply_batch` or `run_command`.
+    pub fn nodes(&self) -> &[Node] {
+        &self.node_render_pass.nodes
+    }
+
+    /// The graph's edges, indexed the way `Remap::edges` is
+    pub fn edges(&self) -> &[Edge] {
+        &self.edge_render_pass.edges
+    }
+
+    /// Selected nodes, if there's a selection
+    pub fn selection(&self) -> Option<&BTreeSet<u32>> {
+        self.selection.as_ref()
+    }
+
+    /// Node under the mouse
+    pub fn hovered(&self) -> Option<NodeId> {
+        self.hovered
+    }
+
+    /// Nodes pinned in place
+    pub fn pinned(&self) -> BTreeSet<u32> {
+        self.physics.pinned()
+    }
+
+    pub fn forces(&self) -> ForceParams {
+        self.physics.params
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Whether the simulation is stopped, nodes only moving when dragged
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// What the pause shortcut toggles
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Reply to the last command, shown in the status bar
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn prefs(&self) -> &Preferences {
+        &self.prefs
+    }
+
+    /// Takes back the latest edit, returns whether there was one
+    pub fn undo(&mut self) -> bool {
+        let Some(patches) = self.history.undo().map(|edit| edit.undo()) else {
+            return false;
+        };
+        self.patch(patches);
+        true
+    }
+
+    /// Makes the latest undone edit again, returns whether there was one
+    pub fn redo(&mut self) -> bool {
+        let Some(patches) = self.history.redo().map(|edit| edit.redo()) else {
+            return false;
+        };
+        self.patch(patches);
+        true
+    }
+
+    fn patch(&mut self, patches: Vec<Patch>) {
+        self.input.cancel();
+        for patch in patches {
+            let result = match patch {
+                Patch::Batch(mutation) => self.batch(mutation).map(|_| ()),
+                Patch::Reinsert { nodes, edges } => {
+                    self.reinsert(nodes, edges);
+                    Ok(())
+                }
+            };
+            // Something changed the graph behind the history's back, what's
+            // left of it can't be trusted
+            if let Err(err) = result {
+                log::warn!("dropping the undo history: {err}");
+                self.history.clear();
+                return;
+            }
+        }
+        // Edits carry the captions that followed from them, see `relabel`
+        self.labels_stale = false;
+    }
+
+    /// Puts nodes and edges an undone batch removed back under their old
+    /// ids, see `Graph::reinsert`
+    fn reinsert(&mut self, nodes: Vec<(NodeId, Node)>, edges: Vec<(u32, Edge)>) {
+        let node_ids: Vec<NodeId> = nodes.iter().map(|(id, _)| *id).collect();
+        let edge_ids: Vec<u32> = edges.iter().map(|(id, _)| *id).collect();
+        let mut graph = Graph {
+            nodes: std::mem::take(&mut self.node_render_pass.nodes),
+            edges: std::mem::take(&mut self.edge_render_pass.edges),
+        };
+        let remap = graph.reinsert(nodes, edges);
+        self.node_render_pass.nodes = graph.nodes;
+        self.edge_render_pass.edges = graph.edges;
+        self.renumbered(&remap, &[], true);
+        for id in node_ids {
+            self.changes.emit(Change::NodeAdded(id));
+        }
+        for id in edge_ids {
+            self.changes.emit(Change::EdgeAdded(id));
+        }
+    }
+
+    /// Catches everything kept alongside the graph up with a change to it,
+    /// where `remap` says where the nodes and edges before it went and
+    /// `updated` nodes were replaced. Anything referring to nodes by index
+    /// is remapped or reset when they've `moved`.
+    fn renumbered(&mut self, remap: &Remap, updated: &[NodeId], moved: bool) {
+        // Kept nodes stay where the simulation has them, which can be ahead
+        // of their drawn position between ticks
+        let old_objs = std::mem::take(&mut self.physics.objs);
+        let strength = self.physics.params.strength;
+        self.physics.objs = self
+            .node_render_pass
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| physics::Object::from_node(i as u32, node, strength))
+            .collect();
+        for (old, obj) in old_objs.iter().enumerate() {
+            let Some(new) = remap.node(old as NodeId) else {
+                continue;
+            };
+            let kept = &mut self.physics.objs[new as usize];
+            // Updated nodes start over from where they were set, but a
+            // recolored or resized pinned node stays pinned
+            kept.fixed = obj.fixed;
+            if !updated.contains(&(old as NodeId)) {
+                (kept.x, kept.y, kept.z) = (obj.x, obj.y, obj.z);
+                (kept.vx, kept.vy, kept.vz) = (obj.vx, obj.vy, obj.vz);
+            }
+        }
+        self.physics.clear_step();
+        self.external_ids = graph::external_ids(&self.node_render_pass.nodes);
+        self.edge_render_pass.edge_map = algorithms::incidence_map(
+            &self.edge_render_pass.edges,
+            0..self.edge_render_pass.edges.len() as u32,
+        );
+        if let Some(sample) = &mut self.sample_view {
+            sample.invalidate();
+        }
+        let anchor = self.physics.anchor.take();
+        self.set_anchor(anchor.and_then(|anchor| remap.node(anchor.node)));
+        self.refresh_partition();
+        if let Some(labels) = &mut self.label_template {
+            labels.remap(remap);
+        }
+        self.session_unknown.remap(remap);
+        self.labels_stale = true;
+        self.analysis_stale = true;
+
+        if moved {
+            self.physics.frozen = remap.node_set(&self.physics.frozen);
+            self.physics.heat.clear();
+            self.node_render_pass.hidden = remap.node_set(&self.node_render_pass.hidden);
+            self.node_render_pass.pulsing = remap.node_set(&self.node_render_pass.pulsing);
+            self.hovered = None;
+            self.edge_render_pass.hidden.clear();
+            self.node_render_pass.highlight = None;
+            self.edge_render_pass.highlight = None;
+            self.input.cancel();
+            if let Some(selection) = self.selection.take() {
+                let selection = remap.node_set(&selection);
+                self.selection = (!selection.is_empty()).then_some(selection);
+                self.changes.emit(Change::SelectionChanged);
+            }
+            self.search = None;
+            self.context_menu = None;
+            self.trail.clear();
+            self.transition = None;
+            self.brush_tail = None;
+            self.path = None;
+        }
+        self.node_render_pass.write(&self.device, &self.queue);
+        self.edge_render_pass.write(&self.device, &self.queue);
+    }
+
+    /// Removes every node and edge, along with anything derived from them
+    pub fn clear_graph(&mut self) {
+        self.node_render_pass.nodes.clear();
+        self.node_render_pass.highlight = None;
+        self.node_render_pass.hidden.clear();
+        self.edge_render_pass.edges.clear();
+        self.edge_render_pass.edge_map.clear();
+        self.edge_render_pass.highlight = None;
+        self.edge_render_pass.hidden.clear();
+        self.node_render_pass.pulsing.clear();
+        self.hovered = None;
+        if let Some(sample) = &mut self.sample_view {
+            sample.invalidate();
+        }
+        self.physics.objs.clear();
+        self.physics.frozen.clear();
+        self.physics.heat.clear();
+        self.physics.anchor = None;
+        self.physics.partition = None;
+        self.input.cancel();
+        if self.selection.take().is_some() {
+            self.changes.emit(Change::SelectionChanged);
+        }
+        self.search = None;
+        self.context_menu = None;
+        self.trail.clear();
+        self.transition = None;
+        self.brush_tail = None;
+        self.path = None;
+        self.mst = None;
+        self.analysis_stale = true;
+        self.history.clear();
+        if let Some(labels) = &mut self.label_template {
+            labels.forget();
+        }
+        self.external_ids.clear();
+        self.changes.emit(Change::GraphCleared);
+    }
+
+    /// Replaces the current graph and picks force parameters to suit it. A
+    /// graph with edges to nodes it doesn't have is refused, leaving the
+    /// current one as it was.
+    pub fn load_graph(&mut self, mut graph: Graph) -> Result<(), String> {
+        graph.check()?;
+        self.clear_graph();
+        let (nodes, edges) = (graph.nodes.len(), graph.edges.len());
+        if self.limits.past_hard(nodes, edges) {
+            graph = self.limits.sample(&graph, &mut self.rng);
+            self.changes.emit(Change::GraphSampled { nodes, edges });
+        }
+        self.preset = self
+            .preset_override
+            .unwrap_or_else(|| Preset::for_size(graph.nodes.len(), graph.edges.len()));
+        self.physics.set_params(
+            self.preset
+                .apply(ForceParams::calibrate(&graph.nodes, &graph.edges)),
+        );
+        self.ticker = Some(Ticker::new(self.preset.tick_rate));
+        self.physics.reheat();
+        self.apply_batch(GraphMutation {
+            add_nodes: graph.nodes,
+            add_edges: graph.edges,
+            ..Default::default()
+        })?;
+        // Loading starts over rather than being undoable
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Adds a generated graph to the right of the current one as one
+    /// undoable step, or loads it like a file when there's nothing to put it
+    /// beside
+    fn spawn(&mut self, generator: Generator, seed: u64) -> Result<String, String> {
+        generator.check()?;
+        let mut graph = generator.generate(seed);
+        let msg = format!(
+            "generated a {} graph of {} nodes and {} edges with seed {seed}",
+            generator.name(),
+            graph.nodes.len(),
+            graph.edges.len()
+        );
+        let (Some((lo, hi)), Some((min, max))) = (
+            export::graph_bounds(&self.node_render_pass.nodes),
+            export::graph_bounds(&graph.nodes),
+        ) else {
+            self.load_graph(graph)?;
+            return Ok(msg);
+        };
+        // Level with the current graph's middle
+        let offset = vec2(
+            hi.x + SPAWN_GAP - min.x,
+            (lo.y + hi.y - min.y - max.y) / 2.0,
+        );
+        for node in &mut graph.nodes {
+            node.position += offset.extend(0.0);
+        }
+        let base = self.node_render_pass.nodes.len() as NodeId;
+        for edge in &mut graph.edges {
+            (edge.a_id, edge.b_id) = (edge.a_id + base, edge.b_id + base);
+        }
+        graph.sync_edges();
+        self.apply_batch(GraphMutation {
+            add_nodes: graph.nodes,
+            add_edges: graph.edges,
+            ..Default::default()
+        })?;
+        self.physics.reheat();
+        Ok(msg)
+    }
+
+    /// Replaces the graph with a file's, picking the format from its
+    /// extension. What the `load` command and dropping a file on the window
+    /// do.
+    pub fn load_file<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<String, String> {
+        let path = path.as_ref();
+        let graph = formats::load(path).map_err(|e| format!("{e:#}"))?;
+        self.node_render_pass.image_dir = path.parent().map(Into::into);
+        let msg = self.show_loaded(graph)?;
+        self.prefs.add_recent_file(path.display().to_string());
+        self.save_prefs();
+        Ok(msg)
+    }
+
+    /// Like `load_file`, for contents read from somewhere other than a path,
+    /// such as a file picked in the browser. `name`'s extension picks the
+    /// format.
+    pub fn load_source(&mut self, name: &str, src: &str) -> Result<String, String> {
+        let graph = formats::importer_for(std::path::Path::new(name))
+            .and_then(|importer| importer.import(src))
+            .with_context(|| format!("failed to import {name}"))
+            .map_err(|e| format!("{e:#}"))?;
+        self.node_render_pass.image_dir = None;
+        self.show_loaded(graph)
+    }
+
+    /// Opens a session or a graph file. Older sessions were saved as JSON,
+    /// which are told apart from JSON graphs by their contents.
+    pub fn open<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<String, String> {
+        let path = path.as_ref();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        let json = ext == Some("json");
+        if ext == Some(SESSION_EXTENSION)
+            || json && std::fs::read_to_string(path).is_ok_and(|src| session::is_session(&src))
+        {
+            self.load_session(path)
+        } else {
+            self.load_file(path)
+        }
+    }
+
+    /// What Ctrl+O does: asks for a file and opens it. On the web the file
+    /// arrives later, see `process_console`.
+    pub fn pick_file(&mut self) -> Result<String, String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = picker::pick_file().ok_or("nothing picked")?;
+            self.open(path)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.file_picker.open()?;
+            Ok("picking a file".into())
+        }
+    }
+
+    /// Shows a graph just read from a file, merged and laid out the way
+    /// loaded graphs are. Returns what to report.
+    fn show_loaded(&mut self, mut graph: Graph) -> Result<String, String> {
+        let mut msg = format!(
+            "loaded {} nodes and {} edges",
+            graph.nodes.len(),
+            graph.edges.len()
+        );
+        if let Some(merge) = &self.edge_merge {
+            let merged = graph.merge_parallel_edges(merge);
+            msg += &format!(" ({merged} merged as parallels)");
+        }
+        let (nodes, edges) = (graph.nodes.len(), graph.edges.len());
+        let placed = !layout::is_initial_placement(&graph);
+        self.load_graph(graph)?;
+        self.session_unknown = UnknownFields::default();
+        if self.limits.past_hard(nodes, edges) {
+            msg += &format!(
+                ", past the limits so only {} nodes and {} edges are shown",
+                self.node_render_pass.nodes.len(),
+                self.edge_render_pass.edges.len()
+            );
+        }
+        // Laid out the way the last graph was, unless the file says where
+        // its nodes go
+        if let Some(layout) = self.prefs.layout.clone().filter(|_| !placed) {
+            match Command::parse(&layout).and_then(|layout| self.run_command(layout)) {
+                Ok(_) => msg += &format!(", laid out with {layout}"),
+                Err(err) => log::warn!("can't lay out with {layout}: {err}"),
+            }
+        }
+        Ok(format!("{msg}, forces {}", self.physics.params))
+    }
+
+    /// Saves `prefs`, only logging when that fails as they're a
+    /// convenience
+    fn save_prefs(&self) {
+        if let Err(err) = self.prefs.save() {
+            log::warn!("can't save preferences: {err:#}");
+        }
+    }
+
+    /// The graph, view and forces as they are now, for saving
+    pub fn session(&self) -> Session {
+        Session {
+            graph: Graph {
+                nodes: self.node_render_pass.nodes.clone(),
+                edges: self.edge_render_pass.edges.clone(),
+            },
+            translate: self.camera.translate.truncate(),
+            scale: self.camera.scale,
+            forces: self.physics.params,
+            alpha: self.physics.alpha,
+            pinned: self.physics.pinned(),
+            annotations: (self.annotation_render_pass.annotations.iter())
+                .map(annotation::Annotation::to_command)
+                .collect(),
+            snapshots: self.snapshots.clone(),
+            unknown: self.session_unknown.clone(),
+        }
+    }
+
+    /// Replaces the graph, view and forces with a saved session's
+    pub fn restore_session(&mut self, session: Session) -> Result<(), String> {
+        let nodes = session.graph.nodes.len();
+        self.load_graph(session.graph)?;
+        self.session_unknown = session.unknown;
+        self.physics.set_params(session.forces);
+        self.physics.alpha = session.alpha;
+        let src: String = (session.annotations.iter())
+            .map(|a| a.clone() + "\n")
+            .collect();
+        match annotation::parse_annotations(&src) {
+            Ok(annotations) => self.annotation_render_pass.annotations = annotations,
+            Err(e) => log::warn!("session annotations: {e}"),
+        }
+        // Indices no longer line up if the graph had to be sampled
+        if self.node_render_pass.nodes.len() == nodes {
+            for node in session.pinned {
+                self.physics.toggle_pin(node);
+            }
+            self.snapshots = session.snapshots;
+        }
+        self.camera.update_scale(&self.queue, session.scale, None);
+        self.camera.update_translate(
+            &self.queue,
+            session.translate.extend(self.camera.translate.z),
+        );
+        Ok(())
+    }
+
+    /// What the `session save` command and Ctrl+S do
+    pub fn save_session<P: AsRef<std::path::Path>>(&self, path: P) -> Result<String, String> {
+        let path = path.as_ref();
+        self.session().save(path).map_err(|e| format!("{e:#}"))?;
+        Ok(format!("saved the session to {}", path.display()))
+    }
+
+    /// What the `session load` command does
+    pub fn load_session<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<String, String> {
+        let path = path.as_ref();
+        let session = Session::load(path).map_err(|e| format!("{e:#}"))?;
+        self.node_render_pass.image_dir = path.parent().map(Into::into);
+        self.restore_session(session)?;
+        self.prefs.add_recent_file(path.display().to_string());
+        self.save_prefs();
+        Ok(format!(
+            "loaded the session from {}, {} nodes and {} edges",
+            path.display(),
+            self.node_render_pass.nodes.len(),
+            self.edge_render_pass.edges.len()
+        ))
+    }
+
+    pub fn show_histogram(&mut self, source: HistogramSource) {
+        self.histogram = Some(HistogramChart::new(source));
+    }
+
+    pub fn hide_histogram(&mut self) {
+        self.histogram = None;
+        self.node_render_pass.highlight = None;
+    }
+
+    pub fn hide_timeline(&mut self) {
+        self.timeline = None;
+        self.node_render_pass.hidden.clear();
+        self.edge_render_pass.hidden.clear();
+        // What the sample leaves out stays hidden
+        if let Some(sample) = &self.sample_view {
+            if !sample.is_full(self.camera.scale) {
+                let (hidden_nodes, hidden_edges) = sample.hidden();
+                self.node_render_pass.hidden.extend(hidden_nodes);
+                self.edge_render_pass.hidden.extend(hidden_edges);
+            }
+        }
+    }
+
+    /// Gives overlay widgets first pick at a click, returns true if one of them
+    /// consumed it
+    fn overlay_click(&mut self, pos: &cgmath::Vector2<f32>) -> bool {
+        let (w, h) = self.widget_area();
+        let pos = self.to_ui(pos);
+        self.histogram
+            .as_mut()
+            .is_some_and(|histogram| histogram.click(w, h, &pos))
+            || self
+                .timeline
+                .as_mut()
+                .is_some_and(|timeline| timeline.click(w, h, &pos))
+    }
+
+    pub fn run_command(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Path {
+                from,
+                to,
+                weight,
+                heuristic,
+            } => {
+                let nodes = &self.node_render_pass.nodes;
+                let a = console::resolve_node(nodes, &from)
+                    .ok_or_else(|| format!("no such node: {from}"))?;
+                let b = console::resolve_node(nodes, &to)
+                    .ok_or_else(|| format!("no such node: {to}"))?;
+                let edges = &self.edge_render_pass.edges;
+                let edge_map = &self.edge_render_pass.edge_map;
+                let path = match heuristic {
+                    Heuristic::None => {
+                        algorithms::shortest_path(nodes.len(), edges, edge_map, a, b, &weight)
+                    }
+                    Heuristic::Euclidean => algorithms::astar(
+                        nodes.len(),
+                        edges,
+                        edge_map,
+                        a,
+                        b,
+                        &weight,
+                        algorithms::euclidean_heuristic(nodes, edges, &weight, b),
+                    ),
+                }
+                .ok_or_else(|| format!("no path from {from} to {to}"))?;
+                let msg = format!("path {:?} cost {}", path.nodes, path.cost);
+                self.path = Some(path);
+                Ok(msg)
+            }
+            Command::Scc => {
+                let components = algorithms::cyclic_components(
+                    self.node_render_pass.nodes.len(),
+                    &self.edge_render_pass.edges,
+                );
+                let count = components.len();
+                self.components = match self.components {
+                    Some(_) => None,
+                    None => Some(components),
+                };
+                Ok(format!("{count} strongly connected components with cycles"))
+            }
+            Command::Cycle => {
+                let cycle = algorithms::find_cycle(
+                    self.node_render_pass.nodes.len(),
+                    &self.edge_render_pass.edges,
+                )
+                .ok_or_else(|| "graph is acyclic".to_string())?;
+                let msg = format!("cycle {:?}", cycle.nodes);
+                self.path = Some(cycle);
+                Ok(msg)
+            }
+            Command::Mst { weight, links_only } => {
+                let mut mst = MstView {
+                    weight,
+                    links_only,
+                    edges: vec![],
+                    edge_map: BTreeMap::new(),
+                };
+                self.update_mst(&mut mst);
+                let msg = format!(
+                    "spanning forest has {} of {} edges",
+                    mst.edges.len(),
+                    self.edge_render_pass.edges.len()
+                );
+                self.mst = Some(mst);
+                Ok(msg)
+            }
+            Command::Load { path } => self.load_file(&path),
+            Command::Dedup(None) => {
+                self.edge_merge = None;
+                Ok("loaded graphs keep their parallel edges".into())
+            }
+            Command::Dedup(Some(merge)) => {
+                let edges = &self.edge_render_pass.edges;
+                let (merged, removed) = merge.apply(edges);
+                if removed > 0 {
+                    self.apply_batch(GraphMutation {
+                        add_edges: merged,
+                        remove_edges: (0..edges.len() as u32).collect(),
+                        ..Default::default()
+                    })?;
+                }
+                let msg = format!(
+                    "merged {removed} parallel edges, taking the {} of their {} and counting them in {MULTIPLICITY_ATTR}",
+                    merge.aggregate, merge.weight
+                );
+                self.edge_merge = Some(merge);
+                Ok(msg)
+            }
+            Command::Save { path, selected } => {
+                let graph = if selected {
+                    let selection = self.selection.as_ref().ok_or("nothing is selected")?;
+                    self.subgraph(selection)
+                } else {
+                    Graph {
+                        nodes: self.node_render_pass.nodes.clone(),
+                        edges: self.edge_render_pass.edges.clone(),
+                    }
+                };
+                formats::save(&path, &graph).map_err(|e| format!("{e:#}"))?;
+                Ok(format!(
+                    "saved {} nodes and {} edges to {path}",
+                    graph.nodes.len(),
+                    graph.edges.len()
+                ))
+            }
+            Command::Diff { old, new } => {
+                let old = formats::load(&old).map_err(|e| format!("{e:#}"))?;
+                let new = formats::load(&new).map_err(|e| format!("{e:#}"))?;
+                let union = diff::diff(&old, &new);
+                let count = |status: diff::DiffStatus| {
+                    union
+                        .nodes
+                        .iter()
+                        .filter(|n| {
+                            n.attributes.get(diff::DIFF_ATTR).and_then(|v| v.as_text())
+                                == Some(status.as_str())
+                        })
+                        .count()
+                };
+                let msg = format!(
+                    "{} nodes added, {} removed, {} unchanged",
+                    count(diff::DiffStatus::Added),
+                    count(diff::DiffStatus::Removed),
+                    count(diff::DiffStatus::Unchanged)
+                );
+                self.load_graph(union)?;
+                Ok(msg)
+            }
+            Command::Annotate(annotation) => {
+                self.annotation_render_pass.annotations.push(annotation);
+                Ok(format!(
+                    "{} annotations",
+                    self.annotation_render_pass.annotations.len()
+                ))
+            }
+            Command::SaveAnnotations { path } => {
+                let annotations = &self.annotation_render_pass.annotations;
+                let src: String = annotations.iter().map(|a| a.to_command() + "\n").collect();
+                std::fs::write(&path, src).map_err(|e| format!("{path}: {e}"))?;
+                Ok(format!("saved {} annotations to {path}", annotations.len()))
+            }
+            Command::LoadAnnotations { path } => {
+                let src = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+                let annotations =
+                    annotation::parse_annotations(&src).map_err(|e| format!("{path}: {e}"))?;
+                let msg = format!("loaded {} annotations", annotations.len());
+                self.annotation_render_pass.annotations = annotations;
+                Ok(msg)
+            }
+            Command::ClearAnnotations => {
+                self.annotation_render_pass.annotations.clear();
+                Ok("cleared annotations".into())
+            }
+            Command::Style(rule) => {
+                let what = match rule.channel {
+                    Channel::Width => ("edges", "edge"),
+                    Channel::Color | Channel::Size => ("nodes", "node"),
+                };
+                let scale = rule.scale(&self.node_render_pass.nodes, &self.edge_render_pass.edges);
+                let msg = match scale {
+                    Some(_) => format!("styling {} by {}", what.0, rule.attr),
+                    None => format!("no {} has a usable {} attribute yet", what.1, rule.attr),
+                };
+                self.styles.retain(|r| r.channel != rule.channel);
+                self.styles.push(rule);
+                Ok(msg)
+            }
+            Command::LabelTemplate(template) => {
+                let msg = format!("labeling nodes as {}", template.source);
+                match &mut self.label_template {
+                    Some(labels) => labels.template = template,
+                    None => self.label_template = Some(TemplatedLabels::new(template)),
+                }
+                let step = self.relabel(true)?;
+                self.history.record(step);
+                Ok(msg)
+            }
+            Command::ClearStyle => {
+                self.styles.clear();
+                if let Some(labels) = self.label_template.take() {
+                    self.apply_batch(GraphMutation {
+                        update_nodes: labels.restore(&self.node_render_pass.nodes),
+                        ..Default::default()
+                    })?;
+                }
+                Ok("cleared style rules, nodes keep their current look apart from templated labels".into())
+            }
+            Command::Spring {
+                spring,
+                selected_only,
+            } => {
+                let link_distance = self.physics.params.link_distance;
+                let mut updates = self.edges_in_scope(selected_only)?;
+                let mut edges: Vec<&mut Edge> = updates.iter_mut().map(|(_, edge)| edge).collect();
+                let count = match &spring {
+                    Spring::Set {
+                        rest_length,
+                        strength,
+                    } => {
+                        for edge in edges.iter_mut() {
+                            edge.rest_length = rest_length.or(edge.rest_length);
+                            edge.strength = strength.unwrap_or(edge.strength);
+                        }
+                        edges.len()
+                    }
+                    Spring::Weight(attr) => {
+                        match edge::weigh_springs(&mut edges, attr, link_distance) {
+                            0 => return Err(format!("no edge has a positive {attr}")),
+                            count => count,
+                        }
+                    }
+                    Spring::Reset => {
+                        for edge in edges.iter_mut() {
+                            edge.rest_length = None;
+                            edge.strength = DEFAULT_EDGE_STRENGTH;
+                        }
+                        edges.len()
+                    }
+                };
+                self.update_edges(updates);
+                self.physics.reheat();
+                Ok(format!("set the springs of {count} edges"))
+            }
+            Command::Marker {
+                head,
+                marker,
+                selected_only,
+            } => {
+                let mut updates = self.edges_in_scope(selected_only)?;
+                for (_, edge) in updates.iter_mut() {
+                    *if head { &mut edge.head } else { &mut edge.tail } = marker;
+                }
+                let count = updates.len();
+                self.update_edges(updates);
+                let end = if head { "head" } else { "tail" };
+                Ok(format!("{end} marker {} on {count} edges", marker.name()))
+            }
+            Command::Shape {
+                shape,
+                selected_only,
+            } => {
+                let mut updates = self.nodes_in_scope(selected_only)?;
+                for (_, node) in updates.iter_mut() {
+                    node.shape = shape;
+                }
+                let count = updates.len();
+                self.update_nodes(updates);
+                Ok(format!("{} shape on {count} nodes", shape.name()))
+            }
+            Command::Image {
+                path,
+                selected_only,
+            } => {
+                let mut updates = self.nodes_in_scope(selected_only)?;
+                for (_, node) in updates.iter_mut() {
+                    node.image = path.clone();
+                }
+                let count = updates.len();
+                self.update_nodes(updates);
+                Ok(match path {
+                    Some(path) => format!("{path} on {count} nodes"),
+                    None => format!("removed the image from {count} nodes"),
+                })
+            }
+            Command::ImageRetry => {
+                self.node_render_pass.retry_images();
+                Ok("loading images again".into())
+            }
+            Command::Label { node, text } => {
+                let id = console::resolve_node(&self.node_render_pass.nodes, &node)
+                    .ok_or_else(|| format!("no such node: {node}"))?;
+                let mut updated = self.node_render_pass.nodes[id as usize].clone();
+                updated.label = text;
+                let msg = match &updated.label {
+                    Some(text) => format!("labeled {node} {text}"),
+                    None => format!("{node} is labeled by name again"),
+                };
+                self.apply_batch(GraphMutation {
+                    update_nodes: vec![(id, updated)],
+                    ..Default::default()
+                })?;
+                Ok(msg)
+            }
+            Command::Labels { visible, min_zoom } => {
+                self.label_render_pass.visible = visible;
+                if let Some(min_zoom) = min_zoom {
+                    self.label_render_pass.min_zoom = min_zoom;
+                }
+                Ok(format!(
+                    "labels {}, fading out below zoom {}",
+                    if visible { "shown" } else { "hidden" },
+                    self.label_render_pass.min_zoom
+                ))
+            }
+            Command::Metrics(target) => {
+                self.metrics_frame = 0;
+                let (sink, msg): (Box<dyn MetricsSink>, _) = match target {
+                    None => {
+                        self.metrics = None;
+                        return Ok("metrics off".into());
+                    }
+                    Some(MetricsTarget::Csv(path)) => {
+                        let file =
+                            std::fs::File::create(&path).map_err(|e| format!("{path}: {e}"))?;
+                        let sink = CsvSink::new(file).map_err(|e| format!("{path}: {e}"))?;
+                        (Box::new(sink), format!("writing metrics to {path}"))
+                    }
+                    Some(MetricsTarget::Prometheus(addr)) => {
+                        let sink =
+                            PrometheusSink::bind(&addr).map_err(|e| format!("{addr}: {e}"))?;
+                        let msg = format!("serving metrics on http://{}", sink.addr);
+                        (Box::new(sink), msg)
+                    }
+                };
+                self.metrics = Some(sink);
+                Ok(msg)
+            }
+            Command::Badge { corner, attr } => {
+                let badges = &mut self.label_render_pass.badges;
+                badges.retain(|badge| badge.corner != corner);
+                Ok(match attr {
+                    Some(attr) => {
+                        let message = format!("badging nodes with {attr}");
+                        badges.push(Badge::new(attr, corner));
+                        message
+                    }
+                    None => "badge removed".into(),
+                })
+            }
+            Command::Pulse { node: None, .. } => {
+                let count = self.node_render_pass.pulsing.len();
+                self.node_render_pass.pulsing.clear();
+                Ok(format!("stopped {count} nodes pulsing"))
+            }
+            Command::Pulse {
+                node: Some(key),
+                on,
+            } => {
+                let nodes = match key.as_str() {
+                    "selected" => self.selection.clone().ok_or("nothing selected")?,
+                    key => {
+                        BTreeSet::from([console::resolve_node(&self.node_render_pass.nodes, key)
+                            .ok_or_else(|| format!("no such node: {key}"))?])
+                    }
+                };
+                for node in &nodes {
+                    self.set_pulsing(*node, on);
+                }
+                let verb = if on { "pulsing" } else { "stopped pulsing" };
+                Ok(format!("{verb} {} nodes", nodes.len()))
+            }
+            Command::Legend(anchor) => {
+                self.legend = anchor.map(|anchor| Legend {
+                    anchor,
+                    ..self.legend.unwrap_or_default()
+                });
+                Ok(match anchor {
+                    Some(_) => "legend shown".into(),
+                    None => "legend hidden".into(),
+                })
+            }
+            Command::Export { path, options } if path.ends_with(".html") => {
+                self.export_html(&path, options.region)
+                    .map_err(|e| format!("{e:#}"))?;
+                Ok(format!("exported interactive page to {path}"))
+            }
+            Command::Export { path, options } => {
+                let (w, h) = self
+                    .export_png(&path, options)
+                    .map_err(|e| format!("{e:#}"))?;
+                Ok(format!("exported {w}x{h} image to {path}"))
+            }
+            Command::Shake {
+                radius,
+                selected_only,
+            } => {
+                let only = match (&self.selection, selected_only) {
+                    (_, false) => None,
+                    (Some(selection), true) => Some(selection),
+                    (None, true) => return Err("nothing selected".into()),
+                };
+                self.physics.shake(&mut self.rng, radius, only);
+                Ok(match only {
+                    Some(only) => format!("shook {} nodes", only.len()),
+                    None => format!("shook {} nodes", self.physics.objs.len()),
+                })
+            }
+            Command::Freeze(scope) => {
+                let nodes = self.scope_nodes(&scope)?;
+                let count = nodes.len();
+                self.physics.frozen.extend(nodes);
+                Ok(format!("froze {count} nodes"))
+            }
+            Command::Anchor(None) => {
+                self.physics.anchor.take().ok_or("no anchor set")?;
+                self.physics.reheat();
+                Ok("anchor released".into())
+            }
+            Command::Anchor(Some(key)) => {
+                let nodes = &self.node_render_pass.nodes;
+                let node = match key.as_str() {
+                    "selected" => match &self.selection {
+                        Some(selection) if selection.len() == 1 => *selection.first().unwrap(),
+                        Some(_) => return Err("select a single node to anchor".into()),
+                        None => return Err("nothing selected".into()),
+                    },
+                    key => console::resolve_node(nodes, key)
+                        .ok_or_else(|| format!("no such node: {key}"))?,
+                };
+                self.set_anchor(Some(node));
+                self.physics.reheat();
+                Ok(format!("anchored {key}"))
+            }
+            Command::Bipartite(None) => {
+                self.physics
+                    .partition
+                    .take()
+                    .ok_or("no bipartite layout set")?;
+                self.physics.reheat();
+                Ok("sides released".into())
+            }
+            Command::Bipartite(Some((attr, shape))) => {
+                let partition = Partition::new(&self.node_render_pass.nodes, &attr, shape)?;
+                let mut graph = Graph {
+                    nodes: self.node_render_pass.nodes.clone(),
+                    edges: self.edge_render_pass.edges.clone(),
+                };
+                layout::bipartite_placement(
+                    &mut graph,
+                    &partition,
+                    self.physics.params.link_distance,
+                );
+                let target = Snapshot::capture("bipartite", &graph.nodes);
+                self.transition = Some(Transition::new(&self.node_render_pass.nodes, &target));
+                self.physics.partition = Some(partition);
+                self.physics.alpha = layout::REFINE_ALPHA;
+                Ok(format!("split nodes by {attr}"))
+            }
+            Command::Multilevel => {
+                if self.node_render_pass.nodes.is_empty() {
+                    return Err("the graph is empty, nothing to lay out".into());
+                }
+                let mut graph = Graph {
+                    nodes: self.node_render_pass.nodes.clone(),
+                    edges: self.edge_render_pass.edges.clone(),
+                };
+                layout::multilevel(&mut graph, self.rng.next_u64());
+                let target = Snapshot::capture("multilevel", &graph.nodes);
+                self.transition = Some(Transition::new(&self.node_render_pass.nodes, &target));
+                self.physics.alpha = layout::REFINE_ALPHA;
+                self.prefs.layout = Some("multilevel".into());
+                self.save_prefs();
+                Ok(format!("laid out {} nodes", graph.nodes.len()))
+            }
+            Command::Spectral => {
+                if self.node_render_pass.nodes.is_empty() {
+                    return Err("the graph is empty, nothing to lay out".into());
+                }
+                let mut graph = Graph {
+                    nodes: self.node_render_pass.nodes.clone(),
+                    edges: self.edge_render_pass.edges.clone(),
+                };
+                layout::spectral_placement(
+                    &mut graph,
+                    self.rng.next_u64(),
+                    self.physics.params.link_distance,
+                );
+                let target = Snapshot::capture("spectral", &graph.nodes);
+                self.transition = Some(Transition::new(&self.node_render_pass.nodes, &target));
+                self.physics.reheat();
+                self.prefs.layout = Some("spectral".into());
+                self.save_prefs();
+                Ok(format!("placed {} nodes", graph.nodes.len()))
+            }
+            Command::Unfreeze(scope) => {
+                let count = self.unfreeze(&scope)?;
+                Ok(format!("unfroze {count} nodes"))
+            }
+            Command::Reheat(scope) => {
+                self.unfreeze(&scope)?;
+                self.physics.reheat();
+                Ok(format!(
+                    "reheated, {} nodes still frozen",
+                    self.physics.frozen.len()
+                ))
+            }
+            Command::Forces {
+                auto,
+                link_distance,
+                repulsion_range,
+                strength,
+                gravity,
+                collision,
+                collision_iterations,
+                repulsion,
+            } => {
+                let mut params = if auto {
+                    self.preset.apply(ForceParams::calibrate(
+                        &self.node_render_pass.nodes,
+                        &self.edge_render_pass.edges,
+                    ))
+                } else {
+                    self.physics.params
+                };
+                params.link_distance = link_distance.unwrap_or(params.link_distance);
+                params.repulsion_range = repulsion_range.unwrap_or(params.repulsion_range);
+                params.strength = strength.unwrap_or(params.strength);
+                params.gravity = gravity.unwrap_or(params.gravity);
+                params.collision = collision.unwrap_or(params.collision);
+                params.collision_iterations =
+                    collision_iterations.unwrap_or(params.collision_iterations);
+                params.repulsion = repulsion.unwrap_or(params.repulsion);
+                if params != self.physics.params {
+                    self.physics.set_params(params);
+                    self.physics.reheat();
+                }
+                Ok(format!("forces {params}"))
+            }
+            Command::Snapshot { name } => {
+                let snapshot = Snapshot::capture(&name, &self.node_render_pass.nodes);
+                match self.snapshots.iter_mut().find(|s| s.name == name) {
+                    Some(existing) => *existing = snapshot,
+                    None => self.snapshots.push(snapshot),
+                }
+                Ok(format!("saved snapshot {name}"))
+            }
+            Command::Restore { name } => {
+                let snapshot = self
+                    .snapshots
+                    .iter()
+                    .find(|s| s.name == name)
+                    .ok_or_else(|| format!("no such snapshot: {name}"))?;
+                self.transition = Some(Transition::new(&self.node_render_pass.nodes, snapshot));
+                Ok(format!("restoring snapshot {name}"))
+            }
+            Command::ListSnapshots => Ok(if self.snapshots.is_empty() {
+                "no snapshots".into()
+            } else {
+                self.snapshots
+                    .iter()
+                    .map(|s| format!("{} ({} nodes)", s.name, s.positions.len()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }),
+            Command::SaveSnapshots { path } => {
+                std::fs::write(&path, snapshot::write_snapshots(&self.snapshots))
+                    .map_err(|e| format!("{path}: {e}"))?;
+                Ok(format!(
+                    "saved {} snapshots to {path}",
+                    self.snapshots.len()
+                ))
+            }
+            Command::LoadSnapshots { path } => {
+                let src = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+                let loaded = snapshot::parse_snapshots(&src).map_err(|e| format!("{path}: {e}"))?;
+                let msg = format!("loaded {} snapshots", loaded.len());
+                for snapshot in loaded {
+                    self.snapshots.retain(|s| s.name != snapshot.name);
+                    self.snapshots.push(snapshot);
+                }
+                Ok(msg)
+            }
+            Command::SaveSession { path } => self.save_session(path),
+            Command::LoadSession { path } => self.load_session(path),
+            Command::PickTolerance(pixels) => {
+                self.pick_tolerance = pixels;
+                Ok(format!("picking nodes up to {pixels} px away"))
+            }
+            Command::Preset(None) => {
+                self.preset_override = None;
+                let preset = Preset::for_size(
+                    self.node_render_pass.nodes.len(),
+                    self.edge_render_pass.edges.len(),
+                );
+                Ok(self.use_preset(preset))
+            }
+            Command::Preset(Some(preset)) => {
+                self.preset_override = Some(preset);
+                Ok(self.use_preset(preset))
+            }
+            Command::Limits {
+                soft_nodes,
+                soft_edges,
+                hard_nodes,
+                hard_edges,
+            } => {
+                let limits = &mut self.limits;
+                limits.soft_nodes = soft_nodes.unwrap_or(limits.soft_nodes);
+                limits.soft_edges = soft_edges.unwrap_or(limits.soft_edges);
+                limits.hard_nodes = hard_nodes.unwrap_or(limits.hard_nodes);
+                limits.hard_edges = hard_edges.unwrap_or(limits.hard_edges);
+                Ok(limits.describe())
+            }
+            Command::Sample(None) => {
+                self.sample_view.take().ok_or("not sampling")?;
+                self.node_render_pass.hidden.clear();
+                self.edge_render_pass.hidden.clear();
+                Ok("drawing every node".into())
+            }
+            Command::Sample(Some(view)) => {
+                let msg = format!(
+                    "drawing a sample of {} nodes below zoom {}",
+                    view.size, view.full_zoom
+                );
+                self.sample_view = Some(view);
+                Ok(msg)
+            }
+            Command::Region(None) => {
+                self.region_margin.take().ok_or("not limited to a region")?;
+                self.physics.region = None;
+                self.physics.reheat();
+                Ok("simulating every node".into())
+            }
+            Command::Region(Some(margin)) => {
+                self.region_margin = Some(margin);
+                Ok(format!(
+                    "simulating only nodes in view and {}% around it",
+                    margin * 100.0
+                ))
+            }
+            Command::Cull(culling) => {
+                self.culling = culling;
+                Ok(if culling {
+                    "drawing only what's on screen".into()
+                } else {
+                    "drawing everything, on screen or not".into()
+                })
+            }
+            Command::Generate { generator, seed } => {
+                let seed = seed.unwrap_or_else(|| self.rng.next_u64());
+                self.spawn(generator, seed)
+            }
+            Command::Quality(None) => {
+                self.adaptive = None;
+                self.set_quality(Quality::levels(self.full_settings)[0]);
+                Ok("drawing at full quality".into())
+            }
+            Command::Quality(Some(budget)) => {
+                if self.gpu_timer.is_none() {
+                    return Err("this adapter can't time frames to adapt quality to".into());
+                }
+                let adaptive = AdaptiveQuality::new(self.full_settings, budget);
+                self.set_quality(adaptive.quality());
+                self.adaptive = Some(adaptive);
+                Ok(format!(
+                    "lowering quality while frames take the GPU over {budget} ms"
+                ))
+            }
+            Command::Lod(zoom) => {
+                self.lod_zoom = zoom;
+                Ok(match zoom {
+                    Some(zoom) => format!("less detail while zoomed out past {zoom}"),
+                    None => "full detail at any zoom".into(),
+                })
+            }
+            Command::TickRate(rate) => {
+                self.ticker = rate.map(Ticker::new);
+                Ok(match rate {
+                    Some(rate) => format!("ticking at {rate} Hz"),
+                    None => "ticking once per frame".into(),
+                })
+            }
+            Command::Units(units) => {
+                let msg = format!("1 world unit = {}", units.format(1.0));
+                self.units = units;
+                Ok(msg)
+            }
+            Command::Lang(path) => {
+                self.strings = match &path {
+                    None => Catalog::english(),
+                    Some(path) => load_catalog(path)?,
+                };
+                self.prefs.lang = path;
+                self.save_prefs();
+                Ok(format!("language: {}", self.strings.lang))
+            }
+            Command::Vsync(vsync) => {
+                self.config.present_mode = present_mode(vsync);
+                self.surface.configure(&self.device, &self.config);
+                self.prefs.vsync = vsync;
+                self.save_prefs();
+                Ok(format!("vsync {}", if vsync { "on" } else { "off" }))
+            }
+            Command::Background(color) => {
+                self.prefs.background = color;
+                self.save_prefs();
+                Ok(format!(
+                    "background {}",
+                    color::to_hex(self.prefs.background())
+                ))
+            }
+            Command::Bind { key, acts_as } => {
+                let msg = match acts_as {
+                    Some(acts_as) => {
+                        let shortcut = input::shortcut(acts_as)
+                            .ok_or_else(|| format!("{acts_as} isn't bound to anything"))?;
+                        self.prefs.keys.insert(key, acts_as);
+                        format!("{key} now does what {acts_as} does ({shortcut:?})")
+                    }
+                    None => {
+                        self.prefs
+                            .keys
+                            .remove(&key)
+                            .ok_or_else(|| format!("{key} isn't rebound"))?;
+                        format!("{key} is back to its own binding")
+                    }
+                };
+                self.save_prefs();
+                Ok(msg)
+            }
+            Command::Prefs { reset } => {
+                if reset {
+                    self.prefs = Preferences::default();
+                    self.strings = Catalog::english();
+                    self.config.present_mode = present_mode(self.prefs.vsync);
+                    self.surface.configure(&self.device, &self.config);
+                    self.save_prefs();
+                }
+                Ok(self.prefs.to_json())
+            }
+            Command::Clear => {
+                self.path = None;
+                self.components = None;
+                self.mst = None;
+                Ok("cleared".into())
+            }
+        }
+    }
+
+    /// Resolves a command's scope into the nodes it covers
+    fn scope_nodes(&self, scope: &Scope) -> Result<BTreeSet<u32>, String> {
+        let nodes = &self.node_render_pass.nodes;
+        let seeds =
+            match scope {
+                Scope::All => return Ok((0..nodes.len() as u32).collect()),
+                Scope::Selected => self.selection.clone().ok_or("nothing selected")?,
+                Scope::Component(key) => BTreeSet::from([console::resolve_node(nodes, key)
+                    .ok_or_else(|| format!("no such node: {key}"))?]),
+            };
+        Ok(algorithms::connected_component(
+            nodes.len(),
+            &self.edge_render_pass.edges,
+            seeds,
+        ))
+    }
+
+    /// Switches the running simulation over to `preset`, keeping the other
+    /// force parameters
+    fn use_preset(&mut self, preset: Preset) -> String {
+        self.preset = preset;
+        self.physics.set_params(preset.apply(self.physics.params));
+        self.ticker = Some(Ticker::new(preset.tick_rate));
+        self.physics.reheat();
+        format!("preset {preset}")
+    }
+
+    /// Makes `node` the root of the radial layout, working out how far every
+    /// node is from it. Has to run again whenever edges change.
+    fn set_anchor(&mut self, node: Option<u32>) {
+        self.physics.anchor = node.map(|node| {
+            Anchor::new(
+                node,
+                self.node_render_pass.nodes.len(),
+                &self.edge_render_pass.edges,
+            )
+        });
+    }
+
+    /// Reads the partition's sides again after nodes changed, and drops it
+    /// once its attribute doesn't split them in two anymore
+    fn refresh_partition(&mut self) {
+        let Some(partition) = self.physics.partition.take() else {
+            return;
+        };
+        match Partition::new(
+            &self.node_render_pass.nodes,
+            &partition.attr,
+            partition.shape,
+        ) {
+            Ok(partition) => self.physics.partition = Some(partition),
+            Err(e) => log::info!("dropping bipartite forces: {e}"),
+        }
+    }
+
+    /// Returns how many frozen nodes were released
+    fn unfreeze(&mut self, scope: &Scope) -> Result<usize, String> {
+        let nodes = self.scope_nodes(scope)?;
+        let before = self.physics.frozen.len();
+        self.physics.frozen.retain(|i| !nodes.contains(i));
+        Ok(before - self.physics.frozen.len())
+    }
+
+    /// Runs queued console commands, and on the web opens the files picked
+    /// since, returns whether there were any
+    pub fn process_console(&mut self) -> bool {
+        let lines = self.console.poll();
+        for line in &lines {
+            let done = Command::parse(line).and_then(|command| self.run_command(command));
+            self.report(done);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if self.open_picked_files() {
+            return true;
+        }
+        self.sync_shared() || !lines.is_empty()
+    }
+
+    /// Shows what a command or shortcut did, or why it failed, in the status
+    /// bar, and logs it for when the bar is hidden or the reply is longer
+    /// than a line
+    pub fn report(&mut self, done: Result<String, String>) {
+        let msg = match done {
+            Ok(msg) => {
+                log::info!("{msg}");
+                msg
+            }
+            Err(err) => {
+                log::warn!("{err}");
+                self.strings.format("status-error", &[("error", &err)])
+            }
+        };
+        let mut lines = msg.lines();
+        let first = lines.next().unwrap_or_default().to_string();
+        self.message = Some(match lines.next() {
+            Some(_) => first + " …",
+            None => first,
+        });
+    }
+
+    /// A handle other threads can read the graph through and queue batches
+    /// on. Those are applied between frames like any other batch, undo
+    /// included. The graph is published to readers after them, and while
+    /// the layout moves at most every `PUBLISH_INTERVAL`.
+    pub fn graph_handle(&mut self) -> GraphHandle {
+        let graph = || Graph {
+            nodes: self.node_render_pass.nodes.clone(),
+            edges: self.edge_render_pass.edges.clone(),
+        };
+        self.shared
+            .get_or_insert_with(|| GraphHandle::new(graph()))
+            .clone()
+    }
+
+    /// Applies the batches queued on `graph_handle` and publishes the graph
+    /// if it's due, returns whether there were any batches
+    fn sync_shared(&mut self) -> bool {
+        let Some(handle) = self.shared.clone() else {
+            return false;
+        };
+        let pending = handle.drain();
+        let applied = !pending.is_empty();
+        for batch in pending {
+            // Rejections go back to whoever submitted the batch
+            batch.run(|mutation| self.apply_batch(mutation));
+        }
+        let due = applied
+            || !self.is_animating()
+            || self
+                .published
+                .is_none_or(|at| at.elapsed() >= PUBLISH_INTERVAL);
+        if (applied || self.shared_stale)
+            && due
+            && handle.publish(&self.node_render_pass.nodes, &self.edge_render_pass.edges)
+        {
+            self.published = Some(Instant::now());
+            self.shared_stale = false;
+        }
+        applied
+    }
+
+    /// Opens the files Ctrl+O's chooser has read, returns whether there were
+    /// any
+    #[cfg(target_arch = "wasm32")]
+    fn open_picked_files(&mut self) -> bool {
+        let files = self.file_picker.poll();
+        for file in &files {
+            let done = if session::is_session(&file.contents) {
+                Session::parse(&file.contents)
+                    .map_err(|e| format!("{e:#}"))
+                    .and_then(|session| self.restore_session(session))
+                    .map(|()| format!("loaded the session from {}", file.name))
+            } else {
+                self.load_source(&file.name, &file.contents)
+            };
+            self.report(done);
+        }
+        !files.is_empty()
+    }
+
+    /// Whether the next frame would differ from the last one without any new
+    /// input, i.e. nodes are still moving or something is animating. When it
+    /// isn't, the event loop stops redrawing until an event arrives.
+    pub fn is_animating(&self) -> bool {
+        if self.suspended {
+            return false;
+        }
+        let simulating = !self.paused
+            && !self.physics.is_settled()
+            && self.physics.frozen.len() < self.physics.objs.len();
+        simulating
+            || self.transition.is_some()
+            || (self.physics.magnet.is_some() && !self.paused)
+            || self.input.gesture != Gesture::Idle
+            // The trail shrinks away after a drag
+            || !self.trail.is_empty()
+            || !self.node_render_pass.pulsing.is_empty()
+            || self.node_render_pass.images_loading()
+    }
+
+    /// Works out `components` and `mst` again for the graph as it is now
+    fn refresh_analysis(&mut self) {
+        let node_count = self.node_render_pass.nodes.len();
+        if let Some(components) = &mut self.components {
+            *components = algorithms::cyclic_components(node_count, &self.edge_render_pass.edges);
+        }
+        if let Some(mut mst) = self.mst.take() {
+            self.update_mst(&mut mst);
+            self.mst = Some(mst);
+        }
+    }
+
+    fn update_mst(&self, mst: &mut MstView) {
+        let edges = &self.edge_render_pass.edges;
+        mst.edges = algorithms::minimum_spanning_forest(
+            self.node_render_pass.nodes.len(),
+            edges,
+            &mst.weight,
+        );
+        mst.edge_map = algorithms::incidence_map(edges, mst.edges.iter().copied());
+    }
+
+    fn refresh_hulls(&mut self) {
+        self.hull_render_pass.hulls.clear();
+
+        if let Some(components) = &self.components {
+            let nodes = &self.node_render_pass.nodes;
+            self.hull_render_pass.hulls = components
+                .iter()
+                .zip(ColorGenerator::new())
+                .map(|(component, mut color)| {
+                    color.w = 0.25;
+                    let radius = component
+                        .iter()
+                        .map(|&i| nodes[i as usize].size.x)
+                        .fold(0.0, f32::max);
+                    Hull::around(
+                        component
+                            .iter()
+                            .map(|&i| nodes[i as usize].position.truncate()),
+                        radius,
+                        radius * 0.5,
+                        color,
+                    )
+                })
+                .collect();
+        }
+
+        self.hull_render_pass.write(&self.queue);
+    }
+
+    /// Brings templated labels up to date with the graph as one batch, see
+    /// `TemplatedLabels::relabel`. Returns the step for the history, which
+    /// is up to the caller.
+    fn relabel(&mut self, all: bool) -> Result<Step, String> {
+        self.labels_stale = false;
+        let Some(labels) = &mut self.label_template else {
+            return Ok(Step::default());
+        };
+        let mutation = GraphMutation {
+            update_nodes: labels.relabel(
+                &self.node_render_pass.nodes,
+                &self.edge_render_pass.edges,
+                all,
+            ),
+            ..Default::default()
+        };
+        let step = Step::of(self, &mutation);
+        self.batch(mutation)?;
+        self.labels_stale = false;
+        Ok(step)
+    }
+
+    /// Applies the style rules to the nodes, returning the scales they used
+    fn apply_styles(&mut self) -> Vec<(StyleRule, Scale)> {
+        self.styles
+            .iter()
+            .filter_map(|rule| {
+                rule.apply(
+                    &mut self.node_render_pass.nodes,
+                    &mut self.edge_render_pass.edges,
+                )
+                .map(|scale| (rule.clone(), scale))
+            })
+            .collect()
+    }
+
+    fn refresh_overlay(&mut self, scales: &[(StyleRule, Scale)]) {
+        self.overlay_render_pass.clear();
+        self.node_render_pass.selected = self.selection.clone().unwrap_or_default();
+        self.node_render_pass.pinned = self.physics.pinned();
+        let (w, h) = self.widget_area();
+
+        if let Some(legend) = &self.legend {
+            self.overlay_render_pass
+                .extend(legend.rects(w, h, scales, &self.strings));
+        }
+
+        if let Some(histogram) = &mut self.histogram {
+            histogram.update(
+                &self.node_render_pass.nodes,
+                &self.edge_render_pass.edge_map,
+            );
+            self.overlay_render_pass.extend(histogram.rects(w, h));
+        }
+
+        if let Some(timeline) = &mut self.timeline {
+            timeline.update(&self.node_render_pass.nodes, &self.edge_render_pass.edges);
+            let hidden_nodes = timeline.hidden_nodes(&self.node_render_pass.nodes);
+            self.edge_render_pass.hidden =
+                timeline.hidden_edges(&self.edge_render_pass.edges, &hidden_nodes);
+            self.node_render_pass.hidden = hidden_nodes;
+            self.overlay_render_pass.extend(timeline.rects(w, h));
+        }
+
+        if let Some(sample) = &mut self.sample_view {
+            sample.update(
+                self.node_render_pass.nodes.len(),
+                &self.edge_render_pass.edges,
+            );
+            // The timeline has just worked out what it hides, otherwise
+            // start over only when the sample or zoom changed
+            let redraw = sample.redraw(self.camera.scale);
+            if self.timeline.is_none() && redraw {
+                self.node_render_pass.hidden.clear();
+                self.edge_render_pass.hidden.clear();
+            }
+            if (self.timeline.is_some() || redraw) && !sample.is_full(self.camera.scale) {
+                let (hidden_nodes, hidden_edges) = sample.hidden();
+                self.node_render_pass.hidden.extend(hidden_nodes);
+                self.edge_render_pass.hidden.extend(hidden_edges);
+            }
+        }
+
+        if let Some(ruler) = &self.ruler {
+            let zoom = self.camera.scale / self.scale_factor as f32;
+            self.overlay_render_pass
+                .extend(ruler.rects(w, h, zoom, &self.units));
+        }
+
+        if let Some((stats, elapsed)) = &self.tree_stats {
+            let strings = &self.strings;
+            let lines = [
+                strings.format(
+                    "quadtree-nodes",
+                    &[("count", &self.node_render_pass.nodes.len())],
+                ),
+                strings.format("quadtree-cells", &[("count", &stats.cells)]),
+                strings.format("quadtree-leaves", &[("count", &stats.leaves)]),
+                strings.format("quadtree-depth", &[("depth", &stats.depth)]),
+                strings.format(
+                    "quadtree-per-leaf",
+                    &[
+                        ("mean", &format!("{:.2}", stats.mean_per_leaf)),
+                        ("max", &stats.max_per_leaf),
+                    ],
+                ),
+                strings.format("quadtree-crowded", &[("count", &stats.crowded)]),
+                strings.format(
+                    match self.preset_override {
+                        Some(_) => "stats-preset-chosen",
+                        None => "stats-preset-auto",
+                    },
+                    &[
+                        ("name", &self.preset.name),
+                        ("solver", &self.physics.params.repulsion),
+                        ("passes", &self.physics.params.collision_iterations),
+                        (
+                            "rate",
+                            &self
+                                .ticker
+                                .map_or("per frame".into(), |ticker| format!("{} Hz", ticker.rate)),
+                        ),
+                    ],
+                ),
+                strings.format(
+                    "quadtree-build",
+                    &[("ms", &format!("{:.3}", elapsed.as_secs_f64() * 1000.0))],
+                ),
+                self.gpu_stats(),
+                self.quality_stats(),
+            ];
+            self.overlay_render_pass.extend(StatsPanel::default().rects(
+                w,
+                h,
+                strings.get("quadtree-title"),
+                &lines,
+            ));
+        }
+
+        if let Some((file, err)) = &self.shader_error {
+            let lines: Vec<String> = err
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .take(SHADER_ERROR_LINES)
+                .map(|line| line.chars().take(SHADER_ERROR_WIDTH).collect())
+                .collect();
+            let panel = StatsPanel {
+                anchor: overlay::Anchor::BottomLeft,
+                ..Default::default()
+            };
+            self.overlay_render_pass.extend(panel.rects(
+                w,
+                h,
+                &self.strings.format("shader-error", &[("file", file)]),
+                &lines,
+            ));
+        }
+
+        if let Some(status_bar) = &self.status_bar {
+            let (w, h) = self.ui_size();
+            let items = self.status_items();
+            self.overlay_render_pass
+                .extend(status_bar.rects(w, h, &items));
+        }
+
+        self.overlay_render_pass.extend(self.gesture_rects());
+        if let Some(menu) = &self.context_menu {
+            let pointer = self.mouse.pos.map(|pos| self.to_ui(&pos));
+            self.overlay_render_pass
+                .extend(menu.rects(pointer, &self.strings));
+        }
+        if let Some(search) = &self.search {
+            self.overlay_render_pass
+                .extend(search.rects(w, &self.node_render_pass.nodes));
+        }
+        if let Some(palette) = &self.palette {
+            self.overlay_render_pass
+                .extend(palette.rects(w, &self.prefs.recent_files));
+        }
+        if let Some(help) = &self.help {
+            let (w, h) = self.ui_size();
+            self.overlay_render_pass
+                .extend(help.rects(w, h, &self.strings));
+        }
+        // Widgets are laid out in logical pixels so they keep their size on
+        // high DPI monitors
+        self.overlay_render_pass.scale(self.scale_factor as f32);
+
+        // A hovered node takes precedence over a histogram brush, which takes
+        // precedence over a selection, which takes precedence over the
+        // highlighted path
+        let hover = self.hover_neighborhood();
+        (self.node_render_pass.glowing, self.edge_render_pass.glowing) =
+            hover.clone().unwrap_or_default();
+        if let Some((nodes, edges)) = hover {
+            self.node_render_pass.highlight = Some(nodes);
+            self.edge_render_pass.highlight = Some(edges);
+        } else {
+            let brushed = self
+                .histogram
+                .as_ref()
+                .and_then(HistogramChart::selection)
+                .or_else(|| self.selection.clone());
+            let path = self.path.as_ref().filter(|_| brushed.is_none());
+            self.node_render_pass.highlight =
+                brushed.or_else(|| path.map(|p| p.nodes.iter().copied().collect()));
+            self.edge_render_pass.highlight = path
+                .map(|p| p.edges.iter().copied().collect())
+                .or_else(|| self.mst.as_ref().map(|m| m.edges.iter().copied().collect()));
+        }
+
+        self.overlay_render_pass.write(&self.queue);
+    }
+
+    /// The hovered node with its neighbors, and the edges touching it, left
+    /// out while a gesture is under way
+    fn hover_neighborhood(&self) -> Option<(BTreeSet<u32>, BTreeSet<u32>)> {
+        let node = self
+            .hovered
+            .filter(|_| self.input.gesture == Gesture::Idle)?;
+        let edges = &self.edge_render_pass.edges;
+        let incident: BTreeSet<u32> = self
+            .edge_render_pass
+            .edge_map
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|i| !self.edge_render_pass.hidden.contains(i))
+            .collect();
+        let mut nodes = BTreeSet::from([node]);
+        for i in &incident {
+            let edge = &edges[*i as usize];
+            nodes.extend([edge.a_id, edge.b_id]);
+        }
+        Some((nodes, incident))
+    }
+
+    /// Window size in logical pixels, what overlay widgets are laid out in
+    fn ui_size(&self) -> (f32, f32) {
+        let factor = self.scale_factor as f32;
+        (self.camera.width / factor, self.camera.height / factor)
+    }
+
+    /// Part of the window anchored widgets are laid out in, which excludes the
+    /// status bar
+    fn widget_area(&self) -> (f32, f32) {
+        let (w, h) = self.ui_size();
+        let bar = self.status_bar.as_ref().map_or(0.0, StatusBar::height);
+        (w, h - bar)
+    }
+
+    /// GPU time per part of the frame for the stats panel, with the MSAA
+    /// samples it's drawn with
+    fn gpu_stats(&self) -> String {
+        let strings = &self.strings;
+        let samples = self.settings.sample_count;
+        let Some(timer) = &self.gpu_timer else {
+            return strings.format("stats-gpu-unsupported", &[("samples", &samples)]);
+        };
+        let ms = |ms: f32| format!("{ms:.2}");
+        match timer.timings {
+            None => strings.format("stats-gpu-measuring", &[("samples", &samples)]),
+            Some(GpuTimings {
+                total,
+                phases: None,
+            }) => strings.format(
+                "stats-gpu-total",
+                &[("ms", &ms(total)), ("samples", &samples)],
+            ),
+            Some(GpuTimings {
+                total,
+                phases: Some(phases),
+            }) => {
+                let (total, phases) = (ms(total), phases.map(ms));
+                let mut args: Vec<(&str, &dyn std::fmt::Display)> =
+                    vec![("ms", &total), ("samples", &samples)];
+                args.extend(
+                    PHASES
+                        .iter()
+                        .zip(&phases)
+                        .map(|(name, ms)| (*name, ms as _)),
+                );
+                strings.format("stats-gpu", &args)
+            }
+        }
+    }
+
+    /// How much of the full MSAA and resolution frames are drawn with, and
+    /// the budget keeping them there
+    fn quality_stats(&self) -> String {
+        let samples = self.settings.sample_count;
+        let percent = (self.render_scale * 100.0).round();
+        match &self.adaptive {
+            Some(adaptive) => self.strings.format(
+                "stats-quality-adaptive",
+                &[
+                    ("samples", &samples),
+                    ("percent", &percent),
+                    ("budget", &adaptive.budget),
+                ],
+            ),
+            None => self.strings.format(
+                "stats-quality-fixed",
+                &[("samples", &samples), ("percent", &percent)],
+            ),
+        }
+    }
+
+    /// Mode, cursor position, selection size, simulation state and the last
+    /// message
+    fn status_items(&self) -> Vec<String> {
+        let cursor = match self.mouse.pos {
+            Some(pos) => {
+                let p = self.camera.screen_to_world(pos);
+                format!(
+                    "{}, {} {}",
+                    format_amount(self.units.from_world(p.x)),
+                    format_amount(self.units.from_world(p.y)),
+                    self.units.name
+                )
+            }
+            None => "-".into(),
+        };
+        let selected = self.selection.as_ref().map_or(0, BTreeSet::len);
+        let objs = &self.physics.objs;
+        let simulation = if self.paused {
+            self.strings.get("status-paused").to_string()
+        } else if !objs.is_empty() && self.physics.frozen.len() >= objs.len() {
+            self.strings.get("status-frozen").to_string()
+        } else if self.physics.is_settled() {
+            self.strings.get("status-settled").to_string()
+        } else {
+            self.strings.format(
+                "status-running",
+                &[("alpha", &format!("{:.3}", self.physics.alpha))],
+            )
+        };
+        let mut items = vec![
+            self.strings
+                .get(&format!("mode-{}", self.input.mode()))
+                .to_string(),
+            cursor,
+            self.strings
+                .format("status-selected", &[("count", &selected)]),
+            simulation,
+        ];
+        items.extend(self.message.clone());
+        items
+    }
+
+    /// Converts a mouse position to the overlay's logical pixel space
+    fn to_ui(&self, pos: &cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let (w, h) = self.ui_size();
+        mouse_to_overlay_space(w, h, &(pos / self.scale_factor as f32))
+    }
+
+    /// Outline of the box or lasso being drawn
+    fn gesture_rects(&self) -> Vec<Rect> {
+        match &self.input.gesture {
+            Gesture::BoxSelecting { start, end } => {
+                let a = self.to_ui(start);
+                let b = self.to_ui(end);
+                let min = vec2(a.x.min(b.x), a.y.min(b.y));
+                let size = vec2((a.x - b.x).abs(), (a.y - b.y).abs());
+                let s = SELECTION_STROKE;
+                vec![
+                    Rect::new(min, size, SELECTION_FILL),
+                    Rect::new(min, (size.x, s), SELECTION_COLOR),
+                    Rect::new(min + vec2(0.0, size.y - s), (size.x, s), SELECTION_COLOR),
+                    Rect::new(min, (s, size.y), SELECTION_COLOR),
+                    Rect::new(min + vec2(size.x - s, 0.0), (s, size.y), SELECTION_COLOR),
+                ]
+            }
+            Gesture::Lasso(points) => points
+                .iter()
+                .map(|p| {
+                    let p = self.to_ui(p);
+                    let s = SELECTION_STROKE;
+                    Rect::new(p - vec2(s, s) / 2.0, (s, s), SELECTION_COLOR)
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Edge being drawn or reconnected, from its fixed end to the node under
+    /// the pointer or the pointer itself, red while releasing there wouldn't
+    /// create it
+    fn ghost_edge(
+        &self,
+    ) -> Option<(
+        cgmath::Vector2<f32>,
+        cgmath::Vector2<f32>,
+        cgmath::Vector4<f32>,
+    )> {
+        let (from, end) = match self.input.gesture {
+            Gesture::CreatingEdge(from) => (from, None),
+            Gesture::ReconnectingEdge(end) => (
+                self.edge_render_pass.edges[end.edge as usize].other(end.node),
+                Some(end),
+            ),
+            _ => return None,
+        };
+        let valid = |to| match end {
+            None => self.check_edge(from, to).is_ok(),
+            Some(end) => to == end.node || self.reconnected(end, to).is_ok(),
+        };
+        let pos = self.mouse.pos?;
+        let start = self.position(from)?;
+        let pointer = self.camera.screen_to_world(pos);
+        Some(match self.node_at(pos) {
+            Some(to) if !valid(to) => (start, pointer, INVALID_EDGE_COLOR),
+            Some(to) => (start, self.position(to)?, GHOST_EDGE_COLOR),
+            None => (start, pointer, GHOST_EDGE_COLOR),
+        })
+    }
+
+    /// A zero size (minimized window, collapsed canvas) suspends rendering,
+    /// any other size resumes it
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width == 0 || new_size.height == 0 {
+            self.suspend();
+        } else {
+            self.suspended = false;
+            self.size = new_size;
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(&self.device, &self.config);
+            self.create_targets();
+            self.camera
+                .resize(new_size.width as f32, new_size.height as f32, &self.queue);
+        }
+    }
+
+    /// Stops updating and rendering until the next non-zero `resize`. The
+    /// surface is left configured at its last size, it's reconfigured on
+    /// resume.
+    pub fn suspend(&mut self) {
+        if self.suspended {
+            return;
+        }
+        self.suspended = true;
+        self.input.cancel();
+        // Time spent away shouldn't be ticked through on resume
+        self.last_frame = None;
+    }
+
+    /// The window moved to a monitor with a different scale factor. The zoom
+    /// is adjusted so the graph keeps its apparent size, `resize` should
+    /// follow with the new physical size.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        let ratio = (scale_factor / self.scale_factor) as f32;
+        self.scale_factor = scale_factor;
+        let center = vec2(0.0, 0.0);
+        self.camera
+            .update_scale(&self.queue, self.camera.scale * ratio, Some(center));
+    }
+
+    /// Rebuilds the drag trail, the edge being drawn and force arrows. Once
+    /// the drag ends the trail shrinks from its oldest end until it's gone.
+    fn refresh_debug(&mut self) {
+        let dragged = self.input.dragged_node().filter(|_| self.show_trail);
+        match dragged {
+            Some(node) => {
+                let position = self.node_render_pass.nodes[node as usize].position;
+                self.trail.push_back(position.truncate());
+                if self.trail.len() > TRAIL_LENGTH {
+                    self.trail.pop_front();
+                }
+            }
+            None => {
+                self.trail.pop_front();
+            }
+        }
+
+        let ghost = self.ghost_edge();
+        let pass = &mut self.debug_render_pass;
+        pass.clear();
+        let width = DEBUG_STROKE * self.scale_factor as f32 / self.camera.scale;
+        if let Some((from, to, color)) = ghost {
+            pass.arrow(from, to, width, color);
+        }
+        let count = self.trail.len();
+        for (i, (a, b)) in self.trail.iter().zip(self.trail.iter().skip(1)).enumerate() {
+            // Older segments fade out
+            let mut color = TRAIL_COLOR;
+            color.w *= (i + 1) as f32 / count as f32;
+            pass.line(*a, *b, width, color);
+        }
+
+        self.tree_stats = None;
+        if self.show_forces || self.show_tree {
+            let positions: Vec<_> = self
+                .node_render_pass
+                .nodes
+                .iter()
+                .map(|n| n.position.truncate())
+                .collect();
+            let start = Instant::now();
+            let tree = Quadtree::build(&positions);
+            let elapsed = start.elapsed();
+            for cell in &tree.cells {
+                let max = cell.min + vec2(cell.size, cell.size);
+                self.debug_render_pass
+                    .rect(cell.min, max, width, CELL_COLOR);
+            }
+            if self.show_tree {
+                self.tree_stats = Some((tree.stats(), elapsed));
+            }
+        }
+        if self.show_forces {
+            self.draw_forces(width);
+        }
+
+        if let Some(node) = dragged {
+            let edges = &self.edge_render_pass.edges;
+            let links = link_map(&self.mst, &self.edge_render_pass.edge_map);
+            for node in std::iter::once(node).chain(self.neighbors(node)) {
+                let (repulsion, attraction) = self.physics.forces(node, edges, links);
+                let center = self.node_render_pass.nodes[node as usize]
+                    .position
+                    .truncate();
+                for (force, color) in [(repulsion, REPULSION_COLOR), (attraction, ATTRACTION_COLOR)]
+                {
+                    self.debug_render_pass.arrow(
+                        center,
+                        center + force * FORCE_ARROW_SCALE,
+                        width,
+                        color,
+                    );
+                }
+            }
+        }
+        self.debug_render_pass.write(&self.queue);
+    }
+
+    /// Node radii and the net force on every node
+    fn draw_forces(&mut self, width: f32) {
+        let nodes = &self.node_render_pass.nodes;
+        let pass = &mut self.debug_render_pass;
+        let edges = &self.edge_render_pass.edges;
+        let links = link_map(&self.mst, &self.edge_render_pass.edge_map);
+        for (i, node) in nodes.iter().enumerate() {
+            if self.node_render_pass.hidden.contains(&(i as u32)) {
+                continue;
+            }
+            let center = node.position.truncate();
+            let (repulsion, attraction) = self.physics.forces(i as u32, edges, links);
+            pass.circle(center, node.radius(), width, RADIUS_COLOR);
+            pass.arrow(
+                center,
+                center + (repulsion + attraction) * FORCE_ARROW_SCALE,
+                width,
+                NET_FORCE_COLOR,
+            );
+        }
+    }
+
+    /// Moves the nodes one frame further along a snapshot restore
+    fn step_transition(&mut self) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        match transition.step() {
+            Some(positions) => {
+                for (obj, p) in self.physics.objs.iter_mut().zip(positions) {
+                    obj.x = p.x;
+                    obj.y = p.y;
+                    (obj.vx, obj.vy, obj.vz) = (0.0, 0.0, 0.0);
+                }
+            }
+            None => self.transition = None,
+        }
+    }
+
+    /// Follows the dragged node, and throws it with the speed it had once
+    /// it's let go
+    fn track_throw(&mut self, elapsed: f32) {
+        if let Some(node) = self.input.dragged_node() {
+            if self.observed.dragged != Some(node) {
+                self.drag_velocity.clear();
+            }
+            let position = self.node_render_pass.nodes[node as usize].position;
+            self.drag_velocity.push(elapsed, position.truncate());
+            return;
+        }
+        // Released since the last frame
+        let Some(node) = self.observed.dragged else {
+            return;
+        };
+        let velocity = self.drag_velocity.velocity();
+        self.drag_velocity.clear();
+        if node as usize >= self.physics.objs.len() {
+            return;
+        }
+        // One tick per frame without a ticker, at the 60 frames per second
+        // `frame_time` assumes when it can't tell
+        let tick_rate = self.ticker.map_or(60.0, |ticker| ticker.rate);
+        let neighborhood = algorithms::neighborhood(&self.edge_render_pass.edges, node, THROW_HOPS);
+        self.physics.throw(node, velocity / tick_rate, neighborhood);
+    }
+
+    /// Seconds since the previous update, assuming 60 frames per second for
+    /// the first one
+    fn frame_time(&mut self) -> f32 {
+        let now = Instant::now();
+        match self.last_frame.replace(now) {
+            Some(last) => (now - last).as_secs_f32(),
+            None => 1.0 / 60.0,
+        }
+    }
+
+    /// Rebuilds the pipelines drawn with shaders edited since the last
+    /// frame. A shader that doesn't compile leaves the pipeline as it was
+    /// and its error on screen.
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
+        for (file, source) in watcher.changed() {
+            self.shader_sources.insert(file.clone(), source);
+            // A snippet is rebuilt as every shader including it
+            let mut rebuilt: Vec<(String, String)> = self
+                .shader_sources
+                .iter()
+                .filter(|(_, source)| shader::includes(source, &file))
+                .map(|(file, source)| (file.clone(), source.clone()))
+                .collect();
+            if rebuilt.is_empty() {
+                rebuilt.push((file.clone(), self.shader_sources[&file].clone()));
+            }
+            for (file, source) in rebuilt {
+                match self.rebuild_pipelines(&file, &source) {
+                    None => log::info!("{file} changed, but no pipeline uses it"),
+                    Some(Ok(())) => {
+                        log::info!("reloaded {file}");
+                        self.shader_error = None;
+                    }
+                    Some(Err(err)) => {
+                        log::warn!("{file} didn't compile, keeping the last version: {err}");
+                        self.shader_error = Some((file, err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rebuilds every pipeline drawn with the shader `file` from `source`,
+    /// with the current `settings` and snippets. `None` when no pipeline
+    /// uses `file`.
+    fn rebuild_pipelines(&mut self, file: &str, source: &str) -> Option<Result<(), String>> {
+        let source = match shader::expand(source, |file| {
+            self.shader_sources.get(file).map(String::as_str)
+        }) {
+            Ok(source) => source,
+            Err(err) => return Some(Err(err)),
+        };
+        let source = source.as_str();
+        let (device, format, camera, settings) = (
+            &self.device,
+            self.config.format,
+            &self.camera_bind_group_layout,
+            self.settings,
+        );
+        let rebuild =
+            |build: &dyn Fn() -> wgpu::RenderPipeline| hot_reload::catch_validation(device, build);
+        Some(match file {
+            "node.wgsl" => rebuild(&|| {
+                let atlas = &self.node_render_pass.atlas_layout;
+                NodeRenderPass::create_pipeline(device, format, camera, atlas, settings, source)
+            })
+            .map(|pipeline| self.node_render_pass.pipeline = pipeline),
+            // Markers and lines are drawn by more pipelines from the same
+            // shader
+            "edge.wgsl" => rebuild(&|| {
+                EdgeRenderPass::create_pipeline(device, format, camera, settings, source)
+            })
+            .and_then(|pipeline| {
+                let markers = rebuild(&|| {
+                    EdgeRenderPass::create_marker_pipeline(device, format, camera, settings, source)
+                })?;
+                let lines = rebuild(&|| {
+                    EdgeRenderPass::create_line_pipeline(device, format, camera, settings, source)
+                })?;
+                self.edge_render_pass.pipeline = pipeline;
+                self.edge_render_pass.marker_pipeline = markers;
+                self.edge_render_pass.line_pipeline = lines;
+                Ok(())
+            }),
+            "hull.wgsl" => rebuild(&|| {
+                HullRenderPass::create_pipeline(device, format, camera, settings, source)
+            })
+            .map(|pipeline| self.hull_render_pass.pipeline = pipeline),
+            "overlay.wgsl" => {
+                rebuild(&|| OverlayRenderPass::create_pipeline(device, format, camera, source))
+                    .map(|pipeline| self.overlay_render_pass.pipeline = pipeline)
+            }
+            "label.wgsl" => rebuild(&|| {
+                let atlas = &self.label_render_pass.atlas_layout;
+                LabelRenderPass::create_pipeline(device, format, camera, atlas, settings, source)
+            })
+            .map(|pipeline| self.label_render_pass.pipeline = pipeline),
+            // Debug shapes are drawn like annotations
+            "annotation.wgsl" => rebuild(&|| {
+                AnnotationRenderPass::create_pipeline(device, format, camera, settings, source)
+            })
+            .and_then(|annotation| {
+                let debug = rebuild(&|| {
+                    DebugRenderPass::create_pipeline(device, format, camera, settings, source)
+                })?;
+                self.annotation_render_pass.pipeline = annotation;
+                self.debug_render_pass.pipeline = debug;
+                Ok(())
+            }),
+            "upscale.wgsl" => rebuild(&|| {
+                let layout = &self.upscale_pass.layout;
+                UpscalePass::create_pipeline(device, format, layout, source)
+            })
+            .map(|pipeline| self.upscale_pass.pipeline = pipeline),
+            _ => return None,
+        })
+    }
+
+    pub fn update(&mut self) {
+        self.process_console();
+        self.reload_shaders();
+
+        if std::mem::take(&mut self.analysis_stale) {
+            self.refresh_analysis();
+        }
+        let elapsed = self.frame_time();
+        if !self.node_render_pass.pulsing.is_empty() {
+            let time = (self.camera.time + elapsed) % ANIMATION_CLOCK_WRAP;
+            self.camera.set_time(&self.queue, time);
+        }
+        self.track_throw(elapsed);
+        self.step_transition();
+        let (ticks, t) = match &mut self.ticker {
+            Some(ticker) => (ticker.advance(elapsed), ticker.fraction()),
+            None => (1, 1.0),
+        };
+        self.physics.magnet = self.magnet();
+        self.physics.region = self.region_margin.map(|margin| self.view_region(margin));
+        let held = self.held_nodes(self.input.dragged_node());
+        let tick_time = Stopwatch::start();
+        let t = if self.transition.is_none() && !self.paused {
+            for _ in 0..ticks {
+                self.physics.tick(
+                    &held,
+                    &self.edge_render_pass.edges,
+                    link_map(&self.mst, &self.edge_render_pass.edge_map),
+                );
+            }
+            t
+        } else {
+            self.physics.clear_step();
+            1.0
+        };
+        let tick_ms = tick_time.ms();
+        self.physics.apply_interpolated(
+            t,
+            self.node_render_pass.nodes.as_mut_slice(),
+            &mut self.edge_render_pass.edges,
+            &self.edge_render_pass.edge_map,
+        );
+        self.node_grid.sync(&self.node_render_pass.nodes);
+        self.refresh_hulls();
+        self.annotation_render_pass.write(&self.queue);
+        self.refresh_debug();
+        let degraded = self.limits.past_soft(
+            self.node_render_pass.nodes.len(),
+            self.edge_render_pass.edges.len(),
+        );
+        let far = self.lod_zoom.is_some_and(|zoom| self.camera.scale < zoom);
+        self.label_render_pass.degraded = degraded || far;
+        self.edge_render_pass.degraded = degraded;
+        self.edge_render_pass.thin = far;
+        self.node_render_pass.plain = far;
+        if self.labels_stale {
+            // Captions follow from the edit that outdated them and undo with it
+            match self.relabel(false) {
+                Ok(step) => self.history.amend(step),
+                Err(err) => log::warn!("can't relabel nodes: {err}"),
+            }
+        }
+        let scales = self.apply_styles();
+        self.physics.resize(&self.node_render_pass.nodes);
+        edge::fit_markers(
+            &mut self.edge_render_pass.edges,
+            &self.node_render_pass.nodes,
+        );
+        self.refresh_overlay(&scales);
+        let upload_time = Stopwatch::start();
+        self.label_render_pass.update(
+            &self.device,
+            &self.queue,
+            &self.node_render_pass.nodes,
+            &self.node_render_pass.hidden,
+            self.node_render_pass.highlight.as_ref(),
+            self.camera.scale,
+        );
+        let view = self.culling.then(|| self.view_region(CULL_MARGIN));
+        self.node_render_pass.view = view;
+        self.edge_render_pass.view = view;
+        self.node_render_pass.write(&self.device, &self.queue);
+        self.edge_render_pass.write(&self.device, &self.queue);
+        self.record_metrics(FrameMetrics {
+            frame: self.metrics_frame,
+            fps: 1.0 / elapsed,
+            tick_ms,
+            upload_ms: upload_time.ms(),
+            nodes: self.node_render_pass.nodes.len(),
+            edges: self.edge_render_pass.edges.len(),
+        });
+        self.observe_changes();
+        self.changes.flush();
+        self.shared_stale = true;
+    }
+
+    /// Hands a frame's metrics to the sink, dropping the sink once it fails
+    fn record_metrics(&mut self, metrics: FrameMetrics) {
+        let Some(sink) = &mut self.metrics else {
+            return;
+        };
+        self.metrics_frame += 1;
+        if let Err(err) = sink.record(&metrics) {
+            log::warn!("stopped exporting metrics: {err}");
+            self.metrics = None;
+        }
+    }
+
+    /// Emits the changes that aren't tied to a single call: camera moves,
+    /// drags ending and the layout coming to rest
+    fn observe_changes(&mut self) {
+        if self.camera.matrix != self.observed.camera {
+            self.observed.camera = self.camera.matrix;
+            self.changes.emit(Change::CameraChanged);
+        }
+        let dragged = self.input.dragged_node();
+        if let Some(node) = self.observed.dragged.filter(|node| dragged != Some(*node)) {
+            // The rest of the selection came along for the drag
+            for node in self.held_nodes(Some(node)) {
+                self.changes.emit(Change::NodeMoved(node));
+            }
+            let moved = std::mem::take(&mut self.drag_start)
+                .into_iter()
+                .filter_map(|(id, before)| {
+                    let after = self.node_render_pass.nodes.get(id as usize)?;
+                    (after.position != before.position).then(|| (id, before, after.clone()))
+                })
+                .collect();
+            self.history.record(Step::updated(moved));
+        }
+        self.observed.dragged = dragged;
+        let settled = self.physics.is_settled();
+        if settled && !self.observed.settled {
+            self.changes.emit(Change::LayoutSettled);
+        }
+        self.observed.settled = settled;
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        if self.suspended {
+            return Ok(());
+        }
+        let output = self.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        // Only timed while the stats are up or quality adapts to it, queries
+        // aren't free
+        let timed = (self.show_tree || self.adaptive.is_some())
+            && self
+                .gpu_timer
+                .as_mut()
+                .is_some_and(|timer| timer.collect(&self.device));
+        let timer = self.gpu_timer.as_ref().filter(|_| timed);
+        self.draw(
+            &mut encoder,
+            self.upscale_pass.target().unwrap_or(&view),
+            self.msaa_texture.as_ref().map(|msaa| &msaa.view),
+            self.depth_texture.as_ref().map(|depth| &depth.view),
+            &self.camera.bind_group,
+            timer,
+        );
+        self.upscale_pass.render(&mut encoder, &view);
+        self.draw_overlays(&mut encoder, &view, &self.camera.bind_group, timer);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(timer) = self.gpu_timer.as_mut().filter(|_| timed) {
+            timer.request();
+        }
+        output.present();
+        self.adapt_quality();
+        Ok(())
+    }
+
+    /// Lowers or restores quality when `adaptive` finds frames have been
+    /// over or well under budget for a while
+    fn adapt_quality(&mut self) {
+        let timings = self.gpu_timer.as_ref().and_then(|timer| timer.timings);
+        let (Some(adaptive), Some(timings)) = (&mut self.adaptive, timings) else {
+            return;
+        };
+        if let Some(quality) = adaptive.observe(timings.total) {
+            log::info!(
+                "drawing with {}x msaa at {}% resolution, {:.2} ms a frame on the GPU",
+                quality.sample_count,
+                quality.scale * 100.0,
+                timings.total
+            );
+            self.set_quality(quality);
+        }
+    }
+
+    /// Draws with `quality` from the next frame on, rebuilding every
+    /// pipeline if its MSAA differs from the current one
+    fn set_quality(&mut self, quality: Quality) {
+        let settings = RenderSettings {
+            sample_count: quality.sample_count,
+            ..self.settings
+        };
+        if settings != self.settings {
+            self.settings = settings;
+            for (file, _) in SHADERS {
+                let source = self.shader_sources[file].clone();
+                if let Some(Err(err)) = self.rebuild_pipelines(file, &source) {
+                    log::error!("{file} didn't compile for {settings:?}: {err}");
+                }
+            }
+        }
+        self.render_scale = quality.scale;
+        self.create_targets();
+    }
+
+    /// Recreates the textures frames are drawn into, at the window's size
+    /// scaled by `render_scale`
+    fn create_targets(&mut self) {
+        let quality = Quality {
+            sample_count: self.settings.sample_count,
+            scale: self.render_scale,
+        };
+        let mut config = self.config.clone();
+        (config.width, config.height) = quality.scaled(self.config.width, self.config.height);
+        (self.msaa_texture, self.depth_texture) =
+            self.settings.attachments(&self.device, &config, "Window");
+        self.upscale_pass
+            .resize(&self.device, &config, self.render_scale >= 1.0);
+    }
+
+    /// Records the graph's passes into `target`, shared by the window and
+    /// exports, which then finish the frame with `draw_overlays`. `timer`
+    /// times the frame, see `GpuTimer`.
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        msaa: Option<&wgpu::TextureView>,
+        depth: Option<&wgpu::TextureView>,
+        camera_bind_group: &wgpu::BindGroup,
+        timer: Option<&GpuTimer>,
+    ) {
+        let (view, resolve_target) = match msaa {
+            Some(msaa) => (msaa, Some(target)),
+            None => (target, None),
+        };
+        if let Some(timer) = timer {
+            timer.write(encoder, Mark::Begin);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear({
+                        // Clear values skip the shaders, so they're converted here
+                        let c = color::to_linear(self.prefs.background());
+                        wgpu::Color {
+                            r: c.x as f64,
+                            g: c.y as f64,
+                            b: c.z as f64,
+                            a: c.w as f64,
+                        }
+                    }),
+                    store: true,
+                },
+            })],
+            // depth_stencil_attachment: None,
+            depth_stencil_attachment: depth.map(|view| wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        let mark = |render_pass: &mut wgpu::RenderPass, mark| {
+            if let Some(timer) = timer {
+                timer.write_in_pass(render_pass, mark);
+            }
+        };
+
+        mark(&mut render_pass, Mark::PassStart);
+        self.hull_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        self.edge_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        mark(&mut render_pass, Mark::Edges);
+        self.node_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        mark(&mut render_pass, Mark::Nodes);
+        self.label_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        self.annotation_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        mark(&mut render_pass, Mark::Labels);
+        self.debug_render_pass
+            .render(camera_bind_group, &mut render_pass);
+    }
+
+    /// Draws the overlays over `target` once the graph is drawn, resolved
+    /// and upscaled into it, so they stay sharp at any quality. Ends the
+    /// frame `draw` started timing.
+    fn draw_overlays(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        timer: Option<&GpuTimer>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        self.overlay_render_pass
+            .render(camera_bind_group, &mut render_pass);
+        if let Some(timer) = timer {
+            timer.write_in_pass(&mut render_pass, Mark::Overlay);
+        }
+        drop(render_pass);
+
+        if let Some(timer) = timer {
+            timer.write(encoder, Mark::End);
+            timer.resolve(encoder);
+        }
+    }
+
+    /// Writes the graph as a standalone web page, opening on the window's
+    /// view or, for `ExportRegion::Graph`, fitted to the whole graph
+    pub fn export_html(&self, path: &str, region: ExportRegion) -> anyhow::Result<()> {
+        let view = match region {
+            ExportRegion::View => Some(HtmlView {
+                center: self.camera.translate.truncate() / self.camera.scale,
+                scale: self.camera.scale,
+            }),
+            ExportRegion::Graph => None,
+        };
+        let graph = Graph {
+            nodes: self.node_render_pass.nodes.clone(),
+            edges: self.edge_render_pass.edges.clone(),
+        };
+        std::fs::write(path, Html { view }.export(&graph))
+            .with_context(|| format!("failed to write {path}"))
+    }
+
+    /// Renders the scene offscreen at `options.scale` times the window
+    /// resolution and writes it as a PNG, returning the image size. Images
+    /// larger than a tile are rendered tile by tile and streamed to the file.
+    /// Fitting the image to the graph fails when there's nothing to fit it to.
+    pub fn export_png(&mut self, path: &str, options: ExportOptions) -> anyhow::Result<(u32, u32)> {
+        if !(1..=MAX_EXPORT_SCALE).contains(&options.scale) {
+            bail!("export scale must be between 1 and {MAX_EXPORT_SCALE}");
+        }
+        if options.width == Some(0) {
+            bail!("export width must be positive");
+        }
+        if options.region == ExportRegion::Graph
+            && export::graph_bounds(&self.node_render_pass.nodes).is_none()
+        {
+            bail!("the graph is empty, export the view instead");
+        }
+        let view = ExportView::new(&self.camera, &self.node_render_pass.nodes, &options);
+        if view.width == 0 || view.height == 0 {
+            bail!(
+                "nothing to export from a {}x{} view",
+                view.width,
+                view.height
+            );
+        }
+        // The image may show more than the window does, the next `update`
+        // culls again
+        self.node_render_pass.view = None;
+        self.edge_render_pass.view = None;
+        // Images get every detail, however far out they're taken
+        self.node_render_pass.plain = false;
+        self.edge_render_pass.thin = false;
+        self.node_render_pass.write(&self.device, &self.queue);
+        self.edge_render_pass.write(&self.device, &self.queue);
+        let tile_size = DEFAULT_TILE_SIZE
+            .min(self.device.limits().max_texture_dimension_2d)
+            .min(view.width.max(view.height));
+
+        let mut camera = Camera::with_layout(
+            view.translate,
+            tile_size as f32,
+            tile_size as f32,
+            view.scale,
+            &self.device,
+            &self.camera_bind_group_layout,
+        );
+        let config = wgpu::SurfaceConfiguration {
+            width: tile_size,
+            height: tile_size,
+            ..self.config.clone()
+        };
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Export Target"),
+            size: wgpu::Extent3d {
+                width: tile_size,
+                height: tile_size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let (msaa, depth) = self.settings.attachments(&self.device, &config, "Export");
+
+        // Overlays are laid out in window pixels, scale them up with the image
+        // and put the window's version back once every tile is rendered
+        let window_rects = std::mem::take(&mut self.overlay_render_pass.rects);
+        let image_rects: Vec<_> = window_rects
+            .iter()
+            .map(|rect| {
+                let mut rect = *rect;
+                rect.origin *= view.ui_scale;
+                rect.size *= view.ui_scale;
+                rect
+            })
+            .collect();
+
+        let mut png = PngStream::create(path, view.width, view.height)?;
+        let result = view.tiles(tile_size).into_iter().try_for_each(|row| {
+            let band_height = row[0].height;
+            let mut band = vec![0u8; (view.width * band_height * 4) as usize];
+            for tile in row {
+                camera.update_translate(&self.queue, view.tile_translate(&tile, tile_size));
+                let offset = cgmath::vec2(tile.x as f32, tile.y as f32);
+                self.overlay_render_pass.rects = image_rects
+                    .iter()
+                    .map(|rect| {
+                        let mut rect = *rect;
+                        rect.origin -= offset;
+                        rect
+                    })
+                    .collect();
+                self.overlay_render_pass.write(&self.queue);
+
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Export Encoder"),
+                        });
+                self.draw(
+                    &mut encoder,
+                    &target_view,
+                    msaa.as_ref().map(|msaa| &msaa.view),
+                    depth.as_ref().map(|depth| &depth.view),
+                    &camera.bind_group,
+                    None,
+                );
+                self.draw_overlays(&mut encoder, &target_view, &camera.bind_group, None);
+                self.queue.submit(std::iter::once(encoder.finish()));
+
+                let pixels = export::read_texture(
+                    &self.device,
+                    &self.queue,
+                    &target,
+                    config.format,
+                    tile_size,
+                    tile_size,
+                )?;
+                let (src_row, dst_row) = (tile_size as usize * 4, view.width as usize * 4);
+                for y in 0..tile.height as usize {
+                    let src = y * src_row;
+                    let dst = y * dst_row + tile.x as usize * 4;
+                    let len = tile.width as usize * 4;
+                    band[dst..dst + len].copy_from_slice(&pixels[src..src + len]);
+                }
+            }
+            png.write_rows(&band)
+        });
+
+        self.overlay_render_pass.rects = window_rects;
+        self.overlay_render_pass.write(&self.queue);
+        result?;
+        png.finish()?;
+        Ok((view.width, view.height))
+    }
+}
+
+impl GraphView for State {
+    fn nodes(&self) -> &[Node] {
+        &self.node_render_pass.nodes
+    }
+
+    fn node_by_external_id(&self, id: &str) -> Option<NodeId> {
+        self.external_ids.get(id).copied()
+    }
+
+    fn edges(&self) -> &[Edge] {
+        &self.edge_render_pass.edges
+    }
+
+    fn node_hidden(&self, id: NodeId) -> bool {
+        self.node_render_pass.hidden.contains(&id)
+    }
+
+    fn edge_hidden(&self, index: usize) -> bool {
+        self.edge_render_pass.hidden.contains(&(index as u32))
     }
 }
@@ -0,0 +1,169 @@
+use cgmath::{vec2, vec4, InnerSpace};
+
+use crate::{
+    i18n::Catalog,
+    overlay::{text_rects, text_size, Rect},
+};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+/// Distance from the menu's center to the middle of each item, in window
+/// pixels
+pub const RADIUS: f32 = 72.0;
+/// Pointer distance from the center under which nothing is picked, so a
+/// click right where the menu opened closes it
+pub const DEAD_ZONE: f32 = 16.0;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.8);
+const HOVER_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 0.9);
+const TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const HOVER_TEXT_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 1.0);
+const HUB_SIZE: f32 = 8.0;
+const PADDING: f32 = 6.0;
+
+/// What the node context menu offers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Delete,
+    /// Pins the node, or unpins it if it already is
+    Pin,
+    /// Gives the node the next color new nodes would get
+    Color,
+    /// Opens the palette on `label <node>` for typing its caption
+    Label,
+    /// Draws an edge from the node to whichever one is clicked next
+    StartEdge,
+}
+
+/// Items clockwise from the top
+pub const MENU_ITEMS: [MenuItem; 5] = [
+    MenuItem::Delete,
+    MenuItem::Pin,
+    MenuItem::Color,
+    MenuItem::Label,
+    MenuItem::StartEdge,
+];
+
+impl MenuItem {
+    /// Message id of the item's text
+    pub fn message(&self, pinned: bool) -> &'static str {
+        match self {
+            MenuItem::Delete => "menu-delete",
+            MenuItem::Pin if pinned => "menu-unpin",
+            MenuItem::Pin => "menu-pin",
+            MenuItem::Color => "menu-color",
+            MenuItem::Label => "menu-label",
+            MenuItem::StartEdge => "menu-edge",
+        }
+    }
+}
+
+/// Radial menu of node actions, opened by right-clicking a node. Items sit
+/// in a ring around where it was opened and the pointer picks one by its
+/// direction from there, so they're quick to hit without aiming.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextMenu {
+    pub node: u32,
+    /// Where the menu was opened, in overlay space (top-left origin, y down)
+    pub center: cgmath::Vector2<f32>,
+    /// Whether the node was pinned when the menu opened
+    pub pinned: bool,
+}
+
+impl ContextMenu {
+    pub fn new(node: u32, center: cgmath::Vector2<f32>, pinned: bool) -> Self {
+        Self {
+            node,
+            center,
+            pinned,
+        }
+    }
+
+    /// Middle of the `i`th item, in overlay space
+    fn item_center(&self, i: usize) -> cgmath::Vector2<f32> {
+        let angle = i as f32 * std::f32::consts::TAU / MENU_ITEMS.len() as f32;
+        self.center + vec2(angle.sin(), -angle.cos()) * RADIUS
+    }
+
+    /// Item in the direction of `pos` from the center, `None` within
+    /// `DEAD_ZONE` of it or past twice `RADIUS`
+    pub fn item_at(&self, pos: cgmath::Vector2<f32>) -> Option<MenuItem> {
+        let offset = pos - self.center;
+        let distance = offset.magnitude();
+        if !(DEAD_ZONE..=RADIUS * 2.0).contains(&distance) {
+            return None;
+        }
+        // Clockwise from the top, y pointing down
+        let angle = offset.x.atan2(-offset.y).rem_euclid(std::f32::consts::TAU);
+        let sector = std::f32::consts::TAU / MENU_ITEMS.len() as f32;
+        let i = (angle / sector).round() as usize % MENU_ITEMS.len();
+        Some(MENU_ITEMS[i])
+    }
+
+    /// The ring of items, the one `pointer` (in overlay space) would pick
+    /// lit up
+    pub fn rects(&self, pointer: Option<cgmath::Vector2<f32>>, strings: &Catalog) -> Vec<Rect> {
+        let hovered = pointer.and_then(|pos| self.item_at(pos));
+        let mut rects = vec![Rect::new(
+            self.center - vec2(HUB_SIZE, HUB_SIZE) / 2.0,
+            (HUB_SIZE, HUB_SIZE),
+            HOVER_COLOR,
+        )];
+        for (i, item) in MENU_ITEMS.iter().enumerate() {
+            let label = strings.get(item.message(self.pinned));
+            let size = text_size(label, TEXT_PIXEL) + vec2(PADDING, PADDING) * 2.0;
+            let origin = self.item_center(i) - size / 2.0;
+            let (background, text) = if hovered == Some(*item) {
+                (HOVER_COLOR, HOVER_TEXT_COLOR)
+            } else {
+                (BACKGROUND_COLOR, TEXT_COLOR)
+            };
+            rects.push(Rect::new(origin, size, background));
+            rects.extend(text_rects(
+                label,
+                origin + vec2(PADDING, PADDING),
+                TEXT_PIXEL,
+                text,
+            ));
+        }
+        rects
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn items_are_picked_by_direction() {
+        let menu = ContextMenu::new(3, vec2(400.0, 300.0), false);
+        assert_eq!(
+            menu.item_at(vec2(400.0, 300.0 - RADIUS)),
+            Some(MenuItem::Delete)
+        );
+        // Far out and a little off still counts
+        assert_eq!(
+            menu.item_at(vec2(410.0, 300.0 - RADIUS * 1.8)),
+            Some(MenuItem::Delete)
+        );
+        for (i, item) in MENU_ITEMS.iter().enumerate() {
+            assert_eq!(menu.item_at(menu.item_center(i)), Some(*item));
+        }
+        assert_eq!(menu.item_at(vec2(405.0, 300.0)), None);
+        assert_eq!(menu.item_at(vec2(400.0, 300.0 + RADIUS * 3.0)), None);
+
+        let strings = Catalog::english();
+        let plain = menu.rects(None, &strings);
+        let lit = menu.rects(Some(menu.item_center(1)), &strings);
+        assert_eq!(plain.len(), lit.len());
+        assert_eq!(
+            lit.iter().filter(|rect| rect.color == HOVER_COLOR).count(),
+            2,
+            "the hub and the item under the pointer"
+        );
+        for item in MENU_ITEMS {
+            assert_ne!(strings.get(item.message(true)), item.message(true));
+            assert_ne!(strings.get(item.message(false)), item.message(false));
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// How long a scrape gets to send its request and take the response, so a
+/// client that stalls can't hold the others up
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often the serving thread checks whether its sink was dropped
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
+/// What one frame cost, as exported by a `MetricsSink`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameMetrics {
+    /// Frames recorded before this one
+    pub frame: u64,
+    /// Frames per second, from the time since the previous frame
+    pub fps: f32,
+    /// Milliseconds spent stepping the physics
+    pub tick_ms: f32,
+    /// Milliseconds spent writing instance buffers
+    pub upload_ms: f32,
+    pub nodes: usize,
+    pub edges: usize,
+}
+
+/// Somewhere frame metrics go, for watching long running deployments degrade
+pub trait MetricsSink {
+    fn record(&mut self, metrics: &FrameMetrics) -> io::Result<()>;
+}
+
+/// Appends a row per frame to a CSV file, after a header row
+pub struct CsvSink<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(out: W) -> io::Result<Self> {
+        let mut out = BufWriter::new(out);
+        writeln!(out, "frame,fps,tick_ms,upload_ms,nodes,edges")?;
+        Ok(Self { out })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+            .into_inner()
+            .unwrap_or_else(|_| panic!("rows are flushed as they're recorded"))
+    }
+}
+
+impl<W: Write> MetricsSink for CsvSink<W> {
+    fn record(&mut self, m: &FrameMetrics) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{},{:.2},{:.3},{:.3},{},{}",
+            m.frame, m.fps, m.tick_ms, m.upload_ms, m.nodes, m.edges
+        )?;
+        // Whatever watches the file sees every frame, even if we crash
+        self.out.flush()
+    }
+}
+
+/// Serves the latest frame's metrics over HTTP in the Prometheus text
+/// format, on any path. Scrapes are answered from a background thread, so
+/// they work while the window isn't drawing. Dropping the sink stops the
+/// thread and closes the port.
+pub struct PrometheusSink {
+    latest: Arc<Mutex<FrameMetrics>>,
+    pub addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl PrometheusSink {
+    /// Starts listening on `addr`, port 0 picks a free one
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        // Accepting without blocking lets the thread notice `stop`
+        listener.set_nonblocking(true)?;
+        let latest = Arc::new(Mutex::new(FrameMetrics::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (shared, stopped) = (latest.clone(), stop.clone());
+        let server = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let _ = respond(stream, &shared);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(ACCEPT_POLL);
+                    }
+                    Err(e) => log::warn!("metrics on {addr}: {e}"),
+                }
+            }
+        });
+        Ok(Self {
+            latest,
+            addr,
+            stop,
+            server: Some(server),
+        })
+    }
+}
+
+impl Drop for PrometheusSink {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+/// Answers one scrape with the latest metrics
+fn respond(mut stream: TcpStream, latest: &Mutex<FrameMetrics>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    // The request itself doesn't matter, every path gets the same
+    let mut request = [0; 1024];
+    if stream.read(&mut request)? == 0 {
+        return Ok(());
+    }
+    let body = prometheus_text(&latest.lock().unwrap());
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+impl MetricsSink for PrometheusSink {
+    fn record(&mut self, metrics: &FrameMetrics) -> io::Result<()> {
+        *self.latest.lock().unwrap() = *metrics;
+        Ok(())
+    }
+}
+
+/// `metrics` in the Prometheus text exposition format
+pub fn prometheus_text(m: &FrameMetrics) -> String {
+    let gauges = [
+        ("fps", "Frames per second", m.fps as f64),
+        (
+            "tick_ms",
+            "Milliseconds spent on physics last frame",
+            m.tick_ms as f64,
+        ),
+        (
+            "upload_ms",
+            "Milliseconds spent uploading instances last frame",
+            m.upload_ms as f64,
+        ),
+        ("nodes", "Nodes in the graph", m.nodes as f64),
+        ("edges", "Edges in the graph", m.edges as f64),
+    ];
+    let mut out = String::new();
+    for (name, help, value) in gauges {
+        writeln!(out, "# HELP force_graph_{name} {help}").unwrap();
+        writeln!(out, "# TYPE force_graph_{name} gauge").unwrap();
+        writeln!(out, "force_graph_{name} {value}").unwrap();
+    }
+    writeln!(out, "# HELP force_graph_frames_total Frames drawn").unwrap();
+    writeln!(out, "# TYPE force_graph_frames_total counter").unwrap();
+    writeln!(out, "force_graph_frames_total {}", m.frame + 1).unwrap();
+    out
+}
+
+/// Wall clock time since it was started. The browser has no clock to read,
+/// so there it always reads zero.
+pub struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    pub fn ms(&self) -> f32 {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed().as_secs_f32() * 1000.0;
+        #[cfg(target_arch = "wasm32")]
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FRAME: FrameMetrics = FrameMetrics {
+        frame: 41,
+        fps: 59.5,
+        tick_ms: 1.25,
+        upload_ms: 0.5,
+        nodes: 10,
+        edges: 12,
+    };
+
+    #[test]
+    fn csv_rows() {
+        let mut sink = CsvSink::new(vec![]).unwrap();
+        sink.record(&FRAME).unwrap();
+        let csv = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            csv,
+            "frame,fps,tick_ms,upload_ms,nodes,edges\n41,59.50,1.250,0.500,10,12\n"
+        );
+    }
+
+    #[test]
+    fn serves_prometheus_text() {
+        let text = prometheus_text(&FRAME);
+        assert!(text.contains("# TYPE force_graph_fps gauge\nforce_graph_fps 59.5\n"));
+        assert!(text.contains("force_graph_frames_total 42\n"));
+
+        let mut sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+        sink.record(&FRAME).unwrap();
+        let scrape = || {
+            let mut stream = TcpStream::connect(sink.addr).unwrap();
+            stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = scrape();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(&text));
+
+        // A client that never sends its request times out instead of
+        // holding up the next scrape
+        let _silent = TcpStream::connect(sink.addr).unwrap();
+        assert!(scrape().ends_with(&text));
+
+        let addr = sink.addr;
+        drop(sink);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -1,9 +1,45 @@
+use instant::{Duration, Instant};
+
+use crate::input::PointerButton;
+
+/// How far the pointer may wander while a button is held, in window pixels,
+/// before the press counts as a drag rather than a click
+pub const DRAG_THRESHOLD: f32 = 4.0;
+/// Longest gap between the clicks of a double click
+pub const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+/// How far apart the clicks of a double click may be, in window pixels
+pub const DOUBLE_CLICK_DISTANCE: f32 = 6.0;
+
+/// What letting go of a button amounted to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Release {
+    Click,
+    /// A click soon after another in the same spot. The next click starts
+    /// counting afresh, so a triple click is a double click and a click.
+    DoubleClick,
+    /// The pointer moved past `DRAG_THRESHOLD` while the button was held
+    Drag,
+}
+
+/// A button being held down
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Held {
+    /// Where it was pressed
+    pos: cgmath::Vector2<f32>,
+    dragged: bool,
+}
+
+/// The pointer and its buttons. Positions are window pixels relative to the
+/// center with y pointing up, like `InputState` takes them.
 #[derive(Clone)]
 pub struct Mouse {
     /// Mouse position in screen coordinates
     pub pos: Option<cgmath::Vector2<f32>>,
     pub last_pos: cgmath::Vector2<f32>,
-    pub clicked: bool,
+    /// Buttons held down, by `button_index`
+    held: [Option<Held>; 3],
+    /// When and where each button was last clicked, for double clicks
+    last_click: [Option<(Instant, cgmath::Vector2<f32>)>; 3],
 }
 
 impl Default for Mouse {
@@ -11,7 +47,130 @@ impl Default for Mouse {
         Self {
             pos: Default::default(),
             last_pos: (0.0, 0.0).into(),
-            clicked: Default::default(),
+            held: [None; 3],
+            last_click: [None; 3],
         }
     }
 }
+
+fn button_index(button: PointerButton) -> usize {
+    match button {
+        PointerButton::Primary => 0,
+        PointerButton::Secondary => 1,
+        PointerButton::Middle => 2,
+    }
+}
+
+impl Mouse {
+    pub fn moved(&mut self, pos: cgmath::Vector2<f32>) {
+        self.pos = Some(pos);
+        for held in self.held.iter_mut().flatten() {
+            held.dragged |= cgmath::MetricSpace::distance(held.pos, pos) > DRAG_THRESHOLD;
+        }
+    }
+
+    /// The pointer left the window. Buttons held at the time are let go
+    /// without a click, their release won't reach the window.
+    pub fn left(&mut self) {
+        self.last_pos = self.pos.unwrap_or((0.0, 0.0).into());
+        self.pos = None;
+        self.held = [None; 3];
+    }
+
+    /// `button` went down at the current position. Nothing is tracked when the
+    /// pointer's position isn't known.
+    pub fn press(&mut self, button: PointerButton) {
+        self.held[button_index(button)] = self.pos.map(|pos| Held {
+            pos,
+            dragged: false,
+        });
+    }
+
+    /// `button` came up at `now`, `None` if its press wasn't seen
+    pub fn release(&mut self, button: PointerButton, now: Instant) -> Option<Release> {
+        let i = button_index(button);
+        let held = self.held[i].take()?;
+        if held.dragged {
+            self.last_click[i] = None;
+            return Some(Release::Drag);
+        }
+        let double = self.last_click[i].is_some_and(|(time, pos)| {
+            now.duration_since(time) <= DOUBLE_CLICK_TIME
+                && cgmath::MetricSpace::distance(pos, held.pos) <= DOUBLE_CLICK_DISTANCE
+        });
+        if double {
+            self.last_click[i] = None;
+            Some(Release::DoubleClick)
+        } else {
+            self.last_click[i] = Some((now, held.pos));
+            Some(Release::Click)
+        }
+    }
+
+    pub fn is_pressed(&self, button: PointerButton) -> bool {
+        self.held[button_index(button)].is_some()
+    }
+
+    /// Whether `button` is held and has moved far enough to be a drag
+    pub fn is_dragging(&self, button: PointerButton) -> bool {
+        self.held[button_index(button)].is_some_and(|held| held.dragged)
+    }
+
+    /// Where the held `button` went down
+    pub fn press_pos(&self, button: PointerButton) -> Option<cgmath::Vector2<f32>> {
+        self.held[button_index(button)].map(|held| held.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cgmath::vec2;
+
+    use super::*;
+
+    #[test]
+    fn clicks_drags_and_double_clicks() {
+        let mut mouse = Mouse::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let primary = PointerButton::Primary;
+
+        // A little jitter is still a click
+        mouse.moved(vec2(10.0, 10.0));
+        mouse.press(primary);
+        mouse.moved(vec2(12.0, 11.0));
+        assert!(mouse.is_pressed(primary) && !mouse.is_dragging(primary));
+        assert_eq!(mouse.press_pos(primary), Some(vec2(10.0, 10.0)));
+        assert_eq!(mouse.release(primary, at(0)), Some(Release::Click));
+
+        // Quickly clicking again nearby makes a double click, and the click
+        // after that starts over
+        mouse.press(primary);
+        assert_eq!(mouse.release(primary, at(300)), Some(Release::DoubleClick));
+        mouse.press(primary);
+        assert_eq!(mouse.release(primary, at(400)), Some(Release::Click));
+        mouse.press(primary);
+        assert_eq!(mouse.release(primary, at(900)), Some(Release::Click));
+
+        // Moving away turns a press into a drag for good, even coming back
+        mouse.press(primary);
+        mouse.moved(vec2(30.0, 10.0));
+        assert!(mouse.is_dragging(primary));
+        mouse.moved(vec2(12.0, 11.0));
+        assert_eq!(mouse.release(primary, at(1000)), Some(Release::Drag));
+        assert!(!mouse.is_pressed(primary));
+
+        // Buttons are tracked separately, and releases without presses (the
+        // press happened outside the window) are ignored
+        mouse.press(primary);
+        mouse.press(PointerButton::Secondary);
+        assert_eq!(
+            mouse.release(PointerButton::Secondary, at(1100)),
+            Some(Release::Click)
+        );
+        assert!(mouse.is_pressed(primary));
+        mouse.left();
+        assert_eq!(mouse.release(primary, at(1200)), None);
+        assert_eq!(mouse.pos, None);
+    }
+}
@@ -1,18 +1,139 @@
-use std::mem::MaybeUninit;
+use std::collections::BTreeSet;
+#[cfg(feature = "render")]
+use std::path::PathBuf;
 
+#[cfg(feature = "render")]
 use bytemuck::{Pod, Zeroable};
-use wgpu::util::DeviceExt;
 
-use crate::{texture::Texture, Vertex, SAMPLE_COUNT};
+use crate::{attributes::Attributes, graph};
+#[cfg(feature = "render")]
+use crate::{
+    instancing::{self, InstanceBuffer, Quad},
+    physics::Region,
+    shader,
+    texture::{Atlas, ImageLoader},
+    RenderSettings, Vertex,
+};
 
+/// Nodes the instance buffer starts out with room for, it doubles from there
+#[cfg(feature = "render")]
 pub const DEFAULT_INSTANCE_BUFFER_CAP: usize = 1024;
+/// Width and height of the atlas node images are kept in, in texels
+#[cfg(feature = "render")]
+pub const IMAGE_ATLAS_SIZE: u32 = 2048;
+/// Node images are shrunk to fit this many texels across, plenty for the
+/// size nodes are drawn at and small enough for a couple hundred to share
+/// the atlas
+#[cfg(feature = "render")]
+pub const MAX_IMAGE_SIZE: u32 = 128;
+/// Alpha multiplier for nodes outside the current highlight set
+pub const DIMMED_ALPHA: f32 = 0.15;
+/// Largest radius a node's shape is drawn at, in world units; smaller nodes
+/// get the smaller of their width and height, see `Node::radius`.
+pub const DISK_RADIUS: f32 = 200.0;
+/// Smallest radius `Node::rescale` shrinks a node to, in world units
+pub const MIN_RADIUS: f32 = 5.0;
+/// How far outside a node's disk a click still picks it, in logical pixels
+pub const PICK_TOLERANCE: f32 = 4.0;
+/// Depth `edge.wgsl` draws edges at, through `shader::CONSTANTS`. Nodes get
+/// depths of their own in front of it, see `depth`.
+pub const EDGE_DEPTH: f32 = 0.1;
 
+#[cfg(feature = "render")]
 pub struct NodeRenderPass {
     pub nodes: Vec<Node>,
-    pub pipeline: wgpu::RenderPipeline,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub instance_buffer: wgpu::Buffer,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) quad: Quad,
+    pub(crate) instance_buffer: InstanceBuffer,
+    /// More instances than the buffer holds as of the last `write`
+    overflowed: bool,
+    /// Where each node sits in the instance buffer, which is sorted back to
+    /// front, `NO_SLOT` for culled ones
+    slots: Vec<u32>,
+    /// Nodes in the instance buffer as of the last `write`, the rest are
+    /// culled
+    drawn: usize,
+    /// When set, nodes not in the set are drawn dimmed
+    pub highlight: Option<BTreeSet<u32>>,
+    /// Nodes in this set are not drawn
+    pub hidden: BTreeSet<u32>,
+    /// Nodes in this set get a ring around them
+    pub selected: BTreeSet<u32>,
+    /// Nodes in this set get a dot in the middle, for being pinned in place
+    pub pinned: BTreeSet<u32>,
+    /// Nodes in this set pulse, for drawing attention to them such as while
+    /// an alert on them is active
+    pub pulsing: BTreeSet<u32>,
+    /// Nodes in this set are drawn brightened, such as the one under the
+    /// cursor and its neighbors
+    pub glowing: BTreeSet<u32>,
+    /// When set, only nodes overlapping it are uploaded and drawn, the rest
+    /// being off screen. Taken up on the next `write`.
+    pub view: Option<Region>,
+    /// Set while zoomed out past `State::lod_zoom`, which leaves rings, pin
+    /// dots, pulses and glows out, too small to make out there
+    pub plain: bool,
+    /// Node images, uploaded as nodes that show them are drawn
+    images: Atlas<PathBuf>,
+    /// What the pipeline is rebuilt with on a shader reload
+    pub(crate) atlas_layout: wgpu::BindGroupLayout,
+    /// Decodes node images in the background, and keeps them for when they
+    /// have to be uploaded again
+    image_loader: ImageLoader,
+    /// Where relative image paths are found, the loaded graph file's
+    /// directory. `None` leaves them relative to the working directory.
+    pub image_dir: Option<PathBuf>,
+    /// Each node's image's texture coordinates as of the last `write`, zero
+    /// for nodes without one
+    image_uvs: Vec<[f32; 4]>,
+}
+
+/// Outline of a node, drawn by `node.wgsl` from its signed distance so the
+/// edge stays smooth at any zoom
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Circle,
+    Square,
+    Diamond,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Circle, Shape::Square, Shape::Diamond];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Circle => "circle",
+            Shape::Square => "square",
+            Shape::Diamond => "diamond",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shape| shape.name() == name)
+    }
+
+    /// The shape closest to a Graphviz node shape such as `box` or
+    /// `ellipse`, `None` for shapes with no close match
+    pub fn from_dot(shape: &str) -> Option<Self> {
+        match shape {
+            "circle" | "doublecircle" | "ellipse" | "oval" | "point" => Some(Shape::Circle),
+            "box" | "rect" | "rectangle" | "square" => Some(Shape::Square),
+            "diamond" => Some(Shape::Diamond),
+            _ => None,
+        }
+    }
+
+    /// How far `(dx, dy)` is from the center as the shape measures it, so a
+    /// point is on a node of radius `r` when this is at most `r`. Matches
+    /// `shape_distance` in `node.wgsl`.
+    pub fn norm(self, dx: f32, dy: f32) -> f32 {
+        match self {
+            Shape::Circle => (dx * dx + dy * dy).sqrt(),
+            Shape::Square => dx.abs().max(dy.abs()),
+            Shape::Diamond => dx.abs() + dy.abs(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,56 +142,123 @@ pub struct Node {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
     pub color: cgmath::Vector4<f32>,
+    pub shape: Shape,
+    /// PNG or JPEG file drawn inside the node's shape, such as an avatar or
+    /// an icon
+    pub image: Option<String>,
+    pub attributes: Attributes,
+    /// Caption drawn under the node, its name when unset (see `caption`)
+    pub label: Option<String>,
+    /// Stable name given from outside, such as its id in the file it was
+    /// loaded from, for referring to it while its index changes
+    pub external_id: Option<String>,
 }
 
+#[cfg(feature = "render")]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C)]
-pub struct NodeRaw {
+pub(crate) struct NodeRaw {
     model: [[f32; 4]; 4],
     color: [f32; 4],
     center: [f32; 3],
+    /// 1 draws a selection ring inside the disk's edge
+    ring: f32,
+    depth: f32,
+    /// 1 draws a pin dot in the middle
+    pin: f32,
+    /// 1 makes the node pulse
+    pulse: f32,
+    /// 1 brightens the node
+    glow: f32,
+    /// `Shape`, numbered in declaration order
+    shape: u32,
+    /// Where the node's image is in the image atlas, as `AtlasRegion::uv`,
+    /// all zero without one
+    image: [f32; 4],
 }
 
+#[cfg(feature = "render")]
 impl NodeRenderPass {
-    const INDICES: &[u16] = &[0, 1, 3, 3, 1, 2];
-    const VERTICES: &[Vertex] = &[
-        Vertex {
-            // vertex a, index = 0
-            position: [-1.0, -1.0],
-        },
-        Vertex {
-            // vertex b, index = 1
-            position: [1.0, -1.0],
-        },
-        Vertex {
-            // vertex c, index = 2
-            position: [1.0, 1.0],
-        },
-        Vertex {
-            // vertex d, index = 3
-            position: [-1.0, 1.0],
-        },
-    ];
-
     pub fn new(
         nodes: Vec<Node>,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
         camera_bind_group_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
     ) -> Self {
+        let atlas_layout = Atlas::<String>::bind_group_layout(device);
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            &atlas_layout,
+            settings,
+            &shader::builtin(include_str!("node.wgsl")),
+        );
+
+        let quad = Quad::new(device, "Node");
+
+        let instance_buffer = InstanceBuffer::new(
+            device,
+            "Node",
+            std::mem::size_of::<NodeRaw>(),
+            DEFAULT_INSTANCE_BUFFER_CAP,
+        );
+        let mut pass = Self {
+            slots: vec![],
+            drawn: 0,
+            nodes,
+            pipeline,
+            quad,
+            instance_buffer,
+            overflowed: false,
+            highlight: None,
+            hidden: BTreeSet::new(),
+            selected: BTreeSet::new(),
+            pinned: BTreeSet::new(),
+            pulsing: BTreeSet::new(),
+            glowing: BTreeSet::new(),
+            view: None,
+            plain: false,
+            images: Atlas::new(
+                device,
+                &atlas_layout,
+                IMAGE_ATLAS_SIZE,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                "Node Images",
+            ),
+            atlas_layout,
+            image_loader: ImageLoader::new(MAX_IMAGE_SIZE),
+            image_dir: None,
+            image_uvs: vec![],
+        };
+        pass.write(device, queue);
+        pass
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `node.wgsl` or a hot
+    /// reloaded edit of it
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        atlas_layout: &wgpu::BindGroupLayout,
+        settings: RenderSettings,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Node Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("node.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Node"),
-            bind_group_layouts: &[camera_bind_group_layout],
+            bind_group_layouts: &[camera_bind_group_layout, atlas_layout],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Node Render Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
@@ -104,87 +292,159 @@ impl NodeRenderPass {
                 ..Default::default()
             },
             // depth_stencil: None,
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: SAMPLE_COUNT as u32,
-                ..Default::default()
-            },
+            // Drawn back to front, each node nearer than the last, so writing
+            // depth never hides a node behind one that's already drawn
+            depth_stencil: settings
+                .depth_stencil(wgpu::CompareFunction::LessEqual)
+                .map(|state| wgpu::DepthStencilState {
+                    depth_write_enabled: true,
+                    ..state
+                }),
+            multisample: settings.multisample(),
             multiview: None,
-        });
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(Self::VERTICES),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: "Node Index Buffer".into(),
-            contents: bytemuck::cast_slice(Self::INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        })
+    }
 
-        let mut instance_vec: Vec<MaybeUninit<NodeRaw>> = (0..DEFAULT_INSTANCE_BUFFER_CAP)
-            .map(|_| MaybeUninit::zeroed())
-            .collect();
-        for (i, node) in nodes.iter().enumerate() {
-            instance_vec[i] = MaybeUninit::new(node.to_instance());
+    fn instance(&self, idx: usize) -> NodeRaw {
+        let mut raw = self.nodes[idx].to_instance();
+        if let Some(slot) = self.slots.get(idx) {
+            raw.depth = depth(*slot, self.nodes.len());
+        }
+        if let Some(uv) = self.image_uvs.get(idx) {
+            raw.image = *uv;
+        }
+        if self.selected.contains(&(idx as u32)) {
+            raw.ring = 1.0;
         }
+        if self.pinned.contains(&(idx as u32)) {
+            raw.pin = 1.0;
+        }
+        if self.pulsing.contains(&(idx as u32)) {
+            raw.pulse = 1.0;
+        }
+        if self.glowing.contains(&(idx as u32)) {
+            raw.glow = 1.0;
+        }
+        if self.plain {
+            (raw.ring, raw.pin, raw.pulse, raw.glow) = (0.0, 0.0, 0.0, 0.0);
+        }
+        if self.hidden.contains(&(idx as u32)) {
+            raw.color[3] = 0.0;
+        } else if let Some(highlight) = &self.highlight {
+            if !highlight.contains(&(idx as u32)) {
+                raw.color[3] *= DIMMED_ALPHA;
+            }
+        }
+        raw
+    }
 
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Node Instance Buffer"),
-            size: (std::mem::size_of::<NodeRaw>() * DEFAULT_INSTANCE_BUFFER_CAP) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    fn instance_count(&self) -> u32 {
+        instancing::instance_count(self.drawn, self.instance_buffer.capacity())
+    }
+
+    /// Uploads every node in `view`, packed together at the start of the
+    /// instance buffer
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        // Blending needs whatever is behind a node drawn before it, depth
+        // testing can't sort that out for translucent disks
+        let mut order = instancing::back_to_front(self.nodes.iter().map(|node| node.position.z));
+        if let Some(view) = &self.view {
+            order.retain(|i| {
+                let node = &self.nodes[*i as usize];
+                let center = node.position.truncate();
+                view.overlaps(center - node.size, center + node.size)
+            });
+        }
+        self.drawn = order.len();
+        self.instance_buffer.reserve(device, queue, self.drawn);
+        instancing::warn_overflow(
+            self.drawn,
+            self.instance_buffer.capacity(),
+            "nodes",
+            &mut self.overflowed,
+        );
+        self.slots = instancing::slots(&order, self.nodes.len());
+        self.images.next_frame();
+        self.image_uvs = vec![[0.0; 4]; self.nodes.len()];
+        for i in &order[..self.instance_count() as usize] {
+            if let Some(path) = self.nodes[*i as usize].image.clone() {
+                self.image_uvs[*i as usize] = self.image_uv(queue, &path);
+            }
+        }
         queue.write_buffer(
-            &instance_buffer,
+            self.instance_buffer.buffer(),
             0,
-            bytemuck::cast_slice(&nodes.iter().map(Node::to_instance).collect::<Vec<_>>()),
+            bytemuck::cast_slice(
+                &order[..self.instance_count() as usize]
+                    .iter()
+                    .map(|i| self.instance(*i as usize))
+                    .collect::<Vec<_>>(),
+            ),
         );
+    }
 
-        Self {
-            nodes,
-            pipeline,
-            vertex_buffer,
-            index_buffer,
-            instance_buffer,
+    /// Where `path`'s image is in the atlas, uploading it if it isn't
+    /// there. Zero, drawing the node without it, while it's being decoded,
+    /// if it can't be loaded or if there's no room left this frame.
+    fn image_uv(&mut self, queue: &wgpu::Queue, path: &str) -> [f32; 4] {
+        let path = match &self.image_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        if let Some(region) = self.images.get(&path) {
+            return region.uv(self.images.size());
         }
+        let Some(image) = self.image_loader.get(&path) else {
+            return [0.0; 4];
+        };
+        self.images
+            .insert(queue, path, image.width, image.height, &image.texels)
+            .map_or([0.0; 4], |region| region.uv(self.images.size()))
     }
 
-    pub fn write(&mut self, queue: &wgpu::Queue) {
-        queue.write_buffer(
-            &self.instance_buffer,
-            0,
-            bytemuck::cast_slice(&self.nodes.iter().map(Node::to_instance).collect::<Vec<_>>()),
-        );
+    /// Tries images that failed to load again on the next `write`, for
+    /// after they've been fixed
+    pub fn retry_images(&mut self) {
+        self.image_loader.retry_failed();
+    }
+
+    /// Whether images are still being decoded, to be drawn once they are
+    pub fn images_loading(&self) -> bool {
+        self.image_loader.is_loading()
     }
 
-    pub fn add_node(&mut self, node: Node, queue: &wgpu::Queue) {
-        let raw = node.to_instance();
+    /// Appends a node, growing the instance buffer if it's full. It's
+    /// uploaded right away unless the instance buffer is out of date anyway,
+    /// in which case the next `write` catches up.
+    pub fn add_node(&mut self, node: Node, device: &wgpu::Device, queue: &wgpu::Queue) {
         let idx = self.nodes.len();
         self.nodes.push(node);
-        queue.write_buffer(
-            &self.instance_buffer,
-            (idx * std::mem::size_of::<NodeRaw>()) as u64,
-            bytemuck::cast_slice(&[raw]),
-        )
+        self.instance_buffer
+            .reserve(device, queue, self.nodes.len());
+        if self.slots.len() != idx {
+            return;
+        }
+        // Drawn until the next `write` culls it, even off screen
+        self.slots.push(self.drawn as u32);
+        self.drawn += 1;
+        self.update_node(idx as u32, queue);
     }
 
     pub fn update_node(&mut self, idx: u32, queue: &wgpu::Queue) {
+        let Some(slot) = self.slots.get(idx as usize).map(|slot| *slot as usize) else {
+            return;
+        };
+        if slot >= self.instance_buffer.capacity() {
+            return;
+        }
         queue.write_buffer(
-            &self.instance_buffer,
-            (idx as usize * std::mem::size_of::<NodeRaw>()) as u64,
-            bytemuck::cast_slice(&[self.nodes.get(idx as usize).unwrap().to_instance()]),
+            self.instance_buffer.buffer(),
+            (slot * std::mem::size_of::<NodeRaw>()) as u64,
+            bytemuck::cast_slice(&[self.instance(idx as usize)]),
         )
     }
 
-    pub fn render<'a, 'b>(
+    pub fn render<'a>(
         &'a self,
         camera_bind_group: &'a wgpu::BindGroup,
         render_pass: &mut wgpu::RenderPass<'a>,
@@ -195,10 +455,13 @@ impl NodeRenderPass {
 
         render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, camera_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..Self::INDICES.len() as u32, 0, 0..self.nodes.len() as u32);
+        render_pass.set_bind_group(1, &self.images.bind_group, &[]);
+        self.quad.draw(
+            render_pass,
+            self.instance_buffer.buffer(),
+            std::mem::size_of::<NodeRaw>(),
+            self.instance_count(),
+        );
     }
 }
 
@@ -214,18 +477,53 @@ impl Node {
             position: pos.into(),
             rotation,
             color: color.into(),
+            shape: Shape::default(),
+            image: None,
+            attributes: Attributes::new(),
+            label: None,
+            external_id: None,
+        }
+    }
+
+    /// Text shown under the node: its `label`, or else its name attribute
+    pub fn caption(&self) -> Option<String> {
+        self.label.clone().or_else(|| graph::node_name(self))
+    }
+
+    /// Radius of the disk drawn for the node, in world units. Other shapes
+    /// are as wide and as tall as it.
+    pub fn radius(&self) -> f32 {
+        self.size.x.min(self.size.y).min(DISK_RADIUS)
+    }
+
+    /// Scales the node by `factor`, keeping its proportions and its radius
+    /// between `MIN_RADIUS` and `DISK_RADIUS`. Returns whether it changed.
+    pub fn rescale(&mut self, factor: f32) -> bool {
+        let smallest = self.size.x.min(self.size.y);
+        if smallest <= 0.0 {
+            return false;
         }
+        let target = (smallest * factor).clamp(MIN_RADIUS, DISK_RADIUS);
+        let size = self.size * (target / smallest);
+        let changed = size != self.size;
+        self.size = size;
+        changed
     }
 
+    /// How far the world position `pos` is outside the node's shape,
+    /// negative inside it
+    pub fn gap(&self, pos: cgmath::Vector2<f32>) -> f32 {
+        let (dx, dy) = (pos.x - self.position.x, pos.y - self.position.y);
+        self.shape.norm(dx, dy) - self.radius()
+    }
+
+    /// Whether the world position `pos` is on the node's shape
     pub fn intersects(&self, pos: &cgmath::Vector3<f32>) -> bool {
-        pos.x <= self.position.x + (self.size.x * 1.0)
-            && pos.x >= self.position.x - (self.size.x * 1.0)
-            && pos.y <= self.position.y + (self.size.y * 1.0)
-            && pos.y >= self.position.y - (self.size.y * 1.0)
-        // && self.position.x == pos.z
+        self.gap(pos.truncate()) <= 0.0
     }
 
-    pub fn to_instance(&self) -> NodeRaw {
+    #[cfg(feature = "render")]
+    pub(crate) fn to_instance(&self) -> NodeRaw {
         NodeRaw {
             model: (cgmath::Matrix4::from_translation(self.position)
                 * cgmath::Matrix4::from_nonuniform_scale(self.size.x, self.size.y, 1.0)
@@ -233,12 +531,64 @@ impl Node {
             .into(),
             color: self.color.into(),
             center: self.position.into(),
+            ring: 0.0,
+            depth: 0.0,
+            pin: 0.0,
+            pulse: 0.0,
+            glow: 0.0,
+            shape: self.shape as u32,
+            image: [0.0; 4],
         }
     }
 }
 
+/// Depth to draw the node in `slot` of the back to front order at, out of
+/// `count`. Each slot gets its own, nearer than the ones before it and every
+/// edge, so overlapping disks don't fight over who's in front.
+pub fn depth(slot: u32, count: usize) -> f32 {
+    EDGE_DEPTH * (1.0 - (slot + 1) as f32 / (count + 1) as f32)
+}
+
+/// Node under the world position `pos`, skipping `hidden` ones. Later nodes
+/// are drawn over earlier ones, so they win where shapes overlap. Missing
+/// every shape by up to `tolerance` world units still picks the node whose
+/// edge is closest.
+pub fn pick(
+    nodes: &[Node],
+    pos: cgmath::Vector2<f32>,
+    tolerance: f32,
+    hidden: &BTreeSet<u32>,
+) -> Option<u32> {
+    pick_among(nodes, (0..nodes.len() as u32).rev(), pos, tolerance, hidden)
+}
+
+/// `pick` over just the `candidates`, which are expected topmost first
+pub fn pick_among(
+    nodes: &[Node],
+    candidates: impl IntoIterator<Item = u32>,
+    pos: cgmath::Vector2<f32>,
+    tolerance: f32,
+    hidden: &BTreeSet<u32>,
+) -> Option<u32> {
+    let gaps = candidates
+        .into_iter()
+        .filter(|i| !hidden.contains(i))
+        .map(|i| (i, nodes[i as usize].gap(pos)));
+    let mut nearest: Option<(u32, f32)> = None;
+    for (i, gap) in gaps {
+        if gap <= 0.0 {
+            return Some(i);
+        }
+        if gap <= tolerance && nearest.is_none_or(|(_, best)| gap < best) {
+            nearest = Some((i, gap));
+        }
+    }
+    nearest.map(|(i, _)| i)
+}
+
+#[cfg(feature = "render")]
 impl NodeRaw {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+    const ATTRIBUTES: [wgpu::VertexAttribute; 13] = wgpu::vertex_attr_array![
         // model matrix
         2 => Float32x4,
         3 => Float32x4,
@@ -246,7 +596,15 @@ impl NodeRaw {
         5 => Float32x4,
         // color
         6 => Float32x4,
-        7 => Float32x4
+        7 => Float32x3,
+        8 => Float32,
+        9 => Float32,
+        10 => Float32,
+        11 => Float32,
+        12 => Float32,
+        13 => Uint32,
+        // image texture coordinates
+        14 => Float32x4,
     ];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -260,8 +618,68 @@ impl NodeRaw {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    #[cfg(feature = "render")]
     #[test]
     fn size() {
         println!("size: {}", std::mem::size_of::<super::NodeRaw>());
     }
+
+    #[test]
+    fn shapes_pick_by_their_outline() {
+        let mut node = Node::new(
+            (50.0, 50.0),
+            (0.0, 0.0, 0.0),
+            cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            (1.0, 1.0, 1.0, 1.0),
+        );
+        let corner = cgmath::vec2(45.0, 45.0);
+        let tip = cgmath::vec2(49.0, 0.0);
+        assert!(node.gap(corner) > 0.0 && node.gap(tip) < 0.0);
+        node.shape = Shape::Square;
+        assert!(node.gap(corner) < 0.0 && node.gap(tip) < 0.0);
+        node.shape = Shape::Diamond;
+        assert!(node.gap(corner) > node.gap(tip));
+        assert_eq!(node.gap(cgmath::vec2(60.0, 0.0)), 10.0);
+
+        assert!(Shape::ALL
+            .iter()
+            .all(|s| Shape::parse(s.name()) == Some(*s)));
+        assert_eq!(Shape::from_dot("box"), Some(Shape::Square));
+        assert_eq!(Shape::from_dot("record"), None);
+    }
+
+    #[test]
+    fn rescaling_keeps_proportions_and_bounds() {
+        let mut node = Node::new(
+            (20.0, 40.0),
+            (0.0, 0.0, 0.0),
+            cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            (1.0, 1.0, 1.0, 1.0),
+        );
+        assert!(node.rescale(0.5));
+        assert_eq!(node.size, cgmath::vec2(10.0, 20.0));
+        assert!(node.rescale(0.1));
+        assert_eq!(node.radius(), MIN_RADIUS);
+        assert!(!node.rescale(0.5), "already as small as it gets");
+        assert!(node.rescale(100.0));
+        assert_eq!(node.radius(), DISK_RADIUS);
+        assert_eq!(node.size.y, 2.0 * DISK_RADIUS);
+        assert!(!node.rescale(1.25));
+
+        // Default nodes have room to grow
+        let mut graph = crate::graph::Graph::new();
+        graph.add_named_node("a");
+        assert!(graph.nodes[0].rescale(1.25));
+    }
+
+    #[test]
+    fn later_slots_are_nearer() {
+        let depths: Vec<f32> = (0..4).map(|slot| depth(slot, 4)).collect();
+        assert!(depths.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(depths[0] < EDGE_DEPTH && depths[3] > 0.0);
+        // A node added since the last sort is still in front of the rest
+        assert!(depth(4, 5) < depths[3]);
+    }
 }
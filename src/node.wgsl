@@ -2,9 +2,12 @@ struct Camera {
     view_proj: mat4x4<f32>,
     dimensions: vec2<f32>,
     scale: f32,
+    time: f32,
 };
 
 @binding(0) @group(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var images: texture_2d<f32>;
+@group(1) @binding(1) var images_sampler: sampler;
 
 struct VertexInput {
     @location(0) pos: vec3<f32>
@@ -18,12 +21,70 @@ struct InstanceInput {
 
     @location(6) color: vec4<f32>,
     @location(7) center: vec3<f32>,
+    @location(8) ring: f32,
+    // Depth of its own, nearer the later the node is drawn
+    @location(9) depth: f32,
+    @location(10) pin: f32,
+    @location(11) pulse: f32,
+    @location(12) glow: f32,
+    // 0 circle, 1 square, 2 diamond
+    @location(13) shape: u32,
+    // Image's corners in the atlas as (u0, v0, u1, v1), all zero for none
+    @location(14) image: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
-    @location(1) center: vec2<f32>
+    @location(1) center: vec2<f32>,
+    @location(2) ring: f32,
+    @location(3) pin: f32,
+    @location(4) pulse: f32,
+    @location(5) @interpolate(flat) shape: u32,
+    @location(6) image: vec4<f32>,
+    // Position across the shape's bounding square, -1 to 1 with y up
+    @location(7) local: vec2<f32>,
+    // The shape's radius in pixels, which ring widths and the like are
+    // measured against
+    @location(8) radius: f32,
+}
+
+// Selection ring, matching the selection box's color
+let RING_COLOR: vec4<f32> = vec4<f32>(0.95, 0.69, 0.2, 1.0);
+// Ring thickness in pixels
+let RING_WIDTH: f32 = 3.0;
+// Dot marking a pinned node
+let PIN_COLOR: vec4<f32> = vec4<f32>(0.1, 0.1, 0.1, 0.9);
+// Dot radius in pixels
+let PIN_RADIUS: f32 = 5.0;
+// Ring throbbing on pulsing nodes, matching the error badge's color
+let PULSE_COLOR: vec4<f32> = vec4<f32>(0.95, 0.3, 0.25, 1.0);
+// Pulses per second
+let PULSE_RATE: f32 = 1.5;
+// How much wider than the selection ring the pulse swells, in pixels
+let PULSE_SWELL: f32 = 9.0;
+// Largest radius a node's shape is drawn at, `DISK_RADIUS` in node.rs
+let DISK_RADIUS: f32 = 200.0;
+
+#include "constants.wgsl"
+#include "color.wgsl"
+
+#include "glow.wgsl"
+
+// Distance from the center in radii, as the shape measures it: 1 on its
+// outline. `Shape::norm` in node.rs.
+fn shape_distance(shape: u32, p: vec2<f32>) -> f32 {
+    switch (shape) {
+        case 1u: {
+            return max(abs(p.x), abs(p.y));
+        }
+        case 2u: {
+            return abs(p.x) + abs(p.y);
+        }
+        default: {
+            return length(p);
+        }
+    }
 }
 
 @vertex
@@ -40,8 +101,19 @@ fn vs_main(in: VertexInput, instance: InstanceInput, @builtin(vertex_index) in_v
     // let pos = camera.view_proj * vec4<f32>(0.0, 0.0, 0.5, 1.0);
     // let pos = camera.view_proj * vec4<f32>(in.pos.xy, 0.9, 1.0);
     // let pos = camera.view_proj * vec4<f32>(in.pos.xy, 0.9, 1.0);
-    out.color = instance.color;
-    out.position = pos;
+    out.color = srgb_to_linear(brighten(instance.color, instance.glow));
+    out.position = vec4<f32>(pos.xy, instance.depth * pos.w, pos.w);
+    out.ring = instance.ring;
+    out.pin = instance.pin;
+    out.pulse = instance.pulse;
+    out.shape = instance.shape;
+    out.image = instance.image;
+    // The quad spans the node's size, its shape only the smallest of that
+    // and `DISK_RADIUS`, as `Node::radius` has it
+    let half = vec2<f32>(length(instance.model_matrix_0.xyz), length(instance.model_matrix_1.xyz));
+    let radius = min(min(half.x, half.y), DISK_RADIUS);
+    out.local = in.pos.xy * half / radius;
+    out.radius = radius * camera.scale;
 
     let center2 = vec4<f32>(0.0, 0.0, 0.0, 1.0);
     let center = (camera.view_proj * model * center2).xy;
@@ -61,11 +133,11 @@ fn vs_main(in: VertexInput, instance: InstanceInput, @builtin(vertex_index) in_v
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let radius = 50.0 * camera.scale;
-    let len = length(in.position.xy - in.center.xy);
-    let r = len / radius;
+    let radius = in.radius;
+    let offset = in.local;
+    let r = shape_distance(in.shape, offset);
 
-    // Distance function for disk: https://computergraphics.stackexchange.com/questions/10712/understanding-sdfsigned-distance-functions-for-a-torus
+    // Distance function for the shape: https://computergraphics.stackexchange.com/questions/10712/understanding-sdfsigned-distance-functions-for-a-torus
     var start: f32 = 0.0;
     var end: f32 = 0.0;
     var val: f32 = 0.0;
@@ -83,9 +155,35 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
     // let circle = (in.color * alpha);
     // let circle = in.color * alpha;
-    let circle = vec4<f32>(in.color.xyz, alpha);
-
-    return circle;
+    // The image fills the shape's bounding square over the node's color,
+    // sampled without derivatives as nodes without one branch around it
+    var fill = in.color.rgb;
+    if (in.image.z > in.image.x) {
+        let uv = mix(in.image.xy, in.image.zw, vec2<f32>(in.local.x, -in.local.y) * 0.5 + 0.5);
+        let texel = textureSampleLevel(images, images_sampler, uv, 0.0);
+        fill = mix(fill, texel.rgb, texel.a);
+    }
+    let circle = vec4<f32>(fill, in.color.a * alpha);
+
+    // Band just inside the edge, blended over the fill
+    let inner = 1.0 - RING_WIDTH / radius;
+    let band = smoothstep(inner - delta, inner + delta, r) * in.ring;
+    let ring = srgb_to_linear(RING_COLOR);
+    let ringed = vec4<f32>(mix(circle.rgb, ring.rgb, band), mix(circle.a, ring.a * alpha, band));
+
+    // Band swelling inwards and back and fading with it, over the selection
+    // ring so an alert shows on selected nodes too
+    let wave = 0.5 - 0.5 * cos(camera.time * PULSE_RATE * 6.2831853);
+    let swell = 1.0 - (RING_WIDTH + PULSE_SWELL * wave) / radius;
+    let throb = smoothstep(swell - delta, swell + delta, r) * in.pulse * (1.0 - 0.6 * wave);
+    let pulse = srgb_to_linear(PULSE_COLOR);
+    let pulsed = vec4<f32>(mix(ringed.rgb, pulse.rgb, throb), mix(ringed.a, pulse.a * alpha, throb));
+
+    // Round dot in the middle of pinned nodes, whatever their shape
+    let dot_edge = PIN_RADIUS / radius;
+    let dot = (1.0 - smoothstep(dot_edge - delta, dot_edge + delta, length(offset))) * in.pin;
+    let pin = srgb_to_linear(PIN_COLOR);
+    return vec4<f32>(mix(pulsed.rgb, pin.rgb, dot * pin.a), pulsed.a);
 }
 
 // @fragment
@@ -0,0 +1,303 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    font,
+    instancing::{self, Quad},
+    Vertex,
+};
+
+pub const DEFAULT_INSTANCE_BUFFER_CAP: usize = 4096;
+
+/// Draws flat colored rectangles in screen space on top of the graph. Overlay
+/// widgets (charts, etc.) push their geometry every frame.
+pub struct OverlayRenderPass {
+    pub rects: Vec<Rect>,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) quad: Quad,
+    pub(crate) instance_buffer: wgpu::Buffer,
+    /// More instances than the buffer holds as of the last `write`
+    overflowed: bool,
+}
+
+/// Rectangle in window pixels, origin at the top-left corner and y pointing down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub origin: cgmath::Vector2<f32>,
+    pub size: cgmath::Vector2<f32>,
+    pub color: cgmath::Vector4<f32>,
+}
+
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct RectRaw {
+    origin: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 4],
+}
+
+impl OverlayRenderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let pipeline = Self::create_pipeline(
+            device,
+            format,
+            camera_bind_group_layout,
+            include_str!("overlay.wgsl"),
+        );
+
+        let quad = Quad::new(device, "Overlay");
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Instance Buffer"),
+            size: (std::mem::size_of::<RectRaw>() * DEFAULT_INSTANCE_BUFFER_CAP) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            rects: vec![],
+            pipeline,
+            quad,
+            instance_buffer,
+            overflowed: false,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `overlay.wgsl` or a hot
+    /// reloaded edit of it. Overlays get a pass of their own on the final
+    /// target, after MSAA is resolved and the graph upscaled, so it draws
+    /// without either and at full resolution whatever the quality.
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), RectRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    pub fn push(&mut self, rect: Rect) {
+        self.rects.push(rect);
+    }
+
+    pub fn extend<I: IntoIterator<Item = Rect>>(&mut self, rects: I) {
+        self.rects.extend(rects);
+    }
+
+    /// Multiplies every rect's position and size, e.g. to go from logical to
+    /// physical pixels
+    pub fn scale(&mut self, factor: f32) {
+        for rect in self.rects.iter_mut() {
+            rect.origin *= factor;
+            rect.size *= factor;
+        }
+    }
+
+    fn instance_count(&self) -> u32 {
+        instancing::instance_count(self.rects.len(), DEFAULT_INSTANCE_BUFFER_CAP)
+    }
+
+    pub fn write(&mut self, queue: &wgpu::Queue) {
+        instancing::warn_overflow(
+            self.rects.len(),
+            DEFAULT_INSTANCE_BUFFER_CAP,
+            "rects",
+            &mut self.overflowed,
+        );
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(
+                &self.rects[..self.instance_count() as usize]
+                    .iter()
+                    .copied()
+                    .map(Rect::to_instance)
+                    .collect::<Vec<_>>(),
+            ),
+        );
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        if self.rects.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        self.quad.draw(
+            render_pass,
+            &self.instance_buffer,
+            std::mem::size_of::<RectRaw>(),
+            self.instance_count(),
+        );
+    }
+}
+
+impl Rect {
+    pub fn new<O, S, C>(origin: O, size: S, color: C) -> Self
+    where
+        O: Into<cgmath::Vector2<f32>>,
+        S: Into<cgmath::Vector2<f32>>,
+        C: Into<cgmath::Vector4<f32>>,
+    {
+        Self {
+            origin: origin.into(),
+            size: size.into(),
+            color: color.into(),
+        }
+    }
+
+    pub fn contains(&self, pos: &cgmath::Vector2<f32>) -> bool {
+        pos.x >= self.origin.x
+            && pos.x <= self.origin.x + self.size.x
+            && pos.y >= self.origin.y
+            && pos.y <= self.origin.y + self.size.y
+    }
+
+    pub(crate) fn to_instance(self) -> RectRaw {
+        RectRaw {
+            origin: self.origin.into(),
+            size: self.size.into(),
+            color: self.color.into(),
+        }
+    }
+}
+
+impl RectRaw {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x4,
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<RectRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Converts a mouse position (centered, y up, see `Mouse::pos`) into overlay
+/// space (top-left origin, y down)
+pub fn mouse_to_overlay_space(
+    width: f32,
+    height: f32,
+    pos: &cgmath::Vector2<f32>,
+) -> cgmath::Vector2<f32> {
+    cgmath::vec2(pos.x + width / 2.0, height / 2.0 - pos.y)
+}
+
+/// Rects drawing `text` with its top-left corner at `origin`, each font pixel
+/// being `pixel` window pixels wide
+pub fn text_rects(
+    text: &str,
+    origin: cgmath::Vector2<f32>,
+    pixel: f32,
+    color: cgmath::Vector4<f32>,
+) -> Vec<Rect> {
+    font::runs(text)
+        .into_iter()
+        .map(|run| {
+            Rect::new(
+                origin + cgmath::vec2(run.col as f32, run.row as f32) * pixel,
+                cgmath::vec2(run.len as f32, 1.0) * pixel,
+                color,
+            )
+        })
+        .collect()
+}
+
+/// Size in window pixels of `text` drawn with `text_rects`
+pub fn text_size(text: &str, pixel: f32) -> cgmath::Vector2<f32> {
+    let (w, h) = font::measure(text);
+    cgmath::vec2(w as f32, h as f32) * pixel
+}
+
+/// Window corner an overlay widget is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Parses `top-left`, `top-right`, `bottom-left` or `bottom-right`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "top-left" => Some(Anchor::TopLeft),
+            "top-right" => Some(Anchor::TopRight),
+            "bottom-left" => Some(Anchor::BottomLeft),
+            "bottom-right" => Some(Anchor::BottomRight),
+            _ => None,
+        }
+    }
+
+    /// Top-left corner of a widget of `size` placed `margin` pixels from the corner
+    pub fn place(
+        &self,
+        width: f32,
+        height: f32,
+        size: cgmath::Vector2<f32>,
+        margin: f32,
+    ) -> cgmath::Vector2<f32> {
+        match self {
+            Anchor::TopLeft => cgmath::vec2(margin, margin),
+            Anchor::TopRight => cgmath::vec2(width - size.x - margin, margin),
+            Anchor::BottomLeft => cgmath::vec2(margin, height - size.y - margin),
+            Anchor::BottomRight => cgmath::vec2(width - size.x - margin, height - size.y - margin),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    dimensions: vec2<f32>,
+    scale: f32,
+};
+
+@binding(0) @group(0) var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+};
+
+struct Rect {
+    @location(1) origin: vec2<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+#include "constants.wgsl"
+#include "color.wgsl"
+
+@vertex
+fn vs_main(in: VertexInput, rect: Rect) -> VertexOutput {
+    // camera.dimensions is twice the window size (see Camera::to_raw)
+    let window = camera.dimensions / 2.0;
+
+    // Quad corners are in [-1, 1], rects are in pixels with the origin top-left
+    let corner = vec2<f32>(in.pos.x * 0.5 + 0.5, 0.5 - in.pos.y * 0.5);
+    let px = rect.origin + corner * rect.size;
+    let clip = vec2<f32>((px.x / window.x) * 2.0 - 1.0, 1.0 - (px.y / window.y) * 2.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(clip, 0.0, 1.0);
+    out.color = srgb_to_linear(rect.color);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -1,16 +1,478 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::{edge::Edge, node::Node};
+use cgmath::{vec2, InnerSpace};
+
+use crate::{
+    algorithms,
+    edge::Edge,
+    graph::DEFAULT_NODE_SIZE,
+    node::Node,
+    quadtree::Quadtree,
+    rng::Rng,
+    spatial::{SpatialGrid, DEFAULT_CELL_SIZE},
+    ticker::DEFAULT_TICK_RATE,
+};
 
 pub const DEFAULT_STRENGTH: f32 = -100.0;
 pub const DEFAULT_MAX_DIST: f32 = 500.0;
 pub const DEFAULT_MIN_DIST: f32 = 200.0;
+/// Default `ForceParams::gravity`, enough to bring stray components back
+/// without squeezing connected ones
+pub const DEFAULT_GRAVITY: f32 = 0.002;
+/// Default `ForceParams::collision`
+pub const DEFAULT_COLLISION: f32 = 0.5;
+/// Default `ForceParams::collision_iterations`
+pub const DEFAULT_COLLISION_ITERATIONS: u32 = 1;
+/// Default radius of the random nudge applied by `Physics::shake`
+pub const DEFAULT_SHAKE: f32 = 50.0;
+/// Largest per tick movement, in world units, of a layout considered at rest.
+/// Well under a pixel at the default zoom.
+pub const SETTLED_STEP: f32 = 0.05;
+
+/// Share of every node's velocity lost each tick, see `Physics::velocity_decay`
+pub const DEFAULT_VELOCITY_DECAY: f32 = 0.4;
+/// Node attribute read as its mass, see `Object::mass`
+pub const MASS_ATTR: &str = "mass";
+
+/// Alpha a thrown node and its neighbourhood are reheated to
+pub const THROW_ALPHA: f32 = 0.3;
+/// Share of a local reheat lost each tick
+const HEAT_DECAY: f32 = 0.02;
+/// Alpha kept up while a magnet is held, so the nodes it moves push their
+/// neighbours along instead of bunching up on a cooled layout
+const MAGNET_ALPHA: f32 = 0.1;
+/// Share of the way to its ring a node covers each tick at full alpha
+const ANCHOR_PULL: f32 = 0.1;
+/// Like `ANCHOR_PULL` for a node's side of a `Partition`, weaker so links
+/// can still bend the layout
+const PARTITION_PULL: f32 = 0.02;
+/// Distance between the two sides of a `Partition`, in link distances
+pub const PARTITION_GAP: f32 = 2.0;
+/// Default `Repulsion::BarnesHut` threshold, coarse enough to pay off on big
+/// graphs while staying within a few percent of the exact forces
+pub const DEFAULT_THETA: f32 = 0.9;
+
+/// How repulsion is summed over the other nodes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repulsion {
+    /// Every pair of nodes, which is exact but costs the node count squared
+    Exact,
+    /// Groups of nodes whose quadtree cell looks smaller than `theta` times
+    /// its distance push as one body at their center of mass. 0 is exact.
+    BarnesHut { theta: f32 },
+}
+
+impl std::fmt::Display for Repulsion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repulsion::Exact => write!(f, "exact"),
+            Repulsion::BarnesHut { theta } => write!(f, "barnes-hut theta={theta}"),
+        }
+    }
+}
+
+/// Tunable constants of the force model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForceParams {
+    /// Links stop pulling once their ends are this close
+    pub link_distance: f32,
+    /// Nodes further apart than this don't repel each other
+    pub repulsion_range: f32,
+    /// Repulsion every node exerts, negative pushes apart. Both forces scale
+    /// with it, so it sets how fast the layout moves rather than its shape.
+    pub strength: f32,
+    /// Share of its distance to `Physics::focus` a node is pulled back each
+    /// tick at full alpha, which keeps disconnected components from drifting
+    /// off. 0 turns it off.
+    pub gravity: f32,
+    /// Share of the overlap between two disks undone per collision pass, 0
+    /// lets nodes overlap
+    pub collision: f32,
+    /// Collision passes per tick. More untangle crowds faster, as every pass
+    /// can push nodes into their other neighbours.
+    pub collision_iterations: u32,
+    pub repulsion: Repulsion,
+}
+
+impl Default for ForceParams {
+    fn default() -> Self {
+        Self {
+            link_distance: DEFAULT_MIN_DIST,
+            repulsion_range: DEFAULT_MAX_DIST,
+            strength: DEFAULT_STRENGTH,
+            gravity: DEFAULT_GRAVITY,
+            collision: DEFAULT_COLLISION,
+            collision_iterations: DEFAULT_COLLISION_ITERATIONS,
+            repulsion: Repulsion::Exact,
+        }
+    }
+}
+
+/// One of the `ForceParams`, for tuning them one at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceParam {
+    LinkDistance,
+    RepulsionRange,
+    Strength,
+    Gravity,
+    Collision,
+}
+
+impl ForceParams {
+    pub fn get(&self, param: ForceParam) -> f32 {
+        match param {
+            ForceParam::LinkDistance => self.link_distance,
+            ForceParam::RepulsionRange => self.repulsion_range,
+            ForceParam::Strength => self.strength,
+            ForceParam::Gravity => self.gravity,
+            ForceParam::Collision => self.collision,
+        }
+    }
+
+    /// These parameters with `param` changed to `value`
+    pub fn with(mut self, param: ForceParam, value: f32) -> Self {
+        match param {
+            ForceParam::LinkDistance => self.link_distance = value,
+            ForceParam::RepulsionRange => self.repulsion_range = value,
+            ForceParam::Strength => self.strength = value,
+            ForceParam::Gravity => self.gravity = value,
+            ForceParam::Collision => self.collision = value,
+        }
+        self
+    }
+
+    /// Picks parameters from the graph's node sizes and degree distribution.
+    /// Links are long enough for nodes to sit a few radii apart, and for the
+    /// busiest hubs (90th percentile degree) to fit their neighbors in a ring
+    /// around them. Edges count by their spring strength, so once `spring
+    /// weight=` has weighed them, heavy edges, which pull their nodes closer,
+    /// make more room than light ones. The defaults are what a graph of
+    /// default sized nodes with low degrees gets, and what an empty one gets.
+    pub fn calibrate(nodes: &[Node], edges: &[Edge]) -> Self {
+        let mut radii: Vec<f32> = nodes.iter().map(|n| n.size.x.max(n.size.y)).collect();
+        let radius = percentile(&mut radii, 0.5).unwrap_or(DEFAULT_NODE_SIZE);
+        let spacing = radius * DEFAULT_MIN_DIST / DEFAULT_NODE_SIZE;
+
+        let mut degrees = vec![0.0; nodes.len()];
+        for edge in edges {
+            degrees[edge.a_id as usize] += edge.strength;
+            degrees[edge.b_id as usize] += edge.strength;
+        }
+        let hub = percentile(&mut degrees, 0.9).unwrap_or(0.0);
+
+        let link_distance = spacing.max(hub * spacing / std::f32::consts::TAU);
+        let scale = link_distance / DEFAULT_MIN_DIST;
+        Self {
+            link_distance,
+            repulsion_range: DEFAULT_MAX_DIST * scale,
+            strength: DEFAULT_STRENGTH * scale,
+            ..Self::default()
+        }
+    }
+}
+
+impl std::fmt::Display for ForceParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "link={:.0} range={:.0} strength={:.1} gravity={} collision={} passes={} solver={}",
+            self.link_distance,
+            self.repulsion_range,
+            self.strength,
+            self.gravity,
+            self.collision,
+            self.collision_iterations,
+            self.repulsion
+        )
+    }
+}
+
+/// Solver settings suited to a graph's size, picked on load so big graphs
+/// stay interactive without any tuning: how repulsion is summed, whether
+/// nodes collide, both of which cost the node count squared when done
+/// exactly, and how often the simulation ticks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub repulsion: Repulsion,
+    pub collision: bool,
+    /// Ticks per second
+    pub tick_rate: f32,
+}
+
+impl Preset {
+    pub const SMALL: Self = Self {
+        name: "small",
+        repulsion: Repulsion::Exact,
+        collision: true,
+        tick_rate: DEFAULT_TICK_RATE,
+    };
+    pub const MEDIUM: Self = Self {
+        name: "medium",
+        repulsion: Repulsion::BarnesHut {
+            theta: DEFAULT_THETA,
+        },
+        collision: true,
+        tick_rate: DEFAULT_TICK_RATE,
+    };
+    pub const LARGE: Self = Self {
+        name: "large",
+        repulsion: Repulsion::BarnesHut {
+            theta: DEFAULT_THETA,
+        },
+        collision: false,
+        tick_rate: DEFAULT_TICK_RATE / 2.0,
+    };
+    pub const ALL: [Self; 3] = [Self::SMALL, Self::MEDIUM, Self::LARGE];
+
+    /// Most nodes and edges a `SMALL` graph has
+    pub const SMALL_LIMIT: (usize, usize) = (1_000, 5_000);
+    /// Most nodes and edges a `MEDIUM` graph has
+    pub const MEDIUM_LIMIT: (usize, usize) = (10_000, 50_000);
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name == name)
+    }
+
+    /// Smallest preset whose limits fit both counts
+    pub fn for_size(nodes: usize, edges: usize) -> Self {
+        let fits =
+            |(max_nodes, max_edges): (usize, usize)| nodes <= max_nodes && edges <= max_edges;
+        if fits(Self::SMALL_LIMIT) {
+            Self::SMALL
+        } else if fits(Self::MEDIUM_LIMIT) {
+            Self::MEDIUM
+        } else {
+            Self::LARGE
+        }
+    }
+
+    /// `params` with this preset's solver and collisions
+    pub fn apply(&self, params: ForceParams) -> ForceParams {
+        ForceParams {
+            repulsion: self.repulsion,
+            collision_iterations: if self.collision {
+                params.collision_iterations.max(1)
+            } else {
+                0
+            },
+            ..params
+        }
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} repulsion, collisions {}, {} Hz",
+            self.name,
+            self.repulsion,
+            if self.collision { "on" } else { "off" },
+            self.tick_rate
+        )
+    }
+}
+
+/// Value below which `fraction` of `values` fall, `None` when empty
+fn percentile(values: &mut [f32], fraction: f32) -> Option<f32> {
+    values.sort_by(f32::total_cmp);
+    let last = values.len().checked_sub(1)?;
+    Some(values[(last as f32 * fraction).round() as usize])
+}
 
 pub struct Physics {
     pub objs: Vec<Object>,
     pub alpha: f32,
     pub alpha_decay: f32,
     pub alpha_target: f32,
+    pub params: ForceParams,
+    /// Share of its velocity a node loses each tick. Higher settles sooner
+    /// with less overshoot, lower lets nodes coast.
+    pub velocity_decay: f32,
+    /// Nodes left out of `tick`, they keep their position but still push and
+    /// pull on everything else
+    pub frozen: BTreeSet<u32>,
+    /// Farthest any node moved during the last tick, in world units
+    pub last_step: f32,
+    /// How far each node moved during the last tick, what
+    /// `apply_interpolated` blends over
+    step: Vec<[f32; 3]>,
+    /// Pointer force applied on top of the others while it's set
+    pub magnet: Option<Magnet>,
+    /// Alpha of nodes reheated on their own, used where it's above the
+    /// global `alpha`. Fades out faster than it.
+    pub heat: BTreeMap<u32, f32>,
+    /// Root of a radial layout laid over the others while it's set
+    pub anchor: Option<Anchor>,
+    /// Sides of a bipartite layout kept apart while it's set
+    pub partition: Option<Partition>,
+    /// Where gravity pulls nodes to, see `ForceParams::gravity`
+    pub focus: cgmath::Vector2<f32>,
+    /// Only nodes inside it are simulated while it's set. The rest stay put
+    /// like `frozen` ones, so big graphs only pay for the part being looked at.
+    pub region: Option<Region>,
+    /// Where nodes are as of the last collision pass, so each one is only
+    /// checked against the nodes near it
+    collision_grid: SpatialGrid,
+}
+
+/// Default margin around the view a region of interest adds on each side,
+/// as a fraction of the view's size, so nodes just off screen settle too
+pub const DEFAULT_REGION_MARGIN: f32 = 0.5;
+
+/// Box in world space, see `Physics::region` and `NodeRenderPass::view`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min: cgmath::Vector2<f32>,
+    pub max: cgmath::Vector2<f32>,
+}
+
+impl Region {
+    /// `min`..`max` grown by `margin` of its size on each side
+    pub fn around(min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>, margin: f32) -> Self {
+        let grow = (max - min) * margin;
+        Self {
+            min: min - grow,
+            max: max + grow,
+        }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (self.min.x..=self.max.x).contains(&x) && (self.min.y..=self.max.y).contains(&y)
+    }
+
+    /// Whether any of the box `min`..`max` is inside
+    pub fn overlaps(&self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>) -> bool {
+        min.x <= self.max.x && max.x >= self.min.x && min.y <= self.max.y && max.y >= self.min.y
+    }
+}
+
+/// Pulls nodes within `radius` of `center` towards it, or pushes them away
+/// when `strength` is negative. Nodes move `strength` world units a tick at
+/// the center, falling off to nothing at the edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Magnet {
+    pub center: cgmath::Vector2<f32>,
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl Magnet {
+    /// Displacement of a node at `pos` over one tick. Pulled nodes stop at
+    /// the center rather than overshoot it.
+    pub fn pull(&self, pos: cgmath::Vector2<f32>) -> Option<cgmath::Vector2<f32>> {
+        let offset = self.center - pos;
+        let dist = offset.magnitude();
+        if dist >= self.radius || dist == 0.0 {
+            return None;
+        }
+        let step = (self.strength * (1.0 - dist / self.radius)).min(dist);
+        Some(offset * (step / dist))
+    }
+}
+
+/// Pins `node` at the origin and draws every node it reaches onto a ring
+/// around it, one `link_distance` further out per link between them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub node: u32,
+    /// Links from `node` by index, `None` for nodes in other components,
+    /// which are left to the other forces
+    pub hops: Vec<Option<u32>>,
+}
+
+impl Anchor {
+    pub fn new(node: u32, node_count: usize, edges: &[Edge]) -> Self {
+        Self {
+            node,
+            hops: algorithms::hops(node_count, edges, node),
+        }
+    }
+
+    /// Distance from the origin `node` settles at, given the link distance
+    pub fn radius(&self, node: u32, link_distance: f32) -> Option<f32> {
+        let hops = self.hops.get(node as usize).copied().flatten()?;
+        Some(hops as f32 * link_distance)
+    }
+}
+
+/// How the sides of a `Partition` are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BipartiteShape {
+    /// Side by side, the first on the left
+    Columns,
+    /// Around the origin, the first inside
+    Rings,
+}
+
+/// Splits nodes in two by an attribute with two values, the lower one first.
+/// While it's set on `Physics` each side is drawn to its column or ring, see
+/// `layout::bipartite_placement`, and nodes without the attribute are left
+/// to the other forces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    pub attr: String,
+    pub shape: BipartiteShape,
+    /// Side of each node by index
+    pub sides: Vec<Option<usize>>,
+    /// Nodes on each side
+    counts: [usize; 2],
+}
+
+impl Partition {
+    /// Fails when `attr` has more than two values, or fewer
+    pub fn new(nodes: &[Node], attr: &str, shape: BipartiteShape) -> Result<Self, String> {
+        let values: BTreeSet<String> = nodes
+            .iter()
+            .filter_map(|node| Some(node.attributes.get(attr)?.to_string()))
+            .collect();
+        if values.len() != 2 {
+            return Err(format!(
+                "{attr} has {} values, a bipartite layout needs 2",
+                values.len()
+            ));
+        }
+        let sides: Vec<Option<usize>> = nodes
+            .iter()
+            .map(|node| {
+                let value = node.attributes.get(attr)?.to_string();
+                values.iter().position(|v| *v == value)
+            })
+            .collect();
+        let mut counts = [0; 2];
+        for side in sides.iter().flatten() {
+            counts[*side] += 1;
+        }
+        Ok(Self {
+            attr: attr.to_string(),
+            shape,
+            sides,
+            counts,
+        })
+    }
+
+    pub fn side(&self, node: u32) -> Option<usize> {
+        self.sides.get(node as usize).copied().flatten()
+    }
+
+    /// Where `side` sits across the partition, given the link distance: its
+    /// column's x, or its ring's radius. Rings are big enough to fit their
+    /// nodes a link distance apart.
+    pub fn offset(&self, side: usize, link_distance: f32) -> f32 {
+        let gap = PARTITION_GAP * link_distance;
+        match self.shape {
+            BipartiteShape::Columns => (side as f32 - 0.5) * gap,
+            BipartiteShape::Rings => {
+                let fit = |count: usize| count as f32 * link_distance / std::f32::consts::TAU;
+                let inner = fit(self.counts[0]).max(link_distance);
+                match side {
+                    0 => inner,
+                    _ => fit(self.counts[1]).max(inner + gap),
+                }
+            }
+        }
+    }
 }
 
 pub struct Object {
@@ -19,6 +481,20 @@ pub struct Object {
     pub y: f32,
     pub z: f32,
     pub strength: f32,
+    /// Velocity in world units per tick. Forces add to it, divided by
+    /// `mass`, and `Physics::velocity_decay` slows it down.
+    pub vx: f32,
+    pub vy: f32,
+    pub vz: f32,
+    /// How hard the node is to move, 1 unless it has a positive `MASS_ATTR`
+    pub mass: f32,
+    /// Radius of the node's disk, `Node::radius` of its size, what collisions
+    /// keep apart
+    pub radius: f32,
+    /// Pinned in place. Like a node in `Physics::frozen` it still pushes and
+    /// pulls on the others, but it's pinned on its own rather than with its
+    /// component, and reheating doesn't let it go.
+    pub fixed: bool,
 }
 
 impl Physics {
@@ -26,100 +502,529 @@ impl Physics {
     pub fn new(nodes: &[Node]) -> Self {
         Self {
             objs: nodes
-                .into_iter()
+                .iter()
                 .enumerate()
                 .map(|(i, node)| Object::from_node(i as u32, node, DEFAULT_STRENGTH))
                 .collect(),
             alpha: 1.0,
             alpha_decay: (1.0 - Self::ALPHA_MIN.powf(1.0 / 900.0)) / 100.0,
             alpha_target: 0.0,
+            params: ForceParams::default(),
+            velocity_decay: DEFAULT_VELOCITY_DECAY,
+            frozen: BTreeSet::new(),
+            last_step: f32::INFINITY,
+            step: vec![],
+            magnet: None,
+            heat: BTreeMap::new(),
+            anchor: None,
+            partition: None,
+            focus: vec2(0.0, 0.0),
+            region: None,
+            collision_grid: SpatialGrid::default(),
         }
     }
 
+    /// Whether ticking any further would move nodes by less than
+    /// `SETTLED_STEP`, so frames can stop being drawn until something changes
+    pub fn is_settled(&self) -> bool {
+        let alpha = self.heat.values().fold(self.alpha, |a, b| a.max(*b));
+        alpha <= Self::ALPHA_MIN || self.last_step < SETTLED_STEP
+    }
+
+    /// Whether each node stays where it is this tick: `held` (being dragged),
+    /// frozen, pinned or outside the `region`
+    fn still(&self, held: &BTreeSet<u32>) -> Vec<bool> {
+        self.objs
+            .iter()
+            .map(|obj| {
+                held.contains(&obj.i)
+                    || self.frozen.contains(&obj.i)
+                    || obj.fixed
+                    || self
+                        .region
+                        .is_some_and(|region| !region.contains(obj.x, obj.y))
+            })
+            .collect()
+    }
+
+    /// Alpha that scales the forces on `node`
+    fn node_alpha(&self, node: u32) -> f32 {
+        self.heat
+            .get(&node)
+            .map_or(self.alpha, |heat| heat.max(self.alpha))
+    }
+
+    /// Steps the simulation once, leaving the `held` nodes (being dragged),
+    /// frozen, pinned and out of region ones where they are. Every force
+    /// accelerates nodes except the magnet, which moves them directly as
+    /// it's meant to feel like pushing them around.
     pub fn tick(
         &mut self,
-        dragging: Option<u32>,
+        held: &BTreeSet<u32>,
         edges: &[Edge],
         edge_map: &BTreeMap<u32, Vec<u32>>,
     ) {
-        // self.alpha += (self.alpha_target - self.alpha) * self.alpha_decay;
-        self.alpha = 1.0;
+        self.alpha += (self.alpha_target - self.alpha) * self.alpha_decay;
+        if self.magnet.is_some() {
+            self.alpha = self.alpha.max(MAGNET_ALPHA);
+        }
+        let before: Vec<[f32; 3]> = self.objs.iter().map(|o| [o.x, o.y, o.z]).collect();
+        let still = self.still(held);
 
-        let dragging = dragging.map(|x| x as usize).unwrap_or(usize::MAX);
         let len = self.objs.len();
-        for i in 0..len {
-            if i == dragging {
+        let tree = match self.params.repulsion {
+            Repulsion::BarnesHut { theta } if theta > 0.0 => Some((self.cell_tree(), theta)),
+            _ => None,
+        };
+        for i in (0..len).filter(|i| !still[*i]) {
+            let alpha = self.node_alpha(i as u32);
+            if let Some(((tree, strengths), theta)) = &tree {
+                let [x, y, z] = self.barnes_hut(i, tree, strengths, *theta, alpha);
+                let obj = &mut self.objs[i];
+                obj.vx += x / obj.mass;
+                obj.vy += y / obj.mass;
+                obj.vz += z / obj.mass;
                 continue;
             }
             for j in 0..len {
                 let obj = unsafe { self.objs.get_unchecked(i) };
                 let other = unsafe { self.objs.get_unchecked(j) };
-                let idx = obj.i;
-                let x = obj.x;
-                let y = obj.y;
-                let z = obj.z;
-                if idx == other.i {
-                    continue;
-                }
-
-                let dx = x - other.x;
-                let dy = y - other.y;
-                let dz = z - other.z;
-                let dist = (dx * dx + dy * dy + dz * dz).sqrt();
-                if dist >= DEFAULT_MAX_DIST || dist.is_nan() {
+                let Some([x, y, z]) = self.repulsion(obj, other, alpha) else {
                     continue;
-                }
-                let force = other.strength * (self.alpha / dist);
-                let force_x = force * dx * (self.alpha / dist);
-                let force_y = force * dy * (self.alpha / dist);
-                let force_z = force * dz * (self.alpha / dist);
+                };
 
                 let obj = unsafe { self.objs.get_unchecked_mut(i) };
 
-                obj.x -= force_x;
-                obj.y -= force_y;
-                obj.z -= force_z;
+                obj.vx += x / obj.mass;
+                obj.vy += y / obj.mass;
+                obj.vz += z / obj.mass;
             }
         }
 
         for (&node, connections) in edge_map.iter() {
-            if node == dragging as u32 {
+            if still[node as usize] {
                 continue;
             }
 
             for other_id in connections {
                 let edge = &edges[*other_id as usize];
                 let a = &self.objs[node as usize];
-                let b = if node == edge.a_id {
-                    &self.objs[edge.b_id as usize]
-                } else {
-                    &self.objs[edge.a_id as usize]
+                let b = &self.objs[edge.other(node) as usize];
+                let Some([x, y, z]) = attraction(a, b, edge, self.params.link_distance) else {
+                    continue;
+                };
+
+                let a = &mut self.objs[node as usize];
+
+                a.vx += x / a.mass;
+                a.vy += y / a.mass;
+                a.vz += z / a.mass;
+            }
+        }
+
+        if self.params.gravity > 0.0 {
+            for i in 0..self.objs.len() {
+                let alpha = self.node_alpha(self.objs[i].i);
+                let obj = &mut self.objs[i];
+                if still[obj.i as usize] {
+                    continue;
+                }
+                let pull = self.params.gravity * alpha;
+                obj.vx += (self.focus.x - obj.x) * pull;
+                obj.vy += (self.focus.y - obj.y) * pull;
+            }
+        }
+
+        if let Some(anchor) = &self.anchor {
+            for i in 0..self.objs.len() {
+                let alpha = self.node_alpha(self.objs[i].i);
+                let obj = &mut self.objs[i];
+                if still[obj.i as usize] {
+                    continue;
+                }
+                if obj.i == anchor.node {
+                    (obj.x, obj.y) = (0.0, 0.0);
+                    (obj.vx, obj.vy) = (0.0, 0.0);
+                    continue;
+                }
+                let Some(radius) = anchor.radius(obj.i, self.params.link_distance) else {
+                    continue;
                 };
+                let dist = obj.x.hypot(obj.y);
+                if dist == 0.0 {
+                    continue;
+                }
+                let step = (radius - dist) * ANCHOR_PULL * alpha / dist;
+                obj.vx += obj.x * step;
+                obj.vy += obj.y * step;
+            }
+        }
 
-                let dx = a.x - b.x;
-                let dy = a.y - b.y;
-                let dz = a.z - b.z;
-                let dist = (dx * dx + dy * dy + dz * dz).sqrt();
-                if dist.is_nan() {
+        if let Some(partition) = &self.partition {
+            for i in 0..self.objs.len() {
+                let alpha = self.node_alpha(self.objs[i].i);
+                let obj = &mut self.objs[i];
+                if still[obj.i as usize] {
                     continue;
                 }
-                if dist <= DEFAULT_MIN_DIST {
+                let Some(side) = partition.side(obj.i) else {
                     continue;
+                };
+                let target = partition.offset(side, self.params.link_distance);
+                match partition.shape {
+                    BipartiteShape::Columns => obj.vx += (target - obj.x) * PARTITION_PULL * alpha,
+                    BipartiteShape::Rings => {
+                        let dist = obj.x.hypot(obj.y);
+                        if dist > 0.0 {
+                            let step = (target - dist) * PARTITION_PULL * alpha / dist;
+                            obj.vx += obj.x * step;
+                            obj.vy += obj.y * step;
+                        }
+                    }
                 }
+            }
+        }
 
-                let dist = dist * 0.00001;
-                let force = -a.strength * dist;
-                let force_x = (force * dx) * dist;
-                let force_y = (force * dy) * dist;
-                let force_z = (force * dz) * dist;
+        let keep = 1.0 - self.velocity_decay;
+        for obj in self.objs.iter_mut() {
+            if still[obj.i as usize] {
+                (obj.vx, obj.vy, obj.vz) = (0.0, 0.0, 0.0);
+                continue;
+            }
+            obj.vx *= keep;
+            obj.vy *= keep;
+            obj.vz *= keep;
+            obj.x += obj.vx;
+            obj.y += obj.vy;
+            obj.z += obj.vz;
+            // What's left would only creep, stop rather than decay forever
+            if (obj.vx * obj.vx + obj.vy * obj.vy + obj.vz * obj.vz).sqrt() < SETTLED_STEP {
+                (obj.vx, obj.vy, obj.vz) = (0.0, 0.0, 0.0);
+            }
+        }
 
-                let a = &mut self.objs[node as usize];
+        for _ in 0..self.params.collision_iterations {
+            self.collide(&still);
+        }
 
-                a.x -= force_x;
-                a.y -= force_y;
-                a.z -= force_z;
+        if let Some(magnet) = self.magnet {
+            for obj in self.objs.iter_mut() {
+                if still[obj.i as usize] {
+                    continue;
+                }
+                if let Some(pull) = magnet.pull(vec2(obj.x, obj.y)) {
+                    obj.x += pull.x;
+                    obj.y += pull.y;
+                }
             }
         }
+
+        self.heat.retain(|_, heat| {
+            *heat *= 1.0 - HEAT_DECAY;
+            *heat > Self::ALPHA_MIN
+        });
+
+        self.step = self
+            .objs
+            .iter()
+            .zip(before)
+            .map(|(o, [x, y, z])| [o.x - x, o.y - y, o.z - z])
+            .collect();
+        self.last_step = self
+            .step
+            .iter()
+            .map(|[dx, dy, dz]| (dx * dx + dy * dy + dz * dz).sqrt())
+            .fold(0.0, f32::max);
+    }
+
+    /// Pushes overlapping disks apart by `ForceParams::collision` of their
+    /// overlap, the lighter one further. Nodes that stay put push the other
+    /// one the whole way. Each node is only checked against those filed near
+    /// it in `collision_grid`, pairs in index order as a full scan would.
+    fn collide(&mut self, still: &[bool]) {
+        let strength = self.params.collision;
+        if strength <= 0.0 {
+            return;
+        }
+        // Cells as wide as the widest disk, so nothing overlapping a node
+        // is more than a couple of cells away from it
+        let widest = self.objs.iter().map(|obj| obj.radius).fold(0.0, f32::max);
+        let cell_size = (2.0 * widest).max(DEFAULT_CELL_SIZE);
+        if self.collision_grid.cell_size() != cell_size {
+            self.collision_grid = SpatialGrid::new(cell_size);
+        }
+        self.collision_grid
+            .sync_points(self.objs.iter().map(|obj| vec2(obj.x, obj.y)));
+        let len = self.objs.len();
+        for i in 0..len {
+            let obj = &self.objs[i];
+            let mut near = self
+                .collision_grid
+                .near(vec2(obj.x, obj.y), obj.radius + widest);
+            near.retain(|j| *j as usize > i);
+            near.sort_unstable();
+            for j in near {
+                let j = j as usize;
+                let (a, b) = (&self.objs[i], &self.objs[j]);
+                let (mut dx, mut dy) = (b.x - a.x, b.y - a.y);
+                let reach = a.radius + b.radius;
+                if dx.abs() >= reach || dy.abs() >= reach || (still[i] && still[j]) {
+                    continue;
+                }
+                let mut dist = dx.hypot(dy);
+                if dist >= reach {
+                    continue;
+                }
+                if dist == 0.0 {
+                    // Stacked exactly, any direction will do as long as it's
+                    // the same every run
+                    let angle = j as f32 * std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+                    (dx, dy, dist) = (angle.cos(), angle.sin(), 1.0);
+                }
+                let push = (reach - dist) * strength / dist;
+                let share = match (still[i], still[j]) {
+                    (true, _) => 0.0,
+                    (_, true) => 1.0,
+                    _ => b.mass / (a.mass + b.mass),
+                };
+                let a = &mut self.objs[i];
+                a.x -= dx * push * share;
+                a.y -= dy * push * share;
+                let b = &mut self.objs[j];
+                b.x += dx * push * (1.0 - share);
+                b.y += dy * push * (1.0 - share);
+            }
+        }
+    }
+
+    /// Forgets the last tick's movement, for when nodes were moved some other
+    /// way and blending from where the tick left them would be wrong
+    pub fn clear_step(&mut self) {
+        self.step.clear();
+    }
+
+    /// Displacement of `obj` caused by `other` pushing it away, at a given
+    /// alpha
+    fn repulsion(&self, obj: &Object, other: &Object, alpha: f32) -> Option<[f32; 3]> {
+        if obj.i == other.i {
+            return None;
+        }
+
+        self.push(
+            [obj.x - other.x, obj.y - other.y, obj.z - other.z],
+            other.strength,
+            alpha,
+        )
+    }
+
+    /// Displacement of a node `[dx, dy, dz]` away from something pushing with
+    /// `strength`, at a given alpha
+    fn push(&self, [dx, dy, dz]: [f32; 3], strength: f32, alpha: f32) -> Option<[f32; 3]> {
+        let dist = (dx * dx + dy * dy + dz * dz).sqrt();
+        // Stacked nodes have no direction to push in, collisions part them
+        if dist >= self.params.repulsion_range || dist.is_nan() || dist == 0.0 {
+            return None;
+        }
+        let force = strength * (alpha / dist);
+        Some([
+            -(force * dx * (alpha / dist)),
+            -(force * dy * (alpha / dist)),
+            -(force * dz * (alpha / dist)),
+        ])
+    }
+
+    /// Quadtree over the nodes, with the summed strength of every cell
+    fn cell_tree(&self) -> (Quadtree, Vec<f32>) {
+        let positions: Vec<_> = self.objs.iter().map(|o| vec2(o.x, o.y)).collect();
+        let tree = Quadtree::build(&positions);
+        let mut strengths = vec![0.0; tree.cells.len()];
+        // Children come after their parents
+        for (i, cell) in tree.cells.iter().enumerate().rev() {
+            strengths[i] = cell
+                .nodes
+                .iter()
+                .map(|n| self.objs[*n as usize].strength)
+                .chain(cell.children.iter().map(|c| strengths[*c as usize]))
+                .sum();
+        }
+        (tree, strengths)
+    }
+
+    /// Repulsion on node `i` with far away cells of `tree` standing in for
+    /// the nodes in them, see `Repulsion::BarnesHut`
+    fn barnes_hut(
+        &self,
+        i: usize,
+        tree: &Quadtree,
+        strengths: &[f32],
+        theta: f32,
+        alpha: f32,
+    ) -> [f32; 3] {
+        let obj = &self.objs[i];
+        let pos = vec2(obj.x, obj.y);
+        let range = self.params.repulsion_range;
+        let mut total = [0.0; 3];
+        let mut add = |force: Option<[f32; 3]>| {
+            if let Some(force) = force {
+                total = [
+                    total[0] + force[0],
+                    total[1] + force[1],
+                    total[2] + force[2],
+                ];
+            }
+        };
+        let mut stack = if tree.cells.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(c) = stack.pop() {
+            let cell = &tree.cells[c as usize];
+            // Nothing in a cell out of range can reach the node
+            let nearest = vec2(
+                pos.x.clamp(cell.min.x, cell.min.x + cell.size),
+                pos.y.clamp(cell.min.y, cell.min.y + cell.size),
+            );
+            if (nearest - pos).magnitude() >= range {
+                continue;
+            }
+            if cell.children.is_empty() {
+                for n in &cell.nodes {
+                    add(self.repulsion(obj, &self.objs[*n as usize], alpha));
+                }
+                continue;
+            }
+            let offset = pos - cell.center_of_mass;
+            if cell.size < theta * offset.magnitude() {
+                add(self.push([offset.x, offset.y, 0.0], strengths[c as usize], alpha));
+            } else {
+                stack.extend(&cell.children);
+            }
+        }
+        total
+    }
+
+    /// What the next tick would add to `node`'s velocity before its mass is
+    /// taken into account, split into repulsion from every other node and
+    /// attraction along its links. Frozen and dragged nodes are included even
+    /// though `tick` leaves them in place.
+    pub fn forces(
+        &self,
+        node: u32,
+        edges: &[Edge],
+        edge_map: &BTreeMap<u32, Vec<u32>>,
+    ) -> (cgmath::Vector2<f32>, cgmath::Vector2<f32>) {
+        let obj = &self.objs[node as usize];
+        let alpha = self.node_alpha(node);
+        let repulsion = self
+            .objs
+            .iter()
+            .filter_map(|other| self.repulsion(obj, other, alpha))
+            .fold(vec2(0.0, 0.0), |sum, [x, y, _]| sum + vec2(x, y));
+        let links = edge_map
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .filter_map(|edge| {
+                let edge = &edges[*edge as usize];
+                let other = &self.objs[edge.other(node) as usize];
+                attraction(obj, other, edge, self.params.link_distance)
+            })
+            .fold(vec2(0.0, 0.0), |sum, [x, y, _]| sum + vec2(x, y));
+        (repulsion, links)
+    }
+
+    /// Replaces the force parameters, including every node's strength
+    pub fn set_params(&mut self, params: ForceParams) {
+        self.params = params;
+        for obj in self.objs.iter_mut() {
+            obj.strength = params.strength;
+        }
+    }
+
+    /// Changes a single force parameter, see `set_params`
+    pub fn set_param(&mut self, param: ForceParam, value: f32) {
+        self.set_params(self.params.with(param, value));
+    }
+
+    /// Pins `node` if it isn't, unpins it if it is, and returns whether it's
+    /// pinned now
+    pub fn toggle_pin(&mut self, node: u32) -> bool {
+        let obj = &mut self.objs[node as usize];
+        obj.fixed = !obj.fixed;
+        (obj.vx, obj.vy, obj.vz) = (0.0, 0.0, 0.0);
+        let fixed = obj.fixed;
+        // Let go, it may have somewhere to be
+        self.last_step = f32::INFINITY;
+        fixed
+    }
+
+    /// Takes up the nodes' current sizes for collisions, which style rules
+    /// may have changed
+    pub fn resize(&mut self, nodes: &[Node]) {
+        for (obj, node) in self.objs.iter_mut().zip(nodes) {
+            obj.radius = node.radius();
+        }
+    }
+
+    /// Every pinned node, see `Object::fixed`
+    pub fn pinned(&self) -> BTreeSet<u32> {
+        self.objs
+            .iter()
+            .filter(|obj| obj.fixed)
+            .map(|obj| obj.i)
+            .collect()
+    }
+
+    /// Restores full strength to every force after the layout has cooled down
+    pub fn reheat(&mut self) {
+        self.alpha = 1.0;
+    }
+
+    /// Sets `node` moving at `velocity` world units per tick, as when a
+    /// dragged node is let go mid-motion, and reheats just `neighborhood`
+    /// around it. Throws are capped to travel less than one repulsion range.
+    pub fn throw(
+        &mut self,
+        node: u32,
+        velocity: cgmath::Vector2<f32>,
+        neighborhood: impl IntoIterator<Item = u32>,
+    ) {
+        let max_speed = self.params.repulsion_range * self.velocity_decay;
+        let speed = velocity.magnitude();
+        let velocity = if speed > max_speed {
+            velocity * (max_speed / speed)
+        } else {
+            velocity
+        };
+        let obj = &mut self.objs[node as usize];
+        (obj.vx, obj.vy) = (velocity.x, velocity.y);
+        for i in neighborhood {
+            let heat = self.heat.entry(i).or_insert(0.0);
+            *heat = heat.max(THROW_ALPHA);
+        }
+        self.last_step = f32::INFINITY;
+    }
+
+    /// Moves nodes by a random offset of at most `radius` and reheats, which
+    /// helps a tangled layout out of a local minimum. Only nodes in `only` are
+    /// moved when it's given, frozen and pinned nodes never are.
+    pub fn shake(&mut self, rng: &mut Rng, radius: f32, only: Option<&BTreeSet<u32>>) {
+        for obj in self.objs.iter_mut() {
+            if only.is_some_and(|only| !only.contains(&obj.i))
+                || self.frozen.contains(&obj.i)
+                || obj.fixed
+            {
+                continue;
+            }
+            // Uniform over the disk, see `layout::random_placement`
+            let r = radius * rng.next_f32().sqrt();
+            let angle = rng.range(0.0, std::f32::consts::TAU);
+            obj.x += r * angle.cos();
+            obj.y += r * angle.sin();
+        }
+        self.clear_step();
+        self.reheat();
     }
 
     pub fn apply(
@@ -127,23 +1032,65 @@ impl Physics {
         nodes: &mut [Node],
         edges: &mut [Edge],
         edge_map: &BTreeMap<u32, Vec<u32>>,
+    ) {
+        self.apply_interpolated(1.0, nodes, edges, edge_map);
+    }
+
+    /// Like `apply`, but places nodes `t` of the way through the last tick's
+    /// movement, for drawing between ticks when they run slower than frames
+    pub fn apply_interpolated(
+        &self,
+        t: f32,
+        nodes: &mut [Node],
+        edges: &mut [Edge],
+        edge_map: &BTreeMap<u32, Vec<u32>>,
     ) {
         assert_eq!(nodes.len(), self.objs.len());
 
         for (i, obj) in self.objs.iter().enumerate() {
             let node = unsafe { nodes.get_unchecked_mut(i) };
             obj.apply(node);
+            if let Some([dx, dy, dz]) = self.step.get(i).filter(|_| t < 1.0) {
+                let back = 1.0 - t;
+                node.position.x -= dx * back;
+                node.position.y -= dy * back;
+                node.position.z -= dz * back;
+            }
 
-            edge_map.get(&(i as u32)).map(|node_edges| {
+            if let Some(node_edges) = edge_map.get(&(i as u32)) {
                 node_edges.iter().for_each(|edge_id| {
                     let edge = unsafe { edges.get_unchecked_mut(*edge_id as usize) };
                     obj.apply_edge(i as u32, node, edge);
                 })
-            });
+            }
         }
     }
 }
 
+/// Displacement of `a` caused by `edge` pulling it towards `b`, nothing once
+/// they're within the edge's rest length, `link_distance` unless it has its
+/// own
+fn attraction(a: &Object, b: &Object, edge: &Edge, link_distance: f32) -> Option<[f32; 3]> {
+    let dx = a.x - b.x;
+    let dy = a.y - b.y;
+    let dz = a.z - b.z;
+    let dist = (dx * dx + dy * dy + dz * dz).sqrt();
+    if dist.is_nan() {
+        return None;
+    }
+    if dist <= edge.rest_length.unwrap_or(link_distance) {
+        return None;
+    }
+
+    let dist = dist * 0.00001;
+    let force = -a.strength * edge.strength * dist;
+    Some([
+        -((force * dx) * dist),
+        -((force * dy) * dist),
+        -((force * dz) * dist),
+    ])
+}
+
 impl Object {
     pub fn from_node(i: u32, node: &Node, strength: f32) -> Self {
         Self {
@@ -152,6 +1099,17 @@ impl Object {
             z: node.position.z,
             strength,
             i,
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            mass: node
+                .attributes
+                .get(MASS_ATTR)
+                .and_then(|v| v.as_number())
+                .filter(|mass| *mass > 0.0)
+                .unwrap_or(1.0),
+            radius: node.radius(),
+            fixed: false,
         }
     }
 
@@ -163,9 +1121,534 @@ impl Object {
 
     pub fn apply_edge(&self, id: u32, node: &Node, edge: &mut Edge) {
         if edge.a_id == id {
-            edge.a_center = node.position.clone();
+            edge.a_center = node.position;
         } else if edge.b_id == id {
-            edge.b_center = node.position.clone();
+            edge.b_center = node.position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn calibration_makes_room_for_hubs() {
+        let mut path = Graph::new();
+        let nodes: Vec<u32> = (0..5)
+            .map(|i| path.add_named_node(&i.to_string()))
+            .collect();
+        for pair in nodes.windows(2) {
+            path.add_edge(pair[0], pair[1]);
+        }
+        assert_eq!(
+            ForceParams::calibrate(&path.nodes, &path.edges),
+            ForceParams::default()
+        );
+
+        // Every node is a hub with 19 neighbors
+        let mut clique = Graph::new();
+        let nodes: Vec<u32> = (0..20)
+            .map(|i| clique.add_named_node(&i.to_string()))
+            .collect();
+        for (i, a) in nodes.iter().enumerate() {
+            for b in &nodes[i + 1..] {
+                clique.add_edge(*a, *b);
+            }
+        }
+        let params = ForceParams::calibrate(&clique.nodes, &clique.edges);
+        assert!(params.link_distance > DEFAULT_MIN_DIST * 3.0);
+        assert_eq!(
+            params.repulsion_range / params.link_distance,
+            DEFAULT_MAX_DIST / DEFAULT_MIN_DIST
+        );
+        assert!(params.strength < DEFAULT_STRENGTH);
+
+        // Edges twice as strong pull the neighbors in twice as close
+        for edge in clique.edges.iter_mut() {
+            edge.strength = 2.0;
         }
+        let heavy = ForceParams::calibrate(&clique.nodes, &clique.edges);
+        assert_eq!(heavy.link_distance, params.link_distance * 2.0);
+    }
+
+    #[test]
+    fn alpha_cools_until_reheated() {
+        let mut physics = Physics::new(&[]);
+        let mut last = physics.alpha;
+        for _ in 0..1000 {
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+            assert!(physics.alpha < last);
+            last = physics.alpha;
+        }
+        assert!(physics.alpha > 0.9, "{}", physics.alpha);
+        // Down to `ALPHA_MIN` over about 90 000 ticks
+        for _ in 0..90_000 {
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        }
+        assert!(
+            physics.alpha < Physics::ALPHA_MIN * 1.1,
+            "{}",
+            physics.alpha
+        );
+
+        physics.reheat();
+        assert_eq!(physics.alpha, 1.0);
+        physics.alpha_target = 1.0;
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert_eq!(physics.alpha, 1.0);
+    }
+
+    #[test]
+    fn shaking_moves_the_loose_nodes_it_is_given() {
+        let mut graph = Graph::new();
+        for name in ["shaken", "pinned", "left out", "frozen"] {
+            graph.add_named_node(name);
+        }
+        let mut physics = Physics::new(&graph.nodes);
+        physics.toggle_pin(1);
+        physics.frozen.insert(3);
+        physics.alpha = 0.0;
+        let only = BTreeSet::from([0, 1, 3]);
+        physics.shake(&mut Rng::new(7), 50.0, Some(&only));
+
+        let moved: Vec<f32> = physics.objs.iter().map(|o| o.x.hypot(o.y)).collect();
+        assert!(moved[0] > 0.0 && moved[0] <= 50.0, "{}", moved[0]);
+        assert_eq!(&moved[1..], [0.0, 0.0, 0.0]);
+        assert_eq!(physics.alpha, 1.0);
+    }
+
+    #[test]
+    fn held_nodes_stay_where_they_are_put() {
+        let mut graph = Graph::new();
+        for (name, x) in [("a", 0.0), ("b", 10.0), ("c", 20.0)] {
+            let node = graph.add_named_node(name);
+            graph.nodes[node as usize].position = cgmath::vec3(x, 0.0, 0.0);
+        }
+        let mut physics = Physics::new(&graph.nodes);
+        physics.alpha_target = 1.0;
+        let held = BTreeSet::from([0, 1]);
+        for _ in 0..10 {
+            physics.tick(&held, &[], &BTreeMap::new());
+        }
+        let xs: Vec<f32> = physics.objs.iter().map(|o| o.x).collect();
+        assert_eq!(&xs[..2], [0.0, 10.0]);
+        assert!(xs[2] > 20.0, "{}", xs[2]);
+    }
+
+    #[test]
+    fn stacked_nodes_stay_finite() {
+        let theta = DEFAULT_THETA;
+        for repulsion in [Repulsion::Exact, Repulsion::BarnesHut { theta }] {
+            let mut graph = Graph::new();
+            for (name, x) in [("a", 0.0), ("b", 0.0), ("c", 50.0)] {
+                let node = graph.add_named_node(name);
+                graph.nodes[node as usize].position = cgmath::vec3(x, 0.0, 0.0);
+            }
+            let mut sim = crate::simulation::Simulation::new(graph);
+            sim.physics.set_params(ForceParams {
+                repulsion,
+                ..ForceParams::default()
+            });
+            sim.run(10);
+            let positions = sim.positions();
+            assert!(
+                positions.iter().all(|p| p.x.is_finite() && p.y.is_finite()),
+                "{repulsion}: {positions:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn magnet_fades_with_distance() {
+        let mut magnet = Magnet {
+            center: vec2(0.0, 0.0),
+            radius: 100.0,
+            strength: 10.0,
+        };
+        assert_eq!(magnet.pull(vec2(50.0, 0.0)), Some(vec2(-5.0, 0.0)));
+        assert_eq!(magnet.pull(vec2(0.0, -2.0)), Some(vec2(0.0, 2.0)));
+        assert_eq!(magnet.pull(vec2(100.0, 0.0)), None);
+        assert_eq!(magnet.pull(vec2(0.0, 0.0)), None);
+        magnet.strength = -10.0;
+        assert_eq!(magnet.pull(vec2(0.0, 50.0)), Some(vec2(0.0, 5.0)));
+
+        let mut graph = Graph::new();
+        for name in ["near", "far", "frozen"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[0].position = cgmath::vec3(50.0, 0.0, 0.0);
+        graph.nodes[1].position = cgmath::vec3(500.0, 0.0, 0.0);
+        graph.nodes[2].position = cgmath::vec3(0.0, 50.0, 0.0);
+        let mut physics = Physics::new(&graph.nodes);
+        physics.set_params(ForceParams {
+            repulsion_range: 0.0,
+            gravity: 0.0,
+            collision: 0.0,
+            ..ForceParams::default()
+        });
+        physics.alpha = 0.0;
+        physics.frozen.insert(2);
+        physics.magnet = Some(Magnet {
+            strength: 10.0,
+            ..magnet
+        });
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        let positions: Vec<_> = physics.objs.iter().map(|o| (o.x, o.y)).collect();
+        assert_eq!(positions, [(45.0, 0.0), (500.0, 0.0), (0.0, 50.0)]);
+        assert_eq!(physics.alpha, MAGNET_ALPHA);
+    }
+
+    #[test]
+    fn anchor_lays_out_rings_by_hops() {
+        let mut graph = Graph::new();
+        let nodes: Vec<u32> = (0..4)
+            .map(|i| graph.add_named_node(&i.to_string()))
+            .collect();
+        graph.add_edge(nodes[1], nodes[0]);
+        graph.add_edge(nodes[1], nodes[2]);
+        for (i, node) in graph.nodes.iter_mut().enumerate() {
+            node.position = cgmath::vec3(100.0 + i as f32 * 300.0, 50.0, 0.0);
+        }
+        let anchor = Anchor::new(2, graph.nodes.len(), &graph.edges);
+        assert_eq!(anchor.hops, [Some(2), Some(1), Some(0), None]);
+        assert_eq!(anchor.radius(0, 10.0), Some(20.0));
+        assert_eq!(anchor.radius(3, 10.0), None);
+
+        let mut physics = Physics::new(&graph.nodes);
+        physics.set_params(ForceParams {
+            repulsion_range: 0.0,
+            gravity: 0.0,
+            ..ForceParams::default()
+        });
+        physics.anchor = Some(anchor);
+        physics.alpha_target = 1.0;
+        let edge_map = algorithms::incidence_map(&graph.edges, 0..2);
+        for _ in 0..300 {
+            physics.tick(&BTreeSet::new(), &graph.edges, &edge_map);
+        }
+        let dist = |i: usize| physics.objs[i].x.hypot(physics.objs[i].y);
+        assert_eq!(dist(2), 0.0);
+        let link = physics.params.link_distance;
+        assert!((dist(1) - link).abs() < 1.0, "{}", dist(1));
+        assert!((dist(0) - 2.0 * link).abs() < 1.0, "{}", dist(0));
+        // Out of reach, nothing moves it
+        assert_eq!((physics.objs[3].x, physics.objs[3].y), (1000.0, 50.0));
+    }
+
+    #[test]
+    fn thrown_nodes_coast_to_a_stop() {
+        let mut graph = Graph::new();
+        for name in ["thrown", "neighbor", "bystander"] {
+            graph.add_named_node(name);
+        }
+        for (i, node) in graph.nodes.iter_mut().enumerate() {
+            node.position = cgmath::vec3(i as f32 * 1000.0, 0.0, 0.0);
+        }
+        let mut physics = Physics::new(&graph.nodes);
+        physics.alpha = 0.0;
+        physics.last_step = 0.0;
+        assert!(physics.is_settled());
+
+        physics.throw(0, vec2(0.0, 1000.0), [0, 1]);
+        assert!(!physics.is_settled());
+        assert_eq!(physics.node_alpha(1), THROW_ALPHA);
+        assert_eq!(physics.node_alpha(2), 0.0);
+        let max_speed = physics.params.repulsion_range * physics.velocity_decay;
+        assert_eq!(physics.objs[0].vy, max_speed);
+
+        let mut ticks = 0;
+        while physics.objs[0].vy > 0.0 {
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+            ticks += 1;
+        }
+        // Decay is geometric, the node covers less than speed / decay
+        let y = physics.objs[0].y;
+        assert!(y > max_speed && y < physics.params.repulsion_range, "{y}");
+        assert!(ticks < 100, "{ticks}");
+        for _ in 0..1000 {
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        }
+        assert!(physics.heat.is_empty());
+        assert!(physics.is_settled());
+    }
+
+    #[test]
+    fn heavier_nodes_move_less() {
+        let mut graph = Graph::new();
+        for name in ["light", "heavy"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[1].position.x = 50.0;
+        graph.nodes[1]
+            .attributes
+            .insert(MASS_ATTR.into(), 4.0.into());
+        let mut physics = Physics::new(&graph.nodes);
+        physics.params.gravity = 0.0;
+        assert_eq!((physics.objs[0].mass, physics.objs[1].mass), (1.0, 4.0));
+
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        let light = -physics.objs[0].x;
+        let heavy = physics.objs[1].x - 50.0;
+        assert!(light > 0.0);
+        assert!((light - heavy * 4.0).abs() < 1e-3, "{light} {heavy}");
+        // Velocity carries on after the push, slowing down
+        let vx = physics.objs[0].vx;
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert!(physics.objs[0].vx.abs() > vx.abs() * (1.0 - physics.velocity_decay));
+    }
+
+    #[test]
+    fn pinned_nodes_stay_put() {
+        let mut graph = Graph::new();
+        for name in ["pinned", "free"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[1].position.x = 50.0;
+        let mut physics = Physics::new(&graph.nodes);
+        assert!(physics.toggle_pin(0));
+        assert_eq!(physics.pinned(), BTreeSet::from([0]));
+
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        physics.reheat();
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert_eq!((physics.objs[0].x, physics.objs[0].y), (0.0, 0.0));
+        // It still pushes the other one away
+        assert!(physics.objs[1].x > 50.0);
+
+        assert!(!physics.toggle_pin(0));
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert!(physics.objs[0].x < 0.0);
+    }
+
+    #[test]
+    fn only_nodes_in_the_region_move() {
+        let mut graph = Graph::new();
+        for name in ["inside", "neighbor", "far"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[1].position.x = 30.0;
+        graph.nodes[2].position.x = 5_000.0;
+        let mut physics = Physics::new(&graph.nodes);
+        let region = Region::around(vec2(-50.0, -50.0), vec2(50.0, 50.0), 0.5);
+        assert_eq!(region.min, vec2(-100.0, -100.0));
+        assert!(region.contains(100.0, 0.0) && !region.contains(100.1, 0.0));
+        physics.region = Some(region);
+
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert!(physics.objs[0].x < 0.0 && physics.objs[1].x > 30.0);
+        assert_eq!((physics.objs[2].x, physics.objs[2].y), (5_000.0, 0.0));
+
+        physics.region = None;
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert_ne!((physics.objs[2].x, physics.objs[2].y), (5_000.0, 0.0));
+    }
+
+    #[test]
+    fn gravity_brings_strays_back() {
+        let mut graph = Graph::new();
+        for name in ["stray", "frozen"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[0].position = cgmath::vec3(10_000.0, 0.0, 0.0);
+        graph.nodes[1].position = cgmath::vec3(0.0, 10_000.0, 0.0);
+        let mut physics = Physics::new(&graph.nodes);
+        physics.frozen.insert(1);
+        physics.focus = vec2(100.0, 100.0);
+        physics.alpha_target = 1.0;
+        for _ in 0..10_000 {
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        }
+        let stray = vec2(physics.objs[0].x, physics.objs[0].y);
+        assert!((stray - physics.focus).magnitude() < 1.0, "{stray:?}");
+        assert_eq!(physics.objs[1].y, 10_000.0);
+
+        physics.params.gravity = 0.0;
+        physics.objs[0].x = 10_000.0;
+        physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+        assert_eq!(physics.objs[0].x, 10_000.0);
+    }
+
+    #[test]
+    fn collisions_push_overlapping_nodes_apart() {
+        let mut graph = Graph::new();
+        for name in ["held", "pushed", "apart"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[1].position.x = 1.0;
+        graph.nodes[2].position.x = 10_000.0;
+        let mut physics = Physics::new(&graph.nodes);
+        physics.params.repulsion_range = 0.0;
+        physics.params.gravity = 0.0;
+        let reach = physics.objs[0].radius + physics.objs[1].radius;
+        for _ in 0..30 {
+            physics.tick(&BTreeSet::from([0]), &[], &BTreeMap::new());
+        }
+        assert_eq!(physics.objs[0].x, 0.0);
+        assert!(
+            (physics.objs[1].x - reach).abs() < 0.01,
+            "{}",
+            physics.objs[1].x
+        );
+        assert_eq!(physics.objs[2].x, 10_000.0);
+
+        physics.params.collision = 0.0;
+        physics.objs[1].x = 1.0;
+        physics.tick(&BTreeSet::from([0]), &[], &BTreeMap::new());
+        assert_eq!(physics.objs[1].x, 1.0);
+
+        // A disk far wider than a grid cell still reaches the ones it covers
+        physics.params.collision = 1.0;
+        physics.objs[2].radius = 20_000.0;
+        physics.collide(&[true, false, true]);
+        assert!(physics.objs[1].x < -10_000.0, "{}", physics.objs[1].x);
+    }
+
+    #[test]
+    fn collisions_keep_nodes_apart_by_their_sizes() {
+        let mut graph = Graph::new();
+        for name in ["small", "big"] {
+            graph.add_named_node(name);
+        }
+        graph.nodes[0].size = cgmath::vec2(10.0, 10.0);
+        graph.nodes[1].size = cgmath::vec2(30.0, 40.0);
+        graph.nodes[1].position.x = 1.0;
+        let mut physics = Physics::new(&graph.nodes);
+        physics.params.repulsion_range = 0.0;
+        physics.params.gravity = 0.0;
+        physics.params.collision = 1.0;
+        physics.tick(&BTreeSet::from([0]), &[], &BTreeMap::new());
+        assert!(
+            (physics.objs[1].x - 40.0).abs() < 0.01,
+            "{}",
+            physics.objs[1].x
+        );
+
+        // Growing a node pushes its neighbours further once it's taken up
+        graph.nodes[0].size = cgmath::vec2(20.0, 20.0);
+        physics.resize(&graph.nodes);
+        physics.tick(&BTreeSet::from([0]), &[], &BTreeMap::new());
+        assert!(
+            (physics.objs[1].x - 50.0).abs() < 0.01,
+            "{}",
+            physics.objs[1].x
+        );
+    }
+
+    #[test]
+    fn collisions_only_check_nearby_nodes() {
+        let mut graph = Graph::new();
+        // A 60 by 60 grid of nodes overlapping their neighbours
+        for i in 0..3600 {
+            let id = graph.add_named_node(&i.to_string());
+            graph.nodes[id as usize].position =
+                cgmath::vec3((i % 60) as f32 * 40.0, (i / 60) as f32 * 40.0, 0.0);
+        }
+        let mut physics = Physics::new(&graph.nodes);
+        physics.params.collision = 1.0;
+        let still = vec![false; graph.nodes.len()];
+        // All pairs would take over 6 million checks a pass
+        for _ in 0..10 {
+            physics.collide(&still);
+        }
+        let (a, b) = (&physics.objs[0], &physics.objs[1]);
+        assert!((b.x - a.x).hypot(b.y - a.y) > 40.0);
+    }
+
+    #[test]
+    fn edges_pull_to_their_own_rest_length() {
+        let mut graph = Graph::new();
+        let nodes: Vec<u32> = (0..4)
+            .map(|i| graph.add_named_node(&i.to_string()))
+            .collect();
+        for (i, node) in graph.nodes.iter_mut().enumerate() {
+            node.position = cgmath::vec3((i % 2) as f32 * 1000.0, (i / 2) as f32 * 5000.0, 0.0);
+        }
+        graph.add_edge(nodes[0], nodes[1]);
+        let short = graph.add_edge(nodes[2], nodes[3]) as usize;
+        graph.edges[short] = graph.edges[short].clone().with_spring(Some(50.0), 4.0);
+        let mut physics = Physics::new(&graph.nodes);
+        physics.set_params(ForceParams {
+            repulsion_range: 0.0,
+            gravity: 0.0,
+            collision: 0.0,
+            ..ForceParams::default()
+        });
+        physics.alpha_target = 1.0;
+        let edge_map = algorithms::incidence_map(&graph.edges, 0..2);
+        for _ in 0..1000 {
+            physics.tick(&BTreeSet::new(), &graph.edges, &edge_map);
+        }
+        let length = |a: usize| (physics.objs[a + 1].x - physics.objs[a].x).abs();
+        let link = physics.params.link_distance;
+        assert!((length(0) - link).abs() < 10.0, "{}", length(0));
+        // The pull fades as the nodes close in, it never gets them past 50
+        assert!((50.0..link / 2.0).contains(&length(2)), "{}", length(2));
+    }
+
+    #[test]
+    fn presets_follow_graph_size() {
+        assert_eq!(Preset::for_size(0, 0), Preset::SMALL);
+        assert_eq!(Preset::for_size(1_000, 5_000), Preset::SMALL);
+        assert_eq!(Preset::for_size(500, 6_000), Preset::MEDIUM);
+        assert_eq!(Preset::for_size(2_000, 100), Preset::MEDIUM);
+        assert_eq!(Preset::for_size(20_000, 0), Preset::LARGE);
+        assert_eq!(Preset::from_name("large"), Some(Preset::LARGE));
+
+        let params = ForceParams {
+            collision_iterations: 3,
+            ..ForceParams::default()
+        };
+        let large = Preset::LARGE.apply(params);
+        assert_eq!(large.collision_iterations, 0);
+        assert!(matches!(large.repulsion, Repulsion::BarnesHut { .. }));
+        let small = Preset::SMALL.apply(large);
+        assert_eq!(small.collision_iterations, 1);
+        assert_eq!(small.repulsion, Repulsion::Exact);
+        assert_eq!(Preset::SMALL.apply(params), params);
+    }
+
+    #[test]
+    fn barnes_hut_stays_close_to_exact_repulsion() {
+        let mut graph = Graph::new();
+        let mut rng = Rng::new(7);
+        for i in 0..300 {
+            graph.add_named_node(&i.to_string());
+            graph.nodes[i].position = cgmath::vec3(
+                rng.range(-2_000.0, 2_000.0),
+                rng.range(-2_000.0, 2_000.0),
+                0.0,
+            );
+        }
+        let velocities = |repulsion| {
+            let mut physics = Physics::new(&graph.nodes);
+            physics.set_params(ForceParams {
+                repulsion_range: 10_000.0,
+                gravity: 0.0,
+                collision: 0.0,
+                repulsion,
+                ..ForceParams::default()
+            });
+            physics.tick(&BTreeSet::new(), &[], &BTreeMap::new());
+            physics
+                .objs
+                .iter()
+                .map(|o| vec2(o.vx, o.vy))
+                .collect::<Vec<_>>()
+        };
+        let exact = velocities(Repulsion::Exact);
+        let approx = velocities(Repulsion::BarnesHut {
+            theta: DEFAULT_THETA,
+        });
+        let error: f32 = exact
+            .iter()
+            .zip(&approx)
+            .map(|(e, a)| (e - a).magnitude() / e.magnitude())
+            .sum::<f32>()
+            / exact.len() as f32;
+        assert!(error < 0.05, "{error}");
+        // Far enough off to show cells did stand in for nodes
+        assert!(error > 0.0);
     }
 }
@@ -0,0 +1,92 @@
+//! Picking a graph or session file to open: a native dialog on desktop, the
+//! browser's file chooser on the web.
+
+/// Extensions offered: sessions', and the ones `formats::importer_for`
+/// reads. Older sessions are `.json` too.
+pub const OPEN_EXTENSIONS: &[&str] = &[
+    "fdg", "graphml", "dot", "gv", "json", "txt", "edges", "edgelist",
+];
+
+/// Asks for a file with the platform's open dialog, `None` when it's
+/// cancelled. Blocks until the dialog closes.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pick_file() -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open a graph or session")
+        .add_filter("Graphs and sessions", OPEN_EXTENSIONS)
+        .pick_file()
+}
+
+/// A file picked in the browser, read into memory as there's no path to
+/// open it from
+#[cfg(target_arch = "wasm32")]
+pub struct PickedFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// Opens the browser's file chooser through a detached `<input type=file>`.
+/// Picked files are read asynchronously and handed over by `poll`.
+#[cfg(target_arch = "wasm32")]
+pub struct FilePicker {
+    tx: std::sync::mpsc::Sender<PickedFile>,
+    rx: std::sync::mpsc::Receiver<PickedFile>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for FilePicker {
+    fn default() -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        Self { tx, rx }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FilePicker {
+    pub fn open(&self) -> Result<(), String> {
+        use wasm_bindgen::{closure::Closure, JsCast};
+
+        let input: web_sys::HtmlInputElement = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.create_element("input").ok())
+            .and_then(|element| element.dyn_into().ok())
+            .ok_or("can't create a file input")?;
+        input.set_type("file");
+        let accept: Vec<String> = OPEN_EXTENSIONS
+            .iter()
+            .map(|ext| format!(".{ext}"))
+            .collect();
+        input.set_accept(&accept.join(","));
+
+        let tx = self.tx.clone();
+        let picked = input.clone();
+        let onchange = Closure::once(move || {
+            let Some(file) = picked.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            wasm_bindgen_futures::spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(file.text()).await {
+                    Ok(text) => {
+                        // The receiver lives as long as the picker
+                        let _ = tx.send(PickedFile {
+                            name: file.name(),
+                            contents: text.as_string().unwrap_or_default(),
+                        });
+                    }
+                    Err(_) => log::warn!("couldn't read {}", file.name()),
+                }
+            });
+        });
+        input.set_onchange(Some(onchange.as_ref().unchecked_ref()));
+        // Leaked, as nothing says when the browser is done with the input.
+        // It's one small closure per pick.
+        onchange.forget();
+        input.click();
+        Ok(())
+    }
+
+    /// Drains the files read since the last call
+    pub fn poll(&self) -> Vec<PickedFile> {
+        self.rx.try_iter().collect()
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Map, Value};
+
+use crate::color;
+
+/// Most files `Preferences::recent_files` remembers
+pub const MAX_RECENT_FILES: usize = 10;
+/// Directory under the platform's config directory, and the localStorage
+/// key on the web, preferences are kept under
+const APP_NAME: &str = "force-graph-wgpu";
+const PREFS_FILE: &str = "preferences.json";
+
+/// Settings that belong to the user rather than to a graph, kept between
+/// runs. Sessions (see `session::Session`) are saved separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preferences {
+    /// Color the window is cleared to, `color::BACKGROUND` when unset
+    pub background: Option<cgmath::Vector4<f32>>,
+    /// Waits for the display's refresh before presenting frames
+    pub vsync: bool,
+    /// One-shot layout, `multilevel` or `spectral`, last run. Graphs loaded
+    /// from files that don't place their nodes are laid out with it.
+    pub layout: Option<String>,
+    /// Most recently loaded files first
+    pub recent_files: Vec<String>,
+    /// Keys standing in for others in `KEYMAP`, e.g. `q` doing what `h` does
+    pub keys: BTreeMap<char, char>,
+    /// Catalog file the UI strings were last loaded from, English when unset
+    pub lang: Option<String>,
+    /// Whether the help overlay was dismissed once already, it only opens
+    /// by itself on the first run
+    pub seen_help: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            background: None,
+            vsync: true,
+            layout: None,
+            recent_files: vec![],
+            keys: BTreeMap::new(),
+            lang: None,
+            seen_help: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// Moves `path` to the front of `recent_files`, forgetting the oldest
+    /// past `MAX_RECENT_FILES`
+    pub fn add_recent_file<S: Into<String>>(&mut self, path: S) {
+        let path = path.into();
+        self.recent_files.retain(|recent| *recent != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// The key `key` acts as
+    pub fn key(&self, key: char) -> char {
+        self.keys.get(&key).copied().unwrap_or(key)
+    }
+
+    pub fn background(&self) -> cgmath::Vector4<f32> {
+        self.background.unwrap_or(color::BACKGROUND)
+    }
+
+    pub fn to_json(&self) -> String {
+        let keys: Map<String, Value> = self
+            .keys
+            .iter()
+            .map(|(key, acts_as)| (key.to_string(), json!(acts_as.to_string())))
+            .collect();
+        let root = json!({
+            "background": self.background.map(color::to_hex),
+            "vsync": self.vsync,
+            "layout": self.layout,
+            "recent_files": self.recent_files,
+            "keys": keys,
+            "lang": self.lang,
+            "seen_help": self.seen_help,
+        });
+        serde_json::to_string_pretty(&root).expect("preferences are plain values")
+    }
+
+    /// Reads what `to_json` writes. Missing fields keep their defaults, so
+    /// files from older versions still load.
+    pub fn parse(src: &str) -> Result<Self> {
+        let root: Value = serde_json::from_str(src)?;
+        let mut prefs = Self::default();
+        let string = |key: &str| -> Result<Option<String>> {
+            match root.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(value) => Err(anyhow!("{key}: expected a string, got {value}")),
+            }
+        };
+        if let Some(hex) = string("background")? {
+            let color = color::from_hex(&hex).ok_or_else(|| anyhow!("background: bad color"))?;
+            prefs.background = Some(color);
+        }
+        if let Some(vsync) = root.get("vsync") {
+            prefs.vsync = vsync
+                .as_bool()
+                .ok_or_else(|| anyhow!("vsync: expected true or false"))?;
+        }
+        if let Some(seen_help) = root.get("seen_help") {
+            prefs.seen_help = seen_help
+                .as_bool()
+                .ok_or_else(|| anyhow!("seen_help: expected true or false"))?;
+        }
+        prefs.layout = string("layout")?;
+        prefs.lang = string("lang")?;
+        if let Some(files) = root.get("recent_files") {
+            prefs.recent_files = files
+                .as_array()
+                .ok_or_else(|| anyhow!("recent_files: expected an array"))?
+                .iter()
+                .map(|file| {
+                    file.as_str()
+                        .map(String::from)
+                        .ok_or_else(|| anyhow!("recent_files: expected paths, got {file}"))
+                })
+                .collect::<Result<_>>()?;
+            prefs.recent_files.truncate(MAX_RECENT_FILES);
+        }
+        if let Some(keys) = root.get("keys") {
+            for (key, acts_as) in keys
+                .as_object()
+                .ok_or_else(|| anyhow!("keys: expected an object"))?
+            {
+                let acts_as = acts_as.as_str().unwrap_or_default();
+                match (single_char(key), single_char(acts_as)) {
+                    (Some(key), Some(acts_as)) => {
+                        prefs.keys.insert(key, acts_as);
+                    }
+                    _ => return Err(anyhow!("keys: {key} must map one key to another")),
+                }
+            }
+        }
+        Ok(prefs)
+    }
+
+    /// The saved preferences, or the defaults when there are none or they
+    /// can't be read
+    pub fn load() -> Self {
+        let src = match read() {
+            Ok(Some(src)) => src,
+            Ok(None) => return Self::default(),
+            Err(err) => {
+                log::warn!("can't read preferences: {err:#}");
+                return Self::default();
+            }
+        };
+        Self::parse(&src).unwrap_or_else(|err| {
+            log::warn!("ignoring broken preferences: {err:#}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write(&self.to_json())
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+/// `preferences.json` in this app's directory under the platform's config
+/// directory: `$XDG_CONFIG_HOME` or `~/.config` on Linux, `~/Library/Application
+/// Support` on macOS and `%APPDATA%` on Windows
+#[cfg(not(target_arch = "wasm32"))]
+pub fn prefs_path() -> Option<std::path::PathBuf> {
+    use std::{env, path::PathBuf};
+
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let config = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+    Some(config?.join(APP_NAME).join(PREFS_FILE))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read() -> Result<Option<String>> {
+    let Some(path) = prefs_path() else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(src) => Ok(Some(src)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(src: &str) -> Result<()> {
+    let path = prefs_path().ok_or_else(|| anyhow!("no config directory to save to"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, src).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| anyhow!("no localStorage"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read() -> Result<Option<String>> {
+    local_storage()?
+        .get_item(APP_NAME)
+        .map_err(|_| anyhow!("localStorage refused to read {APP_NAME}"))
+        .context(PREFS_FILE)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(src: &str) -> Result<()> {
+    local_storage()?
+        .set_item(APP_NAME, src)
+        .map_err(|_| anyhow!("localStorage refused to store {APP_NAME}"))
+        .context(PREFS_FILE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preferences_round_trip() {
+        let mut prefs = Preferences {
+            background: color::from_hex("#f0f0f0"),
+            vsync: false,
+            layout: Some("spectral".into()),
+            lang: Some("locales/de.ftl".into()),
+            seen_help: true,
+            ..Preferences::default()
+        };
+        prefs.keys.insert('q', 'h');
+        for i in 0..MAX_RECENT_FILES + 2 {
+            prefs.add_recent_file(format!("{i}.graphml"));
+        }
+        prefs.add_recent_file("3.graphml");
+        assert_eq!(prefs.recent_files.len(), MAX_RECENT_FILES);
+        assert_eq!(prefs.recent_files[..2], ["3.graphml", "11.graphml"]);
+        assert_eq!((prefs.key('q'), prefs.key('h')), ('h', 'h'));
+
+        assert_eq!(Preferences::parse(&prefs.to_json()).unwrap(), prefs);
+        // Older files missing fields get the defaults for them
+        assert_eq!(
+            Preferences::parse(r#"{"vsync": false}"#).unwrap(),
+            Preferences {
+                vsync: false,
+                ..Preferences::default()
+            }
+        );
+        assert!(Preferences::parse(r#"{"keys": {"q": "hh"}}"#).is_err());
+        assert!(Preferences::parse(r#"{"background": "nope"}"#).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! The types most programs built on this crate need, `use force_graph_wgpu::prelude::*`
+//! to bring them all in. Everything here is meant to stay stable; the modules
+//! themselves also expose the renderer, which may change between releases.
+
+pub use crate::{
+    attributes::{AttrValue, Attributes},
+    changes::{Change, Changes},
+    edge::Edge,
+    formats::{exporter_for, importer_for, load, load_with, save, Exporter, Importer},
+    graph::{
+        Aggregate, EdgeMerge, EdgeRef, EdgeRejection, Graph, GraphMutation, GraphView, NodeId,
+        NodeRef, Remap,
+    },
+    i18n::Catalog,
+    node::Node,
+    physics::{Anchor, BipartiteShape, ForceParam, ForceParams, Magnet, Partition, Physics},
+    rng::Rng,
+    simulation::Simulation,
+    style::{Channel, LabelTemplate, Scale, StyleRule, TemplatedLabels},
+    units::Units,
+};
+
+#[cfg(feature = "render")]
+pub use crate::{
+    annotation::Annotation,
+    export::{ExportOptions, ExportRegion},
+};
@@ -0,0 +1,184 @@
+use cgmath::vec2;
+
+/// Subdivision stops here, so nodes sharing a position can't recurse forever
+pub const MAX_DEPTH: u32 = 16;
+
+/// Barnes–Hut quadtree over node positions. Every cell knows how many nodes it
+/// holds and their center of mass, which is what lets far away groups of
+/// nodes be treated as a single body.
+#[derive(Debug, Clone, Default)]
+pub struct Quadtree {
+    /// Cells in depth first order, the root comes first
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    /// Bottom-left corner of the square
+    pub min: cgmath::Vector2<f32>,
+    /// Side length of the square
+    pub size: f32,
+    pub depth: u32,
+    /// Number of nodes inside
+    pub mass: u32,
+    pub center_of_mass: cgmath::Vector2<f32>,
+    /// Indices into `Quadtree::cells` of the non-empty quadrants
+    pub children: Vec<u32>,
+    /// Node indices, only set on leaves
+    pub nodes: Vec<u32>,
+}
+
+/// Shape of a tree, for spotting layouts that make it degenerate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeStats {
+    pub cells: usize,
+    pub leaves: usize,
+    pub depth: u32,
+    /// Most nodes sharing one leaf
+    pub max_per_leaf: u32,
+    pub mean_per_leaf: f32,
+    /// Leaves holding more than one node, i.e. nodes `MAX_DEPTH`
+    /// subdivisions couldn't separate
+    pub crowded: usize,
+}
+
+impl Quadtree {
+    /// Builds the tree over `positions`, one square leaf per node except
+    /// where nodes are closer than `MAX_DEPTH` subdivisions allow
+    pub fn build(positions: &[cgmath::Vector2<f32>]) -> Self {
+        let mut tree = Self::default();
+        let Some((min, max)) = bounds(positions) else {
+            return tree;
+        };
+        let size = (max.x - min.x).max(max.y - min.y).max(1.0);
+        tree.subdivide(
+            positions,
+            (0..positions.len() as u32).collect(),
+            min,
+            size,
+            0,
+        );
+        tree
+    }
+
+    fn subdivide(
+        &mut self,
+        positions: &[cgmath::Vector2<f32>],
+        nodes: Vec<u32>,
+        min: cgmath::Vector2<f32>,
+        size: f32,
+        depth: u32,
+    ) -> u32 {
+        let mass = nodes.len() as u32;
+        let center_of_mass = nodes
+            .iter()
+            .fold(vec2(0.0, 0.0), |sum, i| sum + positions[*i as usize])
+            / mass as f32;
+        let index = self.cells.len() as u32;
+        self.cells.push(Cell {
+            min,
+            size,
+            depth,
+            mass,
+            center_of_mass,
+            children: vec![],
+            nodes: vec![],
+        });
+        if nodes.len() <= 1 || depth == MAX_DEPTH {
+            self.cells[index as usize].nodes = nodes;
+            return index;
+        }
+
+        let half = size / 2.0;
+        let mut quadrants: [Vec<u32>; 4] = Default::default();
+        for i in nodes {
+            let p = positions[i as usize];
+            let right = p.x >= min.x + half;
+            let top = p.y >= min.y + half;
+            quadrants[right as usize + 2 * top as usize].push(i);
+        }
+        for (q, nodes) in quadrants.into_iter().enumerate() {
+            if nodes.is_empty() {
+                continue;
+            }
+            let corner = min + vec2((q % 2) as f32, (q / 2) as f32) * half;
+            let child = self.subdivide(positions, nodes, corner, half, depth + 1);
+            self.cells[index as usize].children.push(child);
+        }
+        index
+    }
+
+    pub fn stats(&self) -> TreeStats {
+        let leaves: Vec<u32> = self
+            .cells
+            .iter()
+            .filter(|c| c.children.is_empty())
+            .map(|c| c.mass)
+            .collect();
+        let nodes: u32 = leaves.iter().sum();
+        TreeStats {
+            cells: self.cells.len(),
+            leaves: leaves.len(),
+            depth: self.depth(),
+            max_per_leaf: leaves.iter().copied().max().unwrap_or(0),
+            mean_per_leaf: nodes as f32 / leaves.len().max(1) as f32,
+            crowded: leaves.iter().filter(|mass| **mass > 1).count(),
+        }
+    }
+
+    /// Deepest level of any cell, the root being 0
+    pub fn depth(&self) -> u32 {
+        self.cells.iter().map(|c| c.depth).max().unwrap_or(0)
+    }
+}
+
+fn bounds(
+    positions: &[cgmath::Vector2<f32>],
+) -> Option<(cgmath::Vector2<f32>, cgmath::Vector2<f32>)> {
+    positions.iter().fold(None, |bounds, p| {
+        Some(match bounds {
+            None => (*p, *p),
+            Some((lo, hi)) => (
+                vec2(lo.x.min(p.x), lo.y.min(p.y)),
+                vec2(hi.x.max(p.x), hi.y.max(p.y)),
+            ),
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn leaves_hold_one_node() {
+        let positions = [
+            vec2(0.0, 0.0),
+            vec2(100.0, 100.0),
+            vec2(90.0, 95.0),
+            vec2(0.0, 100.0),
+            // Coincident nodes share the deepest leaf
+            vec2(50.0, 50.0),
+            vec2(50.0, 50.0),
+        ];
+        let tree = Quadtree::build(&positions);
+        let root = &tree.cells[0];
+        assert_eq!(root.mass, 6);
+        assert_eq!(root.center_of_mass, vec2(290.0 / 6.0, 395.0 / 6.0));
+
+        let mut leaves: Vec<Vec<u32>> = tree
+            .cells
+            .iter()
+            .filter(|c| c.children.is_empty())
+            .map(|c| c.nodes.clone())
+            .collect();
+        leaves.sort();
+        assert_eq!(leaves, [vec![0], vec![1], vec![2], vec![3], vec![4, 5]]);
+        assert_eq!(tree.depth(), MAX_DEPTH);
+        assert!(Quadtree::build(&[]).cells.is_empty());
+
+        let stats = tree.stats();
+        assert_eq!((stats.leaves, stats.max_per_leaf, stats.crowded), (5, 2, 1));
+        assert_eq!(stats.mean_per_leaf, 1.2);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::RenderSettings;
+
+/// GPU milliseconds a frame may take before quality is lowered, leaving room
+/// under a 60 Hz display's 16.7 for the CPU's share
+pub const DEFAULT_FRAME_BUDGET: f32 = 12.0;
+/// Fractions of the window's resolution drawn at, largest first
+const SCALES: [f32; 3] = [1.0, 0.75, 0.5];
+/// Frames in a row over budget before dropping a level, so a single slow
+/// frame (a shader compiling, a graph loading) doesn't
+const SLOW_FRAMES: u32 = 30;
+/// Frames in a row well under budget before raising a level. Longer than
+/// `SLOW_FRAMES`, since a level that's too costly shows as stutter.
+const FAST_FRAMES: u32 = 120;
+/// Fraction of the budget frames have to stay under for a level to be
+/// raised. The next level up can cost twice as much or more.
+const HEADROOM: f32 = 0.5;
+/// Frames ignored after a change, while averages measured at the old level
+/// catch up
+const SETTLE_FRAMES: u32 = 60;
+
+/// How much of the window's quality a frame is drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    /// MSAA samples per pixel
+    pub sample_count: u8,
+    /// Fraction of the window's resolution the graph is drawn at before
+    /// being stretched over it
+    pub scale: f32,
+}
+
+impl Quality {
+    /// Levels from `full` down, each cheaper than the last: MSAA goes first,
+    /// then resolution
+    pub fn levels(full: RenderSettings) -> Vec<Self> {
+        let msaa = (full.sample_count > 1).then_some(Self {
+            sample_count: full.sample_count,
+            scale: 1.0,
+        });
+        msaa.into_iter()
+            .chain(SCALES.iter().map(|&scale| Self {
+                sample_count: 1,
+                scale,
+            }))
+            .collect()
+    }
+
+    /// Size drawn at for a `width` by `height` window, never empty
+    pub fn scaled(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |n: u32| ((n as f32 * self.scale).round() as u32).max(1);
+        (scale(width), scale(height))
+    }
+}
+
+/// Lowers MSAA, then resolution, while frames take the GPU longer than a
+/// budget, and brings them back once there's room again. Keeps dragging and
+/// zooming smooth on integrated graphics with big graphs.
+#[derive(Debug, Clone)]
+pub struct AdaptiveQuality {
+    /// GPU milliseconds a frame may take
+    pub budget: f32,
+    levels: Vec<Quality>,
+    level: usize,
+    slow: u32,
+    fast: u32,
+    settling: u32,
+}
+
+impl AdaptiveQuality {
+    /// Starts at full quality, as `full` draws
+    pub fn new(full: RenderSettings, budget: f32) -> Self {
+        Self {
+            budget,
+            levels: Quality::levels(full),
+            level: 0,
+            slow: 0,
+            fast: 0,
+            settling: 0,
+        }
+    }
+
+    pub fn quality(&self) -> Quality {
+        self.levels[self.level]
+    }
+
+    /// Takes the GPU time of the latest frame, in milliseconds, and returns
+    /// the quality to switch to when it's time for a change
+    pub fn observe(&mut self, frame_ms: f32) -> Option<Quality> {
+        if self.settling > 0 {
+            self.settling -= 1;
+            return None;
+        }
+        if frame_ms > self.budget {
+            self.slow += 1;
+            self.fast = 0;
+        } else if frame_ms < self.budget * HEADROOM {
+            self.fast += 1;
+            self.slow = 0;
+        } else {
+            (self.slow, self.fast) = (0, 0);
+        }
+        let level = if self.slow >= SLOW_FRAMES && self.level + 1 < self.levels.len() {
+            self.level + 1
+        } else if self.fast >= FAST_FRAMES && self.level > 0 {
+            self.level - 1
+        } else {
+            return None;
+        };
+        self.level = level;
+        (self.slow, self.fast) = (0, 0);
+        self.settling = SETTLE_FRAMES;
+        Some(self.quality())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quality_follows_the_budget() {
+        let mut adaptive = AdaptiveQuality::new(RenderSettings::DEFAULT, 10.0);
+        let full = adaptive.quality();
+        assert_eq!((full.sample_count, full.scale), (crate::SAMPLE_COUNT, 1.0));
+        // A short hitch changes nothing
+        for _ in 0..SLOW_FRAMES - 1 {
+            assert_eq!(adaptive.observe(30.0), None);
+        }
+        assert_eq!(adaptive.observe(8.0), None);
+
+        let mut changes = vec![];
+        for _ in 0..1000 {
+            changes.extend(adaptive.observe(30.0));
+        }
+        let scales: Vec<(u8, f32)> = changes.iter().map(|q| (q.sample_count, q.scale)).collect();
+        assert_eq!(scales, [(1, 1.0), (1, 0.75), (1, 0.5)]);
+
+        // Back up one level at a time once frames are cheap again
+        for _ in 0..SETTLE_FRAMES + FAST_FRAMES {
+            adaptive.observe(2.0);
+        }
+        assert_eq!(adaptive.quality().scale, 0.75);
+        for _ in 0..1000 {
+            adaptive.observe(2.0);
+        }
+        assert_eq!(adaptive.quality(), full);
+
+        // Without MSAA to give up, resolution goes first
+        let safe = Quality::levels(RenderSettings::SAFE);
+        assert_eq!(safe.len(), SCALES.len());
+        assert_eq!(safe[1].scaled(1001, 3), (751, 2));
+        assert_eq!(
+            Quality {
+                sample_count: 1,
+                scale: 0.5
+            }
+            .scaled(1, 1),
+            (1, 1)
+        );
+    }
+}
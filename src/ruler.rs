@@ -0,0 +1,79 @@
+use cgmath::{vec2, vec4};
+
+use crate::{
+    overlay::{text_rects, text_size, Rect},
+    units::{format_amount, nice_length, Units},
+};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+/// Longest the bar gets before snapping down to a round length, in window
+/// pixels
+pub const MAX_BAR_LENGTH: f32 = 200.0;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.6);
+const BAR_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const BAR_THICKNESS: f32 = 2.0;
+const TICK_HEIGHT: f32 = 8.0;
+const GAP: f32 = 6.0;
+
+/// Scale bar showing how long a round number of units is at the current zoom,
+/// centered along the bottom edge
+#[derive(Debug, Clone, Copy)]
+pub struct Ruler {
+    pub margin: f32,
+    pub padding: f32,
+}
+
+impl Default for Ruler {
+    fn default() -> Self {
+        Self {
+            margin: 16.0,
+            padding: 8.0,
+        }
+    }
+}
+
+impl Ruler {
+    /// `zoom` is window pixels per world unit
+    pub fn rects(&self, width: f32, height: f32, zoom: f32, units: &Units) -> Vec<Rect> {
+        let amount = nice_length(units.from_world(MAX_BAR_LENGTH / zoom));
+        if amount == 0.0 {
+            return vec![];
+        }
+        let bar = units.to_world(amount) * zoom;
+        let label = format!("{} {}", format_amount(amount), units.name);
+        let text = text_size(&label, TEXT_PIXEL);
+
+        let content = vec2(bar.max(text.x), TICK_HEIGHT + GAP + text.y);
+        let size = content + vec2(self.padding, self.padding) * 2.0;
+        let origin = vec2((width - size.x) / 2.0, height - size.y - self.margin);
+        let inner = origin + vec2(self.padding, self.padding);
+        let bar_x = inner.x + (content.x - bar) / 2.0;
+        let bar_y = inner.y + TICK_HEIGHT - BAR_THICKNESS;
+
+        let mut rects = vec![
+            Rect::new(origin, size, BACKGROUND_COLOR),
+            Rect::new(vec2(bar_x, bar_y), vec2(bar, BAR_THICKNESS), BAR_COLOR),
+            Rect::new(
+                vec2(bar_x, inner.y),
+                vec2(BAR_THICKNESS, TICK_HEIGHT),
+                BAR_COLOR,
+            ),
+            Rect::new(
+                vec2(bar_x + bar - BAR_THICKNESS, inner.y),
+                vec2(BAR_THICKNESS, TICK_HEIGHT),
+                BAR_COLOR,
+            ),
+        ];
+        let label_x = inner.x + (content.x - text.x) / 2.0;
+        rects.extend(text_rects(
+            &label,
+            vec2(label_x, inner.y + TICK_HEIGHT + GAP),
+            TEXT_PIXEL,
+            TEXT_COLOR,
+        ));
+        rects
+    }
+}
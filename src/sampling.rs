@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use crate::{edge::Edge, limits, rng::Rng};
+
+/// Default `SampleView::full_zoom`, twice the default zoom
+pub const DEFAULT_FULL_ZOOM: f32 = 2.0;
+
+/// Draws only a sample of the graph while zoomed out, for getting a feel
+/// for graphs too big to make sense of whole. The rest stays in the graph,
+/// for searches, paths and the like, and is drawn again from `full_zoom` in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleView {
+    /// Most nodes drawn
+    pub size: usize,
+    pub seed: u64,
+    /// Camera scale from which every node is drawn
+    pub full_zoom: f32,
+    /// Sampled nodes by index, `None` until the next `update` picks them
+    nodes: Option<BTreeSet<u32>>,
+    /// Nodes left out of the sample and the edges touching them, worked out
+    /// along with it
+    hidden: (BTreeSet<u32>, BTreeSet<u32>),
+    /// Whether the whole graph was drawn when `redraw` was last asked, `None`
+    /// after sampling again
+    drawn_full: Option<bool>,
+}
+
+impl SampleView {
+    pub fn new(size: usize, seed: u64, full_zoom: f32) -> Self {
+        Self {
+            size,
+            seed,
+            full_zoom,
+            nodes: None,
+            hidden: Default::default(),
+            drawn_full: None,
+        }
+    }
+
+    /// Has the next `update` sample again, for after the graph changed
+    pub fn invalidate(&mut self) {
+        self.nodes = None;
+    }
+
+    /// Samples the graph if it hasn't been since it last changed
+    pub fn update(&mut self, node_count: usize, edges: &[Edge]) {
+        if self.nodes.is_some() {
+            return;
+        }
+        let mut rng = Rng::new(self.seed);
+        let sampled = induced_edge_sample(node_count, edges, self.size, &mut rng);
+        let nodes: BTreeSet<u32> = (0..node_count as u32)
+            .filter(|i| !sampled.contains(i))
+            .collect();
+        let edges = edges
+            .iter()
+            .enumerate()
+            .filter(|(_, edge)| nodes.contains(&edge.a_id) || nodes.contains(&edge.b_id))
+            .map(|(i, _)| i as u32)
+            .collect();
+        self.nodes = Some(sampled);
+        self.hidden = (nodes, edges);
+        self.drawn_full = None;
+    }
+
+    /// Whether the whole graph is drawn at camera `scale`
+    pub fn is_full(&self, scale: f32) -> bool {
+        scale >= self.full_zoom
+    }
+
+    /// Whether what's hidden at camera `scale` differs from when this was
+    /// last asked, having sampled again or crossed `full_zoom`
+    pub fn redraw(&mut self, scale: f32) -> bool {
+        let full = self.is_full(scale);
+        self.drawn_full.replace(full) != Some(full)
+    }
+
+    /// Nodes left out of the sample, and the edges touching them, as of the
+    /// last `update`
+    pub fn hidden(&self) -> (&BTreeSet<u32>, &BTreeSet<u32>) {
+        (&self.hidden.0, &self.hidden.1)
+    }
+}
+
+/// `size` nodes picked so the graph between them keeps the shape of the
+/// whole one's degree distribution: the ends of edges taken at random, and
+/// every edge between them drawn (induced edge sampling). Picking nodes
+/// themselves at random would leave each with only a fraction of its links.
+/// Nodes without any get their share of the sample picked on their own.
+pub fn induced_edge_sample(
+    node_count: usize,
+    edges: &[Edge],
+    size: usize,
+    rng: &mut Rng,
+) -> BTreeSet<u32> {
+    if size >= node_count {
+        return (0..node_count as u32).collect();
+    }
+    let mut linked = vec![false; node_count];
+    for edge in edges {
+        linked[edge.a_id as usize] = true;
+        linked[edge.b_id as usize] = true;
+    }
+    let isolated: Vec<u32> = (0..node_count as u32)
+        .filter(|i| !linked[*i as usize])
+        .collect();
+    let mut picked: BTreeSet<u32> =
+        limits::choose(isolated.len(), size * isolated.len() / node_count, rng)
+            .into_iter()
+            .map(|i| isolated[i])
+            .collect();
+
+    // A shuffle of the edges, stopped as soon as the sample is full
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    for i in 0..order.len() {
+        if picked.len() >= size {
+            break;
+        }
+        let j = i + (rng.next_u64() % (order.len() - i) as u64) as usize;
+        order.swap(i, j);
+        let edge = &edges[order[i]];
+        picked.insert(edge.a_id);
+        if picked.len() < size {
+            picked.insert(edge.b_id);
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::{Graph, GraphView};
+
+    fn mean_degree(graph: &Graph) -> f32 {
+        2.0 * graph.edges.len() as f32 / graph.nodes.len() as f32
+    }
+
+    #[test]
+    fn edge_samples_keep_degrees_better_than_node_samples() {
+        let mut graph = Graph::new();
+        let mut rng = Rng::new(11);
+        for i in 0..400 {
+            graph.add_named_node(&i.to_string());
+        }
+        for a in 0..400 {
+            for _ in 0..4 {
+                let b = (rng.next_u64() % 400) as u32;
+                if a != b && graph.check_edge(a, b).is_ok() {
+                    graph.add_edge(a, b);
+                }
+            }
+        }
+        // A few nodes nothing links to
+        for i in 0..20 {
+            graph.add_named_node(&format!("alone {i}"));
+        }
+        let whole = mean_degree(&graph);
+
+        let by_edges = induced_edge_sample(graph.nodes.len(), &graph.edges, 100, &mut rng);
+        assert_eq!(by_edges.len(), 100);
+        assert!(
+            by_edges.iter().any(|i| *i >= 400),
+            "alone nodes get a share"
+        );
+        let by_nodes: BTreeSet<u32> = limits::choose(graph.nodes.len(), 100, &mut rng)
+            .into_iter()
+            .map(|i| i as u32)
+            .collect();
+        let error = |sample: &BTreeSet<u32>| (mean_degree(&graph.subgraph(sample)) - whole).abs();
+        assert!(
+            error(&by_edges) < error(&by_nodes),
+            "{} vs {}",
+            error(&by_edges),
+            error(&by_nodes)
+        );
+
+        let mut view = SampleView::new(100, 5, DEFAULT_FULL_ZOOM);
+        view.update(graph.nodes.len(), &graph.edges);
+        let (nodes, edges) = view.hidden();
+        assert_eq!(nodes.len(), graph.nodes.len() - 100);
+        assert!(edges.iter().all(|i| {
+            let edge = &graph.edges[*i as usize];
+            nodes.contains(&edge.a_id) || nodes.contains(&edge.b_id)
+        }));
+        // Same seed, same sample
+        let mut again = SampleView::new(100, 5, DEFAULT_FULL_ZOOM);
+        again.update(graph.nodes.len(), &graph.edges);
+        assert_eq!(again, view);
+        assert!(view.is_full(DEFAULT_FULL_ZOOM) && !view.is_full(1.0));
+
+        // Only sampling again or crossing the zoom changes what's hidden
+        assert!(view.redraw(1.0));
+        assert!(!view.redraw(1.5));
+        assert!(view.redraw(DEFAULT_FULL_ZOOM));
+        assert!(!view.redraw(DEFAULT_FULL_ZOOM));
+        view.invalidate();
+        view.update(graph.nodes.len(), &graph.edges);
+        assert!(view.redraw(DEFAULT_FULL_ZOOM));
+    }
+}
@@ -0,0 +1,367 @@
+use cgmath::{vec2, vec4};
+
+use crate::{
+    console::COMMANDS,
+    graph::node_name,
+    node::Node,
+    overlay::{text_rects, text_size, Rect},
+};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+/// Matches listed under the query
+pub const MAX_RESULTS: usize = 8;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.8);
+const QUERY_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const RESULT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const COMMAND_COLOR: cgmath::Vector4<f32> = vec4(0.6, 0.8, 1.0, 0.9);
+const SELECTED_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.15);
+const WIDTH: f32 = 320.0;
+const MARGIN: f32 = 48.0;
+const PADDING: f32 = 8.0;
+const GAP: f32 = 6.0;
+
+/// Popup for picking an edge's target by name, for when dragging across the
+/// graph isn't practical
+#[derive(Debug, Clone)]
+pub struct QuickSearch {
+    pub source: u32,
+    pub query: String,
+    /// Index into the current matches
+    selected: usize,
+    /// The key that opened the popup, which also arrives as typed text
+    trigger: Option<char>,
+}
+
+impl QuickSearch {
+    pub fn new(source: u32) -> Self {
+        Self {
+            source,
+            query: String::new(),
+            selected: 0,
+            trigger: None,
+        }
+    }
+
+    /// Opened with a key press, the character it types is dropped
+    pub fn opened_with(source: u32, trigger: char) -> Self {
+        Self {
+            trigger: Some(trigger),
+            ..Self::new(source)
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if self.trigger.take() == Some(c) {
+            return;
+        }
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the highlighted match by `step`, wrapping around
+    pub fn select(&mut self, nodes: &[Node], step: isize) {
+        let count = self.matches(nodes).len() as isize;
+        if count > 0 {
+            self.selected = (self.selected as isize + step).rem_euclid(count) as usize;
+        }
+    }
+
+    /// Named nodes containing the query, ignoring case. Names starting with
+    /// the query come first, then shorter names. The source never matches.
+    pub fn matches(&self, nodes: &[Node]) -> Vec<(u32, String)> {
+        let query = self.query.to_lowercase();
+        let mut matches: Vec<(u32, String)> = nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i as u32 != self.source)
+            .filter_map(|(i, node)| Some((i as u32, node_name(node)?)))
+            .filter(|(_, name)| name.to_lowercase().contains(&query))
+            .collect();
+        matches.sort_by_key(|(_, name)| {
+            (
+                !name.to_lowercase().starts_with(&query),
+                name.len(),
+                name.clone(),
+            )
+        });
+        matches.truncate(MAX_RESULTS);
+        matches
+    }
+
+    /// The node Enter would connect to
+    pub fn target(&self, nodes: &[Node]) -> Option<u32> {
+        let matches = self.matches(nodes);
+        matches
+            .get(self.selected)
+            .or(matches.first())
+            .map(|(id, _)| *id)
+    }
+
+    pub fn rects(&self, width: f32, nodes: &[Node]) -> Vec<Rect> {
+        let source = nodes
+            .get(self.source as usize)
+            .and_then(node_name)
+            .unwrap_or_else(|| format!("#{}", self.source));
+        let lines: Vec<_> = self
+            .matches(nodes)
+            .into_iter()
+            .map(|(_, name)| (name, RESULT_COLOR))
+            .collect();
+        popup_rects(
+            width,
+            &format!("{source} -> {}_", self.query),
+            &lines,
+            self.selected,
+        )
+    }
+}
+
+/// Something Ctrl+P's palette lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteItem {
+    /// A recently opened file, by path
+    File(String),
+    /// A console command, by name
+    Command(&'static str),
+}
+
+/// What picking from the palette does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteAction {
+    /// Opens a graph or session file
+    Open(String),
+    /// Runs a console line
+    Run(String),
+}
+
+/// Popup for reopening recent files and running console commands, opened
+/// with Ctrl+P. Commands are completed first, then run with whatever is
+/// typed after their name.
+#[derive(Debug, Clone, Default)]
+pub struct QuickOpen {
+    pub query: String,
+    /// Index into the current matches
+    selected: usize,
+}
+
+impl QuickOpen {
+    /// Opened with `query` already typed, such as a command waiting for its
+    /// last argument
+    pub fn with_query<S: Into<String>>(query: S) -> Self {
+        Self {
+            query: query.into(),
+            selected: 0,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the highlighted match by `step`, wrapping around
+    pub fn select(&mut self, recent: &[String], step: isize) {
+        let count = self.matches(recent).len() as isize;
+        if count > 0 {
+            self.selected = (self.selected as isize + step).rem_euclid(count) as usize;
+        }
+    }
+
+    /// Recent files containing the query, most recent first, and commands
+    /// containing its first word, those starting with it first. A command
+    /// named exactly that comes before everything. Ignores case.
+    pub fn matches(&self, recent: &[String]) -> Vec<PaletteItem> {
+        let query = self.query.to_lowercase();
+        let word = query.split_whitespace().next().unwrap_or_default();
+        let mut commands: Vec<&'static str> = COMMANDS
+            .iter()
+            .copied()
+            .filter(|name| name.contains(word))
+            .collect();
+        commands.sort_by_key(|name| (*name != word, !name.starts_with(word), *name));
+        let exact = commands.first().filter(|name| **name == word).copied();
+        let files = recent
+            .iter()
+            .filter(|path| path.to_lowercase().contains(&query))
+            .map(|path| PaletteItem::File(path.clone()));
+        let mut matches: Vec<PaletteItem> = exact
+            .map(PaletteItem::Command)
+            .into_iter()
+            .chain(files)
+            .chain(
+                commands
+                    .into_iter()
+                    .filter(|name| Some(*name) != exact)
+                    .map(PaletteItem::Command),
+            )
+            .collect();
+        matches.truncate(MAX_RESULTS);
+        matches
+    }
+
+    /// What Enter does. Picking a command the query doesn't start with yet
+    /// completes it instead, leaving the palette open for its arguments.
+    pub fn enter(&mut self, recent: &[String]) -> Option<PaletteAction> {
+        let matches = self.matches(recent);
+        match matches.get(self.selected).or(matches.first()) {
+            Some(PaletteItem::File(path)) => Some(PaletteAction::Open(path.clone())),
+            Some(PaletteItem::Command(name)) => {
+                if self.query.split_whitespace().next() == Some(name) {
+                    return Some(PaletteAction::Run(self.query.trim().to_string()));
+                }
+                self.query = format!("{name} ");
+                self.selected = 0;
+                None
+            }
+            None => {
+                let line = self.query.trim();
+                (!line.is_empty()).then(|| PaletteAction::Run(line.to_string()))
+            }
+        }
+    }
+
+    pub fn rects(&self, width: f32, recent: &[String]) -> Vec<Rect> {
+        let room = WIDTH - 2.0 * PADDING;
+        let lines: Vec<_> = self
+            .matches(recent)
+            .into_iter()
+            .map(|item| match item {
+                PaletteItem::File(path) => (fit_start(&path, room), RESULT_COLOR),
+                PaletteItem::Command(name) => (name.to_string(), COMMAND_COLOR),
+            })
+            .collect();
+        popup_rects(width, &format!("> {}_", self.query), &lines, self.selected)
+    }
+}
+
+/// `text` with as much cut off its start as it takes to fit in `room`
+/// window pixels, so paths keep their file names
+fn fit_start(text: &str, room: f32) -> String {
+    let mut cut = text.to_string();
+    let mut start = 0;
+    while text_size(&cut, TEXT_PIXEL).x > room && start < text.len() {
+        start += text[start..].chars().next().map_or(1, char::len_utf8);
+        cut = format!("...{}", &text[start..]);
+    }
+    cut
+}
+
+/// A popup at the top of a window `width` pixels wide: a prompt, with lines
+/// of text under it and the `selected` one highlighted
+fn popup_rects(
+    width: f32,
+    prompt: &str,
+    lines: &[(String, cgmath::Vector4<f32>)],
+    selected: usize,
+) -> Vec<Rect> {
+    let line = text_size("0", TEXT_PIXEL).y + GAP;
+    let height = PADDING * 2.0 + line * (lines.len() + 1) as f32;
+    let origin = vec2((width - WIDTH) / 2.0, MARGIN);
+    let mut rects = vec![Rect::new(origin, (WIDTH, height), BACKGROUND_COLOR)];
+
+    let mut y = origin.y + PADDING;
+    let x = origin.x + PADDING;
+    rects.extend(text_rects(prompt, vec2(x, y), TEXT_PIXEL, QUERY_COLOR));
+    for (i, (text, color)) in lines.iter().enumerate() {
+        y += line;
+        if i == selected {
+            rects.push(Rect::new(
+                vec2(origin.x, y - GAP / 2.0),
+                (WIDTH, line),
+                SELECTED_COLOR,
+            ));
+        }
+        rects.extend(text_rects(text, vec2(x, y), TEXT_PIXEL, *color));
+    }
+    rects
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn ranks_prefix_matches_first() {
+        let mut graph = Graph::new();
+        for name in ["alpha", "paris", "pa", "Parma", "lyon"] {
+            graph.add_named_node(name);
+        }
+        let mut search = QuickSearch::opened_with(0, 'e');
+        for c in "ePA".chars() {
+            search.push(c);
+        }
+        let names: Vec<String> = search
+            .matches(&graph.nodes)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+        // "alpha" is the source, so it's left out despite containing "pa"
+        assert_eq!(names, ["pa", "Parma", "paris"]);
+
+        search.select(&graph.nodes, -1);
+        assert_eq!(search.target(&graph.nodes), Some(1));
+        search.pop();
+        search.pop();
+        assert_eq!(search.matches(&graph.nodes).len(), 4);
+    }
+
+    #[test]
+    fn palette_opens_files_and_completes_commands() {
+        let recent = ["graphs/lodge.dot".to_string(), "web.graphml".to_string()];
+        let mut palette = QuickOpen::default();
+        for c in "lod".chars() {
+            palette.push(c);
+        }
+        // The command named exactly that beats the file containing it
+        assert_eq!(
+            palette.matches(&recent)[..2],
+            [
+                PaletteItem::Command("lod"),
+                PaletteItem::File("graphs/lodge.dot".into())
+            ]
+        );
+        palette.select(&recent, 1);
+        assert_eq!(
+            palette.enter(&recent),
+            Some(PaletteAction::Open("graphs/lodge.dot".into()))
+        );
+
+        let mut palette = QuickOpen::default();
+        for c in "tickr".chars() {
+            palette.push(c);
+        }
+        assert_eq!(palette.enter(&recent), None);
+        assert_eq!(palette.query, "tickrate ");
+        for c in "30".chars() {
+            palette.push(c);
+        }
+        assert_eq!(
+            palette.enter(&recent),
+            Some(PaletteAction::Run("tickrate 30".into()))
+        );
+        // Every command listed is one the console knows
+        for name in COMMANDS {
+            let err = crate::console::Command::parse(name).err();
+            assert!(
+                !err.is_some_and(|err| err.starts_with("unknown command")),
+                "{name}"
+            );
+        }
+        assert_eq!(fit_start("web.graphml", 1000.0), "web.graphml");
+        let cut = fit_start(&"dir/".repeat(20), 200.0);
+        assert!(cut.starts_with("...") && text_size(&cut, TEXT_PIXEL).x <= 200.0);
+    }
+}
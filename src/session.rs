@@ -0,0 +1,798 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use cgmath::{vec2, vec4, One};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    edge::Marker,
+    graph::{Graph, Remap},
+    node::{Node, Shape},
+    physics::{ForceParams, Repulsion},
+    snapshot::Snapshot,
+};
+
+/// Format version written into every session file
+pub const SESSION_VERSION: u64 = 3;
+/// Oldest version able to read what this build writes, written as
+/// `min_version`. Later versions that only add fields keep it, so older
+/// builds can still open their files; files needing a later reader are
+/// refused rather than half read.
+pub const MIN_READER_VERSION: u64 = 2;
+/// Extension of session files. Version 1 sessions were saved as `.json`.
+pub const SESSION_EXTENSION: &str = "fdg";
+/// Where Ctrl+S saves the session and Ctrl+O loads it from
+pub const DEFAULT_SESSION_PATH: &str = "session.fdg";
+
+/// Upgrades a session from version `i + 1` to the next, for each `i`
+const MIGRATIONS: [fn(&mut Value) -> Result<()>; 2] = [migrate_v1, migrate_v2];
+
+const ROOT_FIELDS: &[&str] = &[
+    "format",
+    "version",
+    "min_version",
+    "ids",
+    "camera",
+    "forces",
+    "alpha",
+    "pinned",
+    "nodes",
+    "edges",
+    "annotations",
+    "snapshots",
+];
+const NODE_FIELDS: &[&str] = &[
+    "id",
+    "external_id",
+    "label",
+    "x",
+    "y",
+    "width",
+    "height",
+    "color",
+    "shape",
+    "image",
+    "attributes",
+];
+const EDGE_FIELDS: &[&str] = &[
+    "source",
+    "target",
+    "color",
+    "width",
+    "head",
+    "tail",
+    "strength",
+    "length",
+    "attributes",
+];
+
+/// Everything needed to pick up where a session left off: the graph with
+/// its layout and styling, the view, and the force model
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub graph: Graph,
+    /// `Camera::translate`, in screen pixels
+    pub translate: cgmath::Vector2<f32>,
+    pub scale: f32,
+    pub forces: ForceParams,
+    /// `Physics::alpha`, so a settled layout stays put when it's loaded
+    pub alpha: f32,
+    pub pinned: BTreeSet<u32>,
+    /// Annotations in their console form, see `Annotation::to_command`
+    pub annotations: Vec<String>,
+    /// Saved layouts, see the `snapshot` command
+    pub snapshots: Vec<Snapshot>,
+    /// Fields from a later version, written back out on save
+    pub unknown: UnknownFields,
+}
+
+/// Fields a later version wrote that this one doesn't know, kept so saving
+/// the session again doesn't lose them. Nodes' are kept by id and edges' by
+/// index, which tells parallel edges apart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnknownFields {
+    pub root: Map<String, Value>,
+    pub nodes: BTreeMap<String, Map<String, Value>>,
+    pub edges: BTreeMap<u32, Map<String, Value>>,
+}
+
+impl UnknownFields {
+    /// Follows edges to their new indices, forgetting removed ones
+    pub fn remap(&mut self, remap: &Remap) {
+        self.edges = std::mem::take(&mut self.edges)
+            .into_iter()
+            .filter_map(|(i, fields)| {
+                Some((remap.edges.get(i as usize).copied().flatten()?, fields))
+            })
+            .collect();
+    }
+}
+
+impl Session {
+    /// Pretty printed JSON with sorted keys, so saving the same session
+    /// twice gives the same file. Edges and pins refer to nodes by id, see
+    /// `node_ids`.
+    pub fn to_json(&self) -> String {
+        let (ids, external) = node_ids(&self.graph);
+        let nodes: Vec<Value> = self
+            .graph
+            .nodes
+            .iter()
+            .zip(&ids)
+            .map(|(node, id)| {
+                let mut fields = node_json(node, id, external);
+                keep_unknown(&mut fields, self.unknown.nodes.get(id));
+                fields
+            })
+            .collect();
+        let edges: Vec<Value> = self
+            .graph
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(i, edge)| {
+                let mut fields = json!({
+                    "source": ids[edge.a_id as usize],
+                    "target": ids[edge.b_id as usize],
+                    "color": color_json(edge.color),
+                    "width": edge.line_width,
+                    "head": edge.head.name(),
+                    "tail": edge.tail.name(),
+                    "strength": edge.strength,
+                    "attributes": attributes_json(&edge.attributes),
+                });
+                if let Some(length) = edge.rest_length {
+                    fields["length"] = json!(length);
+                }
+                keep_unknown(&mut fields, self.unknown.edges.get(&(i as u32)));
+                fields
+            })
+            .collect();
+        let pinned: Vec<&String> = self.pinned.iter().map(|i| &ids[*i as usize]).collect();
+        let snapshots: Vec<Value> = self
+            .snapshots
+            .iter()
+            .map(|snapshot| {
+                let positions: Vec<[f32; 2]> =
+                    snapshot.positions.iter().map(|p| [p.x, p.y]).collect();
+                json!({ "name": snapshot.name, "positions": positions })
+            })
+            .collect();
+        let params = &self.forces;
+        let theta = match params.repulsion {
+            Repulsion::Exact => Value::Null,
+            Repulsion::BarnesHut { theta } => json!(theta),
+        };
+        let mut root = json!({
+            "format": SESSION_EXTENSION,
+            "version": SESSION_VERSION,
+            "min_version": MIN_READER_VERSION,
+            "ids": if external { "external" } else { "index" },
+            "camera": {
+                "translate": [self.translate.x, self.translate.y],
+                "scale": self.scale,
+            },
+            "forces": {
+                "link_distance": params.link_distance,
+                "repulsion_range": params.repulsion_range,
+                "strength": params.strength,
+                "gravity": params.gravity,
+                "collision": params.collision,
+                "collision_iterations": params.collision_iterations,
+                "theta": theta,
+            },
+            "alpha": self.alpha,
+            "pinned": pinned,
+            "nodes": nodes,
+            "edges": edges,
+            "annotations": self.annotations,
+            "snapshots": snapshots,
+        });
+        keep_unknown(&mut root, Some(&self.unknown.root));
+        serde_json::to_string_pretty(&root).expect("session values are all finite")
+    }
+
+    /// Reads sessions of any version, migrating older ones. Later ones are
+    /// read as long as their `min_version` allows, keeping the fields this
+    /// version doesn't know in `unknown`.
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut root: Value = serde_json::from_str(src)?;
+        let version = root
+            .get("version")
+            .and_then(Value::as_u64)
+            .filter(|version| *version > 0)
+            .ok_or_else(|| anyhow!("not a session file, it has no version"))?;
+        let min_version = root
+            .get("min_version")
+            .and_then(Value::as_u64)
+            .unwrap_or(version);
+        if min_version > SESSION_VERSION {
+            bail!(
+                "session version {version} needs version {min_version} to read it, this build reads up to {SESSION_VERSION}"
+            );
+        }
+        let from = (version as usize - 1).min(MIGRATIONS.len());
+        for migrate in &MIGRATIONS[from..] {
+            migrate(&mut root).with_context(|| format!("failed to upgrade version {version}"))?;
+        }
+
+        let external = match root.get("ids").and_then(Value::as_str) {
+            Some("external") => true,
+            Some("index") => false,
+            _ => bail!("ids: expected \"external\" or \"index\""),
+        };
+        let mut unknown = UnknownFields {
+            root: unknown_fields(&root, ROOT_FIELDS),
+            ..UnknownFields::default()
+        };
+        let mut graph = Graph::new();
+        let mut ids = BTreeMap::new();
+        for (i, value) in array(&root, "nodes")?.iter().enumerate() {
+            let (node, id) = parse_node(value, external).with_context(|| format!("node {i}"))?;
+            if ids.insert(id.clone(), graph.add_node(node)).is_some() {
+                bail!("node {i}: id {id} is taken");
+            }
+            let fields = unknown_fields(value, NODE_FIELDS);
+            if !fields.is_empty() {
+                unknown.nodes.insert(id, fields);
+            }
+        }
+        for (i, value) in array(&root, "edges")?.iter().enumerate() {
+            let edge = parse_edge(&mut graph, &ids, value).with_context(|| format!("edge {i}"))?;
+            let fields = unknown_fields(value, EDGE_FIELDS);
+            if !fields.is_empty() {
+                unknown.edges.insert(edge, fields);
+            }
+        }
+        let annotations = optional_array(&root, "annotations")?
+            .iter()
+            .map(|annotation| {
+                annotation
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| anyhow!("annotations: expected strings, got {annotation}"))
+            })
+            .collect::<Result<_>>()?;
+        let snapshots = optional_array(&root, "snapshots")?
+            .iter()
+            .enumerate()
+            .map(|(i, value)| parse_snapshot(value).with_context(|| format!("snapshot {i}")))
+            .collect::<Result<_>>()?;
+
+        let camera = object(&root, "camera")?;
+        let translate = match camera.get("translate").and_then(Value::as_array) {
+            Some(xy) if xy.len() == 2 => vec2(
+                number_value(&xy[0]).context("camera translate")?,
+                number_value(&xy[1]).context("camera translate")?,
+            ),
+            _ => bail!("camera: expected a translate of [x, y]"),
+        };
+        let forces = object(&root, "forces")?;
+        let forces = ForceParams {
+            link_distance: number(forces, "link_distance")?,
+            repulsion_range: number(forces, "repulsion_range")?,
+            strength: number(forces, "strength")?,
+            gravity: number(forces, "gravity")?,
+            collision: number(forces, "collision")?,
+            collision_iterations: number(forces, "collision_iterations")? as u32,
+            repulsion: match forces.get("theta") {
+                None | Some(Value::Null) => Repulsion::Exact,
+                Some(theta) => Repulsion::BarnesHut {
+                    theta: number_value(theta).context("theta")?,
+                },
+            },
+        };
+        let pinned = array(&root, "pinned")?
+            .iter()
+            .map(|id| node_index(&ids, id).context("pinned"))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            translate,
+            scale: number(camera, "scale")?,
+            forces,
+            alpha: root.get("alpha").map_or(Ok(1.0), number_value)?,
+            pinned,
+            annotations,
+            snapshots,
+            graph,
+            unknown,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&src).with_context(|| format!("failed to load {}", path.display()))
+    }
+}
+
+/// Whether `src` is a session rather than a graph, both being JSON: sessions
+/// are the ones with a version
+pub fn is_session(src: &str) -> bool {
+    serde_json::from_str::<Value>(src).is_ok_and(|root| root.get("version").is_some())
+}
+
+/// Ids nodes are saved under, and whether they're their external ids. Those
+/// are used when every node has a unique one, as they stay put while nodes
+/// come and go, otherwise the ids are `n0`, `n1`, ... by index.
+fn node_ids(graph: &Graph) -> (Vec<String>, bool) {
+    match graph.stable_ids() {
+        Some(ids) => (ids.into_iter().map(String::from).collect(), true),
+        None => (
+            (0..graph.nodes.len()).map(|i| format!("n{i}")).collect(),
+            false,
+        ),
+    }
+}
+
+/// Version 1 referred to nodes by index, and had a node's external id as
+/// its `id`. Gives nodes ids the way version 2 does and refers to them by
+/// those.
+fn migrate_v1(root: &mut Value) -> Result<()> {
+    let nodes = root
+        .get_mut("nodes")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow!("expected a \"nodes\" array"))?;
+    let external: Option<Vec<String>> = nodes
+        .iter()
+        .map(|node| node.get("id").and_then(Value::as_str).map(String::from))
+        .collect();
+    let external = external.filter(|ids| ids.iter().collect::<BTreeSet<_>>().len() == ids.len());
+    let ids = match &external {
+        Some(ids) => ids.clone(),
+        None => (0..nodes.len()).map(|i| format!("n{i}")).collect(),
+    };
+    for (node, id) in nodes.iter_mut().zip(&ids) {
+        let fields = node
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("nodes: expected objects"))?;
+        if let (None, Some(external_id)) = (&external, fields.remove("id")) {
+            fields.insert("external_id".into(), external_id);
+        }
+        fields.insert("id".into(), json!(id));
+    }
+
+    let id_of = |key: &str, i: &Value| -> Result<Value> {
+        i.as_u64()
+            .and_then(|i| ids.get(i as usize))
+            .map(|id| json!(id))
+            .ok_or_else(|| anyhow!("{key}: no node {i}"))
+    };
+    let edges = root
+        .get_mut("edges")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow!("expected an \"edges\" array"))?;
+    for edge in edges {
+        for key in ["source", "target"] {
+            let end = edge.get(key).unwrap_or(&Value::Null);
+            edge[key] = id_of(key, end)?;
+        }
+    }
+    let pinned = root
+        .get_mut("pinned")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow!("expected a \"pinned\" array"))?;
+    for i in pinned {
+        *i = id_of("pinned", i)?;
+    }
+    root["ids"] = json!(if external.is_some() {
+        "external"
+    } else {
+        "index"
+    });
+    root["version"] = json!(2);
+    Ok(())
+}
+
+/// Version 3 added annotations and snapshots, which a version 2 session has
+/// none of
+fn migrate_v2(root: &mut Value) -> Result<()> {
+    root["version"] = json!(3);
+    Ok(())
+}
+
+/// Fields of `value` not among `known`
+fn unknown_fields(value: &Value, known: &[&str]) -> Map<String, Value> {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !known.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Adds the unknown fields back, never over a known one
+fn keep_unknown(fields: &mut Value, unknown: Option<&Map<String, Value>>) {
+    let (Some(fields), Some(unknown)) = (fields.as_object_mut(), unknown) else {
+        return;
+    };
+    for (key, value) in unknown {
+        fields.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+fn node_index(ids: &BTreeMap<String, u32>, id: &Value) -> Result<u32> {
+    let id = id
+        .as_str()
+        .ok_or_else(|| anyhow!("expected a node id, got {id}"))?;
+    ids.get(id).copied().ok_or_else(|| anyhow!("no node {id}"))
+}
+
+fn node_json(node: &Node, id: &str, external: bool) -> Value {
+    let mut fields = json!({
+        "id": id,
+        "x": node.position.x,
+        "y": node.position.y,
+        "width": node.size.x,
+        "height": node.size.y,
+        "color": color_json(node.color),
+        "shape": node.shape.name(),
+        "attributes": attributes_json(&node.attributes),
+    });
+    match &node.external_id {
+        Some(external_id) if !external => fields["external_id"] = json!(external_id),
+        _ => {}
+    }
+    if let Some(label) = &node.label {
+        fields["label"] = json!(label);
+    }
+    if let Some(image) = &node.image {
+        fields["image"] = json!(image);
+    }
+    fields
+}
+
+/// The node and the id it was saved under
+fn parse_node(value: &Value, external: bool) -> Result<(Node, String)> {
+    let fields = value
+        .as_object()
+        .ok_or_else(|| anyhow!("expected an object"))?;
+    let mut node = Node::new(
+        (number(fields, "width")?, number(fields, "height")?),
+        (number(fields, "x")?, number(fields, "y")?, 0.0),
+        cgmath::Quaternion::one(),
+        parse_color(fields)?,
+    );
+    if let Some(name) = fields.get("shape") {
+        node.shape = name
+            .as_str()
+            .and_then(Shape::parse)
+            .ok_or_else(|| anyhow!("shape: unknown shape {name}"))?;
+    }
+    node.attributes = parse_attributes(fields)?;
+    let id = optional_string(fields, "id")?.ok_or_else(|| anyhow!("missing id"))?;
+    node.external_id = match external {
+        true => Some(id.clone()),
+        false => optional_string(fields, "external_id")?,
+    };
+    node.label = optional_string(fields, "label")?;
+    node.image = optional_string(fields, "image")?;
+    Ok((node, id))
+}
+
+/// Adds the edge, returning the ids of its ends
+/// The index of the edge added
+fn parse_edge(graph: &mut Graph, ids: &BTreeMap<String, u32>, value: &Value) -> Result<u32> {
+    let fields = value
+        .as_object()
+        .ok_or_else(|| anyhow!("expected an object"))?;
+    let end = |key: &str| -> Result<u32> {
+        let id = fields.get(key).unwrap_or(&Value::Null);
+        node_index(ids, id).with_context(|| key.to_string())
+    };
+    let (a, b) = (end("source")?, end("target")?);
+    let marker = |key| -> Result<Marker> {
+        match fields.get(key) {
+            None => Ok(Marker::None),
+            Some(name) => name
+                .as_str()
+                .and_then(Marker::parse)
+                .ok_or_else(|| anyhow!("{key}: unknown marker {name}")),
+        }
+    };
+    let (head, tail) = (marker("head")?, marker("tail")?);
+    let color = parse_color(fields)?;
+    let line_width = number(fields, "width")?;
+    let strength = number(fields, "strength")?;
+    let rest_length = fields.get("length").map(number_value).transpose()?;
+    let attributes = parse_attributes(fields)?;
+
+    let index = graph.add_edge(a, b);
+    let edge = &mut graph.edges[index as usize];
+    edge.color = color;
+    edge.line_width = line_width;
+    edge.head = head;
+    edge.tail = tail;
+    edge.strength = strength;
+    edge.rest_length = rest_length;
+    edge.attributes = attributes;
+    Ok(index)
+}
+
+fn parse_snapshot(value: &Value) -> Result<Snapshot> {
+    let fields = value
+        .as_object()
+        .ok_or_else(|| anyhow!("expected an object"))?;
+    let name = optional_string(fields, "name")?.ok_or_else(|| anyhow!("expected a name"))?;
+    let positions = fields
+        .get("positions")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("expected a \"positions\" array"))?
+        .iter()
+        .map(|p| match p.as_array().map(Vec::as_slice) {
+            Some([x, y]) => Ok(vec2(number_value(x)?, number_value(y)?)),
+            _ => Err(anyhow!("positions: expected [x, y], got {p}")),
+        })
+        .collect::<Result<_>>()?;
+    Ok(Snapshot { name, positions })
+}
+
+/// Colors are kept as `[r, g, b, a]` rather than hex, which would round them
+/// and drop alpha
+fn color_json(color: cgmath::Vector4<f32>) -> Value {
+    json!([color.x, color.y, color.z, color.w])
+}
+
+fn parse_color(fields: &Map<String, Value>) -> Result<cgmath::Vector4<f32>> {
+    let channels = fields
+        .get("color")
+        .and_then(Value::as_array)
+        .filter(|channels| channels.len() == 4)
+        .ok_or_else(|| anyhow!("expected a color of [r, g, b, a]"))?
+        .iter()
+        .map(number_value)
+        .collect::<Result<Vec<f32>>>()
+        .context("color")?;
+    Ok(vec4(channels[0], channels[1], channels[2], channels[3]))
+}
+
+fn attributes_json(attributes: &Attributes) -> Value {
+    Value::Object(
+        attributes
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    AttrValue::Number(n) => json!(n),
+                    AttrValue::Text(text) => json!(text),
+                };
+                (key.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+fn parse_attributes(fields: &Map<String, Value>) -> Result<Attributes> {
+    let Some(attributes) = fields.get("attributes") else {
+        return Ok(Attributes::new());
+    };
+    attributes
+        .as_object()
+        .ok_or_else(|| anyhow!("attributes must be an object"))?
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(text) => AttrValue::Text(text.clone()),
+                value => AttrValue::Number(
+                    number_value(value).with_context(|| format!("attribute {key}"))?,
+                ),
+            };
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+fn array<'a>(root: &'a Value, key: &str) -> Result<&'a Vec<Value>> {
+    root.get(key)
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("expected a \"{key}\" array"))
+}
+
+/// Empty when missing, as in sessions from before the field was added
+fn optional_array<'a>(root: &'a Value, key: &str) -> Result<&'a [Value]> {
+    match root.get(key) {
+        None => Ok(&[]),
+        Some(value) => value
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow!("expected a \"{key}\" array")),
+    }
+}
+
+fn object<'a>(root: &'a Value, key: &str) -> Result<&'a Map<String, Value>> {
+    root.get(key)
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("expected a \"{key}\" object"))
+}
+
+fn number(fields: &Map<String, Value>, key: &str) -> Result<f32> {
+    let value = fields.get(key).ok_or_else(|| anyhow!("missing {key}"))?;
+    number_value(value).with_context(|| key.to_string())
+}
+
+fn number_value(value: &Value) -> Result<f32> {
+    value
+        .as_f64()
+        .map(|n| n as f32)
+        .ok_or_else(|| anyhow!("expected a number, got {value}"))
+}
+
+fn optional_string(fields: &Map<String, Value>, key: &str) -> Result<Option<String>> {
+    match fields.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(value) => Err(anyhow!("{key}: expected a string, got {value}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sessions_round_trip() {
+        let mut graph = Graph::new();
+        let a = graph.add_named_node("a");
+        let b = graph.add_named_node("b");
+        graph.nodes[a as usize].position = cgmath::vec3(12.5, -40.0, 0.0);
+        graph.nodes[b as usize].size = vec2(30.0, 30.0);
+        graph.nodes[b as usize].color = vec4(0.1, 0.2, 0.3, 0.4);
+        graph.nodes[b as usize].label = Some("bee".into());
+        graph.nodes[b as usize].shape = Shape::Diamond;
+        graph.nodes[b as usize].image = Some("bee.png".into());
+        graph.nodes[b as usize]
+            .attributes
+            .insert("weight".into(), AttrValue::Number(2.5));
+        let edge = graph.add_edge(a, b) as usize;
+        graph.edges[edge].head = Marker::Arrow;
+        graph.edges[edge].rest_length = Some(80.0);
+        graph.edges[edge].strength = 0.5;
+        graph.add_node(Node::new(
+            (10.0, 10.0),
+            (1.0, 2.0, 0.0),
+            cgmath::Quaternion::one(),
+            (1.0, 1.0, 1.0, 1.0),
+        ));
+        let session = Session {
+            graph,
+            translate: vec2(-100.0, 33.3),
+            scale: 0.75,
+            forces: ForceParams {
+                repulsion: Repulsion::BarnesHut { theta: 0.7 },
+                ..ForceParams::default()
+            },
+            alpha: 0.01,
+            pinned: BTreeSet::from([1]),
+            annotations: vec!["note 10 20 \"hi\"".into()],
+            snapshots: vec![Snapshot {
+                name: "start".into(),
+                positions: vec![vec2(1.0, 2.0), vec2(-3.5, 4.0), vec2(0.0, 0.0)],
+            }],
+            unknown: UnknownFields::default(),
+        };
+
+        let loaded = Session::parse(&session.to_json()).unwrap();
+        let nodes = |session: &Session| {
+            session
+                .graph
+                .nodes
+                .iter()
+                .map(|n| {
+                    let fields = (n.position, n.size, n.color, n.shape, n.image.clone());
+                    let fields = (fields, n.label.clone());
+                    (fields, n.external_id.clone(), n.attributes.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+        let edges = |session: &Session| {
+            session
+                .graph
+                .edges
+                .iter()
+                .map(|e| {
+                    let fields = (e.a_id, e.b_id, e.color, e.line_width, e.head, e.tail);
+                    (fields, e.rest_length, e.strength, e.attributes.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(nodes(&loaded), nodes(&session));
+        assert_eq!(edges(&loaded), edges(&session));
+        assert_eq!(loaded.translate, session.translate);
+        assert_eq!(loaded.scale, session.scale);
+        assert_eq!(loaded.forces, session.forces);
+        assert_eq!(loaded.alpha, session.alpha);
+        assert_eq!(loaded.pinned, session.pinned);
+        assert_eq!(loaded.annotations, session.annotations);
+        assert_eq!(loaded.snapshots, session.snapshots);
+
+        assert_eq!(loaded.to_json(), session.to_json());
+
+        let newer = session
+            .to_json()
+            .replacen("\"min_version\": 2", "\"min_version\": 99", 1);
+        assert!(Session::parse(&newer).is_err());
+        assert!(Session::parse(r#"{"nodes": [], "edges": []}"#).is_err());
+        let dangling = session
+            .to_json()
+            .replacen("\"target\": \"n1\"", "\"target\": \"n7\"", 1);
+        assert!(Session::parse(&dangling).is_err());
+    }
+
+    const V1: &str = r#"{
+        "version": 1,
+        "camera": { "translate": [0, 0], "scale": 1 },
+        "forces": {
+            "link_distance": 1, "repulsion_range": 1, "strength": 1, "gravity": 1,
+            "collision": 1, "collision_iterations": 1, "theta": null
+        },
+        "pinned": [2],
+        "nodes": [
+            { "id": "a", "x": 0, "y": 0, "width": 10, "height": 10, "color": [1, 1, 1, 1] },
+            { "id": "b", "x": 0, "y": 0, "width": 10, "height": 10, "color": [1, 1, 1, 1] },
+            { "id": "c", "x": 0, "y": 0, "width": 10, "height": 10, "color": [1, 1, 1, 1] }
+        ],
+        "edges": [
+            { "source": 2, "target": 0, "color": [1, 1, 1, 1], "width": 1, "strength": 1 },
+            { "source": 2, "target": 0, "color": [1, 1, 1, 1], "width": 1, "strength": 1 }
+        ]
+    }"#;
+
+    #[test]
+    fn older_and_newer_sessions_load() {
+        assert_eq!(MIGRATIONS.len() as u64, SESSION_VERSION - 1);
+        let old = Session::parse(V1).unwrap();
+        let ids: Vec<_> = old
+            .graph
+            .nodes
+            .iter()
+            .map(|n| n.external_id.clone())
+            .collect();
+        assert_eq!(ids, [Some("a".into()), Some("b".into()), Some("c".into())]);
+        assert_eq!((old.graph.edges[0].a_id, old.graph.edges[0].b_id), (2, 0));
+        assert_eq!(old.pinned, BTreeSet::from([2]));
+        assert!(old.annotations.is_empty() && old.snapshots.is_empty());
+        // Saved again, edges and pins name nodes by their ids
+        let saved = old.to_json();
+        let root: Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(
+            (&root["edges"][0]["source"], &root["pinned"]),
+            (&json!("c"), &json!(["c"]))
+        );
+
+        // Ids a version 1 file can't use, being repeated, give way to indices
+        let repeated = V1.replacen(r#""id": "b""#, r#""id": "a""#, 1);
+        let old = Session::parse(&repeated).unwrap();
+        assert_eq!(old.graph.nodes[1].external_id.as_deref(), Some("a"));
+        assert!(old.to_json().contains(r#""source": "n2""#));
+
+        // Later versions' fields are kept as they were, on the parallel edge
+        // they were written on
+        let mut newer = saved
+            .replacen("\"version\": 3", "\"version\": 4", 1)
+            .replacen("\"alpha\"", "\"theme\": \"dark\",\n  \"alpha\"", 1)
+            .replacen("\"id\": \"b\"", "\"id\": \"b\", \"icon\": \"star\"", 1);
+        let last_head = newer.rfind("\"head\"").unwrap();
+        newer.insert_str(last_head, "\"bend\": 0.5, ");
+        let loaded = Session::parse(&newer).unwrap();
+        assert_eq!(loaded.unknown.root["theme"], "dark");
+        assert_eq!(loaded.unknown.nodes["b"]["icon"], "star");
+        assert_eq!(loaded.unknown.edges.keys().collect::<Vec<_>>(), [&1]);
+        assert_eq!(loaded.unknown.edges[&1]["bend"], 0.5);
+        assert_eq!(
+            Session::parse(&loaded.to_json()).unwrap().unknown,
+            loaded.unknown
+        );
+    }
+}
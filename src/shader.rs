@@ -0,0 +1,94 @@
+//! WGSL shared between shaders. WGSL has no includes of its own, so a
+//! shader names the snippets it uses with `#include "file.wgsl"` lines, which
+//! `expand` replaces before the source is compiled.
+
+use crate::{color, edge, node};
+
+/// Snippets shaders can include, by file name, as compiled in
+pub const SNIPPETS: [(&str, &str); 2] = [
+    ("glow.wgsl", include_str!("glow.wgsl")),
+    ("color.wgsl", include_str!("color.wgsl")),
+];
+
+/// Included like a snippet, but written out from the Rust side's constants
+/// so shaders can't drift from them
+pub const CONSTANTS: &str = "constants.wgsl";
+
+/// `source` with each `#include` line replaced by the snippet `snippet`
+/// finds for its file, or by `CONSTANTS`. Snippets can't include others.
+pub fn expand<'a>(
+    source: &str,
+    snippet: impl Fn(&str) -> Option<&'a str>,
+) -> Result<String, String> {
+    let mut expanded = String::with_capacity(source.len());
+    for line in source.lines() {
+        match include(line) {
+            Some(CONSTANTS) => expanded += &constants(),
+            Some(file) => {
+                expanded += snippet(file).ok_or_else(|| format!("nothing to include as {file}"))?;
+            }
+            None => expanded += line,
+        }
+        expanded.push('\n');
+    }
+    Ok(expanded)
+}
+
+/// `source` expanded with the compiled in snippets, what pipelines are first
+/// built from
+pub fn builtin(source: &str) -> String {
+    let snippet = |file: &str| {
+        SNIPPETS
+            .iter()
+            .find(|(name, _)| *name == file)
+            .map(|(_, snippet)| *snippet)
+    };
+    expand(source, snippet).expect("the compiled in shaders include compiled in snippets")
+}
+
+/// Whether `source` includes the snippet `file`
+pub fn includes(source: &str, file: &str) -> bool {
+    source.lines().any(|line| include(line) == Some(file))
+}
+
+fn constants() -> String {
+    [
+        ("EDGE_DEPTH", node::EDGE_DEPTH),
+        ("MARKER_SCALE", edge::MARKER_SCALE),
+        ("SRGB_CUTOFF", color::SRGB_CUTOFF),
+        ("SRGB_SLOPE", color::SRGB_SLOPE),
+        ("SRGB_OFFSET", color::SRGB_OFFSET),
+        ("SRGB_GAMMA", color::SRGB_GAMMA),
+    ]
+    .iter()
+    .map(|(name, value)| format!("let {name}: f32 = {value:?};"))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// The file an `#include "file"` line names
+fn include(line: &str) -> Option<&str> {
+    let file = line.trim().strip_prefix("#include")?.trim();
+    file.strip_prefix('"')?.strip_suffix('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn includes_are_expanded() {
+        let source = "let A: f32 = 1.0;\n  #include \"glow.wgsl\"\nfn main() {}\n";
+        assert!(includes(source, "glow.wgsl"));
+        assert!(!includes(source, "color.wgsl"));
+        let expanded = expand(source, |file| {
+            (file == "glow.wgsl").then_some("fn glow() {}")
+        })
+        .unwrap();
+        assert_eq!(expanded, "let A: f32 = 1.0;\nfn glow() {}\nfn main() {}\n");
+        assert!(expand(source, |_| None).is_err());
+        assert!(builtin(source).contains("fn brighten"));
+        let constants = expand("#include \"constants.wgsl\"", |_| None).unwrap();
+        assert!(constants.contains(&format!("let EDGE_DEPTH: f32 = {:?};", node::EDGE_DEPTH)));
+    }
+}
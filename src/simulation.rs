@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    algorithms,
+    edge::Edge,
+    graph::{Graph, GraphMutation, GraphView, NodeId, Remap},
+    layout,
+    node::Node,
+    physics::{self, Anchor, ForceParams, Physics},
+};
+
+/// Radius of the disk nodes are scattered in by `Simulation::seeded`
+pub const DEFAULT_SEED_RADIUS: f32 = 500.0;
+
+/// Runs the force layout on a `Graph` without a window or GPU. This is the
+/// same physics `State` steps every frame, so layouts computed here match
+/// what the viewer would show.
+pub struct Simulation {
+    pub graph: Graph,
+    pub physics: Physics,
+    edge_map: BTreeMap<NodeId, Vec<u32>>,
+}
+
+impl Simulation {
+    /// Starts from the positions already stored in the graph
+    pub fn new(graph: Graph) -> Self {
+        let physics = Physics::new(&graph.nodes);
+        let edge_map = algorithms::incidence_map(&graph.edges, 0..graph.edges.len() as u32);
+        Self {
+            graph,
+            physics,
+            edge_map,
+        }
+    }
+
+    /// Starts from a random placement derived from `seed`
+    pub fn seeded(mut graph: Graph, seed: u64) -> Self {
+        layout::random_placement(&mut graph, seed, DEFAULT_SEED_RADIUS);
+        Self::new(graph)
+    }
+
+    /// Starts from a multilevel layout derived from `seed`, see
+    /// `layout::multilevel`. It's close to rest already, which makes it the
+    /// better start for big graphs.
+    pub fn multilevel(mut graph: Graph, seed: u64) -> Self {
+        layout::multilevel(&mut graph, seed);
+        let mut sim = Self::new(graph);
+        sim.physics
+            .set_params(ForceParams::calibrate(&sim.graph.nodes, &sim.graph.edges));
+        sim.physics.alpha = layout::REFINE_ALPHA;
+        sim
+    }
+
+    /// Starts from a spectral placement, see `layout::spectral_placement`.
+    /// Mesh-like graphs settle unfolded from there, where random starts
+    /// often leave them creased.
+    pub fn spectral(mut graph: Graph, seed: u64) -> Self {
+        let params = ForceParams::calibrate(&graph.nodes, &graph.edges);
+        layout::spectral_placement(&mut graph, seed, params.link_distance);
+        let mut sim = Self::new(graph);
+        sim.physics.set_params(params);
+        sim
+    }
+
+    pub fn tick(&mut self) {
+        self.physics
+            .tick(&BTreeSet::new(), &self.graph.edges, &self.edge_map);
+        self.physics
+            .apply(&mut self.graph.nodes, &mut self.graph.edges, &self.edge_map);
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// Ticks until the layout comes to rest or `max_ticks` run out, returns
+    /// the number of ticks run
+    pub fn run_until_settled(&mut self, max_ticks: usize) -> usize {
+        for ticks in 0..max_ticks {
+            if self.physics.is_settled() {
+                return ticks;
+            }
+            self.tick();
+        }
+        max_ticks
+    }
+
+    /// Changes the graph between ticks, the way `State::apply_batch` does in
+    /// the viewer. Kept nodes keep their velocity and stay pinned, frozen or
+    /// anchored, added nodes start where they're given.
+    pub fn apply(&mut self, mutation: GraphMutation) -> Result<Remap, String> {
+        let removes = mutation.removes();
+        let remap = self.graph.apply(mutation)?;
+        let old_objs = std::mem::take(&mut self.physics.objs);
+        let strength = self.physics.params.strength;
+        self.physics.objs = self
+            .graph
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| physics::Object::from_node(i as u32, node, strength))
+            .collect();
+        for (old, obj) in old_objs.iter().enumerate() {
+            if let Some(new) = remap.node(old as NodeId) {
+                let kept = &mut self.physics.objs[new as usize];
+                (kept.vx, kept.vy, kept.vz) = (obj.vx, obj.vy, obj.vz);
+                kept.fixed = obj.fixed;
+            }
+        }
+        self.physics.clear_step();
+        self.edge_map =
+            algorithms::incidence_map(&self.graph.edges, 0..self.graph.edges.len() as u32);
+        if removes {
+            self.physics.frozen = remap.node_set(&self.physics.frozen);
+            self.physics.heat.clear();
+        }
+        if let Some(anchor) = self.physics.anchor.take() {
+            self.physics.anchor = remap
+                .node(anchor.node)
+                .map(|node| Anchor::new(node, self.graph.nodes.len(), &self.graph.edges));
+        }
+        Ok(remap)
+    }
+
+    pub fn positions(&self) -> Vec<cgmath::Vector2<f32>> {
+        self.graph
+            .nodes
+            .iter()
+            .map(|node| node.position.truncate())
+            .collect()
+    }
+}
+
+impl GraphView for Simulation {
+    fn nodes(&self) -> &[Node] {
+        &self.graph.nodes
+    }
+
+    fn edges(&self) -> &[Edge] {
+        &self.graph.edges
+    }
+}
@@ -0,0 +1,150 @@
+use cgmath::{vec2, VectorSpace};
+
+use crate::node::Node;
+
+/// Frames a restore takes to move nodes into place
+pub const RESTORE_FRAMES: u32 = 45;
+
+/// Node positions saved under a name, so a hand-tuned layout can be brought
+/// back after experimenting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub name: String,
+    /// Indexed by node
+    pub positions: Vec<cgmath::Vector2<f32>>,
+}
+
+impl Snapshot {
+    pub fn capture<S: Into<String>>(name: S, nodes: &[Node]) -> Self {
+        Self {
+            name: name.into(),
+            positions: nodes.iter().map(|n| n.position.truncate()).collect(),
+        }
+    }
+}
+
+/// Animated move from the current positions to a snapshot's
+#[derive(Debug, Clone)]
+pub struct Transition {
+    from: Vec<cgmath::Vector2<f32>>,
+    to: Vec<cgmath::Vector2<f32>>,
+    frame: u32,
+}
+
+impl Transition {
+    /// Nodes the snapshot doesn't cover stay where they are, positions for
+    /// nodes that no longer exist are ignored
+    pub fn new(nodes: &[Node], snapshot: &Snapshot) -> Self {
+        let from: Vec<_> = nodes.iter().map(|n| n.position.truncate()).collect();
+        let to = from
+            .iter()
+            .enumerate()
+            .map(|(i, p)| snapshot.positions.get(i).copied().unwrap_or(*p))
+            .collect();
+        Self { from, to, frame: 0 }
+    }
+
+    /// Advances one frame and returns every node's position, `None` once the
+    /// nodes have arrived
+    pub fn step(&mut self) -> Option<Vec<cgmath::Vector2<f32>>> {
+        if self.frame >= RESTORE_FRAMES {
+            return None;
+        }
+        self.frame += 1;
+        let t = self.frame as f32 / RESTORE_FRAMES as f32;
+        // Ease in and out
+        let t = t * t * (3.0 - 2.0 * t);
+        Some(
+            self.from
+                .iter()
+                .zip(&self.to)
+                .map(|(a, b)| a.lerp(*b, t))
+                .collect(),
+        )
+    }
+}
+
+/// Serializes snapshots as a `snapshot <name>` line each, followed by one
+/// `<x> <y>` line per node
+pub fn write_snapshots(snapshots: &[Snapshot]) -> String {
+    let mut src = String::new();
+    for snapshot in snapshots {
+        src += &format!("snapshot {}\n", snapshot.name);
+        for p in &snapshot.positions {
+            src += &format!("{} {}\n", p.x, p.y);
+        }
+    }
+    src
+}
+
+pub fn parse_snapshots(src: &str) -> Result<Vec<Snapshot>, String> {
+    let mut snapshots: Vec<Snapshot> = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("snapshot ") {
+            snapshots.push(Snapshot {
+                name: name.trim().to_string(),
+                positions: vec![],
+            });
+            continue;
+        }
+        let snapshot = snapshots
+            .last_mut()
+            .ok_or_else(|| format!("line {}: position before any snapshot", i + 1))?;
+        let coords: Vec<f32> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("line {}: expected two numbers, got {line:?}", i + 1))?;
+        let [x, y] = coords[..] else {
+            return Err(format!(
+                "line {}: expected two numbers, got {line:?}",
+                i + 1
+            ));
+        };
+        snapshot.positions.push(vec2(x, y));
+    }
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn round_trip_and_restore() {
+        let snapshots = vec![
+            Snapshot {
+                name: "curated v1".into(),
+                positions: vec![vec2(0.5, -2.0), vec2(100.0, 250.25)],
+            },
+            Snapshot {
+                name: "empty".into(),
+                positions: vec![],
+            },
+        ];
+        let src = write_snapshots(&snapshots);
+        assert_eq!(parse_snapshots(&src), Ok(snapshots.clone()));
+        assert!(parse_snapshots("1 2").is_err());
+        assert!(parse_snapshots("snapshot a\n1 2 3").is_err());
+
+        let mut graph = Graph::new();
+        for name in ["a", "b", "c"] {
+            graph.add_named_node(name);
+        }
+        let mut transition = Transition::new(&graph.nodes, &snapshots[0]);
+        let mut last = None;
+        while let Some(positions) = transition.step() {
+            last = Some(positions);
+        }
+        // The third node isn't in the snapshot and stays put
+        assert_eq!(
+            last.unwrap(),
+            [vec2(0.5, -2.0), vec2(100.0, 250.25), vec2(0.0, 0.0)]
+        );
+    }
+}
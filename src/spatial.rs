@@ -0,0 +1,201 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    graph::DEFAULT_NODE_SIZE,
+    node::{self, Node, DISK_RADIUS},
+};
+
+/// Side of a `SpatialGrid` cell in world units, so a default node's disk
+/// reaches no further than the cells around its own
+pub const DEFAULT_CELL_SIZE: f32 = 2.0 * DEFAULT_NODE_SIZE;
+
+type CellKey = (i32, i32);
+
+/// Uniform grid over node centers for finding the nodes near a point without
+/// looking at every one of them. Nodes are filed under the cell holding
+/// their center and move between cells as `update` and `sync` see them move,
+/// so keeping it current costs a lookup per node rather than a rebuild.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<CellKey, Vec<u32>>,
+    /// Cell each node is filed under, by node index
+    homes: Vec<CellKey>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            homes: vec![],
+        }
+    }
+
+    fn key(&self, pos: cgmath::Vector2<f32>) -> CellKey {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Side of a cell in world units
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Nodes filed so far. Any past this are left for `sync` to file.
+    pub fn len(&self) -> usize {
+        self.homes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.homes.is_empty()
+    }
+
+    /// Refiles `node`, which has moved to `pos`. Nodes past the ones already
+    /// filed are ignored until the next `sync`.
+    pub fn update(&mut self, node: u32, pos: cgmath::Vector2<f32>) {
+        let key = self.key(pos);
+        let Some(home) = self.homes.get_mut(node as usize) else {
+            return;
+        };
+        if *home == key {
+            return;
+        }
+        let old = std::mem::replace(home, key);
+        self.unfile(old, node);
+        self.cells.entry(key).or_default().push(node);
+    }
+
+    fn unfile(&mut self, key: CellKey, node: u32) {
+        if let Some(cell) = self.cells.get_mut(&key) {
+            cell.retain(|i| *i != node);
+            if cell.is_empty() {
+                self.cells.remove(&key);
+            }
+        }
+    }
+
+    /// Catches up with `nodes`: refiles the ones that changed cells, files
+    /// new ones and drops ones that are gone
+    pub fn sync(&mut self, nodes: &[Node]) {
+        self.sync_points(nodes.iter().map(|node| node.position.truncate()));
+    }
+
+    /// `sync` for points other than node centers, such as where the physics
+    /// has nodes mid-tick. The `i`th point is filed as node `i`.
+    pub fn sync_points(&mut self, points: impl ExactSizeIterator<Item = cgmath::Vector2<f32>>) {
+        while self.homes.len() > points.len() {
+            let node = self.homes.len() as u32 - 1;
+            let home = self.homes.pop().expect("more homes than nodes");
+            self.unfile(home, node);
+        }
+        for (i, pos) in points.enumerate() {
+            if i < self.homes.len() {
+                self.update(i as u32, pos);
+            } else {
+                let key = self.key(pos);
+                self.homes.push(key);
+                self.cells.entry(key).or_default().push(i as u32);
+            }
+        }
+    }
+
+    /// Nodes whose centers may be within `reach` of `pos`, in no particular
+    /// order. It errs on the side of too many, never too few.
+    pub fn near(&self, pos: cgmath::Vector2<f32>, reach: f32) -> Vec<u32> {
+        let reach = cgmath::vec2(reach, reach);
+        let (min, max) = (self.key(pos - reach), self.key(pos + reach));
+        let mut found = vec![];
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                found.extend(self.cells.get(&(x, y)).into_iter().flatten());
+            }
+        }
+        found
+    }
+
+    /// `node::pick`, looking only at nodes near `pos`. Nodes not filed yet
+    /// are always looked at, so ones added since the last `sync` can be
+    /// picked.
+    pub fn pick(
+        &self,
+        nodes: &[Node],
+        pos: cgmath::Vector2<f32>,
+        tolerance: f32,
+        hidden: &BTreeSet<u32>,
+    ) -> Option<u32> {
+        let filed = self.homes.len().min(nodes.len()) as u32;
+        let mut candidates = self.near(pos, DISK_RADIUS + tolerance);
+        candidates.retain(|i| *i < filed);
+        candidates.extend(filed..nodes.len() as u32);
+        // Topmost first, as `node::pick` expects
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+        node::pick_among(nodes, candidates, pos, tolerance, hidden)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cgmath::vec2;
+
+    use super::*;
+
+    fn node(x: f32, y: f32) -> Node {
+        Node::new(
+            (50.0, 50.0),
+            (x, y, 0.0),
+            cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            (1.0, 1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn grid_picks_like_a_full_scan() {
+        let mut nodes: Vec<Node> = (0..100)
+            .map(|i| node((i % 10) as f32 * 130.0, (i / 10) as f32 * 130.0 - 600.0))
+            .collect();
+        let mut grid = SpatialGrid::default();
+        grid.sync(&nodes);
+        assert_eq!(grid.len(), 100);
+        let hidden = BTreeSet::from([55]);
+        let check = |grid: &SpatialGrid, nodes: &[Node]| {
+            for x in (-100..1400).step_by(37) {
+                for y in (-700..700).step_by(41) {
+                    let pos = vec2(x as f32, y as f32);
+                    assert_eq!(
+                        grid.pick(nodes, pos, 8.0, &hidden),
+                        node::pick(nodes, pos, 8.0, &hidden),
+                        "{pos:?}"
+                    );
+                }
+            }
+        };
+        check(&grid, &nodes);
+        assert!(grid.near(vec2(-5000.0, 0.0), 10.0).is_empty());
+
+        // Moved far, and on top of another node, which it now covers
+        nodes[3].position = cgmath::vec3(130.0, -600.0, 0.0);
+        nodes[7].position = cgmath::vec3(5000.0, 5000.0, 0.0);
+        grid.update(3, vec2(130.0, -600.0));
+        grid.update(7, vec2(5000.0, 5000.0));
+        assert_eq!(grid.near(vec2(5000.0, 5000.0), 1.0), [7]);
+        // Added and removed without a sync yet
+        nodes.push(node(-60.0, -600.0));
+        check(&grid, &nodes);
+        nodes.truncate(50);
+        check(&grid, &nodes);
+
+        grid.sync(&nodes);
+        assert_eq!(grid.len(), 50);
+        assert_eq!(grid.cells.values().map(Vec::len).sum::<usize>(), 50);
+        check(&grid, &nodes);
+    }
+}
@@ -0,0 +1,52 @@
+use cgmath::{vec2, vec4};
+
+use crate::overlay::{text_rects, text_size, Anchor, Rect};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.6);
+const TITLE_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.9);
+const GAP: f32 = 6.0;
+
+/// Panel of diagnostic numbers, one `label: value` line each
+#[derive(Debug, Clone, Copy)]
+pub struct StatsPanel {
+    pub anchor: Anchor,
+    pub margin: f32,
+    pub padding: f32,
+}
+
+impl Default for StatsPanel {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            margin: 16.0,
+            padding: 8.0,
+        }
+    }
+}
+
+impl StatsPanel {
+    pub fn rects(&self, width: f32, height: f32, title: &str, lines: &[String]) -> Vec<Rect> {
+        let line = text_size("0", TEXT_PIXEL).y + GAP;
+        let content = std::iter::once(title)
+            .chain(lines.iter().map(String::as_str))
+            .map(|text| text_size(text, TEXT_PIXEL))
+            .fold(vec2(0.0f32, 0.0f32), |acc, size| {
+                vec2(acc.x.max(size.x), acc.y + line)
+            });
+        let size = content - vec2(0.0, GAP) + vec2(self.padding, self.padding) * 2.0;
+        let origin = self.anchor.place(width, height, size, self.margin);
+
+        let mut rects = vec![Rect::new(origin, size, BACKGROUND_COLOR)];
+        let mut pos = origin + vec2(self.padding, self.padding);
+        rects.extend(text_rects(title, pos, TEXT_PIXEL, TITLE_COLOR));
+        for text in lines {
+            pos.y += line;
+            rects.extend(text_rects(text, pos, TEXT_PIXEL, TEXT_COLOR));
+        }
+        rects
+    }
+}
@@ -0,0 +1,65 @@
+use cgmath::{vec2, vec4};
+
+use crate::overlay::{text_rects, text_size, Rect};
+
+/// Window pixels per font pixel
+pub const TEXT_PIXEL: f32 = 2.0;
+
+const BACKGROUND_COLOR: cgmath::Vector4<f32> = vec4(0.0, 0.0, 0.0, 0.7);
+const TEXT_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.85);
+const SEPARATOR_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.25);
+
+/// Slim bar along the bottom edge listing what the app is doing, one item per
+/// section
+#[derive(Debug, Clone, Copy)]
+pub struct StatusBar {
+    pub padding: f32,
+    /// Space between items, with a separator in the middle
+    pub spacing: f32,
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        Self {
+            padding: 4.0,
+            spacing: 24.0,
+        }
+    }
+}
+
+impl StatusBar {
+    /// Window pixels the bar takes up from the bottom, other bottom widgets
+    /// are placed above it
+    pub fn height(&self) -> f32 {
+        text_size("0", TEXT_PIXEL).y + self.padding * 2.0
+    }
+
+    pub fn rects(&self, width: f32, height: f32, items: &[String]) -> Vec<Rect> {
+        let bar_height = self.height();
+        let top = height - bar_height;
+        let mut rects = vec![Rect::new(
+            vec2(0.0, top),
+            vec2(width, bar_height),
+            BACKGROUND_COLOR,
+        )];
+        let mut x = self.padding * 2.0;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                let separator = x - self.spacing / 2.0;
+                rects.push(Rect::new(
+                    vec2(separator, top + self.padding),
+                    vec2(1.0, bar_height - self.padding * 2.0),
+                    SEPARATOR_COLOR,
+                ));
+            }
+            rects.extend(text_rects(
+                item,
+                vec2(x, top + self.padding),
+                TEXT_PIXEL,
+                TEXT_COLOR,
+            ));
+            x += text_size(item, TEXT_PIXEL).x + self.spacing;
+        }
+        rects
+    }
+}
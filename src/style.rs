@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use cgmath::{vec2, vec4, VectorSpace};
+
+use crate::{
+    attributes::{AttrValue, Attributes},
+    edge::Edge,
+    graph::{self, NodeId, Remap},
+    node::Node,
+    ColorGenerator,
+};
+
+/// Node half-extent for the smallest value of a size rule
+pub const MIN_NODE_SIZE: f32 = 25.0;
+/// Node half-extent for the largest value of a size rule
+pub const MAX_NODE_SIZE: f32 = 100.0;
+/// Line width for the smallest value of a width rule
+pub const MIN_EDGE_WIDTH: f32 = 4.0;
+/// Line width for the largest value of a width rule
+pub const MAX_EDGE_WIDTH: f32 = 30.0;
+/// Gradient color for the smallest value of a numeric color rule
+pub const LOW_COLOR: cgmath::Vector4<f32> = vec4(0.23, 0.37, 0.8, 1.0);
+/// Gradient color for the largest value of a numeric color rule
+pub const HIGH_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.35, 0.2, 1.0);
+
+/// Visual property that a rule drives, of nodes unless it says otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Color,
+    Size,
+    /// Edge line width
+    Width,
+}
+
+/// Maps an attribute onto a channel. Nodes or edges without the attribute
+/// are left untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleRule {
+    pub attr: String,
+    pub channel: Channel,
+}
+
+/// How a rule's values translate into colors or sizes, derived from the nodes
+/// the rule is applied to. This is also what the legend draws.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scale {
+    /// One palette color per distinct value, in sorted order
+    Categorical(Vec<(String, cgmath::Vector4<f32>)>),
+    /// Numbers interpolated from `LOW_COLOR` at `min` to `HIGH_COLOR` at `max`
+    Gradient { min: f32, max: f32 },
+    /// Numbers interpolated from `MIN_NODE_SIZE` at `min` to `MAX_NODE_SIZE` at `max`
+    Size { min: f32, max: f32 },
+    /// Numbers interpolated from `MIN_EDGE_WIDTH` at `min` to `MAX_EDGE_WIDTH` at `max`
+    Width { min: f32, max: f32 },
+}
+
+impl StyleRule {
+    pub fn new<S: Into<String>>(attr: S, channel: Channel) -> Self {
+        Self {
+            attr: attr.into(),
+            channel,
+        }
+    }
+
+    /// Builds the scale for the values present in `nodes`, or `edges` for a
+    /// width rule. Returns `None` if none has the attribute, or if a size or
+    /// width rule's values aren't numeric.
+    pub fn scale(&self, nodes: &[Node], edges: &[Edge]) -> Option<Scale> {
+        let attributes: Vec<&Attributes> = match self.channel {
+            Channel::Width => edges.iter().map(|e| &e.attributes).collect(),
+            Channel::Color | Channel::Size => nodes.iter().map(|n| &n.attributes).collect(),
+        };
+        let values: Vec<&AttrValue> = attributes
+            .into_iter()
+            .filter_map(|a| a.get(&self.attr))
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        let numbers: Option<Vec<f32>> = values.iter().map(|v| v.as_number()).collect();
+        let range = numbers.map(|numbers| {
+            numbers.iter().fold((f32::MAX, f32::MIN), |(min, max), n| {
+                (min.min(*n), max.max(*n))
+            })
+        });
+        match (self.channel, range) {
+            (Channel::Color, Some((min, max))) => Some(Scale::Gradient { min, max }),
+            (Channel::Size, Some((min, max))) => Some(Scale::Size { min, max }),
+            (Channel::Width, Some((min, max))) => Some(Scale::Width { min, max }),
+            (Channel::Color, None) => {
+                let categories: BTreeSet<String> = values.iter().map(|v| v.to_string()).collect();
+                Some(Scale::Categorical(
+                    categories.into_iter().zip(ColorGenerator::new()).collect(),
+                ))
+            }
+            (Channel::Size | Channel::Width, None) => None,
+        }
+    }
+
+    /// Restyles every node, or edge, that has the attribute and returns the
+    /// scale used
+    pub fn apply(&self, nodes: &mut [Node], edges: &mut [Edge]) -> Option<Scale> {
+        let scale = self.scale(nodes, edges)?;
+        if let Scale::Width { min, max } = scale {
+            for edge in edges.iter_mut() {
+                if let Some(n) = edge.attributes.get(&self.attr).and_then(|v| v.as_number()) {
+                    edge.line_width =
+                        MIN_EDGE_WIDTH + (MAX_EDGE_WIDTH - MIN_EDGE_WIDTH) * normalize(n, min, max);
+                }
+            }
+            return Some(scale);
+        }
+        for node in nodes.iter_mut() {
+            let Some(value) = node.attributes.get(&self.attr) else {
+                continue;
+            };
+            match &scale {
+                Scale::Categorical(categories) => {
+                    let key = value.to_string();
+                    if let Some((_, color)) = categories.iter().find(|(c, _)| *c == key) {
+                        node.color = *color;
+                    }
+                }
+                Scale::Gradient { min, max } => {
+                    if let Some(n) = value.as_number() {
+                        node.color = gradient(normalize(n, *min, *max));
+                    }
+                }
+                Scale::Size { min, max } => {
+                    if let Some(n) = value.as_number() {
+                        let size = MIN_NODE_SIZE
+                            + (MAX_NODE_SIZE - MIN_NODE_SIZE) * normalize(n, *min, *max);
+                        node.size = vec2(size, size);
+                    }
+                }
+                // Edges, restyled above
+                Scale::Width { .. } => (),
+            }
+        }
+        Some(scale)
+    }
+}
+
+/// Color at `t` in [0, 1] along the numeric color ramp
+pub fn gradient(t: f32) -> cgmath::Vector4<f32> {
+    LOW_COLOR.lerp(HIGH_COLOR, t.clamp(0.0, 1.0))
+}
+
+/// Position of `value` in `[min, max]`, a degenerate range maps everything to 0.5
+fn normalize(value: f32, min: f32, max: f32) -> f32 {
+    if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        0.5
+    }
+}
+
+/// Caption made from each node's attributes, such as `{name} ({degree})`.
+/// Besides attributes, `{name}` falls back to the node's name (see
+/// `graph::node_name`), `{id}` to its external id, and `{degree}` and
+/// `{index}` count its edges and give its position. Fields nothing fills
+/// come out empty, `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelTemplate {
+    pub source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+impl LabelTemplate {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed {{ in label template: {source}"))?;
+                    let field = rest[..end].trim();
+                    if field.is_empty() {
+                        return Err(format!("empty {{}} in label template: {source}"));
+                    }
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Field(field.to_string()));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("unmatched }} in label template: {source}")),
+                c => text.push(c),
+            }
+        }
+        parts.push(Part::Text(text));
+        parts.retain(|part| part != &Part::Text(String::new()));
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// The caption for the node at `index`, which has `degree` edges
+    pub fn render(&self, node: &Node, index: usize, degree: usize) -> String {
+        let mut caption = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => caption += text,
+                Part::Field(field) => {
+                    let value = match (node.attributes.get(field), field.as_str()) {
+                        (Some(value), _) => value.to_string(),
+                        (None, "name") => graph::node_name(node).unwrap_or_default(),
+                        (None, "id") => node.external_id.clone().unwrap_or_default(),
+                        (None, "degree") => degree.to_string(),
+                        (None, "index") => index.to_string(),
+                        (None, _) => String::new(),
+                    };
+                    caption += &value;
+                }
+            }
+        }
+        caption
+    }
+}
+
+/// A label template along with what it wrote, so it can follow the graph
+/// without overwriting labels edited since and can be taken back
+#[derive(Debug, Clone)]
+pub struct TemplatedLabels {
+    pub template: LabelTemplate,
+    /// By node, the label it had before the template and the caption written
+    /// over it
+    written: BTreeMap<NodeId, (Option<String>, String)>,
+}
+
+impl TemplatedLabels {
+    pub fn new(template: LabelTemplate) -> Self {
+        Self {
+            template,
+            written: BTreeMap::new(),
+        }
+    }
+
+    /// Node updates bringing captions up to date with the graph. Labels
+    /// edited since the template wrote them are left alone unless `all`,
+    /// such as when the template itself changed.
+    pub fn relabel(&mut self, nodes: &[Node], edges: &[Edge], all: bool) -> Vec<(NodeId, Node)> {
+        let mut degrees = vec![0; nodes.len()];
+        for edge in edges {
+            for id in [edge.a_id, edge.b_id] {
+                if let Some(degree) = degrees.get_mut(id as usize) {
+                    *degree += 1;
+                }
+            }
+        }
+        let mut updates = vec![];
+        for (i, node) in nodes.iter().enumerate() {
+            let caption = self.template.render(node, i, degrees[i]);
+            let id = i as NodeId;
+            match self.written.get_mut(&id) {
+                Some((_, written)) => {
+                    // A label matching what the template would write now is
+                    // still its own, such as after undoing an edit
+                    let owned = all
+                        || node.label.as_ref() == Some(written)
+                        || node.label.as_ref() == Some(&caption);
+                    if !owned {
+                        continue;
+                    }
+                    *written = caption.clone();
+                }
+                None => {
+                    self.written
+                        .insert(id, (node.label.clone(), caption.clone()));
+                }
+            }
+            if node.label.as_ref() != Some(&caption) {
+                let mut node = node.clone();
+                node.label = Some(caption);
+                updates.push((id, node));
+            }
+        }
+        updates
+    }
+
+    /// Node updates putting back the labels the template replaced, where its
+    /// caption is still showing
+    pub fn restore(&self, nodes: &[Node]) -> Vec<(NodeId, Node)> {
+        self.written
+            .iter()
+            .filter_map(|(id, (before, written))| {
+                let node = nodes.get(*id as usize)?;
+                (node.label.as_ref() == Some(written)).then(|| {
+                    let mut node = node.clone();
+                    node.label = before.clone();
+                    (*id, node)
+                })
+            })
+            .collect()
+    }
+
+    /// Follows the nodes written to their new ids, forgetting removed ones
+    pub fn remap(&mut self, remap: &Remap) {
+        self.written = std::mem::take(&mut self.written)
+            .into_iter()
+            .filter_map(|(id, written)| Some((remap.node(id)?, written)))
+            .collect();
+    }
+
+    /// Forgets what was written, for when the graph is replaced wholesale
+    pub fn forget(&mut self) {
+        self.written.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn scales_follow_the_values() {
+        let mut graph = Graph::new();
+        for (name, team, score) in [("a", "red", 1.0), ("b", "blue", 3.0), ("c", "red", 5.0)] {
+            let node = graph.add_named_node(name);
+            let attributes = &mut graph.nodes[node as usize].attributes;
+            attributes.insert("team".into(), team.into());
+            attributes.insert("score".into(), score.into());
+        }
+        graph.add_named_node("d");
+        let edge = graph.add_edge(0, 1) as usize;
+        graph.edges[edge]
+            .attributes
+            .insert("load".into(), 2.0.into());
+
+        let (nodes, edges) = (&mut graph.nodes, &mut graph.edges);
+        let Some(Scale::Categorical(categories)) =
+            StyleRule::new("team", Channel::Color).apply(nodes, edges)
+        else {
+            panic!("team should be categorical");
+        };
+        let names: Vec<_> = categories.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["blue", "red"]);
+        assert_eq!(nodes[0].color, categories[1].1);
+        assert_eq!(nodes[2].color, nodes[0].color);
+
+        assert_eq!(
+            StyleRule::new("score", Channel::Color).apply(nodes, edges),
+            Some(Scale::Gradient { min: 1.0, max: 5.0 })
+        );
+        assert_eq!(nodes[0].color, gradient(0.0));
+        assert_eq!(nodes[2].color, gradient(1.0));
+
+        let untouched = nodes[3].size;
+        assert_eq!(
+            StyleRule::new("score", Channel::Size).apply(nodes, edges),
+            Some(Scale::Size { min: 1.0, max: 5.0 })
+        );
+        assert_eq!(
+            nodes[1].size,
+            vec2(1.0, 1.0) * (MIN_NODE_SIZE + MAX_NODE_SIZE) / 2.0
+        );
+        assert_eq!(nodes[3].size, untouched);
+
+        // A single value sits in the middle of the range
+        assert_eq!(
+            StyleRule::new("load", Channel::Width).apply(nodes, edges),
+            Some(Scale::Width { min: 2.0, max: 2.0 })
+        );
+        assert_eq!(edges[0].line_width, (MIN_EDGE_WIDTH + MAX_EDGE_WIDTH) / 2.0);
+
+        assert_eq!(
+            StyleRule::new("team", Channel::Size).scale(nodes, edges),
+            None
+        );
+        assert_eq!(
+            StyleRule::new("missing", Channel::Color).scale(nodes, edges),
+            None
+        );
+    }
+
+    #[test]
+    fn label_templates() {
+        let mut graph = Graph::new();
+        let id = graph.add_named_node("svc-auth");
+        let node = &mut graph.nodes[id as usize];
+        node.attributes.insert("team".into(), "core".into());
+        node.attributes.insert("cpu".into(), 0.5.into());
+
+        let template = LabelTemplate::parse("{name} ({degree}) {{{team}}} {cpu}{missing}").unwrap();
+        assert_eq!(
+            template.render(&graph.nodes[0], 0, 3),
+            "svc-auth (3) {core} 0.5"
+        );
+        // Attributes come before the built in fields
+        graph.nodes[0]
+            .attributes
+            .insert("degree".into(), "many".into());
+        assert_eq!(
+            LabelTemplate::parse("#{index}: { degree }")
+                .unwrap()
+                .render(&graph.nodes[0], 7, 3),
+            "#7: many"
+        );
+
+        assert!(LabelTemplate::parse("{name").is_err());
+        assert!(LabelTemplate::parse("name}").is_err());
+        assert!(LabelTemplate::parse("{}").is_err());
+    }
+
+    #[test]
+    fn templated_labels_spare_edits_and_come_off() {
+        let mut graph = Graph::new();
+        for name in ["a", "b", "c"] {
+            graph.add_named_node(name);
+        }
+        graph.add_edge(0, 1);
+        graph.nodes[2].label = Some("mine".into());
+        let mut labels = TemplatedLabels::new(LabelTemplate::parse("{name} ({degree})").unwrap());
+        let relabel = |graph: &mut Graph, labels: &mut TemplatedLabels, all| {
+            let updates = labels.relabel(&graph.nodes, &graph.edges, all);
+            for (id, node) in updates.iter().cloned() {
+                graph.nodes[id as usize] = node;
+            }
+            updates.len()
+        };
+        let captions = |graph: &Graph| -> Vec<Option<String>> {
+            graph.nodes.iter().map(|node| node.label.clone()).collect()
+        };
+
+        assert_eq!(relabel(&mut graph, &mut labels, false), 3);
+        assert_eq!(
+            captions(&graph),
+            [
+                Some("a (1)".into()),
+                Some("b (1)".into()),
+                Some("c (0)".into())
+            ]
+        );
+        assert_eq!(relabel(&mut graph, &mut labels, false), 0);
+
+        // Captions follow the graph, labels edited since stay
+        graph.nodes[1].label = Some("edited".into());
+        graph.add_edge(0, 2);
+        assert_eq!(relabel(&mut graph, &mut labels, false), 2);
+        assert_eq!(
+            captions(&graph),
+            [
+                Some("a (2)".into()),
+                Some("edited".into()),
+                Some("c (1)".into())
+            ]
+        );
+
+        // Removing node 0 moves the others up
+        labels.remap(&Remap {
+            nodes: vec![None, Some(0), Some(1)],
+            edges: vec![],
+        });
+        graph.nodes.remove(0);
+        graph.edges.clear();
+        for (id, node) in labels.restore(&graph.nodes) {
+            graph.nodes[id as usize] = node;
+        }
+        assert_eq!(
+            captions(&graph),
+            [Some("edited".into()), Some("mine".into())]
+        );
+
+        // A new template takes over edited labels too
+        labels.template = LabelTemplate::parse("{index}").unwrap();
+        assert_eq!(relabel(&mut graph, &mut labels, true), 2);
+        assert_eq!(captions(&graph), [Some("0".into()), Some("1".into())]);
+    }
+}
@@ -1,8 +1,13 @@
-use std::num::NonZeroU32;
-
-use anyhow::*;
 // use image::GenericImageView;
 
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -54,6 +59,42 @@ impl Texture {
         }
     }
 
+    /// Texture rendered into at `config`'s size and then sampled with linear
+    /// filtering, such as the graph drawn below the window's resolution
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
@@ -96,6 +137,47 @@ impl Texture {
         }
     }
 
+    /// Square texture images are copied into, sampled with linear filtering
+    pub fn create_atlas_texture(
+        device: &wgpu::Device,
+        size: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Copied from when the atlas grows
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
     // pub fn from_bytes(
     //     device: &wgpu::Device,
     //     queue: &wgpu::Queue,
@@ -164,3 +246,706 @@ impl Texture {
     //     })
     // }
 }
+
+/// An RGBA image with 8 bits a channel, rows top to bottom, as uploaded to
+/// an `Rgba8UnormSrgb` atlas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<u8>,
+}
+
+impl Image {
+    /// Decodes a PNG or JPEG file's contents, telling them apart by their
+    /// signature
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.starts_with(b"\x89PNG") {
+            Self::decode_png(bytes)
+        } else if bytes.starts_with(&[0xff, 0xd8]) {
+            Self::decode_jpeg(bytes)
+        } else {
+            Err(anyhow::anyhow!("not a PNG or JPEG image"))
+        }
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::decode(&bytes).with_context(|| format!("failed to decode {}", path.display()))
+    }
+
+    fn decode_png(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        // Palettes expanded and 16 bit channels cut down to 8
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+        let texels = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => rgba(&buf, 3, |p| [p[0], p[1], p[2], 255]),
+            png::ColorType::GrayscaleAlpha => rgba(&buf, 2, |p| [p[0], p[0], p[0], p[1]]),
+            png::ColorType::Grayscale => rgba(&buf, 1, |p| [p[0], p[0], p[0], 255]),
+            png::ColorType::Indexed => anyhow::bail!("unexpanded palette"),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            texels,
+        })
+    }
+
+    fn decode_jpeg(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decoder = jpeg_decoder::Decoder::new(bytes);
+        let pixels = decoder.decode()?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| anyhow::anyhow!("no image in the file"))?;
+        let texels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => rgba(&pixels, 3, |p| [p[0], p[1], p[2], 255]),
+            jpeg_decoder::PixelFormat::L8 => rgba(&pixels, 1, |p| [p[0], p[0], p[0], 255]),
+            // Big endian, keeping the high byte
+            jpeg_decoder::PixelFormat::L16 => rgba(&pixels, 2, |p| [p[0], p[0], p[0], 255]),
+            jpeg_decoder::PixelFormat::CMYK32 => rgba(&pixels, 4, |p| {
+                let k = p[3] as u32;
+                let channel = |c: u8| (255 - c) as u32 * (255 - k) / 255;
+                [
+                    channel(p[0]) as u8,
+                    channel(p[1]) as u8,
+                    channel(p[2]) as u8,
+                    255,
+                ]
+            }),
+        };
+        Ok(Self {
+            width: info.width as u32,
+            height: info.height as u32,
+            texels,
+        })
+    }
+
+    /// The image shrunk to fit in `max` by `max` texels, keeping its aspect
+    /// ratio, by averaging the texels each one covers. Images already small
+    /// enough come back as they are.
+    pub fn fit(self, max: u32) -> Self {
+        if self.width <= max && self.height <= max {
+            return self;
+        }
+        let ratio = self.width.max(self.height) as f32 / max as f32;
+        let width = ((self.width as f32 / ratio).round() as u32).clamp(1, max);
+        let height = ((self.height as f32 / ratio).round() as u32).clamp(1, max);
+        let mut texels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let (y0, y1) = span(y, height, self.height);
+            for x in 0..width {
+                let (x0, x1) = span(x, width, self.width);
+                let mut sum = [0u32; 4];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let i = ((sy * self.width + sx) * 4) as usize;
+                        for (c, total) in sum.iter_mut().enumerate() {
+                            *total += self.texels[i + c] as u32;
+                        }
+                    }
+                }
+                let count = (x1 - x0) * (y1 - y0);
+                texels.extend(sum.map(|total| (total / count) as u8));
+            }
+        }
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/// An image read from disk, or why it couldn't be
+type Loaded = Result<Image, String>;
+/// A file's path and what reading it gave
+type Decoded = (PathBuf, Loaded);
+
+/// Reads and decodes image files off the render thread, shrunk to fit `max`
+/// by `max` texels. Every image decoded, and every file that failed to, is
+/// kept by path so each file is read at most once until `retry_failed`. On
+/// the web, which has no threads to decode on, files are read as they're
+/// asked for.
+pub struct ImageLoader {
+    max: u32,
+    loaded: HashMap<PathBuf, Loaded>,
+    /// Asked for and still being decoded
+    pending: HashSet<PathBuf>,
+    /// Paths for the decoding thread, started with the first one
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: Option<mpsc::Sender<PathBuf>>,
+    done: (mpsc::Sender<Decoded>, mpsc::Receiver<Decoded>),
+}
+
+impl ImageLoader {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max,
+            loaded: HashMap::new(),
+            pending: HashSet::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs: None,
+            done: mpsc::channel(),
+        }
+    }
+
+    fn load(path: &Path, max: u32) -> Loaded {
+        Image::load(path)
+            .map(|image| image.fit(max))
+            .map_err(|err| format!("{err:#}"))
+    }
+
+    /// The image at `path` if it's been decoded, otherwise starts decoding
+    /// it unless it already is or failed to. Failures are logged once.
+    pub fn get(&mut self, path: &Path) -> Option<&Image> {
+        self.poll();
+        if !self.loaded.contains_key(path) && self.pending.insert(path.to_path_buf()) {
+            self.request(path.to_path_buf());
+        }
+        self.loaded.get(path)?.as_ref().ok()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request(&mut self, path: PathBuf) {
+        let jobs = self.jobs.get_or_insert_with(|| {
+            let (jobs, queue) = mpsc::channel::<PathBuf>();
+            let (done, max) = (self.done.0.clone(), self.max);
+            // Ends once the loader, and with it the job sender, is dropped
+            std::thread::spawn(move || {
+                for path in queue {
+                    let image = Self::load(&path, max);
+                    if done.send((path, image)).is_err() {
+                        break;
+                    }
+                }
+            });
+            jobs
+        });
+        if let Err(mpsc::SendError(path)) = jobs.send(path) {
+            let err = "the image decoding thread stopped".to_string();
+            let _ = self.done.0.send((path, Err(err)));
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn request(&mut self, path: PathBuf) {
+        let image = Self::load(&path, self.max);
+        let _ = self.done.0.send((path, image));
+    }
+
+    /// Takes in the images decoded since the last call, returns whether
+    /// there were any
+    pub fn poll(&mut self) -> bool {
+        let mut any = false;
+        while let Ok((path, image)) = self.done.1.try_recv() {
+            if let Err(err) = &image {
+                log::warn!("{err}");
+            }
+            self.pending.remove(&path);
+            self.loaded.insert(path, image);
+            any = true;
+        }
+        any
+    }
+
+    /// Whether any image is still being decoded
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Forgets the files that failed to load, so they're read again the
+    /// next time they're asked for
+    pub fn retry_failed(&mut self) {
+        self.loaded.retain(|_, image| image.is_ok());
+    }
+}
+
+/// Texels `pixels` of `channels` bytes each turned to RGBA with `texel`
+fn rgba(pixels: &[u8], channels: usize, texel: impl Fn(&[u8]) -> [u8; 4]) -> Vec<u8> {
+    pixels.chunks_exact(channels).flat_map(texel).collect()
+}
+
+/// Source texels `[start, end)` that the `i`th of `count` texels covers, out
+/// of `len`, never empty
+fn span(i: u32, count: u32, len: u32) -> (u32, u32) {
+    let start = i * len / count;
+    let end = ((i + 1) * len / count).max(start + 1);
+    (start, end)
+}
+
+/// Texels left empty around each image so linear filtering doesn't pick up
+/// its neighbours
+const PADDING: u32 = 1;
+
+/// Where an image sits in an atlas, in texels from the top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    /// Texture coordinates of the top-left and bottom-right corners, as
+    /// `[u0, v0, u1, v1]`, in an atlas `size` texels wide
+    pub fn uv(&self, size: u32) -> [f32; 4] {
+        let size = size as f32;
+        [
+            self.x as f32 / size,
+            self.y as f32 / size,
+            (self.x + self.width) as f32 / size,
+            (self.y + self.height) as f32 / size,
+        ]
+    }
+}
+
+/// A row of images as tall as the tallest one it was opened for, filled left
+/// to right
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next image goes
+    x: u32,
+    /// Frame any of its images was last asked for
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    region: AtlasRegion,
+    shelf: usize,
+}
+
+/// Decides where images go in an atlas, without touching the GPU. Space is
+/// reclaimed a shelf at a time, evicting the one used longest ago, never one
+/// used in the current frame.
+#[derive(Debug)]
+pub(crate) struct Packer<K> {
+    size: u32,
+    shelves: Vec<Shelf>,
+    entries: HashMap<K, Entry>,
+}
+
+impl<K: Hash + Eq> Packer<K> {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: vec![],
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K, frame: u64) -> Option<AtlasRegion> {
+        let entry = self.entries.get(key)?;
+        let shelf = &mut self.shelves[entry.shelf];
+        shelf.last_used = shelf.last_used.max(frame);
+        Some(entry.region)
+    }
+
+    /// Room for a `width` by `height` image under `key`, evicting older
+    /// images if that's what it takes. None if it's larger than the atlas or
+    /// everything in the way is in use this frame.
+    pub fn allocate(&mut self, key: K, width: u32, height: u32, frame: u64) -> Option<AtlasRegion> {
+        if let Some(old) = self.entries.remove(&key) {
+            // Replaced images leave a gap until their shelf is evicted
+            self.shelves[old.shelf].last_used = self.shelves[old.shelf].last_used.max(frame);
+        }
+        let (w, h) = (width + PADDING, height + PADDING);
+        if w > self.size || h > self.size {
+            return None;
+        }
+        let shelf = match self.fit(w, h) {
+            Some(shelf) => shelf,
+            None => {
+                self.evict(h, frame)?;
+                self.fit(w, h)?
+            }
+        };
+        let row = &mut self.shelves[shelf];
+        let region = AtlasRegion {
+            x: row.x,
+            y: row.y,
+            width,
+            height,
+        };
+        row.x += w;
+        row.last_used = frame;
+        self.entries.insert(key, Entry { region, shelf });
+        Some(region)
+    }
+
+    /// The shortest shelf with room, opening a new one if none has any
+    fn fit(&mut self, w: u32, h: u32) -> Option<usize> {
+        let existing = (0..self.shelves.len())
+            .filter(|i| {
+                let shelf = &self.shelves[*i];
+                shelf.height >= h && shelf.x + w <= self.size
+            })
+            .min_by_key(|i| self.shelves[*i].height);
+        existing.or_else(|| {
+            let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+            (y + h <= self.size).then(|| {
+                self.shelves.push(Shelf {
+                    y,
+                    height: h,
+                    x: 0,
+                    last_used: 0,
+                });
+                self.shelves.len() - 1
+            })
+        })
+    }
+
+    /// Empties the least recently used shelf at least `h` texels tall
+    fn evict(&mut self, h: u32, frame: u64) -> Option<()> {
+        let (index, _) = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= h && shelf.last_used < frame)
+            .min_by_key(|(_, shelf)| shelf.last_used)?;
+        self.entries.retain(|_, entry| entry.shelf != index);
+        self.shelves[index].x = 0;
+        Some(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Widens and deepens the atlas to `size`. Images stay where they are,
+    /// shelves get longer and new ones open below them.
+    pub fn grow(&mut self, size: u32) {
+        self.size = self.size.max(size);
+    }
+}
+
+/// A texture many small images share, so icons, glyphs and the like can all
+/// be drawn from a single bind group. Images are looked up by key, and ones
+/// not asked for in a while make room for new ones once the atlas fills up;
+/// callers re-insert whatever `get` no longer finds. Call `next_frame` once
+/// per frame so the atlas knows what's still in use.
+pub struct Atlas<K> {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    label: String,
+    packer: Packer<K>,
+    frame: u64,
+}
+
+impl<K: Hash + Eq> Atlas<K> {
+    /// Layout every atlas bind group follows: the texture at binding 0 and
+    /// its sampler at binding 1, visible to fragment shaders
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Atlas Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// An empty `size` by `size` atlas. Use `R8Unorm` for coverage masks such
+    /// as glyphs and `Rgba8UnormSrgb` for color images.
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        size: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = Texture::create_atlas_texture(device, size, format, label);
+        let bind_group = Self::bind_group(device, layout, &texture, label);
+        Self {
+            texture,
+            bind_group,
+            format,
+            label: label.to_string(),
+            packer: Packer::new(size),
+            frame: 1,
+        }
+    }
+
+    fn bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Bind Group")),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Doubles the atlas's width and height, copying what it holds into the
+    /// top-left corner of the new texture so every region stays valid; only
+    /// their texture coordinates change, see `AtlasRegion::uv`. False when
+    /// it's already as large as the device allows.
+    pub fn grow(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> bool {
+        let old = self.size();
+        let size = (old * 2).min(device.limits().max_texture_dimension_2d);
+        if size <= old {
+            return false;
+        }
+        let texture = Texture::create_atlas_texture(device, size, self.format, &self.label);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&format!("{} Growth", self.label)),
+        });
+        let corner = |texture| wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        };
+        encoder.copy_texture_to_texture(
+            corner(&self.texture.texture),
+            corner(&texture.texture),
+            wgpu::Extent3d {
+                width: old,
+                height: old,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+        self.bind_group = Self::bind_group(device, layout, &texture, &self.label);
+        self.texture = texture;
+        self.packer.grow(size);
+        true
+    }
+
+    /// Width and height in texels
+    pub fn size(&self) -> u32 {
+        self.packer.size
+    }
+
+    /// Images currently held
+    pub fn len(&self) -> usize {
+        self.packer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Where `key`'s image is, keeping it around for this frame
+    pub fn get(&mut self, key: &K) -> Option<AtlasRegion> {
+        self.packer.get(key, self.frame)
+    }
+
+    /// Copies a `width` by `height` image into the atlas under `key`,
+    /// replacing any image it had. `texels` are rows top to bottom in the
+    /// atlas's format. None if there's no room for it this frame.
+    pub fn insert(
+        &mut self,
+        queue: &wgpu::Queue,
+        key: K,
+        width: u32,
+        height: u32,
+        texels: &[u8],
+    ) -> Option<AtlasRegion> {
+        let bytes_per_texel = self.format.describe().block_size as u32;
+        assert_eq!(
+            texels.len(),
+            (width * height * bytes_per_texel) as usize,
+            "{width}x{height} image of the wrong length"
+        );
+        let region = self.packer.allocate(key, width, height, self.frame)?;
+        if width > 0 && height > 0 {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: region.x,
+                        y: region.y,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                texels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(width * bytes_per_texel),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        Some(region)
+    }
+
+    /// `key`'s image, drawing and uploading it with `draw` (returning width,
+    /// height and texels) if the atlas doesn't have it
+    pub fn get_or_insert_with(
+        &mut self,
+        queue: &wgpu::Queue,
+        key: K,
+        draw: impl FnOnce() -> (u32, u32, Vec<u8>),
+    ) -> Option<AtlasRegion> {
+        if let Some(region) = self.get(&key) {
+            return Some(region);
+        }
+        let (width, height, texels) = draw();
+        self.insert(queue, key, width, height, &texels)
+    }
+
+    /// Starts a new frame, images not asked for from now on can be evicted
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packer_reuses_the_stalest_shelf() {
+        let mut packer = Packer::new(16);
+        // Two shelves of 8 texels (7 plus padding), two images each
+        let a = packer.allocate("a", 7, 7, 1).unwrap();
+        let b = packer.allocate("b", 7, 7, 1).unwrap();
+        let c = packer.allocate("c", 7, 7, 2).unwrap();
+        packer.allocate("d", 7, 7, 2).unwrap();
+        assert_eq!((a.x, a.y, b.x, b.y, c.y), (0, 0, 8, 0, 8));
+        assert!(packer.allocate("huge", 16, 1, 2).is_none());
+        // Full, and everything was used this frame
+        packer.get(&"b", 2);
+        assert!(packer.allocate("e", 3, 3, 2).is_none());
+
+        // Asking for "a" keeps its shelf, so "c" and "d" go
+        assert_eq!(packer.get(&"a", 3), Some(a));
+        let e = packer.allocate("e", 3, 3, 3).unwrap();
+        assert_eq!((e.x, e.y), (0, 8));
+        assert_eq!(packer.get(&"c", 3), None);
+        assert_eq!(packer.get(&"b", 3), Some(b));
+        assert_eq!(packer.len(), 3);
+        assert_eq!(e.uv(16), [0.0, 0.5, 3.0 / 16.0, 11.0 / 16.0]);
+
+        // Growing makes room without moving anything
+        assert!(packer.allocate("f", 12, 7, 3).is_none());
+        packer.grow(32);
+        let f = packer.allocate("f", 12, 7, 3).unwrap();
+        assert_eq!((f.x, f.y), (16, 0));
+        let g = packer.allocate("g", 30, 7, 3).unwrap();
+        assert_eq!((g.x, g.y), (0, 16));
+        assert_eq!(packer.get(&"a", 3), Some(a));
+        assert_eq!(packer.len(), 5);
+    }
+
+    #[test]
+    fn images_decode_and_shrink() {
+        // 4x2 gray PNG, white on the left half and black on the right
+        let mut png = vec![];
+        let mut encoder = png::Encoder::new(&mut png, 4, 2);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 255, 0, 0, 255, 255, 0, 0])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let image = Image::decode(&png).unwrap();
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.texels[4..12], [255, 255, 255, 255, 0, 0, 0, 255]);
+        assert!(Image::decode(b"GIF89a").is_err());
+
+        let small = image.clone().fit(2);
+        assert_eq!((small.width, small.height), (2, 1));
+        assert_eq!(small.texels, [255, 255, 255, 255, 0, 0, 0, 255]);
+        assert_eq!(image.clone().fit(8), image);
+    }
+
+    #[test]
+    fn image_loader_decodes_each_file_once() {
+        let dir = std::env::temp_dir().join(format!("image-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dot.png");
+        let mut png = vec![];
+        let mut encoder = png::Encoder::new(&mut png, 4, 4);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[128; 16]).unwrap();
+        writer.finish().unwrap();
+        std::fs::write(&path, png).unwrap();
+        let missing = dir.join("missing.png");
+
+        let mut loader = ImageLoader::new(2);
+        let wait = |loader: &mut ImageLoader| {
+            for _ in 0..500 {
+                if !loader.is_loading() {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(2));
+                loader.poll();
+            }
+            panic!("images never finished decoding");
+        };
+        assert!(loader.get(&path).is_none(), "decoded in the background");
+        assert!(loader.get(&missing).is_none());
+        wait(&mut loader);
+        let image = loader.get(&path).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+
+        // Neither read again, even once the files change
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&missing, b"GIF89a").unwrap();
+        assert!(loader.get(&path).is_some());
+        assert!(loader.get(&missing).is_none());
+        assert!(!loader.is_loading());
+        loader.retry_failed();
+        assert!(loader.get(&missing).is_none());
+        assert!(loader.is_loading());
+        wait(&mut loader);
+        assert!(loader.get(&path).is_some(), "kept");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
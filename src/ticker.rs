@@ -0,0 +1,65 @@
+/// Rate the viewer ticks at until told otherwise, in ticks per second
+pub const DEFAULT_TICK_RATE: f32 = 60.0;
+/// Fastest rate `tickrate` accepts, in ticks per second
+pub const MAX_TICK_RATE: f32 = 1000.0;
+/// Most ticks run for one frame. A frame that took longer than this many
+/// ticks drops the rest instead of stalling the next frames catching up.
+pub const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Steps the simulation at a fixed rate regardless of how often frames are
+/// drawn, by accumulating frame time and running whole ticks out of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ticker {
+    /// Ticks per second
+    pub rate: f32,
+    /// Seconds not yet spent on a tick
+    pending: f32,
+}
+
+impl Ticker {
+    pub fn new(rate: f32) -> Self {
+        Self { rate, pending: 0.0 }
+    }
+
+    /// Adds a frame's `elapsed` seconds and returns how many ticks are due
+    pub fn advance(&mut self, elapsed: f32) -> u32 {
+        let period = 1.0 / self.rate;
+        self.pending += elapsed.max(0.0);
+        let due = (self.pending / period).floor();
+        if due > MAX_TICKS_PER_FRAME as f32 {
+            self.pending = 0.0;
+            return MAX_TICKS_PER_FRAME;
+        }
+        self.pending -= due * period;
+        due as u32
+    }
+
+    /// How far into the next tick the clock is, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        (self.pending * self.rate).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ticks_follow_the_rate() {
+        // 30 Hz at 60 fps ticks every other frame, halfway through in between
+        let mut slow = Ticker::new(30.0);
+        assert_eq!(slow.advance(1.0 / 60.0), 0);
+        assert!((slow.fraction() - 0.5).abs() < 1e-3);
+        assert_eq!(slow.advance(1.0 / 60.0), 1);
+        let ticks: u32 = (0..58).map(|_| slow.advance(1.0 / 60.0)).sum();
+        assert!((29..=30).contains(&ticks));
+
+        let mut fast = Ticker::new(240.0);
+        let ticks: u32 = (0..60).map(|_| fast.advance(1.0 / 60.0)).sum();
+        assert!((239..=240).contains(&ticks));
+
+        // A long stall doesn't pile up ticks
+        assert_eq!(fast.advance(10.0), MAX_TICKS_PER_FRAME);
+        assert_eq!(fast.advance(0.0), 0);
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeSet;
+
+use cgmath::{vec2, vec4};
+
+use crate::{
+    attributes::Attributes,
+    chart::ChartFrame,
+    edge::Edge,
+    node::Node,
+    overlay::{Anchor, Rect},
+};
+
+/// Numeric attribute holding the time an element appears
+pub const START_ATTR: &str = "start";
+/// Numeric attribute holding the time an element disappears
+pub const END_ATTR: &str = "end";
+
+pub const DEFAULT_TIMELINE_BUCKETS: usize = 64;
+
+const NODE_AREA_COLOR: cgmath::Vector4<f32> = vec4(0.37, 0.71, 0.61, 0.8);
+const EDGE_AREA_COLOR: cgmath::Vector4<f32> = vec4(0.0, 1.0, 0.0, 0.35);
+const SCRUBBER_COLOR: cgmath::Vector4<f32> = vec4(1.0, 1.0, 1.0, 0.25);
+const CURSOR_COLOR: cgmath::Vector4<f32> = vec4(0.95, 0.69, 0.2, 1.0);
+const SCRUBBER_HEIGHT: f32 = 6.0;
+
+/// Lifetime of an element, `[start, end]`: it's still there at its end, so
+/// scrubbing to the last timestamp shows what was alive then. Elements
+/// without a start or end attribute are unbounded on that side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Span {
+    pub fn from_attributes(attributes: &Attributes) -> Self {
+        let get = |key: &str| attributes.get(key).and_then(|v| v.as_number());
+        Self {
+            start: get(START_ATTR).unwrap_or(f32::NEG_INFINITY),
+            end: get(END_ATTR).unwrap_or(f32::INFINITY),
+        }
+    }
+
+    pub fn contains(&self, t: f32) -> bool {
+        t >= self.start && t <= self.end
+    }
+}
+
+/// Scrubber for time-varying graphs. Shows how many nodes/edges are alive over
+/// time as an area chart and hides elements that are not alive at `time`.
+pub struct Timeline {
+    pub time: f32,
+    /// Time covered by the chart, derived from the elements' spans
+    pub range: (f32, f32),
+    pub frame: ChartFrame,
+    pub buckets: usize,
+    pub node_counts: Vec<f32>,
+    pub edge_counts: Vec<f32>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            time: 0.0,
+            range: (0.0, 0.0),
+            frame: ChartFrame {
+                anchor: Anchor::BottomRight,
+                ..Default::default()
+            },
+            buckets: DEFAULT_TIMELINE_BUCKETS,
+            node_counts: vec![],
+            edge_counts: vec![],
+        }
+    }
+
+    pub fn update(&mut self, nodes: &[Node], edges: &[Edge]) {
+        let node_spans: Vec<Span> = nodes
+            .iter()
+            .map(|n| Span::from_attributes(&n.attributes))
+            .collect();
+        let edge_spans: Vec<Span> = edges
+            .iter()
+            .map(|e| Span::from_attributes(&e.attributes))
+            .collect();
+
+        let (min, max) = node_spans
+            .iter()
+            .chain(edge_spans.iter())
+            .flat_map(|s| [s.start, s.end])
+            .filter(|t| t.is_finite())
+            .fold((f32::MAX, f32::MIN), |(min, max), t| {
+                (min.min(t), max.max(t))
+            });
+        self.range = if min <= max { (min, max) } else { (0.0, 0.0) };
+        self.time = self.time.clamp(self.range.0, self.range.1);
+
+        let samples: Vec<f32> = (0..self.buckets)
+            .map(|i| self.time_at((i as f32 + 0.5) / self.buckets as f32))
+            .collect();
+        let count = |spans: &[Span]| -> Vec<f32> {
+            samples
+                .iter()
+                .map(|&t| spans.iter().filter(|s| s.contains(t)).count() as f32)
+                .collect()
+        };
+        self.node_counts = count(&node_spans);
+        self.edge_counts = count(&edge_spans);
+    }
+
+    /// Converts a position in [0, 1] along the chart into a timestamp
+    pub fn time_at(&self, t: f32) -> f32 {
+        self.range.0 + (self.range.1 - self.range.0) * t
+    }
+
+    fn progress(&self) -> f32 {
+        let len = self.range.1 - self.range.0;
+        if len <= 0.0 {
+            0.0
+        } else {
+            (self.time - self.range.0) / len
+        }
+    }
+
+    pub fn rects(&self, width: f32, height: f32) -> Vec<Rect> {
+        let max = self
+            .node_counts
+            .iter()
+            .chain(self.edge_counts.iter())
+            .fold(0.0f32, |a, b| a.max(*b));
+        let plot = self.frame.plot_area(width, height);
+        let scrubber = Rect::new(
+            plot.origin - vec2(0.0, SCRUBBER_HEIGHT),
+            vec2(plot.size.x, SCRUBBER_HEIGHT / 2.0),
+            SCRUBBER_COLOR,
+        );
+        let handle = Rect::new(
+            scrubber.origin + vec2(plot.size.x * self.progress() - SCRUBBER_HEIGHT / 2.0, -2.0),
+            vec2(SCRUBBER_HEIGHT, SCRUBBER_HEIGHT),
+            CURSOR_COLOR,
+        );
+
+        std::iter::once(self.frame.background(width, height))
+            .chain(
+                self.frame
+                    .area(width, height, &self.node_counts, max, NODE_AREA_COLOR),
+            )
+            .chain(
+                self.frame
+                    .area(width, height, &self.edge_counts, max, EDGE_AREA_COLOR),
+            )
+            .chain([
+                scrubber,
+                handle,
+                self.frame
+                    .marker(width, height, self.progress(), 2.0, CURSOR_COLOR),
+            ])
+            .collect()
+    }
+
+    /// Handles a click in overlay space by jumping to the timestamp under the
+    /// cursor, returns true if the chart consumed it
+    pub fn click(&mut self, width: f32, height: f32, pos: &cgmath::Vector2<f32>) -> bool {
+        if !self.frame.contains(width, height, pos) {
+            return false;
+        }
+        if let Some(t) = self.frame.fraction_at(width, height, pos) {
+            self.time = self.time_at(t);
+        }
+        true
+    }
+
+    /// Nodes that are not alive at the current time
+    pub fn hidden_nodes(&self, nodes: &[Node]) -> BTreeSet<u32> {
+        nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !Span::from_attributes(&n.attributes).contains(self.time))
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    /// Edges that are not alive at the current time, or whose endpoints aren't
+    pub fn hidden_edges(&self, edges: &[Edge], hidden_nodes: &BTreeSet<u32>) -> BTreeSet<u32> {
+        edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| {
+                !Span::from_attributes(&e.attributes).contains(self.time)
+                    || hidden_nodes.contains(&e.a_id)
+                    || hidden_nodes.contains(&e.b_id)
+            })
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{attributes::AttrValue, graph::Graph};
+
+    /// Nodes alive from `0..=2`, `1..=3` and always, the first two linked
+    fn graph() -> Graph {
+        let mut graph = Graph::new();
+        for (name, span) in [
+            ("a", Some((0.0, 2.0))),
+            ("b", Some((1.0, 3.0))),
+            ("c", None),
+        ] {
+            let node = graph.add_named_node(name);
+            if let Some((start, end)) = span {
+                let attributes = &mut graph.nodes[node as usize].attributes;
+                attributes.insert(START_ATTR.into(), AttrValue::Number(start));
+                attributes.insert(END_ATTR.into(), AttrValue::Number(end));
+            }
+        }
+        graph.add_edge(0, 1);
+        graph
+    }
+
+    #[test]
+    fn elements_live_through_their_end() {
+        let graph = graph();
+        let mut timeline = Timeline::new();
+        timeline.buckets = 3;
+        timeline.update(&graph.nodes, &graph.edges);
+        assert_eq!(timeline.range, (0.0, 3.0));
+        // Sampled at 0.5, 1.5 and 2.5
+        assert_eq!(timeline.node_counts, [2.0, 3.0, 2.0]);
+        assert_eq!(timeline.edge_counts, [1.0, 1.0, 1.0]);
+
+        timeline.time = 2.0;
+        let hidden = timeline.hidden_nodes(&graph.nodes);
+        assert!(hidden.is_empty());
+        timeline.time = 3.0;
+        let hidden = timeline.hidden_nodes(&graph.nodes);
+        assert_eq!(hidden, BTreeSet::from([0]));
+        // Edges go with either end
+        assert_eq!(
+            timeline.hidden_edges(&graph.edges, &hidden),
+            BTreeSet::from([0])
+        );
+    }
+
+    #[test]
+    fn clicking_the_chart_jumps_to_its_time() {
+        let graph = graph();
+        let mut timeline = Timeline::new();
+        timeline.update(&graph.nodes, &graph.edges);
+        let (width, height) = (800.0, 600.0);
+        let plot = timeline.frame.plot_area(width, height);
+        let pos = plot.origin + vec2(plot.size.x / 3.0, plot.size.y / 2.0);
+        assert!(timeline.click(width, height, &pos));
+        assert!((timeline.time - 1.0).abs() < 1e-4, "{}", timeline.time);
+        assert!(!timeline.click(width, height, &vec2(0.0, 0.0)));
+        assert!((timeline.time - 1.0).abs() < 1e-4);
+    }
+}
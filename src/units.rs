@@ -0,0 +1,76 @@
+/// What one world unit stands for, for scenes whose positions encode real
+/// quantities such as milliseconds or meters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    pub name: String,
+    /// Amount of `name` one world unit is worth
+    pub per_world_unit: f32,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self::new(1.0, "units")
+    }
+}
+
+impl Units {
+    pub fn new<S: Into<String>>(per_world_unit: f32, name: S) -> Self {
+        Self {
+            name: name.into(),
+            per_world_unit,
+        }
+    }
+
+    pub fn from_world(&self, world: f32) -> f32 {
+        world * self.per_world_unit
+    }
+
+    pub fn to_world(&self, amount: f32) -> f32 {
+        amount / self.per_world_unit
+    }
+
+    /// A world distance as a label, e.g. "2.5 ms"
+    pub fn format(&self, world: f32) -> String {
+        format!("{} {}", format_amount(self.from_world(world)), self.name)
+    }
+}
+
+/// Rounds to three decimals and drops the ones that are zero
+pub fn format_amount(amount: f32) -> String {
+    let rounded = (amount * 1000.0).round() / 1000.0;
+    // Avoids printing "-0"
+    format!("{}", rounded + 0.0)
+}
+
+/// Largest 1, 2 or 5 times a power of ten that doesn't exceed `max`, the
+/// lengths a ruler snaps to
+pub fn nice_length(max: f32) -> f32 {
+    if max <= 0.0 || !max.is_finite() {
+        return 0.0;
+    }
+    let magnitude = 10f32.powf(max.log10().floor());
+    let step = [5.0, 2.0, 1.0]
+        .into_iter()
+        .find(|step| step * magnitude <= max)
+        .unwrap_or(1.0);
+    step * magnitude
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nice_lengths_and_labels() {
+        assert_eq!(nice_length(740.0), 500.0);
+        assert_eq!(nice_length(199.0), 100.0);
+        assert_eq!(nice_length(0.3), 0.2);
+        assert_eq!(nice_length(0.0), 0.0);
+
+        let ms = Units::new(0.5, "ms");
+        assert_eq!(ms.format(25.0), "12.5 ms");
+        assert_eq!(ms.to_world(12.5), 25.0);
+        assert_eq!(format_amount(-0.0001), "0");
+        assert_eq!(format_amount(1.23456), "1.235");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::texture::{Atlas, Texture};
+
+/// Lets the graph be drawn below the window's resolution: passes render into
+/// `target` instead of the window, and `render` stretches it over the window
+/// with linear filtering. See `AdaptiveQuality`.
+pub struct UpscalePass {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    /// A texture and its sampler, the same bindings as an atlas
+    pub(crate) layout: wgpu::BindGroupLayout,
+    /// The reduced resolution texture and the bind group sampling it, `None`
+    /// while drawing at full resolution
+    scaled: Option<(Texture, wgpu::BindGroup)>,
+}
+
+impl UpscalePass {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = Atlas::<()>::bind_group_layout(device);
+        let pipeline = Self::create_pipeline(device, format, &layout, include_str!("upscale.wgsl"));
+        Self {
+            pipeline,
+            layout,
+            scaled: None,
+        }
+    }
+
+    /// Pipeline drawing with `source`, the WGSL of `upscale.wgsl` or a hot
+    /// reloaded edit of it. It writes every pixel of the window once, so it
+    /// never needs MSAA or depth.
+    pub(crate) fn create_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Upscale Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Upscale"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Upscale Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Draws at `config`'s size from now on, or straight into the window
+    /// when `full` is set
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        full: bool,
+    ) {
+        if full {
+            self.scaled = None;
+            return;
+        }
+        let texture = Texture::create_render_target(device, config, "Scaled Target");
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scaled Target Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+        self.scaled = Some((texture, bind_group));
+    }
+
+    /// What passes should draw into instead of the window, if anything
+    pub fn target(&self) -> Option<&wgpu::TextureView> {
+        self.scaled.as_ref().map(|(texture, _)| &texture.view)
+    }
+
+    /// Stretches `target` over `window`, when drawing at reduced resolution
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, window: &wgpu::TextureView) {
+        let Some((_, bind_group)) = &self.scaled else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Upscale Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: window,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,79 @@
+//! Blends the palette over the background at a few alphas the way the GPU
+//! does with an sRGB target, and compares the stored bytes against
+//! `tests/fixtures/blending.txt`. A change to how colors are decoded,
+//! blended or encoded shows up here. The shaders decode with the constants
+//! `color::srgb_to_linear` does, and `shaders_share_the_decoding` checks
+//! none of them has a decoding of its own.
+
+mod common;
+
+use cgmath::vec4;
+use force_graph_wgpu::{color, shader, ColorGenerator};
+
+/// Edge fades and antialiased coverage both land on these
+const ALPHAS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+/// Allowed difference per channel, `powf` isn't bit-exact across platforms
+const TOLERANCE: f32 = 1.0;
+
+fn blended() -> Vec<[u8; 4]> {
+    let black = vec4(0.0, 0.0, 0.0, 1.0);
+    let white = vec4(1.0, 1.0, 1.0, 1.0);
+    let palette = ColorGenerator::new().colors;
+    let pairs = palette
+        .iter()
+        .map(|c| (*c, color::BACKGROUND))
+        .chain([(white, black), (black, white)]);
+
+    let mut out = vec![];
+    for (src, dst) in pairs {
+        for alpha in ALPHAS {
+            let src = vec4(src.x, src.y, src.z, alpha);
+            out.push(color::to_bytes(color::blend_over(src, dst)));
+        }
+    }
+    out
+}
+
+#[test]
+fn palette_over_background() {
+    let actual: Vec<Vec<f32>> = blended()
+        .iter()
+        .map(|[r, g, b, _]| vec![*r as f32, *g as f32, *b as f32])
+        .collect();
+    common::check_fixture("blending", &actual, TOLERANCE);
+}
+
+#[test]
+fn shaders_share_the_decoding() {
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut decoding = 0;
+    for entry in std::fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        if !name.ends_with(".wgsl") || shader::SNIPPETS.iter().any(|(s, _)| *s == name) {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            !source.contains("fn srgb_to_linear"),
+            "{name} decodes sRGB itself"
+        );
+        if source.contains("srgb_to_linear(") {
+            assert!(shader::includes(&source, "color.wgsl"), "{name}");
+            let expanded = shader::builtin(&source);
+            for constant in ["SRGB_CUTOFF", "SRGB_SLOPE", "SRGB_OFFSET", "SRGB_GAMMA"] {
+                assert!(expanded.contains(&format!("let {constant}: f32")), "{name}");
+            }
+            decoding += 1;
+        }
+    }
+    assert_eq!(decoding, 6);
+}
+
+#[test]
+fn blending_happens_in_linear_light() {
+    // Half coverage of white over black is half the light, not half the
+    // sRGB value, which is what made antialiased edges look thin
+    let half = color::blend_over(vec4(1.0, 1.0, 1.0, 0.5), vec4(0.0, 0.0, 0.0, 1.0));
+    assert_eq!(color::to_bytes(half), [188, 188, 188, 255]);
+}
@@ -0,0 +1,46 @@
+//! Fixtures shared by the integration tests, in `tests/fixtures`. A test
+//! compares what it computes against its fixture; if a change to it is
+//! intended, regenerate the fixtures with `UPDATE_FIXTURES=1 cargo test` and
+//! review the diff.
+
+use std::path::PathBuf;
+
+/// Compares `actual`, a line of numbers per value, against the fixture
+/// `name`, each number within `tolerance` of the fixture's. Writes `actual`
+/// to the fixture instead when `UPDATE_FIXTURES` is set.
+pub fn check_fixture(name: &str, actual: &[Vec<f32>], tolerance: f32) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(format!("{name}.txt"));
+
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        let src: String = actual
+            .iter()
+            .map(|line| {
+                let line: Vec<String> = line.iter().map(f32::to_string).collect();
+                line.join(" ") + "\n"
+            })
+            .collect();
+        std::fs::write(&path, src).unwrap();
+        return;
+    }
+
+    let src = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}, run with UPDATE_FIXTURES=1", path.display()));
+    let expected: Vec<Vec<f32>> = src
+        .lines()
+        .map(|line| line.split(' ').map(|n| n.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(actual.len(), expected.len(), "{name}: line count");
+    for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, e)| (a - e).abs() <= tolerance),
+            "{name}: line {i} is {actual:?}, expected {expected:?}"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Uses the crate the way a server would, as a layout library with no window
+//! or GPU. Also runs with `--no-default-features`, where the renderer isn't
+//! built at all.
+
+use std::collections::BTreeSet;
+
+use force_graph_wgpu::{
+    edge::{DEFAULT_EDGE_COLOR, DEFAULT_LINE_WIDTH},
+    prelude::*,
+};
+
+fn triangle() -> Graph {
+    let mut graph = Graph::new();
+    let [a, b, c] = ["a", "b", "c"].map(|name| graph.add_named_node(name));
+    graph.add_edge(a, b);
+    graph.add_edge(b, c);
+    graph.add_edge(c, a);
+    graph
+}
+
+#[test]
+fn lays_out_without_a_window() {
+    let mut sim = Simulation::seeded(triangle(), 1);
+    let ticks = sim.run_until_settled(10_000);
+    assert!(ticks < 10_000);
+    let positions = sim.positions();
+    assert_eq!(positions.len(), 3);
+    assert!(positions.iter().all(|p| p.x.is_finite() && p.y.is_finite()));
+    assert_eq!(sim.nodes().len(), 3);
+}
+
+#[test]
+fn graph_changes_between_ticks() {
+    let mut sim = Simulation::seeded(triangle(), 1);
+    sim.run(50);
+    sim.physics.frozen.insert(2);
+
+    let mut mutation = GraphMutation::default();
+    let mut d = Graph::new();
+    d.add_named_node("d");
+    mutation.add_nodes = d.nodes;
+    mutation.add_edges.push(Edge::from_nodes(
+        (&sim.graph.nodes[2], 2),
+        (&mutation.add_nodes[0], 3),
+        DEFAULT_EDGE_COLOR,
+        DEFAULT_LINE_WIDTH,
+    ));
+    mutation.remove_nodes.insert(0);
+    let remap = sim.apply(mutation).unwrap();
+
+    assert_eq!(remap.node(0), None);
+    assert_eq!(remap.node(3), Some(2));
+    assert_eq!(sim.graph.node_by_name("d"), Some(2));
+    // Edges touching `a` went with it
+    assert_eq!(sim.edges().len(), 2);
+    assert_eq!(sim.physics.frozen, BTreeSet::from([1]));
+    assert_eq!(sim.physics.objs.len(), 3);
+    sim.run(50);
+    assert_eq!(sim.positions().len(), 3);
+
+    let mut bad = GraphMutation::default();
+    bad.remove_edges.insert(7);
+    assert!(sim.apply(bad).is_err());
+}
@@ -0,0 +1,118 @@
+//! Runs the seeded simulation on small reference graphs and compares the
+//! resulting layout against fixtures in `tests/fixtures`. Any change to the
+//! physics that moves nodes (reordering float operations included) shows up
+//! here.
+
+mod common;
+
+use force_graph_wgpu::prelude::*;
+
+const SEED: u64 = 0x5EED;
+const TICKS: usize = 300;
+/// Allowed drift per coordinate, in world units
+const TOLERANCE: f32 = 1e-2;
+
+fn named(names: &[&str], edges: &[(&str, &str)]) -> Graph {
+    let mut graph = Graph::new();
+    for name in names {
+        graph.add_named_node(name);
+    }
+    for (a, b) in edges {
+        let (a, b) = (graph.node_or_insert(a), graph.node_or_insert(b));
+        graph.add_edge(a, b);
+    }
+    graph
+}
+
+fn path() -> Graph {
+    named(&[], &[("a", "b"), ("b", "c"), ("c", "d"), ("d", "e")])
+}
+
+fn star() -> Graph {
+    named(
+        &[],
+        &[
+            ("hub", "a"),
+            ("hub", "b"),
+            ("hub", "c"),
+            ("hub", "d"),
+            ("hub", "e"),
+        ],
+    )
+}
+
+fn grid() -> Graph {
+    let mut edges = vec![];
+    let name = |x: usize, y: usize| format!("{x},{y}");
+    for y in 0..3 {
+        for x in 0..3 {
+            if x < 2 {
+                edges.push((name(x, y), name(x + 1, y)));
+            }
+            if y < 2 {
+                edges.push((name(x, y), name(x, y + 1)));
+            }
+        }
+    }
+    let edges: Vec<(&str, &str)> = edges
+        .iter()
+        .map(|(a, b)| (a.as_str(), b.as_str()))
+        .collect();
+    named(&[], &edges)
+}
+
+/// Two components and an isolated node
+fn disconnected() -> Graph {
+    named(
+        &["lonely"],
+        &[("a", "b"), ("b", "c"), ("c", "a"), ("x", "y")],
+    )
+}
+
+fn check(name: &str, graph: Graph) {
+    let mut sim = Simulation::seeded(graph, SEED);
+    sim.run(TICKS);
+    let positions: Vec<Vec<f32>> = sim.positions().iter().map(|p| vec![p.x, p.y]).collect();
+    common::check_fixture(name, &positions, TOLERANCE);
+}
+
+#[test]
+fn path_layout() {
+    check("path", path());
+}
+
+#[test]
+fn star_layout() {
+    check("star", star());
+}
+
+#[test]
+fn grid_layout() {
+    check("grid", grid());
+}
+
+#[test]
+fn disconnected_layout() {
+    check("disconnected", disconnected());
+}
+
+#[test]
+fn layouts_come_to_rest() {
+    for graph in [path(), star(), grid(), disconnected()] {
+        let mut sim = Simulation::seeded(graph, SEED);
+        let ticks = sim.run_until_settled(10_000);
+        assert!(ticks < 10_000);
+        assert!(sim.physics.is_settled());
+    }
+}
+
+#[test]
+fn same_seed_same_layout() {
+    let run = |seed| {
+        let mut sim = Simulation::seeded(grid(), seed);
+        sim.run(50);
+        sim.positions()
+    };
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}